@@ -0,0 +1,171 @@
+//! Per-line type annotations for `tyf annotate`, built from ty's inlay hints
+//! and function hovers.
+
+use crate::lsp::position::{byte_to_column, column_to_byte, LSP_UNIT};
+use crate::lsp::protocol::{InlayHint, InlayHintKind};
+use crate::workspace::navigation::is_identifier_char;
+
+/// What an annotation describes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AnnotationKind {
+    /// A variable bound by an assignment, `for` or `with` target, etc.
+    Variable,
+    /// A function or method signature.
+    Function,
+}
+
+impl AnnotationKind {
+    pub const fn label(self) -> &'static str {
+        match self {
+            Self::Variable => "variable",
+            Self::Function => "function",
+        }
+    }
+}
+
+/// One inferred type, anchored where ty reported it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Annotation {
+    /// 0-based line
+    pub line: u32,
+    /// 0-based LSP column where the name starts
+    pub character: u32,
+    pub kind: AnnotationKind,
+    pub name: String,
+    /// The type, e.g. `list[int]`, or for functions the signature without
+    /// `def name`, e.g. `(a: int, b: str = ...) -> bool`.
+    pub ty: String,
+}
+
+/// Variable annotations from ty's type inlay hints for `text`.
+///
+/// Type hints are labeled `: T` and placed right after the name they
+/// describe. Parameter-name hints at call sites are skipped.
+pub fn variable_annotations(text: &str, hints: &[InlayHint]) -> Vec<Annotation> {
+    let lines: Vec<&str> = text.lines().collect();
+    hints
+        .iter()
+        .filter(|hint| hint.kind != Some(InlayHintKind::Parameter))
+        .filter_map(|hint| {
+            let label = hint.label.text();
+            let ty = label.trim_start().strip_prefix(':')?.trim();
+            let line = lines.get(usize::try_from(hint.position.line).ok()?)?;
+            let end = column_to_byte(line, hint.position.character, LSP_UNIT);
+            let before = &line[..end];
+            let start = before
+                .char_indices()
+                .rev()
+                .take_while(|&(_, c)| is_identifier_char(c))
+                .last()
+                .map_or(end, |(i, _)| i);
+            let name = &before[start..];
+            (!name.is_empty() && !ty.is_empty()).then(|| Annotation {
+                line: hint.position.line,
+                character: byte_to_column(line, start, LSP_UNIT),
+                kind: AnnotationKind::Variable,
+                name: name.to_string(),
+                ty: ty.to_string(),
+            })
+        })
+        .collect()
+}
+
+/// A function annotation from its hover signature, e.g. `def f(a: int) -> str`.
+///
+/// ty spreads long signatures over several lines; they are joined into one.
+/// Returns `None` when the hover holds no parameter list.
+pub fn function_annotation(
+    name: &str,
+    line: u32,
+    character: u32,
+    signature: &str,
+) -> Option<Annotation> {
+    let joined = signature.lines().map(str::trim).collect::<Vec<_>>().join(" ");
+    let joined = joined.replace("( ", "(").replace(" )", ")").replace(",)", ")");
+    let params = &joined[joined.find('(')?..];
+    Some(Annotation {
+        line,
+        character,
+        kind: AnnotationKind::Function,
+        name: name.to_string(),
+        ty: params.trim().to_string(),
+    })
+}
+
+/// Text of a `# type:` comment for the annotations on one line.
+///
+/// A lone variable gets just its type, as in PEP 484 type comments; several
+/// variables (tuple unpacking, `for k, v in ...`) are named.
+pub fn type_comment(annotations: &[&Annotation]) -> String {
+    match annotations {
+        [single] => single.ty.clone(),
+        _ => annotations
+            .iter()
+            .map(|a| match a.kind {
+                AnnotationKind::Variable => format!("{}: {}", a.name, a.ty),
+                AnnotationKind::Function => format!("{}{}", a.name, a.ty),
+            })
+            .collect::<Vec<_>>()
+            .join(", "),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lsp::protocol::{InlayHintLabel, Position};
+
+    fn hint(line: u32, character: u32, label: &str, kind: Option<InlayHintKind>) -> InlayHint {
+        InlayHint {
+            position: Position { line, character },
+            label: InlayHintLabel::String(label.to_string()),
+            kind,
+        }
+    }
+
+    #[test]
+    fn test_variable_annotations_name_the_hinted_variable() {
+        let text = "total = add(1, b=2)\nfor key, value in items.items():\n    pass\n";
+        let hints = [
+            hint(0, 5, ": int", Some(InlayHintKind::Type)),
+            hint(0, 15, "b=", Some(InlayHintKind::Parameter)),
+            hint(1, 7, ": str", None),
+            hint(1, 14, ": list[int]", Some(InlayHintKind::Type)),
+        ];
+        let annotations = variable_annotations(text, &hints);
+        let names: Vec<(&str, &str)> =
+            annotations.iter().map(|a| (a.name.as_str(), a.ty.as_str())).collect();
+        assert_eq!(names, vec![("total", "int"), ("key", "str"), ("value", "list[int]")]);
+        assert_eq!(annotations[2].character, 9);
+    }
+
+    #[test]
+    fn test_variable_annotations_use_utf16_columns() {
+        let text = "s = '🐍'; größe = 1\n";
+        let annotations = variable_annotations(text, &[hint(0, 15, ": int", None)]);
+        assert_eq!(annotations[0].name, "größe");
+        assert_eq!(annotations[0].character, 10);
+    }
+
+    #[test]
+    fn test_function_annotation_joins_multiline_signatures() {
+        let annotation =
+            function_annotation("add", 3, 4, "def add(\n    self,\n    a: int,\n) -> int").unwrap();
+        assert_eq!(annotation.ty, "(self, a: int) -> int");
+        assert!(function_annotation("x", 0, 0, "int").is_none());
+    }
+
+    #[test]
+    fn test_type_comment() {
+        let a = Annotation {
+            line: 0,
+            character: 1,
+            kind: AnnotationKind::Variable,
+            name: "a".to_string(),
+            ty: "int".to_string(),
+        };
+        let b = Annotation { name: "b".to_string(), ty: "str".to_string(), ..a };
+        assert_eq!(type_comment(&[&a]), "int");
+        assert_eq!(type_comment(&[&a, &b]), "a: int, b: str");
+    }
+}
@@ -0,0 +1,144 @@
+//! Symbol selection for `tyf api`: which modules and names make up a
+//! package's public API.
+
+use std::collections::HashSet;
+
+use crate::lsp::protocol::{DocumentSymbol, Position, SymbolKind};
+
+/// A public class or function; a class also lists its public members.
+#[derive(Debug, Clone)]
+pub struct ApiSymbol {
+    pub name: String,
+    pub kind: SymbolKind,
+    /// Where the name is declared, as ty reports it.
+    pub position: Position,
+    pub members: Vec<Self>,
+}
+
+fn is_private(name: &str) -> bool {
+    name.starts_with('_')
+}
+
+/// Whether a dotted module name is public: none of its parts starts with
+/// an underscore.
+pub fn is_public_module(module: &str) -> bool {
+    module.split('.').all(|part| !is_private(part))
+}
+
+/// The public classes and functions in a module's outline.
+///
+/// A module with an `__all__` exports exactly the names it lists; otherwise
+/// every top-level name without a leading underscore is public. Class
+/// members are kept when they are methods or properties with a public name,
+/// plus `__init__`, whose signature is the constructor's.
+pub fn public_symbols(symbols: &[DocumentSymbol], exports: &HashSet<String>) -> Vec<ApiSymbol> {
+    symbols
+        .iter()
+        .filter(|symbol| matches!(symbol.kind, SymbolKind::Class | SymbolKind::Function))
+        .filter(|symbol| {
+            if exports.is_empty() {
+                !is_private(&symbol.name)
+            } else {
+                exports.contains(&symbol.name)
+            }
+        })
+        .map(|symbol| {
+            let members = if symbol.kind == SymbolKind::Class {
+                symbol
+                    .children
+                    .iter()
+                    .flatten()
+                    .filter(|member| {
+                        matches!(
+                            member.kind,
+                            SymbolKind::Method | SymbolKind::Function | SymbolKind::Property
+                        ) && (member.name == "__init__" || !is_private(&member.name))
+                    })
+                    .map(|member| ApiSymbol {
+                        name: member.name.clone(),
+                        kind: member.kind.clone(),
+                        position: member.selection_range.start.clone(),
+                        members: Vec::new(),
+                    })
+                    .collect()
+            } else {
+                Vec::new()
+            };
+            ApiSymbol {
+                name: symbol.name.clone(),
+                kind: symbol.kind.clone(),
+                position: symbol.selection_range.start.clone(),
+                members,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lsp::protocol::Range;
+
+    fn symbol(
+        name: &str,
+        kind: SymbolKind,
+        line: u32,
+        children: Vec<DocumentSymbol>,
+    ) -> DocumentSymbol {
+        let pos = |character| Position { line, character };
+        DocumentSymbol {
+            name: name.to_string(),
+            detail: None,
+            kind,
+            tags: None,
+            deprecated: None,
+            range: Range { start: pos(0), end: pos(10) },
+            selection_range: Range { start: pos(4), end: pos(7) },
+            children: (!children.is_empty()).then_some(children),
+        }
+    }
+
+    fn names(symbols: &[ApiSymbol]) -> Vec<String> {
+        symbols
+            .iter()
+            .flat_map(|s| {
+                let members = s.members.iter().map(|m| format!("{}.{}", s.name, m.name));
+                std::iter::once(s.name.clone()).chain(members)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn public_symbols_skip_private_names_and_variables() {
+        let outline = vec![
+            symbol(
+                "Order",
+                SymbolKind::Class,
+                0,
+                vec![
+                    symbol("__init__", SymbolKind::Method, 1, Vec::new()),
+                    symbol("__eq__", SymbolKind::Method, 3, Vec::new()),
+                    symbol("_recalc", SymbolKind::Method, 5, Vec::new()),
+                    symbol("total", SymbolKind::Property, 7, Vec::new()),
+                    symbol("status", SymbolKind::Variable, 9, Vec::new()),
+                ],
+            ),
+            symbol("create_order", SymbolKind::Function, 12, Vec::new()),
+            symbol("_helper", SymbolKind::Function, 15, Vec::new()),
+            symbol("DEFAULT_TAX", SymbolKind::Constant, 18, Vec::new()),
+        ];
+        let public = public_symbols(&outline, &HashSet::new());
+        assert_eq!(names(&public), ["Order", "Order.__init__", "Order.total", "create_order"]);
+        assert_eq!((public[0].position.line, public[0].position.character), (0, 4));
+
+        let exports = HashSet::from(["_helper".to_string()]);
+        assert_eq!(names(&public_symbols(&outline, &exports)), ["_helper"]);
+    }
+
+    #[test]
+    fn private_modules_are_not_public() {
+        assert!(is_public_module("shop.orders"));
+        assert!(!is_public_module("shop._internal.tax"));
+        assert!(!is_public_module("_vendor"));
+    }
+}
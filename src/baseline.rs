@@ -0,0 +1,266 @@
+//! Diagnostics baseline for `tyf check --baseline`.
+//!
+//! A baseline records the diagnostics a project already has, so CI can fail on
+//! new ones only while the old ones are fixed over time. Entries are keyed by
+//! file (relative to the workspace, so the file can be committed), code and
+//! message, with a count. Lines are deliberately left out: an edit elsewhere
+//! in a file moves its existing diagnostics without making them new.
+
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::lsp::protocol::Diagnostic;
+
+/// Version of the baseline file format.
+const FORMAT_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+struct Key {
+    file: String,
+    code: String,
+    message: String,
+}
+
+impl Key {
+    fn new(file: &str, diagnostic: &Diagnostic) -> Self {
+        Self {
+            file: file.to_string(),
+            code: diagnostic.code.clone().unwrap_or_default(),
+            message: diagnostic.message.clone(),
+        }
+    }
+}
+
+/// On-disk shape of a baseline file.
+#[derive(Serialize, Deserialize)]
+struct BaselineFile {
+    version: u32,
+    diagnostics: Vec<Entry>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Entry {
+    file: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    code: String,
+    message: String,
+    count: usize,
+}
+
+/// Known diagnostics, counted per file, code and message.
+#[derive(Debug, Default)]
+pub struct Baseline {
+    counts: BTreeMap<Key, usize>,
+}
+
+/// How a file's current diagnostics compare to the baseline.
+pub struct Comparison<'a> {
+    /// Diagnostics beyond what the baseline records, in their original order.
+    pub new: Vec<&'a Diagnostic>,
+    /// Diagnostics the baseline already records.
+    pub known: usize,
+    /// Baselined diagnostics that no longer occur.
+    pub fixed: usize,
+}
+
+impl Baseline {
+    /// Read a baseline file written by [`Baseline::save`].
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path).with_context(|| {
+            format!(
+                "Can't read baseline {}. Create it with `tyf check --baseline {} --update-baseline`",
+                path.display(),
+                path.display()
+            )
+        })?;
+        let file: BaselineFile = serde_json::from_str(&text)
+            .with_context(|| format!("{} is not a tyf baseline file", path.display()))?;
+        if file.version != FORMAT_VERSION {
+            anyhow::bail!(
+                "{} uses baseline format v{}, but this tyf reads v{FORMAT_VERSION}",
+                path.display(),
+                file.version
+            );
+        }
+        let mut baseline = Self::default();
+        for entry in file.diagnostics {
+            let key = Key { file: entry.file, code: entry.code, message: entry.message };
+            *baseline.counts.entry(key).or_default() += entry.count;
+        }
+        Ok(baseline)
+    }
+
+    /// Write the baseline, sorted so that diffs of the file stay small.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let diagnostics = self
+            .counts
+            .iter()
+            .map(|(key, &count)| Entry {
+                file: key.file.clone(),
+                code: key.code.clone(),
+                message: key.message.clone(),
+                count,
+            })
+            .collect();
+        let file = BaselineFile { version: FORMAT_VERSION, diagnostics };
+        let mut text = serde_json::to_string_pretty(&file)?;
+        text.push('\n');
+        std::fs::write(path, text)
+            .with_context(|| format!("Failed to write baseline {}", path.display()))
+    }
+
+    /// Total number of diagnostics recorded.
+    pub fn len(&self) -> usize {
+        self.counts.values().sum()
+    }
+
+    /// Record `diagnostics` as the known ones for `file`, replacing earlier entries.
+    pub fn record(&mut self, file: &str, diagnostics: &[Diagnostic]) {
+        self.counts.retain(|key, _| key.file != file);
+        for diagnostic in diagnostics {
+            *self.counts.entry(Key::new(file, diagnostic)).or_default() += 1;
+        }
+    }
+
+    /// Split the current `diagnostics` of `file` into new and known ones.
+    ///
+    /// When a file has more diagnostics with the same code and message than
+    /// the baseline records, the extra ones are new; which of them count as
+    /// extra is decided by file order.
+    pub fn compare<'a>(&self, file: &str, diagnostics: &'a [Diagnostic]) -> Comparison<'a> {
+        let mut remaining: HashMap<(&str, &str), usize> = self
+            .counts
+            .iter()
+            .filter(|(key, _)| key.file == file)
+            .map(|(key, &count)| ((key.code.as_str(), key.message.as_str()), count))
+            .collect();
+        let mut new = Vec::new();
+        let mut known = 0;
+        for diagnostic in diagnostics {
+            let code = diagnostic.code.as_deref().unwrap_or_default();
+            match remaining.get_mut(&(code, diagnostic.message.as_str())) {
+                Some(count) if *count > 0 => {
+                    *count -= 1;
+                    known += 1;
+                }
+                _ => new.push(diagnostic),
+            }
+        }
+        Comparison { new, known, fixed: remaining.values().sum() }
+    }
+}
+
+/// How `file` is named in a baseline: relative to `root`, with `/` separators.
+///
+/// Files outside the workspace keep their full path.
+pub fn file_key(root: &Path, file: &Path) -> String {
+    file.strip_prefix(root).map_or_else(
+        |_| file.to_string_lossy().into_owned(),
+        |relative| {
+            relative
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/")
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lsp::protocol::{DiagnosticSeverity, Position, Range};
+
+    fn diagnostic(line: u32, code: &str, message: &str) -> Diagnostic {
+        let position = Position { line, character: 0 };
+        Diagnostic {
+            range: Range { start: position.clone(), end: position },
+            severity: DiagnosticSeverity::Error,
+            code: Some(code.to_string()),
+            source: Some("ty".to_string()),
+            message: message.to_string(),
+            related_information: None,
+        }
+    }
+
+    #[test]
+    fn test_compare_ignores_lines_and_counts_duplicates() {
+        let mut baseline = Baseline::default();
+        baseline.record(
+            "app.py",
+            &[diagnostic(3, "unresolved-reference", "Name `x` used when not defined")],
+        );
+
+        // The known diagnostic moved down; a second copy of it and another code are new.
+        let current = [
+            diagnostic(10, "unresolved-reference", "Name `x` used when not defined"),
+            diagnostic(12, "unresolved-reference", "Name `x` used when not defined"),
+            diagnostic(14, "invalid-return-type", "Return type does not match"),
+        ];
+        let comparison = baseline.compare("app.py", &current);
+        assert_eq!(comparison.known, 1);
+        assert_eq!(comparison.fixed, 0);
+        let new_lines: Vec<u32> = comparison.new.iter().map(|d| d.range.start.line).collect();
+        assert_eq!(new_lines, vec![12, 14]);
+
+        // Nothing recorded for other files.
+        assert_eq!(baseline.compare("other.py", &current).new.len(), 3);
+    }
+
+    #[test]
+    fn test_compare_reports_fixed_entries() {
+        let mut baseline = Baseline::default();
+        baseline.record("app.py", &[diagnostic(1, "a", "one"), diagnostic(2, "b", "two")]);
+        let current = [diagnostic(1, "a", "one")];
+        let comparison = baseline.compare("app.py", &current);
+        assert!(comparison.new.is_empty());
+        assert_eq!(comparison.fixed, 1);
+    }
+
+    #[test]
+    fn test_record_replaces_only_that_file() {
+        let mut baseline = Baseline::default();
+        baseline.record("a.py", &[diagnostic(1, "x", "m"), diagnostic(2, "x", "m")]);
+        baseline.record("b.py", &[diagnostic(1, "y", "n")]);
+        assert_eq!(baseline.len(), 3);
+
+        baseline.record("a.py", &[]);
+        assert_eq!(baseline.len(), 1);
+        assert_eq!(baseline.compare("b.py", &[diagnostic(5, "y", "n")]).known, 1);
+    }
+
+    #[test]
+    fn test_save_and_load_round_trip() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let path = dir.path().join(".tyf-baseline.json");
+        let mut baseline = Baseline::default();
+        baseline.record("pkg/b.py", &[diagnostic(1, "y", "n")]);
+        baseline.record("a.py", &[diagnostic(1, "x", "m"), diagnostic(9, "x", "m")]);
+        baseline.save(&path).expect("save");
+
+        let text = std::fs::read_to_string(&path).expect("read");
+        assert!(text.find("a.py").expect("a.py") < text.find("pkg/b.py").expect("pkg/b.py"));
+        assert!(text.contains("\"count\": 2"), "got:\n{text}");
+
+        let loaded = Baseline::load(&path).expect("load");
+        assert_eq!(loaded.len(), 3);
+        assert_eq!(loaded.compare("a.py", &[diagnostic(4, "x", "m")]).fixed, 1);
+    }
+
+    #[test]
+    fn test_load_missing_file_suggests_update() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let err = Baseline::load(&dir.path().join("missing.json")).expect_err("missing");
+        assert!(format!("{err:#}").contains("--update-baseline"), "got: {err:#}");
+    }
+
+    #[test]
+    fn test_file_key_is_workspace_relative() {
+        let root = Path::new("/ws");
+        assert_eq!(file_key(root, Path::new("/ws/pkg/app.py")), "pkg/app.py");
+        assert_eq!(file_key(root, Path::new("/elsewhere/app.py")), "/elsewhere/app.py");
+    }
+}
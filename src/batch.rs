@@ -0,0 +1,415 @@
+//! Query scripts for `tyf batch`.
+//!
+//! Each line of stdin is a tyf command line without the leading `tyf`, such
+//! as `def app.py:10:5` or `refs MyClass --references-limit 5`, or a JSON
+//! object giving one (`{"id": 1, "args": ["hover", "foo"]}`). Every command
+//! runs over the same daemon connection, and its `--format json` output is
+//! written as one JSON object per line, in input order.
+
+use anyhow::{Context, Result};
+use clap::Parser;
+use serde_json::{json, Value};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+use crate::cli::args::{Cli, Commands};
+use crate::cli::exit::usage_message;
+use crate::cli::output::{OutputFormatter, SourceCache};
+use crate::commands::{self, ReferenceOptions};
+use crate::daemon::client::DaemonClient;
+use crate::debug::DebugLog;
+use crate::lsp::uri::path_to_uri;
+use crate::workspace::page::ResultPage;
+
+/// One command of a batch script.
+#[derive(Debug, PartialEq, Eq)]
+pub struct BatchCommand {
+    /// Echoed back with the result: the JSON `id`, else the line number.
+    pub id: Value,
+    /// The command line as given, also echoed back.
+    pub command: String,
+    pub args: Vec<String>,
+}
+
+impl BatchCommand {
+    /// Parse line `number` of the script; `None` for blank lines and `#` comments.
+    pub fn parse(line: &str, number: usize) -> Option<Result<Self>> {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            return None;
+        }
+        if !line.starts_with('{') {
+            let command = line.to_string();
+            return Some(split_words(line).map(|args| Self { id: json!(number), command, args }));
+        }
+        let parse = || {
+            let mut object: serde_json::Map<String, Value> =
+                serde_json::from_str(line).context("Invalid JSON")?;
+            let id = object.remove("id").unwrap_or_else(|| json!(number));
+            let (command, args) = match (object.remove("args"), object.remove("command")) {
+                (Some(args), _) => {
+                    let args: Vec<String> = serde_json::from_value(args)
+                        .context("\"args\" must be an array of strings")?;
+                    (join_words(&args), args)
+                }
+                (None, Some(Value::String(command))) => {
+                    let args = split_words(&command)?;
+                    (command, args)
+                }
+                _ => anyhow::bail!("Expected an \"args\" array or a \"command\" string"),
+            };
+            Ok(Self { id, command, args })
+        };
+        Some(parse())
+    }
+}
+
+/// Split a command line into words the way a POSIX shell would, minus
+/// expansions: whitespace separates words, quotes group them, and a
+/// backslash escapes the next character outside single quotes.
+pub fn split_words(line: &str) -> Result<Vec<String>> {
+    let mut words = Vec::new();
+    let mut word: Option<String> = None;
+    let mut quote: Option<char> = None;
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (None, '\'' | '"') => {
+                quote = Some(c);
+                word.get_or_insert_with(String::new);
+            }
+            (None | Some('"'), '\\') => {
+                let escaped = chars.next().context("Trailing backslash")?;
+                word.get_or_insert_with(String::new).push(escaped);
+            }
+            (None, c) if c.is_whitespace() => words.extend(word.take()),
+            (_, c) => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+    if let Some(q) = quote {
+        anyhow::bail!("Unterminated {q} quote");
+    }
+    words.extend(word);
+    Ok(words)
+}
+
+/// The inverse of [`split_words`]: `args` as a command line, single-quoting
+/// the words that need it.
+pub fn join_words(args: &[String]) -> String {
+    let quote = |word: &String| {
+        let plain =
+            !word.is_empty() && !word.contains(|c: char| c.is_whitespace() || "'\"\\#".contains(c));
+        if plain {
+            word.clone()
+        } else {
+            format!("'{}'", word.replace('\'', r"'\''"))
+        }
+    };
+    args.iter().map(quote).collect::<Vec<_>>().join(" ")
+}
+
+/// A batch run's settings, taken from the `tyf batch` command line.
+pub struct BatchRunner<'a> {
+    pub workspace_root: &'a Path,
+    /// Relative file arguments are resolved against this directory first.
+    pub cwd: &'a Path,
+    /// Renders each result; always JSON.
+    pub formatter: &'a OutputFormatter,
+    pub timeout: Duration,
+    pub zero_based: bool,
+    pub include_deps: bool,
+    pub debug_log: Option<&'a Arc<DebugLog>>,
+}
+
+impl BatchRunner<'_> {
+    /// Run the commands on stdin until it closes, writing one result line each.
+    pub async fn run(&self) -> Result<()> {
+        let mut lines = BufReader::new(tokio::io::stdin()).lines();
+        let mut stdout = tokio::io::stdout();
+        let mut client: Option<DaemonClient> = None;
+        let mut number = 0;
+        while let Some(line) = lines.next_line().await.context("Failed to read from stdin")? {
+            number += 1;
+            let Some(command) = BatchCommand::parse(&line, number) else { continue };
+            let output = match command {
+                Ok(command) => {
+                    let result = self.execute(&mut client, &command.args).await;
+                    // Start afresh after a failure in case the connection broke.
+                    if result.is_err() {
+                        client = None;
+                    }
+                    result_line(&command.id, &command.command, result)
+                }
+                Err(e) => result_line(&json!(number), line.trim(), Err(e)),
+            };
+            let mut text = output.to_string();
+            text.push('\n');
+            stdout.write_all(text.as_bytes()).await.context("Failed to write to stdout")?;
+            stdout.flush().await.context("Failed to flush stdout")?;
+        }
+        Ok(())
+    }
+
+    /// Run one command line, connecting to the daemon on first use.
+    async fn execute(&self, client: &mut Option<DaemonClient>, args: &[String]) -> Result<Value> {
+        let mut cli =
+            Cli::try_parse_from(std::iter::once("tyf").chain(args.iter().map(String::as_str)))
+                .map_err(|e| anyhow::anyhow!(usage_message(&e)))?;
+        crate::absolutize_file_args(&mut cli.command, self.cwd);
+        let client = match client {
+            Some(client) => client,
+            None => client.insert(commands::connect_daemon(self.timeout, self.debug_log).await?),
+        };
+        let text = self.run_command(client, cli.command).await?;
+        Ok(serde_json::from_str(&text).unwrap_or(Value::String(text)))
+    }
+
+    /// The `--format json` output of `command`.
+    #[allow(clippy::too_many_lines)]
+    async fn run_command(&self, client: &mut DaemonClient, command: Commands) -> Result<String> {
+        let root = self.workspace_root;
+        let formatter = self.formatter;
+        match command {
+            Commands::Definition { queries, file, stdin_path } => {
+                if stdin_path.is_some() {
+                    anyhow::bail!("--stdin-path can't be used inside a batch");
+                }
+                let results = commands::collect_definitions(
+                    client,
+                    root,
+                    file.as_deref(),
+                    &queries,
+                    self.zero_based,
+                )
+                .await?;
+                let cache = SourceCache::from_uris(
+                    results.iter().flat_map(|(_, locs)| locs).map(|l| l.uri.as_str()),
+                )
+                .await;
+                Ok(formatter.format_find_results(&results, &cache))
+            }
+            Commands::Stubs { queries, file, decl, type_definition, implementation } => {
+                let kinds = commands::goto_kinds(decl, type_definition, implementation);
+                let entries = commands::collect_goto(
+                    client,
+                    root,
+                    file.as_deref(),
+                    &queries,
+                    &kinds,
+                    self.zero_based,
+                )
+                .await?;
+                let cache = SourceCache::from_uris(
+                    entries.iter().flat_map(|e| &e.locations).map(|l| l.uri.as_str()),
+                )
+                .await;
+                Ok(formatter.format_goto(&entries, &cache))
+            }
+            Commands::Hover { mut queries, file, line, column, stdin_path } => {
+                if stdin_path.is_some() {
+                    anyhow::bail!("--stdin-path can't be used inside a batch");
+                }
+                if let (Some(file), Some((line, column))) = (&file, line.zip(column)) {
+                    queries.insert(0, format!("{}:{line}:{column}", file.display()));
+                }
+                let entries = commands::collect_hover_entries(
+                    client,
+                    root,
+                    file.as_deref(),
+                    &queries,
+                    self.zero_based,
+                )
+                .await?;
+                let cache = SourceCache::from_uris(
+                    entries.iter().filter_map(|e| e.location.as_ref()).map(|l| l.uri.as_str()),
+                )
+                .await;
+                Ok(formatter.format_hover_results(&entries, &cache))
+            }
+            Commands::References {
+                mut queries,
+                file,
+                line,
+                column,
+                stdin,
+                include_declaration,
+                references_limit,
+                max_results,
+                limit,
+                offset,
+                tests,
+                context: _,
+                kind,
+                count,
+                count_by_file,
+                unique,
+                git,
+                group_by: _,
+                sort,
+            } => {
+                if stdin {
+                    anyhow::bail!("--stdin can't be used inside a batch");
+                }
+                if let (Some(file), Some((line, column))) = (&file, line.zip(column)) {
+                    queries.insert(0, format!("{}:{line}:{column}", file.display()));
+                }
+                let options = ReferenceOptions {
+                    include_declaration,
+                    include_deps: self.include_deps,
+                    zero_based: self.zero_based,
+                    references_limit,
+                    page: ResultPage::new(offset, Some(limit.unwrap_or(max_results))),
+                    show_tests: tests,
+                    kinds: kind.iter().map(|k| k.symbol_kind()).collect(),
+                    unique,
+                    git,
+                    sort,
+                };
+                if count || count_by_file {
+                    let counts = commands::collect_reference_counts(
+                        client,
+                        root,
+                        file.as_deref(),
+                        &queries,
+                        &options,
+                    )
+                    .await?;
+                    return Ok(formatter.format_reference_counts(&counts, count_by_file));
+                }
+                let results =
+                    commands::collect_references(client, root, file.as_deref(), &queries, options)
+                        .await?;
+                let cache = SourceCache::from_uris(results.iter().flat_map(|r| {
+                    let main = r.displayed.iter().map(|e| e.location.uri.as_str());
+                    let test = r
+                        .test_references
+                        .iter()
+                        .flat_map(|t| t.displayed.iter().map(|e| e.location.uri.as_str()));
+                    main.chain(test)
+                }))
+                .await;
+                Ok(formatter.format_enriched_references_results(&results, &cache))
+            }
+            Commands::Members { symbols, file, all } => {
+                let mut results = Vec::new();
+                for symbol in &symbols {
+                    let result = commands::members_single_class(
+                        client,
+                        root,
+                        file.as_deref(),
+                        symbol,
+                        all,
+                        self.zero_based,
+                    )
+                    .await?;
+                    if let Some(problem) = commands::not_a_class(&result) {
+                        anyhow::bail!(problem);
+                    }
+                    results.push(result);
+                }
+                let cache =
+                    SourceCache::from_uris(results.iter().map(|r| r.file_uri.as_str())).await;
+                Ok(formatter.format_members_results(&results, &cache))
+            }
+            Commands::Symbols { query, kind, limit, offset, within } => {
+                let found = commands::collect_symbols(
+                    client,
+                    root,
+                    &query,
+                    &kind,
+                    ResultPage::new(offset, limit),
+                    within.as_deref(),
+                    self.include_deps,
+                )
+                .await?
+                .symbols;
+                let cache =
+                    SourceCache::from_uris(found.iter().map(|s| s.location.uri.as_str())).await;
+                Ok(formatter.format_workspace_symbols(&found, &cache))
+            }
+            Commands::DocumentSymbols { file } => {
+                let symbols = client
+                    .execute_document_symbols(root.to_path_buf(), file.to_string_lossy().into())
+                    .await?
+                    .symbols;
+                let source_path = root.join(&file).to_string_lossy().into_owned();
+                let uri = path_to_uri(Path::new(&source_path));
+                let cache = SourceCache::from_uris([uri.as_str()]).await;
+                Ok(formatter.format_document_symbols(&symbols, &source_path, &cache))
+            }
+            _ => anyhow::bail!(
+                "tyf batch runs def, hover, refs, members, symbols and list commands only"
+            ),
+        }
+    }
+}
+
+/// The output line for one command: its `result`, or the `error` it failed with.
+fn result_line(id: &Value, command: &str, result: Result<Value>) -> Value {
+    match result {
+        Ok(result) => json!({ "id": id, "command": command, "result": result }),
+        Err(e) => json!({ "id": id, "command": command, "error": format!("{e:#}") }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_words() {
+        assert_eq!(split_words("def  app.py:10:5 ").unwrap(), ["def", "app.py:10:5"]);
+        assert_eq!(
+            split_words(r#"symbols '' --in "my dir" a\ b"#).unwrap(),
+            ["symbols", "", "--in", "my dir", "a b"]
+        );
+        assert!(split_words("hover 'foo").is_err());
+
+        let args = ["symbols", "", "--in", "my dir", "it's"].map(String::from);
+        assert_eq!(join_words(&args), r"symbols '' --in 'my dir' 'it'\''s'");
+        assert_eq!(split_words(&join_words(&args)).unwrap(), args);
+    }
+
+    #[test]
+    fn test_parses_text_and_json_lines() {
+        assert!(BatchCommand::parse("  ", 1).is_none());
+        assert!(BatchCommand::parse("# comment", 2).is_none());
+        assert_eq!(
+            BatchCommand::parse("refs MyClass", 3).unwrap().unwrap(),
+            BatchCommand {
+                id: json!(3),
+                command: "refs MyClass".into(),
+                args: vec!["refs".into(), "MyClass".into()]
+            }
+        );
+        assert_eq!(
+            BatchCommand::parse(r#"{"id": "a", "args": ["hover", "foo"]}"#, 4).unwrap().unwrap(),
+            BatchCommand {
+                id: json!("a"),
+                command: "hover foo".into(),
+                args: vec!["hover".into(), "foo".into()]
+            }
+        );
+        assert_eq!(
+            BatchCommand::parse(r#"{"command": "def x"}"#, 5).unwrap().unwrap(),
+            BatchCommand {
+                id: json!(5),
+                command: "def x".into(),
+                args: vec!["def".into(), "x".into()]
+            }
+        );
+        assert!(BatchCommand::parse(r#"{"id": 1}"#, 6).unwrap().is_err());
+    }
+
+    #[test]
+    fn test_result_line() {
+        let ok = result_line(&json!(1), "def x", Ok(json!([])));
+        assert_eq!(ok, json!({ "id": 1, "command": "def x", "result": [] }));
+        let failed = result_line(&json!(2), "def x", Err(anyhow::anyhow!("boom")));
+        assert_eq!(failed["error"], "boom");
+        assert!(failed.get("result").is_none());
+    }
+}
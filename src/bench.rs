@@ -0,0 +1,132 @@
+//! Latency statistics and target selection for `tyf bench`.
+
+use std::ops::ControlFlow;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use serde::Serialize;
+
+use crate::workspace::ty_config::TyConfig;
+use crate::workspace::walker::WorkspaceWalker;
+
+/// Number of queries sent in one batched references request.
+pub const REFS_BATCH_SIZE: usize = 100;
+
+/// How many Python files to try when looking for a symbol to benchmark.
+const MAX_CANDIDATE_FILES: usize = 20;
+
+/// Latency summary of one benchmark.
+#[derive(Debug, Clone, Serialize)]
+pub struct BenchStat {
+    pub name: String,
+    pub samples: usize,
+    pub p50_ms: f64,
+    pub p90_ms: f64,
+    pub p99_ms: f64,
+    pub max_ms: f64,
+}
+
+impl BenchStat {
+    /// Summarize `samples` with nearest-rank percentiles.
+    pub fn from_samples(name: &str, samples: &[Duration]) -> Self {
+        let mut sorted = samples.to_vec();
+        sorted.sort_unstable();
+        Self {
+            name: name.to_string(),
+            samples: sorted.len(),
+            p50_ms: millis(percentile(&sorted, 50)),
+            p90_ms: millis(percentile(&sorted, 90)),
+            p99_ms: millis(percentile(&sorted, 99)),
+            max_ms: millis(sorted.last().copied().unwrap_or_default()),
+        }
+    }
+}
+
+/// Everything `tyf bench` reports.
+#[derive(Debug, Clone, Serialize)]
+pub struct BenchReport {
+    pub workspace: String,
+    /// The symbol or position the suite queried, e.g. `Calculator.add (example.py:10:9)`
+    pub target: String,
+    pub ty_version: Option<String>,
+    pub results: Vec<BenchStat>,
+}
+
+/// Nearest-rank percentile of an ascending slice; zero when empty.
+fn percentile(sorted: &[Duration], pct: usize) -> Duration {
+    if sorted.is_empty() {
+        return Duration::ZERO;
+    }
+    let rank = (pct * sorted.len()).div_ceil(100).max(1);
+    sorted[rank.min(sorted.len()) - 1]
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+/// The first few `.py` files of the workspace in a stable order, shallow files first.
+///
+/// The walk skips what ty skips (see [`WorkspaceWalker`]), so the benchmark
+/// target is a file ty actually analyses.
+pub fn candidate_files(root: &Path, config: &TyConfig) -> Vec<PathBuf> {
+    let mut found = Vec::new();
+    WorkspaceWalker::new(root, config).walk(|path| {
+        if path.extension().is_some_and(|ext| ext == "py") {
+            found.push(path.to_path_buf());
+        }
+        if found.len() < MAX_CANDIDATE_FILES {
+            ControlFlow::Continue(())
+        } else {
+            ControlFlow::Break(())
+        }
+    });
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(values: &[u64]) -> Vec<Duration> {
+        values.iter().map(|&v| Duration::from_millis(v)).collect()
+    }
+
+    #[test]
+    fn test_percentiles_use_nearest_rank() {
+        let samples = ms(&[5, 1, 4, 2, 3, 10, 9, 8, 7, 6]);
+        let stat = BenchStat::from_samples("hover", &samples);
+        assert_eq!(stat.samples, 10);
+        assert!((stat.p50_ms - 5.0).abs() < 1e-9);
+        assert!((stat.p90_ms - 9.0).abs() < 1e-9);
+        assert!((stat.p99_ms - 10.0).abs() < 1e-9);
+        assert!((stat.max_ms - 10.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_percentiles_of_no_samples_are_zero() {
+        let stat = BenchStat::from_samples("cold start", &[]);
+        assert_eq!(stat.samples, 0);
+        assert!(stat.p50_ms.abs() < 1e-9);
+        assert!(stat.max_ms.abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_candidate_files_are_sorted_and_skip_hidden_dirs() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let root = dir.path();
+        std::fs::create_dir_all(root.join("pkg")).expect("mkdir");
+        std::fs::create_dir_all(root.join(".venv/lib")).expect("mkdir");
+        for file in ["b.py", "a.py", "pkg/c.py", ".venv/lib/d.py", "notes.txt"] {
+            std::fs::write(root.join(file), "x = 1\n").expect("write");
+        }
+
+        let files = candidate_files(root, &TyConfig::default());
+        let names: Vec<_> =
+            files.iter().map(|f| f.strip_prefix(root).expect("under root").to_path_buf()).collect();
+        assert_eq!(
+            names,
+            vec![PathBuf::from("a.py"), PathBuf::from("b.py"), PathBuf::from("pkg/c.py")]
+        );
+    }
+}
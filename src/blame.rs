@@ -0,0 +1,237 @@
+//! Who last touched a line, from `git blame`, for `tyf refs --git`.
+//!
+//! Each file is blamed once with `git blame --porcelain`, however many of its
+//! lines are asked about, and kept for the rest of the command. Files are
+//! blamed in parallel, a few at a time. A file git can't blame (outside a
+//! repository, untracked, or without git on PATH) simply has no blame.
+
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+
+use serde::Serialize;
+use tokio::process::Command;
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+
+/// How many `git blame` processes run at once.
+const PARALLEL_BLAMES: usize = 8;
+
+/// Length of the abbreviated commit hash shown.
+const SHORT_HASH: usize = 8;
+
+/// The last commit that changed a line.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct LineBlame {
+    /// Abbreviated commit hash (all zeros for uncommitted changes)
+    pub commit: String,
+    pub author: String,
+    /// Author date as `YYYY-MM-DD`, in the author's time zone
+    pub date: String,
+}
+
+/// Blame of every file looked up so far, by absolute path.
+#[derive(Default)]
+pub struct BlameCache {
+    /// Blame by 0-based line, or `None` when git couldn't blame the file
+    files: HashMap<String, Option<HashMap<u32, LineBlame>>>,
+}
+
+impl BlameCache {
+    /// Blame those of `paths` not blamed yet.
+    pub async fn load<'a>(&mut self, paths: impl IntoIterator<Item = &'a str>) {
+        let limit = Arc::new(Semaphore::new(PARALLEL_BLAMES));
+        let mut blames = JoinSet::new();
+        for path in paths {
+            if self.files.contains_key(path) {
+                continue;
+            }
+            self.files.insert(path.to_string(), None);
+            let path = path.to_string();
+            let limit = Arc::clone(&limit);
+            blames.spawn(async move {
+                let _permit = limit.acquire_owned().await;
+                let lines = blame_file(Path::new(&path)).await;
+                (path, lines)
+            });
+        }
+        while let Some(joined) = blames.join_next().await {
+            if let Ok((path, lines)) = joined {
+                self.files.insert(path, lines);
+            }
+        }
+    }
+
+    /// The blame of 0-based `line` of `path`, if it was loaded.
+    pub fn line(&self, path: &str, line: u32) -> Option<&LineBlame> {
+        self.files.get(path)?.as_ref()?.get(&line)
+    }
+}
+
+/// Run `git blame` on `path` from its own directory, so that the repository
+/// it belongs to is used.
+async fn blame_file(path: &Path) -> Option<HashMap<u32, LineBlame>> {
+    let dir = path.parent()?;
+    let output = Command::new("git")
+        .arg("blame")
+        .arg("--porcelain")
+        .arg("--")
+        .arg(path)
+        .current_dir(dir)
+        .output()
+        .await;
+    match output {
+        Ok(output) if output.status.success() => {
+            Some(parse_porcelain(&String::from_utf8_lossy(&output.stdout)))
+        }
+        Ok(output) => {
+            tracing::debug!(
+                "git blame {}: {}",
+                path.display(),
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+            None
+        }
+        Err(e) => {
+            tracing::debug!("git not found on PATH, skipping blame: {e}");
+            None
+        }
+    }
+}
+
+/// What `--porcelain` says about a commit, once, on its first line.
+#[derive(Default)]
+struct CommitInfo {
+    author: String,
+    time: i64,
+    /// Offset from UTC in seconds
+    offset: i64,
+}
+
+/// Blame by 0-based line from `git blame --porcelain` output.
+///
+/// Every line starts with a `<hash> <original line> <final line>` header;
+/// the first line of each commit follows it with `key value` lines about
+/// the commit, and every line ends with its content after a tab.
+fn parse_porcelain(output: &str) -> HashMap<u32, LineBlame> {
+    let mut commits: HashMap<&str, CommitInfo> = HashMap::new();
+    let mut lines: Vec<(u32, &str)> = Vec::new();
+    let mut current: Option<&str> = None;
+    for line in output.lines() {
+        if line.starts_with('\t') {
+            current = None;
+            continue;
+        }
+        if let Some(hash) = current {
+            let info = commits.entry(hash).or_default();
+            if let Some(author) = line.strip_prefix("author ") {
+                info.author = author.to_string();
+            } else if let Some(time) = line.strip_prefix("author-time ") {
+                info.time = time.parse().unwrap_or(0);
+            } else if let Some(zone) = line.strip_prefix("author-tz ") {
+                info.offset = zone_offset(zone);
+            }
+            continue;
+        }
+        let mut fields = line.split(' ');
+        let (Some(hash), Some(_), Some(final_line)) = (fields.next(), fields.next(), fields.next())
+        else {
+            continue;
+        };
+        if let Ok(number) = final_line.parse::<u32>() {
+            lines.push((number.saturating_sub(1), hash));
+            commits.entry(hash).or_default();
+            current = Some(hash);
+        }
+    }
+    lines
+        .into_iter()
+        .filter_map(|(line, hash)| {
+            let info = commits.get(hash)?;
+            Some((
+                line,
+                LineBlame {
+                    commit: hash.chars().take(SHORT_HASH).collect(),
+                    author: info.author.clone(),
+                    date: civil_date(info.time + info.offset),
+                },
+            ))
+        })
+        .collect()
+}
+
+/// Seconds east of UTC for a `+HHMM`/`-HHMM` zone.
+fn zone_offset(zone: &str) -> i64 {
+    let (sign, digits) = match zone.split_at_checked(1) {
+        Some(("-", digits)) => (-1, digits),
+        Some(("+", digits)) => (1, digits),
+        _ => return 0,
+    };
+    let Ok(hhmm) = digits.parse::<i64>() else { return 0 };
+    sign * (hhmm / 100 * 3600 + hhmm % 100 * 60)
+}
+
+/// `YYYY-MM-DD` of a Unix timestamp, by the proleptic Gregorian calendar.
+fn civil_date(timestamp: i64) -> String {
+    // Howard Hinnant's days_from_civil, inverted.
+    let days = timestamp.div_euclid(86_400) + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 { shifted_month + 3 } else { shifted_month - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    format!("{year:04}-{month:02}-{day:02}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PORCELAIN: &str = "\
+1f2e3d4c5b6a79881f2e3d4c5b6a79881f2e3d4c 1 1 2
+author Ada Lovelace
+author-mail <ada@example.com>
+author-time 1700000000
+author-tz +0100
+summary Add users
+filename app.py
+\tdef user():
+1f2e3d4c5b6a79881f2e3d4c5b6a79881f2e3d4c 2 2
+\t    return 1
+0000000000000000000000000000000000000000 3 3 1
+author Not Committed Yet
+author-time 1760000000
+author-tz -0500
+filename app.py
+\tuser()
+";
+
+    #[test]
+    fn parse_porcelain_maps_lines_to_commits() {
+        let blame = parse_porcelain(PORCELAIN);
+        assert_eq!(blame.len(), 3);
+        let first = LineBlame {
+            commit: "1f2e3d4c".to_string(),
+            author: "Ada Lovelace".to_string(),
+            date: "2023-11-14".to_string(),
+        };
+        assert_eq!(blame[&0], first);
+        // Later lines of a commit carry no details of their own.
+        assert_eq!(blame[&1], first);
+        assert_eq!(blame[&2].author, "Not Committed Yet");
+        assert_eq!(blame[&2].commit, "00000000");
+    }
+
+    #[test]
+    fn civil_date_applies_zone_offset() {
+        assert_eq!(civil_date(0), "1970-01-01");
+        assert_eq!(civil_date(951_782_400), "2000-02-29");
+        // 2023-11-14 22:13:20 UTC is already the 15th in UTC+2.
+        assert_eq!(civil_date(1_700_000_000 + zone_offset("+0200")), "2023-11-15");
+        assert_eq!(zone_offset("-0530"), -(5 * 3600 + 30 * 60));
+    }
+}
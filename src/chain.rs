@@ -0,0 +1,208 @@
+//! Attribute-chain parsing for `tyf where`, and finding each hop's name in
+//! the text of a class definition.
+
+use crate::workspace::navigation::is_identifier_char;
+
+fn is_identifier(s: &str) -> bool {
+    let mut chars = s.chars();
+    chars.next().is_some_and(|c| c == '_' || c.is_alphabetic()) && chars.all(is_identifier_char)
+}
+
+/// Split `order.customer.address.city` into its names.
+pub fn parse_chain(expression: &str) -> anyhow::Result<Vec<&str>> {
+    let segments: Vec<&str> = expression.trim().split('.').map(str::trim).collect();
+    if let Some(bad) = segments.iter().find(|s| !is_identifier(s)) {
+        anyhow::bail!(
+            "'{expression}' is not an attribute chain like order.customer.address: \
+             '{bad}' is not a name"
+        );
+    }
+    Ok(segments)
+}
+
+/// Byte offset of `name` in `line` as a whole word that isn't itself an attribute.
+pub fn find_identifier(line: &str, name: &str) -> Option<usize> {
+    line.match_indices(name).map(|(i, _)| i).find(|&i| {
+        let before = line[..i].chars().next_back();
+        let after = line[i + name.len()..].chars().next();
+        !before.is_some_and(|c| is_identifier_char(c) || c == '.')
+            && !after.is_some_and(is_identifier_char)
+    })
+}
+
+/// Where `name` occurs in `text`, as a 0-based line and byte offset.
+///
+/// With `near`, the closest occurrence at or above that line wins (the
+/// binding in scope there), then the closest one below it. Without it, the
+/// first occurrence in the file.
+pub fn find_root(text: &str, name: &str, near: Option<usize>) -> Option<(usize, usize)> {
+    let lines: Vec<&str> = text.lines().collect();
+    let at = |idx: usize| find_identifier(lines[idx], name).map(|byte| (idx, byte));
+    match near {
+        Some(near) => {
+            let near = near.min(lines.len().checked_sub(1)?);
+            (0..=near).rev().find_map(at).or_else(|| (near + 1..lines.len()).find_map(at))
+        }
+        None => (0..lines.len()).find_map(at),
+    }
+}
+
+/// The class whose attributes a value of hover type `ty` has.
+///
+/// Handles the shapes ty's hover uses: a plain type (`Customer`), a class
+/// object (`<class 'Customer'>`, `type[Customer]`), a function or property
+/// (`def customer(self) -> Customer`), `self` in a method (`Self@Customer`),
+/// and optional types (`Customer | None`,
+/// `Optional[Customer]`). Other unions, and types ty couldn't infer
+/// (`Unknown`), have no single class and give `None`. Generic types give
+/// their origin, e.g. `list` for `list[Order]`.
+pub fn receiver_class(ty: &str) -> Option<String> {
+    let mut ty = ty.trim();
+    if let Some(rest) = ty.strip_prefix("<class '") {
+        ty = rest.split('\'').next()?;
+    }
+    if let Some((_, returns)) = ty.rsplit_once("->") {
+        ty = returns.trim();
+    }
+    let parts: Vec<&str> =
+        split_top_level(ty, '|').into_iter().filter(|part| *part != "None").collect();
+    let [ty] = parts.as_slice() else {
+        return None;
+    };
+    let ty = ["Optional[", "type["]
+        .iter()
+        .find_map(|prefix| ty.strip_prefix(prefix)?.strip_suffix(']'))
+        .unwrap_or(ty);
+    let ty = ty.strip_prefix("Self@").unwrap_or(ty);
+    let head = ty.split('[').next()?.trim();
+    let name = head.rsplit('.').next()?;
+    (is_identifier(name) && name != "Unknown").then(|| name.to_string())
+}
+
+/// Split `text` on `separator` outside of brackets and parentheses, trimmed.
+fn split_top_level(text: &str, separator: char) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut depth = 0usize;
+    let mut start = 0;
+    for (i, c) in text.char_indices() {
+        match c {
+            '[' | '(' => depth += 1,
+            ']' | ')' => depth = depth.saturating_sub(1),
+            c if c == separator && depth == 0 => {
+                parts.push(text[start..i].trim());
+                start = i + c.len_utf8();
+            }
+            _ => {}
+        }
+    }
+    parts.push(text[start..].trim());
+    parts
+}
+
+/// Base class names from a class header like `class Order(Base, Generic[T]):`.
+///
+/// Keyword arguments (`metaclass=...`), `object` and `Generic` are skipped;
+/// dotted bases (`models.Base`) give their last name.
+pub fn class_bases(header: &str) -> Vec<String> {
+    let Some((_, rest)) = header.split_once('(') else {
+        return Vec::new();
+    };
+    let mut depth = 1usize;
+    let end = rest
+        .char_indices()
+        .find(|&(_, c)| {
+            match c {
+                '(' | '[' => depth += 1,
+                ')' | ']' => depth -= 1,
+                _ => {}
+            }
+            depth == 0
+        })
+        .map_or(rest.len(), |(i, _)| i);
+    split_top_level(&rest[..end], ',')
+        .into_iter()
+        .filter(|base| !base.is_empty() && !base.contains('='))
+        .filter_map(|base| base.split('[').next()?.rsplit('.').next())
+        .map(str::trim)
+        .filter(|name| is_identifier(name) && !matches!(*name, "object" | "Generic"))
+        .map(str::to_string)
+        .collect()
+}
+
+/// The first `self.<attr> = ...` or `self.<attr>: T = ...` in `lines`.
+///
+/// Instance attributes assigned in methods don't show up in the document
+/// outline, so they are found in the class body's text. Returns the index in
+/// `lines` and the byte offset of `attr`.
+pub fn find_self_assignment(lines: &[&str], attr: &str) -> Option<(usize, usize)> {
+    let target = format!("self.{attr}");
+    lines.iter().enumerate().find_map(|(idx, line)| {
+        let (start, _) = line.match_indices(&target).find(|&(i, _)| {
+            let before = line[..i].chars().next_back();
+            let rest = &line[i + target.len()..];
+            let assigns = rest.trim_start().strip_prefix('=').is_some_and(|r| !r.starts_with('='))
+                || rest.trim_start().starts_with(':');
+            !before.is_some_and(|c| is_identifier_char(c) || c == '.')
+                && !rest.starts_with(is_identifier_char)
+                && assigns
+        })?;
+        Some((idx, start + "self.".len()))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_chain() {
+        assert_eq!(parse_chain("order.customer.city").unwrap(), ["order", "customer", "city"]);
+        assert_eq!(parse_chain("order").unwrap(), ["order"]);
+        assert!(parse_chain("order..city").is_err());
+        assert!(parse_chain("order.items[0]").is_err());
+    }
+
+    #[test]
+    fn test_find_root_prefers_binding_above_line() {
+        let text = "order = first()\nx = other.order\norder = second()\nprint(order)\n";
+        assert_eq!(find_root(text, "order", None), Some((0, 0)));
+        assert_eq!(find_root(text, "order", Some(1)), Some((0, 0)));
+        assert_eq!(find_root(text, "order", Some(3)), Some((3, 6)));
+        assert_eq!(find_root(text, "orders", None), None);
+    }
+
+    #[test]
+    fn test_receiver_class() {
+        assert_eq!(receiver_class("Customer").as_deref(), Some("Customer"));
+        assert_eq!(receiver_class("<class 'Customer'>").as_deref(), Some("Customer"));
+        assert_eq!(receiver_class("type[Customer]").as_deref(), Some("Customer"));
+        assert_eq!(receiver_class("def customer(self) -> Customer").as_deref(), Some("Customer"));
+        assert_eq!(receiver_class("Customer | None").as_deref(), Some("Customer"));
+        assert_eq!(receiver_class("Optional[models.Customer]").as_deref(), Some("Customer"));
+        assert_eq!(receiver_class("Self@Customer").as_deref(), Some("Customer"));
+        assert_eq!(receiver_class("list[Order]").as_deref(), Some("list"));
+        assert_eq!(receiver_class("Customer | Supplier"), None);
+        assert_eq!(receiver_class("Unknown"), None);
+    }
+
+    #[test]
+    fn test_class_bases() {
+        assert_eq!(class_bases("class Order(models.Base, Generic[T], metaclass=Meta):"), ["Base"]);
+        assert_eq!(class_bases("class Order(Mixin[int], Base):"), ["Mixin", "Base"]);
+        assert!(class_bases("class Order:").is_empty());
+        assert!(class_bases("class Order(object):").is_empty());
+    }
+
+    #[test]
+    fn test_find_self_assignment() {
+        let lines = [
+            "    def __init__(self, customer):",
+            "        if self.customer == customer:",
+            "        self.customer_id = 1",
+            "        self.customer: Customer = customer",
+        ];
+        assert_eq!(find_self_assignment(&lines, "customer"), Some((3, 13)));
+        assert_eq!(find_self_assignment(&lines, "customer_id"), Some((2, 13)));
+        assert_eq!(find_self_assignment(&lines, "address"), None);
+    }
+}
@@ -1,6 +1,17 @@
 use clap::builder::styling::{AnsiColor, Styles};
-use clap::{Parser, Subcommand, ValueEnum};
+use clap::parser::ValueSource;
+use clap::{ArgAction, ArgMatches, CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum};
+use clap_complete::engine::ArgValueCompleter;
+use clap_complete::Shell;
+use serde_json::Value;
+use std::ffi::OsString;
 use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::cli::completions::complete_symbol;
+use crate::cli::output::Invocation;
+use crate::cli::output_sinks::LocationList;
+use crate::lsp::protocol::SymbolKind;
 
 /// When to use colored output.
 #[derive(Clone, Default, ValueEnum)]
@@ -14,6 +25,24 @@ pub enum ColorMode {
     Never,
 }
 
+/// Which daemon a command talks to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum DaemonScope {
+    /// One daemon for all of the user's projects
+    User,
+    /// One daemon per workspace root, e.g. for CI jobs sharing a machine
+    Project,
+}
+
+impl DaemonScope {
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::User => "user",
+            Self::Project => "project",
+        }
+    }
+}
+
 const STYLES: Styles = Styles::styled()
     .header(AnsiColor::Green.on_default().bold())
     .literal(AnsiColor::Cyan.on_default().bold())
@@ -28,14 +57,51 @@ const HELP_TEMPLATE: &str = "\
 Symbol Lookup:
   show         Definition, signature, and usages of a symbol by name
   find         Find where a symbol is defined by name (--fuzzy for partial matching)
+  symbols      Search the workspace's symbols, filtered by kind and directory
+  def          Jump to a symbol's definition by name or file:line:col
+  stubs        Declaration, implementation, and type of a symbol: stub or runtime code
+  context      The full source of a symbol's definition, to read or paste into a prompt
+  hover        Type signature and docs of a symbol by name or file:line:col
   refs         All usages of a symbol across the codebase (by name or file:line:col)
+  occurrences  Where a symbol is read and written within one file
+  grep         Text search that keeps only matches resolving to a given symbol
   members      Public interface of a class: methods, properties, and class variables
+  explain-type Expand the types in a symbol's signature (aliases, typed dicts, protocols)
+  where        Where each attribute in a chain like order.customer.city is defined
+  explain      Types, definitions, and diagnostics of everything on one source line
 
 Browsing:
   list         All functions, classes, and variables defined in a file
+  annotate     A file with the types ty infers for its variables and functions
+  imports      Modules a file imports, and the workspace modules that import it
+  rdeps        Files that reference anything defined in a file
+  api          Public classes and functions of a package, with signatures and docs
+  stats        Lines, classes, functions, and methods per file, with totals
+  tui          Full-screen symbol search with a source preview; prints the pick
+
+Checking:
+  check        Type errors and warnings, optionally only those not in a baseline
+  check-all    Error and warning counts for every file in the workspace
+  unused       Functions, classes, and methods nothing in the workspace references
+
+Refactoring:
+  sig-check    Call sites that would break under a proposed signature change
+  move         Move a module and update the imports that refer to it
+  moved        Update the imports of a module that was already moved
 
 Infrastructure:
   daemon       Manage the background LSP server (auto-starts on first use)
+  ty-info      Show the ty server version and which LSP features it supports
+  doctor       Check ty, the workspace, and the daemon, and say how to fix problems
+  config       Show the settings in effect and the config file each comes from
+  alias        Save a command line under a name, to rerun it with tyf run
+  run          Run a saved alias, passing arguments to fill in its placeholders
+  bench        Measure cold start, hover, definition, and batched refs latency
+  mcp          Serve definitions, references, hover, and members to AI agents over MCP
+  batch        Run queries read from stdin over one daemon connection, as JSON lines
+  serve        Speak the daemon's JSON-RPC protocol on stdin and stdout (--stdio)
+  setup        Install ty with uv, pipx, or pip
+  completions  Print a shell completion script (bash, zsh, fish, powershell, elvish)
 
 {options}";
 
@@ -53,7 +119,7 @@ pub struct Cli {
     #[arg(long, value_name = "PATH")]
     pub workspace: Option<PathBuf>,
 
-    /// Enable verbose output
+    /// Enable verbose output, including which workspace root was detected and why
     #[arg(short, long)]
     pub verbose: bool,
 
@@ -61,23 +127,167 @@ pub struct Cli {
     #[arg(short, long)]
     pub debug: bool,
 
-    #[arg(long, value_enum, default_value_t = OutputFormat::Human)]
-    pub format: OutputFormat,
+    /// Log every JSON-RPC message exchanged with ty and the daemon to FILE (default: a
+    /// temp file)
+    #[arg(long, value_name = "FILE", num_args = 0..=1, require_equals = true)]
+    #[allow(clippy::option_option)] // clap's optional value of an optional flag
+    pub trace_lsp: Option<Option<PathBuf>>,
+
+    /// Cut each message in the --trace-lsp log to this many bytes
+    #[arg(long, value_name = "BYTES", requires = "trace_lsp")]
+    pub trace_max_bytes: Option<usize>,
+
+    /// Output format (default: human, or `format` in the config); vim-qf and emacs list
+    /// result locations for an editor
+    #[arg(long, value_enum)]
+    pub format: Option<FormatFlag>,
 
     /// Output detail level: condensed (token-efficient, default) or full (verbose)
     #[arg(long, value_enum, default_value_t = OutputDetail::Condensed)]
     pub detail: OutputDetail,
 
-    /// Timeout in seconds for daemon operations (default: 30)
+    /// Timeout in seconds for daemon operations (default: 30, or `timeout` in the config).
+    /// Reference searches, batch checks and warmup get four times as long
     #[arg(long, value_name = "SECS")]
     pub timeout: Option<u64>,
 
     /// When to use colored output [default: auto]
     #[arg(long, value_enum, default_value_t = ColorMode::Auto)]
     pub color: ColorMode,
+
+    /// Use 0-based line/column numbers for input and output (LSP convention)
+    #[arg(long, global = true)]
+    pub zero_based: bool,
+
+    /// Give locations in json, jsonl and csv output their end and matched text
+    #[arg(long, global = true)]
+    pub with_ranges: bool,
+
+    /// Include results from dependencies and the stdlib, labeled by origin
+    #[arg(long, global = true)]
+    pub include_deps: bool,
+
+    /// Only show find, refs and symbols results in paths matching this glob (repeatable)
+    #[arg(long, global = true, value_name = "GLOB")]
+    pub include: Vec<String>,
+
+    /// Hide find, refs and symbols results in paths matching this glob (repeatable;
+    /// .venv, site-packages and build/ are hidden unless --include-deps)
+    #[arg(long, global = true, value_name = "GLOB")]
+    pub exclude: Vec<String>,
+
+    /// Re-run the command whenever a Python file in the workspace changes
+    #[arg(long, global = true)]
+    pub watch: bool,
+
+    /// Write the output to this file instead of stdout
+    #[arg(long, global = true, value_name = "PATH", conflicts_with = "watch")]
+    pub output_file: Option<PathBuf>,
+
+    /// Open the output file in $EDITOR once written (`vim -q` for --format vim-qf)
+    #[arg(long, global = true, requires = "output_file")]
+    pub open: bool,
+
+    /// Language server to use: ty (default), pyright, pylsp, jedi, or a command line
+    #[arg(long, global = true, value_name = "BACKEND")]
+    pub backend: Option<String>,
+
+    /// The ty executable to run, instead of looking on PATH, in .venv and through uvx
+    #[arg(long, global = true, value_name = "PATH", conflicts_with = "backend")]
+    pub ty_path: Option<PathBuf>,
+
+    /// Daemon socket to use instead of /tmp/ty-find-{uid}.sock; also read from
+    /// `TY_FIND_SOCKET`
+    #[arg(long, global = true, value_name = "PATH")]
+    pub socket: Option<PathBuf>,
+
+    /// Share one daemon between all projects (user, default) or start one per project;
+    /// also read from
+    /// `TY_FIND_DAEMON_SCOPE`
+    #[arg(long, global = true, value_enum, value_name = "SCOPE")]
+    pub daemon_scope: Option<DaemonScope>,
+
+    /// Answer from a ty server started by this command instead of the background
+    /// daemon, for sandboxes that don't allow background processes; also read from
+    /// `TY_FIND_NO_DAEMON`
+    #[arg(long, global = true)]
+    pub no_daemon: bool,
+
+    /// How long a daemon this command starts stays up with nothing to do, e.g. 90s,
+    /// 30m or 2h, instead of the config's idle-timeout; also read from
+    /// `TY_FIND_DAEMON_TTL`
+    #[arg(long, global = true, value_name = "DURATION", value_parser = crate::config::parse_duration)]
+    pub daemon_ttl: Option<Duration>,
+
+    /// Stop a daemon this command starts once the shell (or other process) that ran
+    /// tyf exits
+    #[arg(long, global = true)]
+    pub kill_after: bool,
+
+    /// The subcommand and its arguments, for the `--format json` envelope;
+    /// filled in by [`Cli::try_parse_recording`].
+    #[arg(skip)]
+    pub invocation: Invocation,
+}
+
+impl Cli {
+    /// [`Cli::try_parse_from`], also recording the subcommand and the
+    /// arguments given to it in [`Cli::invocation`].
+    pub fn try_parse_recording<I, T>(args: I) -> Result<Self, clap::Error>
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<OsString> + Clone,
+    {
+        let mut command = Self::command();
+        let matches = command.try_get_matches_from_mut(args)?;
+        let mut cli = Self::from_arg_matches(&matches)?;
+        cli.invocation = invocation(&command, &matches);
+        Ok(cli)
+    }
+}
+
+/// The (innermost) subcommand of `matches` and the arguments given to it on
+/// the command line. Global options are left out, as are defaults.
+fn invocation(command: &clap::Command, matches: &ArgMatches) -> Invocation {
+    let (mut command, mut matches) = (command, matches);
+    let mut names = Vec::new();
+    while let Some((name, sub_matches)) = matches.subcommand() {
+        let Some(subcommand) = command.find_subcommand(name) else { break };
+        names.push(name);
+        (command, matches) = (subcommand, sub_matches);
+    }
+    let query = command
+        .get_arguments()
+        .filter(|arg| !arg.is_global_set())
+        .filter_map(|arg| {
+            let id = arg.get_id().as_str();
+            if matches.value_source(id) != Some(ValueSource::CommandLine) {
+                return None;
+            }
+            let value = match arg.get_action() {
+                ArgAction::SetTrue | ArgAction::SetFalse => Value::Bool(matches.get_flag(id)),
+                ArgAction::Count => Value::from(matches.get_count(id)),
+                _ => {
+                    let mut values: Vec<Value> = matches
+                        .get_raw(id)?
+                        .map(|raw| Value::from(raw.to_string_lossy().into_owned()))
+                        .collect();
+                    let many = matches!(arg.get_action(), ArgAction::Append)
+                        || arg.get_num_args().is_some_and(|n| n.max_values() > 1);
+                    if many || values.len() != 1 {
+                        Value::Array(values)
+                    } else {
+                        values.remove(0)
+                    }
+                }
+            };
+            Some((arg.get_long().unwrap_or(id).to_string(), value))
+        })
+        .collect();
+    Invocation { command: names.join(" "), query }
 }
 
-#[derive(Subcommand)]
+#[derive(Clone, Subcommand)]
 pub enum Commands {
     // -- Symbol Lookup --
     /// Definition, signature, and usages of a symbol by name
@@ -95,17 +305,37 @@ pub enum Commands {
         tyf show MyClass --doc                # include docstring\n  \
         tyf show MyClass --references         # also show all usages\n  \
         tyf show MyClass --all                # show everything\n  \
-        tyf show MyClass --file src/models.py # narrow to one file"
+        tyf show MyClass --file src/models.py # narrow to one file\n\n\
+        When a name matches several symbols, e.g. a Config class in two packages, tyf lists \
+        them and asks which to show if it runs in a terminal; otherwise it shows the first \
+        and lists the others on stderr. --in keeps the matches defined under a file or \
+        directory, --select N picks one from the numbered list, and --all-matches shows \
+        each of them:\n  \
+        tyf show Config --in src/billing\n  \
+        tyf show Config --select 2\n  \
+        tyf show Config --all-matches"
     )]
     Show {
         /// Symbol name(s) to show. Use Class.method to narrow to a specific class.
-        #[arg(required = true, num_args = 1..)]
+        #[arg(required = true, num_args = 1.., add = ArgValueCompleter::new(complete_symbol))]
         symbols: Vec<String>,
 
         /// Narrow the search to a specific file (searches whole project if omitted)
         #[arg(short, long)]
         file: Option<PathBuf>,
 
+        /// Only consider matches defined in this file or directory
+        #[arg(long = "in", value_name = "PATH")]
+        within: Option<PathBuf>,
+
+        /// When a name matches several symbols, show the Nth of them
+        #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..))]
+        select: Option<u32>,
+
+        /// When a name matches several symbols, show every one of them
+        #[arg(long, conflicts_with = "select")]
+        all_matches: bool,
+
         /// Include docstring in output (omitted by default)
         #[arg(short = 'd', long, default_value_t = false)]
         doc: bool,
@@ -133,15 +363,21 @@ pub enum Commands {
         Use Class.method dotted notation to narrow to a specific class member.\n\
         Use --fuzzy for partial/prefix matching (returns richer symbol information \
         including kind and container name).\n\n\
+        Use --kind to skip symbols of other kinds, e.g. the local variables that share a \
+        class's name. The daemon filters before sending results.\n\n\
+        --limit and --offset page through long lists of results, such as a --fuzzy search \
+        for a common prefix.\n\n\
         Examples:\n  \
         tyf find calculate_sum\n  \
         tyf find Calculator.add                  # find a specific class method\n  \
         tyf find calculate_sum multiply divide   # multiple symbols at once\n  \
         tyf find handler --file src/routes.py    # narrow to one file\n  \
-        tyf find handle_ --fuzzy                 # fuzzy/prefix match")]
+        tyf find Config --kind class             # only classes named Config\n  \
+        tyf find handle_ --fuzzy                 # fuzzy/prefix match\n  \
+        tyf find get_ --fuzzy --limit 50 --offset 50  # the second page of 50")]
     Find {
         /// Symbol name(s) to find. Use Class.method to narrow to a specific class.
-        #[arg(required = true, num_args = 1..)]
+        #[arg(required = true, num_args = 1.., add = ArgValueCompleter::new(complete_symbol))]
         symbols: Vec<String>,
 
         /// Narrow the search to a specific file (searches whole project if omitted)
@@ -151,6 +387,197 @@ pub enum Commands {
         /// Use fuzzy/prefix matching via workspace symbols (richer output with kind + container)
         #[arg(long, default_value_t = false)]
         fuzzy: bool,
+
+        /// Only find symbols of these kinds (comma-separated or repeated)
+        #[arg(
+            long,
+            value_enum,
+            value_delimiter = ',',
+            value_name = "KIND",
+            conflicts_with = "file"
+        )]
+        kind: Vec<SymbolKindFilter>,
+
+        /// Show at most N definitions per symbol (0 = all)
+        #[arg(long, value_name = "N")]
+        limit: Option<usize>,
+
+        /// Skip the first N definitions per symbol
+        #[arg(long, default_value_t = 0, value_name = "N")]
+        offset: usize,
+    },
+
+    /// Search the workspace's symbols, filtered by kind and directory
+    #[command(long_about = "Search the workspace's symbols by name, like an editor's \"go to \
+        symbol in workspace\". The query is matched fuzzily (exact names first, then \
+        prefixes, then names containing its letters in order); an empty query matches every \
+        symbol.\n\n\
+        --kind, --in, --limit and --offset are applied by the daemon before results are \
+        sent, so narrowing a large project down stays fast. Without --include-deps only symbols \
+        defined in the workspace are listed.\n\n\
+        Examples:\n  \
+        tyf symbols Order\n  \
+        tyf symbols handle --kind function,method\n  \
+        tyf symbols '' --kind class --in src/models\n  \
+        tyf --format paths symbols test_ --limit 20\n  \
+        tyf --format paths symbols test_ --limit 20 --offset 20   # the next 20")]
+    Symbols {
+        /// Name or part of a name to search for ('' for all symbols)
+        #[arg(add = ArgValueCompleter::new(complete_symbol))]
+        query: String,
+
+        /// Only list symbols of these kinds (comma-separated or repeated)
+        #[arg(long, value_enum, value_delimiter = ',', value_name = "KIND")]
+        kind: Vec<SymbolKindFilter>,
+
+        /// List at most N symbols (0 = all)
+        #[arg(long, value_name = "N")]
+        limit: Option<usize>,
+
+        /// Skip the first N symbols
+        #[arg(long, default_value_t = 0, value_name = "N")]
+        offset: usize,
+
+        /// Only list symbols defined under this directory or in this file
+        #[arg(long = "in", value_name = "PATH")]
+        within: Option<PathBuf>,
+    },
+
+    /// Jump to a symbol's definition by name or position
+    #[command(
+        name = "def",
+        alias = "definition",
+        long_about = "Jump to the definition of a symbol, by name or by position. Names are \
+        looked up across the whole project like 'find', then ty resolves the definition at \
+        the name itself \u{2014} so you get the exact definition site, not just the \
+        declaration line. A name defined in several places lists each definition \
+        separately.\n\n\
+        Use Class.method dotted notation to narrow to a specific class member. With \
+        --file, the name's occurrences in that file are used instead of the project-wide \
+        lookup; comments, strings and keyword-argument names don't count. 'refs' and 'show' \
+        resolve names the same way.\n\n\
+        Examples:\n  \
+        tyf def MyClass\n  \
+        tyf def Calculator.add                 # a specific method\n  \
+        tyf def src/app.py:42:9                # whatever is used at this position\n  \
+        tyf def handler --file src/routes.py   # narrow the name search to one file\n\n\
+        Editors can ask about a buffer that isn't saved: --stdin-path reads the content of \
+        that file from stdin, and positions in it are looked up in that content instead of \
+        the file on disk. The file doesn't have to exist.\n  \
+        tyf def --stdin-path src/app.py src/app.py:42:9 < buffer.py"
+    )]
+    Definition {
+        /// Symbol names or `file:line:col` positions
+        #[arg(required = true, num_args = 1.., add = ArgValueCompleter::new(complete_symbol))]
+        queries: Vec<String>,
+
+        /// Narrow the symbol search to a specific file
+        #[arg(short, long)]
+        file: Option<PathBuf>,
+
+        /// Read the content of this file from stdin (positions only)
+        #[arg(long, value_name = "PATH")]
+        stdin_path: Option<PathBuf>,
+    },
+
+    /// Declaration, implementation, and type of a symbol: stub or runtime code
+    #[command(long_about = "Where a symbol is defined, declared, implemented, and what its \
+        type is, each as ty answers it. When a name resolves to a .pyi stub, 'def' shows the \
+        stub; this shows the runtime code next to it, and the other way round. Locations in \
+        stubs are marked.\n\n\
+        By default all four are listed: definition, declaration (--decl), type definition \
+        (--type) and implementation (--impl). Any of the flags lists only those. A request \
+        the language server doesn't support is reported as such. Names are looked up like \
+        'def'; dependencies are never hidden, since that is where stubs live.\n\n\
+        Examples:\n  \
+        tyf stubs requests.get\n  \
+        tyf stubs Session.request --impl         # only the runtime implementation\n  \
+        tyf stubs src/app.py:42:9 --decl --type  # the declaration and the type's definition\n  \
+        tyf stubs handler --file src/routes.py")]
+    Stubs {
+        /// Symbol names or `file:line:col` positions
+        #[arg(required = true, num_args = 1.., add = ArgValueCompleter::new(complete_symbol))]
+        queries: Vec<String>,
+
+        /// Narrow the symbol search to a specific file
+        #[arg(short, long)]
+        file: Option<PathBuf>,
+
+        /// Show the declaration (textDocument/declaration)
+        #[arg(long)]
+        decl: bool,
+
+        /// Show the definition of the symbol's type (textDocument/typeDefinition)
+        #[arg(long = "type")]
+        type_definition: bool,
+
+        /// Show the implementation (textDocument/implementation)
+        #[arg(long = "impl")]
+        implementation: bool,
+    },
+
+    /// The full source of a symbol's definition, to read or paste into a prompt
+    #[command(long_about = "The full source of a symbol's definition: the whole function or \
+        class, decorators included, rather than just where it starts. Names are looked up \
+        like 'def', by name or at a file:line:col position; ty's definition is then matched \
+        to the file's outline to find where the body ends. A name defined in several places \
+        prints each definition.\n\n\
+        Source is colored when printed to a terminal. Without color it is exactly as in the \
+        file, under a '# name file:first-last' heading, ready to paste into a prompt.\n\n\
+        Examples:\n  \
+        tyf context OrderService.submit\n  \
+        tyf context src/app.py:42:9            # what is used at this position\n  \
+        tyf context User Order                 # several at once\n  \
+        tyf --format json context create_order # with file, lines and kind")]
+    Context {
+        /// Symbol names or `file:line:col` positions
+        #[arg(required = true, num_args = 1.., add = ArgValueCompleter::new(complete_symbol))]
+        queries: Vec<String>,
+
+        /// Narrow the symbol search to a specific file
+        #[arg(short, long)]
+        file: Option<PathBuf>,
+    },
+
+    /// Type signature and docs of a symbol by name or position
+    #[command(long_about = "Type signature and docstring of a symbol, as your editor shows on \
+        hover. Searches the whole project by name \u{2014} no line or column needed \u{2014} \
+        or takes an exact file:line:col position. A name defined in several places shows \
+        each definition.\n\n\
+        Use Class.method dotted notation to narrow to a specific class member.\n\n\
+        Examples:\n  \
+        tyf hover create_order\n  \
+        tyf hover OrderService.submit          # a specific method\n  \
+        tyf hover src/orders.py:42:9           # whatever is at this position\n  \
+        tyf hover src/orders.py -l 42 -c 9     # the same, with flags\n  \
+        tyf hover create_order --file src/orders.py\n\n\
+        With --stdin-path, the content of that file is read from stdin, so an unsaved \
+        editor buffer can be asked about by position:\n  \
+        tyf hover --stdin-path src/orders.py src/orders.py:42:9 < buffer.py")]
+    Hover {
+        /// Symbol names or `file:line:col` positions
+        #[arg(
+            required_unless_present = "line",
+            num_args = 0..,
+            add = ArgValueCompleter::new(complete_symbol)
+        )]
+        queries: Vec<String>,
+
+        /// Narrow the symbol search to a specific file (required for position mode)
+        #[arg(short, long)]
+        file: Option<PathBuf>,
+
+        /// Line number (position mode, requires --file and --column)
+        #[arg(short, long, requires = "file", requires = "column")]
+        line: Option<u32>,
+
+        /// Column number (position mode, requires --file and --line)
+        #[arg(short, long, requires = "file", requires = "line")]
+        column: Option<u32>,
+
+        /// Read the content of this file from stdin (positions only)
+        #[arg(long, value_name = "PATH")]
+        stdin_path: Option<PathBuf>,
     },
 
     /// All usages of a symbol across the codebase
@@ -158,17 +585,37 @@ pub enum Commands {
         name = "refs",
         long_about = "All usages of a symbol across the codebase. Useful before \
         renaming or removing code to understand the impact.\n\n\
-        Use Class.method dotted notation to narrow to a specific class member.\n\n\
+        Use Class.method dotted notation to narrow to a specific class member, and --kind \
+        to only look up names as symbols of those kinds (positions are used as given). \
+        --count prints how many references each symbol has instead of listing them, and \
+        --count-by-file splits that count by file.\n\n\
+        --limit and --offset page through the references of each symbol; the daemon only \
+        sends the page, so even a name used tens of thousands of times answers quickly.\n\n\
+        Several symbols can share references, like two names for the same class. --unique \
+        lists each reference once, under the first symbol that has it, and merges symbols \
+        whose references are all the same.\n\n\
+        --git notes who last changed the line of each reference shown, from `git blame`, \
+        to see who to ask before changing what they use.\n\n\
+        --sort orders references by path, line or busiest file before --references-limit \
+        cuts them, and --group-by lists them under a heading per file, enclosing symbol or \
+        kind of use (import, call, annotation, ...).\n\n\
         Examples:\n  \
         tyf refs myfile.py -l 10 -c 5\n  \
         tyf refs my_func my_class\n  \
         tyf refs Calculator.add                 # refs for a specific method\n  \
+        tyf refs Config --kind class            # the class, not variables named Config\n  \
+        tyf refs User Order --count             # how often each is used\n  \
+        tyf refs User --count-by-file           # ... and where\n  \
+        tyf refs dict --limit 100 --offset 100  # the second 100 references\n  \
+        tyf refs Order OrderAlias --unique      # shared references once\n  \
+        tyf refs process_order --git            # who last touched each use\n  \
+        tyf refs User --group-by file --sort count  # busiest files first\n  \
         tyf refs file.py:10:5 my_func\n  \
         ... | tyf refs --stdin"
     )]
     References {
         /// Symbol names or `file:line:col` positions (auto-detected, parallel)
-        #[arg(num_args = 0..)]
+        #[arg(num_args = 0.., add = ArgValueCompleter::new(complete_symbol))]
         queries: Vec<String>,
 
         /// File path (required for position mode, optional for symbol mode)
@@ -195,9 +642,112 @@ pub enum Commands {
         #[arg(long, default_value_t = 20)]
         references_limit: usize,
 
+        /// Stop loading references per query after this many, noting how many were skipped (0 = unlimited)
+        #[arg(long, default_value_t = 10_000, value_name = "N")]
+        max_results: usize,
+
+        /// Load at most N references per symbol (0 = all; replaces --max-results)
+        #[arg(long, value_name = "N", conflicts_with = "max_results")]
+        limit: Option<usize>,
+
+        /// Skip the first N references per symbol
+        #[arg(long, default_value_t = 0, value_name = "N")]
+        offset: usize,
+
         /// Show test references in a separate section (excluded by default)
         #[arg(short = 't', long, default_value_t = false)]
         tests: bool,
+
+        /// Show N lines of source around each reference, like `grep -C` (human format)
+        #[arg(short = 'C', long, default_value_t = 0, value_name = "N")]
+        context: usize,
+
+        /// Only look up names as symbols of these kinds (comma-separated or repeated)
+        #[arg(
+            long,
+            value_enum,
+            value_delimiter = ',',
+            value_name = "KIND",
+            conflicts_with = "file"
+        )]
+        kind: Vec<SymbolKindFilter>,
+
+        /// Only count the references of each symbol, without listing them
+        #[arg(long, conflicts_with = "context")]
+        count: bool,
+
+        /// Count the references of each symbol per file (implies --count)
+        #[arg(long, conflicts_with = "context")]
+        count_by_file: bool,
+
+        /// List each reference once, under the first symbol that has it
+        #[arg(long, conflicts_with_all = ["count", "count_by_file"])]
+        unique: bool,
+
+        /// Show the last commit, author and date of each reference's line (git blame)
+        #[arg(long, conflicts_with_all = ["count", "count_by_file"])]
+        git: bool,
+
+        /// List the references of each symbol under a heading per file, enclosing symbol, or kind of use
+        #[arg(long, value_enum, value_name = "BY", conflicts_with_all = ["count", "count_by_file"])]
+        group_by: Option<ReferenceGroup>,
+
+        /// Order references by path, by line within each file, or busiest file first
+        #[arg(long, value_enum, value_name = "ORDER", conflicts_with_all = ["count", "count_by_file"])]
+        sort: Option<ReferenceSort>,
+    },
+
+    /// Where a symbol is read and written within one file
+    #[command(long_about = "Every occurrence of a symbol within a single file, each marked as \
+        a read or a write, from ty's document highlights. Much faster than `refs` when only \
+        one file matters, since ty doesn't search the rest of the project.\n\n\
+        A name is looked up like `find` does and its occurrences are listed in the file that \
+        defines it; add --file to pick the file.\n\n\
+        Examples:\n  \
+        tyf occurrences src/orders.py:42:9          # the symbol at this position\n  \
+        tyf occurrences src/orders.py -l 42 -c 9    # the same, with flags\n  \
+        tyf occurrences total --file src/orders.py  # a name in one file")]
+    Occurrences {
+        /// Symbol names or `file:line:col` positions
+        #[arg(
+            required_unless_present = "line",
+            num_args = 0..,
+            add = ArgValueCompleter::new(complete_symbol)
+        )]
+        queries: Vec<String>,
+
+        /// Narrow the symbol search to a specific file (required for position mode)
+        #[arg(short, long)]
+        file: Option<PathBuf>,
+
+        /// Line number (position mode, requires --file and --column)
+        #[arg(short, long, requires = "file", requires = "column")]
+        line: Option<u32>,
+
+        /// Column number (position mode, requires --file and --line)
+        #[arg(short, long, requires = "file", requires = "line")]
+        column: Option<u32>,
+    },
+
+    /// Text search that keeps only matches resolving to a given symbol
+    #[command(long_about = "Search the workspace's Python files for PATTERN, then keep only \
+        the matches whose identifier actually resolves (via go-to-definition) to the \
+        --resolves-to target. Removes the same-name false positives of plain grep: other \
+        classes' `save` methods, unrelated locals, comments and strings.\n\n\
+        PATTERN is a literal string, not a regex. If it starts or ends with an identifier \
+        character, it won't match inside a longer identifier. The target names a symbol \
+        defined in the workspace, optionally qualified by its class and module.\n\n\
+        Examples:\n  \
+        tyf grep save --resolves-to User.save\n  \
+        tyf grep 'User(' --resolves-to app.models.User\n  \
+        tyf --format paths grep settings --resolves-to config.settings")]
+    Grep {
+        /// Literal text to search for
+        pattern: String,
+
+        /// Keep matches that resolve to this symbol (`name`, `Class.member` or `module.Class`)
+        #[arg(long, value_name = "SYMBOL")]
+        resolves_to: String,
     },
 
     /// Public interface of a class: methods, properties, and class variables
@@ -207,15 +757,19 @@ pub enum Commands {
         Excludes private (_prefixed) and dunder (__dunder__) members by default; \
         use --all to include everything.\n\n\
         Note: only shows members defined directly on the class, not inherited members.\n\n\
+        A file:line:col position instead lists every attribute available on the expression \
+        there \u{2014} an instance, a module, a union \u{2014} inherited ones included, as \
+        ty's completion offers them after a '.'.\n\n\
         Examples:\n  \
         tyf members MyClass\n  \
         tyf members MyClass UserService        # multiple classes\n  \
         tyf members MyClass --all              # include __init__, __repr__, etc\n  \
-        tyf members MyClass -f src/models.py   # narrow to one file"
+        tyf members MyClass -f src/models.py   # narrow to one file\n  \
+        tyf members src/app.py:12:5            # attributes of the expression at a position"
     )]
     Members {
-        /// Class name(s) to query (supports multiple classes)
-        #[arg(required = true, num_args = 1..)]
+        /// Class names or `file:line:col` positions of expressions
+        #[arg(required = true, num_args = 1.., add = ArgValueCompleter::new(complete_symbol))]
         symbols: Vec<String>,
 
         /// Narrow the search to a specific file (searches whole project if omitted)
@@ -227,6 +781,69 @@ pub enum Commands {
         all: bool,
     },
 
+    /// Expand the types in a symbol's signature (aliases, typed dicts, protocols)
+    #[command(
+        name = "explain-type",
+        long_about = "Expand the types in a symbol's signature. Fetches the hover signature, \
+        then follows each user-defined type it mentions (type aliases, TypedDicts, Protocols, \
+        classes) to its definition and shows its fields \u{2014} recursively, up to --depth \
+        levels.\n\n\
+        Examples:\n  \
+        tyf explain-type create_order\n  \
+        tyf explain-type src/orders.py:42:9        # by position\n  \
+        tyf explain-type OrderService.submit --depth 1"
+    )]
+    ExplainType {
+        /// Symbol name or `file:line:col` position
+        query: String,
+
+        /// Narrow the symbol search to a specific file
+        #[arg(short, long)]
+        file: Option<PathBuf>,
+
+        /// How many levels of referenced types to expand
+        #[arg(long, default_value_t = 2)]
+        depth: usize,
+    },
+
+    /// Types, definitions, and diagnostics of everything on one source line
+    #[command(long_about = "What is going on at one line of code: the type of every name on \
+        it, where each is defined, and the errors and warnings that touch the line. Names in \
+        comments and strings are left out. Everything is asked of ty in a single daemon \
+        call.\n\n\
+        Examples:\n  \
+        tyf explain src/orders.py:42\n  \
+        tyf --format json explain src/orders.py:42")]
+    Explain {
+        /// The line, as `file:line`
+        location: String,
+    },
+
+    /// Where each attribute in a chain like order.customer.city is defined
+    #[command(long_about = "Follow an attribute chain such as order.customer.address.city \
+        through the types ty infers. The first name is looked up in --file (the binding in \
+        scope at --line, if given) and its type comes from hover; each following attribute \
+        is looked up on that type's class, then on its base classes, and its type gives the \
+        class for the next hop. Prints the class and location that define each hop.\n\n\
+        Class attributes, methods, properties and `self.x = ...` assignments are found. The \
+        chain stops where a type isn't a single class defined in the workspace (a union, a \
+        builtin, a third-party type).\n\n\
+        Examples:\n  \
+        tyf where order.customer.address.city --file src/orders.py\n  \
+        tyf where self.repo.session --file src/services/user.py --line 42")]
+    Where {
+        /// Attribute chain, e.g. `order.customer.address.city`
+        expression: String,
+
+        /// File where the chain's first name is in scope
+        #[arg(short, long)]
+        file: PathBuf,
+
+        /// Line the chain appears on, to pick the right binding of its first name
+        #[arg(short, long)]
+        line: Option<u32>,
+    },
+
     // -- Browsing --
     /// All functions, classes, and variables defined in a file
     #[command(
@@ -236,7 +853,296 @@ pub enum Commands {
         Examples:\n  \
         tyf list src/services/user.py"
     )]
-    DocumentSymbols { file: PathBuf },
+    DocumentSymbols {
+        /// Python file to outline (.py, .pyi, .pyw, or a script with a python shebang)
+        file: PathBuf,
+    },
+
+    /// A file with the types ty infers for its variables and functions
+    #[command(long_about = "Print a Python file with the types ty infers written next to it: \
+        the type of each variable an assignment, `for` or `with` binds, and the signature of \
+        each function and method. Handy for reviewing what ty actually infers.\n\n\
+        Annotations are appended as `# type:` comments by default, or shown in a second \
+        column with --side-by-side. The file itself is not modified.\n\n\
+        Examples:\n  \
+        tyf annotate src/services/user.py\n  \
+        tyf annotate src/services/user.py --side-by-side\n  \
+        tyf --format csv annotate src/services/user.py")]
+    Annotate {
+        /// Python file to annotate
+        file: PathBuf,
+
+        /// Show annotations in a column beside the source instead of as comments
+        #[arg(long)]
+        side_by_side: bool,
+    },
+
+    /// Modules a file imports, and the workspace modules that import it
+    #[command(long_about = "Show a module's place in the workspace import graph: the \
+        workspace modules it imports, the modules from outside the workspace it imports, and \
+        the workspace modules that import it. `from pkg import mod` counts as importing the \
+        submodule `pkg.mod`, and relative imports are resolved.\n\n\
+        Imports are read from the source of every Python file ty would analyse; imports of \
+        modules outside the workspace are resolved with ty to show where they come from.\n\n\
+        With --format dot, prints a Graphviz graph of the module and its neighbours.\n\n\
+        Examples:\n  \
+        tyf imports app/models.py\n  \
+        tyf imports app.models\n  \
+        tyf --format dot imports app/models.py | dot -Tsvg > imports.svg")]
+    Imports {
+        /// Python file, or dotted module name like `app.models`
+        target: String,
+    },
+
+    /// Files that reference anything defined in a file
+    #[command(long_about = "List the files that depend on a file: every file with a reference \
+        to one of its top-level functions, classes, variables and constants, or to the \
+        methods and properties of its top-level classes. References are found by ty, so \
+        uses through imports, re-exports and attribute access all count, and the file \
+        itself is left out.\n\n\
+        With --format paths, prints only the file paths, one per line, to drive targeted \
+        test selection. The daemon resolves several references at once; --jobs sets how \
+        many.\n\n\
+        Examples:\n  \
+        tyf rdeps app/models.py\n  \
+        tyf --format paths rdeps app/models.py | grep '^tests/' | xargs pytest\n  \
+        tyf --format json rdeps app/models.py")]
+    Rdeps {
+        /// Python file whose dependents to list
+        file: PathBuf,
+
+        /// How many references the daemon resolves at once
+        #[arg(short, long, value_name = "N", default_value_t = 8)]
+        jobs: usize,
+    },
+
+    /// Public classes and functions of a package, with signatures and docs
+    #[command(long_about = "Report the public API of a package, module or the whole \
+        workspace: every public class and function, with the public methods and properties \
+        of each class, their signatures and the first paragraph of their docstrings. Handy \
+        for reviewing what a release exposes.\n\n\
+        A module with an `__all__` exports the names it lists; otherwise every top-level name \
+        without a leading underscore is public. Modules with a leading underscore anywhere in \
+        their dotted name, and test files, are left out. Signatures come from ty's hover, \
+        which the daemon runs for several names at once; --jobs sets how many.\n\n\
+        The human format is Markdown; --format json gives the same report as data.\n\n\
+        Examples:\n  \
+        tyf api src/shop\n  \
+        tyf api src/shop > API.md\n  \
+        tyf --format json api src/shop/orders.py")]
+    Api {
+        /// Package directory or module file (default: the whole workspace)
+        path: Option<PathBuf>,
+
+        /// How many hovers the daemon runs at once
+        #[arg(short, long, value_name = "N", default_value_t = 8)]
+        jobs: usize,
+    },
+
+    /// Lines, classes, functions, and methods per file, with totals
+    #[command(long_about = "Count the lines, classes, functions and methods of every Python \
+        file under a directory, or of one file, and print them per file followed by the \
+        totals. Counts come from ty's outline of each file: a function defined directly in \
+        a class body is a method, every other function (nested ones included) is a function. \
+        Files are found the way ty finds them, as in `tyf check-all`.\n\n\
+        The daemon outlines several files at once; --jobs sets how many. Use --format json \
+        or csv to feed the numbers to a dashboard.\n\n\
+        Examples:\n  \
+        tyf stats\n  \
+        tyf stats src/shop\n  \
+        tyf --format csv stats > stats.csv")]
+    Stats {
+        /// Directory or file to count (default: the whole workspace)
+        path: Option<PathBuf>,
+
+        /// How many files the daemon outlines at once
+        #[arg(short, long, value_name = "N", default_value_t = 8)]
+        jobs: usize,
+    },
+
+    /// Full-screen symbol search with a source preview; prints the pick
+    #[command(long_about = "Browse the workspace's symbols in a full-screen terminal view: a \
+        search box, the matching symbols, and the source around the selected one with its \
+        definition highlighted. Matching works as in `tyf symbols`: an empty query lists \
+        every symbol, and dependencies are only searched with --include-deps.\n\n\
+        Type to search, move with \u{2191}/\u{2193} (or Ctrl-P/Ctrl-N, PgUp/PgDn), Ctrl-U \
+        clears the query and Ctrl-W deletes a word. Enter prints the selected symbol's \
+        location as `path:line:col` and exits, so the pick can be handed to an editor; Esc \
+        or Ctrl-C exits with status 1 without printing anything.\n\n\
+        The view is drawn on stderr, leaving stdout for the pick.\n\n\
+        Examples:\n  \
+        tyf tui\n  \
+        tyf tui UserService\n  \
+        code --goto \"$(tyf tui)\"")]
+    Tui {
+        /// Initial search query
+        query: Option<String>,
+    },
+
+    // -- Checking --
+    /// Type errors and warnings, optionally only those not in a baseline
+    #[command(long_about = "Report ty's type errors and warnings for the given files, or for \
+        every Python file in the workspace when none are given. Exits with status 1 when \
+        any error is reported.\n\n\
+        --severity hides less severe diagnostics from the report and the summary counts. \
+        --error-on warning makes reported warnings fail the check too, and --max-errors N \
+        tolerates up to N failing diagnostics, for ratcheting a limit down over time.\n\n\
+        With --baseline, diagnostics recorded in the baseline file are hidden and only new \
+        ones are reported (and fail the check), so a legacy codebase can adopt type \
+        checking in CI without fixing everything first. --update-baseline records the \
+        current diagnostics into the file instead of checking against it. Entries match \
+        by file, code and message, not by line, so unrelated edits don't make old \
+        diagnostics new.\n\n\
+        Examples:\n  \
+        tyf check src/app.py\n  \
+        tyf check --baseline .tyf-baseline.json --update-baseline\n  \
+        tyf check --baseline .tyf-baseline.json\n  \
+        tyf check --severity warning --error-on warning --max-errors 20")]
+    Check {
+        /// Python files to check (default: all Python files in the workspace)
+        files: Vec<PathBuf>,
+
+        /// Only report diagnostics not recorded in this baseline file
+        #[arg(long, value_name = "FILE")]
+        baseline: Option<PathBuf>,
+
+        /// Record the current diagnostics in the --baseline file instead of checking against it
+        #[arg(long, requires = "baseline")]
+        update_baseline: bool,
+
+        /// Only report diagnostics at least this severe (default: all)
+        #[arg(long, value_enum, value_name = "LEVEL")]
+        severity: Option<SeverityLevel>,
+
+        /// Lowest severity that fails the check
+        #[arg(long, value_enum, value_name = "LEVEL", default_value_t = SeverityLevel::Error)]
+        error_on: SeverityLevel,
+
+        /// Fail only when more than N reported diagnostics are at the --error-on severity
+        #[arg(long, value_name = "N", default_value_t = 0)]
+        max_errors: usize,
+    },
+
+    /// Error and warning counts for every file in the workspace
+    #[command(
+        name = "check-all",
+        long_about = "Check every Python file in the workspace and print a table of error, \
+        warning and note counts per file, worst first, followed by the totals. Files are \
+        found the way ty finds them: src.include and src.exclude are honoured, and files \
+        ignored by .gitignore are skipped. Exits with status 1 when any error is found.\n\n\
+        The daemon checks several files at once; --jobs sets how many. Use `tyf check` to \
+        see the diagnostics themselves.\n\n\
+        Examples:\n  \
+        tyf check-all\n  \
+        tyf check-all --jobs 16\n  \
+        tyf --format csv check-all > diagnostics.csv"
+    )]
+    CheckAll {
+        /// How many files the daemon checks at once
+        #[arg(short, long, value_name = "N", default_value_t = 8)]
+        jobs: usize,
+    },
+
+    /// Functions, classes, and methods nothing in the workspace references
+    #[command(long_about = "Find dead code: top-level functions, classes, variables and \
+        constants, and the methods and properties of top-level classes, that ty finds no \
+        reference to anywhere in the workspace apart from their own definition.\n\n\
+        Left out because they are used without being named: dunder methods such as \
+        `__init__` and `__eq__`, names listed in the module's `__all__`, and everything \
+        defined in test files. Methods called only through a base class, framework \
+        callbacks registered by decorator, and names reached through `getattr` or string \
+        lookups still show up, so review the list before deleting anything.\n\n\
+        The daemon resolves several references at once; --jobs sets how many.\n\n\
+        Examples:\n  \
+        tyf unused\n  \
+        tyf unused --path src/services/\n  \
+        tyf --format csv unused > unused.csv")]
+    Unused {
+        /// Only report symbols defined in this file or directory
+        #[arg(long, value_name = "PATH")]
+        path: Option<PathBuf>,
+
+        /// How many references the daemon resolves at once
+        #[arg(short, long, value_name = "N", default_value_t = 8)]
+        jobs: usize,
+    },
+
+    // -- Refactoring --
+    /// Call sites that would break under a proposed signature change
+    #[command(
+        name = "sig-check",
+        long_about = "Check every call of a function against a proposed new signature \
+        before changing it. Shows the current signature from ty, finds the call sites via \
+        references, and reports each call that would no longer bind: too many positional \
+        arguments, unknown or duplicate keywords, missing required arguments.\n\n\
+        Calls are checked by their shape, not their values. Calls that unpack *args or \
+        **kwargs in a way that decides the outcome are listed as unchecked. A leading self \
+        or cls in the new signature is ignored, as method calls don't pass it.\n\n\
+        Examples:\n  \
+        tyf sig-check my_func --new-sig \"def my_func(a, *, b=1)\"\n  \
+        tyf sig-check OrderService.submit --new-sig \"def submit(self, order, /)\"\n  \
+        tyf --format csv sig-check src/app.py:12:5 --new-sig \"(a, b, c=None)\""
+    )]
+    SigCheck {
+        /// Function name or `file:line:col` position
+        query: String,
+
+        /// The proposed signature, e.g. `def my_func(a, *, b=1)`
+        #[arg(long, value_name = "SIGNATURE")]
+        new_sig: String,
+
+        /// Narrow the symbol search to a specific file
+        #[arg(short, long)]
+        file: Option<PathBuf>,
+    },
+
+    /// Move a module and update the imports that refer to it
+    #[command(long_about = "Move or rename a Python module and rewrite the imports of it \
+        across the workspace: `import old.mod`, `from old.mod import x`, `from old import \
+        mod` and relative imports, plus qualified uses like `old.mod.func()` in files that \
+        import it plainly. Where the module's last name changes, `from` imports keep the \
+        old local name with `as`, so the importing code still works. Relative imports in \
+        the moved file become absolute.\n\n\
+        Candidates are found in the source text, then confirmed with ty: an import is only \
+        rewritten if ty resolves it to the moved file.\n\n\
+        Prints a diff of the changes; nothing is written without --apply.\n\n\
+        Examples:\n  \
+        tyf move app/utils.py app/core/helpers.py\n  \
+        tyf move app/utils.py app/core/ --apply")]
+    Move {
+        /// The module file to move
+        old: PathBuf,
+
+        /// Where to move it: a file path, or an existing directory to move it into
+        new: PathBuf,
+
+        /// Move the file and write the changes instead of previewing them
+        #[arg(long)]
+        apply: bool,
+    },
+
+    /// Update the imports of a module that was already moved
+    #[command(long_about = "Rewrite the imports of a Python module that was already moved or \
+        renamed, with `git mv`, an editor or anything else, to its new name. The same \
+        imports are rewritten as by `tyf move`, and relative imports in the moved file, \
+        written for its old package, become absolute.\n\n\
+        Candidates are found in the source text, then checked with ty: the old module is \
+        gone, so an import ty resolves to some other file is left alone.\n\n\
+        Prints a diff of the changes; nothing is written without --apply.\n\n\
+        Examples:\n  \
+        git mv app/utils.py app/core/helpers.py\n  \
+        tyf moved app/utils.py app/core/helpers.py --apply")]
+    Moved {
+        /// Where the module file was
+        old: PathBuf,
+
+        /// Where it is now: a file path, or the directory it was moved into
+        new: PathBuf,
+
+        /// Write the changes instead of previewing them
+        #[arg(long)]
+        apply: bool,
+    },
 
     // -- Infrastructure --
     /// Manage the background LSP server (auto-starts on first use)
@@ -245,6 +1151,177 @@ pub enum Commands {
         command: DaemonCommands,
     },
 
+    /// Show the ty server version and which LSP features it supports
+    #[command(
+        name = "ty-info",
+        long_about = "Show the ty server version and which LSP features it supports for this \
+        workspace, as reported in its initialize response. Useful when a command fails with \
+        \"ty X.Y doesn't support textDocument/...\".\n\n\
+        Examples:\n  \
+        tyf ty-info\n  \
+        tyf --format json ty-info"
+    )]
+    TyInfo,
+
+    /// Check ty, the workspace, and the daemon, and say how to fix problems
+    #[command(long_about = "Check ty, the workspace, and the daemon, and say how to fix \
+        problems. Run it when a command fails with an error you can't place, such as \
+        \"Failed to create LSP client\".\n\n\
+        The checks, in order: how the workspace root was found and whether its ty settings \
+        parse; which language server tyf would start and from where (PATH, the workspace's \
+        .venv, or uvx); a hover on a scratch file, answered by a fresh server outside the \
+        daemon; whether the daemon's socket can be created; and a round trip to the daemon, \
+        starting it if needed. Each failure comes with a suggested fix, and tyf exits with \
+        status 1 if any check failed.\n\n\
+        Examples:\n  \
+        tyf doctor\n  \
+        tyf --backend pyright doctor\n  \
+        tyf --format json doctor")]
+    Doctor,
+
+    /// Show the settings in effect and the config file each comes from
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommands,
+    },
+
+    /// Save a command line under a name, to rerun it with tyf run
+    Alias {
+        #[command(subcommand)]
+        command: AliasCommands,
+    },
+
+    /// Run a saved alias, passing arguments to fill in its placeholders
+    #[command(
+        long_about = "Run a saved alias, passing arguments to fill in its placeholders.\n\n\
+        `{1}`, `{2}`, ... in the alias's command are replaced by the arguments after its name, \
+        in order; arguments no placeholder takes are added at the end, so flags can still be \
+        tacked on. An alias can also be run as `tyf NAME`, when NAME isn't a tyf command.\n\n\
+        Examples:\n  \
+        tyf alias add hotspots \"refs --count --sort count {1}\"\n  \
+        tyf run hotspots 'handler_*'\n  \
+        tyf run hotspots 'handler_*' --limit 10"
+    )]
+    Run {
+        /// Name of the alias (see `tyf alias list`)
+        name: String,
+
+        /// Arguments for the alias's placeholders, then extra arguments for its command
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+        args: Vec<String>,
+    },
+
+    /// Measure cold start, hover, definition, and batched refs latency
+    #[command(long_about = "Measure cold start, hover, definition, and batched refs latency \
+        against the current workspace, ty, and machine, and print p50/p90/p99/max per \
+        benchmark.\n\n\
+        Cold start times a fresh ty server from launch to its first hover answer. The warm \
+        benchmarks go through the daemon like regular commands do. Without a query, the first \
+        function or class tyf finds in the workspace is used.\n\n\
+        Examples:\n  \
+        tyf bench\n  \
+        tyf bench Calculator.add --iterations 50\n  \
+        tyf --format json bench src/app.py:12:5")]
+    Bench {
+        /// Symbol name or `file:line:col` position to query (default: picked automatically)
+        query: Option<String>,
+
+        /// Timed requests per warm benchmark (hover, definition)
+        #[arg(long, default_value_t = 20, value_name = "N")]
+        iterations: usize,
+
+        /// Repetitions of the slow benchmarks (cold start, batched references)
+        #[arg(long, default_value_t = 3, value_name = "N")]
+        runs: usize,
+    },
+
+    /// Serve definitions, references, hover, and members to AI agents over MCP
+    #[command(long_about = "Serve definitions, references, hover, and members to AI agents over \
+        the Model Context Protocol. tyf reads JSON-RPC requests from stdin and writes responses \
+        to stdout, so register it as a stdio server in the agent's MCP settings, started in the \
+        project's directory.\n\n\
+        The tools are definition, references, hover, symbols (fuzzy search), and members. \
+        Like the commands of the same names they go through the background daemon, and \
+        answers are rendered in the --format given here.\n\n\
+        Examples:\n  \
+        tyf mcp\n  \
+        tyf --format json mcp\n  \
+        claude mcp add tyf -- tyf mcp")]
+    Mcp,
+
+    /// Run queries read from stdin over one daemon connection, as JSON lines
+    #[command(long_about = "Run queries read from stdin over one daemon connection, and \
+        write one JSON object per query to stdout, in input order. Lets scripts and build \
+        tools send hundreds of queries without starting tyf for each.\n\n\
+        Each input line is a tyf command line without the leading `tyf` (quote arguments as \
+        in a shell), or a JSON object with an \"args\" array or a \"command\" string and an \
+        optional \"id\". Blank lines and lines starting with # are skipped. The commands \
+        def, hover, refs, members, symbols and list are supported, with their usual \
+        options; global options go on the `tyf batch` command line.\n\n\
+        Each output line has the \"id\" (the line number unless given), the \"command\", and \
+        either the \"result\" the command prints with --format json or an \"error\". A \
+        failing query doesn't stop the batch.\n\n\
+        Examples:\n  \
+        printf 'def app.py:10:5\\nrefs MyClass\\n' | tyf batch\n  \
+        tyf batch < queries.txt > results.jsonl\n  \
+        echo '{\"id\": 7, \"args\": [\"hover\", \"foo\"]}' | tyf batch")]
+    Batch,
+
+    /// Speak the daemon's JSON-RPC protocol on stdin and stdout
+    #[command(long_about = "Speak the daemon's JSON-RPC protocol on stdin and stdout, for \
+        scripts that embed tyf. Requests and responses are the ones the background daemon \
+        takes on its socket, each framed with a Content-Length header; this process is the \
+        server, so no socket is opened, no pidfile is written, and a running daemon is left \
+        alone.\n\n\
+        The server runs until stdin closes or a shutdown request arrives, and the ty \
+        servers it starts stop with it.\n\n\
+        Examples:\n  \
+        tyf serve --stdio\n  \
+        tyf --backend pyright-langserver serve --stdio")]
+    Serve {
+        /// Use stdin and stdout (the only transport so far)
+        #[arg(long, required = true)]
+        stdio: bool,
+    },
+
+    /// Install ty with uv, pipx, or pip
+    #[command(long_about = "Install ty with uv, pipx, or pip. tyf needs ty to answer queries; \
+        when it can't find ty, commands suggest running this.\n\n\
+        With uv, ty is added as a dev dependency of the workspace when it has a \
+        pyproject.toml (`uv add --dev ty`), and installed as a uv tool otherwise. \
+        pipx installs into its managed tool directory; pip installs into the active \
+        virtualenv, or with --user outside one.\n\n\
+        Examples:\n  \
+        tyf setup\n  \
+        tyf setup --with pipx\n  \
+        tyf setup --dry-run")]
+    Setup {
+        /// Installer to use (default: the first of uv, pipx, pip that is available)
+        #[arg(long = "with", value_enum, value_name = "INSTALLER")]
+        installer: Option<Installer>,
+
+        /// Print the install command without running it
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Print a shell completion script (bash, zsh, fish, powershell, elvish)
+    #[command(long_about = "Print a shell completion script (bash, zsh, fish, powershell, \
+        elvish) covering tyf's subcommands, flags and their values.\n\n\
+        For completion of symbol names too, have the shell ask tyf on every Tab instead: \
+        source the output of `COMPLETE=<shell> tyf`. Names come from the running daemon, \
+        so they complete once a tyf command has run in the project.\n\n\
+        Examples:\n  \
+        tyf completions bash > ~/.local/share/bash-completion/completions/tyf\n  \
+        tyf completions zsh > ~/.zfunc/_tyf\n  \
+        tyf completions fish > ~/.config/fish/completions/tyf.fish\n  \
+        echo 'source <(COMPLETE=bash tyf)' >> ~/.bashrc")]
+    Completions {
+        /// Shell to print the script for
+        #[arg(value_enum)]
+        shell: Shell,
+    },
+
     /// Generate markdown documentation from CLI help text
     #[command(hide = true)]
     GenerateDocs {
@@ -254,7 +1331,18 @@ pub enum Commands {
     },
 }
 
-#[derive(Subcommand)]
+/// Tool used by `tyf setup` to install ty.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Installer {
+    /// uv (`uv add --dev ty`, or `uv tool install ty` outside a project)
+    Uv,
+    /// pipx (`pipx install ty`)
+    Pipx,
+    /// pip (`python3 -m pip install ty`)
+    Pip,
+}
+
+#[derive(Clone, Subcommand)]
 pub enum DaemonCommands {
     /// Start the background LSP server
     Start {
@@ -266,16 +1354,240 @@ pub enum DaemonCommands {
     Stop,
     /// Stop and restart the background LSP server
     Restart,
+    /// Re-read the config files without restarting the daemon
+    #[command(long_about = "Have the running daemon re-read its settings: `idle-timeout` and \
+        `cache-size` from the user config, and each workspace's `backend`, `ty-path`, \
+        `exclude` and `roots`. Only what a change affects is redone: a workspace whose \
+        backend changed gets a new language server on its next request, and a symbol index \
+        built with other excludes is rebuilt. Every other language server keeps running, \
+        so ty doesn't have to index the project again.\n\n\
+        Examples:\n  \
+        tyf daemon reload")]
+    Reload,
     /// Show the daemon's running status
     Status,
+    /// Show request counts and latencies, server restarts and memory use per workspace
+    #[command(long_about = "Show what the running daemon has done since it started: requests \
+        and errors per method with mean, median, 95th percentile and slowest latency, how often \
+        language servers were started again, and an estimate of the memory each loaded \
+        workspace takes (its server's resident size where the OS reports it, plus the daemon's \
+        symbol index and response cache).\n\n\
+        Latency percentiles are bucket upper bounds, e.g. `<=50ms`.\n\n\
+        Examples:\n  \
+        tyf daemon stats\n  \
+        tyf --format json daemon stats")]
+    Stats,
+    /// Start language servers for workspaces ahead of their first query
+    Warm {
+        /// Workspaces to warm up (default: the current one)
+        paths: Vec<PathBuf>,
+
+        /// Wait until every workspace is ready and report how long each took
+        #[arg(long)]
+        wait: bool,
+    },
+    /// List the workspaces the daemon holds a language server for
+    #[command(long_about = "List the workspaces the daemon holds a language server for: the \
+        ty version serving each, its process id, how long since a request last used it, and \
+        how often it was started again. A server that has exited is restarted by the next \
+        request for its workspace.\n\n\
+        Examples:\n  \
+        tyf daemon workspaces\n  \
+        tyf --format json daemon workspaces")]
+    Workspaces,
+    /// Stop the language server of one workspace, leaving the daemon running
+    #[command(long_about = "Stop the language server of one workspace and forget its symbol \
+        index and cached answers, leaving the daemon and every other workspace running. The \
+        next request for that workspace starts a fresh server.\n\n\
+        Use this when one project's server is stuck or using too much memory; `tyf daemon \
+        restart` stops every workspace.\n\n\
+        Examples:\n  \
+        tyf daemon drop .\n  \
+        tyf daemon drop ~/src/other-project\n  \
+        tyf daemon drop --all")]
+    Drop {
+        /// Workspace whose server to stop (see `tyf daemon workspaces`)
+        #[arg(required_unless_present = "all")]
+        workspace: Option<PathBuf>,
+
+        /// Stop every workspace's server
+        #[arg(long, conflicts_with = "workspace")]
+        all: bool,
+    },
+    /// Manage the daemon's cache of hover, definition and document symbol answers
+    Cache {
+        #[command(subcommand)]
+        command: CacheCommands,
+    },
+}
+
+#[derive(Clone, Subcommand)]
+pub enum ConfigCommands {
+    /// Print each setting's effective value and where it was set
+    #[command(long_about = "Print each setting's effective value and where it was set: the \
+        command line, `.ty-find.toml` in the workspace root, `[tool.ty-find]` in its \
+        `pyproject.toml`, the user config file, or the built-in default. Then list the config \
+        files tyf looked for.\n\n\
+        The user config file is `~/.config/ty-find/config.toml` (under `$XDG_CONFIG_HOME` \
+        when set), or the file `TYF_CONFIG` names.\n\n\
+        Examples:\n  \
+        tyf config show\n  \
+        tyf --format json config show")]
+    Show,
+}
+
+#[derive(Clone, Subcommand)]
+pub enum AliasCommands {
+    /// Save a command line under a name
+    #[command(
+        long_about = "Save a command line under a name, in the workspace's `.ty-find.toml` \
+        (or the user config file, with --user). Replaces the alias if it exists.\n\n\
+        The command is a tyf command line without the `tyf`. `{1}`, `{2}`, ... in it are \
+        filled in by the arguments given to `tyf run NAME`.\n\n\
+        Examples:\n  \
+        tyf alias add hotspots \"refs --count --sort count {1}\"\n  \
+        tyf alias add --user handlers \"symbols handle_ --kind function\""
+    )]
+    Add {
+        /// Name to run the command by
+        name: String,
+
+        /// The command line, quoted as one argument, like \"refs --count {1}\"
+        command: String,
+
+        /// Save to the user config file instead of the workspace's
+        #[arg(long)]
+        user: bool,
+    },
+
+    /// Delete a saved alias
+    Remove {
+        /// Name of the alias
+        name: String,
+
+        /// Delete from the user config file instead of the workspace's
+        #[arg(long)]
+        user: bool,
+    },
+
+    /// List the aliases in effect and the config file each comes from
+    List,
+}
+
+#[derive(Clone, Subcommand)]
+pub enum CacheCommands {
+    /// Drop every cached answer, so the next queries go to ty
+    Clear,
+}
+
+/// Symbol kinds `--kind` can filter on, in `symbols`, `find` and `refs`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum SymbolKindFilter {
+    Module,
+    Class,
+    Function,
+    Method,
+    Property,
+    Field,
+    Variable,
+    Constant,
+}
+
+impl SymbolKindFilter {
+    /// The LSP kind ty reports for symbols of this kind.
+    pub const fn symbol_kind(self) -> SymbolKind {
+        match self {
+            Self::Module => SymbolKind::Module,
+            Self::Class => SymbolKind::Class,
+            Self::Function => SymbolKind::Function,
+            Self::Method => SymbolKind::Method,
+            Self::Property => SymbolKind::Property,
+            Self::Field => SymbolKind::Field,
+            Self::Variable => SymbolKind::Variable,
+            Self::Constant => SymbolKind::Constant,
+        }
+    }
 }
 
 #[derive(Clone, PartialEq, Eq, ValueEnum)]
 pub enum OutputFormat {
     Human,
     Json,
+    Jsonl,
     Csv,
     Paths,
+    Dot,
+}
+
+/// What `--format` takes: an [`OutputFormat`], or a [`LocationList`] for an
+/// editor, which commands print as JSON lines to be turned into one.
+#[derive(Clone, PartialEq, Eq, ValueEnum)]
+pub enum FormatFlag {
+    Human,
+    Json,
+    Jsonl,
+    Csv,
+    Paths,
+    Dot,
+    VimQf,
+    Emacs,
+}
+
+impl FormatFlag {
+    /// The format commands print in.
+    pub const fn output_format(&self) -> OutputFormat {
+        match self {
+            Self::Human => OutputFormat::Human,
+            Self::Json => OutputFormat::Json,
+            Self::Csv => OutputFormat::Csv,
+            Self::Paths => OutputFormat::Paths,
+            Self::Dot => OutputFormat::Dot,
+            Self::Jsonl | Self::VimQf | Self::Emacs => OutputFormat::Jsonl,
+        }
+    }
+
+    /// The editor list the output is turned into, if any.
+    pub const fn location_list(&self) -> Option<LocationList> {
+        match self {
+            Self::VimQf => Some(LocationList::VimQf),
+            Self::Emacs => Some(LocationList::Emacs),
+            _ => None,
+        }
+    }
+}
+
+/// Diagnostic severity threshold for `check`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum SeverityLevel {
+    /// Errors only
+    #[default]
+    Error,
+    /// Errors and warnings
+    Warning,
+    /// Everything, including informational notes and hints
+    Info,
+}
+
+/// What `refs --group-by` puts references under a heading by.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum ReferenceGroup {
+    /// The file they are in
+    File,
+    /// The function, method or class they are in
+    Symbol,
+    /// How the name is used there: import, definition, call, annotation, assignment or other
+    Kind,
+}
+
+/// The order `refs --sort` lists references in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum ReferenceSort {
+    /// By file path, then position
+    Path,
+    /// By position within each file, files in the order ty reported them
+    Line,
+    /// Files (or groups) with the most references first
+    Count,
 }
 
 #[derive(Clone, Default, ValueEnum)]
@@ -305,10 +1617,21 @@ mod tests {
             "--workspace",
             "--verbose",
             "--debug",
+            "--trace-lsp",
+            "--trace-max-bytes",
             "--format",
             "--detail",
             "--timeout",
             "--color",
+            "--zero-based",
+            "--include-deps",
+            "--include",
+            "--exclude",
+            "--watch",
+            "--backend",
+            "--ty-path",
+            "--socket",
+            "--daemon-scope",
             "--help",
             "--version",
         ];
@@ -321,6 +1644,59 @@ mod tests {
         }
     }
 
+    #[test]
+    fn invocation_records_subcommand_arguments() {
+        let args = ["tyf", "--format", "json", "refs", "User", "save", "-t", "--include", "app/"];
+        let cli = Cli::try_parse_recording(args).unwrap();
+        assert_eq!(cli.invocation.command, "refs");
+        let query = Value::Object(cli.invocation.query);
+        // Global options (--format, --include) are not part of the query.
+        assert_eq!(query, serde_json::json!({"queries": ["User", "save"], "tests": true}));
+
+        let cli = Cli::try_parse_recording(["tyf", "daemon", "stats"]).unwrap();
+        assert_eq!(cli.invocation.command, "daemon stats");
+        let cli = Cli::try_parse_recording(["tyf", "symbols", "User", "--kind", "class"]).unwrap();
+        assert_eq!(cli.invocation.query["kind"], serde_json::json!(["class"]));
+    }
+
+    #[test]
+    fn trace_lsp_takes_an_optional_file() {
+        let cli = Cli::try_parse_from(["tyf", "--trace-lsp", "find", "User"]).unwrap();
+        assert_eq!(cli.trace_lsp, Some(None));
+        assert!(matches!(cli.command, Commands::Find { .. }));
+
+        let args = ["tyf", "--trace-lsp=ty.log", "--trace-max-bytes", "200", "find", "User"];
+        let cli = Cli::try_parse_from(args).unwrap();
+        assert_eq!(cli.trace_lsp, Some(Some(PathBuf::from("ty.log"))));
+        assert_eq!(cli.trace_max_bytes, Some(200));
+
+        let cli = Cli::try_parse_from(["tyf", "find", "User"]).unwrap();
+        assert_eq!(cli.trace_lsp, None);
+        assert!(Cli::try_parse_from(["tyf", "--trace-max-bytes", "200", "find", "User"]).is_err());
+    }
+
+    #[test]
+    fn daemon_options_are_global() {
+        let args = ["tyf", "daemon", "start", "--foreground", "--socket", "/run/tyf.sock"];
+        let cli = Cli::try_parse_from(args).unwrap();
+        assert_eq!(cli.socket, Some(PathBuf::from("/run/tyf.sock")));
+
+        let cli =
+            Cli::try_parse_from(["tyf", "find", "User", "--daemon-scope", "project"]).unwrap();
+        assert_eq!(cli.daemon_scope, Some(DaemonScope::Project));
+        assert!(Cli::try_parse_from(["tyf", "--daemon-scope", "host", "find", "User"]).is_err());
+
+        assert!(Cli::try_parse_from(["tyf", "refs", "User", "--no-daemon"]).unwrap().no_daemon);
+        assert!(!Cli::try_parse_from(["tyf", "refs", "User"]).unwrap().no_daemon);
+
+        let cli =
+            Cli::try_parse_from(["tyf", "--daemon-ttl", "30m", "refs", "User", "--kill-after"])
+                .unwrap();
+        assert_eq!(cli.daemon_ttl, Some(Duration::from_secs(1800)));
+        assert!(cli.kill_after);
+        assert!(Cli::try_parse_from(["tyf", "--daemon-ttl", "soon", "refs", "User"]).is_err());
+    }
+
     /// Verify that `--detail` documents both value variants.
     #[test]
     fn help_shows_detail_variants() {
@@ -366,6 +1742,83 @@ mod tests {
         }
     }
 
+    #[test]
+    fn refs_accepts_count_flags() {
+        let cli = Cli::try_parse_from(["tyf", "refs", "User", "--count"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Commands::References { count: true, count_by_file: false, .. }
+        ));
+        let cli = Cli::try_parse_from(["tyf", "refs", "User", "--count-by-file"]).unwrap();
+        assert!(matches!(cli.command, Commands::References { count_by_file: true, .. }));
+        assert!(Cli::try_parse_from(["tyf", "refs", "User", "--count", "-C", "2"]).is_err());
+    }
+
+    #[test]
+    fn refs_accepts_git_but_not_with_count() {
+        let cli = Cli::try_parse_from(["tyf", "refs", "User", "--git"]).unwrap();
+        assert!(matches!(cli.command, Commands::References { git: true, .. }));
+        assert!(Cli::try_parse_from(["tyf", "refs", "User", "--git", "--count"]).is_err());
+    }
+
+    #[test]
+    fn refs_accepts_group_by_and_sort() {
+        let cli =
+            Cli::try_parse_from(["tyf", "refs", "User", "--group-by", "kind", "--sort", "count"])
+                .unwrap();
+        assert!(matches!(
+            cli.command,
+            Commands::References {
+                group_by: Some(ReferenceGroup::Kind),
+                sort: Some(ReferenceSort::Count),
+                ..
+            }
+        ));
+        let with_count = ["tyf", "refs", "User", "--group-by", "file", "--count"];
+        assert!(Cli::try_parse_from(with_count).is_err());
+        assert!(Cli::try_parse_from(["tyf", "refs", "User", "--sort", "size"]).is_err());
+    }
+
+    #[test]
+    fn refs_and_find_take_limit_and_offset() {
+        let cli = Cli::try_parse_from(["tyf", "refs", "dict", "--limit", "100", "--offset", "200"])
+            .unwrap();
+        assert!(matches!(
+            cli.command,
+            Commands::References { limit: Some(100), offset: 200, max_results: 10_000, .. }
+        ));
+        let with_max = ["tyf", "refs", "dict", "--limit", "5", "--max-results", "9"];
+        assert!(Cli::try_parse_from(with_max).is_err());
+
+        let cli = Cli::try_parse_from(["tyf", "find", "get_", "--fuzzy", "--limit", "3"]).unwrap();
+        assert!(matches!(cli.command, Commands::Find { limit: Some(3), offset: 0, .. }));
+    }
+
+    #[test]
+    fn occurrences_takes_positions_or_flags() {
+        let cli = Cli::try_parse_from(["tyf", "occurrences", "app.py:3:5"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Commands::Occurrences { ref queries, line: None, .. } if queries == &["app.py:3:5"]
+        ));
+        let cli = Cli::try_parse_from(["tyf", "occurrences", "-f", "app.py", "-l", "3", "-c", "5"])
+            .unwrap();
+        assert!(matches!(
+            cli.command,
+            Commands::Occurrences { line: Some(3), column: Some(5), .. }
+        ));
+        assert!(Cli::try_parse_from(["tyf", "occurrences"]).is_err());
+    }
+
+    #[test]
+    fn refs_context_takes_a_line_count() {
+        let cli = Cli::try_parse_from(["tyf", "refs", "my_func", "-C", "2"]).unwrap();
+        match cli.command {
+            Commands::References { context, .. } => assert_eq!(context, 2),
+            _ => panic!("expected References"),
+        }
+    }
+
     #[test]
     fn show_accepts_tests_flag() {
         let cli =
@@ -421,6 +1874,592 @@ mod tests {
         }
     }
 
+    #[test]
+    fn show_disambiguation_flags() {
+        let cli =
+            Cli::try_parse_from(["tyf", "show", "Config", "--in", "src", "--select", "2"]).unwrap();
+        match cli.command {
+            Commands::Show { within, select, all_matches, .. } => {
+                assert_eq!(within.as_deref(), Some(std::path::Path::new("src")));
+                assert_eq!(select, Some(2));
+                assert!(!all_matches);
+            }
+            _ => panic!("expected Show"),
+        }
+        assert!(Cli::try_parse_from(["tyf", "show", "Config", "--select", "0"]).is_err());
+        assert!(Cli::try_parse_from(["tyf", "show", "Config", "--select", "1", "--all-matches"])
+            .is_err());
+    }
+
+    #[test]
+    fn def_accepts_names_positions_and_alias() {
+        let cli = Cli::try_parse_from(["tyf", "def", "MyClass", "app.py:3:5"]).unwrap();
+        match cli.command {
+            Commands::Definition { queries, file, stdin_path } => {
+                assert_eq!(queries, vec!["MyClass", "app.py:3:5"]);
+                assert!(file.is_none());
+                assert!(stdin_path.is_none());
+            }
+            _ => panic!("expected Definition"),
+        }
+        let cli = Cli::try_parse_from(["tyf", "definition", "MyClass"]).unwrap();
+        assert!(matches!(cli.command, Commands::Definition { .. }));
+    }
+
+    #[test]
+    fn context_takes_queries_and_file() {
+        let cli =
+            Cli::try_parse_from(["tyf", "context", "User", "app.py:3:7", "-f", "app.py"]).unwrap();
+        match cli.command {
+            Commands::Context { queries, file } => {
+                assert_eq!(queries, ["User", "app.py:3:7"]);
+                assert_eq!(file, Some(PathBuf::from("app.py")));
+            }
+            _ => panic!("expected Context"),
+        }
+        assert!(Cli::try_parse_from(["tyf", "context"]).is_err());
+    }
+
+    #[test]
+    fn stubs_takes_goto_flags() {
+        let cli =
+            Cli::try_parse_from(["tyf", "stubs", "requests.get", "--impl", "--type"]).unwrap();
+        match cli.command {
+            Commands::Stubs { queries, decl, type_definition, implementation, .. } => {
+                assert_eq!(queries, vec!["requests.get"]);
+                assert!(!decl && type_definition && implementation);
+            }
+            _ => panic!("expected Stubs"),
+        }
+        assert!(Cli::try_parse_from(["tyf", "stubs"]).is_err());
+    }
+
+    #[test]
+    fn explain_takes_a_location() {
+        let cli = Cli::try_parse_from(["tyf", "explain", "src/orders.py:42"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Commands::Explain { ref location } if location == "src/orders.py:42"
+        ));
+        assert!(Cli::try_parse_from(["tyf", "explain"]).is_err());
+    }
+
+    #[test]
+    fn def_and_hover_take_stdin_path() {
+        let cli =
+            Cli::try_parse_from(["tyf", "def", "--stdin-path", "virtual.py", "virtual.py:3:5"])
+                .unwrap();
+        assert!(matches!(
+            cli.command,
+            Commands::Definition { stdin_path: Some(ref p), .. } if p.as_os_str() == "virtual.py"
+        ));
+        let cli =
+            Cli::try_parse_from(["tyf", "hover", "--stdin-path", "virtual.py", "virtual.py:3:5"])
+                .unwrap();
+        assert!(matches!(cli.command, Commands::Hover { stdin_path: Some(_), .. }));
+    }
+
+    #[test]
+    fn hover_accepts_names_and_file() {
+        let cli = Cli::try_parse_from(["tyf", "hover", "create_order", "-f", "orders.py"]).unwrap();
+        match cli.command {
+            Commands::Hover { queries, file, .. } => {
+                assert_eq!(queries, vec!["create_order"]);
+                assert_eq!(file, Some(PathBuf::from("orders.py")));
+            }
+            _ => panic!("expected Hover"),
+        }
+    }
+
+    #[test]
+    fn hover_accepts_a_position_instead_of_queries() {
+        let cli = Cli::try_parse_from(["tyf", "hover", "-f", "orders.py", "-l", "42", "-c", "9"])
+            .unwrap();
+        match cli.command {
+            Commands::Hover { queries, line, column, .. } => {
+                assert!(queries.is_empty());
+                assert_eq!((line, column), (Some(42), Some(9)));
+            }
+            _ => panic!("expected Hover"),
+        }
+        assert!(Cli::try_parse_from(["tyf", "hover"]).is_err());
+        assert!(Cli::try_parse_from(["tyf", "hover", "-l", "42", "-c", "9"]).is_err());
+    }
+
+    #[test]
+    fn zero_based_defaults_to_false() {
+        let cli = Cli::try_parse_from(["tyf", "refs", "my_func"]).unwrap();
+        assert!(!cli.zero_based);
+    }
+
+    #[test]
+    fn with_ranges_is_global() {
+        assert!(!Cli::try_parse_from(["tyf", "refs", "User"]).unwrap().with_ranges);
+        let cli = Cli::try_parse_from(["tyf", "--format", "csv", "refs", "User", "--with-ranges"])
+            .unwrap();
+        assert!(cli.with_ranges);
+    }
+
+    #[test]
+    fn zero_based_accepted_after_subcommand() {
+        let cli = Cli::try_parse_from(["tyf", "refs", "file.py:0:4", "--zero-based"]).unwrap();
+        assert!(cli.zero_based);
+    }
+
+    #[test]
+    fn explain_type_parses_query_and_depth() {
+        let cli =
+            Cli::try_parse_from(["tyf", "explain-type", "create_order", "--depth", "3"]).unwrap();
+        match cli.command {
+            Commands::ExplainType { query, depth, file } => {
+                assert_eq!(query, "create_order");
+                assert_eq!(depth, 3);
+                assert!(file.is_none());
+            }
+            _ => panic!("expected ExplainType"),
+        }
+    }
+
+    #[test]
+    fn grep_requires_resolves_to() {
+        let cli =
+            Cli::try_parse_from(["tyf", "grep", "save", "--resolves-to", "User.save"]).unwrap();
+        match cli.command {
+            Commands::Grep { pattern, resolves_to } => {
+                assert_eq!(pattern, "save");
+                assert_eq!(resolves_to, "User.save");
+            }
+            _ => panic!("expected Grep"),
+        }
+        assert!(Cli::try_parse_from(["tyf", "grep", "save"]).is_err());
+    }
+
+    #[test]
+    fn sig_check_takes_query_and_new_signature() {
+        let cli = Cli::try_parse_from([
+            "tyf",
+            "sig-check",
+            "my_func",
+            "--new-sig",
+            "def my_func(a, *, b=1)",
+        ])
+        .unwrap();
+        match cli.command {
+            Commands::SigCheck { query, new_sig, file } => {
+                assert_eq!(query, "my_func");
+                assert_eq!(new_sig, "def my_func(a, *, b=1)");
+                assert!(file.is_none());
+            }
+            _ => panic!("expected SigCheck"),
+        }
+        assert!(Cli::try_parse_from(["tyf", "sig-check", "my_func"]).is_err());
+    }
+
+    #[test]
+    fn check_update_baseline_requires_baseline() {
+        let cli = Cli::try_parse_from([
+            "tyf",
+            "check",
+            "a.py",
+            "b.py",
+            "--baseline",
+            ".tyf-baseline.json",
+            "--update-baseline",
+        ])
+        .unwrap();
+        match cli.command {
+            Commands::Check {
+                files,
+                baseline,
+                update_baseline,
+                severity,
+                error_on,
+                max_errors,
+            } => {
+                assert_eq!(files, vec![PathBuf::from("a.py"), PathBuf::from("b.py")]);
+                assert_eq!(baseline, Some(PathBuf::from(".tyf-baseline.json")));
+                assert!(update_baseline);
+                assert_eq!(severity, None);
+                assert_eq!(error_on, SeverityLevel::Error);
+                assert_eq!(max_errors, 0);
+            }
+            _ => panic!("expected Check"),
+        }
+        assert!(Cli::try_parse_from(["tyf", "check"]).is_ok());
+        assert!(Cli::try_parse_from(["tyf", "check", "--update-baseline"]).is_err());
+    }
+
+    #[test]
+    fn where_requires_file() {
+        let cli =
+            Cli::try_parse_from(["tyf", "where", "order.customer", "-f", "app.py", "-l", "12"])
+                .unwrap();
+        match cli.command {
+            Commands::Where { expression, file, line } => {
+                assert_eq!(expression, "order.customer");
+                assert_eq!(file, PathBuf::from("app.py"));
+                assert_eq!(line, Some(12));
+            }
+            _ => panic!("expected Where"),
+        }
+        assert!(Cli::try_parse_from(["tyf", "where", "order.customer"]).is_err());
+    }
+
+    #[test]
+    fn move_previews_by_default() {
+        let cli = Cli::try_parse_from(["tyf", "move", "app/utils.py", "app/core/"]).unwrap();
+        match cli.command {
+            Commands::Move { old, new, apply } => {
+                assert_eq!(old, PathBuf::from("app/utils.py"));
+                assert_eq!(new, PathBuf::from("app/core/"));
+                assert!(!apply);
+            }
+            _ => panic!("expected Move"),
+        }
+        assert!(Cli::try_parse_from(["tyf", "move", "app/utils.py"]).is_err());
+
+        let cli =
+            Cli::try_parse_from(["tyf", "moved", "app/utils.py", "app/core/helpers.py"]).unwrap();
+        assert!(matches!(cli.command, Commands::Moved { apply: false, .. }));
+    }
+
+    #[test]
+    fn backend_takes_name_or_command() {
+        let cli = Cli::try_parse_from(["tyf", "find", "foo", "--backend", "pyright"]).unwrap();
+        assert_eq!(cli.backend.as_deref(), Some("pyright"));
+        let cli =
+            Cli::try_parse_from(["tyf", "--backend", "my-lsp --stdio", "find", "foo"]).unwrap();
+        assert_eq!(cli.backend.as_deref(), Some("my-lsp --stdio"));
+        let cli = Cli::try_parse_from(["tyf", "find", "foo", "--ty-path", "/opt/ty"]).unwrap();
+        assert_eq!(cli.ty_path.as_deref(), Some(std::path::Path::new("/opt/ty")));
+        assert!(Cli::try_parse_from(["tyf", "--ty-path", "ty", "--backend", "pylsp", "doctor"])
+            .is_err());
+    }
+
+    #[test]
+    fn imports_takes_file_or_module() {
+        let cli = Cli::try_parse_from(["tyf", "--format", "dot", "imports", "app.models"]).unwrap();
+        match cli.command {
+            Commands::Imports { target } => assert_eq!(target, "app.models"),
+            _ => panic!("expected Imports"),
+        }
+        assert!(cli.format == Some(FormatFlag::Dot));
+        assert!(Cli::try_parse_from(["tyf", "imports"]).is_err());
+    }
+
+    #[test]
+    fn editor_formats_print_json_lines_for_a_location_list() {
+        let cli = Cli::try_parse_from(["tyf", "--format", "vim-qf", "refs", "User"]).unwrap();
+        let format = cli.format.unwrap();
+        assert!(format.output_format() == OutputFormat::Jsonl);
+        assert_eq!(format.location_list(), Some(LocationList::VimQf));
+        assert_eq!(FormatFlag::Json.location_list(), None);
+
+        let open = ["tyf", "refs", "User", "--output-file", "refs.qf", "--open"];
+        let cli = Cli::try_parse_from(open).unwrap();
+        assert_eq!(cli.output_file, Some(PathBuf::from("refs.qf")));
+        assert!(Cli::try_parse_from(["tyf", "refs", "User", "--open"]).is_err());
+    }
+
+    #[test]
+    fn api_takes_optional_path_and_jobs() {
+        let cli = Cli::try_parse_from(["tyf", "api"]).unwrap();
+        assert!(matches!(cli.command, Commands::Api { path: None, jobs: 8 }));
+        let cli = Cli::try_parse_from(["tyf", "api", "src/shop", "-j", "2"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Commands::Api { path: Some(ref path), jobs: 2 } if path.as_os_str() == "src/shop"
+        ));
+    }
+
+    #[test]
+    fn rdeps_takes_file_and_jobs() {
+        let cli = Cli::try_parse_from(["tyf", "rdeps", "app/models.py"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Commands::Rdeps { ref file, jobs: 8 } if file == std::path::Path::new("app/models.py")
+        ));
+        let cli = Cli::try_parse_from(["tyf", "rdeps", "app/models.py", "-j", "2"]).unwrap();
+        assert!(matches!(cli.command, Commands::Rdeps { jobs: 2, .. }));
+        assert!(Cli::try_parse_from(["tyf", "rdeps"]).is_err());
+    }
+
+    #[test]
+    fn symbols_takes_kind_list_limit_and_dir() {
+        let cli = Cli::try_parse_from([
+            "tyf",
+            "symbols",
+            "",
+            "--kind",
+            "class,function",
+            "--limit",
+            "5",
+            "--offset",
+            "10",
+            "--in",
+            "src/models",
+        ])
+        .unwrap();
+        match cli.command {
+            Commands::Symbols { query, kind, limit, offset, within } => {
+                assert_eq!(query, "");
+                assert_eq!(kind, [SymbolKindFilter::Class, SymbolKindFilter::Function]);
+                assert_eq!(limit, Some(5));
+                assert_eq!(offset, 10);
+                assert_eq!(within, Some(PathBuf::from("src/models")));
+            }
+            _ => panic!("expected Symbols"),
+        }
+        assert!(Cli::try_parse_from(["tyf", "symbols", "x", "--kind", "lambda"]).is_err());
+    }
+
+    #[test]
+    fn batch_takes_no_arguments() {
+        let cli = Cli::try_parse_from(["tyf", "--zero-based", "batch"]).unwrap();
+        assert!(matches!(cli.command, Commands::Batch));
+        assert!(cli.zero_based);
+        assert!(Cli::try_parse_from(["tyf", "batch", "def"]).is_err());
+    }
+
+    #[test]
+    fn serve_requires_stdio() {
+        let cli = Cli::try_parse_from(["tyf", "serve", "--stdio"]).unwrap();
+        assert!(matches!(cli.command, Commands::Serve { stdio: true }));
+        assert!(Cli::try_parse_from(["tyf", "serve"]).is_err());
+    }
+
+    #[test]
+    fn doctor_takes_no_arguments() {
+        let cli = Cli::try_parse_from(["tyf", "--timeout", "5", "doctor"]).unwrap();
+        assert!(matches!(cli.command, Commands::Doctor));
+        assert_eq!(cli.timeout, Some(5));
+        assert!(Cli::try_parse_from(["tyf", "doctor", "extra"]).is_err());
+    }
+
+    #[test]
+    fn config_show_parses() {
+        let cli = Cli::try_parse_from(["tyf", "config", "show"]).unwrap();
+        assert!(matches!(cli.command, Commands::Config { command: ConfigCommands::Show }));
+        assert!(cli.format.is_none());
+        assert!(Cli::try_parse_from(["tyf", "config"]).is_err());
+    }
+
+    #[test]
+    fn alias_and_run_parse() {
+        let cli =
+            Cli::try_parse_from(["tyf", "alias", "add", "hotspots", "refs --count {1}", "--user"])
+                .unwrap();
+        match cli.command {
+            Commands::Alias { command: AliasCommands::Add { name, command, user } } => {
+                assert_eq!(
+                    (name.as_str(), command.as_str(), user),
+                    ("hotspots", "refs --count {1}", true)
+                );
+            }
+            _ => panic!("expected Alias Add"),
+        }
+        let cli = Cli::try_parse_from(["tyf", "alias", "remove", "hotspots"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Commands::Alias { command: AliasCommands::Remove { user: false, .. } }
+        ));
+
+        let cli =
+            Cli::try_parse_from(["tyf", "run", "hotspots", "handler_*", "--limit", "5"]).unwrap();
+        match cli.command {
+            Commands::Run { name, args } => {
+                assert_eq!(name, "hotspots");
+                assert_eq!(args, ["handler_*", "--limit", "5"]);
+            }
+            _ => panic!("expected Run"),
+        }
+        assert!(Cli::try_parse_from(["tyf", "run"]).is_err());
+    }
+
+    #[test]
+    fn annotate_takes_file_and_layout() {
+        let cli = Cli::try_parse_from(["tyf", "annotate", "app.py", "--side-by-side"]).unwrap();
+        match cli.command {
+            Commands::Annotate { file, side_by_side } => {
+                assert_eq!(file, PathBuf::from("app.py"));
+                assert!(side_by_side);
+            }
+            _ => panic!("expected Annotate"),
+        }
+        assert!(Cli::try_parse_from(["tyf", "annotate"]).is_err());
+    }
+
+    #[test]
+    fn check_parses_thresholds() {
+        let cli = Cli::try_parse_from([
+            "tyf",
+            "check",
+            "--severity",
+            "warning",
+            "--error-on",
+            "warning",
+            "--max-errors",
+            "20",
+        ])
+        .unwrap();
+        match cli.command {
+            Commands::Check { severity, error_on, max_errors, .. } => {
+                assert_eq!(severity, Some(SeverityLevel::Warning));
+                assert_eq!(error_on, SeverityLevel::Warning);
+                assert_eq!(max_errors, 20);
+            }
+            _ => panic!("expected Check"),
+        }
+        assert!(Cli::try_parse_from(["tyf", "check", "--severity", "fatal"]).is_err());
+    }
+
+    #[test]
+    fn watch_is_accepted_after_the_subcommand() {
+        let cli = Cli::try_parse_from(["tyf", "refs", "my_func", "--watch"]).unwrap();
+        assert!(cli.watch);
+        assert!(!Cli::try_parse_from(["tyf", "refs", "my_func"]).unwrap().watch);
+    }
+
+    #[test]
+    fn completions_parses_shell() {
+        let cli = Cli::try_parse_from(["tyf", "completions", "zsh"]).unwrap();
+        assert!(matches!(cli.command, Commands::Completions { shell: Shell::Zsh }));
+        assert!(Cli::try_parse_from(["tyf", "completions", "tcsh"]).is_err());
+    }
+
+    #[test]
+    fn daemon_cache_clear_parses() {
+        let cli = Cli::try_parse_from(["tyf", "daemon", "cache", "clear"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Commands::Daemon { command: DaemonCommands::Cache { command: CacheCommands::Clear } }
+        ));
+    }
+
+    #[test]
+    fn daemon_stats_parses() {
+        let cli = Cli::try_parse_from(["tyf", "--format", "json", "daemon", "stats"]).unwrap();
+        assert!(matches!(cli.command, Commands::Daemon { command: DaemonCommands::Stats }));
+        let cli = Cli::try_parse_from(["tyf", "daemon", "reload"]).unwrap();
+        assert!(matches!(cli.command, Commands::Daemon { command: DaemonCommands::Reload }));
+    }
+
+    #[test]
+    fn daemon_warm_takes_paths() {
+        let cli = Cli::try_parse_from(["tyf", "daemon", "warm"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Commands::Daemon { command: DaemonCommands::Warm { ref paths, wait: false } }
+                if paths.is_empty()
+        ));
+        let cli = Cli::try_parse_from(["tyf", "daemon", "warm", "a", "b", "--wait"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Commands::Daemon { command: DaemonCommands::Warm { ref paths, wait: true } }
+                if paths.len() == 2
+        ));
+    }
+
+    #[test]
+    fn daemon_drop_takes_workspace_or_all() {
+        let cli = Cli::try_parse_from(["tyf", "daemon", "drop", "proj"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Commands::Daemon { command: DaemonCommands::Drop { workspace: Some(ref w), all: false } }
+                if w == std::path::Path::new("proj")
+        ));
+        let cli = Cli::try_parse_from(["tyf", "daemon", "drop", "--all"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Commands::Daemon { command: DaemonCommands::Drop { workspace: None, all: true } }
+        ));
+        assert!(Cli::try_parse_from(["tyf", "daemon", "drop"]).is_err());
+        assert!(Cli::try_parse_from(["tyf", "daemon", "drop", "proj", "--all"]).is_err());
+        let cli = Cli::try_parse_from(["tyf", "daemon", "workspaces"]).unwrap();
+        assert!(matches!(cli.command, Commands::Daemon { command: DaemonCommands::Workspaces }));
+    }
+
+    #[test]
+    fn find_and_refs_take_kinds() {
+        let cli =
+            Cli::try_parse_from(["tyf", "find", "Config", "--kind", "class,function"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Commands::Find { ref kind, .. }
+                if kind == &[SymbolKindFilter::Class, SymbolKindFilter::Function]
+        ));
+        let cli =
+            Cli::try_parse_from(["tyf", "refs", "Config", "--kind", "class", "--kind", "method"])
+                .unwrap();
+        assert!(matches!(
+            cli.command,
+            Commands::References { ref kind, .. }
+                if kind == &[SymbolKindFilter::Class, SymbolKindFilter::Method]
+        ));
+        assert!(Cli::try_parse_from(["tyf", "find", "x", "--kind", "class", "-f", "a.py"]).is_err());
+        assert!(Cli::try_parse_from(["tyf", "refs", "x", "--kind", "class", "-f", "a.py"]).is_err());
+    }
+
+    #[test]
+    fn check_all_parses_jobs() {
+        let cli = Cli::try_parse_from(["tyf", "check-all"]).unwrap();
+        assert!(matches!(cli.command, Commands::CheckAll { jobs: 8 }));
+        let cli = Cli::try_parse_from(["tyf", "check-all", "-j", "16"]).unwrap();
+        assert!(matches!(cli.command, Commands::CheckAll { jobs: 16 }));
+    }
+
+    #[test]
+    fn unused_parses_path_and_jobs() {
+        let cli = Cli::try_parse_from(["tyf", "unused"]).unwrap();
+        assert!(matches!(cli.command, Commands::Unused { path: None, jobs: 8 }));
+        let cli = Cli::try_parse_from(["tyf", "unused", "--path", "src", "-j", "2"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Commands::Unused { path: Some(ref path), jobs: 2 } if path.as_os_str() == "src"
+        ));
+    }
+
+    #[test]
+    fn stats_parses_path_and_jobs() {
+        let cli = Cli::try_parse_from(["tyf", "stats"]).unwrap();
+        assert!(matches!(cli.command, Commands::Stats { path: None, jobs: 8 }));
+        let cli = Cli::try_parse_from(["tyf", "stats", "src", "-j", "2"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Commands::Stats { path: Some(ref path), jobs: 2 } if path.as_os_str() == "src"
+        ));
+    }
+
+    #[test]
+    fn tui_parses_optional_query() {
+        let cli = Cli::try_parse_from(["tyf", "tui"]).unwrap();
+        assert!(matches!(cli.command, Commands::Tui { query: None }));
+        let cli = Cli::try_parse_from(["tyf", "tui", "User"]).unwrap();
+        assert!(matches!(cli.command, Commands::Tui { query: Some(ref q) } if q == "User"));
+    }
+
+    #[test]
+    fn bench_parses_query_and_counts() {
+        let cli = Cli::try_parse_from(["tyf", "bench"]).unwrap();
+        match cli.command {
+            Commands::Bench { query, iterations, runs } => {
+                assert!(query.is_none());
+                assert_eq!((iterations, runs), (20, 3));
+            }
+            _ => panic!("expected Bench"),
+        }
+
+        let cli =
+            Cli::try_parse_from(["tyf", "bench", "Calculator.add", "--iterations", "50"]).unwrap();
+        match cli.command {
+            Commands::Bench { query, iterations, .. } => {
+                assert_eq!(query.as_deref(), Some("Calculator.add"));
+                assert_eq!(iterations, 50);
+            }
+            _ => panic!("expected Bench"),
+        }
+    }
+
     /// Verify that all subcommands appear in help (except hidden ones like generate-docs).
     #[test]
     fn help_shows_all_subcommands() {
@@ -429,7 +2468,47 @@ mod tests {
         cmd.write_help(&mut buf).unwrap();
         let help = String::from_utf8(buf).unwrap();
 
-        let expected_subcommands = &["show", "find", "refs", "members", "list", "daemon"];
+        let expected_subcommands = &[
+            "show",
+            "find",
+            "symbols",
+            "def",
+            "stubs",
+            "context",
+            "hover",
+            "refs",
+            "occurrences",
+            "grep",
+            "members",
+            "explain-type",
+            "where",
+            "explain",
+            "list",
+            "annotate",
+            "imports",
+            "rdeps",
+            "api",
+            "stats",
+            "tui",
+            "check",
+            "check-all",
+            "unused",
+            "sig-check",
+            "move",
+            "moved",
+            "daemon",
+            "ty-info",
+            "doctor",
+            "config",
+            "alias",
+            "run",
+            "bench",
+            "mcp",
+            "batch",
+            "serve",
+            "setup",
+            "completions",
+        ];
 
         for subcmd in expected_subcommands {
             assert!(
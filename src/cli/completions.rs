@@ -0,0 +1,112 @@
+//! Shell completion for `tyf completions` and `COMPLETE=<shell> tyf`.
+//!
+//! `tyf completions <shell>` prints a static script covering subcommands,
+//! flags and their values. Sourcing `COMPLETE=<shell> tyf` instead has the
+//! shell ask tyf itself on every Tab, which additionally completes symbol
+//! names from the running daemon.
+
+use anyhow::{Context, Result};
+use clap::CommandFactory;
+use clap_complete::engine::CompletionCandidate;
+use clap_complete::Shell;
+use std::ffi::OsStr;
+use std::io::Write;
+
+use crate::cli::args::Cli;
+
+/// Most symbol names offered for one Tab.
+const MAX_SYMBOL_CANDIDATES: usize = 50;
+
+/// How long a Tab may wait for the daemon before offering nothing.
+#[cfg(unix)]
+const SYMBOL_LOOKUP_TIMEOUT: std::time::Duration = std::time::Duration::from_millis(500);
+
+/// Print the completion script for `shell` to stdout.
+pub fn print_script(shell: Shell) -> Result<()> {
+    let mut script = Vec::new();
+    clap_complete::generate(shell, &mut Cli::command(), "tyf", &mut script);
+    std::io::stdout().lock().write_all(&script).context("Failed to write completion script")
+}
+
+/// Complete a symbol name argument from the daemon's workspace symbols.
+///
+/// Only asks a daemon that is already running, so pressing Tab never starts
+/// ty. Positions and paths get no candidates. `Class.me` completes members of
+/// `Class`.
+pub fn complete_symbol(current: &OsStr) -> Vec<CompletionCandidate> {
+    let Some(current) = current.to_str() else {
+        return Vec::new();
+    };
+    if current.contains([':', '/', '\\']) {
+        return Vec::new();
+    }
+    let (container, prefix) = match current.rsplit_once('.') {
+        Some((container, member)) => (Some(container), member),
+        None => (None, current),
+    };
+    let mut names: Vec<String> = workspace_symbols(prefix)
+        .into_iter()
+        .filter(|(name, _)| name.starts_with(prefix))
+        .filter_map(|(name, symbol_container)| match container {
+            Some(container) => (symbol_container.as_deref() == Some(container))
+                .then(|| format!("{container}.{name}")),
+            None => Some(name),
+        })
+        .collect();
+    names.sort();
+    names.dedup();
+    names.truncate(MAX_SYMBOL_CANDIDATES);
+    names.into_iter().map(CompletionCandidate::new).collect()
+}
+
+/// `(name, container)` of the symbols matching `query` in the workspace of
+/// the current directory; empty when the daemon isn't running or is slow.
+#[cfg(unix)]
+fn workspace_symbols(query: &str) -> Vec<(String, Option<String>)> {
+    use crate::daemon::client::DaemonClient;
+    use crate::workspace::detection::WorkspaceDetector;
+
+    if query.is_empty() {
+        return Vec::new();
+    }
+    let Ok(cwd) = std::env::current_dir() else {
+        return Vec::new();
+    };
+    let root = WorkspaceDetector::find_workspace_root(&cwd).unwrap_or(cwd);
+    let Ok(runtime) = tokio::runtime::Builder::new_current_thread().enable_all().build() else {
+        return Vec::new();
+    };
+    let lookup = async {
+        let mut client = DaemonClient::connect_with_timeout(SYMBOL_LOOKUP_TIMEOUT).await?;
+        client.execute_workspace_symbols(root, query.to_string(), Vec::new()).await
+    };
+    match runtime.block_on(tokio::time::timeout(SYMBOL_LOOKUP_TIMEOUT, lookup)) {
+        Ok(Ok(result)) => result.symbols.into_iter().map(|s| (s.name, s.container_name)).collect(),
+        _ => Vec::new(),
+    }
+}
+
+#[cfg(not(unix))]
+fn workspace_symbols(_query: &str) -> Vec<(String, Option<String>)> {
+    Vec::new()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_script_covers_subcommands() {
+        let mut script = Vec::new();
+        clap_complete::generate(Shell::Bash, &mut Cli::command(), "tyf", &mut script);
+        let script = String::from_utf8(script).unwrap();
+        assert!(script.contains("check-all"));
+        assert!(script.contains("completions"));
+    }
+
+    #[test]
+    fn test_positions_and_paths_get_no_symbols() {
+        assert!(complete_symbol(OsStr::new("src/app.py:3:5")).is_empty());
+        assert!(complete_symbol(OsStr::new("src/")).is_empty());
+    }
+}
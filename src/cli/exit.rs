@@ -0,0 +1,174 @@
+//! Exit statuses, and the error report printed for `--format json`.
+//!
+//! | Status | Code           | Meaning                                        |
+//! |--------|----------------|------------------------------------------------|
+//! | 0      |                | Success                                        |
+//! | 1      | `no_results`   | The query ran but found nothing                |
+//! | 1      | `failed`       | Any other failure, e.g. `check` found errors   |
+//! | 2      | `usage`        | Invalid arguments                              |
+//! | 3      | `daemon`       | The daemon could not be reached or failed      |
+//! | 4      | `ty_not_found` | ty is not installed                            |
+//! | 130    |                | Interrupted with Ctrl-C                        |
+
+use std::fmt;
+
+#[cfg(unix)]
+use crate::daemon::client::DaemonFailure;
+use crate::lsp::server::TyNotFound;
+
+/// Exit status after Ctrl-C, as shells report a process killed by `SIGINT`.
+pub const INTERRUPTED: i32 = 130;
+
+/// A lookup ran but found nothing.
+///
+/// Commands return this after printing their own "nothing found" output, so
+/// it is not reported again as an error.
+#[derive(Debug)]
+pub struct NoResults;
+
+impl fmt::Display for NoResults {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("No results found")
+    }
+}
+
+impl std::error::Error for NoResults {}
+
+/// Arguments that parse but can't be acted on, e.g. `--watch` with `daemon`.
+#[derive(Debug)]
+pub struct UsageError(pub String);
+
+impl fmt::Display for UsageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for UsageError {}
+
+/// Why a command failed, as far as scripts need to know.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Failure {
+    NoResults,
+    Failed,
+    Usage,
+    Daemon,
+    TyNotFound,
+}
+
+impl Failure {
+    /// Classify `error` by the marker types anywhere in its chain.
+    pub fn of(error: &anyhow::Error) -> Self {
+        let has = |is: fn(&(dyn std::error::Error + 'static)) -> bool| error.chain().any(is);
+        if has(<dyn std::error::Error>::is::<TyNotFound>) {
+            return Self::TyNotFound;
+        }
+        if has(<dyn std::error::Error>::is::<UsageError>) {
+            return Self::Usage;
+        }
+        #[cfg(unix)]
+        if has(<dyn std::error::Error>::is::<DaemonFailure>) {
+            return Self::Daemon;
+        }
+        if has(<dyn std::error::Error>::is::<NoResults>) {
+            return Self::NoResults;
+        }
+        Self::Failed
+    }
+
+    pub const fn exit_code(self) -> i32 {
+        match self {
+            Self::NoResults | Self::Failed => 1,
+            Self::Usage => 2,
+            Self::Daemon => 3,
+            Self::TyNotFound => 4,
+        }
+    }
+
+    /// Name used as `code` in the JSON error report.
+    pub const fn code(self) -> &'static str {
+        match self {
+            Self::NoResults => "no_results",
+            Self::Failed => "failed",
+            Self::Usage => "usage",
+            Self::Daemon => "daemon",
+            Self::TyNotFound => "ty_not_found",
+        }
+    }
+}
+
+/// `{"error": {"code": ..., "message": ...}}` on one line.
+pub fn error_report(failure: Failure, message: &str) -> String {
+    serde_json::json!({ "error": { "code": failure.code(), "message": message } }).to_string()
+}
+
+/// A clap usage error as one paragraph, without clap's `error:` prefix and
+/// `--help` hint.
+pub fn usage_message(error: &clap::Error) -> String {
+    let text = error.render().to_string();
+    let text = text.strip_prefix("error: ").unwrap_or(&text);
+    text.split("\n\n").next().unwrap_or_default().trim_end().to_string()
+}
+
+/// Whether `args` ask for `--format json` or `jsonl`, for errors raised
+/// before they are parsed.
+pub fn wants_json<S: AsRef<str>>(args: &[S]) -> bool {
+    args.iter().zip(args.iter().skip(1).map(Some).chain([None])).any(|(arg, next)| {
+        let value = match arg.as_ref().strip_prefix("--format") {
+            Some("") => next.map(AsRef::as_ref),
+            Some(rest) => rest.strip_prefix('='),
+            None => None,
+        };
+        matches!(value, Some("json" | "jsonl"))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn failures_map_to_exit_codes() {
+        let ty: anyhow::Error = TyNotFound("no ty".to_string()).into();
+        let ty = ty.context("Failed to start ty");
+        assert_eq!(Failure::of(&ty), Failure::TyNotFound);
+        assert_eq!(Failure::of(&ty).exit_code(), 4);
+
+        let usage: anyhow::Error = UsageError("bad".to_string()).into();
+        assert_eq!(Failure::of(&usage).exit_code(), 2);
+
+        let empty: anyhow::Error = NoResults.into();
+        assert_eq!(Failure::of(&empty), Failure::NoResults);
+        assert_eq!(Failure::of(&empty).exit_code(), 1);
+
+        assert_eq!(Failure::of(&anyhow::anyhow!("3 error(s) found")), Failure::Failed);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn daemon_failures_keep_their_message() {
+        let error = DaemonFailure::wrap(anyhow::anyhow!("Request timed out"));
+        let error = error.context("Failed to find references");
+        assert_eq!(Failure::of(&error).exit_code(), 3);
+        let messages: Vec<String> = error.chain().map(ToString::to_string).collect();
+        assert_eq!(messages, ["Failed to find references", "Request timed out"]);
+    }
+
+    #[test]
+    fn error_report_is_one_json_line() {
+        let report = error_report(Failure::TyNotFound, "ty is not available");
+        let value: serde_json::Value = serde_json::from_str(&report).unwrap();
+        assert_eq!(value["error"]["code"], "ty_not_found");
+        assert_eq!(value["error"]["message"], "ty is not available");
+        assert!(!report.contains('\n'));
+    }
+
+    #[test]
+    fn wants_json_reads_both_spellings() {
+        assert!(wants_json(&["tyf", "--format", "json", "find"]));
+        assert!(wants_json(&["tyf", "find", "--format=jsonl"]));
+        assert!(!wants_json(&["tyf", "--format", "csv", "find"]));
+        assert!(!wants_json(&["tyf", "find", "json"]));
+        assert!(!wants_json(&["tyf", "--format"]));
+    }
+}
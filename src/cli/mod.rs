@@ -1,4 +1,10 @@
 pub mod args;
+pub mod completions;
+pub mod exit;
 pub mod generate_docs;
 pub mod output;
+pub mod output_sinks;
+#[cfg(unix)]
+pub mod progress;
 pub mod style;
+pub mod syntax;
@@ -1,60 +1,50 @@
-use crate::cli::args::{OutputDetail, OutputFormat};
+#[cfg(unix)]
+use crate::bench::BenchReport;
+use crate::blame::LineBlame;
+use crate::cli::args::{OutputDetail, OutputFormat, ReferenceGroup, ReferenceSort};
 use crate::cli::style::Styler;
+use crate::cli::syntax::highlight_python;
+use crate::daemon::protocol::{
+    Diagnostic, DiagnosticSeverity, ExplainedIdentifier, GotoEntry, LatencyBucket, MemberInfo,
+    MembersResult, StatsResult, TyInfoResult, WorkspacesResult,
+};
 #[cfg(unix)]
-use crate::daemon::protocol::{MemberInfo, MembersResult};
+use crate::daemon::stats::BUCKET_BOUNDS_MS;
+#[cfg(unix)]
+use crate::doctor::{DoctorReport, Status};
+use crate::lsp::position::{column_to_byte, convert_column, ColumnUnit, DISPLAY_UNIT, LSP_UNIT};
 use crate::lsp::protocol::{
-    DocumentSymbol, Hover, HoverContents, Location, MarkedStringOrString, SymbolInformation,
-    SymbolKind,
+    DocumentHighlight, DocumentHighlightKind, DocumentSymbol, Hover, HoverContents, Location,
+    MarkedStringOrString, Position, Range, SymbolInformation, SymbolKind,
 };
+use crate::lsp::uri::uri_to_path;
+use crate::workspace::navigation::is_identifier_char;
+use crate::workspace::origin::Origin;
+use crate::workspace::page::ResultPage;
+use crate::workspace::paths::PathMapper;
+pub use crate::workspace::source_cache::SourceCache;
+use crate::workspace::source_cache::SourceFile;
+use serde::Serialize;
 use std::collections::HashMap;
 use std::fmt::Write;
 use std::path::{Path, PathBuf};
 
-/// Pre-read file contents for non-blocking source line lookups during formatting.
-///
-/// Built asynchronously (via `tokio::fs`) in command handlers, then passed into
-/// synchronous formatters so they never block the async runtime on file I/O.
-pub struct SourceCache {
-    files: HashMap<String, String>,
-}
-
-impl SourceCache {
-    /// Create an empty cache (for tests that don't need source).
-    #[cfg(test)]
-    pub fn new() -> Self {
-        Self { files: HashMap::new() }
-    }
-
-    /// Asynchronously read all files referenced by the given `file://` URIs.
-    ///
-    /// Deduplicates paths and silently skips files that cannot be read.
-    pub async fn from_uris<'a>(uris: impl IntoIterator<Item = &'a str>) -> Self {
-        let mut paths: Vec<String> = uris
-            .into_iter()
-            .filter_map(|uri| uri.strip_prefix("file://").map(String::from))
-            .collect();
-        paths.sort();
-        paths.dedup();
-
-        let mut files = HashMap::with_capacity(paths.len());
-        for path in paths {
-            if let Ok(content) = tokio::fs::read_to_string(&path).await {
-                files.insert(path, content);
-            }
-        }
-        Self { files }
-    }
-
-    /// Get the full content of a cached file by absolute path.
-    fn get_content(&self, file_path: &str) -> Option<&str> {
-        self.files.get(file_path).map(String::as_str)
-    }
-
-    #[cfg(test)]
-    fn from_entries(entries: impl IntoIterator<Item = (String, String)>) -> Self {
-        Self { files: entries.into_iter().collect() }
-    }
-}
+/// LSP capabilities tyf relies on, with the commands that need each one.
+#[cfg(unix)]
+pub const TYF_PROVIDERS: &[(&str, &str)] = &[
+    ("definitionProvider", "find, show, stubs, context"),
+    ("declarationProvider", "stubs --decl"),
+    ("typeDefinitionProvider", "stubs --type"),
+    ("implementationProvider", "stubs --impl"),
+    ("hoverProvider", "show, members, explain-type, annotate, api"),
+    ("referencesProvider", "refs, show --references"),
+    ("workspaceSymbolProvider", "find, show, refs (by name)"),
+    ("documentSymbolProvider", "list, members, find --file, context"),
+    ("diagnosticProvider", "check"),
+    ("inlayHintProvider", "annotate"),
+    ("documentHighlightProvider", "occurrences"),
+    ("completionProvider", "members (by position)"),
+];
 
 /// A reference location enriched with enclosing symbol context.
 #[derive(Clone, Debug)]
@@ -63,6 +53,21 @@ pub struct EnrichedReference {
     /// Dot-separated path of the tightest enclosing symbol (e.g. "RequestHandler.process"),
     /// or "module scope" if at top level.
     pub context: String,
+    /// The last commit that changed the line (`refs --git`).
+    pub blame: Option<LineBlame>,
+}
+
+/// The whole source of a definition, for `tyf context`.
+#[derive(Clone, Debug)]
+pub struct DefinitionBody {
+    /// Where ty says the symbol is defined
+    pub location: Location,
+    /// 0-based first and last line of the body, decorators included
+    pub lines: (u32, u32),
+    /// Kind of the outline symbol the body is taken from, if there is one
+    pub kind: Option<SymbolKind>,
+    /// The body's lines, as they are in the file
+    pub source: String,
 }
 
 /// A single show result with optional symbol kind.
@@ -99,12 +104,196 @@ pub struct OutputFormatter {
     detail: OutputDetail,
     cwd: PathBuf,
     s: Styler,
+    /// Offset added to 0-based LSP lines/columns for display (1 by default, 0 with `--zero-based`).
+    base: u32,
+    /// Workspace root used to label result origins; `None` disables labels.
+    origin_root: Option<PathBuf>,
+    /// Label workspace results too, not just the ones outside it (`--include-deps`).
+    label_workspace: bool,
+    /// Maps canonical result paths back to the user's (possibly symlinked) spelling.
+    paths: Option<PathMapper>,
+    /// Source lines shown above and below each reference in human output (`refs -C`).
+    context_lines: usize,
+    /// What references are listed under a heading by (`refs --group-by`).
+    reference_groups: Option<ReferenceGroup>,
+    /// The order references were sorted in (`refs --sort`), which orders their groups too.
+    reference_sort: Option<ReferenceSort>,
+    /// Whether locations carry their end and the text they cover (`--with-ranges`).
+    ranges: bool,
+    /// The command being answered, when `--format json` output goes in a
+    /// [`JsonEnvelope`].
+    invocation: Option<Invocation>,
+}
+
+/// Version of the [`JsonEnvelope`] schema, bumped when its shape changes.
+pub const JSON_ENVELOPE_VERSION: u32 = 1;
+
+/// What `--format json` prints for every command: the command and its
+/// arguments, and its results.
+///
+/// ```json
+/// {"version": 1, "command": "refs", "query": {"symbols": ["User"]}, "results": [...]}
+/// ```
+///
+/// `results` is always an array. Commands answering with a single object
+/// (`refs` of one symbol, `doctor`, `daemon stats`) put it in an array of one.
+#[derive(Serialize, Debug, PartialEq, Eq)]
+pub struct JsonEnvelope<'a> {
+    pub version: u32,
+    pub command: &'a str,
+    pub query: &'a serde_json::Map<String, serde_json::Value>,
+    pub results: Vec<serde_json::Value>,
+}
+
+/// A command as it was given on the command line.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Invocation {
+    /// The subcommand, with its parent for nested ones (`daemon stats`)
+    pub command: String,
+    /// The arguments given to it, by long option name, or by name for
+    /// positional ones
+    pub query: serde_json::Map<String, serde_json::Value>,
+}
+
+/// Convert a 0-based LSP position to display coordinates using the given base.
+///
+/// `source` is the content of the position's file; when available the LSP
+/// (UTF-16) column is converted to a character column.
+fn display_position(position: &Position, base: u32, source: Option<&SourceFile>) -> (u32, u32) {
+    let character = source
+        .and_then(|file| file.line(position.line as usize))
+        .map_or(position.character, |line| {
+            convert_column(line, position.character, LSP_UNIT, DISPLAY_UNIT)
+        });
+    (position.line + base, character + base)
+}
+
+/// A byte count in the largest unit that keeps it at or above 1, e.g. `312.4 MB`.
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KB", "MB", "GB"];
+    #[allow(clippy::cast_precision_loss)] // Fine for a displayed size
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{bytes} B")
+    } else {
+        format!("{size:.1} {}", UNITS[unit])
+    }
+}
+
+/// One compact JSON object per line, as `--format jsonl` prints results.
+fn json_lines(records: impl IntoIterator<Item = serde_json::Value>) -> String {
+    records.into_iter().map(|record| record.to_string()).collect::<Vec<_>>().join("\n")
+}
+
+/// `record` with `key` set to `value`; `record` must be a JSON object.
+fn with_field(
+    mut record: serde_json::Value,
+    key: &str,
+    value: impl Into<serde_json::Value>,
+) -> serde_json::Value {
+    record[key] = value.into();
+    record
+}
+
+/// Whether `uri` is a `.pyi` stub file.
+fn is_stub(uri: &str) -> bool {
+    Path::new(&uri_to_path(uri)).extension().is_some_and(|ext| ext.eq_ignore_ascii_case("pyi"))
+}
+
+/// The source text `range` covers, its lines joined by `\n`; `None` when
+/// the range runs past the end of the file.
+fn range_text(file: &SourceFile, range: &Range) -> Option<String> {
+    let mut text = String::new();
+    for n in range.start.line..=range.end.line {
+        let line = file.line(n as usize)?;
+        let start = if n == range.start.line {
+            column_to_byte(line, range.start.character, LSP_UNIT)
+        } else {
+            0
+        };
+        let end = if n == range.end.line {
+            column_to_byte(line, range.end.character, LSP_UNIT)
+        } else {
+            line.len()
+        };
+        if n > range.start.line {
+            text.push('\n');
+        }
+        text.push_str(line.get(start..end.max(start))?);
+    }
+    Some(text)
 }
 
 /// Read a single line of source code from the cache (1-based line number).
-fn read_source_line(cache: &SourceCache, file_path: &str, line: u32) -> Option<String> {
-    let content = cache.get_content(file_path)?;
-    content.lines().nth((line - 1) as usize).map(|s| s.trim().to_string())
+fn read_source_line(cache: &SourceCache, uri: &str, line: u32) -> Option<String> {
+    cache.get_uri_line(uri, line - 1).map(|s| s.trim().to_string())
+}
+
+/// How the name at `range` is used on `line`, for `refs --group-by kind`:
+/// "import", "definition", "call", "annotation", "assignment" or "other".
+///
+/// ty doesn't say, so this guesses from the text around the name.
+fn reference_kind(line: &str, range: &Range) -> &'static str {
+    let byte = |character| {
+        (convert_column(line, character, LSP_UNIT, ColumnUnit::Utf8) as usize).min(line.len())
+    };
+    let start = byte(range.start.character);
+    let end = if range.end.line == range.start.line {
+        byte(range.end.character).max(start)
+    } else {
+        line.len()
+    };
+    let (Some(before), Some(after)) = (line.get(..start), line.get(end..)) else {
+        return "other";
+    };
+    let (before, after) = (before.trim(), after.trim_start());
+    let statement = line.trim_start();
+    if statement.starts_with("import ") || statement.starts_with("from ") {
+        "import"
+    } else if matches!(before, "def" | "async def" | "class") {
+        "definition"
+    } else if after.starts_with('(') {
+        "call"
+    } else if before.ends_with("->")
+        || before
+            .strip_suffix(':')
+            .and_then(|b| b.trim_end().chars().last())
+            .is_some_and(is_identifier_char)
+    {
+        "annotation"
+    } else if is_assignment(after) {
+        "assignment"
+    } else {
+        "other"
+    }
+}
+
+/// Whether `rest`, the text after a name, assigns to it (`=`, `+=`, `:=`, ...).
+fn is_assignment(rest: &str) -> bool {
+    let Some((op, _)) = rest.split_once('=') else { return false };
+    !rest.starts_with("==")
+        && matches!(
+            op,
+            "" | ":"
+                | "+"
+                | "-"
+                | "*"
+                | "/"
+                | "//"
+                | "%"
+                | "**"
+                | "@"
+                | "&"
+                | "|"
+                | "^"
+                | ">>"
+                | "<<"
+        )
 }
 
 /// Context around a definition: decorator lines and the keyword line.
@@ -141,15 +330,14 @@ fn read_definition_context(
     file_path: &str,
     start_line_0: u32,
 ) -> Option<DefinitionContext> {
-    let content = cache.get_content(file_path)?;
-    let lines: Vec<&str> = content.lines().collect();
+    let file = cache.get(file_path)?;
     let start = start_line_0 as usize;
 
     // Find the keyword line by scanning forward past decorators.
     // Multi-line decorators like `@foo(\n  ...\n)` are tracked via paren depth.
     let mut def_idx = None;
     let mut paren_depth: i32 = 0;
-    for (i, line) in lines.iter().enumerate().skip(start) {
+    for (i, line) in file.lines_from(start) {
         let trimmed = line.trim();
         if paren_depth > 0 {
             // Inside a multi-line decorator — keep scanning.
@@ -164,7 +352,7 @@ fn read_definition_context(
         break;
     }
     let def_idx = def_idx?;
-    let definition_line = lines[def_idx].trim().to_string();
+    let definition_line = file.line(def_idx)?.trim().to_string();
 
     // Scan backwards from the keyword line to collect all decorator lines.
     // Multi-line decorators: when we hit a `)` first, track paren depth
@@ -174,7 +362,7 @@ fn read_definition_context(
     let mut paren_depth: i32 = 0;
     while idx > 0 {
         idx -= 1;
-        let trimmed = lines[idx].trim();
+        let trimmed = file.line(idx)?.trim();
         if paren_depth < 0 {
             // Inside a multi-line decorator, scanning upwards.
             decorator_lines.push(trimmed);
@@ -235,6 +423,431 @@ pub struct EnrichedReferencesResult {
     pub remaining_count: usize,
     /// Test references shown separately (None = no test refs exist).
     pub test_references: Option<TestReferencesSection>,
+    /// References dropped before enrichment because of `--max-results`.
+    pub omitted_count: usize,
+}
+
+/// Reference counts of one query, for `refs --count`.
+pub struct ReferenceCount {
+    /// Symbol name or query label.
+    pub label: String,
+    /// File URIs with their number of references, most first.
+    pub files: Vec<(String, usize)>,
+    /// References in test files that were left out (without `--tests`).
+    pub hidden_tests: usize,
+}
+
+impl ReferenceCount {
+    /// References over all files.
+    pub fn total(&self) -> usize {
+        self.files.iter().map(|(_, count)| count).sum()
+    }
+}
+
+/// One user-defined type expanded by `explain-type`.
+pub struct TypeExpansion {
+    /// Type name as referenced in the signature (e.g. `OrderPayload`).
+    pub name: String,
+    /// Short description: `TypedDict`, `Protocol`, `class`, `type alias`, ...
+    pub kind: &'static str,
+    /// Where the type is defined.
+    pub location: Location,
+    /// Hops from the queried symbol (1 = referenced directly by its signature).
+    pub depth: usize,
+    /// Source of the definition: a class header (e.g. `class Order(TypedDict):`)
+    /// or a whole statement, possibly over several lines.
+    pub definition: String,
+    /// Fields/members with type signatures (classes only).
+    pub members: Vec<String>,
+}
+
+/// Result of an `explain-type` query: the symbol's signature plus expanded types.
+pub struct ExplainTypeResult {
+    /// The query as typed by the user (symbol name or `file:line:col`).
+    pub query: String,
+    /// Type signature from hover, if any.
+    pub signature: Option<String>,
+    /// Referenced types in breadth-first order.
+    pub expansions: Vec<TypeExpansion>,
+}
+
+/// Result of one `hover` query.
+pub struct HoverEntry {
+    /// The query as typed by the user, or a per-definition label when a name
+    /// has several definitions.
+    pub label: String,
+    /// Where hover was requested: the symbol's name, or the given position.
+    pub location: Option<Location>,
+    pub hover: Option<Hover>,
+}
+
+/// Result of one `occurrences` query.
+pub struct OccurrencesEntry {
+    /// The query as typed by the user.
+    pub label: String,
+    /// `file://` URI of the file searched; `None` when a name wasn't found.
+    pub uri: Option<String>,
+    pub highlights: Vec<DocumentHighlight>,
+}
+
+impl OccurrencesEntry {
+    /// Each occurrence as a location, with `read`, `write` or `text` (kind unknown).
+    fn occurrences(&self) -> Vec<(Location, &'static str)> {
+        let uri = self.uri.clone().unwrap_or_default();
+        self.highlights
+            .iter()
+            .map(|highlight| {
+                let kind = match highlight.kind {
+                    Some(DocumentHighlightKind::Read) => "read",
+                    Some(DocumentHighlightKind::Write) => "write",
+                    Some(DocumentHighlightKind::Text) | None => "text",
+                };
+                (Location { uri: uri.clone(), range: highlight.range.clone() }, kind)
+            })
+            .collect()
+    }
+}
+
+/// Result of a `grep` query.
+#[cfg(unix)]
+pub struct GrepResult {
+    pub pattern: String,
+    /// The `--resolves-to` target as typed by the user.
+    pub target: String,
+    /// Text matches found before checking what they resolve to.
+    pub text_matches: usize,
+    /// Matches that resolve to the target, at the identifier that did.
+    pub hits: Vec<Location>,
+}
+
+/// Result of a `sig-check` query.
+#[cfg(unix)]
+pub struct SigCheckResult {
+    pub symbol: String,
+    /// Signature reported by ty's hover, if any.
+    pub current_signature: Option<String>,
+    pub new_signature: String,
+    /// Number of references that are calls.
+    pub call_sites: usize,
+    pub breaking: Vec<BrokenCall>,
+    /// Calls whose outcome depends on unpacked `*args` or `**kwargs`.
+    pub unchecked: Vec<Location>,
+}
+
+/// A call site that would not bind to the proposed signature.
+#[cfg(unix)]
+pub struct BrokenCall {
+    pub location: Location,
+    pub problems: Vec<String>,
+}
+
+/// Result of an `annotate` query.
+#[cfg(unix)]
+pub struct AnnotateResult {
+    pub uri: String,
+    /// Annotations sorted by position.
+    pub annotations: Vec<crate::annotate::Annotation>,
+}
+
+/// One name in a `where` chain and where it is defined.
+#[cfg(unix)]
+pub struct WhereHop {
+    pub name: String,
+    /// Class the attribute is defined in: the receiver's class or one of its
+    /// bases. `None` for the first name in the chain.
+    pub owner: Option<String>,
+    pub location: Option<Location>,
+    /// Type from hover on the definition.
+    pub ty: Option<String>,
+}
+
+/// Result of a `where` query.
+#[cfg(unix)]
+pub struct WhereResult {
+    pub expression: String,
+    /// The hops resolved so far, starting with the chain's first name.
+    pub hops: Vec<WhereHop>,
+    /// Why the chain could not be followed further, if it stopped early.
+    pub unresolved: Option<String>,
+}
+
+/// A file `move` changes, with its text before the change.
+#[cfg(unix)]
+pub struct MovedFile {
+    pub path: PathBuf,
+    pub text: String,
+    pub edits: Vec<crate::refactor::edit::LineEdit>,
+}
+
+/// Result of a `move` (or `moved`): the module's old and new name and the
+/// files it touches.
+#[cfg(unix)]
+pub struct MoveResult {
+    pub old_path: PathBuf,
+    pub new_path: PathBuf,
+    pub change: crate::refactor::imports::ModuleMove,
+    /// Files whose imports change; the moved file is listed by its old path,
+    /// or by its new one when it was already `moved`.
+    pub files: Vec<MovedFile>,
+    /// Import statements rewritten across all files.
+    pub imports: usize,
+    /// Imports left alone because ty resolved them to another module.
+    pub skipped: usize,
+    /// Whether the changes were written, or only previewed.
+    pub applied: bool,
+    /// Whether the file was already at its new path (`tyf moved`), so only
+    /// imports change.
+    pub moved: bool,
+}
+
+/// Result of `explain`: what ty says about one line of a file.
+pub struct LineExplanation {
+    pub uri: String,
+    /// The line explained (0-based).
+    pub line: u32,
+    /// Every identifier on the line, in source order.
+    pub identifiers: Vec<ExplainedIdentifier>,
+    /// Diagnostics whose range includes the line.
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+/// Result of `rdeps`: the files that reference anything a file defines.
+#[cfg(unix)]
+pub struct RdepsResult {
+    pub uri: String,
+    /// Symbols of the file whose references were looked up.
+    pub symbols_checked: usize,
+    /// Referencing files, sorted by path.
+    pub files: Vec<DependentFile>,
+}
+
+/// A file that references symbols defined in the `rdeps` target.
+#[cfg(unix)]
+pub struct DependentFile {
+    pub uri: String,
+    /// The target's symbols it references, as `name` or `Class.name`.
+    pub symbols: Vec<String>,
+    /// Number of references, over all those symbols.
+    pub references: usize,
+}
+
+/// Result of `api`: the public classes and functions under a path.
+#[cfg(unix)]
+pub struct ApiResult {
+    /// The package or module documented, as a path relative to the workspace.
+    pub target: String,
+    /// Public modules with public symbols, by module name.
+    pub modules: Vec<ApiModule>,
+}
+
+/// A public module and what it defines, for `api`.
+#[cfg(unix)]
+pub struct ApiModule {
+    /// Dotted module name.
+    pub module: String,
+    pub uri: String,
+    pub symbols: Vec<ApiItem>,
+}
+
+/// A public class, function, method or property, with ty's hover for it.
+#[cfg(unix)]
+pub struct ApiItem {
+    pub name: String,
+    pub kind: SymbolKind,
+    /// Where the name is declared.
+    pub location: Location,
+    pub hover: Option<Hover>,
+    /// The public methods and properties of a class.
+    pub members: Vec<Self>,
+}
+
+/// Result of `imports`: a module's place in the workspace import graph.
+#[cfg(unix)]
+pub struct ImportsResult {
+    pub module: String,
+    pub path: PathBuf,
+    /// Workspace modules the module imports.
+    pub imports: Vec<ImportEdge>,
+    /// Modules outside the workspace it imports.
+    pub external: Vec<ImportEdge>,
+    /// Workspace modules that import it.
+    pub imported_by: Vec<ImportEdge>,
+}
+
+/// One import between two modules.
+#[cfg(unix)]
+pub struct ImportEdge {
+    /// The module at the other end of the import.
+    pub module: String,
+    /// Its file; for external modules, where ty resolved the import, if anywhere.
+    pub path: Option<PathBuf>,
+    /// 0-based line of the import statement, in the importing file.
+    pub line: usize,
+}
+
+/// Result of a `check` run.
+#[cfg(unix)]
+#[derive(Default)]
+pub struct CheckResult {
+    /// Diagnostics to report, per file, in the order the files were checked.
+    pub files: Vec<FileDiagnostics>,
+    pub files_checked: usize,
+    /// Diagnostics left out because the baseline records them.
+    pub baselined: usize,
+    /// Baselined diagnostics that no longer occur in the checked files.
+    pub fixed: usize,
+    /// Diagnostics left out by `--severity`.
+    pub below_severity: usize,
+}
+
+/// Diagnostics of one file.
+#[cfg(unix)]
+pub struct FileDiagnostics {
+    pub uri: String,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+#[cfg(unix)]
+impl CheckResult {
+    /// Number of reported diagnostics with the given severity.
+    pub fn count(&self, severity: DiagnosticSeverity) -> usize {
+        self.files
+            .iter()
+            .flat_map(|file| &file.diagnostics)
+            .filter(|diagnostic| diagnostic.severity == severity)
+            .count()
+    }
+}
+
+/// Result of a `check-all` run.
+#[cfg(unix)]
+pub struct CheckAllResult {
+    /// Files with at least one diagnostic.
+    pub files: Vec<FileDiagnostics>,
+    pub files_checked: usize,
+    /// Files the daemon couldn't check, with the reason.
+    pub failed: Vec<(String, String)>,
+}
+
+/// Result of an `unused` run.
+#[cfg(unix)]
+pub struct UnusedResult {
+    /// Symbols nothing references, in file order.
+    pub symbols: Vec<UnusedSymbol>,
+    pub files_scanned: usize,
+    pub symbols_checked: usize,
+    /// Candidates ty could not resolve, so left unreported.
+    pub unresolved: usize,
+}
+
+/// A symbol `unused` found no reference to.
+#[cfg(unix)]
+pub struct UnusedSymbol {
+    /// `name`, or `Class.name` for members.
+    pub name: String,
+    pub kind: SymbolKind,
+    /// Where the name is declared.
+    pub location: Location,
+}
+
+/// Result of a `stats` run.
+#[cfg(unix)]
+pub struct CodeStatsResult {
+    /// Every file that was outlined, by path.
+    pub files: Vec<FileStats>,
+    /// Files the daemon couldn't outline, with the reason.
+    pub failed: Vec<(String, String)>,
+}
+
+/// Line and symbol counts of one `stats` file.
+#[cfg(unix)]
+pub struct FileStats {
+    pub uri: String,
+    pub lines: usize,
+    pub counts: crate::code_stats::SymbolCounts,
+}
+
+#[cfg(unix)]
+impl CodeStatsResult {
+    /// Lines and symbols of all files together.
+    pub fn total(&self) -> (usize, crate::code_stats::SymbolCounts) {
+        self.files.iter().fold(Default::default(), |(lines, mut counts), file| {
+            counts += file.counts;
+            (lines + file.lines, counts)
+        })
+    }
+}
+
+/// The settings in effect, for `config show`.
+#[derive(serde::Serialize)]
+pub struct ConfigReport {
+    pub settings: Vec<ConfigSetting>,
+    /// Config files looked for, most specific last.
+    pub files: Vec<ConfigFile>,
+}
+
+/// One setting's effective value.
+#[derive(serde::Serialize)]
+pub struct ConfigSetting {
+    /// The key, or `aliases.<name>` for an alias.
+    pub key: String,
+    pub value: serde_json::Value,
+    /// Where the value was set, or `default`.
+    pub source: String,
+}
+
+/// A saved alias, for `tyf alias list`.
+#[derive(serde::Serialize)]
+pub struct AliasEntry {
+    pub name: String,
+    pub command: String,
+    /// The config file that defines it.
+    pub source: String,
+}
+
+/// A config file `config show` looked for.
+#[derive(serde::Serialize)]
+pub struct ConfigFile {
+    pub path: String,
+    /// `read`, `not found`, or `no [tool.ty-find]` for a `pyproject.toml`
+    /// without tyf settings.
+    pub status: &'static str,
+}
+
+/// Diagnostic counts of one `check-all` file.
+#[cfg(unix)]
+struct FileCounts<'a> {
+    uri: &'a str,
+    errors: usize,
+    warnings: usize,
+    notes: usize,
+}
+
+#[cfg(unix)]
+impl CheckAllResult {
+    /// Per-file counts, most errors first, then most warnings, then by path.
+    fn counts(&self) -> Vec<FileCounts<'_>> {
+        let mut counts: Vec<FileCounts<'_>> = self
+            .files
+            .iter()
+            .map(|file| {
+                let count =
+                    |severity| file.diagnostics.iter().filter(|d| d.severity == severity).count();
+                let errors = count(DiagnosticSeverity::Error);
+                let warnings = count(DiagnosticSeverity::Warning);
+                FileCounts {
+                    uri: &file.uri,
+                    errors,
+                    warnings,
+                    notes: file.diagnostics.len() - errors - warnings,
+                }
+            })
+            .collect();
+        counts.sort_by(|a, b| {
+            b.errors.cmp(&a.errors).then(b.warnings.cmp(&a.warnings)).then(a.uri.cmp(b.uri))
+        });
+        counts
+    }
 }
 
 /// Check whether a position (line, character) is inside a range (inclusive).
@@ -287,6 +900,28 @@ fn find_enclosing_recursive(
     }
 }
 
+/// The outline symbol defined at a position: the deepest one whose header,
+/// from the start of its range (decorators, `def`) to the end of its name,
+/// contains it. `None` when the position is inside a body instead, like a
+/// local variable's.
+pub fn find_defined_symbol(
+    symbols: &[DocumentSymbol],
+    line: u32,
+    character: u32,
+) -> Option<&DocumentSymbol> {
+    symbols.iter().find_map(|symbol| {
+        let header =
+            Range { start: symbol.range.start.clone(), end: symbol.selection_range.end.clone() };
+        if position_in_range(&header, line, character) {
+            Some(symbol)
+        } else if position_in_range(&symbol.range, line, character) {
+            find_defined_symbol(symbol.children.as_deref().unwrap_or_default(), line, character)
+        } else {
+            None
+        }
+    })
+}
+
 /// Strip markdown code fences (`` ```lang `` / `` ``` ``) leaving only content.
 fn strip_code_fences(text: &str) -> String {
     let mut lines: Vec<&str> = Vec::new();
@@ -299,6 +934,46 @@ fn strip_code_fences(text: &str) -> String {
     lines.join("\n")
 }
 
+/// Render hover markdown for the terminal.
+///
+/// ty turns docstrings into markdown: code blocks are fenced, indentation is
+/// kept with `&nbsp;`, and punctuation is backslash-escaped. Fences become a
+/// four-space indent, headings are styled, and `**bold**` markers, escapes
+/// and entities are dropped. Text inside code blocks is left as is.
+fn render_markdown(text: &str, s: Styler) -> String {
+    let mut lines: Vec<String> = Vec::new();
+    let mut in_code = false;
+    for line in text.lines() {
+        if line.trim_start().starts_with("```") {
+            in_code = !in_code;
+            continue;
+        }
+        if in_code {
+            lines.push(format!("    {line}"));
+            continue;
+        }
+        let line = line.replace("&nbsp;", " ").replace("**", "");
+        let mut plain = String::with_capacity(line.len());
+        let mut chars = line.chars().peekable();
+        while let Some(c) = chars.next() {
+            match chars.peek() {
+                Some(&next) if c == '\\' && next.is_ascii_punctuation() => {
+                    plain.push(next);
+                    chars.next();
+                }
+                _ => plain.push(c),
+            }
+        }
+        let heading = plain.trim_start_matches('#');
+        if heading.len() < plain.len() && heading.starts_with(' ') {
+            lines.push(s.heading(heading.trim()));
+        } else {
+            lines.push(plain);
+        }
+    }
+    lines.join("\n")
+}
+
 impl OutputFormatter {
     #[cfg(test)]
     pub fn new(format: OutputFormat) -> Self {
@@ -320,68 +995,268 @@ impl OutputFormatter {
             detail,
             cwd: std::env::current_dir().unwrap_or_else(|_| PathBuf::from("/")),
             s,
+            base: 1,
+            origin_root: None,
+            label_workspace: false,
+            paths: None,
+            context_lines: 0,
+            reference_groups: None,
+            reference_sort: None,
+            ranges: false,
+            invocation: None,
         }
     }
 
-    /// Access the styler (used for error formatting from main).
-    pub fn styler(&self) -> Styler {
-        self.s
+    /// Wrap `--format json` output in a [`JsonEnvelope`] about `invocation`;
+    /// `None` prints results as they are.
+    pub fn with_envelope(mut self, invocation: Option<Invocation>) -> Self {
+        self.invocation = invocation;
+        self
     }
 
-    pub fn format_definitions(
-        &self,
-        locations: &[Location],
-        query_info: &str,
-        cache: &SourceCache,
-    ) -> String {
-        match self.format {
-            OutputFormat::Human => self.format_human(locations, query_info, cache),
-            OutputFormat::Json => Self::format_json(locations),
-            OutputFormat::Csv => self.format_csv(locations),
-            OutputFormat::Paths => self.format_paths(locations),
-        }
+    /// `value` as pretty-printed JSON output, in the envelope when there is one.
+    fn json(&self, value: &(impl Serialize + ?Sized)) -> String {
+        let Some(invocation) = &self.invocation else {
+            return serde_json::to_string_pretty(value).unwrap_or_else(|_| "null".to_string());
+        };
+        let results = match serde_json::to_value(value).unwrap_or_default() {
+            serde_json::Value::Array(results) => results,
+            serde_json::Value::Null => Vec::new(),
+            result => vec![result],
+        };
+        let envelope = JsonEnvelope {
+            version: JSON_ENVELOPE_VERSION,
+            command: &invocation.command,
+            query: &invocation.query,
+            results,
+        };
+        serde_json::to_string_pretty(&envelope).unwrap_or_else(|_| "null".to_string())
     }
 
-    fn format_human(
-        &self,
-        locations: &[Location],
-        query_info: &str,
-        cache: &SourceCache,
-    ) -> String {
-        if locations.is_empty() {
-            return self.s.error(&format!("No results found for: {query_info}"));
-        }
-
-        let mut output = format!("Found {} definition(s) for: {query_info}\n\n", locations.len());
-
-        for (i, location) in locations.iter().enumerate() {
-            let file_path = self.uri_to_path(&location.uri);
-            let line = location.range.start.line + 1;
-            let column = location.range.start.character + 1;
+    /// Label results outside `workspace_root` by origin (site-packages / stdlib),
+    /// and with `all` the workspace ones as well.
+    pub fn with_origin_labels(mut self, workspace_root: PathBuf, all: bool) -> Self {
+        self.origin_root = Some(workspace_root);
+        self.label_workspace = all;
+        self
+    }
 
-            let _ =
-                writeln!(output, "{}. {}", i + 1, self.s.file_location(&file_path, line, column));
+    /// Origin label for a URI, when origin labelling is enabled.
+    fn origin(&self, uri: &str) -> Option<Origin> {
+        let origin = Origin::classify(uri, self.origin_root.as_deref()?);
+        (self.label_workspace || origin != Origin::Workspace).then_some(origin)
+    }
 
-            if let Some(src) = read_source_line(cache, &file_path, line) {
-                let _ = writeln!(output, "   {src}");
+    /// Human-mode suffix like ` [site-packages]` for results outside the workspace.
+    fn origin_tag(&self, uri: &str) -> String {
+        match self.origin(uri) {
+            Some(origin) if origin != Origin::Workspace => {
+                format!(" {}", self.s.dim(&format!("[{}]", origin.as_str())))
             }
-            output.push('\n');
+            _ => String::new(),
         }
+    }
 
-        output
+    /// Show paths the way the user spelled the workspace root, relative to `cwd`.
+    ///
+    /// `cwd` should be the logical working directory (see
+    /// [`crate::workspace::paths::logical_cwd`]) so symlinked checkouts still
+    /// get relative paths.
+    pub fn with_path_mapper(mut self, mapper: PathMapper, cwd: PathBuf) -> Self {
+        self.paths = Some(mapper);
+        self.cwd = cwd;
+        self
+    }
+
+    /// Absolute path behind a URI, in the user's spelling.
+    fn abs_path(&self, uri: &str) -> String {
+        match &self.paths {
+            Some(mapper) => mapper.uri_to_user_path(uri),
+            None => uri_to_path(uri),
+        }
     }
 
-    fn format_json(locations: &[Location]) -> String {
-        serde_json::to_string_pretty(locations).unwrap_or_else(|_| "[]".to_string())
+    /// Report line/column numbers 0-based (LSP convention) instead of 1-based.
+    pub fn with_zero_based(mut self, zero_based: bool) -> Self {
+        self.base = u32::from(!zero_based);
+        self
     }
 
-    fn format_csv(&self, locations: &[Location]) -> String {
-        let mut output = String::from("file,line,column\n");
-        for location in locations {
+    /// Show `n` lines of source around each reference instead of just its line.
+    pub fn with_context_lines(mut self, n: usize) -> Self {
+        self.context_lines = n;
+        self
+    }
+
+    /// List references under a heading per `group`, for `refs --group-by`.
+    /// Groups come in the order of their first reference, or largest first
+    /// when `sort` is [`ReferenceSort::Count`].
+    pub fn with_reference_groups(
+        mut self,
+        group: Option<ReferenceGroup>,
+        sort: Option<ReferenceSort>,
+    ) -> Self {
+        self.reference_groups = group;
+        self.reference_sort = sort;
+        self
+    }
+
+    /// Give each location in JSON and CSV output its end and the text it
+    /// covers, not just its start.
+    pub fn with_ranges(mut self, ranges: bool) -> Self {
+        self.ranges = ranges;
+        self
+    }
+
+    /// `record` plus the `end_line`, `end_column` and `matched` text of
+    /// `location` with `--with-ranges`; unchanged otherwise.
+    fn with_range(
+        &self,
+        mut record: serde_json::Value,
+        location: &Location,
+        cache: &SourceCache,
+    ) -> serde_json::Value {
+        if !self.ranges {
+            return record;
+        }
+        let file = cache.get_uri(&location.uri);
+        let (line, column) = display_position(&location.range.end, self.base, file);
+        record["end_line"] = serde_json::Value::from(line);
+        record["end_column"] = serde_json::Value::from(column);
+        record["matched"] =
+            serde_json::Value::from(file.and_then(|f| range_text(f, &location.range)));
+        record
+    }
+
+    /// The CSV columns [`Self::csv_range`] adds, after a header's others.
+    const fn csv_range_header(&self) -> &'static str {
+        if self.ranges {
+            ",end_line,end_column,matched"
+        } else {
+            ""
+        }
+    }
+
+    /// The end and quoted `matched` text of `location` as trailing CSV
+    /// columns with `--with-ranges`; empty otherwise.
+    fn csv_range(&self, location: &Location, cache: &SourceCache) -> String {
+        if !self.ranges {
+            return String::new();
+        }
+        let file = cache.get_uri(&location.uri);
+        let (line, column) = display_position(&location.range.end, self.base, file);
+        let matched = file.and_then(|f| range_text(f, &location.range)).unwrap_or_default();
+        format!(",{line},{column},\"{}\"", matched.replace('"', "\"\""))
+    }
+
+    /// Locations as JSON, as ty sent them, each with [`Self::with_range`].
+    fn locations_json(&self, locations: &[Location], cache: &SourceCache) -> serde_json::Value {
+        locations
+            .iter()
+            .map(|location| {
+                let record = serde_json::to_value(location).unwrap_or_default();
+                self.with_range(record, location, cache)
+            })
+            .collect()
+    }
+
+    /// Display coordinates for the start of an LSP location.
+    ///
+    /// The column is converted from LSP units to characters using the cached
+    /// source line; files that aren't cached keep the raw LSP column.
+    fn position(&self, cache: &SourceCache, location: &Location) -> (u32, u32) {
+        display_position(&location.range.start, self.base, cache.get_uri(&location.uri))
+    }
+
+    /// Access the styler (used for error formatting from main).
+    pub fn styler(&self) -> Styler {
+        self.s
+    }
+
+    /// Whether results are printed as JSON lines, which commands may stream
+    /// as they arrive instead of printing everything at the end.
+    #[cfg_attr(not(unix), allow(dead_code))]
+    pub fn is_jsonl(&self) -> bool {
+        self.format == OutputFormat::Jsonl
+    }
+
+    /// A location as a JSON lines record: `file`, `line` and `column`, plus
+    /// `origin` when results are labelled by origin.
+    fn location_record(&self, location: &Location, cache: &SourceCache) -> serde_json::Value {
+        let (line, column) = self.position(cache, location);
+        let mut record = serde_json::json!({
+            "file": self.abs_path(&location.uri),
+            "line": line,
+            "column": column,
+        });
+        if let Some(origin) = self.origin(&location.uri) {
+            record["origin"] = serde_json::Value::from(origin.as_str());
+        }
+        record
+    }
+
+    pub fn format_definitions(
+        &self,
+        locations: &[Location],
+        query_info: &str,
+        cache: &SourceCache,
+    ) -> String {
+        match self.format {
+            OutputFormat::Human | OutputFormat::Dot => {
+                self.format_human(locations, query_info, cache)
+            }
+            OutputFormat::Json => self.json(&self.locations_json(locations, cache)),
+            OutputFormat::Jsonl => json_lines(
+                locations
+                    .iter()
+                    .map(|loc| self.with_range(self.location_record(loc, cache), loc, cache)),
+            ),
+            OutputFormat::Csv => self.format_csv(locations, cache),
+            OutputFormat::Paths => self.format_paths(locations),
+        }
+    }
+
+    fn format_human(
+        &self,
+        locations: &[Location],
+        query_info: &str,
+        cache: &SourceCache,
+    ) -> String {
+        if locations.is_empty() {
+            return self.s.error(&format!("No results found for: {query_info}"));
+        }
+
+        let mut output = format!("Found {} definition(s) for: {query_info}\n\n", locations.len());
+
+        for (i, location) in locations.iter().enumerate() {
+            let file_path = self.uri_to_path(&location.uri);
+            let (line, column) = self.position(cache, location);
+
+            let _ = writeln!(
+                output,
+                "{}. {}{}",
+                i + 1,
+                self.s.file_location(&file_path, line, column),
+                self.origin_tag(&location.uri),
+            );
+
+            if let Some(src) = read_source_line(cache, &location.uri, location.range.start.line + 1)
+            {
+                let _ = writeln!(output, "   {src}");
+            }
+            output.push('\n');
+        }
+
+        output
+    }
+
+    fn format_csv(&self, locations: &[Location], cache: &SourceCache) -> String {
+        let mut output = format!("file,line,column{}\n", self.csv_range_header());
+        for location in locations {
             let file_path = self.uri_to_path(&location.uri);
-            let line = location.range.start.line + 1;
-            let column = location.range.start.character + 1;
-            let _ = writeln!(output, "{file_path},{line},{column}");
+            let (line, column) = self.position(cache, location);
+            let range = self.csv_range(location, cache);
+            let _ = writeln!(output, "{file_path},{line},{column}{range}");
         }
         output
     }
@@ -391,11 +1266,10 @@ impl OutputFormatter {
     }
 
     fn uri_to_path(&self, uri: &str) -> String {
-        let abs_path = if let Some(stripped) = uri.strip_prefix("file://") {
-            stripped.to_string()
-        } else {
+        if !uri.starts_with("file://") {
             return uri.to_string();
-        };
+        }
+        let abs_path = self.abs_path(uri);
 
         // Try to make path relative to cwd
         let path = Path::new(&abs_path);
@@ -421,7 +1295,7 @@ impl OutputFormatter {
         }
 
         match self.format {
-            OutputFormat::Human => {
+            OutputFormat::Human | OutputFormat::Dot => {
                 let mut output = String::new();
                 for (symbol, locations) in results {
                     if locations.is_empty() {
@@ -450,20 +1324,26 @@ impl OutputFormatter {
                     .map(|(symbol, locations)| {
                         serde_json::json!({
                             "symbol": symbol,
-                            "definitions": locations,
+                            "definitions": self.locations_json(locations, cache),
                         })
                     })
                     .collect();
-                serde_json::to_string_pretty(&grouped).unwrap_or_else(|_| "[]".to_string())
+                self.json(&grouped)
             }
+            OutputFormat::Jsonl => json_lines(results.iter().flat_map(|(symbol, locations)| {
+                locations.iter().map(|loc| {
+                    let record = self.with_range(self.location_record(loc, cache), loc, cache);
+                    with_field(record, "symbol", symbol.as_str())
+                })
+            })),
             OutputFormat::Csv => {
-                let mut output = String::from("symbol,file,line,column\n");
+                let mut output = format!("symbol,file,line,column{}\n", self.csv_range_header());
                 for (symbol, locations) in results {
                     for location in locations {
                         let file_path = self.uri_to_path(&location.uri);
-                        let line = location.range.start.line + 1;
-                        let column = location.range.start.character + 1;
-                        let _ = writeln!(output, "{symbol},{file_path},{line},{column}");
+                        let (line, column) = self.position(cache, location);
+                        let range = self.csv_range(location, cache);
+                        let _ = writeln!(output, "{symbol},{file_path},{line},{column}{range}");
                     }
                 }
                 output
@@ -482,58 +1362,277 @@ impl OutputFormatter {
         }
     }
 
+    /// Format the locations `stubs` found, one group per query; locations in
+    /// `.pyi` stubs are marked.
+    /// Format the definition bodies of each query, for `tyf context`: in
+    /// human output, the source under a `# symbol file:first-last` heading,
+    /// colored when color is on.
+    pub fn format_definition_bodies(
+        &self,
+        results: &[(String, Vec<DefinitionBody>)],
+        cache: &SourceCache,
+    ) -> String {
+        let bodies =
+            results.iter().flat_map(|(label, bodies)| bodies.iter().map(move |b| (label, b)));
+        let record = |label: &str, body: &DefinitionBody| {
+            let mut record = self.location_record(&body.location, cache);
+            record["start_line"] = serde_json::Value::from(body.lines.0 + self.base);
+            record["end_line"] = serde_json::Value::from(body.lines.1 + self.base);
+            if let Some(kind) = &body.kind {
+                record["kind"] = serde_json::Value::from(format!("{kind:?}"));
+            }
+            record["body"] = serde_json::Value::from(body.source.as_str());
+            with_field(record, "symbol", label)
+        };
+        match self.format {
+            OutputFormat::Human | OutputFormat::Dot => {
+                let mut output = String::new();
+                for (label, bodies) in results {
+                    if bodies.is_empty() {
+                        let message = format!("No results found for: '{label}'");
+                        let _ = writeln!(output, "{}\n", self.s.error(&message));
+                    }
+                    for body in bodies {
+                        let (first, last) = (body.lines.0 + self.base, body.lines.1 + self.base);
+                        let place =
+                            format!("{}:{first}-{last}", self.uri_to_path(&body.location.uri));
+                        let _ = writeln!(
+                            output,
+                            "# {} {}{}",
+                            self.s.symbol(label),
+                            self.s.dim(&place),
+                            self.origin_tag(&body.location.uri)
+                        );
+                        let _ = writeln!(output, "{}\n", highlight_python(&body.source, self.s));
+                    }
+                }
+                output.trim_end().to_string()
+            }
+            OutputFormat::Json => {
+                let records: Vec<serde_json::Value> =
+                    bodies.map(|(label, body)| record(label, body)).collect();
+                self.json(&records)
+            }
+            OutputFormat::Jsonl => json_lines(bodies.map(|(label, body)| record(label, body))),
+            OutputFormat::Csv => {
+                let mut output = String::from("symbol,file,start_line,end_line\n");
+                for (label, body) in bodies {
+                    let _ = writeln!(
+                        output,
+                        "{label},{},{},{}",
+                        self.uri_to_path(&body.location.uri),
+                        body.lines.0 + self.base,
+                        body.lines.1 + self.base
+                    );
+                }
+                output
+            }
+            OutputFormat::Paths => {
+                let mut paths: Vec<String> =
+                    bodies.map(|(_, body)| self.uri_to_path(&body.location.uri)).collect();
+                paths.sort();
+                paths.dedup();
+                paths.join("\n")
+            }
+        }
+    }
+
+    pub fn format_goto(&self, entries: &[GotoEntry], cache: &SourceCache) -> String {
+        let record = |entry: &GotoEntry, location: &Location| {
+            let record =
+                with_field(self.location_record(location, cache), "stub", is_stub(&location.uri));
+            with_field(with_field(record, "kind", entry.kind.key()), "symbol", entry.label.as_str())
+        };
+        let mut labels: Vec<&str> = Vec::new();
+        for entry in entries {
+            if !labels.contains(&entry.label.as_str()) {
+                labels.push(&entry.label);
+            }
+        }
+        match self.format {
+            OutputFormat::Human | OutputFormat::Dot => {
+                self.format_goto_human(entries, &labels, cache)
+            }
+            OutputFormat::Json => {
+                let grouped: Vec<serde_json::Value> = labels
+                    .iter()
+                    .map(|label| {
+                        let mut group = serde_json::json!({ "symbol": label });
+                        let mut unsupported = Vec::new();
+                        for entry in entries.iter().filter(|e| e.label == *label) {
+                            let locations: Vec<_> =
+                                entry.locations.iter().map(|loc| record(entry, loc)).collect();
+                            group[entry.kind.key()] = locations.into();
+                            if entry.unsupported {
+                                unsupported.push(entry.kind);
+                            }
+                        }
+                        if !unsupported.is_empty() {
+                            group["unsupported"] = serde_json::json!(unsupported);
+                        }
+                        group
+                    })
+                    .collect();
+                self.json(&grouped)
+            }
+            OutputFormat::Jsonl => json_lines(
+                entries
+                    .iter()
+                    .flat_map(|entry| entry.locations.iter().map(|loc| record(entry, loc))),
+            ),
+            OutputFormat::Csv => {
+                let mut output = String::from("symbol,kind,file,line,column,stub\n");
+                for entry in entries {
+                    for location in &entry.locations {
+                        let (line, column) = self.position(cache, location);
+                        let _ = writeln!(
+                            output,
+                            "{},{},{},{line},{column},{}",
+                            entry.label,
+                            entry.kind.key(),
+                            self.uri_to_path(&location.uri),
+                            is_stub(&location.uri)
+                        );
+                    }
+                }
+                output
+            }
+            OutputFormat::Paths => {
+                let mut paths: Vec<String> = entries
+                    .iter()
+                    .flat_map(|e| e.locations.iter().map(|loc| self.uri_to_path(&loc.uri)))
+                    .collect();
+                paths.sort();
+                paths.dedup();
+                paths.join("\n")
+            }
+        }
+    }
+
+    fn format_goto_human(
+        &self,
+        entries: &[GotoEntry],
+        labels: &[&str],
+        cache: &SourceCache,
+    ) -> String {
+        let mut output = String::new();
+        for label in labels {
+            if labels.len() > 1 {
+                let _ = writeln!(output, "=== {} ===", self.s.symbol(label));
+            }
+            for entry in entries.iter().filter(|e| e.label == *label) {
+                let kind = format!("{:<16}", entry.kind.label());
+                if entry.unsupported {
+                    let note = "not supported by the language server";
+                    let _ = writeln!(output, "{kind} {}", self.s.dim(note));
+                } else if entry.locations.is_empty() {
+                    let _ = writeln!(output, "{kind} {}", self.s.dim("none"));
+                }
+                for location in &entry.locations {
+                    let (line, column) = self.position(cache, location);
+                    let stub = if is_stub(&location.uri) {
+                        format!(" {}", self.s.dim("[stub]"))
+                    } else {
+                        String::new()
+                    };
+                    let _ = writeln!(
+                        output,
+                        "{kind} {}{stub}{}",
+                        self.s.file_location(&self.uri_to_path(&location.uri), line, column),
+                        self.origin_tag(&location.uri),
+                    );
+                    let source_line = location.range.start.line + 1;
+                    if let Some(src) = read_source_line(cache, &location.uri, source_line) {
+                        let _ = writeln!(output, "{:<16} {src}", "");
+                    }
+                }
+            }
+            output.push('\n');
+        }
+        output.trim_end().to_string()
+    }
+
     /// Format enriched references results (with context and limit support).
+    ///
+    /// The CLI streams with `print_enriched_references_results`; this is the
+    /// same output collected into a string, as `tyf mcp` needs it.
+    #[cfg_attr(not(unix), allow(dead_code))]
     pub fn format_enriched_references_results(
         &self,
         results: &[EnrichedReferencesResult],
         cache: &SourceCache,
     ) -> String {
+        let mut output = String::new();
+        let _ = self.write_enriched_references_results(&mut output, results, cache);
+        if results.len() > 1 && self.format == OutputFormat::Human {
+            output.truncate(output.trim_end().len());
+        }
+        output
+    }
+
+    /// Print enriched references results to stdout as they are formatted.
+    ///
+    /// Prints what `format_enriched_references_results` returns (minus trailing
+    /// blank lines) without building it in memory first, which matters for
+    /// symbols with tens of thousands of references. In machine-readable
+    /// formats the `--limit` notice goes to stderr instead.
+    pub fn print_enriched_references_results(
+        &self,
+        results: &[EnrichedReferencesResult],
+        cache: &SourceCache,
+    ) -> std::io::Result<()> {
+        let mut sink = TrimEndSink::new(std::io::BufWriter::new(std::io::stdout().lock()));
+        let written = self.write_enriched_references_results(&mut sink, results, cache);
+        sink.finish(written)?;
+
+        if self.format != OutputFormat::Human {
+            for result in results.iter().filter(|r| r.omitted_count > 0) {
+                eprintln!("{}", self.s.dim(&Self::truncation_notice(result)));
+            }
+        }
+        Ok(())
+    }
+
+    fn write_enriched_references_results(
+        &self,
+        out: &mut impl Write,
+        results: &[EnrichedReferencesResult],
+        cache: &SourceCache,
+    ) -> std::fmt::Result {
         if results.len() == 1 {
-            return self.format_enriched_references_single(&results[0], cache);
+            return self.write_enriched_references_single(out, &results[0], cache);
         }
 
         match self.format {
-            OutputFormat::Human => {
-                let mut output = String::new();
+            OutputFormat::Human | OutputFormat::Dot => {
                 for result in results {
-                    let _ = writeln!(output, "=== {} ===", self.s.symbol(&result.label));
-                    output.push_str(&self.format_enriched_references_single(result, cache));
-                    output.push('\n');
+                    self.write_enriched_references_grouped(out, result, cache)?;
                 }
-                output.trim_end().to_string()
+                Ok(())
             }
             OutputFormat::Json => {
                 let grouped: Vec<serde_json::Value> =
-                    results.iter().map(Self::enriched_refs_to_json).collect();
-                serde_json::to_string_pretty(&grouped).unwrap_or_else(|_| "[]".to_string())
+                    results.iter().map(|r| self.enriched_refs_to_json(r, cache)).collect();
+                out.write_str(&self.json(&grouped))
+            }
+            OutputFormat::Jsonl => {
+                for result in results {
+                    self.write_enriched_references_jsonl(out, result, cache)?;
+                }
+                Ok(())
             }
             OutputFormat::Csv => {
-                let mut output = String::from("symbol,file,line,column,context,test\n");
+                writeln!(out, "symbol,file,line,column,context,test{}", self.csv_range_header())?;
                 for result in results {
-                    for enriched in &result.displayed {
-                        let file_path = self.uri_to_path(&enriched.location.uri);
-                        let line = enriched.location.range.start.line + 1;
-                        let column = enriched.location.range.start.character + 1;
-                        let _ = writeln!(
-                            output,
-                            "{},{file_path},{line},{column},{},false",
-                            result.label, enriched.context
-                        );
-                    }
-                    if let Some(test_refs) = &result.test_references {
-                        for enriched in &test_refs.displayed {
-                            let file_path = self.uri_to_path(&enriched.location.uri);
-                            let line = enriched.location.range.start.line + 1;
-                            let column = enriched.location.range.start.character + 1;
-                            let _ = writeln!(
-                                output,
-                                "{},{file_path},{line},{column},{},true",
-                                result.label, enriched.context
-                            );
+                    let tests = result.test_references.as_ref().map_or(&[][..], |t| &t.displayed);
+                    for (refs, test) in [(&result.displayed[..], false), (tests, true)] {
+                        for enriched in refs {
+                            write!(out, "{},", result.label)?;
+                            self.write_reference_csv_row(out, enriched, test, cache)?;
                         }
                     }
                 }
-                output
+                Ok(())
             }
             OutputFormat::Paths => {
                 let mut paths: Vec<String> = results
@@ -548,128 +1647,279 @@ impl OutputFormatter {
                     .collect();
                 paths.sort();
                 paths.dedup();
-                paths.join("\n")
+                out.write_str(&paths.join("\n"))
             }
         }
     }
 
-    fn format_enriched_references_human(
+    /// One of several human references results, under its label.
+    fn write_enriched_references_grouped(
         &self,
+        out: &mut impl Write,
         result: &EnrichedReferencesResult,
         cache: &SourceCache,
-    ) -> String {
+    ) -> std::fmt::Result {
+        writeln!(out, "=== {} ===", self.s.symbol(&result.label))?;
+        self.write_enriched_references_single(out, result, cache)?;
+        out.write_char('\n')
+    }
+
+    /// Whether references in this format can be printed one result at a
+    /// time, with [`ReferencesPrinter`].
+    pub fn prints_references_incrementally(&self) -> bool {
+        matches!(self.format, OutputFormat::Human | OutputFormat::Dot | OutputFormat::Jsonl)
+    }
+
+    /// A printer for references results that arrive one at a time; `grouped`
+    /// when there will be several.
+    pub fn references_printer(&self, grouped: bool) -> ReferencesPrinter<'_> {
+        ReferencesPrinter {
+            formatter: self,
+            grouped,
+            sink: TrimEndSink::new(std::io::BufWriter::new(std::io::stdout())),
+            truncated: Vec::new(),
+        }
+    }
+
+    /// Notice printed when `--limit` or `--max-results` cut a references
+    /// result short.
+    fn truncation_notice(result: &EnrichedReferencesResult) -> String {
+        format!(
+            "Stopped loading references for '{}' at the limit; {} more not shown \
+             (page through them with --offset, or use --limit 0 to load all)",
+            result.label, result.omitted_count
+        )
+    }
+
+    /// Print the hint that `remaining` more results follow `page`: after the
+    /// human output, or on stderr for the machine-readable formats.
+    pub fn print_more_results(&self, remaining: usize, page: ResultPage) {
+        if remaining == 0 {
+            return;
+        }
+        let hint = self.s.dim(&format!(
+            "... {remaining} more result(s) — use --offset {} for the next page, \
+             or --limit 0 for all",
+            page.next_offset()
+        ));
+        if self.format == OutputFormat::Human {
+            println!("{hint}");
+        } else {
+            eprintln!("{hint}");
+        }
+    }
+
+    fn write_enriched_references_human(
+        &self,
+        out: &mut impl Write,
+        result: &EnrichedReferencesResult,
+        cache: &SourceCache,
+    ) -> std::fmt::Result {
         if result.total_count == 0
             && result.test_references.as_ref().is_none_or(|t| t.total_count == 0)
         {
-            return self.s.error(&format!("No results found for: '{}'", result.label));
+            return out
+                .write_str(&self.s.error(&format!("No results found for: '{}'", result.label)));
         }
 
-        let mut output =
-            format!("Found {} reference(s) for: '{}'\n\n", result.total_count, result.label);
+        write!(out, "Found {} reference(s) for: '{}'\n\n", result.total_count, result.label)?;
 
-        self.write_enriched_ref_list(&mut output, &result.displayed, cache);
+        self.write_grouped_ref_list(out, &result.displayed, cache)?;
 
         if result.remaining_count > 0 {
-            let _ = writeln!(
-                output,
+            writeln!(
+                out,
                 "... and {} more — use --references-limit 0 to show all",
                 result.remaining_count
-            );
+            )?;
+        }
+        if result.omitted_count > 0 {
+            writeln!(out, "{}", self.s.dim(&Self::truncation_notice(result)))?;
         }
 
-        self.write_test_references_section(&mut output, result.test_references.as_ref(), cache);
+        self.write_test_references_section(out, result.test_references.as_ref(), cache)
+    }
 
-        output
+    /// `refs` split into their `--group-by` groups, each with its name; a
+    /// single unnamed group without `--group-by`.
+    fn reference_groups<'r>(
+        &self,
+        refs: &'r [EnrichedReference],
+        cache: &SourceCache,
+    ) -> Vec<(Option<String>, Vec<&'r EnrichedReference>)> {
+        let Some(by) = self.reference_groups else {
+            return vec![(None, refs.iter().collect())];
+        };
+        let mut groups: Vec<(Option<String>, Vec<&EnrichedReference>)> = Vec::new();
+        for enriched in refs {
+            let name = match by {
+                ReferenceGroup::File => self.uri_to_path(&enriched.location.uri),
+                ReferenceGroup::Symbol => enriched.context.clone(),
+                ReferenceGroup::Kind => cache
+                    .get_uri_line(&enriched.location.uri, enriched.location.range.start.line)
+                    .map_or("other", |line| reference_kind(line, &enriched.location.range))
+                    .to_string(),
+            };
+            match groups.iter_mut().find(|(group, _)| group.as_deref() == Some(name.as_str())) {
+                Some((_, members)) => members.push(enriched),
+                None => groups.push((Some(name), vec![enriched])),
+            }
+        }
+        if self.reference_sort == Some(ReferenceSort::Count) {
+            groups.sort_by_key(|(_, members)| std::cmp::Reverse(members.len()));
+        }
+        groups
     }
 
-    /// Append numbered enriched reference lines (with source) to `output`.
-    fn write_enriched_ref_list(
+    /// Write `refs` as numbered lines, under a heading per group with `--group-by`.
+    fn write_grouped_ref_list(
         &self,
-        output: &mut String,
+        out: &mut impl Write,
         refs: &[EnrichedReference],
         cache: &SourceCache,
-    ) {
-        for (i, enriched) in refs.iter().enumerate() {
+    ) -> std::fmt::Result {
+        for (group, members) in self.reference_groups(refs, cache) {
+            if let Some(group) = group {
+                writeln!(out, "{}", self.s.heading(&format!("{group} ({})", members.len())))?;
+            }
+            self.write_enriched_ref_list(out, members, cache)?;
+        }
+        Ok(())
+    }
+
+    /// Write numbered enriched reference lines (with source) to `out`.
+    fn write_enriched_ref_list<'r>(
+        &self,
+        out: &mut impl Write,
+        refs: impl IntoIterator<Item = &'r EnrichedReference>,
+        cache: &SourceCache,
+    ) -> std::fmt::Result {
+        for (i, enriched) in refs.into_iter().enumerate() {
             let file_path = self.uri_to_path(&enriched.location.uri);
-            let line = enriched.location.range.start.line + 1;
-            let column = enriched.location.range.start.character + 1;
+            let (line, column) = self.position(cache, &enriched.location);
 
-            let _ = writeln!(
-                output,
-                "{}. {} ({})",
+            write!(
+                out,
+                "{}. {} ({}){}",
                 i + 1,
                 self.s.file_location(&file_path, line, column),
                 self.s.dim(&enriched.context),
-            );
+                self.origin_tag(&enriched.location.uri),
+            )?;
+            if let Some(blame) = &enriched.blame {
+                let blame = format!("{} {}, {}", blame.commit, blame.author, blame.date);
+                write!(out, " {}", self.s.dim(&blame))?;
+            }
+            out.write_char('\n')?;
 
-            if let Some(src) = read_source_line(cache, &file_path, line) {
-                let _ = writeln!(output, "   {src}");
+            if self.context_lines > 0 {
+                if let Some(file) = cache.get_uri(&enriched.location.uri) {
+                    self.write_source_context(out, file, &enriched.location.range)?;
+                }
+            } else if let Some(src) = read_source_line(
+                cache,
+                &enriched.location.uri,
+                enriched.location.range.start.line + 1,
+            ) {
+                writeln!(out, "   {src}")?;
             }
-            output.push('\n');
+            out.write_char('\n')?;
+        }
+        Ok(())
+    }
+
+    /// Write the lines around `range` like `grep -C`, numbered, with the match
+    /// line marked by `>` and the matched text highlighted.
+    fn write_source_context(
+        &self,
+        out: &mut impl Write,
+        file: &SourceFile,
+        range: &Range,
+    ) -> std::fmt::Result {
+        let target = range.start.line as usize;
+        if target >= file.line_count() {
+            return Ok(());
+        }
+        let first = target.saturating_sub(self.context_lines);
+        let last = (target + self.context_lines).min(file.line_count() - 1);
+        let base = self.base as usize;
+        let width = (last + base).to_string().len();
+        for (i, text) in file.lines_from(first).take(last + 1 - first) {
+            let number = self.s.line_col(&format!("{:>width$}", i + base));
+            if i == target {
+                writeln!(out, "   > {number} | {}", self.highlight_match(text, range))?;
+            } else {
+                writeln!(out, "     {number} | {text}")?;
+            }
+        }
+        Ok(())
+    }
+
+    /// `line` (the first line of `range`) with the text `range` covers highlighted.
+    fn highlight_match(&self, line: &str, range: &Range) -> String {
+        let byte = |character| convert_column(line, character, LSP_UNIT, ColumnUnit::Utf8) as usize;
+        let start = byte(range.start.character).min(line.len());
+        let end = if range.end.line == range.start.line {
+            byte(range.end.character).clamp(start, line.len())
+        } else {
+            line.len()
+        };
+        match (line.get(..start), line.get(start..end), line.get(end..)) {
+            (Some(before), Some(matched), Some(after)) if !matched.is_empty() => {
+                format!("{before}{}{after}", self.s.highlight(matched))
+            }
+            _ => line.to_string(),
         }
     }
 
-    /// Append the test references section (or hidden hint) to `output`.
+    /// Write the test references section (or hidden hint) to `out`.
     fn write_test_references_section(
         &self,
-        output: &mut String,
+        out: &mut impl Write,
         test_references: Option<&TestReferencesSection>,
         cache: &SourceCache,
-    ) {
+    ) -> std::fmt::Result {
         if let Some(test_refs) = test_references {
             if !test_refs.displayed.is_empty() {
                 let heading = format!("Test references ({}):", test_refs.total_count);
-                let _ = writeln!(output, "\n{}\n", self.s.heading(&heading));
-                self.write_enriched_ref_list(output, &test_refs.displayed, cache);
+                writeln!(out, "\n{}\n", self.s.heading(&heading))?;
+                self.write_grouped_ref_list(out, &test_refs.displayed, cache)?;
                 if test_refs.remaining_count > 0 {
-                    let _ =
-                        writeln!(output, "... and {} more test ref(s)", test_refs.remaining_count);
+                    writeln!(out, "... and {} more test ref(s)", test_refs.remaining_count)?;
                 }
             } else if test_refs.total_count > 0 {
                 let heading =
                     format!("Test references: {} (use --tests/-t to show)", test_refs.total_count);
-                let _ = writeln!(output, "\n{}", self.s.heading(&heading));
+                writeln!(out, "\n{}", self.s.heading(&heading))?;
             }
         }
+        Ok(())
     }
 
-    fn format_enriched_references_single(
+    fn write_enriched_references_single(
         &self,
+        out: &mut impl Write,
         result: &EnrichedReferencesResult,
         cache: &SourceCache,
-    ) -> String {
+    ) -> std::fmt::Result {
         match self.format {
-            OutputFormat::Human => self.format_enriched_references_human(result, cache),
+            OutputFormat::Human | OutputFormat::Dot => {
+                self.write_enriched_references_human(out, result, cache)
+            }
             OutputFormat::Json => {
-                let val = Self::enriched_refs_to_json(result);
-                serde_json::to_string_pretty(&val).unwrap_or_else(|_| "{}".to_string())
+                let val = self.enriched_refs_to_json(result, cache);
+                out.write_str(&self.json(&val))
             }
+            OutputFormat::Jsonl => self.write_enriched_references_jsonl(out, result, cache),
             OutputFormat::Csv => {
-                let has_test_refs =
-                    result.test_references.as_ref().is_some_and(|t| !t.displayed.is_empty());
-                let mut output = String::from("file,line,column,context,test\n");
-                for enriched in &result.displayed {
-                    let file_path = self.uri_to_path(&enriched.location.uri);
-                    let line = enriched.location.range.start.line + 1;
-                    let column = enriched.location.range.start.character + 1;
-                    let _ =
-                        writeln!(output, "{file_path},{line},{column},{},false", enriched.context);
-                }
-                if has_test_refs {
-                    if let Some(test_refs) = &result.test_references {
-                        for enriched in &test_refs.displayed {
-                            let file_path = self.uri_to_path(&enriched.location.uri);
-                            let line = enriched.location.range.start.line + 1;
-                            let column = enriched.location.range.start.character + 1;
-                            let _ = writeln!(
-                                output,
-                                "{file_path},{line},{column},{},true",
-                                enriched.context
-                            );
-                        }
+                writeln!(out, "file,line,column,context,test{}", self.csv_range_header())?;
+                let tests = result.test_references.as_ref().map_or(&[][..], |t| &t.displayed);
+                for (refs, test) in [(&result.displayed[..], false), (tests, true)] {
+                    for enriched in refs {
+                        self.write_reference_csv_row(out, enriched, test, cache)?;
                     }
                 }
-                output
+                Ok(())
             }
             OutputFormat::Paths => {
                 let mut paths: Vec<String> =
@@ -681,73 +1931,161 @@ impl OutputFormatter {
                 }
                 paths.sort();
                 paths.dedup();
-                paths.join("\n")
+                out.write_str(&paths.join("\n"))
+            }
+        }
+    }
+
+    /// One reference as a CSV row: `file,line,column,context,test`, plus
+    /// the range columns with `--with-ranges`.
+    fn write_reference_csv_row(
+        &self,
+        out: &mut impl Write,
+        enriched: &EnrichedReference,
+        test: bool,
+        cache: &SourceCache,
+    ) -> std::fmt::Result {
+        let file_path = self.uri_to_path(&enriched.location.uri);
+        let (line, column) = self.position(cache, &enriched.location);
+        let range = self.csv_range(&enriched.location, cache);
+        writeln!(out, "{file_path},{line},{column},{},{test}{range}", enriched.context)
+    }
+
+    /// Write one JSON line per displayed reference, test references flagged.
+    fn write_enriched_references_jsonl(
+        &self,
+        out: &mut impl Write,
+        result: &EnrichedReferencesResult,
+        cache: &SourceCache,
+    ) -> std::fmt::Result {
+        let tests = result.test_references.as_ref().map_or(&[][..], |t| &t.displayed);
+        for (refs, test) in [(&result.displayed[..], false), (tests, true)] {
+            for record in self.refs_to_json(refs, cache) {
+                let record = with_field(record, "test", test);
+                writeln!(out, "{}", with_field(record, "symbol", result.label.as_str()))?;
             }
         }
+        Ok(())
     }
 
-    fn enriched_refs_to_json(result: &EnrichedReferencesResult) -> serde_json::Value {
-        let refs_json: Vec<serde_json::Value> =
-            result.displayed.iter().map(Self::enriched_ref_to_json).collect();
+    fn enriched_refs_to_json(
+        &self,
+        result: &EnrichedReferencesResult,
+        cache: &SourceCache,
+    ) -> serde_json::Value {
+        let refs_json = self.refs_to_json(&result.displayed, cache);
 
-        let test_refs_json: Vec<serde_json::Value> =
-            result.test_references.as_ref().map_or_else(Vec::new, |t| {
-                t.displayed.iter().map(Self::enriched_ref_to_json).collect()
-            });
+        let test_refs_json: Vec<serde_json::Value> = result
+            .test_references
+            .as_ref()
+            .map_or_else(Vec::new, |t| self.refs_to_json(&t.displayed, cache));
 
         let test_count = result.test_references.as_ref().map_or(0, |t| t.total_count);
 
-        serde_json::json!({
+        let mut val = serde_json::json!({
             "symbol": result.label,
             "reference_count": result.total_count,
             "references": refs_json,
             "test_reference_count": test_count,
             "test_references": test_refs_json,
-        })
+        });
+        if result.omitted_count > 0 {
+            val["omitted_count"] = serde_json::Value::from(result.omitted_count);
+        }
+        val
     }
 
-    fn enriched_ref_to_json(r: &EnrichedReference) -> serde_json::Value {
-        let file_path = r.location.uri.strip_prefix("file://").unwrap_or(&r.location.uri);
-        serde_json::json!({
+    /// JSON for `refs` in their `--group-by` order, each naming its group.
+    fn refs_to_json(
+        &self,
+        refs: &[EnrichedReference],
+        cache: &SourceCache,
+    ) -> Vec<serde_json::Value> {
+        let mut json = Vec::with_capacity(refs.len());
+        for (group, members) in self.reference_groups(refs, cache) {
+            json.extend(members.into_iter().map(|r| {
+                let val = self.enriched_ref_to_json(r, cache);
+                match &group {
+                    Some(group) => with_field(val, "group", group.as_str()),
+                    None => val,
+                }
+            }));
+        }
+        json
+    }
+
+    fn enriched_ref_to_json(
+        &self,
+        r: &EnrichedReference,
+        cache: &SourceCache,
+    ) -> serde_json::Value {
+        let file_path = self.abs_path(&r.location.uri);
+        let (line, column) = self.position(cache, &r.location);
+        let mut val = serde_json::json!({
             "file": file_path,
-            "line": r.location.range.start.line + 1,
-            "column": r.location.range.start.character + 1,
+            "line": line,
+            "column": column,
             "context": r.context,
-        })
+        });
+        if let Some(origin) = self.origin(&r.location.uri) {
+            val["origin"] = serde_json::Value::from(origin.as_str());
+        }
+        if let Some(blame) = &r.blame {
+            val["blame"] = serde_json::json!(blame);
+        }
+        self.with_range(val, &r.location, cache)
+    }
+
+    pub fn format_workspace_symbols(
+        &self,
+        symbols: &[SymbolInformation],
+        cache: &SourceCache,
+    ) -> String {
+        self.format_workspace_symbols_from(symbols, 0, cache)
     }
 
-    pub fn format_workspace_symbols(&self, symbols: &[SymbolInformation]) -> String {
+    /// Format workspace symbols that follow `first` already printed ones:
+    /// numbered on from them, and without a second CSV header.
+    fn format_workspace_symbols_from(
+        &self,
+        symbols: &[SymbolInformation],
+        first: usize,
+        cache: &SourceCache,
+    ) -> String {
         match self.format {
-            OutputFormat::Human => {
+            OutputFormat::Human | OutputFormat::Dot => {
                 let mut output = String::new();
 
-                for (i, symbol) in symbols.iter().enumerate() {
+                for (i, symbol) in symbols.iter().enumerate().map(|(i, s)| (first + i, s)) {
                     let file_path = self.uri_to_path(&symbol.location.uri);
-                    let line = symbol.location.range.start.line + 1;
-                    let column = symbol.location.range.start.character + 1;
+                    let (line, column) = self.position(cache, &symbol.location);
 
                     let kind_str = format!("({:?})", symbol.kind);
                     let _ = write!(
                         output,
-                        "{}. {} {}\n   {}\n\n",
+                        "{}. {} {}\n   {}{}\n\n",
                         i + 1,
                         self.s.symbol(&symbol.name),
                         self.s.dim(&kind_str),
                         self.s.file_location(&file_path, line, column),
+                        self.origin_tag(&symbol.location.uri),
                     );
                 }
 
                 output
             }
-            OutputFormat::Json => {
-                serde_json::to_string_pretty(symbols).unwrap_or_else(|_| "[]".to_string())
-            }
+            OutputFormat::Json => self.json(symbols),
+            OutputFormat::Jsonl => json_lines(symbols.iter().map(|symbol| {
+                let record = self.location_record(&symbol.location, cache);
+                let record = with_field(record, "name", symbol.name.as_str());
+                with_field(record, "kind", format!("{:?}", symbol.kind))
+            })),
             OutputFormat::Csv => {
-                let mut output = String::from("name,kind,file,line,column\n");
+                let mut output =
+                    String::from(if first == 0 { "name,kind,file,line,column\n" } else { "" });
                 for symbol in symbols {
                     let file_path = self.uri_to_path(&symbol.location.uri);
-                    let line = symbol.location.range.start.line + 1;
-                    let column = symbol.location.range.start.character + 1;
+                    let (line, column) = self.position(cache, &symbol.location);
                     let _ = writeln!(
                         output,
                         "{},{:?},{file_path},{line},{column}",
@@ -764,27 +2102,346 @@ impl OutputFormatter {
         }
     }
 
-    pub fn format_document_symbols(&self, symbols: &[DocumentSymbol]) -> String {
+    /// Whether workspace symbols in this format can be printed in parts,
+    /// with [`SymbolsPrinter`].
+    pub fn prints_symbols_incrementally(&self) -> bool {
+        self.format != OutputFormat::Json
+    }
+
+    /// A printer for workspace symbols that arrive in parts.
+    pub const fn symbols_printer(&self) -> SymbolsPrinter<'_> {
+        SymbolsPrinter { formatter: self, printed: 0 }
+    }
+
+    /// Format the symbol picked in `tyf tui`: a bare `path:line:col` for
+    /// editors in human mode, otherwise the same record `symbols` prints.
+    pub fn format_picked_symbol(&self, symbol: &SymbolInformation, cache: &SourceCache) -> String {
         match self.format {
-            OutputFormat::Human => {
+            OutputFormat::Human | OutputFormat::Dot => {
+                let file_path = self.uri_to_path(&symbol.location.uri);
+                let (line, column) = self.position(cache, &symbol.location);
+                format!("{file_path}:{line}:{column}")
+            }
+            _ => self.format_workspace_symbols(std::slice::from_ref(symbol), cache),
+        }
+    }
+
+    /// Format the outline of `file_path`; `cache` supplies its source for column conversion.
+    pub fn format_document_symbols(
+        &self,
+        symbols: &[DocumentSymbol],
+        file_path: &str,
+        cache: &SourceCache,
+    ) -> String {
+        let source = cache.get(file_path);
+        match self.format {
+            OutputFormat::Human | OutputFormat::Dot => {
                 let mut output = String::new();
-                format_document_symbols_recursive(symbols, 0, &mut output);
+                format_document_symbols_recursive(symbols, 0, self.base, source, &mut output);
                 output
             }
-            OutputFormat::Json => {
-                serde_json::to_string_pretty(symbols).unwrap_or_else(|_| "[]".to_string())
+            OutputFormat::Json => self.json(symbols),
+            OutputFormat::Jsonl => {
+                let mut records = Vec::new();
+                document_symbol_records(symbols, None, self.base, source, &mut records);
+                json_lines(records)
             }
             OutputFormat::Csv => {
                 let mut output = String::from("name,kind,line,column\n");
-                format_document_symbols_csv(symbols, &mut output);
+                format_document_symbols_csv(symbols, self.base, source, &mut output);
                 output
             }
             OutputFormat::Paths => {
                 // Paths format doesn't make sense for document symbols, fall back to human
                 let mut output = String::new();
-                format_document_symbols_recursive(symbols, 0, &mut output);
+                format_document_symbols_recursive(symbols, 0, self.base, source, &mut output);
+                output
+            }
+        }
+    }
+
+    /// Format an `explain-type` breakdown: signature followed by each expanded type.
+    pub fn format_explain_type(&self, result: &ExplainTypeResult, cache: &SourceCache) -> String {
+        match self.format {
+            OutputFormat::Human | OutputFormat::Dot => {
+                let Some(signature) = &result.signature else {
+                    return self.s.error(&format!("No results found for: '{}'", result.query));
+                };
+                let mut output = format!("{}\n", self.s.symbol(&result.query));
+                for line in signature.lines() {
+                    let _ = writeln!(output, "  {line}");
+                }
+                for exp in &result.expansions {
+                    let indent = "  ".repeat(exp.depth - 1);
+                    let file_path = self.uri_to_path(&exp.location.uri);
+                    let (line, column) = self.position(cache, &exp.location);
+                    let _ = writeln!(
+                        output,
+                        "\n{indent}{} {} {}",
+                        self.s.symbol(&exp.name),
+                        self.s.dim(&format!("({})", exp.kind)),
+                        self.s.file_location(&file_path, line, column),
+                    );
+                    for line in exp.definition.lines() {
+                        let _ = writeln!(output, "{indent}  {line}");
+                    }
+                    for member in &exp.members {
+                        let _ = writeln!(output, "{indent}    {member}");
+                    }
+                }
+                output.trim_end().to_string()
+            }
+            OutputFormat::Json | OutputFormat::Jsonl => {
+                let types: Vec<serde_json::Value> = result
+                    .expansions
+                    .iter()
+                    .map(|exp| {
+                        let (line, column) = self.position(cache, &exp.location);
+                        serde_json::json!({
+                            "name": exp.name,
+                            "kind": exp.kind,
+                            "file": self.abs_path(&exp.location.uri),
+                            "line": line,
+                            "column": column,
+                            "depth": exp.depth,
+                            "definition": exp.definition,
+                            "members": exp.members,
+                        })
+                    })
+                    .collect();
+                if self.format == OutputFormat::Jsonl {
+                    return json_lines(
+                        types.into_iter().map(|t| with_field(t, "query", result.query.as_str())),
+                    );
+                }
+                let val = serde_json::json!({
+                    "query": result.query,
+                    "signature": result.signature,
+                    "types": types,
+                });
+                self.json(&val)
+            }
+            OutputFormat::Csv => {
+                let mut output = String::from("name,kind,depth,file,line,column,definition\n");
+                for exp in &result.expansions {
+                    let file_path = self.uri_to_path(&exp.location.uri);
+                    let (line, column) = self.position(cache, &exp.location);
+                    let _ = writeln!(
+                        output,
+                        "{},{},{},{file_path},{line},{column},\"{}\"",
+                        exp.name,
+                        exp.kind,
+                        exp.depth,
+                        exp.definition.replace('"', "\"\""),
+                    );
+                }
+                output
+            }
+            OutputFormat::Paths => {
+                let mut paths: Vec<String> =
+                    result.expansions.iter().map(|e| self.uri_to_path(&e.location.uri)).collect();
+                paths.sort();
+                paths.dedup();
+                paths.join("\n")
+            }
+        }
+    }
+
+    /// Format the occurrences of each `tyf occurrences` query.
+    pub fn format_occurrences(&self, entries: &[OccurrencesEntry], cache: &SourceCache) -> String {
+        match self.format {
+            OutputFormat::Human | OutputFormat::Dot => {
+                let mut output = String::new();
+                for entry in entries {
+                    self.write_occurrences_human(&mut output, entry, cache);
+                }
+                output.trim_end().to_string()
+            }
+            OutputFormat::Json | OutputFormat::Jsonl => {
+                let records = |entry: &OccurrencesEntry| -> Vec<serde_json::Value> {
+                    entry
+                        .occurrences()
+                        .iter()
+                        .map(|(location, kind)| {
+                            with_field(self.location_record(location, cache), "kind", *kind)
+                        })
+                        .collect()
+                };
+                if self.format == OutputFormat::Jsonl {
+                    return json_lines(entries.iter().flat_map(|entry| {
+                        records(entry)
+                            .into_iter()
+                            .map(|r| with_field(r, "symbol", entry.label.as_str()))
+                    }));
+                }
+                let values: Vec<serde_json::Value> = entries
+                    .iter()
+                    .map(|entry| {
+                        serde_json::json!({
+                            "symbol": entry.label,
+                            "file": entry.uri.as_deref().map(|uri| self.abs_path(uri)),
+                            "occurrences": records(entry),
+                        })
+                    })
+                    .collect();
+                let val = match values.as_slice() {
+                    [single] => single.clone(),
+                    _ => serde_json::Value::Array(values),
+                };
+                self.json(&val)
+            }
+            OutputFormat::Csv => {
+                let mut output = String::from("symbol,file,line,column,kind\n");
+                for entry in entries {
+                    for (location, kind) in entry.occurrences() {
+                        let (line, column) = self.position(cache, &location);
+                        let file_path = self.uri_to_path(&location.uri);
+                        let _ =
+                            writeln!(output, "{},{file_path},{line},{column},{kind}", entry.label);
+                    }
+                }
+                output
+            }
+            OutputFormat::Paths => {
+                let mut paths: Vec<String> = entries
+                    .iter()
+                    .filter(|entry| !entry.highlights.is_empty())
+                    .filter_map(|entry| entry.uri.as_deref().map(|uri| self.uri_to_path(uri)))
+                    .collect();
+                paths.dedup();
+                paths.join("\n")
+            }
+        }
+    }
+
+    /// One query's occurrences: a header, then position, kind and source line of each.
+    fn write_occurrences_human(
+        &self,
+        output: &mut String,
+        entry: &OccurrencesEntry,
+        cache: &SourceCache,
+    ) {
+        let (Some(uri), false) = (&entry.uri, entry.highlights.is_empty()) else {
+            let message = format!("No results found for: '{}'", entry.label);
+            let _ = writeln!(output, "{}\n", self.s.error(&message));
+            return;
+        };
+        let _ = writeln!(
+            output,
+            "{} occurrence(s) of '{}' in {}:",
+            entry.highlights.len(),
+            self.s.symbol(&entry.label),
+            self.uri_to_path(uri)
+        );
+        let rows: Vec<(String, &str, Location)> = entry
+            .occurrences()
+            .into_iter()
+            .map(|(location, kind)| {
+                let (line, column) = self.position(cache, &location);
+                (format!("{line}:{column}"), kind, location)
+            })
+            .collect();
+        let width = rows.iter().map(|(at, _, _)| at.len()).max().unwrap_or(0);
+        for (at, kind, location) in &rows {
+            let source =
+                read_source_line(cache, uri, location.range.start.line + 1).unwrap_or_default();
+            let _ = writeln!(
+                output,
+                "  {}  {kind:<5}  {source}",
+                self.s.line_col(&format!("{at:<width$}"))
+            );
+        }
+        output.push('\n');
+    }
+
+    /// Format `hover` results: signature and docstring for each query.
+    pub fn format_hover_results(&self, entries: &[HoverEntry], cache: &SourceCache) -> String {
+        match self.format {
+            OutputFormat::Human | OutputFormat::Dot => {
+                let mut output = String::new();
+                for entry in entries {
+                    let Some(hover) = &entry.hover else {
+                        let message = format!("No results found for: '{}'", entry.label);
+                        let _ = writeln!(output, "{}\n", self.s.error(&message));
+                        continue;
+                    };
+                    let _ = write!(output, "{}", self.s.symbol(&entry.label));
+                    if let Some(location) = &entry.location {
+                        let file_path = self.uri_to_path(&location.uri);
+                        let (line, column) = self.position(cache, location);
+                        let _ =
+                            write!(output, " {}", self.s.file_location(&file_path, line, column));
+                    }
+                    output.push('\n');
+                    for line in Self::extract_hover_type(&hover.contents).lines() {
+                        let _ = writeln!(output, "  {line}");
+                    }
+                    if let Some(doc) = Self::extract_hover_doc(&hover.contents) {
+                        output.push('\n');
+                        for line in render_markdown(&doc, self.s).lines() {
+                            let _ = writeln!(output, "  {line}");
+                        }
+                    }
+                    output.push('\n');
+                }
+                output.trim_end().to_string()
+            }
+            OutputFormat::Json | OutputFormat::Jsonl => {
+                let values: Vec<serde_json::Value> = entries
+                    .iter()
+                    .map(|entry| {
+                        let position = entry.location.as_ref().map(|loc| self.position(cache, loc));
+                        serde_json::json!({
+                            "symbol": entry.label,
+                            "file": entry.location.as_ref().map(|loc| self.abs_path(&loc.uri)),
+                            "line": position.map(|(line, _)| line),
+                            "column": position.map(|(_, column)| column),
+                            "signature": entry
+                                .hover
+                                .as_ref()
+                                .map(|h| Self::extract_hover_type(&h.contents)),
+                            "doc": entry.hover.as_ref().and_then(|h| Self::extract_hover_doc(&h.contents)),
+                        })
+                    })
+                    .collect();
+                if self.format == OutputFormat::Jsonl {
+                    return json_lines(values);
+                }
+                let val = match values.as_slice() {
+                    [single] => single.clone(),
+                    _ => serde_json::Value::Array(values),
+                };
+                self.json(&val)
+            }
+            OutputFormat::Csv => {
+                let mut output = String::from("symbol,file,line,column,signature\n");
+                for entry in entries {
+                    let (Some(location), Some(hover)) = (&entry.location, &entry.hover) else {
+                        continue;
+                    };
+                    let file_path = self.uri_to_path(&location.uri);
+                    let (line, column) = self.position(cache, location);
+                    let signature = Self::extract_hover_type(&hover.contents);
+                    let _ = writeln!(
+                        output,
+                        "{},{file_path},{line},{column},\"{}\"",
+                        entry.label,
+                        signature.replace('"', "\"\""),
+                    );
+                }
                 output
             }
+            OutputFormat::Paths => {
+                let mut paths: Vec<String> = entries
+                    .iter()
+                    .filter(|e| e.hover.is_some())
+                    .filter_map(|e| e.location.as_ref())
+                    .map(|loc| self.uri_to_path(&loc.uri))
+                    .collect();
+                paths.sort();
+                paths.dedup();
+                paths.join("\n")
+            }
         }
     }
 
@@ -810,7 +2467,7 @@ impl OutputFormatter {
     ///   ```lang\n<type info>\n```\n---\nDocstring...
     ///
     /// Returns the bare type text without markdown fences or docstring.
-    fn extract_hover_type(contents: &HoverContents) -> String {
+    pub fn extract_hover_type(contents: &HoverContents) -> String {
         let full = Self::extract_hover_text(contents);
 
         // Strip docstring: everything after the first "\n---" separator
@@ -875,8 +2532,9 @@ impl OutputFormatter {
     ) {
         if let Some(location) = location {
             // Use absolute path for cache lookup (cache stores absolute paths from URIs).
-            let abs_path = location.uri.strip_prefix("file://").unwrap_or(&location.uri);
-            if let Some(ctx) = read_definition_context(cache, abs_path, location.range.start.line) {
+            let abs_path = uri_to_path(&location.uri);
+            if let Some(ctx) = read_definition_context(cache, &abs_path, location.range.start.line)
+            {
                 // Show decorators
                 if let Some(decs) = &ctx.decorators {
                     output.push_str(decs);
@@ -932,8 +2590,7 @@ impl OutputFormatter {
         } else {
             for location in entry.definitions {
                 let file_path = self.uri_to_path(&location.uri);
-                let line = location.range.start.line + 1;
-                let column = location.range.start.character + 1;
+                let (line, column) = self.position(cache, location);
                 let _ = writeln!(output, "{}", self.s.file_location(&file_path, line, column));
             }
         }
@@ -974,8 +2631,7 @@ impl OutputFormatter {
             if entry.show_individual_refs {
                 for enriched in &entry.displayed_references {
                     let file_path = self.uri_to_path(&enriched.location.uri);
-                    let line = enriched.location.range.start.line + 1;
-                    let column = enriched.location.range.start.character + 1;
+                    let (line, column) = self.position(cache, &enriched.location);
                     let _ = writeln!(
                         output,
                         "{} ({})",
@@ -1006,8 +2662,7 @@ impl OutputFormatter {
                 let _ = writeln!(output, "{}", self.s.heading(&test_heading));
                 for enriched in &test_refs.displayed {
                     let file_path = self.uri_to_path(&enriched.location.uri);
-                    let line = enriched.location.range.start.line + 1;
-                    let column = enriched.location.range.start.character + 1;
+                    let (line, column) = self.position(cache, &enriched.location);
                     let _ = writeln!(
                         output,
                         "{} ({})",
@@ -1045,8 +2700,7 @@ impl OutputFormatter {
         } else {
             for (i, location) in entry.definitions.iter().enumerate() {
                 let file_path = self.uri_to_path(&location.uri);
-                let line = location.range.start.line + 1;
-                let column = location.range.start.character + 1;
+                let (line, column) = self.position(cache, location);
                 let _ = writeln!(
                     output,
                     "{}. {}",
@@ -1054,7 +2708,9 @@ impl OutputFormatter {
                     self.s.file_location(&file_path, line, column)
                 );
 
-                if let Some(src) = read_source_line(cache, &file_path, line) {
+                if let Some(src) =
+                    read_source_line(cache, &location.uri, location.range.start.line + 1)
+                {
                     let _ = writeln!(output, "   {src}");
                 }
             }
@@ -1099,8 +2755,7 @@ impl OutputFormatter {
             if entry.show_individual_refs {
                 for (i, enriched) in entry.displayed_references.iter().enumerate() {
                     let file_path = self.uri_to_path(&enriched.location.uri);
-                    let line = enriched.location.range.start.line + 1;
-                    let column = enriched.location.range.start.character + 1;
+                    let (line, column) = self.position(cache, &enriched.location);
                     let _ = writeln!(
                         output,
                         "{}. {} ({})",
@@ -1109,7 +2764,11 @@ impl OutputFormatter {
                         self.s.dim(&enriched.context),
                     );
 
-                    if let Some(src) = read_source_line(cache, &file_path, line) {
+                    if let Some(src) = read_source_line(
+                        cache,
+                        &enriched.location.uri,
+                        enriched.location.range.start.line + 1,
+                    ) {
                         let _ = writeln!(output, "   {src}");
                     }
                 }
@@ -1131,15 +2790,18 @@ impl OutputFormatter {
                 let _ = writeln!(output, "{} test reference(s):", test_refs.total_count);
                 for (i, enriched) in test_refs.displayed.iter().enumerate() {
                     let file_path = self.uri_to_path(&enriched.location.uri);
-                    let line = enriched.location.range.start.line + 1;
-                    let column = enriched.location.range.start.character + 1;
+                    let (line, column) = self.position(cache, &enriched.location);
                     let _ = writeln!(
                         output,
                         "{}. {file_path}:{line}:{column} ({})",
                         i + 1,
                         enriched.context
                     );
-                    if let Some(src) = read_source_line(cache, &file_path, line) {
+                    if let Some(src) = read_source_line(
+                        cache,
+                        &enriched.location.uri,
+                        enriched.location.range.start.line + 1,
+                    ) {
                         let _ = writeln!(output, "   {src}");
                     }
                 }
@@ -1162,20 +2824,28 @@ impl OutputFormatter {
             return self.s.error(&format!("No results found for: '{}'", entry.symbol));
         }
         match self.format {
-            OutputFormat::Human => self.format_show_human(entry, 1, cache),
-            OutputFormat::Json => Self::format_show_json_single(entry),
-            OutputFormat::Csv => self.format_show_csv_single(entry, false),
+            OutputFormat::Human | OutputFormat::Dot => self.format_show_human(entry, 1, cache),
+            OutputFormat::Json => self.format_show_json_single(entry, cache),
+            OutputFormat::Jsonl => json_lines([self.show_to_json(entry, cache)]),
+            OutputFormat::Csv => self.format_show_csv_single(entry, false, cache),
             OutputFormat::Paths => self.format_show_paths_single(entry),
         }
     }
 
-    fn format_show_json_single(entry: &ShowEntry<'_>) -> String {
-        let refs_json: Vec<serde_json::Value> =
-            entry.displayed_references.iter().map(Self::enriched_ref_to_json).collect();
+    fn format_show_json_single(&self, entry: &ShowEntry<'_>, cache: &SourceCache) -> String {
+        self.json(&self.show_to_json(entry, cache))
+    }
+
+    fn show_to_json(&self, entry: &ShowEntry<'_>, cache: &SourceCache) -> serde_json::Value {
+        let refs_json: Vec<serde_json::Value> = entry
+            .displayed_references
+            .iter()
+            .map(|r| self.enriched_ref_to_json(r, cache))
+            .collect();
 
         let test_refs_json: Vec<serde_json::Value> =
             entry.test_references.as_ref().map_or_else(Vec::new, |t| {
-                t.displayed.iter().map(Self::enriched_ref_to_json).collect()
+                t.displayed.iter().map(|r| self.enriched_ref_to_json(r, cache)).collect()
             });
 
         let test_count = entry.test_references.as_ref().map_or(0, |t| t.total_count);
@@ -1195,7 +2865,7 @@ impl OutputFormatter {
             None
         };
 
-        let json_val = serde_json::json!({
+        serde_json::json!({
             "symbol": entry.symbol,
             "kind": entry.kind.map(Self::kind_label),
             "definitions": entry.definitions,
@@ -1206,11 +2876,15 @@ impl OutputFormatter {
             "references": refs_json,
             "test_reference_count": test_count,
             "test_references": test_refs_json,
-        });
-        serde_json::to_string_pretty(&json_val).unwrap_or_else(|_| "{}".to_string())
+        })
     }
 
-    fn format_show_csv_single(&self, entry: &ShowEntry<'_>, include_symbol: bool) -> String {
+    fn format_show_csv_single(
+        &self,
+        entry: &ShowEntry<'_>,
+        include_symbol: bool,
+        cache: &SourceCache,
+    ) -> String {
         let header = if include_symbol {
             "symbol,section,file,line,column,context\n"
         } else {
@@ -1220,14 +2894,12 @@ impl OutputFormatter {
         let prefix = if include_symbol { format!("{},", entry.symbol) } else { String::new() };
         for location in entry.definitions {
             let file_path = self.uri_to_path(&location.uri);
-            let line = location.range.start.line + 1;
-            let column = location.range.start.character + 1;
+            let (line, column) = self.position(cache, location);
             let _ = writeln!(output, "{prefix}definition,{file_path},{line},{column},");
         }
         for enriched in &entry.displayed_references {
             let file_path = self.uri_to_path(&enriched.location.uri);
-            let line = enriched.location.range.start.line + 1;
-            let column = enriched.location.range.start.character + 1;
+            let (line, column) = self.position(cache, &enriched.location);
             let _ = writeln!(
                 output,
                 "{prefix}reference,{file_path},{line},{column},{}",
@@ -1237,8 +2909,7 @@ impl OutputFormatter {
         if let Some(test_refs) = &entry.test_references {
             for enriched in &test_refs.displayed {
                 let file_path = self.uri_to_path(&enriched.location.uri);
-                let line = enriched.location.range.start.line + 1;
-                let column = enriched.location.range.start.character + 1;
+                let (line, column) = self.position(cache, &enriched.location);
                 let _ = writeln!(
                     output,
                     "{prefix}test_reference,{file_path},{line},{column},{}",
@@ -1274,7 +2945,7 @@ impl OutputFormatter {
         }
 
         match self.format {
-            OutputFormat::Human => {
+            OutputFormat::Human | OutputFormat::Dot => {
                 let mut output = String::new();
                 for entry in results {
                     if entry.is_empty() {
@@ -1294,20 +2965,18 @@ impl OutputFormatter {
                 output.trim_end().to_string()
             }
             OutputFormat::Json => {
-                let grouped: Vec<serde_json::Value> = results
-                    .iter()
-                    .map(|entry| {
-                        serde_json::from_str(&Self::format_show_json_single(entry))
-                            .unwrap_or_default()
-                    })
-                    .collect();
-                serde_json::to_string_pretty(&grouped).unwrap_or_else(|_| "[]".to_string())
+                let grouped: Vec<serde_json::Value> =
+                    results.iter().map(|entry| self.show_to_json(entry, cache)).collect();
+                self.json(&grouped)
+            }
+            OutputFormat::Jsonl => {
+                json_lines(results.iter().map(|entry| self.show_to_json(entry, cache)))
             }
             OutputFormat::Csv => {
                 let mut output = String::from("symbol,section,file,line,column,context\n");
                 for entry in results {
                     // Skip the header from each entry — we already wrote it
-                    let entry_csv = self.format_show_csv_single(entry, true);
+                    let entry_csv = self.format_show_csv_single(entry, true, cache);
                     for line in entry_csv.lines().skip(1) {
                         output.push_str(line);
                         output.push('\n');
@@ -1368,13 +3037,57 @@ fn categorize_members(
     (methods, properties, class_vars)
 }
 
+/// Display coordinates for a 0-based member/class position reported by the daemon.
+#[cfg(unix)]
+fn member_position(
+    line: u32,
+    character: u32,
+    base: u32,
+    source: Option<&SourceFile>,
+) -> (u32, u32) {
+    display_position(&Position { line, character }, base, source)
+}
+
+/// Display coordinates of a member, when it has a known position.
+#[cfg(unix)]
+fn member_location(m: &MemberInfo, base: u32, source: Option<&SourceFile>) -> Option<(u32, u32)> {
+    Some(member_position(m.line?, m.column?, base, source))
+}
+
+/// One member line of the human format: signature, then position if known.
+#[cfg(unix)]
+fn write_member_row(
+    output: &mut String,
+    m: &MemberInfo,
+    base: u32,
+    source: Option<&SourceFile>,
+    s: Styler,
+) {
+    let sig = m.signature.as_deref().unwrap_or(&m.name);
+    match member_location(m, base, source) {
+        Some((line, col)) => {
+            let loc = format!(":{line}:{col}");
+            let _ = writeln!(output, "    {sig:<60} {}", s.line_col(&loc));
+        }
+        None => {
+            let _ = writeln!(output, "    {sig}");
+        }
+    }
+}
+
 /// Format members as human-readable text for a single class.
 #[cfg(unix)]
-fn format_members_human(result: &MembersResult, file_path: &str, s: Styler) -> String {
+fn format_members_human(
+    result: &MembersResult,
+    file_path: &str,
+    base: u32,
+    source: Option<&SourceFile>,
+    s: Styler,
+) -> String {
     let mut output = String::new();
 
-    let class_line = result.class_line + 1;
-    let class_col = result.class_column + 1;
+    let (class_line, class_col) =
+        member_position(result.class_line, result.class_column, base, source);
     let _ = writeln!(
         output,
         "{} ({})",
@@ -1392,33 +3105,21 @@ fn format_members_human(result: &MembersResult, file_path: &str, s: Styler) -> S
     if !methods.is_empty() {
         let _ = writeln!(output, "  {}:", s.heading("Methods"));
         for m in &methods {
-            let sig = m.signature.as_deref().unwrap_or(&m.name);
-            let line = m.line + 1;
-            let col = m.column + 1;
-            let loc = format!(":{line}:{col}");
-            let _ = writeln!(output, "    {sig:<60} {}", s.line_col(&loc));
+            write_member_row(&mut output, m, base, source, s);
         }
     }
 
     if !properties.is_empty() {
         let _ = writeln!(output, "  {}:", s.heading("Properties"));
         for m in &properties {
-            let sig = m.signature.as_deref().unwrap_or(&m.name);
-            let line = m.line + 1;
-            let col = m.column + 1;
-            let loc = format!(":{line}:{col}");
-            let _ = writeln!(output, "    {sig:<60} {}", s.line_col(&loc));
+            write_member_row(&mut output, m, base, source, s);
         }
     }
 
     if !class_vars.is_empty() {
         let _ = writeln!(output, "  {}:", s.heading("Class variables"));
         for m in &class_vars {
-            let sig = m.signature.as_deref().unwrap_or(&m.name);
-            let line = m.line + 1;
-            let col = m.column + 1;
-            let loc = format!(":{line}:{col}");
-            let _ = writeln!(output, "    {sig:<60} {}", s.line_col(&loc));
+            write_member_row(&mut output, m, base, source, s);
         }
     }
 
@@ -1428,20 +3129,22 @@ fn format_members_human(result: &MembersResult, file_path: &str, s: Styler) -> S
 #[cfg(unix)]
 impl OutputFormatter {
     /// Format a single class members result.
-    pub fn format_members_result(&self, result: &MembersResult) -> String {
+    pub fn format_members_result(&self, result: &MembersResult, cache: &SourceCache) -> String {
         let file_path = self.uri_to_path(&result.file_uri);
+        let source = cache.get_uri(&result.file_uri);
 
         match self.format {
-            OutputFormat::Human => format_members_human(result, &file_path, self.s),
-            OutputFormat::Json => {
-                serde_json::to_string_pretty(result).unwrap_or_else(|_| "{}".to_string())
+            OutputFormat::Human | OutputFormat::Dot => {
+                format_members_human(result, &file_path, self.base, source, self.s)
             }
+            OutputFormat::Json => self.json(result),
+            OutputFormat::Jsonl => json_lines(self.member_records(result, cache)),
             OutputFormat::Csv => {
                 let mut output = String::from("class,member,kind,signature,line,column\n");
                 for m in &result.members {
                     let sig = m.signature.as_deref().unwrap_or("");
-                    let line = m.line + 1;
-                    let col = m.column + 1;
+                    let (line, col) = member_location(m, self.base, source)
+                        .map_or_else(Default::default, |(l, c)| (l.to_string(), c.to_string()));
                     let _ = writeln!(
                         output,
                         "{},{},{},\"{}\",{line},{col}",
@@ -1457,142 +3160,3435 @@ impl OutputFormatter {
         }
     }
 
-    /// Format results for one or more class members queries.
-    pub fn format_members_results(&self, results: &[MembersResult]) -> String {
-        if results.len() == 1 {
-            return self.format_members_result(&results[0]);
-        }
+    /// One JSON lines record per member of `result`.
+    fn member_records(
+        &self,
+        result: &MembersResult,
+        cache: &SourceCache,
+    ) -> Vec<serde_json::Value> {
+        let file = self.abs_path(&result.file_uri);
+        let source = cache.get_uri(&result.file_uri);
+        result
+            .members
+            .iter()
+            .map(|m| {
+                let (line, column) = member_location(m, self.base, source).unzip();
+                serde_json::json!({
+                    "class": result.class_name,
+                    "name": m.name,
+                    "kind": Self::kind_label(&m.kind),
+                    "signature": m.signature,
+                    "file": file,
+                    "line": line,
+                    "column": column,
+                })
+            })
+            .collect()
+    }
 
+    /// Format the ty server's identity and the LSP capabilities it advertised.
+    pub fn format_ty_info(&self, info: &TyInfoResult) -> String {
+        let supports = |provider: &str| info.providers.iter().any(|p| p == provider);
         match self.format {
-            OutputFormat::Human => {
-                let mut output = String::new();
-                for result in results {
-                    output.push_str(&self.format_members_result(result));
-                    output.push('\n');
+            OutputFormat::Human | OutputFormat::Dot => {
+                let name = info.name.as_deref().unwrap_or("ty (no serverInfo reported)");
+                let mut output = match &info.version {
+                    Some(version) => format!("{} {version}\n", self.s.symbol(name)),
+                    None => format!("{}\n", self.s.symbol(name)),
+                };
+                let _ = writeln!(output, "Workspace: {}", info.workspace);
+                let _ = writeln!(output, "\n{}", self.s.heading("Used by tyf:"));
+                for (provider, commands) in TYF_PROVIDERS {
+                    let mark = if supports(provider) { "yes" } else { "no " };
+                    let _ = writeln!(output, "  {mark}  {provider:<26} {}", self.s.dim(commands));
                 }
-                output.trim_end().to_string()
-            }
-            OutputFormat::Json => {
-                serde_json::to_string_pretty(results).unwrap_or_else(|_| "[]".to_string())
+                let others: Vec<&str> = info
+                    .providers
+                    .iter()
+                    .map(String::as_str)
+                    .filter(|p| !TYF_PROVIDERS.iter().any(|(known, _)| known == p))
+                    .collect();
+                if !others.is_empty() {
+                    let _ = writeln!(output, "\n{}", self.s.heading("Also advertised:"));
+                    for provider in others {
+                        let _ = writeln!(output, "  {provider}");
+                    }
+                }
+                output.trim_end().to_string()
             }
+            OutputFormat::Json => self.json(info),
+            OutputFormat::Jsonl => serde_json::to_string(info).unwrap_or_else(|_| "{}".to_string()),
             OutputFormat::Csv => {
-                let mut output = String::from("class,member,kind,signature,line,column\n");
-                for result in results {
-                    let file_path = self.uri_to_path(&result.file_uri);
-                    let _ = file_path; // included in class context
-                    for m in &result.members {
-                        let sig = m.signature.as_deref().unwrap_or("");
-                        let line = m.line + 1;
-                        let col = m.column + 1;
+                let mut output = String::from("provider,supported\n");
+                for (provider, _) in TYF_PROVIDERS {
+                    let _ = writeln!(output, "{provider},{}", supports(provider));
+                }
+                output
+            }
+            OutputFormat::Paths => info.workspace.clone(),
+        }
+    }
+
+    /// Format the daemon's request counters and workspace footprint, for `tyf daemon stats`.
+    #[cfg(unix)]
+    pub fn format_daemon_stats(&self, stats: &StatsResult) -> String {
+        let bucket = |bucket: Option<&LatencyBucket>| match bucket.map(|b| b.le_ms) {
+            Some(Some(ms)) => format!("<={ms}ms"),
+            Some(None) => {
+                format!(">{}ms", BUCKET_BOUNDS_MS[BUCKET_BOUNDS_MS.len() - 1])
+            }
+            None => "-".to_string(),
+        };
+        match self.format {
+            OutputFormat::Human | OutputFormat::Dot => {
+                let (mins, secs) = (stats.uptime / 60, stats.uptime % 60);
+                let mut output = format!(
+                    "Uptime: {}  Requests: {}  Server starts: {} ({} restart(s))\n",
+                    if mins > 0 { format!("{mins}m {secs}s") } else { format!("{secs}s") },
+                    stats.requests,
+                    stats.lsp_starts,
+                    stats.lsp_restarts,
+                );
+                if !stats.methods.is_empty() {
+                    let width = stats.methods.iter().map(|m| m.method.len()).fold(6, usize::max);
+                    let _ = writeln!(
+                        output,
+                        "\n{}",
+                        self.s.heading(&format!(
+                            "{:<width$}  {:>6}  {:>6}  {:>9}  {:>9}  {:>9}  {:>8}",
+                            "method", "calls", "errors", "mean", "p50", "p95", "max"
+                        ))
+                    );
+                    for m in &stats.methods {
                         let _ = writeln!(
                             output,
-                            "{},{},{},\"{}\",{line},{col}",
-                            result.class_name,
-                            m.name,
-                            Self::kind_label(&m.kind),
-                            sig.replace('"', "\"\""),
+                            "{:<width$}  {:>6}  {:>6}  {:>9}  {:>9}  {:>9}  {:>8}",
+                            m.method,
+                            m.calls,
+                            m.errors,
+                            format!("{:.1}ms", m.mean_ms()),
+                            bucket(m.percentile(0.5)),
+                            bucket(m.percentile(0.95)),
+                            format!("{}ms", m.max_ms),
                         );
                     }
                 }
+                if !stats.workspaces.is_empty() {
+                    let _ = writeln!(output, "\n{}", self.s.heading("Workspaces:"));
+                }
+                for ws in &stats.workspaces {
+                    let server =
+                        ws.server_rss_bytes.map_or_else(|| "unknown".to_string(), format_bytes);
+                    let _ = writeln!(
+                        output,
+                        "  {}\n    ~{}: server {server}, index {} ({} files, {} symbols), \
+                         cache {} ({} responses), {} restart(s)",
+                        ws.workspace,
+                        format_bytes(ws.estimated_bytes),
+                        format_bytes(ws.index_bytes),
+                        ws.index_files,
+                        ws.index_symbols,
+                        format_bytes(ws.cache_bytes),
+                        ws.cache_entries,
+                        ws.lsp_restarts,
+                    );
+                }
+                output.trim_end().to_string()
+            }
+            OutputFormat::Json => self.json(stats),
+            OutputFormat::Jsonl => {
+                serde_json::to_string(stats).unwrap_or_else(|_| "{}".to_string())
+            }
+            OutputFormat::Csv => {
+                let mut output = String::from("method,calls,errors,mean_ms,p50,p95,max_ms\n");
+                for m in &stats.methods {
+                    let _ = writeln!(
+                        output,
+                        "{},{},{},{:.1},{},{},{}",
+                        m.method,
+                        m.calls,
+                        m.errors,
+                        m.mean_ms(),
+                        bucket(m.percentile(0.5)),
+                        bucket(m.percentile(0.95)),
+                        m.max_ms
+                    );
+                }
+                output
+            }
+            OutputFormat::Paths => stats
+                .workspaces
+                .iter()
+                .map(|ws| ws.workspace.as_str())
+                .collect::<Vec<_>>()
+                .join("\n"),
+        }
+    }
+
+    /// Format the daemon's pooled workspaces, for `tyf daemon workspaces`.
+    #[cfg(unix)]
+    pub fn format_daemon_workspaces(&self, result: &WorkspacesResult) -> String {
+        match self.format {
+            OutputFormat::Human | OutputFormat::Dot => {
+                if result.workspaces.is_empty() {
+                    return "No workspaces loaded".to_string();
+                }
+                let idle = |secs: u64| match (secs / 3600, secs / 60 % 60, secs % 60) {
+                    (0, 0, s) => format!("{s}s"),
+                    (0, m, s) => format!("{m}m {s}s"),
+                    (h, m, _) => format!("{h}h {m}m"),
+                };
+                let mut output = String::new();
+                for ws in &result.workspaces {
+                    let mut details = vec![ws.server.clone().unwrap_or_else(|| "starting".into())];
+                    if let Some(pid) = ws.server_pid {
+                        details.push(format!("pid {pid}"));
+                    }
+                    details.push(format!("idle {}", idle(ws.idle_secs)));
+                    if ws.restarts > 0 {
+                        details.push(format!("restarted {} time(s)", ws.restarts));
+                    }
+                    if ws.exited {
+                        details.push("exited".to_string());
+                    }
+                    let _ = writeln!(
+                        output,
+                        "{}  {}",
+                        ws.workspace,
+                        self.s.dim(&format!("({})", details.join(", ")))
+                    );
+                }
+                output.trim_end().to_string()
+            }
+            OutputFormat::Json => self.json(result),
+            OutputFormat::Jsonl => result
+                .workspaces
+                .iter()
+                .map(|ws| serde_json::to_string(ws).unwrap_or_else(|_| "{}".to_string()))
+                .collect::<Vec<_>>()
+                .join("\n"),
+            OutputFormat::Csv => {
+                let mut output =
+                    String::from("workspace,backend,server,server_pid,idle_secs,restarts,exited\n");
+                for ws in &result.workspaces {
+                    let _ = writeln!(
+                        output,
+                        "{},{},{},{},{},{},{}",
+                        ws.workspace,
+                        ws.backend,
+                        ws.server.as_deref().unwrap_or(""),
+                        ws.server_pid.map(|pid| pid.to_string()).unwrap_or_default(),
+                        ws.idle_secs,
+                        ws.restarts,
+                        ws.exited
+                    );
+                }
+                output
+            }
+            OutputFormat::Paths => result
+                .workspaces
+                .iter()
+                .map(|ws| ws.workspace.as_str())
+                .collect::<Vec<_>>()
+                .join("\n"),
+        }
+    }
+
+    /// Format the settings in effect, for `tyf config show`.
+    pub fn format_config(&self, report: &ConfigReport) -> String {
+        let value_text = |value: &serde_json::Value| match value {
+            serde_json::Value::String(text) => text.clone(),
+            serde_json::Value::Array(items) if items.is_empty() => "(none)".to_string(),
+            serde_json::Value::Array(items) => items
+                .iter()
+                .map(|item| item.as_str().map_or_else(|| item.to_string(), ToString::to_string))
+                .collect::<Vec<_>>()
+                .join(", "),
+            other => other.to_string(),
+        };
+        match self.format {
+            OutputFormat::Human | OutputFormat::Dot => {
+                let key_width = report.settings.iter().map(|s| s.key.len()).max().unwrap_or(0);
+                let values: Vec<String> =
+                    report.settings.iter().map(|s| value_text(&s.value)).collect();
+                let value_width = values.iter().map(String::len).max().unwrap_or(0);
+                let mut output = String::new();
+                for (setting, value) in report.settings.iter().zip(&values) {
+                    let _ = writeln!(
+                        output,
+                        "{:<key_width$}  {value:<value_width$}  {}",
+                        setting.key,
+                        self.s.dim(&setting.source)
+                    );
+                }
+                let _ = writeln!(output, "\n{}", self.s.heading("Config files:"));
+                for file in &report.files {
+                    let status = if file.status == "read" {
+                        String::new()
+                    } else {
+                        format!(" ({})", file.status)
+                    };
+                    let _ = writeln!(output, "  {}{}", file.path, self.s.dim(&status));
+                }
+                output.trim_end().to_string()
+            }
+            OutputFormat::Json => self.json(report),
+            OutputFormat::Jsonl => {
+                json_lines(report.settings.iter().filter_map(|s| serde_json::to_value(s).ok()))
+            }
+            OutputFormat::Csv => {
+                let mut output = String::from("key,value,source\n");
+                for setting in &report.settings {
+                    let _ = writeln!(
+                        output,
+                        "{},\"{}\",\"{}\"",
+                        setting.key,
+                        value_text(&setting.value).replace('"', "\"\""),
+                        setting.source.replace('"', "\"\"")
+                    );
+                }
+                output
+            }
+            OutputFormat::Paths => report
+                .files
+                .iter()
+                .filter(|file| file.status == "read")
+                .map(|file| file.path.as_str())
+                .collect::<Vec<_>>()
+                .join("\n"),
+        }
+    }
+
+    /// Format the aliases in effect, for `tyf alias list`.
+    pub fn format_aliases(&self, aliases: &[AliasEntry]) -> String {
+        match self.format {
+            OutputFormat::Human | OutputFormat::Dot => {
+                if aliases.is_empty() {
+                    return "No aliases. Add one with: tyf alias add NAME \"COMMAND\"".to_string();
+                }
+                let name_width = aliases.iter().map(|a| a.name.len()).max().unwrap_or(0);
+                let command_width = aliases.iter().map(|a| a.command.len()).max().unwrap_or(0);
+                let mut output = String::new();
+                for alias in aliases {
+                    let _ = writeln!(
+                        output,
+                        "{}  {:<command_width$}  {}",
+                        self.s.symbol(&format!("{:<name_width$}", alias.name)),
+                        alias.command,
+                        self.s.dim(&alias.source)
+                    );
+                }
+                output.trim_end().to_string()
+            }
+            OutputFormat::Json => self.json(aliases),
+            OutputFormat::Jsonl => {
+                json_lines(aliases.iter().filter_map(|a| serde_json::to_value(a).ok()))
+            }
+            OutputFormat::Csv => {
+                let mut output = String::from("name,command,source\n");
+                for alias in aliases {
+                    let _ = writeln!(
+                        output,
+                        "{},\"{}\",\"{}\"",
+                        alias.name,
+                        alias.command.replace('"', "\"\""),
+                        alias.source.replace('"', "\"\"")
+                    );
+                }
+                output
+            }
+            OutputFormat::Paths => {
+                let mut sources: Vec<&str> = Vec::new();
+                for alias in aliases {
+                    if !sources.contains(&alias.source.as_str()) {
+                        sources.push(&alias.source);
+                    }
+                }
+                sources.join("\n")
+            }
+        }
+    }
+
+    /// Format the checks run by `tyf doctor`.
+    #[cfg(unix)]
+    pub fn format_doctor_report(&self, report: &DoctorReport) -> String {
+        match self.format {
+            OutputFormat::Human | OutputFormat::Dot => {
+                let mut output = String::new();
+                for check in &report.checks {
+                    let mark = format!("{:<4}", check.status.label());
+                    let mark = match check.status {
+                        Status::Fail => self.s.error(&mark),
+                        Status::Warn | Status::Skip => self.s.dim(&mark),
+                        Status::Ok => mark,
+                    };
+                    let _ = writeln!(output, "{mark}  {:<16} {}", check.name, check.detail);
+                    if let Some(fix) = &check.fix {
+                        let _ = writeln!(output, "{:<22} {}", "", self.s.dim(&format!("-> {fix}")));
+                    }
+                }
+                output.trim_end().to_string()
+            }
+            OutputFormat::Json => self.json(report),
+            OutputFormat::Jsonl => {
+                json_lines(report.checks.iter().filter_map(|c| serde_json::to_value(c).ok()))
+            }
+            OutputFormat::Csv => {
+                let mut output = String::from("check,status,detail,fix\n");
+                for check in &report.checks {
+                    let _ = writeln!(
+                        output,
+                        "{},{},\"{}\",\"{}\"",
+                        check.name,
+                        check.status.label().to_lowercase(),
+                        check.detail.replace('"', "\"\""),
+                        check.fix.as_deref().unwrap_or_default().replace('"', "\"\"")
+                    );
+                }
+                output
+            }
+            OutputFormat::Paths => report.workspace.clone(),
+        }
+    }
+
+    /// Format the latency percentiles measured by `tyf bench`.
+    #[cfg(unix)]
+    pub fn format_bench_report(&self, report: &BenchReport) -> String {
+        match self.format {
+            OutputFormat::Human | OutputFormat::Dot => {
+                let ty =
+                    report.ty_version.as_deref().map_or_else(String::new, |v| format!(", ty {v}"));
+                let mut output = format!("{}{ty}\n", self.s.heading(&report.workspace));
+                let _ = writeln!(output, "Target: {}\n", self.s.symbol(&report.target));
+                let _ = writeln!(
+                    output,
+                    "{}",
+                    self.s.dim(&format!(
+                        "{:<18} {:>5} {:>10} {:>10} {:>10} {:>10}",
+                        "benchmark", "runs", "p50", "p90", "p99", "max"
+                    ))
+                );
+                for stat in &report.results {
+                    let _ = writeln!(
+                        output,
+                        "{:<18} {:>5} {:>8.1}ms {:>8.1}ms {:>8.1}ms {:>8.1}ms",
+                        stat.name, stat.samples, stat.p50_ms, stat.p90_ms, stat.p99_ms, stat.max_ms
+                    );
+                }
+                output.trim_end().to_string()
+            }
+            OutputFormat::Json => self.json(report),
+            OutputFormat::Jsonl => {
+                json_lines(report.results.iter().filter_map(|r| serde_json::to_value(r).ok()))
+            }
+            OutputFormat::Csv => {
+                let mut output = String::from("benchmark,samples,p50_ms,p90_ms,p99_ms,max_ms\n");
+                for stat in &report.results {
+                    let _ = writeln!(
+                        output,
+                        "{},{},{:.3},{:.3},{:.3},{:.3}",
+                        stat.name, stat.samples, stat.p50_ms, stat.p90_ms, stat.p99_ms, stat.max_ms
+                    );
+                }
+                output
+            }
+            OutputFormat::Paths => report.workspace.clone(),
+        }
+    }
+
+    /// Format the text matches of `tyf grep` that resolve to its target.
+    pub fn format_grep_results(&self, result: &GrepResult, cache: &SourceCache) -> String {
+        match self.format {
+            OutputFormat::Human | OutputFormat::Dot => {
+                if result.hits.is_empty() {
+                    let message = format!(
+                        "No matches for '{}' resolve to {} ({} text match(es))",
+                        result.pattern, result.target, result.text_matches
+                    );
+                    return self.s.error(&message);
+                }
+                let heading = format!(
+                    "{} of {} match(es) for '{}' resolve to {}:",
+                    result.hits.len(),
+                    result.text_matches,
+                    result.pattern,
+                    result.target
+                );
+                let mut output = format!("{}\n\n", self.s.heading(&heading));
+                for (i, location) in result.hits.iter().enumerate() {
+                    let file_path = self.uri_to_path(&location.uri);
+                    let (line, column) = self.position(cache, location);
+                    let _ = writeln!(
+                        output,
+                        "{}. {}",
+                        i + 1,
+                        self.s.file_location(&file_path, line, column)
+                    );
+                    if let Some(src) =
+                        read_source_line(cache, &location.uri, location.range.start.line + 1)
+                    {
+                        let _ = writeln!(output, "   {src}");
+                    }
+                    output.push('\n');
+                }
+                output.trim_end().to_string()
+            }
+            OutputFormat::Json | OutputFormat::Jsonl => {
+                let matches: Vec<serde_json::Value> = result
+                    .hits
+                    .iter()
+                    .map(|location| {
+                        let (line, column) = self.position(cache, location);
+                        let record = serde_json::json!({
+                            "file": self.abs_path(&location.uri),
+                            "line": line,
+                            "column": column,
+                            "text": read_source_line(cache, &location.uri, location.range.start.line + 1),
+                        });
+                        self.with_range(record, location, cache)
+                    })
+                    .collect();
+                if self.format == OutputFormat::Jsonl {
+                    return json_lines(matches);
+                }
+                let val = serde_json::json!({
+                    "pattern": result.pattern,
+                    "resolves_to": result.target,
+                    "text_matches": result.text_matches,
+                    "matches": matches,
+                });
+                self.json(&val)
+            }
+            OutputFormat::Csv => {
+                let mut output = format!("file,line,column,text{}\n", self.csv_range_header());
+                for location in &result.hits {
+                    let file_path = self.uri_to_path(&location.uri);
+                    let (line, column) = self.position(cache, location);
+                    let text =
+                        read_source_line(cache, &location.uri, location.range.start.line + 1)
+                            .unwrap_or_default();
+                    let _ = writeln!(
+                        output,
+                        "{file_path},{line},{column},\"{}\"{}",
+                        text.replace('"', "\"\""),
+                        self.csv_range(location, cache)
+                    );
+                }
                 output
             }
             OutputFormat::Paths => {
                 let mut paths: Vec<String> =
-                    results.iter().map(|r| self.uri_to_path(&r.file_uri)).collect();
+                    result.hits.iter().map(|loc| self.uri_to_path(&loc.uri)).collect();
                 paths.sort();
                 paths.dedup();
                 paths.join("\n")
             }
         }
     }
-}
 
-fn format_document_symbols_recursive(
-    symbols: &[DocumentSymbol],
-    indent: usize,
-    output: &mut String,
-) {
-    for symbol in symbols {
-        let line = symbol.range.start.line + 1;
-        let column = symbol.range.start.character + 1;
-        let indent_str = "  ".repeat(indent);
+    /// Format the call sites `tyf sig-check` found incompatible with the proposed signature.
+    #[allow(clippy::too_many_lines)]
+    pub fn format_sig_check(&self, result: &SigCheckResult, cache: &SourceCache) -> String {
+        match self.format {
+            OutputFormat::Human | OutputFormat::Dot => {
+                let mut output = format!("{}\n", self.s.symbol(&result.symbol));
+                if let Some(current) = &result.current_signature {
+                    // ty spreads long signatures over several lines; keep it on one.
+                    let current = current.lines().map(str::trim).collect::<Vec<_>>().join(" ");
+                    let current = current.replace("( ", "(").replace(" )", ")");
+                    let _ = writeln!(output, "  current:  {current}");
+                }
+                let _ = writeln!(output, "  proposed: {}\n", result.new_signature);
+
+                if result.breaking.is_empty() {
+                    let _ = writeln!(
+                        output,
+                        "No call sites would break ({} checked)",
+                        result.call_sites - result.unchecked.len()
+                    );
+                } else {
+                    let heading = format!(
+                        "{} of {} call site(s) would break:",
+                        result.breaking.len(),
+                        result.call_sites
+                    );
+                    let _ = writeln!(output, "{}\n", self.s.heading(&heading));
+                    for (i, call) in result.breaking.iter().enumerate() {
+                        self.write_call_site(&mut output, i + 1, &call.location, cache);
+                        for problem in &call.problems {
+                            let _ = writeln!(output, "   {}", self.s.error(problem));
+                        }
+                        output.push('\n');
+                    }
+                }
+
+                if !result.unchecked.is_empty() {
+                    let heading = format!(
+                        "{} call site(s) unpack *args or **kwargs and could not be checked:",
+                        result.unchecked.len()
+                    );
+                    let _ = writeln!(output, "\n{}\n", self.s.heading(&heading));
+                    for (i, location) in result.unchecked.iter().enumerate() {
+                        self.write_call_site(&mut output, i + 1, location, cache);
+                        output.push('\n');
+                    }
+                }
+                output.trim_end().to_string()
+            }
+            OutputFormat::Json | OutputFormat::Jsonl => {
+                let site = |location: &Location| {
+                    let (line, column) = self.position(cache, location);
+                    serde_json::json!({
+                        "file": self.abs_path(&location.uri),
+                        "line": line,
+                        "column": column,
+                        "text": read_source_line(cache, &location.uri, location.range.start.line + 1),
+                    })
+                };
+                let breaking: Vec<serde_json::Value> = result
+                    .breaking
+                    .iter()
+                    .map(|call| {
+                        let mut value = site(&call.location);
+                        value["problems"] = serde_json::json!(call.problems);
+                        value
+                    })
+                    .collect();
+                if self.format == OutputFormat::Jsonl {
+                    let breaking = breaking.into_iter().map(|v| with_field(v, "status", "breaks"));
+                    let unchecked = result
+                        .unchecked
+                        .iter()
+                        .map(|loc| with_field(site(loc), "status", "unchecked"));
+                    return json_lines(breaking.chain(unchecked));
+                }
+                let val = serde_json::json!({
+                    "symbol": result.symbol,
+                    "current_signature": result.current_signature,
+                    "new_signature": result.new_signature,
+                    "call_sites": result.call_sites,
+                    "breaking": breaking,
+                    "unchecked": result.unchecked.iter().map(site).collect::<Vec<_>>(),
+                });
+                self.json(&val)
+            }
+            OutputFormat::Csv => {
+                let mut output = String::from("file,line,column,status,problem\n");
+                let rows = result
+                    .breaking
+                    .iter()
+                    .flat_map(|call| {
+                        call.problems.iter().map(|p| (&call.location, "breaks", p.as_str()))
+                    })
+                    .chain(result.unchecked.iter().map(|loc| (loc, "unchecked", "")));
+                for (location, status, problem) in rows {
+                    let file_path = self.uri_to_path(&location.uri);
+                    let (line, column) = self.position(cache, location);
+                    let _ = writeln!(
+                        output,
+                        "{file_path},{line},{column},{status},\"{}\"",
+                        problem.replace('"', "\"\"")
+                    );
+                }
+                output
+            }
+            OutputFormat::Paths => {
+                let mut paths: Vec<String> = result
+                    .breaking
+                    .iter()
+                    .map(|call| self.uri_to_path(&call.location.uri))
+                    .collect();
+                paths.sort();
+                paths.dedup();
+                paths.join("\n")
+            }
+        }
+    }
+
+    /// Format a file annotated with inferred types by `tyf annotate`.
+    ///
+    /// Human output is the whole file, annotated inline with `# type:`
+    /// comments or, with `side_by_side`, in a column beside each line.
+    pub fn format_annotate(
+        &self,
+        result: &AnnotateResult,
+        cache: &SourceCache,
+        side_by_side: bool,
+    ) -> String {
+        use crate::annotate::type_comment;
+
+        let source = cache.get_uri(&result.uri);
+        let position = |annotation: &crate::annotate::Annotation| {
+            let start = Position { line: annotation.line, character: annotation.character };
+            display_position(&start, self.base, source)
+        };
+        match self.format {
+            OutputFormat::Human | OutputFormat::Dot => {
+                let Some(source) = source else {
+                    return self.s.error(&format!("Could not read {}", self.abs_path(&result.uri)));
+                };
+                let mut by_line: HashMap<u32, Vec<&crate::annotate::Annotation>> = HashMap::new();
+                for annotation in &result.annotations {
+                    by_line.entry(annotation.line).or_default().push(annotation);
+                }
+                let width = source.lines().map(|l| l.chars().count()).max().unwrap_or(0).min(80);
+                let mut output = String::new();
+                for (idx, line) in source.lines().enumerate() {
+                    let comment = u32::try_from(idx)
+                        .ok()
+                        .and_then(|n| by_line.get(&n))
+                        .map(|annotations| type_comment(annotations));
+                    match (comment, side_by_side) {
+                        (Some(comment), false) => {
+                            let _ = writeln!(
+                                output,
+                                "{line}  {}",
+                                self.s.dim(&format!("# type: {comment}"))
+                            );
+                        }
+                        (comment, true) => {
+                            let pad = width.saturating_sub(line.chars().count());
+                            let _ = writeln!(
+                                output,
+                                "{line}{:pad$} \u{2502} {}",
+                                "",
+                                self.s.dim(&comment.unwrap_or_default())
+                            );
+                        }
+                        (None, false) => {
+                            let _ = writeln!(output, "{line}");
+                        }
+                    }
+                }
+                output.lines().map(str::trim_end).collect::<Vec<_>>().join("\n")
+            }
+            OutputFormat::Json | OutputFormat::Jsonl => {
+                let annotations: Vec<serde_json::Value> = result
+                    .annotations
+                    .iter()
+                    .map(|annotation| {
+                        let (line, column) = position(annotation);
+                        serde_json::json!({
+                            "line": line,
+                            "column": column,
+                            "kind": annotation.kind.label(),
+                            "name": annotation.name,
+                            "type": annotation.ty,
+                        })
+                    })
+                    .collect();
+                let file = self.abs_path(&result.uri);
+                if self.format == OutputFormat::Jsonl {
+                    return json_lines(
+                        annotations.into_iter().map(|a| with_field(a, "file", file.as_str())),
+                    );
+                }
+                let val = serde_json::json!({
+                    "file": file,
+                    "annotations": annotations,
+                });
+                self.json(&val)
+            }
+            OutputFormat::Csv => {
+                let file_path = self.uri_to_path(&result.uri);
+                let mut output = String::from("file,line,column,kind,name,type\n");
+                for annotation in &result.annotations {
+                    let (line, column) = position(annotation);
+                    let _ = writeln!(
+                        output,
+                        "{file_path},{line},{column},{},{},\"{}\"",
+                        annotation.kind.label(),
+                        annotation.name,
+                        annotation.ty.replace('"', "\"\"")
+                    );
+                }
+                output
+            }
+            OutputFormat::Paths => self.uri_to_path(&result.uri),
+        }
+    }
+
+    /// Format an attribute chain resolved by `tyf where`, one hop per line.
+    #[cfg(unix)]
+    pub fn format_where(&self, result: &WhereResult, cache: &SourceCache) -> String {
+        let label = |hop: &WhereHop| {
+            hop.owner
+                .as_ref()
+                .map_or_else(|| hop.name.clone(), |owner| format!("{owner}.{}", hop.name))
+        };
+        match self.format {
+            OutputFormat::Human | OutputFormat::Dot => {
+                let mut output = format!("{}\n", self.s.heading(&result.expression));
+                let width = result.hops.iter().map(|hop| label(hop).chars().count()).max();
+                for hop in &result.hops {
+                    let label = label(hop);
+                    let pad = width.unwrap_or(0) - label.chars().count();
+                    let _ = write!(
+                        output,
+                        "  {}{:pad$}  {}",
+                        self.s.symbol(&label),
+                        "",
+                        hop.ty.as_deref().unwrap_or("?")
+                    );
+                    if let Some(location) = &hop.location {
+                        let file_path = self.uri_to_path(&location.uri);
+                        let (line, column) = self.position(cache, location);
+                        let _ =
+                            write!(output, "  {}", self.s.file_location(&file_path, line, column));
+                    }
+                    output.push('\n');
+                }
+                if let Some(reason) = &result.unresolved {
+                    let _ = writeln!(output, "  {}", self.s.error(reason));
+                }
+                output.trim_end().to_string()
+            }
+            OutputFormat::Json | OutputFormat::Jsonl => {
+                let hops: Vec<serde_json::Value> = result
+                    .hops
+                    .iter()
+                    .map(|hop| {
+                        let mut val = serde_json::json!({
+                            "name": hop.name,
+                            "class": hop.owner,
+                            "type": hop.ty,
+                        });
+                        if let Some(location) = &hop.location {
+                            let (line, column) = self.position(cache, location);
+                            val["file"] = serde_json::json!(self.abs_path(&location.uri));
+                            val["line"] = serde_json::json!(line);
+                            val["column"] = serde_json::json!(column);
+                        }
+                        val
+                    })
+                    .collect();
+                if self.format == OutputFormat::Jsonl {
+                    return json_lines(hops);
+                }
+                let val = serde_json::json!({
+                    "expression": result.expression,
+                    "hops": hops,
+                    "unresolved": result.unresolved,
+                });
+                self.json(&val)
+            }
+            OutputFormat::Csv => {
+                let mut output = String::from("name,class,type,file,line,column\n");
+                for hop in &result.hops {
+                    let (file_path, line, column) = hop.location.as_ref().map_or_else(
+                        || (String::new(), String::new(), String::new()),
+                        |location| {
+                            let (line, column) = self.position(cache, location);
+                            (self.uri_to_path(&location.uri), line.to_string(), column.to_string())
+                        },
+                    );
+                    let _ = writeln!(
+                        output,
+                        "{},{},\"{}\",{file_path},{line},{column}",
+                        hop.name,
+                        hop.owner.as_deref().unwrap_or_default(),
+                        hop.ty.as_deref().unwrap_or_default().replace('"', "\"\"")
+                    );
+                }
+                output
+            }
+            OutputFormat::Paths => {
+                let mut paths: Vec<String> = Vec::new();
+                for location in result.hops.iter().filter_map(|hop| hop.location.as_ref()) {
+                    let path = self.uri_to_path(&location.uri);
+                    if !paths.contains(&path) {
+                        paths.push(path);
+                    }
+                }
+                paths.join("\n")
+            }
+        }
+    }
+
+    /// Format a module move: a unified diff of every file it changes.
+    ///
+    /// The summary goes to the end of human output, and says how to apply
+    /// the changes when they were only previewed.
+    #[cfg(unix)]
+    #[allow(clippy::too_many_lines)]
+    pub fn format_move(&self, result: &MoveResult) -> String {
+        let display = |path: &Path| self.uri_to_path(&crate::lsp::uri::path_to_uri(path));
+        let new_label = |file: &MovedFile| {
+            if file.path == result.old_path {
+                display(&result.new_path)
+            } else {
+                display(&file.path)
+            }
+        };
+        match self.format {
+            OutputFormat::Human | OutputFormat::Dot => {
+                let mut output = format!(
+                    "{} {} {} {}\n",
+                    self.s.heading(if result.moved { "Moved" } else { "Move" }),
+                    display(&result.old_path),
+                    self.s.dim("\u{2192}"),
+                    display(&result.new_path)
+                );
+                let _ = writeln!(
+                    output,
+                    "  {}\n",
+                    self.s.dim(&format!("{} \u{2192} {}", result.change.old, result.change.new))
+                );
+                for file in &result.files {
+                    output.push_str(&crate::refactor::edit::unified_diff(
+                        &display(&file.path),
+                        &new_label(file),
+                        &file.text,
+                        &file.edits,
+                    ));
+                }
+                let files = result.files.iter().filter(|f| !f.edits.is_empty()).count();
+                let _ = write!(
+                    output,
+                    "\n{} {} import(s) in {files} file(s)",
+                    match (result.applied, result.moved) {
+                        (true, false) => "Moved the file and updated",
+                        (true, true) => "Updated",
+                        (false, _) => "Would update",
+                    },
+                    result.imports,
+                );
+                if result.skipped > 0 {
+                    let _ = write!(
+                        output,
+                        " ({} import(s) of another module with the same name left alone)",
+                        result.skipped
+                    );
+                }
+                if !result.applied {
+                    output.push_str(if result.moved {
+                        ". Re-run with --apply to write the changes."
+                    } else {
+                        ". Re-run with --apply to move the file and write the changes."
+                    });
+                }
+                output
+            }
+            OutputFormat::Json => {
+                let files: Vec<serde_json::Value> = result
+                    .files
+                    .iter()
+                    .map(|file| {
+                        let lines: Vec<&str> = file.text.lines().collect();
+                        let changes: Vec<serde_json::Value> = file
+                            .edits
+                            .iter()
+                            .map(|edit| {
+                                serde_json::json!({
+                                    "line": edit.line + self.base as usize,
+                                    "old": lines.get(edit.line).copied().unwrap_or_default(),
+                                    "new": edit.new_lines,
+                                })
+                            })
+                            .collect();
+                        serde_json::json!({ "file": new_label(file), "changes": changes })
+                    })
+                    .collect();
+                let val = serde_json::json!({
+                    "old_path": display(&result.old_path),
+                    "new_path": display(&result.new_path),
+                    "old_module": result.change.old,
+                    "new_module": result.change.new,
+                    "imports": result.imports,
+                    "skipped": result.skipped,
+                    "applied": result.applied,
+                    "moved": result.moved,
+                    "files": files,
+                });
+                self.json(&val)
+            }
+            OutputFormat::Jsonl => json_lines(result.files.iter().flat_map(|file| {
+                let lines: Vec<&str> = file.text.lines().collect();
+                file.edits
+                    .iter()
+                    .map(|edit| {
+                        serde_json::json!({
+                            "file": new_label(file),
+                            "line": edit.line + self.base as usize,
+                            "old": lines.get(edit.line).copied().unwrap_or_default(),
+                            "new": edit.new_lines,
+                        })
+                    })
+                    .collect::<Vec<_>>()
+            })),
+            OutputFormat::Csv => {
+                let mut output = String::from("file,line,old,new\n");
+                for file in &result.files {
+                    let lines: Vec<&str> = file.text.lines().collect();
+                    for edit in &file.edits {
+                        let old = lines.get(edit.line).copied().unwrap_or_default();
+                        let _ = writeln!(
+                            output,
+                            "{},{},\"{}\",\"{}\"",
+                            new_label(file),
+                            edit.line + self.base as usize,
+                            old.replace('"', "\"\""),
+                            edit.new_lines.join("\n").replace('"', "\"\"")
+                        );
+                    }
+                }
+                output
+            }
+            OutputFormat::Paths => {
+                result.files.iter().map(new_label).collect::<Vec<_>>().join("\n")
+            }
+        }
+    }
+
+    /// Format a module's imports and importers for `tyf imports`.
+    ///
+    /// `--format dot` draws them as a Graphviz digraph, with imports of
+    /// modules outside the workspace dashed.
+    #[cfg(unix)]
+    #[allow(clippy::too_many_lines)]
+    pub fn format_imports(&self, result: &ImportsResult) -> String {
+        let display = |path: &Path| self.uri_to_path(&crate::lsp::uri::path_to_uri(path));
+        let file = |edge: &ImportEdge| edge.path.as_deref().map(display);
+        let sections = [
+            ("imports", &result.imports),
+            ("external", &result.external),
+            ("imported_by", &result.imported_by),
+        ];
+        match self.format {
+            OutputFormat::Human => {
+                let mut output = format!(
+                    "{} {}  {}\n",
+                    self.s.heading("Module"),
+                    self.s.symbol(&result.module),
+                    self.s.dim(&display(&result.path))
+                );
+                // Importers are located at their import statement; imported
+                // modules by their file, with the line importing them.
+                for (title, edges, importers) in [
+                    ("Imports", &result.imports, false),
+                    ("External imports", &result.external, false),
+                    ("Imported by", &result.imported_by, true),
+                ] {
+                    let _ = writeln!(output, "\n{} ({})", self.s.heading(title), edges.len());
+                    for edge in edges {
+                        let line = edge.line + self.base as usize;
+                        let path = file(edge).unwrap_or_else(|| "unresolved".to_string());
+                        let location = if importers {
+                            format!("{path}:{line}")
+                        } else {
+                            format!("{path}  line {line}")
+                        };
+                        let _ = writeln!(
+                            output,
+                            "  {}  {}",
+                            self.s.symbol(&edge.module),
+                            self.s.dim(&location)
+                        );
+                    }
+                }
+                output.trim_end().to_string()
+            }
+            OutputFormat::Json => {
+                let edges = |edges: &[ImportEdge]| -> Vec<serde_json::Value> {
+                    edges
+                        .iter()
+                        .map(|edge| {
+                            serde_json::json!({
+                                "module": edge.module,
+                                "file": file(edge),
+                                "line": edge.line + self.base as usize,
+                            })
+                        })
+                        .collect()
+                };
+                let val = serde_json::json!({
+                    "module": result.module,
+                    "file": display(&result.path),
+                    "imports": edges(&result.imports),
+                    "external": edges(&result.external),
+                    "imported_by": edges(&result.imported_by),
+                });
+                self.json(&val)
+            }
+            OutputFormat::Jsonl => json_lines(sections.iter().flat_map(|(direction, edges)| {
+                edges.iter().map(move |edge| {
+                    serde_json::json!({
+                        "direction": direction,
+                        "module": edge.module,
+                        "file": file(edge),
+                        "line": edge.line + self.base as usize,
+                    })
+                })
+            })),
+            OutputFormat::Csv => {
+                let mut output = String::from("direction,module,file,line\n");
+                for (direction, edges) in sections {
+                    for edge in edges {
+                        let _ = writeln!(
+                            output,
+                            "{direction},{},{},{}",
+                            edge.module,
+                            file(edge).unwrap_or_default(),
+                            edge.line + self.base as usize
+                        );
+                    }
+                }
+                output
+            }
+            OutputFormat::Paths => {
+                let mut paths: Vec<String> = Vec::new();
+                for edge in result.imports.iter().chain(&result.imported_by) {
+                    if let Some(path) = file(edge).filter(|p| !paths.contains(p)) {
+                        paths.push(path);
+                    }
+                }
+                paths.join("\n")
+            }
+            OutputFormat::Dot => {
+                let quote = |name: &str| format!("\"{}\"", name.replace('"', "\\\""));
+                let module = quote(&result.module);
+                let mut output = format!("digraph imports {{\n  {module} [style=bold];\n");
+                let mut seen = std::collections::HashSet::new();
+                for (direction, edges) in sections {
+                    for edge in edges {
+                        let other = quote(&edge.module);
+                        let line = match direction {
+                            "imports" => format!("  {module} -> {other};"),
+                            "external" => format!("  {module} -> {other} [style=dashed];"),
+                            _ => format!("  {other} -> {module};"),
+                        };
+                        if seen.insert(line.clone()) {
+                            let _ = writeln!(output, "{line}");
+                        }
+                    }
+                }
+                output.push('}');
+                output
+            }
+        }
+    }
+
+    /// Format the diagnostics reported by `tyf check`.
+    pub fn format_check(&self, result: &CheckResult, cache: &SourceCache) -> String {
+        let rows = result.files.iter().flat_map(|file| {
+            file.diagnostics.iter().map(move |diagnostic| {
+                let (line, column) =
+                    display_position(&diagnostic.range.start, self.base, cache.get_uri(&file.uri));
+                (file, diagnostic, line, column)
+            })
+        });
+        match self.format {
+            OutputFormat::Human | OutputFormat::Dot => {
+                let mut output = String::new();
+                for (file, diagnostic, line, column) in rows {
+                    let label = self.severity_label(diagnostic);
+                    let file_path = self.uri_to_path(&file.uri);
+                    let _ = writeln!(
+                        output,
+                        "{} {label}: {}",
+                        self.s.file_location(&file_path, line, column),
+                        diagnostic.message
+                    );
+                    if let Some(src) =
+                        read_source_line(cache, &file.uri, diagnostic.range.start.line + 1)
+                    {
+                        let _ = writeln!(output, "   {src}");
+                    }
+                    output.push('\n');
+                }
+                output.push_str(&self.check_summary(result));
+                output
+            }
+            OutputFormat::Json | OutputFormat::Jsonl => {
+                let diagnostics: Vec<serde_json::Value> = rows
+                    .map(|(file, diagnostic, line, column)| {
+                        serde_json::json!({
+                            "file": self.abs_path(&file.uri),
+                            "line": line,
+                            "column": column,
+                            "severity": diagnostic.severity.label(),
+                            "code": diagnostic.code,
+                            "message": diagnostic.message,
+                        })
+                    })
+                    .collect();
+                if self.format == OutputFormat::Jsonl {
+                    return json_lines(diagnostics);
+                }
+                let val = serde_json::json!({
+                    "files_checked": result.files_checked,
+                    "errors": result.count(DiagnosticSeverity::Error),
+                    "warnings": result.count(DiagnosticSeverity::Warning),
+                    "baselined": result.baselined,
+                    "fixed": result.fixed,
+                    "below_severity": result.below_severity,
+                    "diagnostics": diagnostics,
+                });
+                self.json(&val)
+            }
+            OutputFormat::Csv => {
+                let mut output = String::from("file,line,column,severity,code,message\n");
+                for (file, diagnostic, line, column) in rows {
+                    let _ = writeln!(
+                        output,
+                        "{},{line},{column},{},{},\"{}\"",
+                        self.uri_to_path(&file.uri),
+                        diagnostic.severity.label(),
+                        diagnostic.code.as_deref().unwrap_or_default(),
+                        diagnostic.message.replace('"', "\"\"")
+                    );
+                }
+                output
+            }
+            OutputFormat::Paths => result
+                .files
+                .iter()
+                .map(|file| self.uri_to_path(&file.uri))
+                .collect::<Vec<_>>()
+                .join("\n"),
+        }
+    }
+
+    fn explained_type(identifier: &ExplainedIdentifier) -> Option<String> {
+        identifier.hover.as_ref().map(|hover| Self::extract_hover_type(&hover.contents))
+    }
+
+    /// `error[code]`, colored by severity.
+    fn severity_label(&self, diagnostic: &Diagnostic) -> String {
+        let label = match &diagnostic.code {
+            Some(code) => format!("{}[{code}]", diagnostic.severity.label()),
+            None => diagnostic.severity.label().to_string(),
+        };
+        match diagnostic.severity {
+            DiagnosticSeverity::Error => self.s.error(&label),
+            DiagnosticSeverity::Warning => self.s.warning(&label),
+            _ => self.s.dim(&label),
+        }
+    }
+
+    /// Format what `explain` found on a line: each identifier's type and
+    /// definitions, then the diagnostics touching the line.
+    pub fn format_line_explanation(
+        &self,
+        explanation: &LineExplanation,
+        cache: &SourceCache,
+    ) -> String {
+        let content = cache.get_uri(&explanation.uri);
+        let at =
+            |line, character| display_position(&Position { line, character }, self.base, content);
+        let type_of = Self::explained_type;
+        match self.format {
+            OutputFormat::Human | OutputFormat::Dot => {
+                self.format_line_explanation_human(explanation, cache)
+            }
+            OutputFormat::Json | OutputFormat::Jsonl => {
+                let names = explanation.identifiers.iter().map(|identifier| {
+                    let (line, column) = at(explanation.line, identifier.column);
+                    let definitions: Vec<_> = identifier
+                        .definitions
+                        .iter()
+                        .map(|location| self.location_record(location, cache))
+                        .collect();
+                    serde_json::json!({
+                        "name": identifier.name,
+                        "line": line,
+                        "column": column,
+                        "type": type_of(identifier),
+                        "definitions": definitions,
+                    })
+                });
+                let diagnostics = explanation.diagnostics.iter().map(|diagnostic| {
+                    let (line, column) =
+                        at(diagnostic.range.start.line, diagnostic.range.start.character);
+                    serde_json::json!({
+                        "line": line,
+                        "column": column,
+                        "severity": diagnostic.severity.label(),
+                        "code": diagnostic.code,
+                        "message": diagnostic.message,
+                    })
+                });
+                if self.format == OutputFormat::Jsonl {
+                    return json_lines(names.chain(diagnostics));
+                }
+                let (line, _) = at(explanation.line, 0);
+                let val = serde_json::json!({
+                    "file": self.abs_path(&explanation.uri),
+                    "line": line,
+                    "source": read_source_line(cache, &explanation.uri, explanation.line + 1),
+                    "names": names.collect::<Vec<_>>(),
+                    "diagnostics": diagnostics.collect::<Vec<_>>(),
+                });
+                self.json(&val)
+            }
+            OutputFormat::Csv => {
+                let quote = |text: &str| format!("\"{}\"", text.replace('"', "\"\""));
+                let mut output = String::from("entry,line,column,detail,definition\n");
+                for identifier in &explanation.identifiers {
+                    let (line, column) = at(explanation.line, identifier.column);
+                    let definition = identifier.definitions.first().map(|location| {
+                        let (line, column) = self.position(cache, location);
+                        format!("{}:{line}:{column}", self.uri_to_path(&location.uri))
+                    });
+                    let _ = writeln!(
+                        output,
+                        "{},{line},{column},{},{}",
+                        identifier.name,
+                        quote(&type_of(identifier).unwrap_or_default()),
+                        definition.unwrap_or_default()
+                    );
+                }
+                for diagnostic in &explanation.diagnostics {
+                    let (line, column) =
+                        at(diagnostic.range.start.line, diagnostic.range.start.character);
+                    let label = match &diagnostic.code {
+                        Some(code) => format!("{}[{code}]", diagnostic.severity.label()),
+                        None => diagnostic.severity.label().to_string(),
+                    };
+                    let _ =
+                        writeln!(output, "{label},{line},{column},{},", quote(&diagnostic.message));
+                }
+                output
+            }
+            OutputFormat::Paths => {
+                let mut paths: Vec<String> = explanation
+                    .identifiers
+                    .iter()
+                    .flat_map(|identifier| &identifier.definitions)
+                    .map(|location| self.uri_to_path(&location.uri))
+                    .collect();
+                paths.sort();
+                paths.dedup();
+                paths.join("\n")
+            }
+        }
+    }
+
+    fn format_line_explanation_human(
+        &self,
+        explanation: &LineExplanation,
+        cache: &SourceCache,
+    ) -> String {
+        let content = cache.get_uri(&explanation.uri);
+        let at =
+            |line, character| display_position(&Position { line, character }, self.base, content);
+        let file_path = self.uri_to_path(&explanation.uri);
+        let (line, _) = at(explanation.line, 0);
+        let mut output = format!("{}\n", self.s.heading(&format!("{file_path}:{line}")));
+        if let Some(src) = read_source_line(cache, &explanation.uri, explanation.line + 1) {
+            let _ = writeln!(output, "  {src}");
+        }
+        output.push('\n');
+        let width =
+            explanation.identifiers.iter().map(|i| i.name.chars().count()).max().unwrap_or(0);
+        let indent = " ".repeat(width + 2);
+        for identifier in &explanation.identifiers {
+            let padding = " ".repeat(width - identifier.name.chars().count() + 2);
+            let _ = write!(output, "{}{padding}", self.s.symbol(&identifier.name));
+            match Self::explained_type(identifier) {
+                Some(signature) if !signature.is_empty() => {
+                    for (idx, text) in signature.lines().enumerate() {
+                        let prefix = if idx == 0 { "" } else { indent.as_str() };
+                        let _ = writeln!(output, "{prefix}{text}");
+                    }
+                }
+                _ => {
+                    let _ = writeln!(output, "{}", self.s.dim("no type"));
+                }
+            }
+            for location in &identifier.definitions {
+                let (line, column) = self.position(cache, location);
+                let _ = writeln!(
+                    output,
+                    "{indent}{} {}{}",
+                    self.s.dim("defined at"),
+                    self.s.file_location(&self.uri_to_path(&location.uri), line, column),
+                    self.origin_tag(&location.uri),
+                );
+            }
+        }
+        if !explanation.diagnostics.is_empty() {
+            output.push('\n');
+        }
+        for diagnostic in &explanation.diagnostics {
+            let (line, column) = at(diagnostic.range.start.line, diagnostic.range.start.character);
+            let _ = writeln!(
+                output,
+                "{} {}: {}",
+                self.s.file_location(&file_path, line, column),
+                self.severity_label(diagnostic),
+                diagnostic.message
+            );
+        }
+        output.trim_end().to_string()
+    }
+
+    /// Format a `stats` run as a per-file table with a total.
+    #[cfg(unix)]
+    pub fn format_code_stats(&self, result: &CodeStatsResult) -> String {
+        let (lines, total) = result.total();
+        match self.format {
+            OutputFormat::Human | OutputFormat::Dot => {
+                let mut output = String::new();
+                let paths: Vec<String> =
+                    result.files.iter().map(|f| self.uri_to_path(&f.uri)).collect();
+                if !paths.is_empty() {
+                    let width = paths.iter().map(String::len).max().unwrap_or(0).max("file".len());
+                    let header =
+                        format!("{:<width$}  {:>7}  classes  functions  methods", "file", "lines");
+                    let _ = writeln!(output, "{}", self.s.heading(&header));
+                    for (path, f) in paths.iter().zip(&result.files) {
+                        let c = f.counts;
+                        let _ = writeln!(
+                            output,
+                            "{path:<width$}  {:>7}  {:>7}  {:>9}  {:>7}",
+                            f.lines, c.classes, c.functions, c.methods
+                        );
+                    }
+                    output.push('\n');
+                }
+                let summary = format!(
+                    "{} file(s), {lines} line(s): {} class(es), {} function(s), {} method(s)",
+                    result.files.len(),
+                    total.classes,
+                    total.functions,
+                    total.methods
+                );
+                output.push_str(&self.s.heading(&summary));
+                if !result.failed.is_empty() {
+                    let _ = write!(
+                        output,
+                        "\n{}",
+                        self.s
+                            .error(&format!("Could not outline {} file(s):", result.failed.len()))
+                    );
+                    for (uri, error) in &result.failed {
+                        let _ = write!(output, "\n  {}: {error}", self.uri_to_path(uri));
+                    }
+                }
+                output
+            }
+            OutputFormat::Json | OutputFormat::Jsonl => {
+                let files: Vec<serde_json::Value> = result
+                    .files
+                    .iter()
+                    .map(|f| {
+                        serde_json::json!({
+                            "file": self.abs_path(&f.uri),
+                            "lines": f.lines,
+                            "classes": f.counts.classes,
+                            "functions": f.counts.functions,
+                            "methods": f.counts.methods,
+                        })
+                    })
+                    .collect();
+                let failed: Vec<serde_json::Value> = result
+                    .failed
+                    .iter()
+                    .map(|(uri, error)| {
+                        serde_json::json!({ "file": self.abs_path(uri), "error": error })
+                    })
+                    .collect();
+                if self.format == OutputFormat::Jsonl {
+                    return json_lines(files.into_iter().chain(failed));
+                }
+                let val = serde_json::json!({
+                    "total": {
+                        "files": result.files.len(),
+                        "lines": lines,
+                        "classes": total.classes,
+                        "functions": total.functions,
+                        "methods": total.methods,
+                    },
+                    "files": files,
+                    "failed": failed,
+                });
+                self.json(&val)
+            }
+            OutputFormat::Csv => {
+                let mut output = String::from("file,lines,classes,functions,methods\n");
+                for f in &result.files {
+                    let _ = writeln!(
+                        output,
+                        "{},{},{},{},{}",
+                        self.uri_to_path(&f.uri),
+                        f.lines,
+                        f.counts.classes,
+                        f.counts.functions,
+                        f.counts.methods
+                    );
+                }
+                output
+            }
+            OutputFormat::Paths => {
+                result.files.iter().map(|f| self.uri_to_path(&f.uri)).collect::<Vec<_>>().join("\n")
+            }
+        }
+    }
+
+    /// Format a `check-all` run as a per-file summary table.
+    #[cfg(unix)]
+    pub fn format_check_all(&self, result: &CheckAllResult) -> String {
+        let counts = result.counts();
+        let (errors, warnings, notes) = counts
+            .iter()
+            .fold((0, 0, 0), |(e, w, n), c| (e + c.errors, w + c.warnings, n + c.notes));
+        match self.format {
+            OutputFormat::Human | OutputFormat::Dot => {
+                let mut output = String::new();
+                let paths: Vec<String> = counts.iter().map(|c| self.uri_to_path(c.uri)).collect();
+                if !counts.is_empty() {
+                    let width = paths.iter().map(String::len).max().unwrap_or(0).max("file".len());
+                    let header = format!("{:<width$}  errors  warnings  notes", "file");
+                    let _ = writeln!(output, "{}", self.s.heading(&header));
+                    for (path, c) in paths.iter().zip(&counts) {
+                        let _ = writeln!(
+                            output,
+                            "{path:<width$}  {:>6}  {:>8}  {:>5}",
+                            c.errors, c.warnings, c.notes
+                        );
+                    }
+                    output.push('\n');
+                }
+                let summary = if counts.is_empty() {
+                    format!("No diagnostics in {} file(s)", result.files_checked)
+                } else {
+                    let mut summary = format!("Found {errors} error(s), {warnings} warning(s)");
+                    if notes > 0 {
+                        let _ = write!(summary, ", {notes} note(s)");
+                    }
+                    let _ =
+                        write!(summary, " in {} of {} file(s)", counts.len(), result.files_checked);
+                    summary
+                };
+                let summary =
+                    if errors > 0 { self.s.error(&summary) } else { self.s.heading(&summary) };
+                output.push_str(&summary);
+                if !result.failed.is_empty() {
+                    let _ = write!(
+                        output,
+                        "\n{}",
+                        self.s.error(&format!("Could not check {} file(s):", result.failed.len()))
+                    );
+                    for (uri, error) in &result.failed {
+                        let _ = write!(output, "\n  {}: {error}", self.uri_to_path(uri));
+                    }
+                }
+                output
+            }
+            OutputFormat::Json | OutputFormat::Jsonl => {
+                let files: Vec<serde_json::Value> = counts
+                    .iter()
+                    .map(|c| {
+                        serde_json::json!({
+                            "file": self.abs_path(c.uri),
+                            "errors": c.errors,
+                            "warnings": c.warnings,
+                            "notes": c.notes,
+                        })
+                    })
+                    .collect();
+                let failed: Vec<serde_json::Value> = result
+                    .failed
+                    .iter()
+                    .map(|(uri, error)| {
+                        serde_json::json!({ "file": self.abs_path(uri), "error": error })
+                    })
+                    .collect();
+                if self.format == OutputFormat::Jsonl {
+                    return json_lines(files.into_iter().chain(failed));
+                }
+                let val = serde_json::json!({
+                    "files_checked": result.files_checked,
+                    "errors": errors,
+                    "warnings": warnings,
+                    "notes": notes,
+                    "files": files,
+                    "failed": failed,
+                });
+                self.json(&val)
+            }
+            OutputFormat::Csv => {
+                let mut output = String::from("file,errors,warnings,notes\n");
+                for c in &counts {
+                    let _ = writeln!(
+                        output,
+                        "{},{},{},{}",
+                        self.uri_to_path(c.uri),
+                        c.errors,
+                        c.warnings,
+                        c.notes
+                    );
+                }
+                output
+            }
+            OutputFormat::Paths => {
+                counts.iter().map(|c| self.uri_to_path(c.uri)).collect::<Vec<_>>().join("\n")
+            }
+        }
+    }
+
+    /// Format the symbols `tyf unused` found no reference to.
+    #[cfg(unix)]
+    pub fn format_unused(&self, result: &UnusedResult, cache: &SourceCache) -> String {
+        match self.format {
+            OutputFormat::Human | OutputFormat::Dot => {
+                let mut summary = if result.symbols.is_empty() {
+                    format!(
+                        "No unused symbols among {} checked in {} file(s)",
+                        result.symbols_checked, result.files_scanned
+                    )
+                } else {
+                    format!(
+                        "{} of {} symbol(s) in {} file(s) are never referenced",
+                        result.symbols.len(),
+                        result.symbols_checked,
+                        result.files_scanned
+                    )
+                };
+                if result.unresolved > 0 {
+                    let _ = write!(summary, " ({} could not be resolved)", result.unresolved);
+                }
+                if result.symbols.is_empty() {
+                    return self.s.heading(&summary);
+                }
+                let mut output = format!("{}\n\n", self.s.heading(&format!("{summary}:")));
+                for symbol in &result.symbols {
+                    let file_path = self.uri_to_path(&symbol.location.uri);
+                    let (line, column) = self.position(cache, &symbol.location);
+                    let _ = writeln!(
+                        output,
+                        "{} {} ({})",
+                        self.s.file_location(&file_path, line, column),
+                        self.s.symbol(&symbol.name),
+                        Self::kind_label(&symbol.kind)
+                    );
+                }
+                output.trim_end().to_string()
+            }
+            OutputFormat::Json | OutputFormat::Jsonl => {
+                let symbols: Vec<serde_json::Value> = result
+                    .symbols
+                    .iter()
+                    .map(|symbol| {
+                        let record = self.location_record(&symbol.location, cache);
+                        let record = with_field(record, "name", symbol.name.as_str());
+                        with_field(record, "kind", format!("{:?}", symbol.kind))
+                    })
+                    .collect();
+                if self.format == OutputFormat::Jsonl {
+                    return json_lines(symbols);
+                }
+                let val = serde_json::json!({
+                    "files_scanned": result.files_scanned,
+                    "symbols_checked": result.symbols_checked,
+                    "unresolved": result.unresolved,
+                    "unused": symbols,
+                });
+                self.json(&val)
+            }
+            OutputFormat::Csv => {
+                let mut output = String::from("file,line,column,kind,name\n");
+                for symbol in &result.symbols {
+                    let (line, column) = self.position(cache, &symbol.location);
+                    let _ = writeln!(
+                        output,
+                        "{},{line},{column},{},{}",
+                        self.uri_to_path(&symbol.location.uri),
+                        Self::kind_label(&symbol.kind),
+                        symbol.name
+                    );
+                }
+                output
+            }
+            OutputFormat::Paths => {
+                let mut paths: Vec<String> =
+                    result.symbols.iter().map(|s| self.uri_to_path(&s.location.uri)).collect();
+                paths.dedup();
+                paths.join("\n")
+            }
+        }
+    }
+
+    /// Format the files `tyf rdeps` found referencing the target file.
+    #[cfg(unix)]
+    pub fn format_rdeps(&self, result: &RdepsResult) -> String {
+        let target = self.uri_to_path(&result.uri);
+        match self.format {
+            OutputFormat::Human | OutputFormat::Dot => {
+                if result.files.is_empty() {
+                    return self.s.heading(&format!(
+                        "No file references the {} symbol(s) defined in {target}",
+                        result.symbols_checked
+                    ));
+                }
+                let summary =
+                    format!("{} file(s) reference symbols from {target}:", result.files.len());
+                let mut output = format!("{}\n\n", self.s.heading(&summary));
+                for file in &result.files {
+                    let _ = writeln!(
+                        output,
+                        "{}  {}",
+                        self.uri_to_path(&file.uri),
+                        self.s.dim(&format!(
+                            "{} ({} reference(s))",
+                            file.symbols.join(", "),
+                            file.references
+                        ))
+                    );
+                }
+                output.trim_end().to_string()
+            }
+            OutputFormat::Json | OutputFormat::Jsonl => {
+                let files = result.files.iter().map(|file| {
+                    serde_json::json!({
+                        "file": self.abs_path(&file.uri),
+                        "references": file.references,
+                        "symbols": file.symbols,
+                    })
+                });
+                if self.format == OutputFormat::Jsonl {
+                    return json_lines(files);
+                }
+                let val = serde_json::json!({
+                    "file": self.abs_path(&result.uri),
+                    "symbols_checked": result.symbols_checked,
+                    "dependents": files.collect::<Vec<_>>(),
+                });
+                self.json(&val)
+            }
+            OutputFormat::Csv => {
+                let mut output = String::from("file,references,symbols\n");
+                for file in &result.files {
+                    let _ = writeln!(
+                        output,
+                        "{},{},{}",
+                        self.uri_to_path(&file.uri),
+                        file.references,
+                        file.symbols.join(";")
+                    );
+                }
+                output
+            }
+            OutputFormat::Paths => result
+                .files
+                .iter()
+                .map(|file| self.uri_to_path(&file.uri))
+                .collect::<Vec<_>>()
+                .join("\n"),
+        }
+    }
+
+    /// Format the public API `tyf api` found; the human format is Markdown.
+    #[cfg(unix)]
+    pub fn format_api(&self, result: &ApiResult, cache: &SourceCache) -> String {
+        match self.format {
+            OutputFormat::Human | OutputFormat::Dot => {
+                if result.modules.is_empty() {
+                    return format!("No public classes or functions in {}", result.target);
+                }
+                let mut output = format!("# API of {}\n", result.target);
+                for module in &result.modules {
+                    let _ = write!(
+                        output,
+                        "\n## {}\n\nDefined in `{}`.\n",
+                        module.module,
+                        self.uri_to_path(&module.uri)
+                    );
+                    for item in &module.symbols {
+                        let _ = write!(output, "\n### `{}`\n", Self::api_signature(item));
+                        if let Some(summary) = Self::api_summary(item) {
+                            let _ = write!(output, "\n{summary}\n");
+                        }
+                        if !item.members.is_empty() {
+                            output.push('\n');
+                        }
+                        for member in &item.members {
+                            let _ = write!(output, "- `{}`", Self::api_signature(member));
+                            if let Some(summary) = Self::api_summary(member) {
+                                let _ = write!(output, " \u{2014} {summary}");
+                            }
+                            output.push('\n');
+                        }
+                    }
+                }
+                output.trim_end().to_string()
+            }
+            OutputFormat::Json => {
+                let modules: Vec<serde_json::Value> = result
+                    .modules
+                    .iter()
+                    .map(|module| {
+                        serde_json::json!({
+                            "module": module.module,
+                            "file": self.abs_path(&module.uri),
+                            "symbols": module
+                                .symbols
+                                .iter()
+                                .map(|item| self.api_record(item, cache))
+                                .collect::<Vec<_>>(),
+                        })
+                    })
+                    .collect();
+                let val = serde_json::json!({ "target": result.target, "modules": modules });
+                self.json(&val)
+            }
+            OutputFormat::Jsonl => json_lines(result.modules.iter().flat_map(|module| {
+                module.symbols.iter().flat_map(move |item| {
+                    let members = item.members.iter().map(move |member| {
+                        let record = with_field(
+                            self.api_record(member, cache),
+                            "name",
+                            format!("{}.{}", item.name, member.name),
+                        );
+                        with_field(record, "module", module.module.as_str())
+                    });
+                    let mut record = self.api_record(item, cache);
+                    if let Some(fields) = record.as_object_mut() {
+                        fields.remove("members");
+                    }
+                    std::iter::once(with_field(record, "module", module.module.as_str()))
+                        .chain(members)
+                })
+            })),
+            OutputFormat::Csv => {
+                let mut output = String::from("module,name,kind,file,line,signature\n");
+                for module in &result.modules {
+                    let path = self.uri_to_path(&module.uri);
+                    let rows = module.symbols.iter().flat_map(|item| {
+                        std::iter::once((item.name.clone(), item)).chain(
+                            item.members.iter().map(|m| (format!("{}.{}", item.name, m.name), m)),
+                        )
+                    });
+                    for (name, item) in rows {
+                        let (line, _) = self.position(cache, &item.location);
+                        let _ = writeln!(
+                            output,
+                            "{},{name},{},{path},{line},\"{}\"",
+                            module.module,
+                            Self::kind_label(&item.kind),
+                            Self::api_signature(item).replace('"', "\"\""),
+                        );
+                    }
+                }
+                output
+            }
+            OutputFormat::Paths => result
+                .modules
+                .iter()
+                .map(|module| self.uri_to_path(&module.uri))
+                .collect::<Vec<_>>()
+                .join("\n"),
+        }
+    }
+
+    /// How an `api` item is declared: `class Name`, or a signature from hover.
+    #[cfg(unix)]
+    fn api_signature(item: &ApiItem) -> String {
+        if item.kind == SymbolKind::Class {
+            return format!("class {}", item.name);
+        }
+        item.hover.as_ref().map_or_else(
+            || item.name.clone(),
+            |hover| crate::lsp::members::extract_member_signature(&hover.contents, &item.name),
+        )
+    }
+
+    /// The first paragraph of an `api` item's docstring, on one line.
+    #[cfg(unix)]
+    fn api_summary(item: &ApiItem) -> Option<String> {
+        let doc = Self::extract_hover_doc(&item.hover.as_ref()?.contents)?;
+        let paragraph = doc.split("\n\n").next().unwrap_or_default();
+        Some(paragraph.split_whitespace().collect::<Vec<_>>().join(" "))
+    }
+
+    /// JSON record of an `api` item, with its members for a class.
+    #[cfg(unix)]
+    fn api_record(&self, item: &ApiItem, cache: &SourceCache) -> serde_json::Value {
+        let record = self.location_record(&item.location, cache);
+        let record = with_field(record, "name", item.name.as_str());
+        let record = with_field(record, "kind", format!("{:?}", item.kind));
+        let record = with_field(record, "signature", Self::api_signature(item));
+        let record = with_field(record, "doc", Self::api_summary(item));
+        if item.kind != SymbolKind::Class {
+            return record;
+        }
+        let members: Vec<serde_json::Value> =
+            item.members.iter().map(|member| self.api_record(member, cache)).collect();
+        with_field(record, "members", members)
+    }
+
+    /// Format `refs --count` results, with the count per file when `by_file`.
+    pub fn format_reference_counts(&self, counts: &[ReferenceCount], by_file: bool) -> String {
+        match self.format {
+            OutputFormat::Human | OutputFormat::Dot => {
+                let mut output = String::new();
+                for count in counts {
+                    let summary = format!(
+                        "{}: {} reference(s) in {} file(s)",
+                        count.label,
+                        count.total(),
+                        count.files.len()
+                    );
+                    let _ = write!(output, "{}", self.s.heading(&summary));
+                    if count.hidden_tests > 0 {
+                        let hint = format!(
+                            " (+{} in tests, use --tests/-t to count them)",
+                            count.hidden_tests
+                        );
+                        let _ = write!(output, "{}", self.s.dim(&hint));
+                    }
+                    output.push('\n');
+                    if by_file {
+                        for (uri, n) in &count.files {
+                            let _ = writeln!(output, "  {n:>5}  {}", self.uri_to_path(uri));
+                        }
+                    }
+                }
+                output.trim_end().to_string()
+            }
+            OutputFormat::Json | OutputFormat::Jsonl => {
+                let records = counts.iter().map(|count| {
+                    let mut record = serde_json::json!({
+                        "symbol": count.label,
+                        "references": count.total(),
+                        "files": count.files.len(),
+                        "hidden_test_references": count.hidden_tests,
+                    });
+                    if by_file {
+                        let files: Vec<_> = count
+                            .files
+                            .iter()
+                            .map(|(uri, n)| {
+                                serde_json::json!({ "file": self.abs_path(uri), "references": n })
+                            })
+                            .collect();
+                        record["by_file"] = files.into();
+                    }
+                    record
+                });
+                if self.format == OutputFormat::Jsonl {
+                    return json_lines(records);
+                }
+                self.json(&records.collect::<Vec<_>>())
+            }
+            OutputFormat::Csv if by_file => {
+                let mut output = String::from("symbol,file,references\n");
+                for count in counts {
+                    for (uri, n) in &count.files {
+                        let _ = writeln!(output, "{},{},{n}", count.label, self.uri_to_path(uri));
+                    }
+                }
+                output
+            }
+            OutputFormat::Csv => {
+                let mut output = String::from("symbol,references,files\n");
+                for count in counts {
+                    let _ =
+                        writeln!(output, "{},{},{}", count.label, count.total(), count.files.len());
+                }
+                output
+            }
+            OutputFormat::Paths => {
+                let mut paths: Vec<String> = Vec::new();
+                for (uri, _) in counts.iter().flat_map(|count| &count.files) {
+                    let path = self.uri_to_path(uri);
+                    if !paths.contains(&path) {
+                        paths.push(path);
+                    }
+                }
+                paths.join("\n")
+            }
+        }
+    }
+
+    /// Closing line of human `check` output, e.g. `Found 2 errors in 1 of 3 file(s)`.
+    fn check_summary(&self, result: &CheckResult) -> String {
+        let errors = result.count(DiagnosticSeverity::Error);
+        let warnings = result.count(DiagnosticSeverity::Warning);
+        let others =
+            result.files.iter().map(|f| f.diagnostics.len()).sum::<usize>() - errors - warnings;
+        let mut counts = vec![format!("{errors} error(s)"), format!("{warnings} warning(s)")];
+        if others > 0 {
+            counts.push(format!("{others} note(s)"));
+        }
+        let mut summary = if result.files.is_empty() {
+            format!("No diagnostics in {} file(s)", result.files_checked)
+        } else {
+            format!(
+                "Found {} in {} of {} file(s)",
+                counts.join(", "),
+                result.files.len(),
+                result.files_checked
+            )
+        };
+        let mut hidden = Vec::new();
+        if result.baselined > 0 {
+            hidden.push(format!("{} in baseline", result.baselined));
+        }
+        if result.below_severity > 0 {
+            hidden.push(format!("{} below --severity", result.below_severity));
+        }
+        if !hidden.is_empty() {
+            let _ = write!(summary, " ({})", hidden.join(", "));
+        }
+        let mut summary =
+            if errors > 0 { self.s.error(&summary) } else { self.s.heading(&summary) };
+        if result.fixed > 0 {
+            let note = format!(
+                "{} baselined diagnostic(s) no longer occur; run with --update-baseline to drop them",
+                result.fixed
+            );
+            let _ = write!(summary, "\n{}", self.s.dim(&note));
+        }
+        summary
+    }
+
+    /// Write one numbered call site with its source line.
+    fn write_call_site(
+        &self,
+        output: &mut String,
+        number: usize,
+        location: &Location,
+        cache: &SourceCache,
+    ) {
+        let file_path = self.uri_to_path(&location.uri);
+        let (line, column) = self.position(cache, location);
+        let _ = writeln!(output, "{number}. {}", self.s.file_location(&file_path, line, column));
+        if let Some(src) = read_source_line(cache, &location.uri, location.range.start.line + 1) {
+            let _ = writeln!(output, "   {src}");
+        }
+    }
+
+    /// Format results for one or more class members queries.
+    pub fn format_members_results(&self, results: &[MembersResult], cache: &SourceCache) -> String {
+        if results.len() == 1 {
+            return self.format_members_result(&results[0], cache);
+        }
+
+        match self.format {
+            OutputFormat::Human | OutputFormat::Dot => {
+                let mut output = String::new();
+                for result in results {
+                    output.push_str(&self.format_members_result(result, cache));
+                    output.push('\n');
+                }
+                output.trim_end().to_string()
+            }
+            OutputFormat::Json => self.json(results),
+            OutputFormat::Jsonl => {
+                json_lines(results.iter().flat_map(|r| self.member_records(r, cache)))
+            }
+            OutputFormat::Csv => {
+                let mut output = String::from("class,member,kind,signature,line,column\n");
+                for result in results {
+                    let source = cache.get_uri(&result.file_uri);
+                    for m in &result.members {
+                        let sig = m.signature.as_deref().unwrap_or("");
+                        let (line, col) = member_location(m, self.base, source)
+                            .map_or_else(Default::default, |(l, c)| (l.to_string(), c.to_string()));
+                        let _ = writeln!(
+                            output,
+                            "{},{},{},\"{}\",{line},{col}",
+                            result.class_name,
+                            m.name,
+                            Self::kind_label(&m.kind),
+                            sig.replace('"', "\"\""),
+                        );
+                    }
+                }
+                output
+            }
+            OutputFormat::Paths => {
+                let mut paths: Vec<String> =
+                    results.iter().map(|r| self.uri_to_path(&r.file_uri)).collect();
+                paths.sort();
+                paths.dedup();
+                paths.join("\n")
+            }
+        }
+    }
+}
+
+fn format_document_symbols_recursive(
+    symbols: &[DocumentSymbol],
+    indent: usize,
+    base: u32,
+    source: Option<&SourceFile>,
+    output: &mut String,
+) {
+    for symbol in symbols {
+        let (line, column) = display_position(&symbol.range.start, base, source);
+        let indent_str = "  ".repeat(indent);
+
+        let _ = writeln!(
+            output,
+            "{indent_str}{} ({:?}) - line {line}, col {column}",
+            symbol.name, symbol.kind,
+        );
+
+        if let Some(children) = &symbol.children {
+            format_document_symbols_recursive(children, indent + 1, base, source, output);
+        }
+    }
+}
+
+fn format_document_symbols_csv(
+    symbols: &[DocumentSymbol],
+    base: u32,
+    source: Option<&SourceFile>,
+    output: &mut String,
+) {
+    for symbol in symbols {
+        let (line, column) = display_position(&symbol.range.start, base, source);
+
+        let _ = writeln!(output, "{},{:?},{line},{column}", symbol.name, symbol.kind);
+
+        if let Some(children) = &symbol.children {
+            format_document_symbols_csv(children, base, source, output);
+        }
+    }
+}
+
+/// Flatten a symbol tree into JSON lines records, each naming its container.
+fn document_symbol_records(
+    symbols: &[DocumentSymbol],
+    container: Option<&str>,
+    base: u32,
+    source: Option<&SourceFile>,
+    records: &mut Vec<serde_json::Value>,
+) {
+    for symbol in symbols {
+        let (line, column) = display_position(&symbol.range.start, base, source);
+        records.push(serde_json::json!({
+            "name": symbol.name,
+            "kind": format!("{:?}", symbol.kind),
+            "line": line,
+            "column": column,
+            "container": container,
+        }));
+        if let Some(children) = &symbol.children {
+            document_symbol_records(children, Some(&symbol.name), base, source, records);
+        }
+    }
+}
+
+/// Prints workspace symbols in parts as they arrive, the way
+/// [`OutputFormatter::format_workspace_symbols`] formats them all.
+pub struct SymbolsPrinter<'a> {
+    formatter: &'a OutputFormatter,
+    /// Symbols printed so far.
+    printed: usize,
+}
+
+impl SymbolsPrinter<'_> {
+    /// Print the next part and flush it to stdout.
+    pub fn print(&mut self, symbols: &[SymbolInformation], cache: &SourceCache) {
+        if symbols.is_empty() {
+            return;
+        }
+        let part = self.formatter.format_workspace_symbols_from(symbols, self.printed, cache);
+        // Line formats join their records with newlines.
+        let joined = self.printed > 0
+            && matches!(self.formatter.format, OutputFormat::Jsonl | OutputFormat::Paths);
+        print!("{}{part}", if joined { "\n" } else { "" });
+        let _ = std::io::Write::flush(&mut std::io::stdout());
+        self.printed += symbols.len();
+    }
+
+    /// Symbols printed so far.
+    pub const fn printed(&self) -> usize {
+        self.printed
+    }
+
+    /// End the output, if anything was printed.
+    pub fn finish(self) {
+        if self.printed > 0 {
+            println!();
+        }
+    }
+}
+
+/// Prints references results as they arrive, the way
+/// [`OutputFormatter::print_enriched_references_results`] prints them all.
+///
+/// Only for the formats [`OutputFormatter::prints_references_incrementally`]
+/// accepts.
+pub struct ReferencesPrinter<'a> {
+    formatter: &'a OutputFormatter,
+    grouped: bool,
+    sink: TrimEndSink<std::io::BufWriter<std::io::Stdout>>,
+    /// Notices for the results cut short, printed after machine-readable output.
+    truncated: Vec<String>,
+}
+
+impl ReferencesPrinter<'_> {
+    /// Print one result and flush it to stdout.
+    pub fn print(
+        &mut self,
+        result: &EnrichedReferencesResult,
+        cache: &SourceCache,
+    ) -> std::io::Result<()> {
+        let formatter = self.formatter;
+        let written = match formatter.format {
+            OutputFormat::Human | OutputFormat::Dot if self.grouped => {
+                formatter.write_enriched_references_grouped(&mut self.sink, result, cache)
+            }
+            _ => formatter.write_enriched_references_single(&mut self.sink, result, cache),
+        };
+        if formatter.format != OutputFormat::Human && result.omitted_count > 0 {
+            self.truncated.push(OutputFormatter::truncation_notice(result));
+        }
+        self.sink.flush(written)
+    }
+
+    /// End the output, then print the notices of results cut short.
+    pub fn finish(self) -> std::io::Result<()> {
+        self.sink.finish(Ok(()))?;
+        for notice in &self.truncated {
+            eprintln!("{}", self.formatter.s.dim(notice));
+        }
+        Ok(())
+    }
+}
+
+/// `fmt::Write` adapter that streams to an `io::Write`, dropping trailing whitespace.
+///
+/// Whitespace is held back until more text arrives, so the stream ends exactly
+/// like `println!("{}", output.trim_end())` would, without buffering `output`.
+struct TrimEndSink<W: std::io::Write> {
+    inner: W,
+    pending: String,
+    error: Option<std::io::Error>,
+}
+
+impl<W: std::io::Write> TrimEndSink<W> {
+    fn new(inner: W) -> Self {
+        Self { inner, pending: String::new(), error: None }
+    }
+
+    /// Flush what was written so far, except trailing whitespace, surfacing
+    /// any write error.
+    fn flush(&mut self, written: std::fmt::Result) -> std::io::Result<()> {
+        if let Some(e) = self.error.take() {
+            return Err(e);
+        }
+        if written.is_err() {
+            return Err(std::io::Error::other("formatting failed"));
+        }
+        self.inner.flush()
+    }
+
+    /// Terminate the output with a newline and flush, surfacing any earlier write error.
+    fn finish(mut self, written: std::fmt::Result) -> std::io::Result<()> {
+        if let Some(e) = self.error.take() {
+            return Err(e);
+        }
+        if written.is_err() {
+            return Err(std::io::Error::other("formatting failed"));
+        }
+        self.inner.write_all(b"\n")?;
+        self.inner.flush()
+    }
+}
+
+impl<W: std::io::Write> Write for TrimEndSink<W> {
+    fn write_str(&mut self, s: &str) -> std::fmt::Result {
+        let Some(last) = s.char_indices().rev().find(|(_, c)| !c.is_whitespace()) else {
+            self.pending.push_str(s);
+            return Ok(());
+        };
+        let end = last.0 + last.1.len_utf8();
+        let result = self
+            .inner
+            .write_all(self.pending.as_bytes())
+            .and_then(|()| self.inner.write_all(&s.as_bytes()[..end]));
+        self.pending.clear();
+        self.pending.push_str(&s[end..]);
+        result.map_err(|e| {
+            self.error = Some(e);
+            std::fmt::Error
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lsp::protocol::{Position, Range, SymbolKind};
+    use serde_json::json;
+
+    fn make_location(uri: &str, line: u32, character: u32) -> Location {
+        Location {
+            uri: uri.to_string(),
+            range: Range {
+                start: Position { line, character },
+                end: Position { line, character: character + 5 },
+            },
+        }
+    }
+
+    #[test]
+    fn test_format_definitions_empty() {
+        let formatter = OutputFormatter::new(OutputFormat::Human);
+        let result = formatter.format_definitions(&[], "test:1:1", &SourceCache::new());
+        assert_eq!(result, "No results found for: test:1:1");
+    }
+
+    #[test]
+    fn test_format_definitions_single() {
+        let formatter = OutputFormatter::new(OutputFormat::Human);
+        let locations = [make_location("file:///nonexistent.py", 5, 10)];
+        let result = formatter.format_definitions(&locations, "test:6:11", &SourceCache::new());
+
+        assert!(result.contains("Found 1 definition(s)"));
+        assert!(result.contains("nonexistent.py:6:11"));
+    }
+
+    #[test]
+    fn test_format_definitions_json() {
+        let formatter = OutputFormatter::new(OutputFormat::Json);
+        let locations = [make_location("file:///test.py", 0, 0)];
+        let result = formatter.format_definitions(&locations, "test", &SourceCache::new());
+
+        let parsed: serde_json::Value = serde_json::from_str(&result).unwrap();
+        assert!(parsed.is_array());
+        assert_eq!(parsed[0]["uri"], "file:///test.py");
+    }
+
+    #[test]
+    fn test_format_definitions_csv() {
+        let formatter = OutputFormatter::new(OutputFormat::Csv);
+        let locations = [make_location("file:///test.py", 4, 2)];
+        let result = formatter.format_definitions(&locations, "test", &SourceCache::new());
+
+        assert!(result.starts_with("file,line,column\n"));
+        assert!(result.contains("5,3")); // 0-based -> 1-based
+    }
+
+    #[test]
+    fn test_format_definitions_csv_zero_based() {
+        let formatter = OutputFormatter::new(OutputFormat::Csv).with_zero_based(true);
+        let locations = [make_location("file:///test.py", 4, 2)];
+        let result = formatter.format_definitions(&locations, "test", &SourceCache::new());
+
+        assert!(result.ends_with(",4,2\n"), "expected raw LSP coordinates, got: {result}");
+    }
+
+    #[test]
+    fn test_format_definitions_csv_converts_utf16_column_to_characters() {
+        let formatter = OutputFormatter::new(OutputFormat::Csv);
+        let cache = SourceCache::from_entries([(
+            "/src/app.py".to_string(),
+            "label = \"🎉\"; handler = 1\n".to_string(),
+        )]);
+        // `handler` is at UTF-16 column 15 but character column 14 (0-based)
+        let locations = [make_location("file:///src/app.py", 0, 15)];
+        let result = formatter.format_definitions(&locations, "handler", &cache);
+
+        assert!(result.ends_with(",1,15\n"), "expected 1-based character column, got: {result}");
+    }
+
+    #[test]
+    fn test_format_definitions_with_ranges() {
+        let cache = SourceCache::from_entries([(
+            "/src/app.py".to_string(),
+            "label = \"🎉\"; handler = 1\n".to_string(),
+        )]);
+        // `handler` spans UTF-16 columns 14..21, characters 13..20 (0-based)
+        let mut location = make_location("file:///src/app.py", 0, 14);
+        location.range.end.character = 21;
+        let locations = [location];
+
+        let csv = OutputFormatter::new(OutputFormat::Csv)
+            .with_ranges(true)
+            .format_definitions(&locations, "handler", &cache);
+        assert_eq!(
+            csv,
+            "file,line,column,end_line,end_column,matched\n/src/app.py,1,14,1,21,\"handler\"\n"
+        );
+
+        let jsonl = OutputFormatter::new(OutputFormat::Jsonl)
+            .with_ranges(true)
+            .format_definitions(&locations, "handler", &cache);
+        let record: serde_json::Value = serde_json::from_str(&jsonl).unwrap();
+        assert_eq!((&record["end_line"], &record["end_column"]), (&1.into(), &21.into()));
+        assert_eq!(record["matched"], "handler");
+
+        let json = OutputFormatter::new(OutputFormat::Json).with_ranges(true).format_definitions(
+            &locations,
+            "handler",
+            &SourceCache::new(),
+        );
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(value[0]["range"]["end"]["character"], 21, "the LSP range is kept");
+        assert_eq!(value[0]["end_column"], 22, "uncached files keep LSP columns");
+        assert!(value[0]["matched"].is_null());
+
+        let plain =
+            OutputFormatter::new(OutputFormat::Csv).format_definitions(&locations, "h", &cache);
+        assert_eq!(plain, "file,line,column\n/src/app.py,1,14\n");
+    }
+
+    #[test]
+    fn test_range_text() {
+        let file = SourceFile::new("def f():\n    return \"é\"\n".to_string());
+        let range = |(l1, c1), (l2, c2)| Range {
+            start: Position { line: l1, character: c1 },
+            end: Position { line: l2, character: c2 },
+        };
+        assert_eq!(range_text(&file, &range((0, 4), (0, 5))).as_deref(), Some("f"));
+        assert_eq!(range_text(&file, &range((1, 11), (1, 14))).as_deref(), Some("\"é\""));
+        assert_eq!(range_text(&file, &range((0, 7), (1, 10))).as_deref(), Some(":\n    return"));
+        assert_eq!(range_text(&file, &range((1, 4), (2, 0))), None);
+    }
+
+    #[test]
+    fn test_format_definitions_human_zero_based_keeps_source_line() {
+        let formatter = OutputFormatter::new(OutputFormat::Human).with_zero_based(true);
+        let cache = SourceCache::from_entries([(
+            "/src/app.py".to_string(),
+            "import os\ndef main():\n    pass\n".to_string(),
+        )]);
+        let locations = [make_location("file:///src/app.py", 1, 4)];
+        let result = formatter.format_definitions(&locations, "main", &cache);
+
+        assert!(result.contains("app.py:1:4"), "expected 0-based location, got: {result}");
+        assert!(result.contains("def main():"), "source line must not shift, got: {result}");
+    }
+
+    fn make_explain_result() -> ExplainTypeResult {
+        ExplainTypeResult {
+            query: "create_order".to_string(),
+            signature: Some("def create_order(payload: OrderPayload) -> None".to_string()),
+            expansions: vec![
+                TypeExpansion {
+                    name: "OrderPayload".to_string(),
+                    kind: "TypedDict",
+                    location: make_location("file:///types.py", 3, 6),
+                    depth: 1,
+                    definition: "class OrderPayload(TypedDict):".to_string(),
+                    members: vec!["customer: CustomerRef".to_string()],
+                },
+                TypeExpansion {
+                    name: "CustomerRef".to_string(),
+                    kind: "type alias",
+                    location: make_location("file:///types.py", 0, 0),
+                    depth: 2,
+                    definition: "CustomerRef = int | str".to_string(),
+                    members: Vec::new(),
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn test_format_explain_type_human_nests_by_depth() {
+        let formatter = OutputFormatter::new(OutputFormat::Human);
+        let result = formatter.format_explain_type(&make_explain_result(), &SourceCache::new());
+
+        assert!(result.starts_with("create_order\n  def create_order(payload: OrderPayload)"));
+        assert!(result.contains("OrderPayload (TypedDict) /types.py:4:7\n"));
+        assert!(result.contains("\n    customer: CustomerRef"));
+        assert!(result
+            .contains("\n  CustomerRef (type alias) /types.py:1:1\n    CustomerRef = int | str"));
+    }
+
+    #[test]
+    fn test_format_explain_type_not_found() {
+        let formatter = OutputFormatter::new(OutputFormat::Human);
+        let result = ExplainTypeResult {
+            query: "missing".to_string(),
+            signature: None,
+            expansions: Vec::new(),
+        };
+        assert_eq!(
+            formatter.format_explain_type(&result, &SourceCache::new()),
+            "No results found for: 'missing'"
+        );
+    }
+
+    #[test]
+    fn test_format_explain_type_json() {
+        let formatter = OutputFormatter::new(OutputFormat::Json);
+        let parsed: serde_json::Value = serde_json::from_str(
+            &formatter.format_explain_type(&make_explain_result(), &SourceCache::new()),
+        )
+        .unwrap();
+
+        assert_eq!(parsed["query"], "create_order");
+        assert_eq!(parsed["types"][0]["kind"], "TypedDict");
+        assert_eq!(parsed["types"][0]["line"], 4);
+        assert_eq!(parsed["types"][1]["depth"], 2);
+    }
+
+    fn make_hover_entries() -> Vec<HoverEntry> {
+        use crate::lsp::protocol::{Hover, HoverContents, MarkupContent};
+
+        let hover = Hover {
+            contents: HoverContents::Markup(MarkupContent {
+                kind: crate::lsp::protocol::MarkupKind::Markdown,
+                value: "```python\ndef create_order(payload: dict) -> Order\n```\n---\nCreate an order."
+                    .to_string(),
+            }),
+            range: None,
+        };
+        vec![
+            HoverEntry {
+                label: "create_order".to_string(),
+                location: Some(make_location("file:///orders.py", 9, 4)),
+                hover: Some(hover),
+            },
+            HoverEntry { label: "missing".to_string(), location: None, hover: None },
+        ]
+    }
+
+    #[test]
+    fn test_format_hover_human_shows_signature_and_doc() {
+        let formatter = OutputFormatter::new(OutputFormat::Human);
+        let result = formatter.format_hover_results(&make_hover_entries(), &SourceCache::new());
+
+        assert!(result.starts_with(
+            "create_order /orders.py:10:5\n  def create_order(payload: dict) -> Order\n\n  Create an order.\n"
+        ));
+        assert!(result.ends_with("No results found for: 'missing'"));
+    }
+
+    #[test]
+    fn test_render_markdown_for_terminal() {
+        let doc = "## Example\n\nUse **carefully**:\n```python\nx = a\\_b\n```\n&nbsp;&nbsp;a\\_b";
+        assert_eq!(
+            render_markdown(doc, Styler::no_color()),
+            "Example\n\nUse carefully:\n    x = a\\_b\n  a_b"
+        );
+    }
+
+    #[test]
+    fn test_format_hover_json() {
+        let formatter = OutputFormatter::new(OutputFormat::Json);
+        let entries = make_hover_entries();
+        let parsed: serde_json::Value = serde_json::from_str(
+            &formatter.format_hover_results(&entries[..1], &SourceCache::new()),
+        )
+        .unwrap();
+
+        assert_eq!(parsed["symbol"], "create_order");
+        assert_eq!(parsed["line"], 10);
+        assert_eq!(parsed["signature"], "def create_order(payload: dict) -> Order");
+        assert_eq!(parsed["doc"], "Create an order.");
+    }
+
+    fn make_occurrences_entry() -> OccurrencesEntry {
+        let highlight = |line, kind| DocumentHighlight {
+            range: make_location("file:///src/app.py", line, 0).range,
+            kind,
+        };
+        OccurrencesEntry {
+            label: "total".to_string(),
+            uri: Some("file:///src/app.py".to_string()),
+            highlights: vec![
+                highlight(0, Some(DocumentHighlightKind::Write)),
+                highlight(1, Some(DocumentHighlightKind::Read)),
+                highlight(2, None),
+            ],
+        }
+    }
+
+    #[test]
+    fn test_format_occurrences_human_marks_reads_and_writes() {
+        let formatter = OutputFormatter::new(OutputFormat::Human);
+        let cache = SourceCache::from_entries([(
+            "/src/app.py".to_string(),
+            "total = 0\ntotal += 1\nprint(\"total\")\n".to_string(),
+        )]);
+        let result = formatter.format_occurrences(&[make_occurrences_entry()], &cache);
+
+        assert_eq!(
+            result,
+            "3 occurrence(s) of 'total' in /src/app.py:\n  \
+             1:1  write  total = 0\n  \
+             2:1  read   total += 1\n  \
+             3:1  text   print(\"total\")"
+        );
+    }
+
+    #[test]
+    fn test_format_occurrences_json() {
+        let formatter = OutputFormatter::new(OutputFormat::Json);
+        let parsed: serde_json::Value = serde_json::from_str(
+            &formatter.format_occurrences(&[make_occurrences_entry()], &SourceCache::new()),
+        )
+        .unwrap();
+
+        assert_eq!(parsed["symbol"], "total");
+        let kinds: Vec<&str> = parsed["occurrences"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|o| o["kind"].as_str().unwrap())
+            .collect();
+        assert_eq!(kinds, ["write", "read", "text"]);
+        assert_eq!(parsed["occurrences"][1]["line"], 2);
+    }
+
+    #[cfg(unix)]
+    fn make_ty_info() -> TyInfoResult {
+        TyInfoResult {
+            workspace: "/proj".to_string(),
+            name: Some("ty".to_string()),
+            version: Some("0.0.1-alpha.20".to_string()),
+            providers: vec![
+                "definitionProvider".to_string(),
+                "hoverProvider".to_string(),
+                "semanticTokensProvider".to_string(),
+            ],
+            capabilities: serde_json::json!({}),
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_format_ty_info_human_marks_missing_providers() {
+        let formatter = OutputFormatter::new(OutputFormat::Human);
+        let output = formatter.format_ty_info(&make_ty_info());
+
+        assert!(output.starts_with("ty 0.0.1-alpha.20\nWorkspace: /proj"));
+        assert!(output.contains("yes  definitionProvider"));
+        assert!(output.contains("no   referencesProvider"));
+        assert!(output.contains("Also advertised:\n  semanticTokensProvider"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_format_ty_info_csv() {
+        let formatter = OutputFormatter::new(OutputFormat::Csv);
+        let output = formatter.format_ty_info(&make_ty_info());
+
+        assert!(output.starts_with("provider,supported\n"));
+        assert!(output.contains("hoverProvider,true\n"));
+        assert!(output.contains("referencesProvider,false\n"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_format_daemon_stats() {
+        use crate::daemon::protocol::{MethodStats, WorkspaceStats};
+
+        let bucket = |le_ms, count| LatencyBucket { le_ms, count };
+        let stats = StatsResult {
+            uptime: 125,
+            requests: 4,
+            methods: vec![MethodStats {
+                method: "hover".to_string(),
+                calls: 4,
+                errors: 1,
+                total_ms: 90,
+                max_ms: 60,
+                buckets: vec![bucket(Some(10), 3), bucket(Some(100), 1), bucket(None, 0)],
+            }],
+            lsp_starts: 3,
+            lsp_restarts: 1,
+            workspaces: vec![WorkspaceStats {
+                workspace: "/proj".to_string(),
+                lsp_restarts: 1,
+                server_pid: Some(42),
+                server_rss_bytes: Some(3 * 1024 * 1024),
+                index_files: 2,
+                index_symbols: 7,
+                index_bytes: 2048,
+                cache_entries: 1,
+                cache_bytes: 300,
+                estimated_bytes: 3 * 1024 * 1024 + 2348,
+            }],
+        };
+
+        let human = OutputFormatter::new(OutputFormat::Human).format_daemon_stats(&stats);
+        assert!(human.starts_with("Uptime: 2m 5s  Requests: 4  Server starts: 3 (1 restart(s))"));
+        let hover = human.lines().find(|line| line.starts_with("hover")).unwrap();
+        assert_eq!(
+            hover.split_whitespace().collect::<Vec<_>>(),
+            ["hover", "4", "1", "22.5ms", "<=10ms", "<=100ms", "60ms"]
+        );
+        assert!(human.contains("  /proj\n    ~3.0 MB: server 3.0 MB, index 2.0 KB (2 files"));
+
+        let csv = OutputFormatter::new(OutputFormat::Csv).format_daemon_stats(&stats);
+        assert_eq!(
+            csv,
+            "method,calls,errors,mean_ms,p50,p95,max_ms\nhover,4,1,22.5,<=10ms,<=100ms,60\n"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_format_daemon_workspaces() {
+        use crate::daemon::protocol::WorkspaceEntry;
+
+        let result = WorkspacesResult {
+            workspaces: vec![
+                WorkspaceEntry {
+                    workspace: "/proj".to_string(),
+                    backend: "ty".to_string(),
+                    server: Some("ty 0.0.2".to_string()),
+                    server_pid: Some(42),
+                    idle_secs: 125,
+                    restarts: 1,
+                    exited: false,
+                },
+                WorkspaceEntry {
+                    workspace: "/other".to_string(),
+                    backend: "ty".to_string(),
+                    server: Some("ty 0.0.2".to_string()),
+                    server_pid: None,
+                    idle_secs: 7300,
+                    restarts: 0,
+                    exited: true,
+                },
+            ],
+        };
+
+        let human = OutputFormatter::new(OutputFormat::Human).format_daemon_workspaces(&result);
+        assert_eq!(
+            human,
+            "/proj  (ty 0.0.2, pid 42, idle 2m 5s, restarted 1 time(s))\n\
+             /other  (ty 0.0.2, idle 2h 1m, exited)"
+        );
+        let empty = WorkspacesResult { workspaces: Vec::new() };
+        assert_eq!(
+            OutputFormatter::new(OutputFormat::Human).format_daemon_workspaces(&empty),
+            "No workspaces loaded"
+        );
+
+        let csv = OutputFormatter::new(OutputFormat::Csv).format_daemon_workspaces(&result);
+        assert_eq!(csv.lines().nth(1), Some("/proj,ty,ty 0.0.2,42,125,1,false"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_format_doctor_report() {
+        use crate::doctor::Check;
+
+        let report = DoctorReport {
+            workspace: "/proj".to_string(),
+            checks: vec![
+                Check::ok("socket", "/tmp/ty-find-1000.sock (exists)"),
+                Check::fail("daemon", "connection refused", "Run `tyf daemon restart`"),
+            ],
+        };
+
+        let human = OutputFormatter::new(OutputFormat::Human).format_doctor_report(&report);
+        assert_eq!(
+            human,
+            "ok    socket           /tmp/ty-find-1000.sock (exists)\n\
+             FAIL  daemon           connection refused\n\
+             \x20                      -> Run `tyf daemon restart`"
+        );
+
+        let csv = OutputFormatter::new(OutputFormat::Csv).format_doctor_report(&report);
+        assert!(csv.ends_with("daemon,fail,\"connection refused\",\"Run `tyf daemon restart`\"\n"));
+
+        let json = OutputFormatter::new(OutputFormat::Json).format_doctor_report(&report);
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(value["checks"][1]["status"], "fail");
+        assert!(value["checks"][0].get("fix").is_none());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_format_bench_report_human_and_csv() {
+        use crate::bench::BenchStat;
+        use std::time::Duration;
+
+        let report = BenchReport {
+            workspace: "/proj".to_string(),
+            target: "Calculator.add (example.py:10:9)".to_string(),
+            ty_version: Some("0.0.1-alpha.20".to_string()),
+            results: vec![BenchStat::from_samples(
+                "warm hover",
+                &[Duration::from_millis(4), Duration::from_millis(6)],
+            )],
+        };
+
+        let human = OutputFormatter::new(OutputFormat::Human).format_bench_report(&report);
+        assert!(human.starts_with("/proj, ty 0.0.1-alpha.20\nTarget: Calculator.add"));
+        assert!(
+            human.contains("warm hover             2      4.0ms      6.0ms      6.0ms      6.0ms")
+        );
+
+        let csv = OutputFormatter::new(OutputFormat::Csv).format_bench_report(&report);
+        assert_eq!(
+            csv,
+            "benchmark,samples,p50_ms,p90_ms,p99_ms,max_ms\nwarm hover,2,4.000,6.000,6.000,6.000\n"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_format_unused() {
+        let mut result = UnusedResult {
+            symbols: vec![UnusedSymbol {
+                name: "Order.legacy_total".to_string(),
+                kind: SymbolKind::Method,
+                location: make_location("file:///proj/app.py", 9, 8),
+            }],
+            files_scanned: 4,
+            symbols_checked: 20,
+            unresolved: 1,
+        };
+
+        let human =
+            OutputFormatter::new(OutputFormat::Human).format_unused(&result, &SourceCache::new());
+        assert!(human.starts_with(
+            "1 of 20 symbol(s) in 4 file(s) are never referenced (1 could not be resolved):\n\n"
+        ));
+        assert!(human.contains("/proj/app.py:10:9 Order.legacy_total (method)"));
+
+        let json =
+            OutputFormatter::new(OutputFormat::Json).format_unused(&result, &SourceCache::new());
+        let value: serde_json::Value = serde_json::from_str(&json).expect("valid json");
+        assert_eq!(value["symbols_checked"], 20);
+        assert_eq!(value["unused"][0]["name"], "Order.legacy_total");
+        assert_eq!(value["unused"][0]["kind"], "Method");
+        assert_eq!(value["unused"][0]["line"], 10);
+
+        let csv =
+            OutputFormatter::new(OutputFormat::Csv).format_unused(&result, &SourceCache::new());
+        assert!(csv.ends_with(",10,9,method,Order.legacy_total\n"));
+
+        result.symbols.clear();
+        result.unresolved = 0;
+        let empty =
+            OutputFormatter::new(OutputFormat::Human).format_unused(&result, &SourceCache::new());
+        assert_eq!(empty, "No unused symbols among 20 checked in 4 file(s)");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_format_reference_counts() {
+        let counts = vec![
+            ReferenceCount {
+                label: "User".to_string(),
+                files: vec![
+                    ("file:///proj/app/views.py".to_string(), 3),
+                    ("file:///proj/app/models.py".to_string(), 1),
+                ],
+                hidden_tests: 2,
+            },
+            ReferenceCount { label: "save".to_string(), files: Vec::new(), hidden_tests: 0 },
+        ];
+
+        let human =
+            OutputFormatter::new(OutputFormat::Human).format_reference_counts(&counts, false);
+        assert_eq!(
+            human,
+            "User: 4 reference(s) in 2 file(s) (+2 in tests, use --tests/-t to count them)\n\
+             save: 0 reference(s) in 0 file(s)"
+        );
+        let by_file =
+            OutputFormatter::new(OutputFormat::Human).format_reference_counts(&counts, true);
+        assert!(by_file.contains("\n      3  /proj/app/views.py\n      1  /proj/app/models.py\n"));
+
+        let json = OutputFormatter::new(OutputFormat::Json).format_reference_counts(&counts, true);
+        let value: serde_json::Value = serde_json::from_str(&json).expect("valid json");
+        assert_eq!(value[0]["references"], 4);
+        assert_eq!(value[0]["hidden_test_references"], 2);
+        assert_eq!(value[0]["by_file"][0]["references"], 3);
+        let json = OutputFormatter::new(OutputFormat::Json).format_reference_counts(&counts, false);
+        assert!(!json.contains("by_file"));
+
+        let csv = OutputFormatter::new(OutputFormat::Csv).format_reference_counts(&counts, false);
+        assert_eq!(csv, "symbol,references,files\nUser,4,2\nsave,0,0\n");
+        let csv = OutputFormatter::new(OutputFormat::Csv).format_reference_counts(&counts, true);
+        assert!(csv.starts_with("symbol,file,references\nUser,/proj/app/views.py,3\n"));
+    }
+
+    #[test]
+    fn test_format_api() {
+        use crate::lsp::protocol::{MarkupContent, MarkupKind};
+        let item = |name: &str, kind, line, hover: Option<&str>, members| ApiItem {
+            name: name.to_string(),
+            kind,
+            location: Location {
+                uri: "file:///proj/shop/orders.py".to_string(),
+                range: Range {
+                    start: Position { line, character: 4 },
+                    end: Position { line, character: 4 },
+                },
+            },
+            hover: hover.map(|value| Hover {
+                contents: HoverContents::Markup(MarkupContent {
+                    kind: MarkupKind::Markdown,
+                    value: value.to_string(),
+                }),
+                range: None,
+            }),
+            members,
+        };
+        let total = "```python\ndef total(self) -> float\n```\n---\nSum of the\nprices.\n\nMore.";
+        let result = ApiResult {
+            target: "shop".to_string(),
+            modules: vec![ApiModule {
+                module: "shop.orders".to_string(),
+                uri: "file:///proj/shop/orders.py".to_string(),
+                symbols: vec![item(
+                    "Order",
+                    SymbolKind::Class,
+                    0,
+                    Some("```python\n<class 'Order'>\n```"),
+                    vec![item("total", SymbolKind::Method, 3, Some(total), Vec::new())],
+                )],
+            }],
+        };
+        let cache = SourceCache::from_entries(std::iter::empty());
+
+        let human = OutputFormatter::new(OutputFormat::Human).format_api(&result, &cache);
+        assert!(human.starts_with("# API of shop\n\n## shop.orders\n"));
+        assert!(human
+            .contains("### `class Order`\n\n- `total(self) -> float` \u{2014} Sum of the prices."));
+
+        let json = OutputFormatter::new(OutputFormat::Json).format_api(&result, &cache);
+        let value: serde_json::Value = serde_json::from_str(&json).expect("valid json");
+        let order = &value["modules"][0]["symbols"][0];
+        assert_eq!(order["signature"], "class Order");
+        assert_eq!(order["doc"], serde_json::Value::Null);
+        assert_eq!(order["members"][0]["line"], 4);
+
+        let csv = OutputFormatter::new(OutputFormat::Csv).format_api(&result, &cache);
+        assert!(csv.contains(
+            "shop.orders,Order.total,method,/proj/shop/orders.py,4,\"total(self) -> float\"\n"
+        ));
+
+        let empty = ApiResult { target: "shop".to_string(), modules: Vec::new() };
+        let human = OutputFormatter::new(OutputFormat::Human).format_api(&empty, &cache);
+        assert_eq!(human, "No public classes or functions in shop");
+    }
+
+    #[test]
+    fn test_format_rdeps() {
+        let mut result = RdepsResult {
+            uri: "file:///proj/app/models.py".to_string(),
+            symbols_checked: 5,
+            files: vec![
+                DependentFile {
+                    uri: "file:///proj/app/views.py".to_string(),
+                    symbols: vec!["Order".to_string(), "Order.total".to_string()],
+                    references: 3,
+                },
+                DependentFile {
+                    uri: "file:///proj/tests/test_orders.py".to_string(),
+                    symbols: vec!["Order".to_string()],
+                    references: 1,
+                },
+            ],
+        };
+
+        let human = OutputFormatter::new(OutputFormat::Human).format_rdeps(&result);
+        assert!(human.starts_with("2 file(s) reference symbols from /proj/app/models.py:\n\n"));
+        assert!(human.contains("/proj/app/views.py  Order, Order.total (3 reference(s))"));
+
+        let paths = OutputFormatter::new(OutputFormat::Paths).format_rdeps(&result);
+        assert_eq!(paths, "/proj/app/views.py\n/proj/tests/test_orders.py");
+
+        let json = OutputFormatter::new(OutputFormat::Json).format_rdeps(&result);
+        let value: serde_json::Value = serde_json::from_str(&json).expect("valid json");
+        assert_eq!(value["symbols_checked"], 5);
+        assert_eq!(value["dependents"][0]["symbols"][1], "Order.total");
+        assert_eq!(value["dependents"][1]["references"], 1);
+
+        let csv = OutputFormatter::new(OutputFormat::Csv).format_rdeps(&result);
+        assert!(csv.contains("/proj/app/views.py,3,Order;Order.total\n"));
+
+        result.files.clear();
+        let empty = OutputFormatter::new(OutputFormat::Human).format_rdeps(&result);
+        assert_eq!(empty, "No file references the 5 symbol(s) defined in /proj/app/models.py");
+        assert_eq!(OutputFormatter::new(OutputFormat::Paths).format_rdeps(&result), "");
+    }
+
+    #[test]
+    fn test_format_aliases() {
+        let aliases = vec![
+            AliasEntry {
+                name: "h".to_string(),
+                command: "symbols handle_".to_string(),
+                source: "/home/me/.config/ty-find/config.toml".to_string(),
+            },
+            AliasEntry {
+                name: "hotspots".to_string(),
+                command: "refs --count {1}".to_string(),
+                source: "/proj/.ty-find.toml".to_string(),
+            },
+        ];
+        let human = OutputFormatter::new(OutputFormat::Human).format_aliases(&aliases);
+        assert_eq!(
+            human,
+            "h         symbols handle_   /home/me/.config/ty-find/config.toml\n\
+             hotspots  refs --count {1}  /proj/.ty-find.toml"
+        );
+        let empty = OutputFormatter::new(OutputFormat::Human).format_aliases(&[]);
+        assert!(empty.starts_with("No aliases."), "{empty}");
+
+        let csv = OutputFormatter::new(OutputFormat::Csv).format_aliases(&aliases);
+        assert!(csv.ends_with("hotspots,\"refs --count {1}\",\"/proj/.ty-find.toml\"\n"), "{csv}");
+        let json = OutputFormatter::new(OutputFormat::Json).format_aliases(&aliases);
+        let value: serde_json::Value = serde_json::from_str(&json).expect("valid json");
+        assert_eq!(value[1]["command"], "refs --count {1}");
+    }
+
+    #[test]
+    fn test_format_config() {
+        let report = ConfigReport {
+            settings: vec![
+                ConfigSetting {
+                    key: "format".to_string(),
+                    value: serde_json::json!("human"),
+                    source: "default".to_string(),
+                },
+                ConfigSetting {
+                    key: "exclude".to_string(),
+                    value: serde_json::json!(["build", "vendor"]),
+                    source: "/proj/.ty-find.toml".to_string(),
+                },
+            ],
+            files: vec![
+                ConfigFile {
+                    path: "/home/me/.config/ty-find/config.toml".to_string(),
+                    status: "not found",
+                },
+                ConfigFile { path: "/proj/.ty-find.toml".to_string(), status: "read" },
+            ],
+        };
+
+        let human = OutputFormatter::new(OutputFormat::Human).format_config(&report);
+        assert!(human.starts_with("format   human          default\n"), "{human}");
+        assert!(human.contains("exclude  build, vendor  /proj/.ty-find.toml\n"), "{human}");
+        assert!(human.contains("  /home/me/.config/ty-find/config.toml (not found)\n"));
+
+        let json = OutputFormatter::new(OutputFormat::Json).format_config(&report);
+        let value: serde_json::Value = serde_json::from_str(&json).expect("valid json");
+        assert_eq!(value["settings"][1]["value"][0], "build");
+        assert_eq!(value["files"][1]["status"], "read");
+
+        let csv = OutputFormatter::new(OutputFormat::Csv).format_config(&report);
+        assert!(csv.ends_with("exclude,\"build, vendor\",\"/proj/.ty-find.toml\"\n"));
+        let paths = OutputFormatter::new(OutputFormat::Paths).format_config(&report);
+        assert_eq!(paths, "/proj/.ty-find.toml");
+    }
+
+    #[test]
+    fn test_format_picked_symbol() {
+        let symbol = make_symbol_info("save", SymbolKind::Method, "file:///proj/app.py", 9);
+        let cache = SourceCache::new();
+        let human = OutputFormatter::new(OutputFormat::Human).format_picked_symbol(&symbol, &cache);
+        assert_eq!(human, "/proj/app.py:10:1");
+
+        let json = OutputFormatter::new(OutputFormat::Json).format_picked_symbol(&symbol, &cache);
+        let value: serde_json::Value = serde_json::from_str(&json).expect("valid json");
+        assert_eq!(value[0]["name"], "save");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_format_grep_results() {
+        let mut result = GrepResult {
+            pattern: "save".to_string(),
+            target: "User.save".to_string(),
+            text_matches: 3,
+            hits: vec![make_location("file:///proj/app.py", 9, 9)],
+        };
+
+        let human = OutputFormatter::new(OutputFormat::Human)
+            .format_grep_results(&result, &SourceCache::new());
+        assert!(human.starts_with("1 of 3 match(es) for 'save' resolve to User.save:\n\n1. "));
+        assert!(human.contains("/proj/app.py:10:10"));
+
+        let json = OutputFormatter::new(OutputFormat::Json)
+            .format_grep_results(&result, &SourceCache::new());
+        let value: serde_json::Value = serde_json::from_str(&json).expect("valid json");
+        assert_eq!(value["text_matches"], 3);
+        assert_eq!(value["matches"][0]["line"], 10);
+
+        result.hits.clear();
+        let empty = OutputFormatter::new(OutputFormat::Human)
+            .format_grep_results(&result, &SourceCache::new());
+        assert_eq!(empty, "No matches for 'save' resolve to User.save (3 text match(es))");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_format_sig_check() {
+        let result = SigCheckResult {
+            symbol: "calculate_sum".to_string(),
+            current_signature: Some("def calculate_sum(\n    a,\n    b\n) -> Unknown".to_string()),
+            new_signature: "def calculate_sum(a, *, b=1)".to_string(),
+            call_sites: 2,
+            breaking: vec![BrokenCall {
+                location: make_location("file:///proj/main.py", 18, 12),
+                problems: vec!["takes 1 positional argument(s) but 2 were given".to_string()],
+            }],
+            unchecked: Vec::new(),
+        };
+
+        let human = OutputFormatter::new(OutputFormat::Human)
+            .format_sig_check(&result, &SourceCache::new());
+        assert!(human.starts_with(
+            "calculate_sum\n  current:  def calculate_sum(a, b) -> Unknown\n  \
+             proposed: def calculate_sum(a, *, b=1)\n\n1 of 2 call site(s) would break:\n\n1. "
+        ));
+        assert!(human.ends_with("\n   takes 1 positional argument(s) but 2 were given"));
+
+        let csv =
+            OutputFormatter::new(OutputFormat::Csv).format_sig_check(&result, &SourceCache::new());
+        assert!(
+            csv.ends_with(",19,13,breaks,\"takes 1 positional argument(s) but 2 were given\"\n")
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_format_annotate_inline_and_side_by_side() {
+        use crate::annotate::{Annotation, AnnotationKind};
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("app.py");
+        std::fs::write(&path, "def f(a):\n    x = [a]\n    return x\n").unwrap();
+        let uri = crate::lsp::uri::path_to_uri(&path);
+        let cache = tokio::runtime::Runtime::new()
+            .unwrap()
+            .block_on(SourceCache::from_uris([uri.as_str()]));
+        let result = AnnotateResult {
+            uri,
+            annotations: vec![
+                Annotation {
+                    line: 0,
+                    character: 4,
+                    kind: AnnotationKind::Function,
+                    name: "f".to_string(),
+                    ty: "(a) -> list[Unknown]".to_string(),
+                },
+                Annotation {
+                    line: 1,
+                    character: 4,
+                    kind: AnnotationKind::Variable,
+                    name: "x".to_string(),
+                    ty: "list[Unknown]".to_string(),
+                },
+            ],
+        };
+
+        let formatter = OutputFormatter::new(OutputFormat::Human);
+        assert_eq!(
+            formatter.format_annotate(&result, &cache, false),
+            "def f(a):  # type: (a) -> list[Unknown]\n    x = [a]  # type: list[Unknown]\n    return x"
+        );
+        assert_eq!(
+            formatter.format_annotate(&result, &cache, true),
+            "def f(a):    \u{2502} (a) -> list[Unknown]\n    x = [a]  \u{2502} list[Unknown]\n    return x \u{2502}"
+        );
+
+        let csv = OutputFormatter::new(OutputFormat::Csv).format_annotate(&result, &cache, false);
+        assert!(csv.ends_with(",2,5,variable,x,\"list[Unknown]\"\n"), "got:\n{csv}");
+    }
 
-        let _ = writeln!(
+    #[cfg(unix)]
+    #[test]
+    fn test_format_where() {
+        let hop = |name: &str, owner: Option<&str>, ty: &str, line: u32| WhereHop {
+            name: name.to_string(),
+            owner: owner.map(str::to_string),
+            location: Some(make_location("file:///proj/models.py", line, 4)),
+            ty: Some(ty.to_string()),
+        };
+        let result = WhereResult {
+            expression: "order.customer.city".to_string(),
+            hops: vec![
+                hop("order", None, "Order", 20),
+                hop("customer", Some("Order"), "Customer", 8),
+                hop("city", Some("Person"), "str", 3),
+            ],
+            unresolved: None,
+        };
+        let cache = SourceCache::new();
+        let output = OutputFormatter::new(OutputFormat::Human).format_where(&result, &cache);
+        assert_eq!(
             output,
-            "{indent_str}{} ({:?}) - line {line}, col {column}",
-            symbol.name, symbol.kind,
+            "order.customer.city\n  order           Order  /proj/models.py:21:5\n  \
+             Order.customer  Customer  /proj/models.py:9:5\n  \
+             Person.city     str  /proj/models.py:4:5"
         );
 
-        if let Some(children) = &symbol.children {
-            format_document_symbols_recursive(children, indent + 1, output);
-        }
+        let stopped = WhereResult {
+            unresolved: Some("Stopped at .zip: `str` is not a class".to_string()),
+            hops: result.hops.into_iter().take(1).collect(),
+            ..result
+        };
+        let output = OutputFormatter::new(OutputFormat::Human).format_where(&stopped, &cache);
+        assert!(output.ends_with("\n  Stopped at .zip: `str` is not a class"), "got:\n{output}");
+
+        let csv = OutputFormatter::new(OutputFormat::Csv).format_where(&stopped, &cache);
+        assert_eq!(
+            csv,
+            "name,class,type,file,line,column\norder,,\"Order\",/proj/models.py,21,5\n"
+        );
     }
-}
 
-fn format_document_symbols_csv(symbols: &[DocumentSymbol], output: &mut String) {
-    for symbol in symbols {
-        let line = symbol.range.start.line + 1;
-        let column = symbol.range.start.character + 1;
+    #[cfg(unix)]
+    #[test]
+    fn test_format_imports() {
+        let edge = |module: &str, path: Option<&str>, line| ImportEdge {
+            module: module.to_string(),
+            path: path.map(PathBuf::from),
+            line,
+        };
+        let result = ImportsResult {
+            module: "app.models".to_string(),
+            path: PathBuf::from("/proj/app/models.py"),
+            imports: vec![edge("app.db", Some("/proj/app/db.py"), 2)],
+            external: vec![edge("requests", None, 0)],
+            imported_by: vec![edge("app.views", Some("/proj/app/views.py"), 4)],
+        };
+        let human = OutputFormatter::new(OutputFormat::Human).format_imports(&result);
+        assert!(human.contains("Imports (1)\n  app.db  /proj/app/db.py  line 3"), "got:\n{human}");
+        assert!(human.contains("  requests  unresolved  line 1"), "got:\n{human}");
+        assert!(human.ends_with("Imported by (1)\n  app.views  /proj/app/views.py:5"));
 
-        let _ = writeln!(output, "{},{:?},{line},{column}", symbol.name, symbol.kind);
+        let dot = OutputFormatter::new(OutputFormat::Dot).format_imports(&result);
+        assert_eq!(
+            dot,
+            "digraph imports {\n  \"app.models\" [style=bold];\n  \
+             \"app.models\" -> \"app.db\";\n  \
+             \"app.models\" -> \"requests\" [style=dashed];\n  \
+             \"app.views\" -> \"app.models\";\n}"
+        );
 
-        if let Some(children) = &symbol.children {
-            format_document_symbols_csv(children, output);
-        }
+        let paths = OutputFormatter::new(OutputFormat::Paths).format_imports(&result);
+        assert_eq!(paths, "/proj/app/db.py\n/proj/app/views.py");
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::lsp::protocol::{Position, Range, SymbolKind};
+    #[cfg(unix)]
+    #[test]
+    fn test_format_check_all_table() {
+        let diagnostic = |severity| Diagnostic {
+            range: make_location("file:///proj/a.py", 0, 0).range,
+            severity,
+            code: None,
+            source: None,
+            message: String::new(),
+            related_information: None,
+        };
+        let result = CheckAllResult {
+            files: vec![
+                FileDiagnostics {
+                    uri: "file:///proj/app.py".to_string(),
+                    diagnostics: vec![diagnostic(DiagnosticSeverity::Warning)],
+                },
+                FileDiagnostics {
+                    uri: "file:///proj/models.py".to_string(),
+                    diagnostics: vec![
+                        diagnostic(DiagnosticSeverity::Error),
+                        diagnostic(DiagnosticSeverity::Error),
+                        diagnostic(DiagnosticSeverity::Hint),
+                    ],
+                },
+            ],
+            files_checked: 10,
+            failed: vec![("file:///proj/gone.py".to_string(), "File not found".to_string())],
+        };
 
-    fn make_location(uri: &str, line: u32, character: u32) -> Location {
-        Location {
-            uri: uri.to_string(),
-            range: Range {
-                start: Position { line, character },
-                end: Position { line, character: character + 5 },
-            },
-        }
+        let human = OutputFormatter::new(OutputFormat::Human).format_check_all(&result);
+        assert_eq!(
+            human,
+            "file             errors  warnings  notes\n\
+             /proj/models.py       2         0      1\n\
+             /proj/app.py          0         1      0\n\n\
+             Found 2 error(s), 1 warning(s), 1 note(s) in 2 of 10 file(s)\n\
+             Could not check 1 file(s):\n  /proj/gone.py: File not found"
+        );
+
+        let csv = OutputFormatter::new(OutputFormat::Csv).format_check_all(&result);
+        assert_eq!(csv, "file,errors,warnings,notes\n/proj/models.py,2,0,1\n/proj/app.py,0,1,0\n");
     }
 
+    #[cfg(unix)]
     #[test]
-    fn test_format_definitions_empty() {
-        let formatter = OutputFormatter::new(OutputFormat::Human);
-        let result = formatter.format_definitions(&[], "test:1:1", &SourceCache::new());
-        assert_eq!(result, "No results found for: test:1:1");
+    fn test_format_code_stats() {
+        use crate::code_stats::SymbolCounts;
+        let result = CodeStatsResult {
+            files: vec![
+                FileStats {
+                    uri: "file:///proj/app.py".to_string(),
+                    lines: 120,
+                    counts: SymbolCounts { classes: 1, functions: 2, methods: 5 },
+                },
+                FileStats {
+                    uri: "file:///proj/models.py".to_string(),
+                    lines: 40,
+                    counts: SymbolCounts { classes: 2, functions: 0, methods: 3 },
+                },
+            ],
+            failed: vec![("file:///proj/gone.py".to_string(), "File not found".to_string())],
+        };
+
+        let human = OutputFormatter::new(OutputFormat::Human).format_code_stats(&result);
+        assert_eq!(
+            human,
+            "file               lines  classes  functions  methods\n\
+             /proj/app.py         120        1          2        5\n\
+             /proj/models.py       40        2          0        3\n\n\
+             2 file(s), 160 line(s): 3 class(es), 2 function(s), 8 method(s)\n\
+             Could not outline 1 file(s):\n  /proj/gone.py: File not found"
+        );
+
+        let csv = OutputFormatter::new(OutputFormat::Csv).format_code_stats(&result);
+        assert_eq!(
+            csv,
+            "file,lines,classes,functions,methods\n/proj/app.py,120,1,2,5\n/proj/models.py,40,2,0,3\n"
+        );
+
+        let json = OutputFormatter::new(OutputFormat::Json).format_code_stats(&result);
+        let val: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(val["total"]["lines"], 160);
+        assert_eq!(val["total"]["methods"], 8);
+        assert_eq!(val["files"][1]["classes"], 2);
+        assert_eq!(val["failed"][0]["error"], "File not found");
     }
 
+    #[cfg(unix)]
     #[test]
-    fn test_format_definitions_single() {
-        let formatter = OutputFormatter::new(OutputFormat::Human);
-        let locations = [make_location("file:///nonexistent.py", 5, 10)];
-        let result = formatter.format_definitions(&locations, "test:6:11", &SourceCache::new());
+    fn test_format_check() {
+        let location = make_location("file:///proj/app.py", 2, 4);
+        let result = CheckResult {
+            files: vec![FileDiagnostics {
+                uri: location.uri,
+                diagnostics: vec![Diagnostic {
+                    range: location.range,
+                    severity: DiagnosticSeverity::Error,
+                    code: Some("unresolved-reference".to_string()),
+                    source: Some("ty".to_string()),
+                    message: "Name `foo` used when not defined".to_string(),
+                    related_information: None,
+                }],
+            }],
+            files_checked: 3,
+            baselined: 5,
+            fixed: 1,
+            below_severity: 2,
+        };
 
-        assert!(result.contains("Found 1 definition(s)"));
-        assert!(result.contains("nonexistent.py:6:11"));
+        let human =
+            OutputFormatter::new(OutputFormat::Human).format_check(&result, &SourceCache::new());
+        assert!(
+            human.contains(":3:5 error[unresolved-reference]: Name `foo` used when not defined\n"),
+            "got:\n{human}"
+        );
+        assert!(human.contains(
+            "Found 1 error(s), 0 warning(s) in 1 of 3 file(s) (5 in baseline, 2 below --severity)"
+        ));
+        assert!(human.ends_with(
+            "1 baselined diagnostic(s) no longer occur; run with --update-baseline to drop them"
+        ));
+
+        let csv =
+            OutputFormatter::new(OutputFormat::Csv).format_check(&result, &SourceCache::new());
+        assert!(csv.starts_with("file,line,column,severity,code,message\n"));
+        assert!(
+            csv.ends_with(",3,5,error,unresolved-reference,\"Name `foo` used when not defined\"\n")
+        );
+
+        let empty = CheckResult {
+            files: Vec::new(),
+            files_checked: 2,
+            baselined: 0,
+            fixed: 0,
+            below_severity: 0,
+        };
+        let human =
+            OutputFormatter::new(OutputFormat::Human).format_check(&empty, &SourceCache::new());
+        assert_eq!(human, "No diagnostics in 2 file(s)");
+        let jsonl =
+            OutputFormatter::new(OutputFormat::Jsonl).format_check(&empty, &SourceCache::new());
+        assert_eq!(jsonl, "");
     }
 
     #[test]
-    fn test_format_definitions_json() {
-        let formatter = OutputFormatter::new(OutputFormat::Json);
-        let locations = [make_location("file:///test.py", 0, 0)];
-        let result = formatter.format_definitions(&locations, "test", &SourceCache::new());
-
-        let parsed: serde_json::Value = serde_json::from_str(&result).unwrap();
-        assert!(parsed.is_array());
-        assert_eq!(parsed[0]["uri"], "file:///test.py");
+    fn test_format_check_jsonl() {
+        let location = make_location("file:///proj/app.py", 2, 4);
+        let diagnostic = |message: &str| Diagnostic {
+            range: location.range.clone(),
+            severity: DiagnosticSeverity::Warning,
+            code: None,
+            source: Some("ty".to_string()),
+            message: message.to_string(),
+            related_information: None,
+        };
+        let result = CheckResult {
+            files: vec![FileDiagnostics {
+                uri: location.uri.clone(),
+                diagnostics: vec![diagnostic("first"), diagnostic("second")],
+            }],
+            files_checked: 1,
+            ..CheckResult::default()
+        };
+        let jsonl =
+            OutputFormatter::new(OutputFormat::Jsonl).format_check(&result, &SourceCache::new());
+        let lines: Vec<&str> = jsonl.lines().collect();
+        assert_eq!(lines.len(), 2, "got:\n{jsonl}");
+        let first: serde_json::Value = serde_json::from_str(lines[0]).unwrap();
+        assert_eq!(first["file"], "/proj/app.py");
+        assert_eq!(first["line"], 3);
+        assert_eq!(first["severity"], "warning");
+        assert_eq!(first["message"], "first");
     }
 
     #[test]
-    fn test_format_definitions_csv() {
-        let formatter = OutputFormatter::new(OutputFormat::Csv);
-        let locations = [make_location("file:///test.py", 4, 2)];
-        let result = formatter.format_definitions(&locations, "test", &SourceCache::new());
+    fn test_format_line_explanation() {
+        let location = make_location("file:///proj/app.py", 2, 4);
+        let mut hovers = make_hover_entries();
+        let explanation = LineExplanation {
+            uri: location.uri.clone(),
+            line: 2,
+            identifiers: vec![
+                ExplainedIdentifier {
+                    name: "create_order".to_string(),
+                    column: 4,
+                    hover: hovers[0].hover.take(),
+                    definitions: vec![make_location("file:///proj/orders.py", 9, 4)],
+                },
+                ExplainedIdentifier {
+                    name: "x".to_string(),
+                    column: 17,
+                    hover: None,
+                    definitions: Vec::new(),
+                },
+            ],
+            diagnostics: vec![Diagnostic {
+                range: location.range,
+                severity: DiagnosticSeverity::Error,
+                code: Some("missing-argument".to_string()),
+                source: Some("ty".to_string()),
+                message: "No argument provided for required parameter `payload`".to_string(),
+                related_information: None,
+            }],
+        };
 
-        assert!(result.starts_with("file,line,column\n"));
-        assert!(result.contains("5,3")); // 0-based -> 1-based
+        let human = OutputFormatter::new(OutputFormat::Human)
+            .format_line_explanation(&explanation, &SourceCache::new());
+        assert!(human.starts_with("/proj/app.py:3\n\n"), "got:\n{human}");
+        assert!(human.contains(
+            "create_order  def create_order(payload: dict) -> Order\n              \
+             defined at /proj/orders.py:10:5\n\
+             x             no type\n"
+        ));
+        assert!(human.ends_with(
+            "/proj/app.py:3:5 error[missing-argument]: No argument provided for required parameter `payload`"
+        ));
+
+        let json = OutputFormatter::new(OutputFormat::Json)
+            .format_line_explanation(&explanation, &SourceCache::new());
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(value["line"], 3);
+        assert_eq!(value["names"][0]["type"], "def create_order(payload: dict) -> Order");
+        assert_eq!(value["names"][0]["definitions"][0]["line"], 10);
+        assert_eq!(value["names"][1]["column"], 18);
+        assert_eq!(value["diagnostics"][0]["code"], "missing-argument");
     }
 
     #[test]
@@ -1637,6 +6633,7 @@ mod tests {
             displayed: Vec::new(),
             remaining_count: 0,
             test_references: None,
+            omitted_count: 0,
         };
         let output = formatter.format_enriched_references_results(&[result], &SourceCache::new());
         assert_eq!(output, "No results found for: 'test:1:1'");
@@ -1653,7 +6650,7 @@ mod tests {
             location: make_location("file:///test.py", 0, 0),
             container_name: None,
         }];
-        let result = formatter.format_workspace_symbols(&symbols);
+        let result = formatter.format_workspace_symbols(&symbols, &SourceCache::new());
 
         assert!(result.contains("MyClass"));
         assert!(result.contains("Class"));
@@ -1668,6 +6665,27 @@ mod tests {
         assert!(result.contains("test.py"));
     }
 
+    #[cfg(unix)]
+    #[test]
+    fn test_uri_to_path_maps_back_to_symlinked_spelling() {
+        let dir = tempfile::tempdir().unwrap();
+        let real = dir.path().join("real");
+        std::fs::create_dir_all(real.join("pkg")).unwrap();
+        let link = dir.path().join("link");
+        std::os::unix::fs::symlink(&real, &link).unwrap();
+        let mapper = PathMapper::new(&link).unwrap();
+        let uri = format!("file://{}", mapper.canonical_root().join("pkg/app.py").display());
+
+        let relative = OutputFormatter::new(OutputFormat::Human)
+            .with_path_mapper(mapper.clone(), link.clone());
+        assert_eq!(relative.uri_to_path(&uri), "pkg/app.py");
+
+        let elsewhere =
+            OutputFormatter::new(OutputFormat::Human).with_path_mapper(mapper, dir.path().into());
+        assert_eq!(elsewhere.abs_path(&uri), link.join("pkg/app.py").display().to_string());
+        assert_eq!(elsewhere.uri_to_path(&uri), "link/pkg/app.py");
+    }
+
     #[test]
     fn test_uri_to_path_without_file_prefix() {
         let formatter = OutputFormatter::new(OutputFormat::Human);
@@ -2190,27 +7208,28 @@ mod tests {
                 class_line: 4,
                 class_column: 0,
                 symbol_kind: Some(SymbolKind::Class),
+                expression: false,
                 members: vec![
                     MemberInfo {
                         name: "speak".to_string(),
                         kind: SymbolKind::Method,
                         signature: Some("speak(self) -> str".to_string()),
-                        line: 10,
-                        column: 4,
+                        line: Some(10),
+                        column: Some(4),
                     },
                     MemberInfo {
                         name: "name".to_string(),
                         kind: SymbolKind::Property,
                         signature: Some("name: str".to_string()),
-                        line: 7,
-                        column: 4,
+                        line: Some(7),
+                        column: Some(4),
                     },
                     MemberInfo {
                         name: "MAX_LEGS".to_string(),
                         kind: SymbolKind::Variable,
                         signature: Some("MAX_LEGS: int".to_string()),
-                        line: 5,
-                        column: 4,
+                        line: Some(5),
+                        column: Some(4),
                     },
                 ],
             }
@@ -2220,7 +7239,7 @@ mod tests {
         fn test_format_members_human() {
             let formatter = OutputFormatter::new(OutputFormat::Human);
             let result = make_members_result();
-            let output = formatter.format_members_result(&result);
+            let output = formatter.format_members_result(&result, &SourceCache::new());
 
             assert!(output.contains("Animal"), "should show class name");
             assert!(output.contains(":5:1"), "should show class location (1-based)");
@@ -2236,7 +7255,7 @@ mod tests {
         fn test_format_members_json() {
             let formatter = OutputFormatter::new(OutputFormat::Json);
             let result = make_members_result();
-            let output = formatter.format_members_result(&result);
+            let output = formatter.format_members_result(&result, &SourceCache::new());
 
             let parsed: serde_json::Value = serde_json::from_str(&output).unwrap();
             assert_eq!(parsed["class_name"], "Animal");
@@ -2248,7 +7267,7 @@ mod tests {
         fn test_format_members_csv() {
             let formatter = OutputFormatter::new(OutputFormat::Csv);
             let result = make_members_result();
-            let output = formatter.format_members_result(&result);
+            let output = formatter.format_members_result(&result, &SourceCache::new());
 
             assert!(output.starts_with("class,member,kind,signature,line,column\n"));
             assert!(output.contains("Animal,speak,method"));
@@ -2260,7 +7279,7 @@ mod tests {
         fn test_format_members_paths() {
             let formatter = OutputFormatter::new(OutputFormat::Paths);
             let result = make_members_result();
-            let output = formatter.format_members_result(&result);
+            let output = formatter.format_members_result(&result, &SourceCache::new());
 
             assert!(output.contains("models.py"));
         }
@@ -2274,9 +7293,10 @@ mod tests {
                 class_line: 0,
                 class_column: 0,
                 symbol_kind: Some(SymbolKind::Class),
+                expression: false,
                 members: Vec::new(),
             };
-            let output = formatter.format_members_result(&result);
+            let output = formatter.format_members_result(&result, &SourceCache::new());
 
             assert!(output.contains("Empty"));
             assert!(output.contains("(no public members)"));
@@ -2293,21 +7313,54 @@ mod tests {
                     class_line: 20,
                     class_column: 0,
                     symbol_kind: Some(SymbolKind::Class),
+                    expression: false,
                     members: vec![MemberInfo {
                         name: "fetch".to_string(),
                         kind: SymbolKind::Method,
                         signature: Some("fetch(self, item: str) -> str".to_string()),
-                        line: 25,
-                        column: 4,
+                        line: Some(25),
+                        column: Some(4),
                     }],
                 },
             ];
-            let output = formatter.format_members_results(&results);
+            let output = formatter.format_members_results(&results, &SourceCache::new());
 
             assert!(output.contains("Animal"), "should show first class");
             assert!(output.contains("Dog"), "should show second class");
             assert!(output.contains("fetch(self, item: str) -> str"));
         }
+
+        #[test]
+        fn test_format_expression_members_without_positions() {
+            let result = MembersResult {
+                class_name: "order.items".to_string(),
+                file_uri: "file:///src/app.py".to_string(),
+                class_line: 11,
+                class_column: 10,
+                symbol_kind: None,
+                expression: true,
+                members: vec![MemberInfo {
+                    name: "append".to_string(),
+                    kind: SymbolKind::Method,
+                    signature: Some("append(object: Item, /) -> None".to_string()),
+                    line: None,
+                    column: None,
+                }],
+            };
+
+            let human = OutputFormatter::new(OutputFormat::Human)
+                .format_members_result(&result, &SourceCache::new());
+            assert_eq!(
+                human,
+                "order.items (/src/app.py:12:11)\n  Methods:\n    append(object: Item, /) -> None\n"
+            );
+
+            let csv = OutputFormatter::new(OutputFormat::Csv)
+                .format_members_result(&result, &SourceCache::new());
+            assert!(
+                csv.ends_with("order.items,append,method,\"append(object: Item, /) -> None\",,\n")
+            );
+        }
     }
 
     // ── Enclosing symbol tree walk tests ───────────────────────────────
@@ -2365,6 +7418,20 @@ mod tests {
         assert_eq!(find_enclosing_symbol(&symbols, 2, 0), None);
     }
 
+    #[test]
+    fn test_find_defined_symbol_by_name_or_header() {
+        let method = make_doc_symbol("process", SymbolKind::Method, 10, 20, None);
+        let class = make_doc_symbol("RequestHandler", SymbolKind::Class, 5, 30, Some(vec![method]));
+        let symbols = vec![class];
+
+        let name = |found: Option<&DocumentSymbol>| found.map(|s| s.name.clone());
+        assert_eq!(name(find_defined_symbol(&symbols, 5, 3)), Some("RequestHandler".to_string()));
+        assert_eq!(name(find_defined_symbol(&symbols, 10, 0)), Some("process".to_string()));
+        // A local inside the method's body defines no outline symbol.
+        assert_eq!(name(find_defined_symbol(&symbols, 15, 8)), None);
+        assert_eq!(name(find_defined_symbol(&symbols, 40, 0)), None);
+    }
+
     #[test]
     fn test_find_enclosing_symbol_empty_tree() {
         assert_eq!(find_enclosing_symbol(&[], 10, 5), None);
@@ -2430,10 +7497,12 @@ mod tests {
             EnrichedReference {
                 location: make_location("file:///src/main.py", 44, 11),
                 context: "RequestHandler.process".to_string(),
+                blame: None,
             },
             EnrichedReference {
                 location: make_location("file:///src/main.py", 2, 0),
                 context: "module scope".to_string(),
+                blame: None,
             },
         ];
         let entry = ShowEntry {
@@ -2467,6 +7536,7 @@ mod tests {
         let enriched = vec![EnrichedReference {
             location: make_location("file:///src/main.py", 44, 11),
             context: "RequestHandler.process".to_string(),
+            blame: None,
         }];
         let entry = ShowEntry {
             symbol: "my_func",
@@ -2498,9 +7568,11 @@ mod tests {
             displayed: vec![EnrichedReference {
                 location: make_location("file:///src/main.py", 10, 5),
                 context: "Handler.process".to_string(),
+                blame: None,
             }],
             remaining_count: 49,
             test_references: None,
+            omitted_count: 0,
         };
         let output = formatter.format_enriched_references_results(&[result], &SourceCache::new());
 
@@ -2512,6 +7584,195 @@ mod tests {
         assert!(output.contains("... and 49 more"), "should show remaining, got:\n{output}");
     }
 
+    #[test]
+    fn test_format_enriched_references_with_blame() {
+        let blame = LineBlame {
+            commit: "1f2e3d4c".to_string(),
+            author: "Ada Lovelace".to_string(),
+            date: "2023-11-14".to_string(),
+        };
+        let result = EnrichedReferencesResult {
+            label: "my_func".to_string(),
+            total_count: 1,
+            displayed: vec![EnrichedReference {
+                location: make_location("file:///src/main.py", 10, 5),
+                context: "Handler.process".to_string(),
+                blame: Some(blame),
+            }],
+            remaining_count: 0,
+            omitted_count: 0,
+            test_references: None,
+        };
+        let human = OutputFormatter::new(OutputFormat::Human)
+            .format_enriched_references_results(std::slice::from_ref(&result), &SourceCache::new());
+        assert!(
+            human.contains("(Handler.process) 1f2e3d4c Ada Lovelace, 2023-11-14\n"),
+            "got: {human}"
+        );
+
+        let json = OutputFormatter::new(OutputFormat::Json)
+            .format_enriched_references_results(std::slice::from_ref(&result), &SourceCache::new());
+        let parsed: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed["references"][0]["blame"]["author"], "Ada Lovelace");
+        assert_eq!(parsed["references"][0]["blame"]["date"], "2023-11-14");
+    }
+
+    #[test]
+    fn test_format_enriched_references_with_context_lines() {
+        let formatter = OutputFormatter::new(OutputFormat::Human).with_context_lines(1);
+        let cache = SourceCache::from_entries([(
+            "/src/main.py".to_string(),
+            "import app\n\ndef run():\n    app.start()\n".to_string(),
+        )]);
+        let reference = |line, character| EnrichedReference {
+            location: make_location("file:///src/main.py", line, character),
+            context: "run".to_string(),
+            blame: None,
+        };
+        let result = EnrichedReferencesResult {
+            label: "app".to_string(),
+            total_count: 2,
+            displayed: vec![reference(0, 7), reference(3, 4)],
+            remaining_count: 0,
+            test_references: None,
+            omitted_count: 0,
+        };
+        let output = formatter.format_enriched_references_results(&[result], &cache);
+
+        assert!(output.contains("   > 1 | import app\n     2 | \n\n"), "got:\n{output}");
+        assert!(
+            output.contains("     3 | def run():\n   > 4 |     app.start()\n"),
+            "got:\n{output}"
+        );
+    }
+
+    #[test]
+    fn test_format_enriched_references_grouped_by_kind() {
+        let cache = SourceCache::from_entries([(
+            "/src/app.py".to_string(),
+            "from lib import Model
+class Model:
+def build() -> Model:
+    return Model()
+x = Model()
+"
+            .to_string(),
+        )]);
+        let reference = |line, character| EnrichedReference {
+            location: make_location("file:///src/app.py", line, character),
+            context: "module scope".to_string(),
+            blame: None,
+        };
+        let result = EnrichedReferencesResult {
+            label: "Model".to_string(),
+            total_count: 5,
+            displayed: vec![
+                reference(0, 16),
+                reference(1, 6),
+                reference(2, 15),
+                reference(3, 11),
+                reference(4, 4),
+            ],
+            remaining_count: 0,
+            test_references: None,
+            omitted_count: 0,
+        };
+        let results = std::slice::from_ref(&result);
+
+        let human = OutputFormatter::new(OutputFormat::Human)
+            .with_reference_groups(Some(ReferenceGroup::Kind), Some(ReferenceSort::Count))
+            .format_enriched_references_results(results, &cache);
+        let headings: Vec<&str> = human
+            .lines()
+            .filter(|l| {
+                l.ends_with(')') && !l.starts_with(|c: char| c.is_ascii_digit() || c == ' ')
+            })
+            .collect();
+        assert_eq!(
+            headings,
+            ["call (2)", "import (1)", "definition (1)", "annotation (1)"],
+            "got:\n{human}"
+        );
+
+        let json = OutputFormatter::new(OutputFormat::Json)
+            .with_reference_groups(Some(ReferenceGroup::Kind), None)
+            .format_enriched_references_results(results, &cache);
+        let parsed: serde_json::Value = serde_json::from_str(&json).unwrap();
+        let groups: Vec<&str> = parsed["references"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|r| r["group"].as_str().unwrap())
+            .collect();
+        assert_eq!(groups, ["import", "definition", "annotation", "call", "call"]);
+    }
+
+    #[test]
+    fn test_reference_kind_reads_the_source_line() {
+        let range = |start, end| Range {
+            start: Position { line: 0, character: start },
+            end: Position { line: 0, character: end },
+        };
+        assert_eq!(reference_kind("import os", &range(7, 9)), "import");
+        assert_eq!(reference_kind("    async def run(self):", &range(14, 17)), "definition");
+        assert_eq!(reference_kind("def f(user: User) -> None:", &range(12, 16)), "annotation");
+        assert_eq!(reference_kind("data = {\"k\": User}", &range(14, 18)), "other");
+        assert_eq!(reference_kind("    total += 1", &range(4, 9)), "assignment");
+        assert_eq!(reference_kind("if total == 1:", &range(3, 8)), "other");
+        assert_eq!(reference_kind("run (x)", &range(0, 3)), "call");
+    }
+
+    #[test]
+    fn test_format_definition_bodies() {
+        let body = DefinitionBody {
+            location: make_location("file:///src/app.py", 3, 4),
+            lines: (2, 4),
+            kind: Some(SymbolKind::Function),
+            source: "@cache\ndef load(path):\n    return path".to_string(),
+        };
+        let results = vec![("load".to_string(), vec![body]), ("gone".to_string(), Vec::new())];
+
+        let human = OutputFormatter::new(OutputFormat::Human)
+            .format_definition_bodies(&results, &SourceCache::new());
+        assert_eq!(
+            human,
+            "# load /src/app.py:3-5\n@cache\ndef load(path):\n    return path\n\n\
+             No results found for: 'gone'"
+        );
+
+        let json = OutputFormatter::new(OutputFormat::Json)
+            .format_definition_bodies(&results, &SourceCache::new());
+        let parsed: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed[0]["symbol"], "load");
+        assert_eq!(parsed[0]["line"], 4);
+        assert_eq!(parsed[0]["start_line"], 3);
+        assert_eq!(parsed[0]["end_line"], 5);
+        assert_eq!(parsed[0]["kind"], "Function");
+        assert_eq!(parsed[0]["body"], "@cache\ndef load(path):\n    return path");
+    }
+
+    #[test]
+    fn test_highlight_match_marks_the_referenced_text() {
+        let formatter = OutputFormatter::new(OutputFormat::Human);
+        let colored = OutputFormatter::with_detail(
+            OutputFormat::Human,
+            OutputDetail::default(),
+            Styler::new(crate::cli::style::UseColor::Yes),
+        );
+        let range = |start, end| Range {
+            start: Position { line: 0, character: start },
+            end: Position { line: 0, character: end },
+        };
+
+        // The UTF-16 range of `app` after a two-unit emoji.
+        let line = "x = \"🎉\"; app.start()";
+        let highlighted = colored.highlight_match(line, &range(10, 13));
+        assert!(highlighted.starts_with("x = \"🎉\"; \u{1b}["), "got: {highlighted:?}");
+        assert!(highlighted.ends_with(".start()"), "got: {highlighted:?}");
+        assert_eq!(formatter.highlight_match(line, &range(10, 13)), line);
+        assert_eq!(colored.highlight_match(line, &range(40, 50)), line);
+    }
+
     #[test]
     fn test_format_enriched_references_json() {
         let formatter = OutputFormatter::new(OutputFormat::Json);
@@ -2521,9 +7782,11 @@ mod tests {
             displayed: vec![EnrichedReference {
                 location: make_location("file:///src/main.py", 10, 5),
                 context: "Handler.process".to_string(),
+                blame: None,
             }],
             remaining_count: 1,
             test_references: None,
+            omitted_count: 0,
         };
         let output = formatter.format_enriched_references_results(&[result], &SourceCache::new());
         let parsed: serde_json::Value = serde_json::from_str(&output).unwrap();
@@ -2543,18 +7806,22 @@ mod tests {
                 EnrichedReference {
                     location: make_location("file:///a.py", 1, 0),
                     context: "module scope".to_string(),
+                    blame: None,
                 },
                 EnrichedReference {
                     location: make_location("file:///b.py", 2, 0),
                     context: "foo".to_string(),
+                    blame: None,
                 },
                 EnrichedReference {
                     location: make_location("file:///c.py", 3, 0),
                     context: "bar".to_string(),
+                    blame: None,
                 },
             ],
             remaining_count: 0,
             test_references: None,
+            omitted_count: 0,
         };
         let output = formatter.format_enriched_references_results(&[result], &SourceCache::new());
 
@@ -2576,6 +7843,7 @@ mod tests {
             displayed: vec![EnrichedReference {
                 location: make_location("file:///project/src/main.py", 5, 0),
                 context: "module scope".to_string(),
+                blame: None,
             }],
             remaining_count: 1,
             test_references: Some(TestReferencesSection {
@@ -2583,6 +7851,7 @@ mod tests {
                 displayed: Vec::new(),
                 remaining_count: 0,
             }),
+            omitted_count: 0,
         };
         let output = formatter.format_enriched_references_results(&[result], &SourceCache::new());
         assert!(
@@ -2600,6 +7869,7 @@ mod tests {
             displayed: vec![EnrichedReference {
                 location: make_location("file:///project/src/main.py", 5, 0),
                 context: "module scope".to_string(),
+                blame: None,
             }],
             remaining_count: 0,
             test_references: Some(TestReferencesSection {
@@ -2607,9 +7877,11 @@ mod tests {
                 displayed: vec![EnrichedReference {
                     location: make_location("file:///project/tests/test_main.py", 3, 0),
                     context: "test_my_func".to_string(),
+                    blame: None,
                 }],
                 remaining_count: 0,
             }),
+            omitted_count: 0,
         };
         let output = formatter.format_enriched_references_results(&[result], &SourceCache::new());
         assert!(
@@ -2619,6 +7891,48 @@ mod tests {
         assert!(output.contains("test_main.py"), "should show test file, got:\n{output}");
     }
 
+    #[test]
+    fn test_format_enriched_refs_jsonl_has_one_line_per_reference() {
+        let formatter = OutputFormatter::new(OutputFormat::Jsonl);
+        let result = EnrichedReferencesResult {
+            label: "my_func".to_string(),
+            total_count: 2,
+            displayed: vec![
+                EnrichedReference {
+                    location: make_location("file:///project/src/main.py", 5, 0),
+                    context: "module scope".to_string(),
+                    blame: None,
+                },
+                EnrichedReference {
+                    location: make_location("file:///project/src/app.py", 1, 4),
+                    context: "run".to_string(),
+                    blame: None,
+                },
+            ],
+            remaining_count: 0,
+            test_references: Some(TestReferencesSection {
+                total_count: 1,
+                displayed: vec![EnrichedReference {
+                    location: make_location("file:///project/tests/test_main.py", 3, 0),
+                    context: "test_my_func".to_string(),
+                    blame: None,
+                }],
+                remaining_count: 0,
+            }),
+            omitted_count: 0,
+        };
+        let output = formatter.format_enriched_references_results(&[result], &SourceCache::new());
+        let records: Vec<serde_json::Value> =
+            output.lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+        assert_eq!(records.len(), 3, "got:\n{output}");
+        assert_eq!(records[1]["line"], 2);
+        assert_eq!(records[1]["column"], 5);
+        assert_eq!(records[1]["context"], "run");
+        assert_eq!(records[1]["symbol"], "my_func");
+        assert_eq!(records[1]["test"], false);
+        assert_eq!(records[2]["test"], true);
+    }
+
     #[test]
     fn test_format_enriched_refs_json_has_test_fields() {
         let formatter = OutputFormatter::new(OutputFormat::Json);
@@ -2628,9 +7942,11 @@ mod tests {
             displayed: vec![EnrichedReference {
                 location: make_location("file:///project/src/main.py", 5, 0),
                 context: "module scope".to_string(),
+                blame: None,
             }],
             remaining_count: 0,
             test_references: None,
+            omitted_count: 0,
         };
         let output = formatter.format_enriched_references_results(&[result], &SourceCache::new());
         let parsed: serde_json::Value = serde_json::from_str(&output).unwrap();
@@ -2647,6 +7963,7 @@ mod tests {
             displayed: vec![EnrichedReference {
                 location: make_location("file:///project/src/main.py", 5, 0),
                 context: "module scope".to_string(),
+                blame: None,
             }],
             remaining_count: 0,
             test_references: Some(TestReferencesSection {
@@ -2654,9 +7971,11 @@ mod tests {
                 displayed: vec![EnrichedReference {
                     location: make_location("file:///project/tests/test_main.py", 3, 0),
                     context: "test_my_func".to_string(),
+                    blame: None,
                 }],
                 remaining_count: 1,
             }),
+            omitted_count: 0,
         };
         let output = formatter.format_enriched_references_results(&[result], &SourceCache::new());
         let parsed: serde_json::Value = serde_json::from_str(&output).unwrap();
@@ -2673,6 +7992,7 @@ mod tests {
             displayed: vec![EnrichedReference {
                 location: make_location("file:///project/src/main.py", 5, 0),
                 context: "module scope".to_string(),
+                blame: None,
             }],
             remaining_count: 0,
             test_references: Some(TestReferencesSection {
@@ -2680,9 +8000,11 @@ mod tests {
                 displayed: vec![EnrichedReference {
                     location: make_location("file:///project/tests/test_main.py", 3, 0),
                     context: "test_func".to_string(),
+                    blame: None,
                 }],
                 remaining_count: 0,
             }),
+            omitted_count: 0,
         };
         let output = formatter.format_enriched_references_results(&[result], &SourceCache::new());
         assert!(output.contains(",test\n"), "should have test column header, got:\n{output}");
@@ -2699,9 +8021,11 @@ mod tests {
             displayed: vec![EnrichedReference {
                 location: make_location("file:///project/src/main.py", 5, 0),
                 context: "module scope".to_string(),
+                blame: None,
             }],
             remaining_count: 0,
             test_references: None,
+            omitted_count: 0,
         };
         let output = formatter.format_enriched_references_results(&[result], &SourceCache::new());
         assert!(
@@ -2790,6 +8114,7 @@ mod tests {
                 displayed: vec![EnrichedReference {
                     location: make_location("file:///project/tests/test_main.py", 3, 0),
                     context: "test_my_func".to_string(),
+                    blame: None,
                 }],
                 remaining_count: 0,
             }),
@@ -2938,7 +8263,7 @@ mod tests {
     fn test_color_never_produces_no_ansi_in_members() {
         let formatter = OutputFormatter::new(OutputFormat::Human);
         let result = members_tests::make_members_result();
-        let output = formatter.format_members_result(&result);
+        let output = formatter.format_members_result(&result, &SourceCache::new());
 
         assert!(
             !has_ansi(&output),
@@ -2951,7 +8276,7 @@ mod tests {
     fn test_color_always_produces_ansi_in_members() {
         let formatter = formatter_with_color();
         let result = members_tests::make_members_result();
-        let output = formatter.format_members_result(&result);
+        let output = formatter.format_members_result(&result, &SourceCache::new());
 
         assert!(
             has_ansi(&output),
@@ -3134,6 +8459,7 @@ mod tests {
             .map(|i| EnrichedReference {
                 location: make_location("file:///ref.py", u32::try_from(i).unwrap(), 0),
                 context: "module scope".to_string(),
+                blame: None,
             })
             .collect();
         EnrichedReferencesResult {
@@ -3142,9 +8468,36 @@ mod tests {
             displayed,
             remaining_count: 0,
             test_references: None,
+            omitted_count: 0,
         }
     }
 
+    #[test]
+    fn test_trim_end_sink_drops_trailing_whitespace_only() {
+        let mut buf = Vec::new();
+        let mut sink = TrimEndSink::new(&mut buf);
+        sink.write_str("1. a.py:1:1\n\n").unwrap();
+        sink.write_str("   ").unwrap();
+        sink.write_str("x = 1\n\n\n").unwrap();
+        sink.finish(Ok(())).unwrap();
+        assert_eq!(String::from_utf8(buf).unwrap(), "1. a.py:1:1\n\n   x = 1\n");
+    }
+
+    #[test]
+    fn test_format_enriched_references_notes_max_results_truncation() {
+        let mut result = make_enriched_result("foo", 2);
+        result.omitted_count = 30_000;
+
+        let human = OutputFormatter::new(OutputFormat::Human)
+            .format_enriched_references_results(std::slice::from_ref(&result), &SourceCache::new());
+        assert!(human.contains("30000 more not shown"), "got:\n{human}");
+
+        let json = OutputFormatter::new(OutputFormat::Json)
+            .format_enriched_references_results(&[result], &SourceCache::new());
+        let parsed: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed["omitted_count"], 30_000);
+    }
+
     #[test]
     fn test_format_enriched_references_multiple_human() {
         let formatter = OutputFormatter::new(OutputFormat::Human);
@@ -3266,7 +8619,7 @@ mod tests {
         let child = make_doc_symbol("method", SymbolKind::Method, 2, 4, None);
         let parent = make_doc_symbol("MyClass", SymbolKind::Class, 0, 5, Some(vec![child]));
         let symbols = vec![parent];
-        let result = formatter.format_document_symbols(&symbols);
+        let result = formatter.format_document_symbols(&symbols, "test.py", &SourceCache::new());
         assert!(result.contains("MyClass"));
         assert!(result.contains("method"));
     }
@@ -3275,7 +8628,7 @@ mod tests {
     fn test_format_document_symbols_json() {
         let formatter = OutputFormatter::new(OutputFormat::Json);
         let symbols = vec![make_doc_symbol("MyClass", SymbolKind::Class, 0, 5, None)];
-        let result = formatter.format_document_symbols(&symbols);
+        let result = formatter.format_document_symbols(&symbols, "test.py", &SourceCache::new());
         let parsed: serde_json::Value = serde_json::from_str(&result).unwrap();
         assert!(parsed.is_array());
     }
@@ -3284,7 +8637,7 @@ mod tests {
     fn test_format_document_symbols_csv() {
         let formatter = OutputFormatter::new(OutputFormat::Csv);
         let symbols = vec![make_doc_symbol("MyClass", SymbolKind::Class, 0, 5, None)];
-        let result = formatter.format_document_symbols(&symbols);
+        let result = formatter.format_document_symbols(&symbols, "test.py", &SourceCache::new());
         assert!(result.starts_with("name,kind,line,column\n"));
         assert!(result.contains("MyClass"));
     }
@@ -3308,17 +8661,34 @@ mod tests {
     fn test_format_workspace_symbols_json() {
         let formatter = OutputFormatter::new(OutputFormat::Json);
         let symbols = vec![make_symbol_info("MyClass", SymbolKind::Class, "file:///a.py", 0)];
-        let result = formatter.format_workspace_symbols(&symbols);
+        let result = formatter.format_workspace_symbols(&symbols, &SourceCache::new());
         let parsed: serde_json::Value = serde_json::from_str(&result).unwrap();
         assert!(parsed.is_array());
         assert_eq!(parsed[0]["name"], "MyClass");
     }
 
+    #[test]
+    fn test_format_workspace_symbols_jsonl() {
+        let formatter = OutputFormatter::new(OutputFormat::Jsonl);
+        let symbols = vec![
+            make_symbol_info("MyClass", SymbolKind::Class, "file:///a.py", 0),
+            make_symbol_info("helper", SymbolKind::Function, "file:///b.py", 4),
+        ];
+        let result = formatter.format_workspace_symbols(&symbols, &SourceCache::new());
+        let records: Vec<serde_json::Value> =
+            result.lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[1]["name"], "helper");
+        assert_eq!(records[1]["kind"], "Function");
+        assert_eq!(records[1]["file"], "/b.py");
+        assert_eq!(records[1]["line"], 5);
+    }
+
     #[test]
     fn test_format_workspace_symbols_csv() {
         let formatter = OutputFormatter::new(OutputFormat::Csv);
         let symbols = vec![make_symbol_info("MyClass", SymbolKind::Class, "file:///a.py", 0)];
-        let result = formatter.format_workspace_symbols(&symbols);
+        let result = formatter.format_workspace_symbols(&symbols, &SourceCache::new());
         assert!(result.starts_with("name,kind,file,line,column\n"));
         assert!(result.contains("MyClass"));
     }
@@ -3330,11 +8700,54 @@ mod tests {
             make_symbol_info("A", SymbolKind::Class, "file:///a.py", 0),
             make_symbol_info("B", SymbolKind::Function, "file:///b.py", 0),
         ];
-        let result = formatter.format_workspace_symbols(&symbols);
+        let result = formatter.format_workspace_symbols(&symbols, &SourceCache::new());
         assert!(result.contains("a.py"));
         assert!(result.contains("b.py"));
     }
 
+    #[test]
+    fn test_json_envelope_wraps_results_in_an_array() {
+        let invocation = Invocation {
+            command: "find".to_string(),
+            query: serde_json::Map::from_iter([("symbols".to_string(), json!(["User"]))]),
+        };
+        let formatter = OutputFormatter::new(OutputFormat::Json).with_envelope(Some(invocation));
+        let cache = SourceCache::new();
+
+        let locations = [make_location("file:///p/app.py", 2, 4)];
+        let output = formatter.format_definitions(&locations, "User", &cache);
+        let envelope: serde_json::Value = serde_json::from_str(&output).unwrap();
+        assert_eq!(envelope["version"], JSON_ENVELOPE_VERSION);
+        assert_eq!(envelope["command"], "find");
+        assert_eq!(envelope["query"], json!({"symbols": ["User"]}));
+        assert_eq!(envelope["results"][0]["uri"], "file:///p/app.py");
+
+        // A single object is a result of its own; nothing is no results.
+        let object = formatter.json(&json!({"symbol": "User"}));
+        let envelope: serde_json::Value = serde_json::from_str(&object).unwrap();
+        assert_eq!(envelope["results"], json!([{"symbol": "User"}]));
+        let nothing = formatter.format_definitions(&[], "User", &cache);
+        let envelope: serde_json::Value = serde_json::from_str(&nothing).unwrap();
+        assert_eq!(envelope["results"], json!([]));
+
+        // Without an envelope the results are printed as they are.
+        let bare = OutputFormatter::new(OutputFormat::Json);
+        let output = bare.format_definitions(&locations, "User", &cache);
+        assert!(serde_json::from_str::<serde_json::Value>(&output).unwrap().is_array());
+    }
+
+    #[test]
+    fn test_format_workspace_symbols_from_continues_numbering() {
+        let formatter = OutputFormatter::new(OutputFormat::Human);
+        let symbols = vec![make_symbol_info("B", SymbolKind::Function, "file:///b.py", 0)];
+        let result = formatter.format_workspace_symbols_from(&symbols, 100, &SourceCache::new());
+        assert!(result.contains("101."), "numbered after earlier parts: {result}");
+
+        let csv = OutputFormatter::new(OutputFormat::Csv);
+        let result = csv.format_workspace_symbols_from(&symbols, 100, &SourceCache::new());
+        assert!(!result.contains("name,kind"), "no second header: {result}");
+    }
+
     // ========================================================================
     // kind_label
     // ========================================================================
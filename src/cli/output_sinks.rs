@@ -0,0 +1,335 @@
+//! Where command output goes when it isn't simply the terminal: a file
+//! (`--output-file`), and on the way a list of locations for an editor
+//! (`--format vim-qf` or `--format emacs`).
+//!
+//! Commands print straight to stdout, so [`Redirect`] points the process's
+//! stdout at a pipe while the command runs and copies what comes out of it
+//! into an [`OutputSink`]. Editor formats are printed as JSON lines and each
+//! record with a location becomes one line of the list; everything else is
+//! copied as is.
+
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+use std::process::Command;
+
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use serde_json::{Map, Value};
+
+/// A list of locations an editor can step through.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum LocationList {
+    /// Vim's quickfix list: `file:line:column: text`, for `vim -q`
+    VimQf,
+    /// GNU style `file:line.column: text`, for Emacs' compilation mode
+    Emacs,
+}
+
+impl LocationList {
+    /// The list entry for one JSON lines `record`, if it has a location.
+    pub fn entry(self, record: &str) -> Option<String> {
+        let Ok(Value::Object(record)) = serde_json::from_str(record) else { return None };
+        let file = record.get("file")?.as_str()?;
+        let line = record.get("line")?.as_u64()?;
+        let text = entry_text(&record);
+        let separator = match self {
+            Self::VimQf => ':',
+            Self::Emacs => '.',
+        };
+        Some(match record.get("column").and_then(Value::as_u64) {
+            Some(column) => format!("{file}:{line}{separator}{column}: {text}"),
+            None => format!("{file}:{line}: {text}"),
+        })
+    }
+
+    /// The command that opens `file` as this list: `vim -q`, or the file
+    /// in Emacs' compilation mode. `$VISUAL`/`$EDITOR` is used when it names
+    /// the right editor, so that e.g. `nvim` stays `nvim`.
+    pub fn editor(self, file: &Path) -> Command {
+        let (wanted, fallback) = match self {
+            Self::VimQf => ("vim", "vim"),
+            Self::Emacs => ("emacs", "emacs"),
+        };
+        let mut command = editor_command(Some(wanted)).unwrap_or_else(|| Command::new(fallback));
+        match self {
+            Self::VimQf => command.arg("-q").arg(file),
+            Self::Emacs => command.arg(file).arg("-f").arg("compilation-mode"),
+        };
+        command
+    }
+}
+
+/// What a list entry says about its location: a diagnostic's severity and
+/// message, or the symbol there with its kind and enclosing scope.
+fn entry_text(record: &Map<String, Value>) -> String {
+    let field = |key| record.get(key).and_then(Value::as_str).filter(|s| !s.is_empty());
+    if let Some(message) = field("message") {
+        return match field("severity") {
+            Some(severity) => format!("{severity}: {message}"),
+            None => message.to_string(),
+        };
+    }
+    let name = field("symbol").or_else(|| field("name"));
+    let details: Vec<&str> = [field("kind"), field("context")].into_iter().flatten().collect();
+    match (name, details.is_empty()) {
+        (Some(name), true) => name.to_string(),
+        (Some(name), false) => format!("{name} ({})", details.join(", ")),
+        (None, _) => {
+            field("text").map_or_else(|| details.join(", "), |text| text.trim().to_string())
+        }
+    }
+}
+
+/// `$VISUAL`, else `$EDITOR`, split into program and arguments; with
+/// `wanted`, only if the program's name contains it.
+fn editor_command(wanted: Option<&str>) -> Option<Command> {
+    let editor = ["VISUAL", "EDITOR"]
+        .into_iter()
+        .find_map(|name| std::env::var(name).ok().filter(|e| !e.trim().is_empty()))?;
+    let mut words = editor.split_whitespace();
+    let program = words.next()?;
+    let name = Path::new(program).file_name()?.to_string_lossy();
+    if wanted.is_some_and(|wanted| !name.contains(wanted)) {
+        return None;
+    }
+    let mut command = Command::new(program);
+    command.args(words);
+    Some(command)
+}
+
+/// Open `file` in the editor, as `list` if the output is one, and wait for
+/// the editor to exit.
+pub fn open_in_editor(file: &Path, list: Option<LocationList>) -> Result<()> {
+    let mut command = list.map_or_else(
+        || {
+            let mut command = editor_command(None).unwrap_or_else(|| Command::new("vi"));
+            command.arg(file);
+            command
+        },
+        |list| list.editor(file),
+    );
+    let program = command.get_program().to_string_lossy().into_owned();
+    let status = command.status().with_context(|| format!("Failed to start {program}"))?;
+    if !status.success() {
+        anyhow::bail!("{program} exited with {status}");
+    }
+    Ok(())
+}
+
+/// Somewhere command output can be sent.
+pub trait OutputSink: Write + Send {
+    /// Called once all output is written.
+    fn finish(&mut self) -> io::Result<()> {
+        self.flush()
+    }
+}
+
+/// Output written to a file (`--output-file`).
+pub struct FileSink(BufWriter<File>);
+
+impl FileSink {
+    pub fn create(path: &Path) -> Result<Self> {
+        let file = File::create(path)
+            .with_context(|| format!("Failed to create output file {}", path.display()))?;
+        Ok(Self(BufWriter::new(file)))
+    }
+}
+
+impl Write for FileSink {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()
+    }
+}
+
+impl OutputSink for FileSink {
+    fn finish(&mut self) -> io::Result<()> {
+        self.0.flush()?;
+        self.0.get_ref().sync_all()
+    }
+}
+
+/// Output written to the stdout the process started with, once stdout
+/// itself points elsewhere.
+pub struct StdoutSink(File);
+
+impl Write for StdoutSink {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()
+    }
+}
+
+impl OutputSink for StdoutSink {}
+
+/// Copy `output` into `sink`, as `list` entries if given.
+fn copy_output(
+    output: impl io::Read,
+    sink: &mut dyn OutputSink,
+    list: Option<LocationList>,
+) -> io::Result<()> {
+    match list {
+        Some(list) => {
+            for line in BufReader::new(output).lines() {
+                if let Some(entry) = list.entry(&line?) {
+                    writeln!(sink, "{entry}")?;
+                }
+            }
+        }
+        None => {
+            io::copy(&mut BufReader::new(output), sink)?;
+        }
+    }
+    sink.finish()
+}
+
+#[cfg(unix)]
+pub use redirect::Redirect;
+
+/// Stdout can only be redirected on Unix.
+#[cfg(not(unix))]
+pub struct Redirect(());
+
+#[cfg(not(unix))]
+impl Redirect {
+    pub fn start(_sink: Option<Box<dyn OutputSink>>, _list: Option<LocationList>) -> Result<Self> {
+        anyhow::bail!("--output-file and editor formats are only supported on Unix systems")
+    }
+
+    pub fn finish(self) -> Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(unix)]
+mod redirect {
+    use std::fs::File;
+    use std::io::{self, Write};
+    use std::os::fd::{AsFd, AsRawFd, OwnedFd};
+    use std::thread::JoinHandle;
+
+    use anyhow::{Context, Result};
+
+    use super::{copy_output, LocationList, OutputSink, StdoutSink};
+
+    /// The process's stdout pointed at an [`OutputSink`] until [`finish`].
+    ///
+    /// [`finish`]: Redirect::finish
+    pub struct Redirect {
+        /// The stdout the process started with, put back by `finish`
+        saved: OwnedFd,
+        copier: JoinHandle<io::Result<()>>,
+    }
+
+    impl Redirect {
+        /// Send stdout to `sink`, or with only a `list`, back to the
+        /// original stdout as list entries.
+        pub fn start(
+            sink: Option<Box<dyn OutputSink>>,
+            list: Option<LocationList>,
+        ) -> Result<Self> {
+            let saved =
+                io::stdout().as_fd().try_clone_to_owned().context("Failed to save stdout")?;
+            let mut sink = match sink {
+                Some(sink) => sink,
+                None => Box::new(StdoutSink(File::from(
+                    saved.try_clone().context("Failed to save stdout")?,
+                ))),
+            };
+            let (reader, writer) = io::pipe().context("Failed to redirect stdout")?;
+            point_stdout_at(&writer).context("Failed to redirect stdout")?;
+            // Stdout holds the write end now; the copier stops once it's closed.
+            drop(writer);
+            let copier = std::thread::spawn(move || {
+                let mut reader = reader;
+                match copy_output(&mut reader, sink.as_mut(), list) {
+                    // Whoever read the output stopped (`tyf ... | head`); keep
+                    // draining the pipe so the command isn't stopped mid-print.
+                    Err(e) if e.kind() == io::ErrorKind::BrokenPipe => {
+                        io::copy(&mut reader, &mut io::sink()).map(drop)
+                    }
+                    copied => copied,
+                }
+            });
+            Ok(Self { saved, copier })
+        }
+
+        /// Put stdout back and wait for everything printed to reach the sink.
+        pub fn finish(self) -> Result<()> {
+            let flushed = io::stdout().flush();
+            point_stdout_at(&self.saved).context("Failed to restore stdout")?;
+            flushed.context("Failed to write output")?;
+            match self.copier.join() {
+                Ok(copied) => copied.context("Failed to write output"),
+                Err(_) => anyhow::bail!("Failed to write output: the copying thread panicked"),
+            }
+        }
+    }
+
+    /// Make file descriptor 1 refer to what `fd` does.
+    #[allow(unsafe_code)]
+    fn point_stdout_at(fd: &impl AsRawFd) -> io::Result<()> {
+        // SAFETY: `dup2` only duplicates descriptors; `fd` is open for as
+        // long as it's borrowed, and descriptor 1 is replaced atomically.
+        let result = unsafe { libc::dup2(fd.as_raw_fd(), libc::STDOUT_FILENO) };
+        if result == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn location_list_entries_for_records_with_a_location() {
+        let reference =
+            r#"{"file":"/p/app.py","line":3,"column":5,"symbol":"User","context":"run"}"#;
+        assert_eq!(LocationList::VimQf.entry(reference).unwrap(), "/p/app.py:3:5: User (run)");
+        assert_eq!(LocationList::Emacs.entry(reference).unwrap(), "/p/app.py:3.5: User (run)");
+
+        let diagnostic =
+            r#"{"file":"/p/app.py","line":1,"severity":"error","message":"Name `x` used"}"#;
+        assert_eq!(
+            LocationList::VimQf.entry(diagnostic).unwrap(),
+            "/p/app.py:1: error: Name `x` used"
+        );
+
+        assert_eq!(LocationList::VimQf.entry(r#"{"symbol":"User","count":3}"#), None);
+        assert_eq!(LocationList::VimQf.entry("No symbols found"), None);
+    }
+
+    #[test]
+    fn copy_output_keeps_only_list_entries() {
+        struct Collect(Vec<u8>);
+        impl Write for Collect {
+            fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+                self.0.write(buf)
+            }
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+        impl OutputSink for Collect {}
+
+        let output =
+            "{\"file\":\"a.py\",\"line\":1,\"column\":2,\"name\":\"f\",\"kind\":\"Function\"}\n\
+                      not a record\n";
+        let mut sink = Collect(Vec::new());
+        copy_output(output.as_bytes(), &mut sink, Some(LocationList::VimQf)).unwrap();
+        assert_eq!(String::from_utf8(sink.0).unwrap(), "a.py:1:2: f (Function)\n");
+
+        let mut sink = Collect(Vec::new());
+        copy_output(output.as_bytes(), &mut sink, None).unwrap();
+        assert_eq!(String::from_utf8(sink.0).unwrap(), output);
+    }
+}
@@ -0,0 +1,122 @@
+//! The progress line drawn on stderr while a daemon request runs.
+//!
+//! ty reports long work (indexing the workspace, a references search over
+//! it) with `$/progress` notifications, which the daemon forwards while the
+//! request is in flight. Commands show the latest one on a single line that
+//! is redrawn in place and erased before the results are printed.
+
+use std::fmt::Write as _;
+use std::io::Write;
+
+use ratatui::crossterm::terminal;
+
+use crate::daemon::client::ProgressSink;
+use crate::lsp::protocol::WorkProgress;
+
+const SPINNER: [char; 10] = ['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
+
+/// Width of the bar drawn when the server reports a percentage.
+const BAR_WIDTH: usize = 20;
+
+/// Progress shown on stderr, which must be a terminal.
+#[derive(Default)]
+pub struct StderrProgress {
+    /// Updates drawn so far, which picks the spinner frame.
+    frames: usize,
+    /// Whether the line currently shows something to erase.
+    shown: bool,
+}
+
+impl StderrProgress {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn clear(&mut self) {
+        if std::mem::take(&mut self.shown) {
+            let mut stderr = std::io::stderr().lock();
+            let _ = write!(stderr, "\r\x1b[2K");
+            let _ = stderr.flush();
+        }
+    }
+}
+
+impl ProgressSink for StderrProgress {
+    fn update(&mut self, progress: &WorkProgress) {
+        if progress.done {
+            self.clear();
+            return;
+        }
+        // Some terminals (and pseudo-terminals) report a width of 0.
+        let width = terminal::size()
+            .ok()
+            .map(|(columns, _)| usize::from(columns))
+            .filter(|&columns| columns > 0)
+            .unwrap_or(80);
+        let line = progress_line(SPINNER[self.frames % SPINNER.len()], progress, width);
+        self.frames += 1;
+        self.shown = true;
+        let mut stderr = std::io::stderr().lock();
+        let _ = write!(stderr, "\r\x1b[2K{line}");
+        let _ = stderr.flush();
+    }
+
+    fn finish(&mut self) {
+        self.clear();
+    }
+}
+
+impl Drop for StderrProgress {
+    fn drop(&mut self) {
+        self.clear();
+    }
+}
+
+/// One progress line, cut to `width` characters: a bar when the server
+/// knows how far along it is, else the spinner `frame`.
+fn progress_line(frame: char, progress: &WorkProgress, width: usize) -> String {
+    let mut line = match progress.percentage {
+        Some(percentage) => {
+            let filled = BAR_WIDTH * percentage.min(100) as usize / 100;
+            format!(
+                "{} [{}{}] {percentage:>3}%",
+                progress.title,
+                "#".repeat(filled),
+                "-".repeat(BAR_WIDTH - filled)
+            )
+        }
+        None => format!("{frame} {}", progress.title),
+    };
+    if let Some(message) = progress.message.as_deref().filter(|m| !m.is_empty()) {
+        let _ = write!(line, " {message}");
+    }
+    // Leave the last column free so the line never wraps.
+    line.chars().take(width.saturating_sub(1)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn progress(percentage: Option<u32>, message: Option<&str>) -> WorkProgress {
+        WorkProgress {
+            title: "Indexing".to_string(),
+            message: message.map(str::to_string),
+            percentage,
+            done: false,
+        }
+    }
+
+    #[test]
+    fn progress_line_draws_bar_for_percentage() {
+        let line = progress_line('⠋', &progress(Some(45), Some("models.py")), 80);
+        assert_eq!(line, "Indexing [#########-----------]  45% models.py");
+    }
+
+    #[test]
+    fn progress_line_spins_without_percentage_and_fits_width() {
+        assert_eq!(progress_line('⠙', &progress(None, None), 80), "⠙ Indexing");
+        let line = progress_line('⠙', &progress(None, Some("a very long message")), 16);
+        assert_eq!(line, "⠙ Indexing a ve");
+    }
+}
@@ -102,6 +102,36 @@ impl Styler {
         }
     }
 
+    /// The matched text in `refs --context` source lines.
+    /// Bold yellow.
+    pub fn highlight(self, text: &str) -> String {
+        if self.color.enabled() {
+            format!("{}", text.bold().yellow())
+        } else {
+            text.to_string()
+        }
+    }
+
+    /// Python keywords in `context` source.
+    /// Bold blue.
+    pub fn keyword(self, text: &str) -> String {
+        if self.color.enabled() {
+            format!("{}", text.bold().blue())
+        } else {
+            text.to_string()
+        }
+    }
+
+    /// String literals in `context` source.
+    /// Green.
+    pub fn literal(self, text: &str) -> String {
+        if self.color.enabled() {
+            format!("{}", text.green())
+        } else {
+            text.to_string()
+        }
+    }
+
     /// Error messages.
     /// Red.
     pub fn error(self, text: &str) -> String {
@@ -112,6 +142,16 @@ impl Styler {
         }
     }
 
+    /// Warning severities in `check` output.
+    /// Yellow.
+    pub fn warning(self, text: &str) -> String {
+        if self.color.enabled() {
+            format!("{}", text.yellow())
+        } else {
+            text.to_string()
+        }
+    }
+
     /// Format a file path with colored line:col suffix.
     ///
     /// E.g. `src/models.py` in cyan + `:15:1` in dim.
@@ -147,7 +187,10 @@ mod tests {
         assert_eq!(s.symbol("MyClass"), "MyClass");
         assert_eq!(s.file_location("src/foo.py", 15, 1), "src/foo.py:15:1");
         assert_eq!(s.error("boom"), "boom");
+        assert_eq!(s.warning("careful"), "careful");
         assert_eq!(s.dim("[class]"), "[class]");
+        assert_eq!(s.keyword("def"), "def");
+        assert_eq!(s.literal("'x'"), "'x'");
     }
 
     #[test]
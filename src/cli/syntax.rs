@@ -0,0 +1,117 @@
+//! Just enough Python tokenizing to color source for `tyf context`.
+//!
+//! Keywords, string literals and comments are styled; everything else is
+//! copied as is. Without color the output is the input, byte for byte.
+
+use crate::cli::style::Styler;
+use crate::workspace::navigation::is_identifier_char;
+
+/// Python's keywords, including the soft ones that start statements.
+const KEYWORDS: &[&str] = &[
+    "False", "None", "True", "and", "as", "assert", "async", "await", "break", "case", "class",
+    "continue", "def", "del", "elif", "else", "except", "finally", "for", "from", "global", "if",
+    "import", "in", "is", "lambda", "match", "nonlocal", "not", "or", "pass", "raise", "return",
+    "try", "type", "while", "with", "yield",
+];
+
+/// A [`Styler`] method, applied to one kind of token.
+type Paint = fn(Styler, &str) -> String;
+
+/// `source` with its keywords, strings and comments styled by `s`.
+pub fn highlight_python(source: &str, s: Styler) -> String {
+    let mut out = String::with_capacity(source.len());
+    let mut rest = source;
+    while let Some(c) = rest.chars().next() {
+        let (len, style): (usize, Option<Paint>) = if c == '#' {
+            (rest.find('\n').unwrap_or(rest.len()), Some(Styler::dim))
+        } else if c.is_alphabetic() || c == '_' {
+            let word = rest.find(|c: char| !is_identifier_char(c)).unwrap_or(rest.len());
+            if is_string_prefix(&rest[..word]) && rest[word..].starts_with(['"', '\'']) {
+                (word + string_len(&rest[word..]), Some(Styler::literal))
+            } else if KEYWORDS.contains(&&rest[..word]) {
+                (word, Some(Styler::keyword))
+            } else {
+                (word, None)
+            }
+        } else if c == '"' || c == '\'' {
+            (string_len(rest), Some(Styler::literal))
+        } else {
+            (c.len_utf8(), None)
+        };
+        let (token, after) = rest.split_at(len);
+        match style {
+            Some(style) => paint(&mut out, token, s, style),
+            None => out.push_str(token),
+        }
+        rest = after;
+    }
+    out
+}
+
+/// Push `token` styled line by line, so a docstring's color doesn't run
+/// across line breaks.
+fn paint(out: &mut String, token: &str, s: Styler, style: Paint) {
+    for (i, line) in token.split('\n').enumerate() {
+        if i > 0 {
+            out.push('\n');
+        }
+        if !line.is_empty() {
+            out.push_str(&style(s, line));
+        }
+    }
+}
+
+/// Whether `word` can prefix a string literal, like the `rb` of `rb"..."`.
+fn is_string_prefix(word: &str) -> bool {
+    word.len() <= 2 && word.chars().all(|c| matches!(c.to_ascii_lowercase(), 'r' | 'b' | 'f' | 'u'))
+}
+
+/// Length of the string literal `text` starts with, up to and including its
+/// closing quote, or to the end of the line (or of `text`, for triple
+/// quotes) if it is never closed.
+fn string_len(text: &str) -> usize {
+    let quote = &text[..1];
+    let triple = quote.repeat(3);
+    let (open, close) = if text.starts_with(&triple) { (3, triple.as_str()) } else { (1, quote) };
+    let mut chars = text.char_indices().skip(open);
+    while let Some((i, c)) = chars.next() {
+        if c == '\\' {
+            chars.next();
+        } else if text[i..].starts_with(close) {
+            return i + close.len();
+        } else if c == '\n' && open == 1 {
+            return i;
+        }
+    }
+    text.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::style::UseColor;
+
+    #[test]
+    fn test_highlight_without_color_is_the_source() {
+        let source = "def f(x):\n    \"\"\"Doc\n    more\"\"\"\n    return rb'\\'' # done\n";
+        assert_eq!(highlight_python(source, Styler::no_color()), source);
+    }
+
+    #[test]
+    fn test_highlight_styles_keywords_strings_and_comments() {
+        let s = Styler::new(UseColor::Yes);
+        let out = highlight_python("if name == 'def':  # if\n    pass", s);
+        assert!(out.starts_with(&s.keyword("if")), "got: {out:?}");
+        assert!(out.contains(&s.literal("'def'")), "got: {out:?}");
+        assert!(out.contains(&s.dim("# if")), "got: {out:?}");
+        assert!(out.ends_with(&s.keyword("pass")), "got: {out:?}");
+        assert!(out.contains(" name == "), "identifiers stay plain, got: {out:?}");
+    }
+
+    #[test]
+    fn test_string_len() {
+        assert_eq!(string_len("'a\\'b' + c"), 6);
+        assert_eq!(string_len("\"\"\"x\n\"y\"\n\"\"\" + c"), 12);
+        assert_eq!(string_len("'open\nnext"), 5);
+    }
+}
@@ -0,0 +1,106 @@
+//! Symbol counting for `tyf stats`: turns a file's outline into counts.
+
+use std::ops::AddAssign;
+
+use crate::lsp::protocol::{DocumentSymbol, SymbolKind};
+
+/// How many classes, functions and methods a file defines.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SymbolCounts {
+    pub classes: usize,
+    /// Functions outside a class, including ones nested in other functions.
+    pub functions: usize,
+    /// Functions defined directly in a class body.
+    pub methods: usize,
+}
+
+impl AddAssign for SymbolCounts {
+    fn add_assign(&mut self, other: Self) {
+        self.classes += other.classes;
+        self.functions += other.functions;
+        self.methods += other.methods;
+    }
+}
+
+/// Count the classes, functions and methods of an outline, at any depth.
+///
+/// ty reports methods as functions or methods depending on the version, so
+/// a function is a method by where it sits: directly in a class.
+pub fn count(symbols: &[DocumentSymbol]) -> SymbolCounts {
+    let mut counts = SymbolCounts::default();
+    count_into(symbols, false, &mut counts);
+    counts
+}
+
+fn count_into(symbols: &[DocumentSymbol], in_class: bool, counts: &mut SymbolCounts) {
+    for symbol in symbols {
+        match symbol.kind {
+            SymbolKind::Class => counts.classes += 1,
+            SymbolKind::Method | SymbolKind::Constructor => counts.methods += 1,
+            SymbolKind::Function if in_class => counts.methods += 1,
+            SymbolKind::Function => counts.functions += 1,
+            _ => {}
+        }
+        if let Some(children) = &symbol.children {
+            count_into(children, symbol.kind == SymbolKind::Class, counts);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lsp::protocol::{Position, Range};
+
+    fn symbol(name: &str, kind: SymbolKind, children: Vec<DocumentSymbol>) -> DocumentSymbol {
+        let range = Range {
+            start: Position { line: 0, character: 0 },
+            end: Position { line: 0, character: 1 },
+        };
+        DocumentSymbol {
+            name: name.to_string(),
+            detail: None,
+            kind,
+            tags: None,
+            deprecated: None,
+            range: range.clone(),
+            selection_range: range,
+            children: (!children.is_empty()).then_some(children),
+        }
+    }
+
+    #[test]
+    fn count_tells_methods_from_functions_by_nesting() {
+        let symbols = vec![
+            symbol(
+                "Order",
+                SymbolKind::Class,
+                vec![
+                    symbol("__init__", SymbolKind::Constructor, vec![]),
+                    symbol(
+                        "total",
+                        SymbolKind::Function,
+                        vec![symbol("add", SymbolKind::Function, vec![])],
+                    ),
+                    symbol(
+                        "Meta",
+                        SymbolKind::Class,
+                        vec![symbol("m", SymbolKind::Method, vec![])],
+                    ),
+                    symbol("status", SymbolKind::Field, vec![]),
+                ],
+            ),
+            symbol("main", SymbolKind::Function, vec![]),
+            symbol("LIMIT", SymbolKind::Constant, vec![]),
+        ];
+        assert_eq!(count(&symbols), SymbolCounts { classes: 2, functions: 2, methods: 3 });
+        assert_eq!(count(&[]), SymbolCounts::default());
+    }
+
+    #[test]
+    fn counts_add_up() {
+        let mut total = SymbolCounts { classes: 1, functions: 2, methods: 3 };
+        total += SymbolCounts { classes: 1, functions: 0, methods: 4 };
+        assert_eq!(total, SymbolCounts { classes: 2, functions: 2, methods: 7 });
+    }
+}
@@ -1,36 +1,74 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
 use std::collections::{HashMap, HashSet};
+#[cfg(unix)]
+use std::io::IsTerminal;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::time::Duration;
 
 #[cfg(unix)]
-use crate::cli::args::DaemonCommands;
+use crate::blame::BlameCache;
+use crate::cli::args::SymbolKindFilter;
+use crate::cli::args::{AliasCommands, Cli, Installer, ReferenceSort, SeverityLevel};
+#[cfg(unix)]
+use crate::cli::args::{CacheCommands, DaemonCommands};
+use crate::cli::exit::NoResults;
+use crate::cli::exit::UsageError;
+#[cfg(unix)]
+use crate::cli::output::{find_defined_symbol, DefinitionBody, ReferencesPrinter, SymbolsPrinter};
 use crate::cli::output::{
-    find_enclosing_symbol, EnrichedReference, EnrichedReferencesResult, OutputFormatter, ShowEntry,
-    SourceCache,
+    find_enclosing_symbol, AliasEntry, ConfigFile, ConfigReport, ConfigSetting, EnrichedReference,
+    EnrichedReferencesResult, ExplainTypeResult, HoverEntry, OccurrencesEntry, OutputFormatter,
+    ReferenceCount, ShowEntry, SourceCache, TypeExpansion,
 };
 #[cfg(unix)]
-use crate::daemon::client::{ensure_daemon_running, spawn_daemon, DaemonClient, CLIENT_VERSION};
+use crate::cli::progress::StderrProgress;
+use crate::config::{Config, Source};
+#[cfg(unix)]
+use crate::daemon::client::{
+    ensure_daemon_running, spawn_daemon, DaemonClient, DaemonFailure, CLIENT_VERSION,
+};
 #[cfg(unix)]
-use crate::daemon::protocol::BatchReferencesQuery;
+use crate::daemon::protocol::{
+    BatchReferencesEntry, BatchReferencesQuery, FileReferenceCount, GotoEntry,
+    WorkspaceSymbolsResult, DEFAULT_DEFINITIONS_CONCURRENCY, DEFAULT_REFERENCES_CONCURRENCY,
+};
 #[cfg(unix)]
 use crate::daemon::server::DaemonServer;
 use crate::debug::DebugLog;
 use crate::lsp::client::TyLspClient;
-use crate::lsp::protocol::{DocumentSymbol, Location};
-use crate::workspace::navigation::SymbolFinder;
+use crate::lsp::position::{convert_column, ColumnUnit, DISPLAY_UNIT, LSP_UNIT};
+use crate::lsp::protocol::{DocumentSymbol, GotoKind, Location, SymbolInformation, SymbolKind};
+use crate::lsp::server::TyLspServer;
+use crate::lsp::uri::{path_to_uri, uri_to_path};
+use crate::setup;
+use crate::workspace::filter::PathFilter;
+use crate::workspace::navigation::{find_words, is_identifier_char, SymbolFinder};
+use crate::workspace::origin::{retain_workspace_locations, Origin};
+use crate::workspace::page::ResultPage;
+#[cfg(unix)]
+use crate::workspace::source_cache::SourceFile;
 
-/// Helper: connect to the daemon and attach the debug log if present.
+/// Helper: start the daemon if needed, connect, and attach the debug log if present.
+///
+/// When stderr is a terminal, ty's progress on long requests is shown there.
+///
+/// Commands call this once and send every request over the returned client,
+/// rather than reconnecting for each step.
 #[cfg(unix)]
-async fn connect_daemon(
+pub async fn connect_daemon(
     timeout: Duration,
     debug_log: Option<&Arc<DebugLog>>,
 ) -> Result<DaemonClient> {
-    let mut client = DaemonClient::connect_with_timeout(timeout).await?;
+    let mut client = ensure_daemon_running().await.map_err(DaemonFailure::wrap)?;
+    client.set_timeout(timeout);
     if let Some(log) = debug_log {
-        let socket_path = crate::daemon::client::get_socket_path()?;
-        log.log_daemon_connection(&socket_path.to_string_lossy(), true, None);
+        let socket = if crate::daemon::client::daemon_disabled() {
+            "in-process".to_string()
+        } else {
+            crate::daemon::client::get_socket_path()?.to_string_lossy().into_owned()
+        };
+        log.log_daemon_connection(&socket, true, None);
 
         // Log daemon version info via a quick ping
         if let Ok(ping) = client.ping().await {
@@ -39,9 +77,43 @@ async fn connect_daemon(
 
         client.set_debug_log(Arc::clone(log));
     }
+    if std::io::stderr().is_terminal() {
+        client.set_progress(Box::new(StderrProgress::new()));
+    }
     Ok(client)
 }
 
+/// `Ok` when a lookup found something, [`NoResults`] otherwise.
+///
+/// Called after the command has printed its results, including its own
+/// "nothing found" message, so only the exit status changes.
+fn found_any(found: bool) -> Result<()> {
+    if found {
+        Ok(())
+    } else {
+        Err(NoResults.into())
+    }
+}
+
+/// Tell the daemon that `changes` happened on disk, so ty stops serving
+/// what it had read before.
+#[cfg(unix)]
+pub async fn notify_files_changed(
+    workspace_root: &Path,
+    changes: Vec<crate::daemon::protocol::FileChange>,
+    timeout: Duration,
+    debug_log: Option<&Arc<DebugLog>>,
+) -> Result<()> {
+    let mut client = connect_daemon(timeout, debug_log).await?;
+    let result = client.execute_files_changed(workspace_root.to_path_buf(), changes).await?;
+    tracing::debug!(
+        "Updated {} and closed {} changed document(s) in ty",
+        result.updated,
+        result.closed
+    );
+    Ok(())
+}
+
 /// Check whether a file URI corresponds to a Python test file.
 ///
 /// Matches common Python test conventions:
@@ -49,8 +121,8 @@ async fn connect_daemon(
 /// - Filename: `conftest.py`
 /// - Any file under a `tests/` directory segment
 fn is_test_file(uri: &str) -> bool {
-    let path = uri.strip_prefix("file://").unwrap_or(uri);
-    let p = std::path::Path::new(path);
+    let path = uri_to_path(uri);
+    let p = std::path::Path::new(&path);
     let is_py = p.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("py"));
     let file_stem = p.file_stem().and_then(|s| s.to_str()).unwrap_or("");
     let file_name = p.file_name().and_then(|n| n.to_str()).unwrap_or("");
@@ -89,6 +161,67 @@ fn dedup_locations(locations: &mut Vec<Location>) {
     locations.retain(|loc| seen.insert((loc.uri.clone(), loc.range.start.line)));
 }
 
+/// Order locations as `refs --sort` asks. Sorts are stable, so locations that
+/// compare equal keep the order ty reported them in.
+fn sort_locations(locations: &mut [Location], sort: ReferenceSort) {
+    let position = |loc: &Location| (loc.range.start.line, loc.range.start.character);
+    match sort {
+        ReferenceSort::Path => {
+            locations.sort_by(|a, b| a.uri.cmp(&b.uri).then_with(|| position(a).cmp(&position(b))));
+        }
+        ReferenceSort::Line => {
+            let mut first_seen: HashMap<String, usize> = HashMap::new();
+            for loc in locations.iter() {
+                let next = first_seen.len();
+                first_seen.entry(loc.uri.clone()).or_insert(next);
+            }
+            locations.sort_by_key(|loc| (first_seen[&loc.uri], position(loc)));
+        }
+        ReferenceSort::Count => {
+            let mut per_file: HashMap<String, usize> = HashMap::new();
+            for loc in locations.iter() {
+                *per_file.entry(loc.uri.clone()).or_default() += 1;
+            }
+            locations.sort_by(|a, b| {
+                per_file[&b.uri]
+                    .cmp(&per_file[&a.uri])
+                    .then_with(|| a.uri.cmp(&b.uri))
+                    .then_with(|| position(a).cmp(&position(b)))
+            });
+        }
+    }
+}
+
+/// Drop the locations an earlier entry already has, for `refs --unique`.
+///
+/// Locations are the same when their whole range is, so two uses on one
+/// line both stay. An entry left with exactly an earlier entry's locations,
+/// like a second alias of the same class, is merged into it and labelled
+/// with both queries.
+fn unique_references(entries: Vec<BatchReferencesEntry>) -> Vec<BatchReferencesEntry> {
+    let key = |loc: &Location| {
+        let (start, end) = (&loc.range.start, &loc.range.end);
+        (loc.uri.clone(), start.line, start.character, end.line, end.character)
+    };
+    let mut seen = HashSet::new();
+    let mut unique: Vec<BatchReferencesEntry> = Vec::new();
+    for mut entry in entries {
+        let keys: HashSet<_> = entry.locations.iter().map(key).collect();
+        let same = unique.iter_mut().find(|earlier| {
+            !keys.is_empty()
+                && earlier.locations.len() == keys.len()
+                && earlier.locations.iter().all(|loc| keys.contains(&key(loc)))
+        });
+        if let Some(earlier) = same {
+            earlier.label = format!("{}, {}", earlier.label, entry.label);
+            continue;
+        }
+        entry.locations.retain(|loc| seen.insert(key(loc)));
+        unique.push(entry);
+    }
+    unique
+}
+
 /// Count unique files in a slice of locations.
 fn count_unique_files(locations: &[Location]) -> usize {
     let files: HashSet<&str> = locations.iter().map(|loc| loc.uri.as_str()).collect();
@@ -101,11 +234,40 @@ fn count_unique_files(locations: &[Location]) -> usize {
 /// and walks the symbol tree to find the tightest enclosing symbol for each reference.
 /// Falls back to "module scope" when no enclosing symbol is found or when the
 /// documentSymbol call fails.
-#[cfg(unix)]
+/// Where document symbols come from: the daemon, or an in-process ty server
+/// on platforms without one.
+enum SymbolSource<'a> {
+    #[cfg(unix)]
+    Daemon(&'a mut DaemonClient),
+    #[cfg_attr(unix, allow(dead_code))]
+    Direct(&'a TyLspClient),
+}
+
+impl SymbolSource<'_> {
+    async fn document_symbols(
+        &mut self,
+        workspace_root: &Path,
+        file_path: &str,
+    ) -> Result<Vec<DocumentSymbol>> {
+        match self {
+            #[cfg(unix)]
+            Self::Daemon(client) => Ok(client
+                .execute_document_symbols(workspace_root.to_path_buf(), file_path.to_string())
+                .await?
+                .symbols),
+            Self::Direct(client) => {
+                let _ = workspace_root;
+                client.open_document(file_path).await?;
+                client.document_symbols(file_path).await
+            }
+        }
+    }
+}
+
 async fn enrich_references(
     locations: &[Location],
     workspace_root: &Path,
-    client: &mut DaemonClient,
+    source: &mut SymbolSource<'_>,
 ) -> Vec<EnrichedReference> {
     // Collect unique file URIs to minimize daemon calls
     let unique_uris: Vec<String> =
@@ -126,13 +288,10 @@ async fn enrich_references(
     // Fetch document symbols for each unique file, cache results
     let mut symbol_cache: HashMap<String, Vec<DocumentSymbol>> = HashMap::new();
     for uri in &unique_uris {
-        let file_path = uri.strip_prefix("file://").unwrap_or(uri);
-        match client
-            .execute_document_symbols(workspace_root.to_path_buf(), file_path.to_string())
-            .await
-        {
-            Ok(result) => {
-                symbol_cache.insert(uri.clone(), result.symbols);
+        let file_path = uri_to_path(uri);
+        match source.document_symbols(workspace_root, &file_path).await {
+            Ok(symbols) => {
+                symbol_cache.insert(uri.clone(), symbols);
             }
             Err(e) => {
                 tracing::debug!("enrich_references: documentSymbol failed for {uri}: {e}");
@@ -151,7 +310,7 @@ async fn enrich_references(
             } else {
                 "module scope".to_string()
             };
-            EnrichedReference { location: loc.clone(), context }
+            EnrichedReference { location: loc.clone(), context, blame: None }
         })
         .collect()
 }
@@ -160,34 +319,36 @@ async fn enrich_references(
 ///
 /// Workspace-symbol responses return the range of the full declaration
 /// (e.g. the `class` keyword or a decorator), but hover/references need the
-/// cursor on the *name* itself. This helper reads the source and locates the
-/// name — first on the reported line, then on a few subsequent lines to handle
-/// decorators (`@dataclass`, `@property`, etc.) that shift the symbol start
-/// before the actual `class`/`def` keyword.
-async fn find_name_column(file_path: &str, line_0: u32, name: &str) -> Option<(u32, u32)> {
-    let content = match tokio::fs::read_to_string(file_path).await {
-        Ok(c) => c,
-        Err(e) => {
-            tracing::debug!("find_name_column: cannot read {file_path}: {e}");
-            return None;
-        }
+/// cursor on the *name* itself. This helper looks at the source in `sources`
+/// and locates the name — first on the reported line, then on a few
+/// subsequent lines to handle decorators (`@dataclass`, `@property`, etc.)
+/// that shift the symbol start before the actual `class`/`def` keyword.
+fn find_name_column(
+    sources: &SourceCache,
+    file_path: &str,
+    line_0: u32,
+    name: &str,
+) -> Option<(u32, u32)> {
+    let Some(file) = sources.get(file_path) else {
+        tracing::debug!("find_name_column: cannot read {file_path}");
+        return None;
     };
-    let lines: Vec<&str> = content.lines().collect();
     let start = line_0 as usize;
-    if start >= lines.len() {
+    if start >= file.line_count() {
         tracing::debug!(
             "find_name_column: line {line_0} out of range in {file_path} ({} lines)",
-            lines.len()
+            file.line_count()
         );
         return None;
     }
 
     // Search the reported line first, then up to 10 subsequent lines
     // to skip past decorator stacks like @dataclass, @property, etc.
-    for (idx, src_line) in lines.iter().enumerate().skip(start).take(11) {
-        if let Some(col) = src_line.find(name) {
+    for (idx, src_line) in file.lines_from(start).take(11) {
+        if let Some(byte) = src_line.find(name) {
             let line = u32::try_from(idx).ok()?;
-            let col = u32::try_from(col).ok()?;
+            let col =
+                convert_column(src_line, u32::try_from(byte).ok()?, ColumnUnit::Utf8, LSP_UNIT);
             tracing::debug!(
                 "find_name_column: found '{name}' at line {line} col {col} in {file_path}"
             );
@@ -220,15 +381,18 @@ fn parse_dotted_symbol(input: &str) -> Option<(&str, &str)> {
 /// expected container using the document symbol tree.
 /// Returns `(search_name, result)` where `search_name` is the symbol part
 /// actually searched for (the part after the last dot, or the full name).
+/// A non-empty `kinds` keeps only symbols of those kinds.
 #[cfg(unix)]
 async fn workspace_symbols_dotted(
     client: &mut DaemonClient,
     workspace: PathBuf,
     symbol: &str,
+    kinds: &[SymbolKind],
 ) -> Result<(String, crate::daemon::protocol::WorkspaceSymbolsResult)> {
     if let Some((container, member)) = parse_dotted_symbol(symbol) {
-        let result =
-            client.execute_workspace_symbols_exact(workspace.clone(), member.to_string()).await?;
+        let result = client
+            .execute_workspace_symbols_exact(workspace.clone(), member.to_string(), kinds.to_vec())
+            .await?;
 
         if result.symbols.is_empty() {
             return Ok((member.to_string(), result));
@@ -241,12 +405,7 @@ async fn workspace_symbols_dotted(
         let mut filtered = Vec::new();
 
         for sym_info in result.symbols {
-            let file_path = sym_info
-                .location
-                .uri
-                .strip_prefix("file://")
-                .unwrap_or(&sym_info.location.uri)
-                .to_string();
+            let file_path = uri_to_path(&sym_info.location.uri);
 
             let doc_symbols = if let Some(cached) = doc_sym_cache.get(&file_path) {
                 cached
@@ -274,16 +433,60 @@ async fn workspace_symbols_dotted(
 
         Ok((
             member.to_string(),
-            crate::daemon::protocol::WorkspaceSymbolsResult { symbols: filtered },
+            crate::daemon::protocol::WorkspaceSymbolsResult { symbols: filtered, remaining: 0 },
         ))
     } else {
-        let result = client.execute_workspace_symbols_exact(workspace, symbol.to_string()).await?;
+        let result = client
+            .execute_workspace_symbols_exact(workspace, symbol.to_string(), kinds.to_vec())
+            .await?;
         Ok((symbol.to_string(), result))
     }
 }
 
+/// Convert a user-supplied `file:line:col` position to a 0-based LSP position.
+///
+/// Lines and columns are 1-based unless `--zero-based` is set, and columns
+/// count characters; the column is converted to LSP units using the file's
+/// text. If the file can't be read the column is passed through unchanged.
+async fn user_position_to_lsp(
+    workspace_root: &Path,
+    file: &str,
+    line: u32,
+    column: u32,
+    zero_based: bool,
+) -> (u32, u32) {
+    let path = workspace_root.join(file);
+    match tokio::fs::read_to_string(&path).await {
+        Ok(content) => text_position_to_lsp(&content, line, column, zero_based),
+        Err(e) => {
+            tracing::debug!("cannot read {} for column conversion: {e}", path.display());
+            (input_to_lsp(line, zero_based), input_to_lsp(column, zero_based))
+        }
+    }
+}
+
+/// [`user_position_to_lsp`] for a position in `content` rather than a file on disk.
+fn text_position_to_lsp(content: &str, line: u32, column: u32, zero_based: bool) -> (u32, u32) {
+    let line = input_to_lsp(line, zero_based);
+    let column = input_to_lsp(column, zero_based);
+    content.lines().nth(line as usize).map_or((line, column), |src_line| {
+        (line, convert_column(src_line, column, DISPLAY_UNIT, LSP_UNIT))
+    })
+}
+
+/// Convert a user-supplied line or column to a 0-based LSP coordinate.
+///
+/// Input is 1-based by default; with `--zero-based` it is passed through as-is.
+fn input_to_lsp(value: u32, zero_based: bool) -> u32 {
+    if zero_based {
+        value
+    } else {
+        value.saturating_sub(1)
+    }
+}
+
 /// Try to parse a string as `file:line:col`. Returns `None` if it doesn't match.
-fn parse_file_position(input: &str) -> Option<(String, u32, u32)> {
+pub fn parse_file_position(input: &str) -> Option<(String, u32, u32)> {
     let last_colon = input.rfind(':')?;
     let col: u32 = input[last_colon + 1..].parse().ok()?;
     let rest = &input[..last_colon];
@@ -296,6 +499,16 @@ fn parse_file_position(input: &str) -> Option<(String, u32, u32)> {
     Some((file.to_string(), line, col))
 }
 
+/// Build a group label for one of several definitions sharing a name.
+///
+/// Produces `name (path:line)` with the path relative to the workspace root
+/// (when possible) and a 1-based line, e.g. `Config (app/settings.py:12)`.
+fn definition_label(symbol: &str, file_path: &str, line_0: u32, workspace_root: &Path) -> String {
+    let path = Path::new(file_path);
+    let display = path.strip_prefix(workspace_root).unwrap_or(path);
+    format!("{symbol} ({}:{})", display.display(), line_0 + 1)
+}
+
 /// A resolved reference query ready to send to the daemon.
 #[cfg(unix)]
 struct ResolvedQuery {
@@ -309,75 +522,103 @@ struct ResolvedQuery {
     column: u32,
 }
 
+/// One place a symbol name resolved to, before ty is asked about it.
+#[cfg(unix)]
+struct SymbolMatch {
+    /// File containing the name
+    file: String,
+    /// 0-based line of the name
+    line: u32,
+    /// 0-based column of the name
+    column: u32,
+    /// The workspace-symbol entry this came from; `None` for a `--file` search.
+    symbol: Option<SymbolInformation>,
+}
+
+/// Resolve a symbol name to the positions of the name itself.
+///
+/// With `file`, the name's occurrences in that file, a definition site
+/// first. Otherwise the workspace symbols matching the name (dotted names
+/// narrow to a container), each at its name rather than the start of its
+/// range. Shared by `def`, `refs`, `show` and the position-taking commands.
+/// A non-empty `kinds` narrows the workspace symbols; occurrences in `file`
+/// are not filtered.
+#[cfg(unix)]
+async fn resolve_symbol(
+    client: &mut DaemonClient,
+    workspace_root: &Path,
+    file: Option<&Path>,
+    symbol: &str,
+    kinds: &[SymbolKind],
+) -> Result<Vec<SymbolMatch>> {
+    if let Some(file) = file {
+        let file_str = file.to_string_lossy();
+        let finder = SymbolFinder::new(&file_str).await?;
+        let mut positions = finder.find_symbol_positions(symbol);
+        if let Some(preferred) = finder.preferred_position(symbol) {
+            positions.retain(|&position| position != preferred);
+            positions.insert(0, preferred);
+        }
+        return Ok(positions
+            .into_iter()
+            .map(|(line, column)| SymbolMatch {
+                file: file_str.to_string(),
+                line,
+                column,
+                symbol: None,
+            })
+            .collect());
+    }
+
+    let (_search_name, result) =
+        workspace_symbols_dotted(client, workspace_root.to_path_buf(), symbol, kinds).await?;
+    let sources =
+        SourceCache::from_uris(result.symbols.iter().map(|s| s.location.uri.as_str())).await;
+    let mut matches = Vec::with_capacity(result.symbols.len());
+    for sym_info in result.symbols {
+        let file = uri_to_path(&sym_info.location.uri);
+        let start = &sym_info.location.range.start;
+        // Workspace-symbol range.start may point at a decorator or keyword;
+        // hover/references need the symbol *name*.
+        let (line, column) = find_name_column(&sources, &file, start.line, &sym_info.name)
+            .unwrap_or((start.line, start.character));
+        matches.push(SymbolMatch { file, line, column, symbol: Some(sym_info) });
+    }
+    Ok(matches)
+}
+
 /// Resolve symbol names to LSP positions via file search or workspace symbols.
 #[cfg(unix)]
 async fn resolve_symbols_to_queries(
+    client: &mut DaemonClient,
     symbols: &[String],
     file: Option<&Path>,
     workspace_root: &Path,
-    timeout: Duration,
+    kinds: &[SymbolKind],
 ) -> Result<Vec<ResolvedQuery>> {
     let mut resolved = Vec::new();
-
-    if let Some(file) = file {
-        let file_str = file.to_string_lossy();
-        let finder = SymbolFinder::new(&file_str).await?;
-
-        for symbol in symbols {
-            let positions = finder.find_symbol_positions(symbol);
-            if positions.is_empty() {
-                resolved.push(ResolvedQuery {
-                    label: symbol.clone(),
-                    file: String::new(),
-                    line: 0,
-                    column: 0,
-                });
-            } else {
-                for &(ln, col) in &positions {
-                    resolved.push(ResolvedQuery {
-                        label: symbol.clone(),
-                        file: file_str.to_string(),
-                        line: ln,
-                        column: col,
-                    });
-                }
-            }
+    for symbol in symbols {
+        let matches = resolve_symbol(client, workspace_root, file, symbol, kinds).await?;
+        if matches.is_empty() {
+            resolved.push(ResolvedQuery {
+                label: symbol.clone(),
+                file: String::new(),
+                line: 0,
+                column: 0,
+            });
+            continue;
         }
-    } else {
-        let mut client = DaemonClient::connect_with_timeout(timeout).await?;
-        for symbol in symbols {
-            let (_search_name, result) =
-                workspace_symbols_dotted(&mut client, workspace_root.to_path_buf(), symbol).await?;
-
-            if result.symbols.is_empty() {
-                resolved.push(ResolvedQuery {
-                    label: symbol.clone(),
-                    file: String::new(),
-                    line: 0,
-                    column: 0,
-                });
+        // Several distinct definitions share this name: give each its own
+        // label so references are grouped per definition, not lumped together.
+        // Occurrences found by a --file search all belong to one label.
+        let ambiguous = matches.iter().filter(|m| m.symbol.is_some()).count() > 1;
+        for SymbolMatch { file, line, column, .. } in matches {
+            let label = if ambiguous {
+                definition_label(symbol, &file, line, workspace_root)
             } else {
-                for sym_info in &result.symbols {
-                    let file_path = sym_info
-                        .location
-                        .uri
-                        .strip_prefix("file://")
-                        .unwrap_or(&sym_info.location.uri)
-                        .to_string();
-                    let ws_line = sym_info.location.range.start.line;
-                    // Workspace-symbol range.start may point at a decorator
-                    // or keyword; hover/references need the symbol *name*.
-                    let (line, column) = find_name_column(&file_path, ws_line, &sym_info.name)
-                        .await
-                        .unwrap_or((ws_line, sym_info.location.range.start.character));
-                    resolved.push(ResolvedQuery {
-                        label: symbol.clone(),
-                        file: file_path,
-                        line,
-                        column,
-                    });
-                }
-            }
+                symbol.clone()
+            };
+            resolved.push(ResolvedQuery { label, file, line, column });
         }
     }
 
@@ -387,61 +628,75 @@ async fn resolve_symbols_to_queries(
 /// Send resolved queries to the daemon in a single batch RPC and merge results by label.
 #[cfg(unix)]
 async fn execute_references_batch(
+    client: &mut DaemonClient,
     resolved: Vec<ResolvedQuery>,
     workspace_root: &Path,
     include_declaration: bool,
-    timeout: Duration,
-) -> Result<Vec<(String, Vec<Location>)>> {
-    // Split into queries the daemon can handle (have a file) and empty ones
-    let mut empty_labels: Vec<String> = Vec::new();
-    let mut batch_queries: Vec<BatchReferencesQuery> = Vec::new();
-
-    for q in resolved {
-        if q.file.is_empty() {
-            empty_labels.push(q.label);
-        } else {
-            batch_queries.push(BatchReferencesQuery {
-                label: q.label,
-                file: PathBuf::from(q.file),
-                line: q.line,
-                column: q.column,
-            });
-        }
-    }
-
-    let mut merged: Vec<(String, Vec<Location>)> = Vec::new();
-
-    // Add empty results for unresolved symbols
-    for label in empty_labels {
-        if !merged.iter().any(|(s, _)| s == &label) {
-            merged.push((label, Vec::new()));
-        }
-    }
+    workspace_only: bool,
+    page: ResultPage,
+) -> Result<Vec<BatchReferencesEntry>> {
+    let (mut merged, batch_queries) = split_resolved(resolved);
 
     // Send the batch to the daemon in one call
     if !batch_queries.is_empty() {
-        let mut client = DaemonClient::connect_with_timeout(timeout).await?;
         let result = client
-            .execute_batch_references(
+            .execute_batch_references_page(
                 workspace_root.to_path_buf(),
                 batch_queries,
                 include_declaration,
+                workspace_only,
+                page,
             )
             .await?;
+        merge_by_label(&mut merged, result.entries);
+    }
 
-        for entry in result.entries {
-            if let Some(existing) = merged.iter_mut().find(|(s, _)| s == &entry.label) {
-                existing.1.extend(entry.locations);
-            } else {
-                merged.push((entry.label, entry.locations));
-            }
+    for entry in &mut merged {
+        dedup_locations(&mut entry.locations);
+    }
+    Ok(merged)
+}
+
+/// Empty entries for the queries that didn't resolve to a position, one per
+/// label, and batch queries for the rest.
+#[cfg(unix)]
+fn split_resolved(
+    resolved: Vec<ResolvedQuery>,
+) -> (Vec<BatchReferencesEntry>, Vec<BatchReferencesQuery>) {
+    let mut empty: Vec<BatchReferencesEntry> = Vec::new();
+    let mut batch_queries: Vec<BatchReferencesQuery> = Vec::new();
+    for q in resolved {
+        if !q.file.is_empty() {
+            batch_queries.push(BatchReferencesQuery {
+                label: q.label,
+                file: PathBuf::from(q.file),
+                line: q.line,
+                column: q.column,
+            });
+        } else if !empty.iter().any(|e| e.label == q.label) {
+            empty.push(BatchReferencesEntry {
+                label: q.label,
+                locations: Vec::new(),
+                files: Vec::new(),
+                remaining: 0,
+            });
         }
     }
+    (empty, batch_queries)
+}
 
-    for (_, locations) in &mut merged {
-        dedup_locations(locations);
+/// Add `entries` to `merged`, extending the entry with the same label when
+/// there is one.
+#[cfg(unix)]
+fn merge_by_label(merged: &mut Vec<BatchReferencesEntry>, entries: Vec<BatchReferencesEntry>) {
+    for entry in entries {
+        if let Some(existing) = merged.iter_mut().find(|e| e.label == entry.label) {
+            existing.locations.extend(entry.locations);
+            existing.remaining += entry.remaining;
+        } else {
+            merged.push(entry);
+        }
     }
-    Ok(merged)
 }
 
 /// Collect query strings from CLI args and optionally stdin.
@@ -460,38 +715,43 @@ fn collect_queries(queries: &[String], read_stdin: bool) -> Result<Vec<String>>
 }
 
 /// Classify queries as positions or symbols and resolve to LSP coordinates.
+///
+/// Names resolve to symbols of `kinds` only, when it is non-empty.
 #[cfg(unix)]
 async fn classify_and_resolve(
+    client: &mut DaemonClient,
     all_queries: &[String],
     file: Option<&Path>,
     workspace_root: &Path,
-    timeout: Duration,
+    zero_based: bool,
+    kinds: &[SymbolKind],
 ) -> Result<Vec<ResolvedQuery>> {
     let mut resolved: Vec<ResolvedQuery> = Vec::new();
     let mut symbols: Vec<String> = Vec::new();
 
     for q in all_queries {
         if let Some((f, l, c)) = parse_file_position(q) {
-            resolved.push(ResolvedQuery {
-                label: q.clone(),
-                file: f,
-                line: l.saturating_sub(1),
-                column: c.saturating_sub(1),
-            });
+            let (line, column) = match client.buffer_text(workspace_root, &f) {
+                Some(text) => text_position_to_lsp(text, l, c, zero_based),
+                None => user_position_to_lsp(workspace_root, &f, l, c, zero_based).await,
+            };
+            resolved.push(ResolvedQuery { label: q.clone(), file: f, line, column });
         } else {
             symbols.push(q.clone());
         }
     }
 
     if !symbols.is_empty() {
-        resolved.extend(resolve_symbols_to_queries(&symbols, file, workspace_root, timeout).await?);
+        resolved.extend(
+            resolve_symbols_to_queries(client, &symbols, file, workspace_root, kinds).await?,
+        );
     }
 
     Ok(resolved)
 }
 
 #[cfg(unix)]
-#[allow(clippy::too_many_arguments)]
+#[allow(clippy::too_many_arguments, clippy::fn_params_excessive_bools)]
 pub async fn handle_references_command(
     workspace_root: &Path,
     file: Option<&Path>,
@@ -500,84 +760,114 @@ pub async fn handle_references_command(
     read_stdin: bool,
     include_declaration: bool,
     references_limit: usize,
+    page: ResultPage,
+    count: Option<CountBy>,
+    unique: bool,
+    git: bool,
+    sort: Option<ReferenceSort>,
     formatter: &OutputFormatter,
     timeout: Duration,
     show_tests: bool,
+    zero_based: bool,
+    include_deps: bool,
+    kinds: &[SymbolKindFilter],
+    path_filter: &PathFilter,
     debug_log: Option<Arc<DebugLog>>,
 ) -> Result<()> {
-    ensure_daemon_running().await?;
+    let mut client = connect_daemon(timeout, debug_log.as_ref()).await?;
+    client.set_path_filter(path_filter.clone());
+    let options = ReferenceOptions {
+        include_declaration,
+        include_deps,
+        zero_based,
+        references_limit,
+        page,
+        show_tests,
+        kinds: kinds.iter().map(|k| k.symbol_kind()).collect(),
+        unique,
+        git,
+        sort,
+    };
+
+    if let Some(by) = count {
+        let mut all_queries = collect_queries(queries, read_stdin)?;
+        if let (Some(file), Some((line, col))) = (file, position) {
+            all_queries.insert(0, format!("{}:{line}:{col}", file.display()));
+        }
+        if all_queries.is_empty() {
+            return Err(missing_refs_queries());
+        }
+        let counts =
+            collect_reference_counts(&mut client, workspace_root, file, &all_queries, &options)
+                .await?;
+        if let Some(ref log) = debug_log {
+            let total: usize = counts.iter().map(ReferenceCount::total).sum();
+            log.log_result_summary(&format!("{total} reference(s) counted"));
+        }
+        println!("{}", formatter.format_reference_counts(&counts, by == CountBy::File));
+        return found_any(counts.iter().any(|c| c.total() > 0 || c.hidden_tests > 0));
+    }
 
     // Explicit --file -l -c: single position mode
     if let (Some(file), Some((line, col))) = (file, position) {
-        let mut client = connect_daemon(timeout, debug_log.as_ref()).await?;
-        let result = client
-            .execute_references(
-                workspace_root.to_path_buf(),
-                file.to_string_lossy().to_string(),
-                line.saturating_sub(1),
-                col.saturating_sub(1),
-                include_declaration,
-            )
-            .await?;
+        let file_str = file.to_string_lossy().to_string();
+        let (lsp_line, lsp_col) =
+            user_position_to_lsp(workspace_root, &file_str, line, col, zero_based).await;
+        let label = format!("{}:{line}:{col}", file.display());
+        let query = ResolvedQuery { label, file: file_str, line: lsp_line, column: lsp_col };
+        let mut entries = execute_references_batch(
+            &mut client,
+            vec![query],
+            workspace_root,
+            include_declaration,
+            !include_deps,
+            page,
+        )
+        .await?;
+        let Some(entry) = entries.pop() else { return found_any(false) };
 
         if let Some(ref log) = debug_log {
-            log.log_result_summary(&format!("{} reference(s) found", result.locations.len()));
+            log.log_result_summary(&format!("{} reference(s) found", entry.locations.len()));
         }
 
-        let label = format!("{}:{line}:{col}", file.display());
-        let enriched = enrich_and_limit_references(
-            &label,
-            result.locations,
+        let mut enriched = enrich_and_limit_references(
+            &entry.label,
+            entry.locations,
             references_limit,
+            entry.remaining,
             workspace_root,
             &mut client,
             show_tests,
+            sort,
         )
         .await?;
-        let cache = SourceCache::from_uris(
-            enriched.displayed.iter().map(|e| e.location.uri.as_str()).chain(
-                enriched
-                    .test_references
-                    .iter()
-                    .flat_map(|t| t.displayed.iter().map(|e| e.location.uri.as_str())),
-            ),
-        )
-        .await;
-        println!("{}", formatter.format_enriched_references_results(&[enriched], &cache));
-        return Ok(());
+        if git {
+            blame_references(std::slice::from_mut(&mut enriched), &mut BlameCache::default()).await;
+        }
+        let cache = references_sources(std::slice::from_ref(&enriched)).await;
+        return print_references(formatter, &[enriched], &cache);
     }
 
     let all_queries = collect_queries(queries, read_stdin)?;
     if all_queries.is_empty() {
-        anyhow::bail!(
-            "Provide symbol names, file:line:col positions, or --file with --line/--column.\n\
-             Position mode:  tyf refs -f file.py -l 10 -c 5\n\
-             Symbol mode:    tyf refs my_func my_class\n\
-             Mixed/pipe:     tyf refs file.py:10:5 my_func\n\
-             Stdin:          ... | tyf refs --stdin"
-        );
+        return Err(missing_refs_queries());
     }
 
-    let resolved = classify_and_resolve(&all_queries, file, workspace_root, timeout).await?;
-    let merged =
-        execute_references_batch(resolved, workspace_root, include_declaration, timeout).await?;
-
-    // Enrich and limit each result group — reuse a single daemon connection
-    let mut enriched_results = Vec::new();
-    let mut client = DaemonClient::connect_with_timeout(timeout).await?;
-    for (label, locations) in merged {
-        let enriched = enrich_and_limit_references(
-            &label,
-            locations,
-            references_limit,
-            workspace_root,
-            &mut client,
-            show_tests,
-        )
-        .await?;
-        enriched_results.push(enriched);
+    if !options.unique && formatter.prints_references_incrementally() {
+        let streamed =
+            stream_references(&mut client, workspace_root, file, &all_queries, &options, formatter)
+                .await?;
+        if let Some(ref log) = debug_log {
+            log.log_result_summary(&format!("{} reference(s) found", streamed.total));
+            let cmd = format!("refs {}", all_queries.join(" "));
+            log.log_reproduction_commands(workspace_root, &all_queries, &cmd);
+        }
+        return found_any(streamed.found);
     }
 
+    let enriched_results =
+        collect_references(&mut client, workspace_root, file, &all_queries, options).await?;
+
     if let Some(ref log) = debug_log {
         let total: usize = enriched_results.iter().map(|r| r.total_count).sum();
         log.log_result_summary(&format!("{total} reference(s) found"));
@@ -585,38 +875,421 @@ pub async fn handle_references_command(
         log.log_reproduction_commands(workspace_root, &all_queries, &cmd);
     }
 
-    let cache = SourceCache::from_uris(enriched_results.iter().flat_map(|r| {
-        let main = r.displayed.iter().map(|e| e.location.uri.as_str());
-        let test = r
-            .test_references
-            .iter()
-            .flat_map(|t| t.displayed.iter().map(|e| e.location.uri.as_str()));
-        main.chain(test)
-    }))
-    .await;
-    println!("{}", formatter.format_enriched_references_results(&enriched_results, &cache));
+    let cache = references_sources(&enriched_results).await;
+    print_references(formatter, &enriched_results, &cache)
+}
 
-    Ok(())
+/// What [`stream_references`] printed.
+#[cfg(unix)]
+#[derive(Default)]
+struct StreamedReferences {
+    /// References found, outside test files
+    total: usize,
+    /// Whether any query found a reference, in a test file or not
+    found: bool,
 }
 
-/// Apply limit and enrich displayed references with enclosing symbol context.
+/// [`collect_references`] and [`print_references`], except that the
+/// references of each label are printed as soon as the daemon streams them,
+/// rather than once every query is done.
 ///
-/// Always partitions into test vs non-test. When `show_tests` is true, test
-/// references are enriched and returned in a separate section. When false,
-/// only the count is preserved (for the "N hidden" hint).
+/// They are enriched over a second connection, while the first one is
+/// still receiving the rest.
 #[cfg(unix)]
-async fn enrich_and_limit_references(
-    label: &str,
-    locations: Vec<Location>,
-    references_limit: usize,
-    workspace_root: &Path,
+async fn stream_references(
     client: &mut DaemonClient,
-    show_tests: bool,
-) -> Result<EnrichedReferencesResult> {
-    use crate::cli::output::TestReferencesSection;
-
-    let (non_test_locs, test_locs) = partition_test_locations(locations);
-
+    workspace_root: &Path,
+    file: Option<&Path>,
+    queries: &[String],
+    options: &ReferenceOptions,
+    formatter: &OutputFormatter,
+) -> Result<StreamedReferences> {
+    let resolved = classify_and_resolve(
+        client,
+        queries,
+        file,
+        workspace_root,
+        options.zero_based,
+        &options.kinds,
+    )
+    .await?;
+    let labels: HashSet<&str> = resolved.iter().map(|q| q.label.as_str()).collect();
+    let mut printer = formatter.references_printer(labels.len() > 1);
+    let (unresolved, batch_queries) = split_resolved(resolved);
+
+    let mut enrich_client = client.connect_another().await?;
+    let mut blames = BlameCache::default();
+    let mut streamed = StreamedReferences::default();
+    let output = async {
+        print_reference_entries(
+            unresolved,
+            options,
+            workspace_root,
+            &mut enrich_client,
+            &mut printer,
+            &mut blames,
+            &mut streamed,
+        )
+        .await?;
+        if batch_queries.is_empty() {
+            return Ok(());
+        }
+        let (parts, mut received) = tokio::sync::mpsc::unbounded_channel();
+        let fetch = client.stream_batch_references_page(
+            workspace_root.to_path_buf(),
+            batch_queries,
+            options.include_declaration,
+            !options.include_deps,
+            options.page,
+            move |part| {
+                // Gone only if printing failed, which ends the command anyway.
+                let _ = parts.send(part.entries);
+            },
+        );
+        let print = async {
+            while let Some(entries) = received.recv().await {
+                print_reference_entries(
+                    entries,
+                    options,
+                    workspace_root,
+                    &mut enrich_client,
+                    &mut printer,
+                    &mut blames,
+                    &mut streamed,
+                )
+                .await?;
+            }
+            anyhow::Ok(())
+        };
+        let (rest, ()) = tokio::try_join!(fetch, print)?;
+        print_reference_entries(
+            rest.entries,
+            options,
+            workspace_root,
+            &mut enrich_client,
+            &mut printer,
+            &mut blames,
+            &mut streamed,
+        )
+        .await
+    };
+    let finished =
+        output.await.and_then(|()| printer.finish().context("Failed to write references"));
+    match finished {
+        Err(e) if is_broken_pipe(&e) => {}
+        result => result?,
+    }
+    Ok(streamed)
+}
+
+/// Merge `entries` by label, then enrich (and with `--git` blame, keeping
+/// files blamed in `blames`) and print each, adding them up in `streamed`.
+#[cfg(unix)]
+async fn print_reference_entries(
+    entries: Vec<BatchReferencesEntry>,
+    options: &ReferenceOptions,
+    workspace_root: &Path,
+    client: &mut DaemonClient,
+    printer: &mut ReferencesPrinter<'_>,
+    blames: &mut BlameCache,
+    streamed: &mut StreamedReferences,
+) -> Result<()> {
+    let mut merged = Vec::new();
+    merge_by_label(&mut merged, entries);
+    for mut entry in merged {
+        dedup_locations(&mut entry.locations);
+        let mut enriched = enrich_and_limit_references(
+            &entry.label,
+            entry.locations,
+            options.references_limit,
+            entry.remaining,
+            workspace_root,
+            client,
+            options.show_tests,
+            options.sort,
+        )
+        .await?;
+        if options.git {
+            blame_references(std::slice::from_mut(&mut enriched), blames).await;
+        }
+        let cache = references_sources(std::slice::from_ref(&enriched)).await;
+        printer.print(&enriched, &cache).context("Failed to write references")?;
+        streamed.total += enriched.total_count;
+        streamed.found |= enriched.total_count > 0 || enriched.test_references.is_some();
+    }
+    Ok(())
+}
+
+/// Whether `error` is stdout closing early (`tyf refs x | head`), which is
+/// not worth reporting.
+#[cfg(unix)]
+fn is_broken_pipe(error: &anyhow::Error) -> bool {
+    error
+        .downcast_ref::<std::io::Error>()
+        .is_some_and(|e| e.kind() == std::io::ErrorKind::BrokenPipe)
+}
+
+/// The sources of every reference `results` display, for their positions
+/// and context lines.
+#[cfg(unix)]
+async fn references_sources(results: &[EnrichedReferencesResult]) -> SourceCache {
+    SourceCache::from_uris(results.iter().flat_map(|r| {
+        let main = r.displayed.iter().map(|e| e.location.uri.as_str());
+        let test = r
+            .test_references
+            .iter()
+            .flat_map(|t| t.displayed.iter().map(|e| e.location.uri.as_str()));
+        main.chain(test)
+    }))
+    .await
+}
+
+/// The usage error for `tyf refs` without anything to look up.
+#[cfg(unix)]
+fn missing_refs_queries() -> anyhow::Error {
+    UsageError(
+        "Provide symbol names, file:line:col positions, or --file with --line/--column.\n\
+         Position mode:  tyf refs -f file.py -l 10 -c 5\n\
+         Symbol mode:    tyf refs my_func my_class\n\
+         Mixed/pipe:     tyf refs file.py:10:5 my_func\n\
+         Stdin:          ... | tyf refs --stdin"
+            .to_string(),
+    )
+    .into()
+}
+
+/// What `refs --count` counts references by.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CountBy {
+    /// One total per query (`--count`)
+    Symbol,
+    /// A total per query and per file (`--count-by-file`)
+    File,
+}
+
+/// How `collect_references` looks up and trims references.
+#[cfg(unix)]
+pub struct ReferenceOptions {
+    pub include_declaration: bool,
+    pub include_deps: bool,
+    pub zero_based: bool,
+    /// Most references to enrich and show per query (0 = all)
+    pub references_limit: usize,
+    /// Which of each query's references to load
+    pub page: ResultPage,
+    pub show_tests: bool,
+    /// Kinds of symbol a name may resolve to (any when empty)
+    pub kinds: Vec<SymbolKind>,
+    /// List each location once, under the first query that found it
+    pub unique: bool,
+    /// Note who last changed the line of each displayed reference
+    pub git: bool,
+    /// Order to list references in, before `references_limit` applies
+    pub sort: Option<ReferenceSort>,
+}
+
+/// References of each query (a name or `file:line:col`), enriched with the
+/// enclosing symbol and limited as `options` says.
+#[cfg(unix)]
+pub async fn collect_references(
+    client: &mut DaemonClient,
+    workspace_root: &Path,
+    file: Option<&Path>,
+    queries: &[String],
+    options: ReferenceOptions,
+) -> Result<Vec<EnrichedReferencesResult>> {
+    let resolved = classify_and_resolve(
+        client,
+        queries,
+        file,
+        workspace_root,
+        options.zero_based,
+        &options.kinds,
+    )
+    .await?;
+    let merged = execute_references_batch(
+        client,
+        resolved,
+        workspace_root,
+        options.include_declaration,
+        !options.include_deps,
+        options.page,
+    )
+    .await?;
+    let merged = if options.unique { unique_references(merged) } else { merged };
+
+    // Enrich and limit each result group
+    let mut enriched_results = Vec::new();
+    for entry in merged {
+        let enriched = enrich_and_limit_references(
+            &entry.label,
+            entry.locations,
+            options.references_limit,
+            entry.remaining,
+            workspace_root,
+            client,
+            options.show_tests,
+            options.sort,
+        )
+        .await?;
+        enriched_results.push(enriched);
+    }
+    if options.git {
+        blame_references(&mut enriched_results, &mut BlameCache::default()).await;
+    }
+    Ok(enriched_results)
+}
+
+/// Note on each displayed reference of `results` the last commit that
+/// changed its line, blaming files `blames` doesn't know yet.
+#[cfg(unix)]
+async fn blame_references(results: &mut [EnrichedReferencesResult], blames: &mut BlameCache) {
+    let paths: HashSet<String> = results
+        .iter()
+        .flat_map(|r| r.displayed.iter().chain(r.test_references.iter().flat_map(|t| &t.displayed)))
+        .map(|e| uri_to_path(&e.location.uri))
+        .collect();
+    blames.load(paths.iter().map(String::as_str)).await;
+    for result in results {
+        let tests = result.test_references.iter_mut().flat_map(|t| t.displayed.iter_mut());
+        for enriched in result.displayed.iter_mut().chain(tests) {
+            let path = uri_to_path(&enriched.location.uri);
+            enriched.blame = blames.line(&path, enriched.location.range.start.line).cloned();
+        }
+    }
+}
+
+/// Reference counts of each query (a name or `file:line:col`), by file.
+///
+/// The daemon counts, so the locations never cross the socket. Files outside
+/// the workspace and test files are then left out as `options` says, the
+/// latter counted in `hidden_tests`.
+#[cfg(unix)]
+pub async fn collect_reference_counts(
+    client: &mut DaemonClient,
+    workspace_root: &Path,
+    file: Option<&Path>,
+    queries: &[String],
+    options: &ReferenceOptions,
+) -> Result<Vec<ReferenceCount>> {
+    let resolved = classify_and_resolve(
+        client,
+        queries,
+        file,
+        workspace_root,
+        options.zero_based,
+        &options.kinds,
+    )
+    .await?;
+    let mut counts: Vec<ReferenceCount> = Vec::new();
+    let mut batch_queries = Vec::new();
+    for query in resolved {
+        if !counts.iter().any(|c| c.label == query.label) {
+            counts.push(ReferenceCount {
+                label: query.label.clone(),
+                files: Vec::new(),
+                hidden_tests: 0,
+            });
+        }
+        if !query.file.is_empty() {
+            batch_queries.push(BatchReferencesQuery {
+                label: query.label,
+                file: PathBuf::from(query.file),
+                line: query.line,
+                column: query.column,
+            });
+        }
+    }
+    if batch_queries.is_empty() {
+        return Ok(counts);
+    }
+
+    let result = client
+        .execute_batch_reference_counts(
+            workspace_root.to_path_buf(),
+            batch_queries,
+            options.include_declaration,
+            DEFAULT_REFERENCES_CONCURRENCY,
+        )
+        .await?;
+    for entry in result.entries {
+        let Some(count) = counts.iter_mut().find(|c| c.label == entry.label) else { continue };
+        for FileReferenceCount { uri, count: n } in entry.files {
+            if !options.include_deps && Origin::classify(&uri, workspace_root) != Origin::Workspace
+            {
+                continue;
+            }
+            if !options.show_tests && is_test_file(&uri) {
+                count.hidden_tests += n;
+            } else {
+                count.files.push((uri, n));
+            }
+        }
+    }
+    Ok(counts)
+}
+
+/// Stream references to stdout; a closed pipe (`tyf refs x | head`) is not an error.
+///
+/// Fails with [`NoResults`] when no query has a reference.
+#[cfg(unix)]
+fn print_references(
+    formatter: &OutputFormatter,
+    results: &[EnrichedReferencesResult],
+    cache: &SourceCache,
+) -> Result<()> {
+    match formatter.print_enriched_references_results(results, cache) {
+        Err(e) if e.kind() == std::io::ErrorKind::BrokenPipe => return Ok(()),
+        result => result.context("Failed to write references")?,
+    }
+    found_any(results.iter().any(|r| r.total_count > 0 || r.test_references.is_some()))
+}
+
+/// Print JSON lines streamed from a long run, flushing them right away.
+///
+/// Returns `false` once stdout has been closed (`tyf check-all --format jsonl
+/// | head`), after which the caller should stop.
+#[cfg(unix)]
+fn print_records(records: &str) -> Result<bool> {
+    use std::io::Write as _;
+    if records.is_empty() {
+        return Ok(true);
+    }
+    let mut stdout = std::io::stdout().lock();
+    match writeln!(stdout, "{records}").and_then(|()| stdout.flush()) {
+        Ok(()) => Ok(true),
+        Err(e) if e.kind() == std::io::ErrorKind::BrokenPipe => Ok(false),
+        Err(e) => Err(e).context("Failed to write results"),
+    }
+}
+
+/// Sort (with `--sort`), apply limit and enrich displayed references with
+/// enclosing symbol context.
+///
+/// `omitted_count` is how many references the daemon left out after the
+/// requested page. Always partitions into test vs non-test. When `show_tests` is true, test
+/// references are enriched and returned in a separate section. When false,
+/// only the count is preserved (for the "N hidden" hint).
+#[cfg(unix)]
+#[allow(clippy::too_many_arguments)]
+async fn enrich_and_limit_references(
+    label: &str,
+    locations: Vec<Location>,
+    references_limit: usize,
+    omitted_count: usize,
+    workspace_root: &Path,
+    client: &mut DaemonClient,
+    show_tests: bool,
+    sort: Option<ReferenceSort>,
+) -> Result<EnrichedReferencesResult> {
+    use crate::cli::output::TestReferencesSection;
+
+    let (mut non_test_locs, mut test_locs) = partition_test_locations(locations);
+    if let Some(sort) = sort {
+        sort_locations(&mut non_test_locs, sort);
+        sort_locations(&mut test_locs, sort);
+    }
+
     // Process non-test references
     let total_count = non_test_locs.len();
     let display_count =
@@ -627,7 +1300,7 @@ async fn enrich_and_limit_references(
     let displayed = if to_display.is_empty() {
         Vec::new()
     } else {
-        enrich_references(to_display, workspace_root, client).await
+        enrich_references(to_display, workspace_root, &mut SymbolSource::Daemon(client)).await
     };
 
     // Process test references
@@ -639,7 +1312,9 @@ async fn enrich_and_limit_references(
             if references_limit == 0 { test_total } else { references_limit.min(test_total) };
         let test_to_display = &test_locs[..test_display_count];
         let test_remaining = test_total - test_display_count;
-        let test_displayed = enrich_references(test_to_display, workspace_root, client).await;
+        let test_displayed =
+            enrich_references(test_to_display, workspace_root, &mut SymbolSource::Daemon(client))
+                .await;
         Some(TestReferencesSection {
             total_count: test_total,
             displayed: test_displayed,
@@ -660,11 +1335,12 @@ async fn enrich_and_limit_references(
         displayed,
         remaining_count,
         test_references,
+        omitted_count,
     })
 }
 
 #[cfg(not(unix))]
-#[allow(clippy::too_many_arguments)]
+#[allow(clippy::too_many_arguments, clippy::fn_params_excessive_bools)]
 pub async fn handle_references_command(
     _workspace_root: &Path,
     _file: Option<&Path>,
@@ -673,9 +1349,18 @@ pub async fn handle_references_command(
     _read_stdin: bool,
     _include_declaration: bool,
     _references_limit: usize,
+    _page: ResultPage,
+    _count: Option<CountBy>,
+    _unique: bool,
+    _git: bool,
+    _sort: Option<ReferenceSort>,
     _formatter: &OutputFormatter,
     _timeout: Duration,
     _show_tests: bool,
+    _zero_based: bool,
+    _include_deps: bool,
+    _kinds: &[SymbolKindFilter],
+    _path_filter: &PathFilter,
     _debug_log: Option<Arc<DebugLog>>,
 ) -> Result<()> {
     anyhow::bail!(
@@ -683,471 +1368,4064 @@ pub async fn handle_references_command(
     )
 }
 
-#[allow(clippy::too_many_lines)]
-pub async fn handle_find_command(
+/// Text-search the workspace for `pattern` and keep the matches that resolve to `target`.
+///
+/// Instead of asking for the definition at every text match, the matches are
+/// checked against ty's references to the target: a reference is exactly a
+/// place whose definition is the target, and one batched request answers for
+/// the whole workspace.
+#[cfg(unix)]
+pub async fn handle_grep_command(
     workspace_root: &Path,
-    file: Option<&Path>,
-    symbols: &[String],
-    fuzzy: bool,
+    pattern: &str,
+    target: &str,
     formatter: &OutputFormatter,
     timeout: Duration,
     debug_log: Option<Arc<DebugLog>>,
 ) -> Result<()> {
-    // --fuzzy mode: use workspace/symbol pure fuzzy query
-    if fuzzy {
-        #[cfg(not(unix))]
-        {
-            let _ = (workspace_root, symbols, timeout, debug_log);
-            anyhow::bail!(
-                "The --fuzzy flag requires the background daemon, which is only \
-                 supported on Unix systems."
-            );
-        }
-        #[cfg(unix)]
-        {
-            ensure_daemon_running().await?;
-            let mut client = connect_daemon(timeout, debug_log.as_ref()).await?;
+    use crate::cli::output::GrepResult;
+    use crate::grep::find_text_matches;
 
-            for symbol in symbols {
-                let result = client
-                    .execute_workspace_symbols(workspace_root.to_path_buf(), symbol.clone())
-                    .await?;
-
-                if result.symbols.is_empty() {
-                    if let Some(ref log) = debug_log {
-                        log.log_result_summary(&format!(
-                            "0 symbols found matching '{symbol}' (fuzzy)"
-                        ));
-                    }
-                    println!(
-                        "{}",
-                        formatter.styler().error(&format!("No results found matching '{symbol}'"))
-                    );
-                } else {
-                    if let Some(ref log) = debug_log {
-                        log.log_result_summary(&format!(
-                            "{} symbol(s) found matching '{symbol}' (fuzzy)",
-                            result.symbols.len()
-                        ));
-                    }
-                    if symbols.len() > 1 {
-                        let heading =
-                            format!("=== {symbol} ({} match(es)) ===", result.symbols.len());
-                        println!("{}\n", formatter.styler().symbol(&heading));
-                    }
-                    println!("{}", formatter.format_workspace_symbols(&result.symbols));
-                }
-            }
-            if let Some(ref log) = debug_log {
-                let cmd = format!("find {} --fuzzy", symbols.join(" "));
-                log.log_reproduction_commands(workspace_root, symbols, &cmd);
-                // Log LSP snippet for each fuzzy query
-                for sym in symbols {
-                    log.log_lsp_snippet(workspace_root, sym, 0, 0, "workspace/symbol");
-                }
-            }
-            return Ok(());
-        }
+    if pattern.is_empty() {
+        return Err(UsageError("The search pattern is empty".to_string()).into());
     }
+    let mut client = connect_daemon(timeout, debug_log.as_ref()).await?;
 
-    let mut results: Vec<(String, Vec<Location>)> = Vec::new();
-
-    if let Some(file) = file {
-        let client = TyLspClient::new(&workspace_root.to_string_lossy()).await?;
-        let file_str = file.to_string_lossy();
-        let finder = SymbolFinder::new(&file_str).await?;
-        client.open_document(&file_str).await?;
-
-        for symbol in symbols {
-            let positions = finder.find_symbol_positions(symbol);
-
-            if positions.is_empty() {
-                results.push((symbol.clone(), Vec::new()));
-                continue;
-            }
-
-            let mut all_locations = Vec::new();
-            for (line, column) in positions {
-                let locations =
-                    client.goto_definition(&file.to_string_lossy(), line, column).await?;
-                all_locations.extend(locations);
-            }
-            dedup_locations(&mut all_locations);
+    let definitions = resolve_grep_target(&mut client, workspace_root, target).await?;
+    if definitions.is_empty() {
+        anyhow::bail!("Could not find '{target}' in the workspace");
+    }
+    let references = execute_references_batch(
+        &mut client,
+        definitions,
+        workspace_root,
+        true,
+        false,
+        ResultPage::default(),
+    )
+    .await?
+    .into_iter()
+    .flat_map(|entry| entry.locations)
+    .collect::<Vec<_>>();
+    let mut by_file: HashMap<PathBuf, Vec<&Location>> = HashMap::new();
+    for location in &references {
+        by_file.entry(PathBuf::from(uri_to_path(&location.uri))).or_default().push(location);
+    }
 
-            results.push((symbol.clone(), all_locations));
-        }
-    } else {
-        #[cfg(not(unix))]
-        {
-            let _ = (workspace_root, symbols, timeout, debug_log);
-            anyhow::bail!(
-                "Finding symbols without --file requires the background daemon, which is only \
-                 supported on Unix systems. Use --file to search within a specific file instead."
-            );
-        }
-        #[cfg(unix)]
-        {
-            for symbol in symbols {
-                let locations =
-                    find_symbol_via_workspace(workspace_root, symbol, timeout, debug_log.as_ref())
-                        .await?;
-                results.push((symbol.clone(), locations));
+    // Walk the canonical root so paths line up with the URIs ty reports.
+    let root = crate::lsp::uri::canonicalize(workspace_root)
+        .unwrap_or_else(|_| workspace_root.to_path_buf());
+    let config = crate::workspace::ty_config::TyConfig::load_or_default(&root);
+    let mut text_matches = 0;
+    let mut hits: Vec<Location> = Vec::new();
+    for path in crate::workspace::walker::WorkspaceWalker::new(&root, &config).python_files() {
+        let Ok(text) = tokio::fs::read_to_string(&path).await else { continue };
+        let matches = find_text_matches(&text, pattern);
+        text_matches += matches.len();
+        let Some(file_refs) = by_file.get(&path) else { continue };
+        for text_match in matches {
+            let hit = text_match.identifiers.iter().find_map(|&column| {
+                file_refs.iter().find(|r| {
+                    r.range.start.line == text_match.line
+                        && (r.range.start.character..=r.range.end.character).contains(&column)
+                })
+            });
+            if let Some(&location) = hit {
+                hits.push(location.clone());
             }
         }
     }
 
     if let Some(ref log) = debug_log {
-        let total: usize = results.iter().map(|(_, locs)| locs.len()).sum();
-        log.log_result_summary(&format!("{total} definition(s) found"));
-        let cmd = format!("find {}", symbols.join(" "));
-        log.log_reproduction_commands(workspace_root, symbols, &cmd);
-        // Log LSP snippet using the first result location (if any)
-        for (sym, locs) in &results {
-            if let Some(loc) = locs.first() {
-                let file_path = loc.uri.strip_prefix("file://").unwrap_or(&loc.uri);
-                log.log_lsp_snippet(
-                    workspace_root,
-                    file_path,
-                    loc.range.start.line,
-                    loc.range.start.character,
-                    "textDocument/definition",
-                );
-            } else if file.is_none() {
-                log.log_lsp_snippet(workspace_root, sym, 0, 0, "workspace/symbol");
-            }
-        }
+        log.log_result_summary(&format!(
+            "{} of {text_matches} text match(es) resolve to {target}",
+            hits.len()
+        ));
     }
 
-    let cache =
-        SourceCache::from_uris(results.iter().flat_map(|(_, locs)| locs).map(|l| l.uri.as_str()))
-            .await;
-    println!("{}", formatter.format_find_results(&results, &cache));
-
-    Ok(())
+    let cache = SourceCache::from_uris(hits.iter().map(|l| l.uri.as_str())).await;
+    let result =
+        GrepResult { pattern: pattern.to_string(), target: target.to_string(), text_matches, hits };
+    println!("{}", formatter.format_grep_results(&result, &cache));
+    found_any(!result.hits.is_empty())
 }
 
-/// Find a symbol's location(s) using workspace symbols search.
+/// Definitions in the workspace named by a `grep --resolves-to` target.
+///
+/// Candidates are found by their last name segment, then kept if their
+/// module-qualified name (e.g. `app.models.User.save`) ends with the target.
 #[cfg(unix)]
-async fn find_symbol_via_workspace(
+async fn resolve_grep_target(
+    client: &mut DaemonClient,
     workspace_root: &Path,
-    symbol: &str,
-    timeout: Duration,
-    debug_log: Option<&Arc<DebugLog>>,
-) -> Result<Vec<Location>> {
-    ensure_daemon_running().await?;
-    let mut client = connect_daemon(timeout, debug_log).await?;
+    target: &str,
+) -> Result<Vec<ResolvedQuery>> {
+    use crate::grep::{module_name, names_target};
 
-    // Use exact_name filter (with optional container filter for dotted notation)
-    // so the daemon only returns symbols with matching names.
-    let (_search_name, result) =
-        workspace_symbols_dotted(&mut client, workspace_root.to_path_buf(), symbol).await?;
+    let name = target.rsplit('.').next().unwrap_or(target);
+    let candidates = client
+        .execute_workspace_symbols_exact(workspace_root.to_path_buf(), name.to_string(), Vec::new())
+        .await?;
+    let root = crate::lsp::uri::canonicalize(workspace_root)
+        .unwrap_or_else(|_| workspace_root.to_path_buf());
 
-    // If exact matches found, use them; otherwise fall back to fuzzy search
-    // (only for bare names — dotted notation never falls back to avoid confusion).
-    if !result.symbols.is_empty() {
-        return Ok(result.symbols.into_iter().map(|s| s.location).collect());
-    }
-
-    if parse_dotted_symbol(symbol).is_some() {
-        // Dotted notation: no fallback to fuzzy search
-        return Ok(Vec::new());
+    let sources =
+        SourceCache::from_uris(candidates.symbols.iter().map(|s| s.location.uri.as_str())).await;
+    let mut doc_sym_cache: HashMap<String, Vec<DocumentSymbol>> = HashMap::new();
+    let mut resolved = Vec::new();
+    for sym_info in candidates.symbols {
+        let file_path = uri_to_path(&sym_info.location.uri);
+        if !doc_sym_cache.contains_key(&file_path) {
+            let symbols = client
+                .execute_document_symbols(workspace_root.to_path_buf(), file_path.clone())
+                .await
+                .map(|r| r.symbols)
+                .unwrap_or_default();
+            doc_sym_cache.insert(file_path.clone(), symbols);
+        }
+        let start = &sym_info.location.range.start;
+        let enclosing = doc_sym_cache
+            .get(&file_path)
+            .and_then(|symbols| find_enclosing_symbol(symbols, start.line, start.character))
+            .unwrap_or_else(|| sym_info.name.clone());
+        let module = module_name(&root, Path::new(&file_path));
+        let qualified = if module.is_empty() { enclosing } else { format!("{module}.{enclosing}") };
+        if !names_target(&qualified, target) {
+            continue;
+        }
+        // Workspace-symbol range.start may point at a decorator or keyword;
+        // references need the symbol *name*.
+        let (line, column) = find_name_column(&sources, &file_path, start.line, &sym_info.name)
+            .unwrap_or((start.line, start.character));
+        resolved.push(ResolvedQuery { label: target.to_string(), file: file_path, line, column });
     }
+    Ok(resolved)
+}
 
-    // Fallback: fuzzy search (no exact_name filter), reuse the same connection
-    let result =
-        client.execute_workspace_symbols(workspace_root.to_path_buf(), symbol.to_string()).await?;
-    Ok(result.symbols.into_iter().map(|s| s.location).collect())
+#[cfg(not(unix))]
+pub async fn handle_grep_command(
+    _workspace_root: &Path,
+    _pattern: &str,
+    _target: &str,
+    _formatter: &OutputFormatter,
+    _timeout: Duration,
+    _debug_log: Option<Arc<DebugLog>>,
+) -> Result<()> {
+    anyhow::bail!(
+        "The 'grep' command requires the background daemon, which is only supported on Unix systems"
+    )
 }
 
+/// Search the workspace's symbols, letting the daemon apply the kind, path
+/// and limit filters.
 #[cfg(unix)]
-#[allow(clippy::too_many_arguments, clippy::too_many_lines)]
-pub async fn handle_show_command(
+#[allow(clippy::too_many_arguments)]
+pub async fn handle_symbols_command(
     workspace_root: &Path,
-    file: Option<&Path>,
-    symbols: &[String],
+    query: &str,
+    kinds: &[SymbolKindFilter],
+    page: ResultPage,
+    within: Option<&Path>,
+    include_deps: bool,
+    path_filter: &PathFilter,
     formatter: &OutputFormatter,
     timeout: Duration,
-    show_individual_refs: bool,
-    references_limit: usize,
-    show_tests: bool,
-    show_doc: bool,
     debug_log: Option<Arc<DebugLog>>,
 ) -> Result<()> {
-    ensure_daemon_running().await?;
-
-    let mut results: Vec<InspectResult> = Vec::new();
-    for symbol in symbols {
-        // Always fetch references for the count summary
-        let result = inspect_single_symbol(workspace_root, file, symbol, timeout, true).await?;
-        results.push(result);
-    }
-
-    if let Some(ref log) = debug_log {
-        for r in &results {
-            let has_hover = if r.hover.is_some() { "yes" } else { "no" };
-            log.log_result_summary(&format!(
-                "show '{}': {} definition(s), hover={has_hover}, {} reference(s)",
-                r.symbol,
-                r.definitions.len(),
-                r.references.len(),
-            ));
-        }
-        let cmd = format!("show {}", symbols.join(" "));
-        log.log_reproduction_commands(workspace_root, symbols, &cmd);
-    }
-
-    // Build enriched entries — reuse a single daemon connection for all enrichment
-    let mut entries: Vec<ShowEntry<'_>> = Vec::new();
-    let needs_enrichment = show_individual_refs && results.iter().any(|r| !r.references.is_empty());
-    let mut enrich_client = if needs_enrichment {
-        Some(DaemonClient::connect_with_timeout(timeout).await?)
+    let mut client = connect_daemon(timeout, debug_log.as_ref()).await?;
+    client.set_path_filter(path_filter.clone());
+    let (found, remaining) = if formatter.prints_symbols_incrementally() {
+        let search = SymbolSearch { workspace_root, query, kinds, page, within, include_deps };
+        stream_symbols(&mut client, &search, formatter).await?
     } else {
-        None
+        let WorkspaceSymbolsResult { symbols: found, remaining } =
+            collect_symbols(&mut client, workspace_root, query, kinds, page, within, include_deps)
+                .await?;
+        if !found.is_empty() {
+            let cache = SourceCache::from_uris(found.iter().map(|s| s.location.uri.as_str())).await;
+            println!("{}", formatter.format_workspace_symbols(&found, &cache));
+        }
+        (found.len(), remaining)
     };
-    for r in &results {
-        // Partition into non-test and test references
-        let (non_test_refs, test_refs) = partition_test_locations(r.references.clone());
-
-        let total_reference_count = non_test_refs.len();
-        let total_reference_files = count_unique_files(&non_test_refs);
-
-        let (displayed_references, remaining_reference_count) =
-            if show_individual_refs && !non_test_refs.is_empty() {
-                let display_count = if references_limit == 0 {
-                    non_test_refs.len()
-                } else {
-                    references_limit.min(non_test_refs.len())
-                };
-                let to_display = &non_test_refs[..display_count];
-                let remaining = non_test_refs.len() - display_count;
-
-                let enriched = enrich_references(
-                    to_display,
-                    workspace_root,
-                    enrich_client.as_mut().expect("client created above"),
-                )
-                .await;
-                (enriched, remaining)
-            } else {
-                (Vec::new(), 0)
-            };
+    if let Some(ref log) = debug_log {
+        log.log_result_summary(&format!("{found} symbol(s) found matching '{query}'"));
+    }
 
-        // Build test references section
-        let test_references = if test_refs.is_empty() {
-            None
-        } else if show_tests {
-            let test_total = test_refs.len();
-            let (test_displayed, test_remaining) = if show_individual_refs && !test_refs.is_empty()
-            {
-                let test_display_count = if references_limit == 0 {
-                    test_total
-                } else {
-                    references_limit.min(test_total)
-                };
-                let test_to_display = &test_refs[..test_display_count];
-                let remaining = test_total - test_display_count;
-                let enriched = enrich_references(
-                    test_to_display,
-                    workspace_root,
-                    enrich_client.as_mut().expect("client created above"),
-                )
-                .await;
-                (enriched, remaining)
-            } else {
-                (Vec::new(), 0)
-            };
-            Some(crate::cli::output::TestReferencesSection {
-                total_count: test_total,
-                displayed: test_displayed,
-                remaining_count: test_remaining,
-            })
+    if found == 0 {
+        let message = if query.is_empty() {
+            "No symbols found".to_string()
         } else {
-            // Not showing tests, but record count for hint
-            Some(crate::cli::output::TestReferencesSection {
-                total_count: test_refs.len(),
-                displayed: Vec::new(),
-                remaining_count: 0,
-            })
+            format!("No symbols found matching '{query}'")
         };
+        println!("{}", formatter.styler().error(&message));
+        return found_any(false);
+    }
+    formatter.print_more_results(remaining, page);
+    Ok(())
+}
 
-        entries.push(ShowEntry {
-            symbol: r.symbol.as_str(),
-            kind: r.kind.as_ref(),
-            definitions: r.definitions.as_slice(),
-            hover: r.hover.as_ref(),
-            total_reference_count,
-            total_reference_files,
-            displayed_references,
-            remaining_reference_count,
-            show_individual_refs,
-            show_doc,
-            test_references,
+/// A `tyf symbols` search, as [`collect_symbols`] takes it.
+#[cfg(unix)]
+struct SymbolSearch<'a> {
+    workspace_root: &'a Path,
+    query: &'a str,
+    kinds: &'a [SymbolKindFilter],
+    page: ResultPage,
+    within: Option<&'a Path>,
+    include_deps: bool,
+}
+
+/// Print the symbols of `search` part by part as the daemon sends them.
+/// Returns how many were printed and how many more are past the page.
+#[cfg(unix)]
+async fn stream_symbols(
+    client: &mut DaemonClient,
+    search: &SymbolSearch<'_>,
+    formatter: &OutputFormatter,
+) -> Result<(usize, usize)> {
+    let workspace_root = search.workspace_root;
+    let mut printer = formatter.symbols_printer();
+    let (parts, mut received) = tokio::sync::mpsc::unbounded_channel();
+    let fetch = client.stream_symbol_search(
+        workspace_root.to_path_buf(),
+        search.query.to_string(),
+        search.kinds.iter().map(|k| k.symbol_kind()).collect(),
+        symbol_search_prefix(workspace_root, search.within, search.include_deps)?,
+        search.page,
+        move |part| {
+            let _ = parts.send(part.symbols);
+        },
+    );
+    let print = async {
+        while let Some(symbols) = received.recv().await {
+            print_symbols_part(&mut printer, symbols, search).await;
+        }
+    };
+    let (rest, ()) = tokio::join!(fetch, print);
+    let rest = rest?;
+    print_symbols_part(&mut printer, rest.symbols, search).await;
+    let count = printer.printed();
+    printer.finish();
+    Ok((count, rest.remaining))
+}
+
+/// Print one part of `search`'s symbols, without dependencies unless asked.
+#[cfg(unix)]
+async fn print_symbols_part(
+    printer: &mut SymbolsPrinter<'_>,
+    mut symbols: Vec<SymbolInformation>,
+    search: &SymbolSearch<'_>,
+) {
+    if !search.include_deps {
+        symbols.retain(|s| {
+            Origin::classify(&s.location.uri, search.workspace_root) == Origin::Workspace
         });
     }
+    let cache = SourceCache::from_uris(symbols.iter().map(|s| s.location.uri.as_str())).await;
+    printer.print(&symbols, &cache);
+}
 
-    let cache = SourceCache::from_uris(entries.iter().flat_map(|e| {
-        let defs = e.definitions.iter().map(|l| l.uri.as_str());
-        let refs = e.displayed_references.iter().map(|r| r.location.uri.as_str());
-        let test = e
-            .test_references
-            .iter()
-            .flat_map(|t| t.displayed.iter().map(|r| r.location.uri.as_str()));
-        defs.chain(refs).chain(test)
-    }))
-    .await;
-    println!("{}", formatter.format_show_results(&entries, &cache));
+/// Workspace symbols matching `query`, narrowed by the daemon to `kinds`,
+/// the `within` directory (the workspace unless `include_deps`) and `page`.
+#[cfg(unix)]
+pub async fn collect_symbols(
+    client: &mut DaemonClient,
+    workspace_root: &Path,
+    query: &str,
+    kinds: &[SymbolKindFilter],
+    page: ResultPage,
+    within: Option<&Path>,
+    include_deps: bool,
+) -> Result<WorkspaceSymbolsResult> {
+    let path_prefix = symbol_search_prefix(workspace_root, within, include_deps)?;
+    let kinds = kinds.iter().map(|k| k.symbol_kind()).collect();
+    let mut found = client
+        .execute_symbol_search(
+            workspace_root.to_path_buf(),
+            query.to_string(),
+            kinds,
+            path_prefix,
+            page,
+        )
+        .await?;
+    if !include_deps {
+        found
+            .symbols
+            .retain(|s| Origin::classify(&s.location.uri, workspace_root) == Origin::Workspace);
+    }
+    Ok(found)
+}
 
-    Ok(())
+/// The directory a symbol search is narrowed to: `within`, else the
+/// workspace unless `include_deps`.
+#[cfg(unix)]
+fn symbol_search_prefix(
+    workspace_root: &Path,
+    within: Option<&Path>,
+    include_deps: bool,
+) -> Result<Option<PathBuf>> {
+    // ty reports canonical paths, so compare against the canonical directory.
+    Ok(match within {
+        Some(dir) => Some(
+            workspace_root
+                .join(dir)
+                .canonicalize()
+                .with_context(|| format!("Can't search in {}", dir.display()))?,
+        ),
+        None if include_deps => None,
+        None => Some(workspace_root.to_path_buf()),
+    })
 }
 
 #[cfg(not(unix))]
 #[allow(clippy::too_many_arguments)]
-pub async fn handle_show_command(
+pub async fn handle_symbols_command(
     _workspace_root: &Path,
-    _file: Option<&Path>,
-    _symbols: &[String],
+    _query: &str,
+    _kinds: &[SymbolKindFilter],
+    _page: ResultPage,
+    _within: Option<&Path>,
+    _include_deps: bool,
+    _path_filter: &PathFilter,
     _formatter: &OutputFormatter,
     _timeout: Duration,
-    _show_individual_refs: bool,
-    _references_limit: usize,
-    _show_tests: bool,
-    _show_doc: bool,
     _debug_log: Option<Arc<DebugLog>>,
 ) -> Result<()> {
     anyhow::bail!(
-        "The 'show' command requires the background daemon, which is only supported on Unix systems"
+        "The 'symbols' command requires the background daemon, which is only supported on Unix systems"
     )
 }
 
-#[cfg(unix)]
-struct InspectResult {
-    symbol: String,
-    kind: Option<crate::lsp::protocol::SymbolKind>,
-    definitions: Vec<Location>,
-    hover: Option<crate::lsp::protocol::Hover>,
-    references: Vec<Location>,
-}
-
-#[cfg(unix)]
-async fn inspect_single_symbol(
+#[allow(clippy::too_many_arguments, clippy::too_many_lines)]
+pub async fn handle_find_command(
     workspace_root: &Path,
     file: Option<&Path>,
-    symbol: &str,
+    symbols: &[String],
+    fuzzy: bool,
+    kinds: &[SymbolKindFilter],
+    page: ResultPage,
+    include_deps: bool,
+    path_filter: &PathFilter,
+    formatter: &OutputFormatter,
     timeout: Duration,
-    include_references: bool,
-) -> Result<InspectResult> {
-    // Step 1: Find the symbol's location(s)
-    let (mut client, definition_file, def_line, def_col, all_definitions, symbol_kind) =
-        if let Some(file) = file {
-            let file_str = file.to_string_lossy();
-            let finder = SymbolFinder::new(&file_str).await?;
-            let positions = finder.find_symbol_positions(symbol);
+    debug_log: Option<Arc<DebugLog>>,
+) -> Result<()> {
+    let kinds: Vec<SymbolKind> = kinds.iter().map(|k| k.symbol_kind()).collect();
 
-            if positions.is_empty() {
-                return Ok(InspectResult {
-                    symbol: symbol.to_string(),
-                    kind: None,
-                    definitions: Vec::new(),
-                    hover: None,
-                    references: Vec::new(),
+    // --fuzzy mode: use workspace/symbol pure fuzzy query
+    if fuzzy {
+        // Without the daemon (Windows), query an in-process ty server instead.
+        #[cfg(not(unix))]
+        let client = {
+            let _ = timeout;
+            TyLspClient::new(&workspace_root.to_string_lossy()).await?
+        };
+        #[cfg(unix)]
+        let mut client = connect_daemon(timeout, debug_log.as_ref()).await?;
+        #[cfg(unix)]
+        client.set_path_filter(path_filter.clone());
+
+        let mut any_found = false;
+        for symbol in symbols {
+            #[cfg(not(unix))]
+            let (mut found, remaining) = {
+                let mut found = direct_workspace_symbols(&client, symbol).await?;
+                found.retain(|s| kinds.is_empty() || kinds.contains(&s.kind));
+                if !include_deps {
+                    found.retain(|s| {
+                        Origin::classify(&s.location.uri, workspace_root) == Origin::Workspace
+                    });
+                }
+                let remaining = page.apply(&mut found);
+                (found, remaining)
+            };
+            // The daemon drops dependencies itself so that the page is full.
+            #[cfg(unix)]
+            let WorkspaceSymbolsResult { symbols: mut found, remaining } = client
+                .execute_symbol_search(
+                    workspace_root.to_path_buf(),
+                    symbol.clone(),
+                    kinds.clone(),
+                    (!include_deps).then(|| workspace_root.to_path_buf()),
+                    page,
+                )
+                .await?;
+            if !include_deps {
+                found.retain(|s| {
+                    Origin::classify(&s.location.uri, workspace_root) == Origin::Workspace
                 });
             }
 
-            let (first_line, first_col) = positions[0];
-
-            let mut client = DaemonClient::connect_with_timeout(timeout).await?;
-            let mut all_definitions = Vec::new();
-            for (line, column) in &positions {
-                let result = client
-                    .execute_definition(
-                        workspace_root.to_path_buf(),
-                        file_str.to_string(),
-                        *line,
-                        *column,
-                    )
-                    .await?;
-                if let Some(loc) = result.location {
-                    all_definitions.push(loc);
+            if found.is_empty() {
+                if let Some(ref log) = debug_log {
+                    log.log_result_summary(&format!("0 symbols found matching '{symbol}' (fuzzy)"));
+                }
+                println!(
+                    "{}",
+                    formatter.styler().error(&format!("No results found matching '{symbol}'"))
+                );
+            } else {
+                any_found = true;
+                if let Some(ref log) = debug_log {
+                    log.log_result_summary(&format!(
+                        "{} symbol(s) found matching '{symbol}' (fuzzy)",
+                        found.len()
+                    ));
+                }
+                if symbols.len() > 1 {
+                    let heading = format!("=== {symbol} ({} match(es)) ===", found.len());
+                    println!("{}\n", formatter.styler().symbol(&heading));
                 }
+                let cache =
+                    SourceCache::from_uris(found.iter().map(|s| s.location.uri.as_str())).await;
+                println!("{}", formatter.format_workspace_symbols(&found, &cache));
             }
-            dedup_locations(&mut all_definitions);
-
-            // File-based search doesn't provide symbol kind
-            (client, file_str.to_string(), first_line, first_col, all_definitions, None)
-        } else {
-            // Use exact_name filter (with optional container for dotted notation)
-            let mut client = DaemonClient::connect_with_timeout(timeout).await?;
-            let (_search_name, result) =
-                workspace_symbols_dotted(&mut client, workspace_root.to_path_buf(), symbol).await?;
-
-            let matched = &result.symbols;
-
-            if matched.is_empty() {
-                return Ok(InspectResult {
-                    symbol: symbol.to_string(),
-                    kind: None,
-                    definitions: Vec::new(),
-                    hover: None,
-                    references: Vec::new(),
-                });
+            formatter.print_more_results(remaining, page);
+        }
+        if let Some(ref log) = debug_log {
+            let cmd = format!("find {} --fuzzy", symbols.join(" "));
+            log.log_reproduction_commands(workspace_root, symbols, &cmd);
+            // Log LSP snippet for each fuzzy query
+            for sym in symbols {
+                log.log_lsp_snippet(workspace_root, sym, 0, 0, "workspace/symbol");
             }
+        }
+        return found_any(any_found);
+    }
 
-            let first = &matched[0];
-            let file_path =
-                first.location.uri.strip_prefix("file://").unwrap_or(&first.location.uri);
-            let ws_line = first.location.range.start.line;
-            let ws_col = first.location.range.start.character;
-            // Workspace-symbol range.start may point at a decorator or keyword;
-            // hover/references need the symbol *name* position.
-            let name_pos = find_name_column(file_path, ws_line, &first.name).await;
-            let (def_line, def_col) = name_pos.unwrap_or((ws_line, ws_col));
-            tracing::debug!(
-                "inspect: workspace-symbol line={ws_line} col={ws_col}, resolved line={def_line} col={def_col} for '{}'",
-                first.name
-            );
-            let all_definitions: Vec<Location> =
-                matched.iter().map(|s| s.location.clone()).collect();
-
-            (
-                client,
-                file_path.to_string(),
-                def_line,
-                def_col,
-                all_definitions,
-                Some(first.kind.clone()),
-            )
-        };
+    let mut results: Vec<(String, Vec<Location>)> = Vec::new();
 
-    // Steps 2 & 3: Get hover info (and optionally references) via single daemon call
-    tracing::debug!(
-        "inspect: querying hover/refs at {definition_file}:{def_line}:{def_col} for '{symbol}'"
-    );
-    let inspect = client
-        .execute_inspect(
-            workspace_root.to_path_buf(),
-            definition_file,
-            def_line,
-            def_col,
-            include_references,
-        )
-        .await?;
+    // Every place the names occur in the file is asked for its definition
+    // in one batch.
+    #[cfg(unix)]
+    if let Some(file) = file {
+        let finder = SymbolFinder::new(&file.to_string_lossy()).await?;
+        let queries = symbols
+            .iter()
+            .flat_map(|symbol| {
+                finder.find_symbol_positions(symbol).into_iter().map(|(line, column)| {
+                    BatchReferencesQuery {
+                        label: symbol.clone(),
+                        file: file.to_path_buf(),
+                        line,
+                        column,
+                    }
+                })
+            })
+            .collect();
+        let mut client = connect_daemon(timeout, debug_log.as_ref()).await?;
+        let mut found = resolve_definitions(&mut client, workspace_root, queries).await?;
+        for symbol in symbols {
+            let mut all_locations: Vec<Location> = found
+                .iter_mut()
+                .filter(|(label, _)| label == symbol)
+                .flat_map(|(_, locations)| std::mem::take(locations))
+                .collect();
+            dedup_locations(&mut all_locations);
+            results.push((symbol.clone(), all_locations));
+        }
+    }
+    #[cfg(not(unix))]
+    if let Some(file) = file {
+        let client = TyLspClient::new(&workspace_root.to_string_lossy()).await?;
+        let file_str = file.to_string_lossy();
+        let finder = SymbolFinder::new(&file_str).await?;
+        client.open_document(&file_str).await?;
 
-    tracing::debug!(
-        "inspect: hover={}, refs={}",
-        if inspect.hover.is_some() { "present" } else { "NONE" },
-        inspect.references.len()
-    );
+        for symbol in symbols {
+            let positions = finder.find_symbol_positions(symbol);
+
+            if positions.is_empty() {
+                results.push((symbol.clone(), Vec::new()));
+                continue;
+            }
+
+            let mut all_locations = Vec::new();
+            for (line, column) in positions {
+                let locations =
+                    client.goto_definition(&file.to_string_lossy(), line, column).await?;
+                all_locations.extend(locations);
+            }
+            dedup_locations(&mut all_locations);
+
+            results.push((symbol.clone(), all_locations));
+        }
+    }
+    if file.is_none() {
+        #[cfg(not(unix))]
+        {
+            let _ = timeout;
+            let client = TyLspClient::new(&workspace_root.to_string_lossy()).await?;
+            for symbol in symbols {
+                let mut locations = find_symbol_direct(&client, symbol, &kinds).await?;
+                if !include_deps {
+                    retain_workspace_locations(&mut locations, workspace_root);
+                }
+                results.push((symbol.clone(), locations));
+            }
+        }
+        #[cfg(unix)]
+        {
+            let mut client = connect_daemon(timeout, debug_log.as_ref()).await?;
+            client.set_path_filter(path_filter.clone());
+            for symbol in symbols {
+                let mut locations =
+                    find_symbol_via_workspace(&mut client, workspace_root, symbol, &kinds).await?;
+                if !include_deps {
+                    retain_workspace_locations(&mut locations, workspace_root);
+                }
+                results.push((symbol.clone(), locations));
+            }
+        }
+    }
+
+    let remaining: usize = results.iter_mut().map(|(_, locs)| page.apply(locs)).sum();
+
+    if let Some(ref log) = debug_log {
+        let total: usize = results.iter().map(|(_, locs)| locs.len()).sum();
+        log.log_result_summary(&format!("{total} definition(s) found"));
+        let cmd = format!("find {}", symbols.join(" "));
+        log.log_reproduction_commands(workspace_root, symbols, &cmd);
+        // Log LSP snippet using the first result location (if any)
+        for (sym, locs) in &results {
+            if let Some(loc) = locs.first() {
+                let file_path = uri_to_path(&loc.uri);
+                log.log_lsp_snippet(
+                    workspace_root,
+                    &file_path,
+                    loc.range.start.line,
+                    loc.range.start.character,
+                    "textDocument/definition",
+                );
+            } else if file.is_none() {
+                log.log_lsp_snippet(workspace_root, sym, 0, 0, "workspace/symbol");
+            }
+        }
+    }
+
+    let cache =
+        SourceCache::from_uris(results.iter().flat_map(|(_, locs)| locs).map(|l| l.uri.as_str()))
+            .await;
+    println!("{}", formatter.format_find_results(&results, &cache));
+    formatter.print_more_results(remaining, page);
+
+    found_any(results.iter().any(|(_, locations)| !locations.is_empty()))
+}
+
+/// Find a symbol's location(s) using workspace symbols search, keeping only
+/// symbols of `kinds` when it is non-empty.
+#[cfg(unix)]
+async fn find_symbol_via_workspace(
+    client: &mut DaemonClient,
+    workspace_root: &Path,
+    symbol: &str,
+    kinds: &[SymbolKind],
+) -> Result<Vec<Location>> {
+    // Use exact_name filter (with optional container filter for dotted notation)
+    // so the daemon only returns symbols with matching names.
+    let (_search_name, result) =
+        workspace_symbols_dotted(client, workspace_root.to_path_buf(), symbol, kinds).await?;
+
+    // If exact matches found, use them; otherwise fall back to fuzzy search
+    // (only for bare names — dotted notation never falls back to avoid confusion).
+    if !result.symbols.is_empty() {
+        return Ok(result.symbols.into_iter().map(|s| s.location).collect());
+    }
+
+    if parse_dotted_symbol(symbol).is_some() {
+        // Dotted notation: no fallback to fuzzy search
+        return Ok(Vec::new());
+    }
+
+    // Fallback: fuzzy search (no exact_name filter), reuse the same connection
+    let result = client
+        .execute_workspace_symbols(workspace_root.to_path_buf(), symbol.to_string(), kinds.to_vec())
+        .await?;
+    Ok(result.symbols.into_iter().map(|s| s.location).collect())
+}
+
+/// Back-off used by the in-process fallbacks while a fresh ty server indexes.
+#[cfg_attr(unix, allow(dead_code))]
+const DIRECT_WARMUP_DELAYS_MS: &[u64] = &[200, 500, 1000, 2000];
+
+/// Workspace symbol search against an in-process ty server.
+///
+/// Used where the daemon is unavailable (Windows). A freshly started server
+/// answers with an empty list until its index is built, so empty results are
+/// retried with back-off.
+#[cfg_attr(unix, allow(dead_code))]
+async fn direct_workspace_symbols(
+    client: &TyLspClient,
+    query: &str,
+) -> Result<Vec<SymbolInformation>> {
+    let mut symbols = client.workspace_symbols(query).await?;
+    for delay_ms in DIRECT_WARMUP_DELAYS_MS {
+        if !symbols.is_empty() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(*delay_ms)).await;
+        symbols = client.workspace_symbols(query).await?;
+    }
+    Ok(symbols)
+}
+
+/// Exact-name workspace symbols from an in-process ty server.
+///
+/// Applies the same exact-name and `Class.member` filtering the daemon does
+/// for `workspace_symbols_dotted`.
+#[cfg_attr(unix, allow(dead_code))]
+async fn workspace_symbols_exact_direct(
+    client: &TyLspClient,
+    symbol: &str,
+) -> Result<Vec<SymbolInformation>> {
+    let (container, name) = match parse_dotted_symbol(symbol) {
+        Some((container, member)) => (Some(container), member),
+        None => (None, symbol),
+    };
+    let mut matched = direct_workspace_symbols(client, name).await?;
+    matched.retain(|s| s.name == name);
+    let Some(container) = container else {
+        return Ok(matched);
+    };
+
+    let mut doc_sym_cache: HashMap<String, Vec<DocumentSymbol>> = HashMap::new();
+    let mut filtered = Vec::new();
+    for sym_info in matched {
+        let file_path = uri_to_path(&sym_info.location.uri);
+        if !doc_sym_cache.contains_key(&file_path) {
+            let ds = match client.open_document(&file_path).await {
+                Ok(_) => client.document_symbols(&file_path).await.unwrap_or_default(),
+                Err(_) => Vec::new(),
+            };
+            doc_sym_cache.insert(file_path.clone(), ds);
+        }
+        let doc_symbols = doc_sym_cache.get(&file_path).map_or(&[][..], Vec::as_slice);
+        let start = &sym_info.location.range.start;
+        if let Some(enclosing) = find_enclosing_symbol(doc_symbols, start.line, start.character) {
+            if enclosing.starts_with(&format!("{container}.")) {
+                filtered.push(sym_info);
+            }
+        }
+    }
+    Ok(filtered)
+}
+
+/// In-process counterpart of [`find_symbol_via_workspace`].
+#[cfg_attr(unix, allow(dead_code))]
+async fn find_symbol_direct(
+    client: &TyLspClient,
+    symbol: &str,
+    kinds: &[SymbolKind],
+) -> Result<Vec<Location>> {
+    let of_kind = |s: &SymbolInformation| kinds.is_empty() || kinds.contains(&s.kind);
+    let mut matched = workspace_symbols_exact_direct(client, symbol).await?;
+    matched.retain(of_kind);
+    if !matched.is_empty() || parse_dotted_symbol(symbol).is_some() {
+        return Ok(matched.into_iter().map(|s| s.location).collect());
+    }
+    // Bare name with no exact match: fall back to fuzzy search, like the daemon path.
+    let mut fuzzy = direct_workspace_symbols(client, symbol).await?;
+    fuzzy.retain(of_kind);
+    Ok(fuzzy.into_iter().map(|s| s.location).collect())
+}
+
+/// Go to the definition of symbols by name, or of `file:line:col` positions.
+///
+/// Names are resolved like `find` (exact match, `Class.member` narrowing), then
+/// `textDocument/definition` is asked at the name itself, so the result is
+/// ty's precise definition rather than the workspace-symbol declaration range.
+/// A name with several definitions gets one labelled group per definition.
+#[cfg(unix)]
+#[allow(clippy::too_many_arguments)]
+pub async fn handle_definition_command(
+    workspace_root: &Path,
+    file: Option<&Path>,
+    queries: &[String],
+    stdin_path: Option<&Path>,
+    formatter: &OutputFormatter,
+    timeout: Duration,
+    zero_based: bool,
+    debug_log: Option<Arc<DebugLog>>,
+) -> Result<()> {
+    let mut client = connect_daemon(timeout, debug_log.as_ref()).await?;
+    use_stdin_buffer(&mut client, stdin_path, queries)?;
+    let results =
+        collect_definitions(&mut client, workspace_root, file, queries, zero_based).await?;
+
+    if let Some(ref log) = debug_log {
+        let total: usize = results.iter().map(|(_, locs)| locs.len()).sum();
+        log.log_result_summary(&format!("{total} definition(s) found"));
+        let cmd = format!("def {}", queries.join(" "));
+        log.log_reproduction_commands(workspace_root, queries, &cmd);
+    }
+
+    let cache =
+        SourceCache::from_uris(results.iter().flat_map(|(_, locs)| locs).map(|l| l.uri.as_str()))
+            .await;
+    println!("{}", formatter.format_find_results(&results, &cache));
+
+    found_any(results.iter().any(|(_, locations)| !locations.is_empty()))
+}
+
+/// With `--stdin-path`, read that file's unsaved text from stdin and have
+/// `client` send it with the requests about the file.
+///
+/// Only `file:line:col` queries can point into the text: names are looked up
+/// in the files on disk, so they are refused rather than answered from there.
+#[cfg(unix)]
+fn use_stdin_buffer(
+    client: &mut DaemonClient,
+    stdin_path: Option<&Path>,
+    queries: &[String],
+) -> Result<()> {
+    let Some(path) = stdin_path else { return Ok(()) };
+    if let Some(name) = queries.iter().find(|q| parse_file_position(q).is_none()) {
+        anyhow::bail!(
+            "--stdin-path takes file:line:col queries, but '{name}' is a name, which is looked \
+             up in the files on disk"
+        );
+    }
+    let mut text = String::new();
+    std::io::Read::read_to_string(&mut std::io::stdin(), &mut text)
+        .context("Failed to read the file's content from stdin")?;
+    client.set_buffer(path.to_path_buf(), text);
+    Ok(())
+}
+
+/// Definitions of each query (a name or `file:line:col`), labeled by query.
+#[cfg(unix)]
+pub async fn collect_definitions(
+    client: &mut DaemonClient,
+    workspace_root: &Path,
+    file: Option<&Path>,
+    queries: &[String],
+    zero_based: bool,
+) -> Result<Vec<(String, Vec<Location>)>> {
+    let resolved =
+        classify_and_resolve(client, queries, file, workspace_root, zero_based, &[]).await?;
+
+    let mut results: Vec<(String, Vec<Location>)> = Vec::new();
+    let mut batch_queries = Vec::new();
+    for query in resolved {
+        if !results.iter().any(|(label, _)| *label == query.label) {
+            results.push((query.label.clone(), Vec::new()));
+        }
+        if !query.file.is_empty() {
+            batch_queries.push(BatchReferencesQuery {
+                label: query.label,
+                file: PathBuf::from(query.file),
+                line: query.line,
+                column: query.column,
+            });
+        }
+    }
+    // A --file search can match the name several times; merge those.
+    for (label, locations) in resolve_definitions(client, workspace_root, batch_queries).await? {
+        if let Some((_, existing)) = results.iter_mut().find(|(l, _)| *l == label) {
+            existing.extend(locations);
+        }
+    }
+    for (_, locations) in &mut results {
+        dedup_locations(locations);
+    }
+    Ok(results)
+}
+
+/// The definitions at each of `queries`, labelled like the query.
+///
+/// They are resolved in one batch, unless a query points into an unsaved
+/// buffer (`--stdin-path`), which only single definition requests carry.
+#[cfg(unix)]
+async fn resolve_definitions(
+    client: &mut DaemonClient,
+    workspace_root: &Path,
+    queries: Vec<BatchReferencesQuery>,
+) -> Result<Vec<(String, Vec<Location>)>> {
+    if queries.is_empty() {
+        return Ok(Vec::new());
+    }
+    let in_buffer =
+        |q: &BatchReferencesQuery| client.buffer_text(workspace_root, &q.file.to_string_lossy());
+    if !queries.iter().any(|q| in_buffer(q).is_some()) {
+        let result = client
+            .execute_batch_definitions(
+                workspace_root.to_path_buf(),
+                queries,
+                DEFAULT_DEFINITIONS_CONCURRENCY,
+            )
+            .await?;
+        return Ok(result.entries.into_iter().map(|e| (e.label, e.locations)).collect());
+    }
+    let mut definitions = Vec::new();
+    for q in queries {
+        let file = q.file.to_string_lossy().into_owned();
+        let result =
+            client.execute_definition(workspace_root.to_path_buf(), file, q.line, q.column).await?;
+        definitions.push((q.label, result.location.into_iter().collect()));
+    }
+    Ok(definitions)
+}
+
+#[cfg(not(unix))]
+#[allow(clippy::too_many_arguments)]
+pub async fn handle_definition_command(
+    _workspace_root: &Path,
+    _file: Option<&Path>,
+    _queries: &[String],
+    _stdin_path: Option<&Path>,
+    _formatter: &OutputFormatter,
+    _timeout: Duration,
+    _zero_based: bool,
+    _debug_log: Option<Arc<DebugLog>>,
+) -> Result<()> {
+    anyhow::bail!(
+        "The 'def' command requires the background daemon, which is only supported on Unix systems"
+    )
+}
+
+/// Print the whole source of each definition of `queries`: the body of
+/// the function or class, decorators included.
+///
+/// Definitions are found like `def`; the file's outline then says where the
+/// symbol defined there begins and ends.
+#[cfg(unix)]
+#[allow(clippy::too_many_arguments)]
+pub async fn handle_context_command(
+    workspace_root: &Path,
+    file: Option<&Path>,
+    queries: &[String],
+    formatter: &OutputFormatter,
+    timeout: Duration,
+    zero_based: bool,
+    debug_log: Option<Arc<DebugLog>>,
+) -> Result<()> {
+    let mut client = connect_daemon(timeout, debug_log.as_ref()).await?;
+    let definitions =
+        collect_definitions(&mut client, workspace_root, file, queries, zero_based).await?;
+    let cache = SourceCache::from_uris(
+        definitions.iter().flat_map(|(_, locs)| locs).map(|l| l.uri.as_str()),
+    )
+    .await;
+
+    let mut outlines: HashMap<String, Vec<DocumentSymbol>> = HashMap::new();
+    let mut results = Vec::with_capacity(definitions.len());
+    for (label, locations) in definitions {
+        let mut bodies = Vec::with_capacity(locations.len());
+        for location in locations {
+            let Some(source) = cache.get_uri(&location.uri) else { continue };
+            if !outlines.contains_key(&location.uri) {
+                let outline = client
+                    .execute_document_symbols(
+                        workspace_root.to_path_buf(),
+                        uri_to_path(&location.uri),
+                    )
+                    .await?
+                    .symbols;
+                outlines.insert(location.uri.clone(), outline);
+            }
+            bodies.push(definition_body(&outlines[&location.uri], source, location));
+        }
+        results.push((label, bodies));
+    }
+
+    if let Some(ref log) = debug_log {
+        let total: usize = results.iter().map(|(_, bodies)| bodies.len()).sum();
+        log.log_result_summary(&format!("{total} definition(s) found"));
+        let cmd = format!("context {}", queries.join(" "));
+        log.log_reproduction_commands(workspace_root, queries, &cmd);
+    }
+
+    println!("{}", formatter.format_definition_bodies(&results, &cache));
+    found_any(results.iter().any(|(_, bodies)| !bodies.is_empty()))
+}
+
+/// The source of the definition at `location` in `file`: the lines of the
+/// outline symbol defined there, with any decorators above them, or just
+/// its own line when it isn't one (a module-level constant ty has no
+/// outline entry for, say).
+#[cfg(unix)]
+fn definition_body(
+    outline: &[DocumentSymbol],
+    file: &SourceFile,
+    location: Location,
+) -> DefinitionBody {
+    let start = &location.range.start;
+    let symbol = find_defined_symbol(outline, start.line, start.character);
+    let (mut first, mut last) =
+        symbol.map_or((start.line, start.line), |s| (s.range.start.line, symbol_last_line(s)));
+    let at = |line: u32| file.line(line as usize).map_or("", str::trim);
+    while first > 0 && at(first - 1).starts_with('@') {
+        first -= 1;
+    }
+    while last > first && at(last).is_empty() {
+        last -= 1;
+    }
+    let count = u32::try_from(file.line_count()).unwrap_or(u32::MAX);
+    let last = last.min(count.saturating_sub(1)).max(first);
+    let source = file
+        .lines_from(first as usize)
+        .take((last - first + 1) as usize)
+        .map(|(_, line)| line)
+        .collect::<Vec<_>>()
+        .join("\n");
+    DefinitionBody { location, lines: (first, last), kind: symbol.map(|s| s.kind.clone()), source }
+}
+
+#[cfg(not(unix))]
+#[allow(clippy::too_many_arguments)]
+pub async fn handle_context_command(
+    _workspace_root: &Path,
+    _file: Option<&Path>,
+    _queries: &[String],
+    _formatter: &OutputFormatter,
+    _timeout: Duration,
+    _zero_based: bool,
+    _debug_log: Option<Arc<DebugLog>>,
+) -> Result<()> {
+    anyhow::bail!(
+        "The 'context' command requires the background daemon, which is only supported on Unix systems"
+    )
+}
+
+/// The requests `stubs` makes: those its flags pick, or all of them.
+pub fn goto_kinds(decl: bool, type_definition: bool, implementation: bool) -> Vec<GotoKind> {
+    if !(decl || type_definition || implementation) {
+        return GotoKind::ALL.to_vec();
+    }
+    [
+        (decl, GotoKind::Declaration),
+        (type_definition, GotoKind::TypeDefinition),
+        (implementation, GotoKind::Implementation),
+    ]
+    .into_iter()
+    .filter_map(|(picked, kind)| picked.then_some(kind))
+    .collect()
+}
+
+/// Print where symbols are defined, declared, typed and implemented, by
+/// name or `file:line:col`.
+///
+/// Names resolve like `def`. Locations in dependencies are kept, since
+/// that is where most stubs are.
+#[cfg(unix)]
+#[allow(clippy::too_many_arguments)]
+pub async fn handle_stubs_command(
+    workspace_root: &Path,
+    file: Option<&Path>,
+    queries: &[String],
+    kinds: &[GotoKind],
+    formatter: &OutputFormatter,
+    timeout: Duration,
+    zero_based: bool,
+    debug_log: Option<Arc<DebugLog>>,
+) -> Result<()> {
+    let mut client = connect_daemon(timeout, debug_log.as_ref()).await?;
+    let entries =
+        collect_goto(&mut client, workspace_root, file, queries, kinds, zero_based).await?;
+
+    if let Some(ref log) = debug_log {
+        let total: usize = entries.iter().map(|e| e.locations.len()).sum();
+        log.log_result_summary(&format!("{total} location(s) found"));
+        let cmd = format!("stubs {}", queries.join(" "));
+        log.log_reproduction_commands(workspace_root, queries, &cmd);
+    }
+
+    let cache =
+        SourceCache::from_uris(entries.iter().flat_map(|e| &e.locations).map(|l| l.uri.as_str()))
+            .await;
+    println!("{}", formatter.format_goto(&entries, &cache));
+
+    found_any(entries.iter().any(|e| !e.locations.is_empty()))
+}
+
+/// Each of `kinds` for each query (a name or `file:line:col`), grouped by
+/// query label in query order.
+#[cfg(unix)]
+pub async fn collect_goto(
+    client: &mut DaemonClient,
+    workspace_root: &Path,
+    file: Option<&Path>,
+    queries: &[String],
+    kinds: &[GotoKind],
+    zero_based: bool,
+) -> Result<Vec<GotoEntry>> {
+    let resolved =
+        classify_and_resolve(client, queries, file, workspace_root, zero_based, &[]).await?;
+
+    let mut entries: Vec<GotoEntry> = Vec::new();
+    let mut goto_queries = Vec::new();
+    for query in resolved {
+        if !entries.iter().any(|e| e.label == query.label) {
+            entries.extend(kinds.iter().map(|&kind| GotoEntry {
+                label: query.label.clone(),
+                kind,
+                locations: Vec::new(),
+                unsupported: false,
+            }));
+        }
+        if !query.file.is_empty() {
+            goto_queries.push(BatchReferencesQuery {
+                label: query.label,
+                file: PathBuf::from(query.file),
+                line: query.line,
+                column: query.column,
+            });
+        }
+    }
+    if goto_queries.is_empty() {
+        return Ok(entries);
+    }
+
+    // A --file search can match the name several times; merge those.
+    let result =
+        client.execute_goto(workspace_root.to_path_buf(), goto_queries, kinds.to_vec()).await?;
+    for answer in result.entries {
+        if let Some(entry) =
+            entries.iter_mut().find(|e| e.label == answer.label && e.kind == answer.kind)
+        {
+            entry.locations.extend(answer.locations);
+            entry.unsupported |= answer.unsupported;
+        }
+    }
+    for entry in &mut entries {
+        dedup_locations(&mut entry.locations);
+    }
+    Ok(entries)
+}
+
+#[cfg(not(unix))]
+#[allow(clippy::too_many_arguments)]
+pub async fn handle_stubs_command(
+    _workspace_root: &Path,
+    _file: Option<&Path>,
+    _queries: &[String],
+    _kinds: &[GotoKind],
+    _formatter: &OutputFormatter,
+    _timeout: Duration,
+    _zero_based: bool,
+    _debug_log: Option<Arc<DebugLog>>,
+) -> Result<()> {
+    anyhow::bail!(
+        "The 'stubs' command requires the background daemon, which is only supported on Unix systems"
+    )
+}
+
+/// Print the hover signature and docs for symbols by name or `file:line:col`.
+///
+/// Names are resolved like `refs` does: exact workspace-symbol matches, moved
+/// onto the name itself with `find_name_column`, one entry per definition.
+/// `--file -l -c` adds that position as one more `file:line:col` query.
+#[cfg(unix)]
+#[allow(clippy::too_many_arguments)]
+pub async fn handle_hover_command(
+    workspace_root: &Path,
+    file: Option<&Path>,
+    queries: &[String],
+    position: Option<(u32, u32)>,
+    stdin_path: Option<&Path>,
+    formatter: &OutputFormatter,
+    timeout: Duration,
+    zero_based: bool,
+    debug_log: Option<Arc<DebugLog>>,
+) -> Result<()> {
+    let mut queries = queries.to_vec();
+    if let (Some(file), Some((line, col))) = (file, position) {
+        queries.insert(0, format!("{}:{line}:{col}", file.display()));
+    }
+    let queries = queries.as_slice();
+
+    let mut client = connect_daemon(timeout, debug_log.as_ref()).await?;
+    use_stdin_buffer(&mut client, stdin_path, queries)?;
+    let entries =
+        collect_hover_entries(&mut client, workspace_root, file, queries, zero_based).await?;
+
+    if let Some(ref log) = debug_log {
+        let found = entries.iter().filter(|e| e.hover.is_some()).count();
+        log.log_result_summary(&format!("hover found for {found} of {} query(ies)", entries.len()));
+        let cmd = format!("hover {}", queries.join(" "));
+        log.log_reproduction_commands(workspace_root, queries, &cmd);
+    }
+
+    let cache = SourceCache::from_uris(
+        entries.iter().filter_map(|e| e.location.as_ref()).map(|l| l.uri.as_str()),
+    )
+    .await;
+    println!("{}", formatter.format_hover_results(&entries, &cache));
+
+    found_any(entries.iter().any(|e| e.hover.is_some()))
+}
+
+/// Hover entry for each query (a name or `file:line:col`), in query order.
+#[cfg(unix)]
+pub async fn collect_hover_entries(
+    client: &mut DaemonClient,
+    workspace_root: &Path,
+    file: Option<&Path>,
+    queries: &[String],
+    zero_based: bool,
+) -> Result<Vec<HoverEntry>> {
+    let resolved =
+        classify_and_resolve(client, queries, file, workspace_root, zero_based, &[]).await?;
+
+    let mut entries: Vec<HoverEntry> = Vec::new();
+    for query in resolved {
+        // A --file search can match the name several times; the first match wins.
+        if entries.iter().any(|e| e.label == query.label) {
+            continue;
+        }
+        if query.file.is_empty() {
+            entries.push(HoverEntry { label: query.label, location: None, hover: None });
+            continue;
+        }
+        let result = client
+            .execute_hover(
+                workspace_root.to_path_buf(),
+                query.file.clone(),
+                query.line,
+                query.column,
+            )
+            .await?;
+        let position = crate::lsp::protocol::Position { line: query.line, character: query.column };
+        let location = Location {
+            uri: path_to_uri(&workspace_root.join(&query.file)),
+            range: crate::lsp::protocol::Range { start: position.clone(), end: position },
+        };
+        entries.push(HoverEntry {
+            label: query.label,
+            location: Some(location),
+            hover: result.hover,
+        });
+    }
+    Ok(entries)
+}
+
+#[cfg(not(unix))]
+#[allow(clippy::too_many_arguments)]
+pub async fn handle_hover_command(
+    _workspace_root: &Path,
+    _file: Option<&Path>,
+    _queries: &[String],
+    _position: Option<(u32, u32)>,
+    _stdin_path: Option<&Path>,
+    _formatter: &OutputFormatter,
+    _timeout: Duration,
+    _zero_based: bool,
+    _debug_log: Option<Arc<DebugLog>>,
+) -> Result<()> {
+    anyhow::bail!(
+        "The 'hover' command requires the background daemon, which is only supported on Unix systems"
+    )
+}
+
+/// Print where the symbol of each query is read and written within one file.
+///
+/// Queries resolve like `hover` does; `--file -l -c` adds that position as one
+/// more `file:line:col` query.
+#[cfg(unix)]
+#[allow(clippy::too_many_arguments)]
+pub async fn handle_occurrences_command(
+    workspace_root: &Path,
+    file: Option<&Path>,
+    queries: &[String],
+    position: Option<(u32, u32)>,
+    formatter: &OutputFormatter,
+    timeout: Duration,
+    zero_based: bool,
+    debug_log: Option<Arc<DebugLog>>,
+) -> Result<()> {
+    let mut queries = queries.to_vec();
+    if let (Some(file), Some((line, col))) = (file, position) {
+        queries.insert(0, format!("{}:{line}:{col}", file.display()));
+    }
+    let queries = queries.as_slice();
+
+    let mut client = connect_daemon(timeout, debug_log.as_ref()).await?;
+    let resolved =
+        classify_and_resolve(&mut client, queries, file, workspace_root, zero_based, &[]).await?;
+    let mut entries: Vec<OccurrencesEntry> = Vec::new();
+    for query in resolved {
+        // A --file search can match the name several times; the first match wins.
+        if entries.iter().any(|e| e.label == query.label) {
+            continue;
+        }
+        if query.file.is_empty() {
+            entries.push(OccurrencesEntry { label: query.label, uri: None, highlights: vec![] });
+            continue;
+        }
+        let result = client
+            .execute_document_highlights(
+                workspace_root.to_path_buf(),
+                query.file,
+                query.line,
+                query.column,
+            )
+            .await?;
+        entries.push(OccurrencesEntry {
+            label: query.label,
+            uri: Some(result.uri),
+            highlights: result.highlights,
+        });
+    }
+
+    if let Some(ref log) = debug_log {
+        let total: usize = entries.iter().map(|e| e.highlights.len()).sum();
+        log.log_result_summary(&format!("{total} occurrence(s) found"));
+        let cmd = format!("occurrences {}", queries.join(" "));
+        log.log_reproduction_commands(workspace_root, queries, &cmd);
+    }
+
+    let cache = SourceCache::from_uris(entries.iter().filter_map(|e| e.uri.as_deref())).await;
+    println!("{}", formatter.format_occurrences(&entries, &cache));
+
+    found_any(entries.iter().any(|e| !e.highlights.is_empty()))
+}
+
+#[cfg(not(unix))]
+#[allow(clippy::too_many_arguments)]
+pub async fn handle_occurrences_command(
+    _workspace_root: &Path,
+    _file: Option<&Path>,
+    _queries: &[String],
+    _position: Option<(u32, u32)>,
+    _formatter: &OutputFormatter,
+    _timeout: Duration,
+    _zero_based: bool,
+    _debug_log: Option<Arc<DebugLog>>,
+) -> Result<()> {
+    anyhow::bail!(
+        "The 'occurrences' command requires the background daemon, which is only supported on Unix systems"
+    )
+}
+
+#[cfg(unix)]
+#[allow(clippy::too_many_arguments, clippy::too_many_lines)]
+pub async fn handle_show_command(
+    workspace_root: &Path,
+    file: Option<&Path>,
+    within: Option<&Path>,
+    choice: MatchChoice,
+    symbols: &[String],
+    formatter: &OutputFormatter,
+    timeout: Duration,
+    show_individual_refs: bool,
+    references_limit: usize,
+    show_tests: bool,
+    show_doc: bool,
+    debug_log: Option<Arc<DebugLog>>,
+) -> Result<()> {
+    #[cfg(unix)]
+    let mut client = connect_daemon(timeout, debug_log.as_ref()).await?;
+    // Without the daemon (Windows), one in-process ty server serves every symbol.
+    #[cfg(not(unix))]
+    let client = {
+        let _ = timeout;
+        TyLspClient::new(&workspace_root.to_string_lossy()).await?
+    };
+
+    // Always fetch references for the count summary
+    #[cfg(unix)]
+    let results = {
+        let within = within
+            .map(|path| {
+                crate::lsp::uri::canonicalize(path)
+                    .with_context(|| format!("Path not found: {}", path.display()))
+            })
+            .transpose()?;
+        let scope = MatchScope { file, within: within.as_deref(), choice };
+        inspect_symbols(&mut client, workspace_root, scope, symbols, true).await?
+    };
+    #[cfg(not(unix))]
+    let results = {
+        let _ = (within, choice);
+        let mut results: Vec<InspectResult> = Vec::new();
+        for symbol in symbols {
+            results.push(inspect_symbol_direct(&client, file, symbol).await?);
+        }
+        results
+    };
+
+    if let Some(ref log) = debug_log {
+        for r in &results {
+            let has_hover = if r.hover.is_some() { "yes" } else { "no" };
+            log.log_result_summary(&format!(
+                "show '{}': {} definition(s), hover={has_hover}, {} reference(s)",
+                r.symbol,
+                r.definitions.len(),
+                r.references.len(),
+            ));
+        }
+        let cmd = format!("show {}", symbols.join(" "));
+        log.log_reproduction_commands(workspace_root, symbols, &cmd);
+    }
+
+    // Build enriched entries
+    let mut entries: Vec<ShowEntry<'_>> = Vec::new();
+    let needs_enrichment = show_individual_refs && results.iter().any(|r| !r.references.is_empty());
+    #[cfg(unix)]
+    let mut enrich_source = needs_enrichment.then_some(SymbolSource::Daemon(&mut client));
+    #[cfg(not(unix))]
+    let mut enrich_source = needs_enrichment.then_some(SymbolSource::Direct(&client));
+    for r in &results {
+        // Partition into non-test and test references
+        let (non_test_refs, test_refs) = partition_test_locations(r.references.clone());
+
+        let total_reference_count = non_test_refs.len();
+        let total_reference_files = count_unique_files(&non_test_refs);
+
+        let (displayed_references, remaining_reference_count) =
+            if show_individual_refs && !non_test_refs.is_empty() {
+                let display_count = if references_limit == 0 {
+                    non_test_refs.len()
+                } else {
+                    references_limit.min(non_test_refs.len())
+                };
+                let to_display = &non_test_refs[..display_count];
+                let remaining = non_test_refs.len() - display_count;
+
+                let enriched = enrich_references(
+                    to_display,
+                    workspace_root,
+                    enrich_source.as_mut().expect("source created above"),
+                )
+                .await;
+                (enriched, remaining)
+            } else {
+                (Vec::new(), 0)
+            };
+
+        // Build test references section
+        let test_references = if test_refs.is_empty() {
+            None
+        } else if show_tests {
+            let test_total = test_refs.len();
+            let (test_displayed, test_remaining) = if show_individual_refs && !test_refs.is_empty()
+            {
+                let test_display_count = if references_limit == 0 {
+                    test_total
+                } else {
+                    references_limit.min(test_total)
+                };
+                let test_to_display = &test_refs[..test_display_count];
+                let remaining = test_total - test_display_count;
+                let enriched = enrich_references(
+                    test_to_display,
+                    workspace_root,
+                    enrich_source.as_mut().expect("source created above"),
+                )
+                .await;
+                (enriched, remaining)
+            } else {
+                (Vec::new(), 0)
+            };
+            Some(crate::cli::output::TestReferencesSection {
+                total_count: test_total,
+                displayed: test_displayed,
+                remaining_count: test_remaining,
+            })
+        } else {
+            // Not showing tests, but record count for hint
+            Some(crate::cli::output::TestReferencesSection {
+                total_count: test_refs.len(),
+                displayed: Vec::new(),
+                remaining_count: 0,
+            })
+        };
+
+        entries.push(ShowEntry {
+            symbol: r.symbol.as_str(),
+            kind: r.kind.as_ref(),
+            definitions: r.definitions.as_slice(),
+            hover: r.hover.as_ref(),
+            total_reference_count,
+            total_reference_files,
+            displayed_references,
+            remaining_reference_count,
+            show_individual_refs,
+            show_doc,
+            test_references,
+        });
+    }
+
+    let cache = SourceCache::from_uris(entries.iter().flat_map(|e| {
+        let defs = e.definitions.iter().map(|l| l.uri.as_str());
+        let refs = e.displayed_references.iter().map(|r| r.location.uri.as_str());
+        let test = e
+            .test_references
+            .iter()
+            .flat_map(|t| t.displayed.iter().map(|r| r.location.uri.as_str()));
+        defs.chain(refs).chain(test)
+    }))
+    .await;
+    println!("{}", formatter.format_show_results(&entries, &cache));
+
+    found_any(entries.iter().any(|e| !e.definitions.is_empty() || e.hover.is_some()))
+}
+
+struct InspectResult {
+    symbol: String,
+    kind: Option<crate::lsp::protocol::SymbolKind>,
+    definitions: Vec<Location>,
+    hover: Option<crate::lsp::protocol::Hover>,
+    references: Vec<Location>,
+}
+
+/// Which of a name's workspace symbols `show` inspects when it matches
+/// several, e.g. a `Config` class in two packages.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MatchChoice {
+    /// Ask on the terminal; without one, take the first and say so.
+    Ask,
+    /// The match with this 1-based number in the list `Ask` shows.
+    Select(usize),
+    /// Every match, each shown on its own.
+    All,
+}
+
+/// Where `show` looks for a name and which matches it keeps.
+#[cfg(unix)]
+#[derive(Clone, Copy)]
+struct MatchScope<'a> {
+    /// Search this file's occurrences instead of the workspace symbols (`--file`).
+    file: Option<&'a Path>,
+    /// Drop matches defined outside this canonical file or directory (`--in`).
+    within: Option<&'a Path>,
+    choice: MatchChoice,
+}
+
+/// Split a name's matches into the groups to inspect, one result each.
+///
+/// Occurrences from a `--file` search, and a single workspace symbol, stay
+/// one group. Several workspace symbols are narrowed by `choice`; with
+/// [`MatchChoice::All`] each gets its own group, labelled by where it's
+/// defined.
+#[cfg(unix)]
+fn choose_matches(
+    symbol: &str,
+    matches: Vec<SymbolMatch>,
+    choice: MatchChoice,
+    workspace_root: &Path,
+) -> Result<Vec<(String, Vec<SymbolMatch>)>> {
+    if matches.iter().filter(|m| m.symbol.is_some()).count() <= 1 {
+        return Ok(vec![(symbol.to_string(), matches)]);
+    }
+    let choice = match choice {
+        MatchChoice::Ask => ask_for_match(symbol, &matches, workspace_root),
+        choice => choice,
+    };
+    match choice {
+        MatchChoice::All => Ok(matches
+            .into_iter()
+            .map(|m| (definition_label(symbol, &m.file, m.line, workspace_root), vec![m]))
+            .collect()),
+        MatchChoice::Select(n) => {
+            let count = matches.len();
+            let chosen = matches.into_iter().nth(n.wrapping_sub(1)).ok_or_else(|| {
+                UsageError(format!(
+                    "--select {n} is out of range: '{symbol}' matches {count} symbols"
+                ))
+            })?;
+            Ok(vec![(symbol.to_string(), vec![chosen])])
+        }
+        MatchChoice::Ask => unreachable!("ask_for_match never answers Ask"),
+    }
+}
+
+/// A numbered list of `matches` on stderr, and the one picked on stdin.
+///
+/// Without a terminal on both, e.g. in a script or under an AI agent, the
+/// first match is taken and a note says how to pick another.
+#[cfg(unix)]
+fn ask_for_match(symbol: &str, matches: &[SymbolMatch], workspace_root: &Path) -> MatchChoice {
+    let listing = || {
+        matches.iter().enumerate().map(|(i, m)| {
+            let kind = m.symbol.as_ref().map(|s| format!(" ({:?})", s.kind)).unwrap_or_default();
+            format!(
+                "  {}. {}{kind}",
+                i + 1,
+                definition_label(symbol, &m.file, m.line, workspace_root)
+            )
+        })
+    };
+    if !std::io::stdin().is_terminal() || !std::io::stderr().is_terminal() {
+        eprintln!(
+            "'{symbol}' matches {} symbols; showing the first. Pick another with --select N, \
+             --in PATH or --all-matches:",
+            matches.len()
+        );
+        for line in listing() {
+            eprintln!("{line}");
+        }
+        return MatchChoice::Select(1);
+    }
+    eprintln!("'{symbol}' matches {} symbols:", matches.len());
+    for line in listing() {
+        eprintln!("{line}");
+    }
+    loop {
+        eprint!("Which one? [1-{}, a for all] (1) ", matches.len());
+        let _ = std::io::Write::flush(&mut std::io::stderr());
+        let mut answer = String::new();
+        match std::io::stdin().read_line(&mut answer) {
+            Ok(0) | Err(_) => return MatchChoice::Select(1),
+            Ok(_) => {}
+        }
+        if let Some(choice) = parse_match_choice(&answer, matches.len()) {
+            return choice;
+        }
+    }
+}
+
+/// An answer to [`ask_for_match`]: a number from 1 to `count`, `a` or `all`
+/// for every match, or nothing for the first; `None` for anything else.
+#[cfg(unix)]
+fn parse_match_choice(answer: &str, count: usize) -> Option<MatchChoice> {
+    match answer.trim().to_ascii_lowercase().as_str() {
+        "" => Some(MatchChoice::Select(1)),
+        "a" | "all" => Some(MatchChoice::All),
+        n => n.parse().ok().filter(|n| (1..=count).contains(n)).map(MatchChoice::Select),
+    }
+}
+
+/// Inspect every symbol: resolve each to its definitions, then hover and
+/// find references at all of them in one `inspect_many` call, which the
+/// daemon runs several at once.
+#[cfg(unix)]
+async fn inspect_symbols(
+    client: &mut DaemonClient,
+    workspace_root: &Path,
+    scope: MatchScope<'_>,
+    symbols: &[String],
+    include_references: bool,
+) -> Result<Vec<InspectResult>> {
+    let mut groups = Vec::with_capacity(symbols.len());
+    for symbol in symbols {
+        let mut matches = resolve_symbol(client, workspace_root, scope.file, symbol, &[]).await?;
+        if let Some(within) = scope.within {
+            matches.retain(|m| Path::new(&m.file).starts_with(within));
+        }
+        groups.extend(choose_matches(symbol, matches, scope.choice, workspace_root)?);
+    }
+
+    let mut results = Vec::with_capacity(groups.len());
+    let mut queries = Vec::new();
+    for (symbol, matches) in groups {
+        let mut result = InspectResult {
+            symbol,
+            kind: None,
+            definitions: Vec::new(),
+            hover: None,
+            references: Vec::new(),
+        };
+        if let Some(first) = matches.first() {
+            tracing::debug!(
+                "inspect: querying hover/refs at {}:{}:{} for '{}'",
+                first.file,
+                first.line,
+                first.column,
+                result.symbol
+            );
+            result.kind = first.symbol.as_ref().map(|s| s.kind.clone());
+            // Labelled by index: the same name may be asked for twice.
+            queries.push(BatchReferencesQuery {
+                label: results.len().to_string(),
+                file: PathBuf::from(&first.file),
+                line: first.line,
+                column: first.column,
+            });
+            result.definitions = match_definitions(client, workspace_root, &matches).await?;
+        }
+        results.push(result);
+    }
+    if queries.is_empty() {
+        return Ok(results);
+    }
+
+    let answers = client
+        .execute_inspect_many(workspace_root.to_path_buf(), queries, include_references)
+        .await?;
+    for answer in answers.entries {
+        let Some(result) = answer.label.parse::<usize>().ok().and_then(|idx| results.get_mut(idx))
+        else {
+            continue;
+        };
+        tracing::debug!(
+            "inspect '{}': hover={}, refs={}",
+            result.symbol,
+            if answer.hover.is_some() { "present" } else { "NONE" },
+            answer.references.len()
+        );
+        result.hover = answer.hover;
+        result.references = answer.references;
+    }
+    Ok(results)
+}
+
+/// Where the matches of a name are defined.
+///
+/// A workspace symbol is its own definition; occurrences found by a --file
+/// search are looked up in one batch.
+#[cfg(unix)]
+async fn match_definitions(
+    client: &mut DaemonClient,
+    workspace_root: &Path,
+    matches: &[SymbolMatch],
+) -> Result<Vec<Location>> {
+    let mut definitions = Vec::new();
+    let mut occurrences = Vec::new();
+    for m in matches {
+        match &m.symbol {
+            Some(sym_info) => definitions.push(sym_info.location.clone()),
+            None => occurrences.push(BatchReferencesQuery {
+                label: String::new(),
+                file: PathBuf::from(&m.file),
+                line: m.line,
+                column: m.column,
+            }),
+        }
+    }
+    if !occurrences.is_empty() {
+        let result = client
+            .execute_batch_definitions(
+                workspace_root.to_path_buf(),
+                occurrences,
+                DEFAULT_DEFINITIONS_CONCURRENCY,
+            )
+            .await?;
+        definitions.extend(result.entries.into_iter().flat_map(|e| e.locations));
+    }
+    dedup_locations(&mut definitions);
+    Ok(definitions)
+}
+
+/// In-process counterpart of [`inspect_symbols`] for one symbol, used without the daemon.
+#[cfg_attr(unix, allow(dead_code))]
+async fn inspect_symbol_direct(
+    client: &TyLspClient,
+    file: Option<&Path>,
+    symbol: &str,
+) -> Result<InspectResult> {
+    let not_found = || InspectResult {
+        symbol: symbol.to_string(),
+        kind: None,
+        definitions: Vec::new(),
+        hover: None,
+        references: Vec::new(),
+    };
+
+    let (definition_file, def_line, def_col, definitions, kind) = if let Some(file) = file {
+        let file_str = file.to_string_lossy().to_string();
+        let finder = SymbolFinder::new(&file_str).await?;
+        let positions = finder.find_symbol_positions(symbol);
+        let Some((first_line, first_col)) = finder.preferred_position(symbol) else {
+            return Ok(not_found());
+        };
+        client.open_document(&file_str).await?;
+        let mut definitions = Vec::new();
+        for (line, column) in positions {
+            definitions.extend(client.goto_definition(&file_str, line, column).await?);
+        }
+        dedup_locations(&mut definitions);
+        (file_str, first_line, first_col, definitions, None)
+    } else {
+        let matched = workspace_symbols_exact_direct(client, symbol).await?;
+        let Some(first) = matched.first() else {
+            return Ok(not_found());
+        };
+        let file_path = uri_to_path(&first.location.uri);
+        let start = &first.location.range.start;
+        let sources = SourceCache::from_uris([first.location.uri.as_str()]).await;
+        let (def_line, def_col) = find_name_column(&sources, &file_path, start.line, &first.name)
+            .unwrap_or((start.line, start.character));
+        let kind = Some(first.kind.clone());
+        let definitions = matched.iter().map(|s| s.location.clone()).collect();
+        (file_path, def_line, def_col, definitions, kind)
+    };
+
+    client.open_document(&definition_file).await?;
+    let mut hover = client.hover(&definition_file, def_line, def_col).await?;
+    for delay_ms in DIRECT_WARMUP_DELAYS_MS {
+        if hover.is_some() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(*delay_ms)).await;
+        hover = client.hover(&definition_file, def_line, def_col).await?;
+    }
+    let references = client.find_references(&definition_file, def_line, def_col, false).await?;
+
+    Ok(InspectResult { symbol: symbol.to_string(), kind, definitions, hover, references })
+}
+
+/// Names from builtins and `typing` that `explain-type` never tries to expand.
+const NON_EXPANDABLE_TYPE_NAMES: &[&str] = &[
+    "Annotated",
+    "Any",
+    "AsyncGenerator",
+    "AsyncIterator",
+    "Awaitable",
+    "Callable",
+    "ClassVar",
+    "Concatenate",
+    "Coroutine",
+    "Dict",
+    "False",
+    "Final",
+    "FrozenSet",
+    "Generator",
+    "Generic",
+    "Iterable",
+    "Iterator",
+    "List",
+    "Literal",
+    "LiteralString",
+    "Mapping",
+    "MutableMapping",
+    "MutableSequence",
+    "NamedTuple",
+    "Never",
+    "NoReturn",
+    "None",
+    "NotRequired",
+    "Optional",
+    "ParamSpec",
+    "Protocol",
+    "ReadOnly",
+    "Required",
+    "Self",
+    "Sequence",
+    "Set",
+    "True",
+    "Tuple",
+    "Type",
+    "TypeAlias",
+    "TypeGuard",
+    "TypeIs",
+    "TypeVar",
+    "TypedDict",
+    "Union",
+    "Unknown",
+    "Unpack",
+];
+
+/// Upper bound on the number of types a single `explain-type` call expands.
+const EXPLAIN_TYPE_MAX_EXPANSIONS: usize = 20;
+
+/// Upper bound on the classes `where` searches for one attribute (the class and its bases).
+const WHERE_MAX_CLASSES: usize = 20;
+
+/// Extract candidate user-defined type names from a signature or definition.
+///
+/// Keeps capitalized identifiers (the Python convention for classes and type
+/// aliases), skipping builtins/`typing` names and single-letter type variables.
+/// Dotted references (`models.Order`) yield their last segment.
+fn extract_type_names(text: &str) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+    let mut current = String::new();
+    for ch in text.chars().chain(std::iter::once(' ')) {
+        if is_identifier_char(ch) {
+            current.push(ch);
+            continue;
+        }
+        if ch == '.' {
+            current.clear();
+            continue;
+        }
+        let word = std::mem::take(&mut current);
+        let starts_upper = word.chars().next().is_some_and(char::is_uppercase);
+        if starts_upper
+            && word.chars().count() > 1
+            && !NON_EXPANDABLE_TYPE_NAMES.contains(&word.as_str())
+            && !names.contains(&word)
+        {
+            names.push(word);
+        }
+    }
+    names
+}
+
+/// Describe a type definition for `explain-type` from its symbol kind and source line.
+fn classify_type_definition(kind: &SymbolKind, definition_line: &str) -> Option<&'static str> {
+    match kind {
+        SymbolKind::Class | SymbolKind::Interface | SymbolKind::Struct | SymbolKind::Enum => {
+            let bases = definition_line
+                .split_once('(')
+                .map_or("", |(_, rest)| rest.split(')').next().unwrap_or(""));
+            let has_base = |name: &str| bases.split(',').any(|b| b.trim().ends_with(name));
+            Some(if has_base("TypedDict") {
+                "TypedDict"
+            } else if has_base("Protocol") {
+                "Protocol"
+            } else if has_base("NamedTuple") {
+                "NamedTuple"
+            } else if has_base("Enum") {
+                "enum"
+            } else {
+                "class"
+            })
+        }
+        SymbolKind::Variable | SymbolKind::Constant | SymbolKind::TypeParameter => {
+            Some(if definition_line.contains("NewType(") {
+                "NewType"
+            } else if definition_line.contains("TypeVar(") {
+                "type variable"
+            } else {
+                "type alias"
+            })
+        }
+        _ => None,
+    }
+}
+
+/// Resolve a `file:line:col` position or symbol name to a 0-based LSP position.
+///
+/// Symbol names go through [`resolve_symbol`] and use the first match.
+/// Returns `None` when nothing matches.
+#[cfg(unix)]
+async fn resolve_query_position(
+    client: &mut DaemonClient,
+    workspace_root: &Path,
+    file: Option<&Path>,
+    query: &str,
+    zero_based: bool,
+) -> Result<Option<(String, u32, u32)>> {
+    if let Some((f, l, c)) = parse_file_position(query) {
+        let (line, column) = user_position_to_lsp(workspace_root, &f, l, c, zero_based).await;
+        return Ok(Some((f, line, column)));
+    }
+
+    let matches = resolve_symbol(client, workspace_root, file, query, &[]).await?;
+    Ok(matches.into_iter().next().map(|m| (m.file, m.line, m.column)))
+}
+
+/// The lines of a file `explain-type` looks for referenced type names in:
+/// the queried symbol's header, or an expanded type's whole definition.
+#[cfg(unix)]
+#[derive(Clone)]
+struct TypeNameSource {
+    file: String,
+    lines: (u32, u32),
+}
+
+/// The last line of an outline symbol's range.
+#[cfg(unix)]
+fn symbol_last_line(symbol: &DocumentSymbol) -> u32 {
+    // A range ending at the start of a line doesn't take in that line.
+    let end = &symbol.range.end;
+    if end.character == 0 && end.line > symbol.range.start.line {
+        end.line - 1
+    } else {
+        end.line
+    }
+}
+
+/// Where `name` first occurs as a whole word in `lines` of `file`, as a
+/// 0-based LSP position.
+#[cfg(unix)]
+fn find_word_in_lines(
+    file: &SourceFile,
+    name: &str,
+    (first, last): (u32, u32),
+) -> Option<(u32, u32)> {
+    file.lines_from(first as usize).take((last.saturating_sub(first) + 1) as usize).find_map(
+        |(idx, text)| {
+            let byte = find_words(text, name).next()?;
+            let column =
+                convert_column(text, u32::try_from(byte).ok()?, ColumnUnit::Utf8, LSP_UNIT);
+            Some((u32::try_from(idx).ok()?, column))
+        },
+    )
+}
+
+/// The source of an expanded type: a class's header up to its colon, or
+/// the whole statement of anything else, such as a `Union[...]` alias
+/// spread over several lines. Lines keep their indentation relative to
+/// the first one.
+#[cfg(unix)]
+fn type_definition_text(file: &SourceFile, symbol: &DocumentSymbol) -> String {
+    let first = symbol.range.start.line as usize;
+    let last = symbol_last_line(symbol) as usize;
+    let mut lines: Vec<&str> = Vec::new();
+    for (_, line) in file.lines_from(first).take(last + 1 - first) {
+        lines.push(line);
+        let header_done = line.trim_end().ends_with(':');
+        if matches!(symbol.kind, SymbolKind::Class) && header_done {
+            break;
+        }
+    }
+    let indent = lines.first().map_or(0, |line| line.len() - line.trim_start().len());
+    lines
+        .iter()
+        .map(|line| line.get(indent..).filter(|_| line[..indent].trim().is_empty()).unwrap_or(line))
+        .map(str::trim_end)
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Look up a type referenced in `source` and describe its definition.
+///
+/// The name is resolved with ty's definition at the place `source` writes
+/// it, so the type the code means is found even when several modules
+/// define one by that name. A name `source` doesn't spell out (a type
+/// only hover shows) falls back to the workspace symbol by that name,
+/// when there is exactly one.
+///
+/// Returns `None` for names that are not defined in the workspace (e.g.
+/// third-party types) or that resolve to something other than a type.
+#[cfg(unix)]
+async fn expand_type_name(
+    client: &mut DaemonClient,
+    workspace_root: &Path,
+    name: &str,
+    source: &TypeNameSource,
+    depth: usize,
+) -> Result<Option<(TypeExpansion, TypeNameSource)>> {
+    let source_uri = path_to_uri(Path::new(&source.file));
+    let sources = SourceCache::from_uris([source_uri.as_str()]).await;
+    let written = sources.get(&source.file).and_then(|f| find_word_in_lines(f, name, source.lines));
+    let root = workspace_root.to_path_buf();
+    let location = if let Some((line, column)) = written {
+        client.execute_definition(root, source.file.clone(), line, column).await?.location
+    } else {
+        let result =
+            client.execute_workspace_symbols_exact(root, name.to_string(), Vec::new()).await?;
+        match <[_; 1]>::try_from(result.symbols) {
+            Ok([sym]) => Some(sym.location),
+            Err(_) => None,
+        }
+    };
+    let Some(location) = location else {
+        return Ok(None);
+    };
+    if Origin::classify(&location.uri, workspace_root) != Origin::Workspace {
+        return Ok(None);
+    }
+
+    let file_path = uri_to_path(&location.uri);
+    let sources = SourceCache::from_uris([location.uri.as_str()]).await;
+    let Some(file) = sources.get(&file_path) else {
+        return Ok(None);
+    };
+    // A workspace symbol's range starts at the `class` keyword or a decorator.
+    let start = &location.range.start;
+    let (line, column) = find_name_column(&sources, &file_path, start.line, name)
+        .unwrap_or((start.line, start.character));
+    let outline = client
+        .execute_document_symbols(workspace_root.to_path_buf(), file_path.clone())
+        .await?
+        .symbols;
+    let Some(symbol) = find_defined_symbol(&outline, line, column) else {
+        return Ok(None);
+    };
+    let definition = type_definition_text(file, symbol);
+    let Some(kind) = classify_type_definition(&symbol.kind, &definition) else {
+        return Ok(None);
+    };
+
+    let members = if matches!(symbol.kind, SymbolKind::Class) {
+        client
+            .execute_members(
+                workspace_root.to_path_buf(),
+                file_path.clone(),
+                name.to_string(),
+                false,
+            )
+            .await?
+            .members
+            .into_iter()
+            .map(|m| m.signature.unwrap_or(m.name))
+            .collect()
+    } else {
+        Vec::new()
+    };
+
+    let lines = (symbol.range.start.line, symbol_last_line(symbol));
+    let expansion =
+        TypeExpansion { name: name.to_string(), kind, location, depth, definition, members };
+    Ok(Some((expansion, TypeNameSource { file: file_path, lines })))
+}
+
+/// The header of the symbol `explain-type` was asked about, where the
+/// types its signature names are written.
+#[cfg(unix)]
+async fn query_type_source(
+    client: &mut DaemonClient,
+    workspace_root: &Path,
+    file: String,
+    line: u32,
+    column: u32,
+) -> Result<TypeNameSource> {
+    let outline =
+        client.execute_document_symbols(workspace_root.to_path_buf(), file.clone()).await?.symbols;
+    let lines = find_defined_symbol(&outline, line, column)
+        .map_or((line, line), |symbol| (symbol.range.start.line, symbol_last_line(symbol)));
+    Ok(TypeNameSource { file, lines })
+}
+
+#[cfg(unix)]
+#[allow(clippy::too_many_arguments)]
+pub async fn handle_explain_type_command(
+    workspace_root: &Path,
+    file: Option<&Path>,
+    query: &str,
+    max_depth: usize,
+    formatter: &OutputFormatter,
+    timeout: Duration,
+    zero_based: bool,
+    debug_log: Option<Arc<DebugLog>>,
+) -> Result<()> {
+    let mut client = connect_daemon(timeout, debug_log.as_ref()).await?;
+
+    let mut signature = None;
+    let mut queue: std::collections::VecDeque<(String, usize, TypeNameSource)> =
+        std::collections::VecDeque::new();
+    if let Some((target_file, line, column)) =
+        resolve_query_position(&mut client, workspace_root, file, query, zero_based).await?
+    {
+        let root = workspace_root.to_path_buf();
+        signature = client
+            .execute_hover(root, target_file.clone(), line, column)
+            .await?
+            .hover
+            .map(|h| OutputFormatter::extract_hover_type(&h.contents));
+        if let Some(signature) = &signature {
+            let source =
+                query_type_source(&mut client, workspace_root, target_file, line, column).await?;
+            queue.extend(
+                extract_type_names(signature).into_iter().map(|name| (name, 1, source.clone())),
+            );
+        }
+    }
+
+    // Breadth-first expansion of referenced types, bounded by depth and count.
+    let mut expansions: Vec<TypeExpansion> = Vec::new();
+    let own_name = parse_dotted_symbol(query).map_or(query, |(_, member)| member);
+    let mut seen: HashSet<String> = HashSet::from([own_name.to_string()]);
+    while let Some((name, depth, source)) = queue.pop_front() {
+        if expansions.len() >= EXPLAIN_TYPE_MAX_EXPANSIONS {
+            break;
+        }
+        if depth > max_depth || !seen.insert(name.clone()) {
+            continue;
+        }
+        if let Some((expansion, source)) =
+            expand_type_name(&mut client, workspace_root, &name, &source, depth).await?
+        {
+            let mut referenced = extract_type_names(&expansion.definition);
+            for member in &expansion.members {
+                referenced.extend(extract_type_names(member));
+            }
+            queue.extend(referenced.into_iter().map(|n| (n, depth + 1, source.clone())));
+            expansions.push(expansion);
+        }
+    }
+
+    if let Some(ref log) = debug_log {
+        log.log_result_summary(&format!(
+            "explain-type '{query}': {} type(s) expanded",
+            expansions.len()
+        ));
+        let cmd = format!("explain-type {query}");
+        log.log_reproduction_commands(workspace_root, &[query.to_string()], &cmd);
+    }
+
+    let result = ExplainTypeResult { query: query.to_string(), signature, expansions };
+    let cache =
+        SourceCache::from_uris(result.expansions.iter().map(|e| e.location.uri.as_str())).await;
+    println!("{}", formatter.format_explain_type(&result, &cache));
+    Ok(())
+}
+
+#[cfg(not(unix))]
+#[allow(clippy::too_many_arguments)]
+pub async fn handle_explain_type_command(
+    _workspace_root: &Path,
+    _file: Option<&Path>,
+    _query: &str,
+    _max_depth: usize,
+    _formatter: &OutputFormatter,
+    _timeout: Duration,
+    _zero_based: bool,
+    _debug_log: Option<Arc<DebugLog>>,
+) -> Result<()> {
+    anyhow::bail!(
+        "The 'explain-type' command requires the background daemon, which is only supported on Unix systems"
+    )
+}
+
+/// Split a `file:line` location, as `tyf explain` takes it.
+pub fn parse_file_line(input: &str) -> Option<(String, u32)> {
+    let (file, line) = input.rsplit_once(':')?;
+    let line = line.parse().ok()?;
+    (!file.is_empty()).then(|| (file.to_string(), line))
+}
+
+/// Explain one line: the type and definitions of every identifier on it,
+/// and the diagnostics touching it.
+///
+/// The identifiers are found with tree-sitter, then everything is asked of
+/// ty in a single `explain_line` call.
+#[cfg(unix)]
+pub async fn handle_explain_command(
+    workspace_root: &Path,
+    location: &str,
+    formatter: &OutputFormatter,
+    timeout: Duration,
+    zero_based: bool,
+    debug_log: Option<Arc<DebugLog>>,
+) -> Result<()> {
+    use crate::cli::output::LineExplanation;
+    use crate::daemon::protocol::LineIdentifier;
+
+    let Some((file, line)) = parse_file_line(location) else {
+        return Err(UsageError(format!(
+            "Expected a file:line location, like src/orders.py:42 (got '{location}')"
+        ))
+        .into());
+    };
+    let path = crate::lsp::uri::canonicalize(&workspace_root.join(&file))
+        .with_context(|| format!("File not found: {file}"))?;
+    let finder = SymbolFinder::new(&path.to_string_lossy()).await?;
+    let lsp_line = input_to_lsp(line, zero_based);
+    if finder.get_line(lsp_line).is_none() {
+        anyhow::bail!("{file} has no line {line}");
+    }
+    let identifiers: Vec<LineIdentifier> = finder
+        .line_identifiers(lsp_line)
+        .into_iter()
+        .map(|(name, column)| LineIdentifier { name, column })
+        .collect();
+
+    let mut client = connect_daemon(timeout, debug_log.as_ref()).await?;
+    let result = client
+        .execute_explain_line(workspace_root.to_path_buf(), path.clone(), lsp_line, identifiers)
+        .await?;
+
+    if let Some(ref log) = debug_log {
+        log.log_result_summary(&format!(
+            "{} name(s), {} diagnostic(s)",
+            result.identifiers.len(),
+            result.diagnostics.len()
+        ));
+    }
+
+    let explanation = LineExplanation {
+        uri: path_to_uri(&path),
+        line: lsp_line,
+        identifiers: result.identifiers,
+        diagnostics: result.diagnostics,
+    };
+    let definitions = explanation.identifiers.iter().flat_map(|i| &i.definitions);
+    let cache = SourceCache::from_uris(
+        std::iter::once(explanation.uri.as_str()).chain(definitions.map(|l| l.uri.as_str())),
+    )
+    .await;
+    println!("{}", formatter.format_line_explanation(&explanation, &cache));
+
+    found_any(!explanation.identifiers.is_empty() || !explanation.diagnostics.is_empty())
+}
+
+#[cfg(not(unix))]
+pub async fn handle_explain_command(
+    _workspace_root: &Path,
+    _location: &str,
+    _formatter: &OutputFormatter,
+    _timeout: Duration,
+    _zero_based: bool,
+    _debug_log: Option<Arc<DebugLog>>,
+) -> Result<()> {
+    anyhow::bail!(
+        "The 'explain' command requires the background daemon, which is only supported on Unix systems"
+    )
+}
+
+/// Resolve an attribute chain like `order.customer.address.city` hop by hop.
+///
+/// The first name is found in `file` and typed by hover; every following
+/// attribute is looked up on the class of the previous hop's type with
+/// [`find_member`], and hover on its definition gives the next type.
+#[cfg(unix)]
+#[allow(clippy::too_many_arguments)]
+pub async fn handle_where_command(
+    workspace_root: &Path,
+    file: &Path,
+    line: Option<u32>,
+    expression: &str,
+    formatter: &OutputFormatter,
+    timeout: Duration,
+    zero_based: bool,
+    debug_log: Option<Arc<DebugLog>>,
+) -> Result<()> {
+    use crate::chain::{find_root, parse_chain, receiver_class};
+    use crate::cli::output::{WhereHop, WhereResult};
+    use crate::lsp::position::byte_to_column;
+    use crate::lsp::protocol::{Position, Range};
+
+    let segments = parse_chain(expression)?;
+    let path = crate::lsp::uri::canonicalize(&workspace_root.join(file))
+        .with_context(|| format!("File not found: {}", file.display()))?;
+    let text = tokio::fs::read_to_string(&path)
+        .await
+        .with_context(|| format!("Failed to read file: {}", path.display()))?;
+    let file_str = path.to_string_lossy().to_string();
+
+    let near = line.map(|l| input_to_lsp(l, zero_based) as usize);
+    let root = segments[0];
+    let Some((root_line, root_byte)) = find_root(&text, root, near) else {
+        anyhow::bail!("'{root}' does not appear in {}", file.display());
+    };
+    let root_character =
+        byte_to_column(text.lines().nth(root_line).unwrap_or_default(), root_byte, LSP_UNIT);
+    let root_position = Position { line: u32::try_from(root_line)?, character: root_character };
+
+    let mut client = connect_daemon(timeout, debug_log.as_ref()).await?;
+    let ty = hover_type(&mut client, workspace_root, file_str.clone(), &root_position).await?;
+    let definition = client
+        .execute_definition(
+            workspace_root.to_path_buf(),
+            file_str,
+            root_position.line,
+            root_position.character,
+        )
+        .await?
+        .location;
+    let occurrence = Location {
+        uri: path_to_uri(&path),
+        range: Range { start: root_position.clone(), end: root_position },
+    };
+    let mut hops = vec![WhereHop {
+        name: root.to_string(),
+        owner: None,
+        location: Some(definition.unwrap_or(occurrence)),
+        ty,
+    }];
+
+    let mut unresolved = None;
+    for attr in &segments[1..] {
+        let Some(previous) = hops.last() else { break };
+        let Some(class_name) = previous.ty.as_deref().and_then(receiver_class) else {
+            unresolved = Some(previous.ty.as_ref().map_or_else(
+                || format!("Stopped at .{attr}: ty has no type for {}", previous.name),
+                |ty| format!("Stopped at .{attr}: {} is `{ty}`, not a single class", previous.name),
+            ));
+            break;
+        };
+        match find_member(&mut client, workspace_root, &class_name, attr).await? {
+            MemberLookup::UnknownClass => {
+                unresolved = Some(format!(
+                    "Stopped at .{attr}: `{class_name}` is not a class defined in the workspace"
+                ));
+                break;
+            }
+            MemberLookup::Missing => {
+                unresolved = Some(format!(
+                    "Stopped at .{attr}: `{class_name}` and its bases don't define `{attr}`"
+                ));
+                break;
+            }
+            MemberLookup::Found { owner, location } => {
+                let file = uri_to_path(&location.uri);
+                let ty =
+                    hover_type(&mut client, workspace_root, file, &location.range.start).await?;
+                hops.push(WhereHop {
+                    name: (*attr).to_string(),
+                    owner: Some(owner),
+                    location: Some(location),
+                    ty,
+                });
+            }
+        }
+    }
+
+    if let Some(ref log) = debug_log {
+        log.log_result_summary(&format!(
+            "where '{expression}': {} of {} hop(s) resolved",
+            hops.len(),
+            segments.len()
+        ));
+    }
+
+    let result = WhereResult { expression: expression.to_string(), hops, unresolved };
+    let cache = SourceCache::from_uris(
+        result.hops.iter().filter_map(|hop| hop.location.as_ref()).map(|l| l.uri.as_str()),
+    )
+    .await;
+    println!("{}", formatter.format_where(&result, &cache));
+    Ok(())
+}
+
+/// The type at `position` from hover, without code fences or docs.
+#[cfg(unix)]
+async fn hover_type(
+    client: &mut DaemonClient,
+    workspace_root: &Path,
+    file: String,
+    position: &crate::lsp::protocol::Position,
+) -> Result<Option<String>> {
+    let result = client
+        .execute_hover(workspace_root.to_path_buf(), file, position.line, position.character)
+        .await?;
+    Ok(result.hover.map(|h| OutputFormatter::extract_hover_type(&h.contents)))
+}
+
+/// Outcome of looking up an attribute on a class for `where`.
+#[cfg(unix)]
+enum MemberLookup {
+    /// The class isn't defined in the workspace (a builtin, a third-party type).
+    UnknownClass,
+    /// Neither the class nor its bases define the attribute.
+    Missing,
+    /// Where the attribute is defined, and the class defining it.
+    Found { owner: String, location: Location },
+}
+
+/// Find where `attr` is defined for instances of `class_name`.
+///
+/// The class is searched before its bases, breadth-first, as in Python's
+/// attribute lookup for single inheritance. A class defines `attr` if its
+/// outline has a member of that name or its body assigns `self.attr`.
+#[cfg(unix)]
+async fn find_member(
+    client: &mut DaemonClient,
+    workspace_root: &Path,
+    class_name: &str,
+    attr: &str,
+) -> Result<MemberLookup> {
+    use crate::chain::{class_bases, find_self_assignment};
+    use crate::lsp::position::byte_to_column;
+    use crate::lsp::protocol::{Position, Range};
+
+    let mut queue = std::collections::VecDeque::from([class_name.to_string()]);
+    let mut seen: HashSet<String> = HashSet::new();
+    let mut found_class = false;
+    while let Some(name) = queue.pop_front() {
+        if seen.len() >= WHERE_MAX_CLASSES || !seen.insert(name.clone()) {
+            continue;
+        }
+        let symbols = client
+            .execute_workspace_symbols_exact(workspace_root.to_path_buf(), name.clone(), Vec::new())
+            .await?
+            .symbols;
+        let Some(class_info) = symbols.into_iter().find(|s| matches!(s.kind, SymbolKind::Class))
+        else {
+            continue;
+        };
+        let uri = class_info.location.uri;
+        let file_path = uri_to_path(&uri);
+        let outline = client
+            .execute_document_symbols(workspace_root.to_path_buf(), file_path.clone())
+            .await?
+            .symbols;
+        let Some(class) = find_class_symbol(&outline, &name) else {
+            continue;
+        };
+        found_class = true;
+
+        if let Some(member) = class.children.iter().flatten().find(|child| child.name == attr) {
+            let location = Location { uri, range: member.selection_range.clone() };
+            return Ok(MemberLookup::Found { owner: name, location });
+        }
+
+        let text = tokio::fs::read_to_string(&file_path).await.unwrap_or_default();
+        let lines: Vec<&str> = text.lines().collect();
+        let start = class.range.start.line as usize;
+        let end = (class.range.end.line as usize + 1).min(lines.len());
+        let body = lines.get(start..end).unwrap_or_default();
+        if let Some((idx, byte)) = find_self_assignment(body, attr) {
+            let line = u32::try_from(start + idx)?;
+            let character = byte_to_column(body[idx], byte, LSP_UNIT);
+            let end_character = byte_to_column(body[idx], byte + attr.len(), LSP_UNIT);
+            let range = Range {
+                start: Position { line, character },
+                end: Position { line, character: end_character },
+            };
+            return Ok(MemberLookup::Found { owner: name, location: Location { uri, range } });
+        }
+
+        // The header may span several lines: `class Order(\n    Base,\n):`
+        let header_start = class.selection_range.start.line as usize;
+        let mut header = String::new();
+        for line in lines.iter().skip(header_start).take(10) {
+            header.push_str(line);
+            if line.trim_end().ends_with(':') {
+                break;
+            }
+        }
+        queue.extend(class_bases(&header));
+    }
+    Ok(if found_class { MemberLookup::Missing } else { MemberLookup::UnknownClass })
+}
+
+/// The class named `name` anywhere in a document outline.
+#[cfg(unix)]
+fn find_class_symbol<'a>(symbols: &'a [DocumentSymbol], name: &str) -> Option<&'a DocumentSymbol> {
+    symbols.iter().find_map(|symbol| {
+        if symbol.name == name && matches!(symbol.kind, SymbolKind::Class) {
+            return Some(symbol);
+        }
+        find_class_symbol(symbol.children.as_deref().unwrap_or_default(), name)
+    })
+}
+
+#[cfg(not(unix))]
+#[allow(clippy::too_many_arguments)]
+pub async fn handle_where_command(
+    _workspace_root: &Path,
+    _file: &Path,
+    _line: Option<u32>,
+    _expression: &str,
+    _formatter: &OutputFormatter,
+    _timeout: Duration,
+    _zero_based: bool,
+    _debug_log: Option<Arc<DebugLog>>,
+) -> Result<()> {
+    anyhow::bail!(
+        "The 'where' command requires the background daemon, which is only supported on Unix systems"
+    )
+}
+
+/// Check every call of a function against a proposed signature and report the ones that break.
+#[cfg(unix)]
+#[allow(clippy::too_many_arguments)]
+pub async fn handle_sig_check_command(
+    workspace_root: &Path,
+    file: Option<&Path>,
+    query: &str,
+    new_sig: &str,
+    formatter: &OutputFormatter,
+    timeout: Duration,
+    zero_based: bool,
+    debug_log: Option<Arc<DebugLog>>,
+) -> Result<()> {
+    use crate::cli::output::{BrokenCall, SigCheckResult};
+    use crate::sigcheck::{call_arguments, Signature, Verdict};
+
+    // Method calls don't pass `self`, so check them against the bound signature.
+    let proposed = Signature::parse(new_sig)
+        .with_context(|| format!("Can't parse --new-sig '{new_sig}'"))?
+        .without_receiver();
+
+    let mut client = connect_daemon(timeout, debug_log.as_ref()).await?;
+    let Some((target_file, line, column)) =
+        resolve_query_position(&mut client, workspace_root, file, query, zero_based).await?
+    else {
+        anyhow::bail!("Could not find '{query}' in the workspace");
+    };
+    let current_signature = client
+        .execute_hover(workspace_root.to_path_buf(), target_file.clone(), line, column)
+        .await?
+        .hover
+        .map(|h| OutputFormatter::extract_hover_type(&h.contents));
+    let mut references = client
+        .execute_references(workspace_root.to_path_buf(), target_file, line, column, false)
+        .await?
+        .locations;
+    retain_workspace_locations(&mut references, workspace_root);
+    dedup_locations(&mut references);
+
+    let mut sources: HashMap<String, Option<String>> = HashMap::new();
+    let mut call_sites = 0;
+    let mut breaking = Vec::new();
+    let mut unchecked = Vec::new();
+    for location in references {
+        let path = uri_to_path(&location.uri);
+        if !sources.contains_key(&path) {
+            let text = tokio::fs::read_to_string(&path).await.ok();
+            sources.insert(path.clone(), text);
+        }
+        let Some(Some(text)) = sources.get(&path) else { continue };
+        let Some(start) = lsp_position_to_byte(text, &location.range.start) else { continue };
+        // Imports, decorators without arguments and the function passed as a
+        // value are references too, but not calls.
+        let Some(args) = call_arguments(text, start) else { continue };
+        call_sites += 1;
+        match proposed.check_call(&args) {
+            Verdict::Compatible => {}
+            Verdict::Breaks(problems) => breaking.push(BrokenCall { location, problems }),
+            Verdict::Unchecked => unchecked.push(location),
+        }
+    }
+
+    if let Some(ref log) = debug_log {
+        log.log_result_summary(&format!(
+            "sig-check '{query}': {} of {call_sites} call site(s) break, {} unchecked",
+            breaking.len(),
+            unchecked.len()
+        ));
+    }
+
+    let cache = SourceCache::from_uris(
+        breaking
+            .iter()
+            .map(|c| c.location.uri.as_str())
+            .chain(unchecked.iter().map(|l| l.uri.as_str())),
+    )
+    .await;
+    let result = SigCheckResult {
+        symbol: query.to_string(),
+        current_signature,
+        new_signature: new_sig.to_string(),
+        call_sites,
+        breaking,
+        unchecked,
+    };
+    println!("{}", formatter.format_sig_check(&result, &cache));
+    Ok(())
+}
+
+/// Byte offset in `text` of an LSP position, if it is inside the text.
+#[cfg(unix)]
+fn lsp_position_to_byte(text: &str, position: &crate::lsp::protocol::Position) -> Option<usize> {
+    use crate::lsp::position::column_to_byte;
+
+    let mut offset = 0;
+    for (idx, line) in text.split_inclusive('\n').enumerate() {
+        if idx == position.line as usize {
+            let content = line.trim_end_matches(['\n', '\r']);
+            return Some(offset + column_to_byte(content, position.character, LSP_UNIT));
+        }
+        offset += line.len();
+    }
+    None
+}
+
+#[cfg(not(unix))]
+#[allow(clippy::too_many_arguments)]
+pub async fn handle_sig_check_command(
+    _workspace_root: &Path,
+    _file: Option<&Path>,
+    _query: &str,
+    _new_sig: &str,
+    _formatter: &OutputFormatter,
+    _timeout: Duration,
+    _zero_based: bool,
+    _debug_log: Option<Arc<DebugLog>>,
+) -> Result<()> {
+    anyhow::bail!(
+        "The 'sig-check' command requires the background daemon, which is only supported on Unix systems"
+    )
+}
+
+/// Move a module to `new` and rewrite the imports that refer to it.
+///
+/// Import statements naming the module are found in the text of every
+/// workspace file; each is kept only if ty resolves it to the moved file, so
+/// a same-named module elsewhere isn't touched. Without `apply` the changes
+/// are only printed as a diff.
+#[cfg(unix)]
+pub async fn handle_move_command(
+    workspace_root: &Path,
+    old: &Path,
+    new: &Path,
+    apply: bool,
+    formatter: &OutputFormatter,
+    timeout: Duration,
+    debug_log: Option<Arc<DebugLog>>,
+) -> Result<()> {
+    let root = crate::lsp::uri::canonicalize(workspace_root)
+        .unwrap_or_else(|_| workspace_root.to_path_buf());
+    let old_path = crate::lsp::uri::canonicalize(&workspace_root.join(old))
+        .with_context(|| format!("File not found: {}", old.display()))?;
+    crate::lsp::language::check_python_file(&old_path)?;
+    check_not_package(&old_path, old)?;
+    let new_path = resolve_move_destination(&root, &old_path, new)?;
+    if !old_path.starts_with(&root) || !new_path.starts_with(&root) {
+        anyhow::bail!("Both paths must be inside the workspace {}", root.display());
+    }
+
+    let mut client = connect_daemon(timeout, debug_log.as_ref()).await?;
+    let mut result = plan_module_move(&mut client, &root, old_path, new_path, false).await?;
+    if apply {
+        write_move_edits(&result)?;
+        if let Some(parent) = result.new_path.parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create {}", parent.display()))?;
+        }
+        std::fs::rename(&result.old_path, &result.new_path).with_context(|| {
+            format!("Failed to move {} to {}", result.old_path.display(), result.new_path.display())
+        })?;
+        result.applied = true;
+    }
+    log_module_move(debug_log.as_ref(), "move", &result);
+    println!("{}", formatter.format_move(&result));
+    Ok(())
+}
+
+/// Rewrite the imports of a module that was already moved from `old` to
+/// `new`, by `git mv`, an editor or anything else.
+///
+/// Imports are found like for [`handle_move_command`]; the old module no
+/// longer exists, so an import ty can't resolve is taken to be one of it.
+/// Without `apply` the changes are only printed as a diff.
+#[cfg(unix)]
+pub async fn handle_moved_command(
+    workspace_root: &Path,
+    old: &Path,
+    new: &Path,
+    apply: bool,
+    formatter: &OutputFormatter,
+    timeout: Duration,
+    debug_log: Option<Arc<DebugLog>>,
+) -> Result<()> {
+    let root = crate::lsp::uri::canonicalize(workspace_root)
+        .unwrap_or_else(|_| workspace_root.to_path_buf());
+    let mut new_path = workspace_root.join(new);
+    if new_path.is_dir() {
+        if let Some(name) = old.file_name() {
+            new_path.push(name);
+        }
+    }
+    let new_path = crate::lsp::uri::canonicalize(&new_path)
+        .with_context(|| format!("File not found: {}", new_path.display()))?;
+    crate::lsp::language::check_python_file(&new_path)?;
+    check_not_package(&new_path, new)?;
+    let old_path = canonicalize_missing(&root.join(old));
+    if old_path.exists() {
+        anyhow::bail!(
+            "{} still exists; use `tyf move` to move it and update its imports",
+            old.display()
+        );
+    }
+    if old_path.extension() != new_path.extension() {
+        anyhow::bail!("{} and {} must both be modules", old.display(), new.display());
+    }
+    if !old_path.starts_with(&root) || !new_path.starts_with(&root) {
+        anyhow::bail!("Both paths must be inside the workspace {}", root.display());
+    }
+
+    let mut client = connect_daemon(timeout, debug_log.as_ref()).await?;
+    let mut result = plan_module_move(&mut client, &root, old_path, new_path, true).await?;
+    if apply {
+        write_move_edits(&result)?;
+        result.applied = true;
+    }
+    log_module_move(debug_log.as_ref(), "moved", &result);
+    println!("{}", formatter.format_move(&result));
+    Ok(())
+}
+
+/// Refuse to move a package's `__init__` module, `given` as the user wrote it.
+#[cfg(unix)]
+fn check_not_package(path: &Path, given: &Path) -> Result<()> {
+    if path.file_stem().is_some_and(|stem| stem == "__init__") {
+        anyhow::bail!(
+            "{} is a package's __init__ module; moving whole packages isn't supported",
+            given.display()
+        );
+    }
+    Ok(())
+}
+
+/// The imports of the module moving from `old_path` to `new_path`, and the
+/// edits that point them at its new name; nothing is written.
+///
+/// `moved` says the file is already at `new_path`: its own relative imports
+/// are still read from the package it came from.
+#[cfg(unix)]
+async fn plan_module_move(
+    client: &mut DaemonClient,
+    root: &Path,
+    old_path: PathBuf,
+    new_path: PathBuf,
+    moved: bool,
+) -> Result<crate::cli::output::MoveResult> {
+    use crate::cli::output::{MoveResult, MovedFile};
+    use crate::lsp::position::byte_to_column;
+    use crate::refactor::imports::{
+        absolutize_relative_imports, rewrite_imports, rewrite_qualified_uses, ModuleMove,
+    };
+    use crate::refactor::{import_root, package_name};
+
+    let config = crate::workspace::ty_config::TyConfig::load_or_default(root);
+    let old_root = import_root(root, &config, &old_path);
+    let new_root = import_root(root, &config, &new_path);
+    let change = ModuleMove {
+        old: crate::grep::module_name(&old_root, &old_path),
+        new: crate::grep::module_name(&new_root, &new_path),
+    };
+    let old_package = package_name(&old_root, &old_path);
+    let moves_package = old_package != package_name(&new_root, &new_path);
+    let module_file = if moved { &new_path } else { &old_path };
+
+    let mut files = Vec::new();
+    let (mut imports, mut skipped) = (0, 0);
+    for path in crate::workspace::walker::WorkspaceWalker::new(root, &config).python_files() {
+        let Ok(text) = tokio::fs::read_to_string(&path).await else { continue };
+        let package = if path == *module_file {
+            old_package.clone()
+        } else {
+            package_name(&import_root(root, &config, &path), &path)
+        };
+        let candidates = rewrite_imports(&text, &package, &change);
+        let relative = if path == *module_file && moves_package {
+            absolutize_relative_imports(&text, &package)
+        } else {
+            Vec::new()
+        };
+        if candidates.is_empty() && relative.is_empty() {
+            continue;
+        }
+
+        let lines: Vec<&str> = text.lines().collect();
+        let file_str = path.to_string_lossy().to_string();
+        let mut rewrites = Vec::new();
+        for rewrite in candidates {
+            let (line, byte) = rewrite.probe;
+            let column = byte_to_column(lines[line], byte, LSP_UNIT);
+            let definition = client
+                .execute_definition(
+                    root.to_path_buf(),
+                    file_str.clone(),
+                    u32::try_from(line)?,
+                    column,
+                )
+                .await?
+                .location;
+            // No answer means ty couldn't resolve the import at all, which
+            // is what a text match of the module's full name most likely is
+            // (and, once the file has moved, what every import of it is).
+            match definition {
+                Some(location)
+                    if ![&old_path, &new_path]
+                        .contains(&&PathBuf::from(uri_to_path(&location.uri))) =>
+                {
+                    skipped += 1;
+                }
+                _ => rewrites.push(rewrite),
+            }
+        }
+
+        imports += rewrites.len();
+        let qualified = rewrites.iter().any(|r| r.qualified);
+        let import_lines: Vec<usize> =
+            rewrites.iter().flat_map(|r| r.edits.iter().map(|e| e.line)).collect();
+        let mut edits: Vec<_> =
+            relative.into_iter().filter(|e| !import_lines.contains(&e.line)).collect();
+        edits.extend(rewrites.into_iter().flat_map(|r| r.edits));
+        if qualified {
+            edits.extend(rewrite_qualified_uses(&text, &change, &import_lines));
+        }
+        edits.sort_by_key(|e| e.line);
+        // Before a move, the module's own file is listed to show the rename.
+        if !edits.is_empty() || (path == old_path && !moved) {
+            files.push(MovedFile { path, text, edits });
+        }
+    }
+
+    Ok(MoveResult { old_path, new_path, change, files, imports, skipped, applied: false, moved })
+}
+
+/// Write the edits of a planned move to the files they change.
+#[cfg(unix)]
+fn write_move_edits(result: &crate::cli::output::MoveResult) -> Result<()> {
+    for file in result.files.iter().filter(|file| !file.edits.is_empty()) {
+        let text = crate::refactor::edit::apply(&file.text, &file.edits);
+        std::fs::write(&file.path, text)
+            .with_context(|| format!("Failed to write {}", file.path.display()))?;
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn log_module_move(
+    debug_log: Option<&Arc<DebugLog>>,
+    command: &str,
+    result: &crate::cli::output::MoveResult,
+) {
+    if let Some(log) = debug_log {
+        log.log_result_summary(&format!(
+            "{command} {} -> {}: {} import(s) in {} file(s), {} skipped",
+            result.change.old,
+            result.change.new,
+            result.imports,
+            result.files.len(),
+            result.skipped
+        ));
+    }
+}
+
+/// The canonical path a module moves to.
+///
+/// An existing directory means "into this directory, same file name". The
+/// destination itself doesn't exist yet, so its nearest existing ancestor is
+/// canonicalized instead, keeping paths comparable with ty's.
+#[cfg(unix)]
+fn resolve_move_destination(root: &Path, old_path: &Path, new: &Path) -> Result<PathBuf> {
+    let mut new_path = root.join(new);
+    if new_path.is_dir() {
+        if let Some(name) = old_path.file_name() {
+            new_path.push(name);
+        }
+    }
+    if new_path.exists() {
+        anyhow::bail!("{} already exists", new_path.display());
+    }
+    if new_path.extension() != old_path.extension() {
+        anyhow::bail!(
+            "{} must keep the module's .{} extension",
+            new.display(),
+            old_path.extension().unwrap_or_default().to_string_lossy()
+        );
+    }
+    Ok(canonicalize_missing(&new_path))
+}
+
+/// `path`, which doesn't exist, with its nearest existing ancestor
+/// canonicalized, keeping it comparable with the paths ty reports.
+#[cfg(unix)]
+fn canonicalize_missing(path: &Path) -> PathBuf {
+    let mut existing = path;
+    let mut rest = Vec::new();
+    while !existing.exists() {
+        let (Some(parent), Some(name)) = (existing.parent(), existing.file_name()) else {
+            break;
+        };
+        rest.push(name.to_os_string());
+        existing = parent;
+    }
+    let mut resolved =
+        crate::lsp::uri::canonicalize(existing).unwrap_or_else(|_| existing.to_path_buf());
+    resolved.extend(rest.iter().rev());
+    resolved
+}
+
+#[cfg(not(unix))]
+pub async fn handle_move_command(
+    _workspace_root: &Path,
+    _old: &Path,
+    _new: &Path,
+    _apply: bool,
+    _formatter: &OutputFormatter,
+    _timeout: Duration,
+    _debug_log: Option<Arc<DebugLog>>,
+) -> Result<()> {
+    anyhow::bail!(
+        "The 'move' command requires the background daemon, which is only supported on Unix systems"
+    )
+}
+
+#[cfg(not(unix))]
+pub async fn handle_moved_command(
+    _workspace_root: &Path,
+    _old: &Path,
+    _new: &Path,
+    _apply: bool,
+    _formatter: &OutputFormatter,
+    _timeout: Duration,
+    _debug_log: Option<Arc<DebugLog>>,
+) -> Result<()> {
+    anyhow::bail!(
+        "The 'moved' command requires the background daemon, which is only supported on Unix systems"
+    )
+}
+
+/// The file a `tyf imports` target names, or `None` for a dotted module name.
+pub fn imports_target_path(target: &str) -> Option<PathBuf> {
+    let path = Path::new(target);
+    let is_file = target.contains(['/', '\\'])
+        || path.extension().is_some_and(|ext| ext == "py" || ext == "pyi");
+    is_file.then(|| path.to_path_buf())
+}
+
+/// Print the workspace modules `target` imports and the ones importing it.
+///
+/// Every Python file ty would analyse is scanned for import statements,
+/// which are matched against the workspace's module names. Imports of the
+/// target that match none are resolved with ty, to tell where they lead.
+#[cfg(unix)]
+#[allow(clippy::too_many_lines)]
+pub async fn handle_imports_command(
+    workspace_root: &Path,
+    target: &str,
+    formatter: &OutputFormatter,
+    timeout: Duration,
+    debug_log: Option<Arc<DebugLog>>,
+) -> Result<()> {
+    use crate::cli::output::{ImportEdge, ImportsResult};
+    use crate::import_graph::ModuleIndex;
+    use crate::lsp::position::byte_to_column;
+    use crate::refactor::imports::imported_modules;
+    use crate::refactor::{import_root, package_name};
+
+    let root = crate::lsp::uri::canonicalize(workspace_root)
+        .unwrap_or_else(|_| workspace_root.to_path_buf());
+    let config = crate::workspace::ty_config::TyConfig::load_or_default(&root);
+    let files = crate::workspace::walker::WorkspaceWalker::new(&root, &config).python_files();
+    let index = ModuleIndex::new(files.iter().map(|path| {
+        (crate::grep::module_name(&import_root(&root, &config, path), path), path.clone())
+    }));
+
+    let path = match imports_target_path(target) {
+        Some(file) => {
+            let path = crate::lsp::uri::canonicalize(&workspace_root.join(&file))
+                .with_context(|| format!("File not found: {}", file.display()))?;
+            crate::lsp::language::check_python_file(&path)?;
+            path
+        }
+        None => index.path(target).map(Path::to_path_buf).with_context(|| {
+            format!("No module named '{target}' in the workspace {}", root.display())
+        })?,
+    };
+    let module = index
+        .name(&path)
+        .with_context(|| {
+            format!("{} is not a module of the workspace {}", path.display(), root.display())
+        })?
+        .to_string();
+    let text = tokio::fs::read_to_string(&path)
+        .await
+        .with_context(|| format!("Failed to read file: {}", path.display()))?;
+
+    // What the target imports: workspace modules by name, the rest via ty.
+    let package = package_name(&import_root(&root, &config, &path), &path);
+    let mut imports: Vec<ImportEdge> = Vec::new();
+    let mut unresolved = Vec::new();
+    for import in imported_modules(&text, &package) {
+        let resolved = index.resolve(&import);
+        if resolved.is_empty() {
+            unresolved.push(import);
+            continue;
+        }
+        for name in resolved {
+            if name != module && !imports.iter().any(|edge| edge.module == name) {
+                let path = index.path(&name).map(Path::to_path_buf);
+                imports.push(ImportEdge { module: name, path, line: import.line });
+            }
+        }
+    }
+
+    let mut external: Vec<ImportEdge> = Vec::new();
+    if !unresolved.is_empty() {
+        let mut client = connect_daemon(timeout, debug_log.as_ref()).await?;
+        let lines: Vec<&str> = text.lines().collect();
+        let file_str = path.to_string_lossy().to_string();
+        for import in unresolved {
+            if external.iter().any(|edge| edge.module == import.module) {
+                continue;
+            }
+            let (line, byte) = import.probe;
+            let column = byte_to_column(lines[line], byte, LSP_UNIT);
+            let resolved = client
+                .execute_definition(root.clone(), file_str.clone(), u32::try_from(line)?, column)
+                .await?
+                .location
+                .map(|location| PathBuf::from(uri_to_path(&location.uri)));
+            // ty may find workspace modules the names missed, e.g. through
+            // extra search paths.
+            match resolved.as_deref().and_then(|p| index.name(p)) {
+                Some(name) if name == module => {}
+                Some(name) => {
+                    if !imports.iter().any(|edge| edge.module == name) {
+                        imports.push(ImportEdge {
+                            module: name.to_string(),
+                            path: resolved,
+                            line: import.line,
+                        });
+                    }
+                }
+                None => {
+                    external.push(ImportEdge {
+                        module: import.module,
+                        path: resolved,
+                        line: import.line,
+                    });
+                }
+            }
+        }
+    }
+
+    // What imports the target: the first import of it in each other file.
+    let mut imported_by = Vec::new();
+    for file in files.iter().filter(|file| **file != path) {
+        let Some(name) = index.name(file) else { continue };
+        let Ok(text) = tokio::fs::read_to_string(file).await else { continue };
+        let package = package_name(&import_root(&root, &config, file), file);
+        if let Some(import) = imported_modules(&text, &package)
+            .into_iter()
+            .find(|import| index.resolve(import).contains(&module))
+        {
+            imported_by.push(ImportEdge {
+                module: name.to_string(),
+                path: Some(file.clone()),
+                line: import.line,
+            });
+        }
+    }
+
+    if let Some(ref log) = debug_log {
+        log.log_result_summary(&format!(
+            "imports {module}: {} workspace, {} external, imported by {} of {} file(s)",
+            imports.len(),
+            external.len(),
+            imported_by.len(),
+            files.len()
+        ));
+    }
+
+    let result = ImportsResult { module, path, imports, external, imported_by };
+    println!("{}", formatter.format_imports(&result));
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub async fn handle_imports_command(
+    _workspace_root: &Path,
+    _target: &str,
+    _formatter: &OutputFormatter,
+    _timeout: Duration,
+    _debug_log: Option<Arc<DebugLog>>,
+) -> Result<()> {
+    anyhow::bail!(
+        "The 'imports' command requires the background daemon, which is only supported on Unix systems"
+    )
+}
+
+/// Print each setting's effective value and where it came from.
+///
+/// `config` includes the command-line layer; `timeout` is the timeout in
+/// effect, which is the default when nothing sets one.
+pub fn handle_config_show_command(
+    workspace_root: &Path,
+    config: &Config,
+    timeout: Duration,
+    formatter: &OutputFormatter,
+) {
+    let setting = |key: &str, value: serde_json::Value, source: Option<&Source>| ConfigSetting {
+        key: key.to_string(),
+        value,
+        source: source.map_or_else(|| "default".to_string(), ToString::to_string),
+    };
+    let format = config.lookup(|s| s.format.as_deref());
+    let backend = config.lookup(|s| s.backend.as_deref());
+    let (idle_timeout, idle_source) = config.idle_timeout();
+    let (cache_size, cache_source) = config.cache_size();
+    let exclude_sources: Vec<String> = config
+        .layers()
+        .iter()
+        .filter(|layer| !layer.settings.exclude.is_empty())
+        .map(|layer| layer.source.to_string())
+        .collect();
+
+    let mut settings = vec![
+        setting(
+            "format",
+            format.map_or("human", |(format, _)| format).into(),
+            format.map(|(_, source)| source),
+        ),
+        setting(
+            "timeout",
+            timeout.as_secs().into(),
+            config.lookup(|s| s.timeout).map(|(_, source)| source),
+        ),
+        setting("idle-timeout", idle_timeout.as_secs().into(), idle_source),
+        setting("cache-size", cache_size.into(), cache_source),
+        setting(
+            "backend",
+            backend.map_or(crate::lsp::backend::DEFAULT_BACKEND, |(backend, _)| backend).into(),
+            backend.map(|(_, source)| source),
+        ),
+        ConfigSetting {
+            key: "exclude".to_string(),
+            value: config.exclude().into(),
+            source: if exclude_sources.is_empty() {
+                "default".to_string()
+            } else {
+                exclude_sources.join("; ")
+            },
+        },
+    ];
+    if let Some((_, source)) = config.lookup(|s| s.ty_path.as_deref()) {
+        let path = config.ty_path(workspace_root).unwrap_or_default();
+        settings.push(setting("ty-path", path.display().to_string().into(), Some(source)));
+    }
+    if let Some((roots, source)) = config.lookup(|s| s.roots.as_deref()) {
+        settings.push(setting("roots", roots.into(), Some(source)));
+    }
+    for (name, (command, source)) in config.aliases() {
+        settings.push(setting(&format!("aliases.{name}"), command.into(), Some(source)));
+    }
+
+    let mut paths: Vec<PathBuf> = crate::config::user_path().into_iter().collect();
+    paths.push(workspace_root.join("pyproject.toml"));
+    paths.push(workspace_root.join(crate::config::WORKSPACE_FILE));
+    let files = paths
+        .into_iter()
+        .map(|path| {
+            let read = config.layers().iter().any(|layer| {
+                matches!(&layer.source,
+                    Source::User(p) | Source::Pyproject(p) | Source::Workspace(p) if *p == path)
+            });
+            let status = if read {
+                "read"
+            } else if path.is_file() {
+                "no [tool.ty-find]"
+            } else {
+                "not found"
+            };
+            ConfigFile { path: path.display().to_string(), status }
+        })
+        .collect();
+
+    println!("{}", formatter.format_config(&ConfigReport { settings, files }));
+}
+
+/// Add, remove, or list the aliases `tyf run` runs.
+///
+/// Aliases are saved to the workspace's `.ty-find.toml`, or with `--user`
+/// to the user config file.
+pub fn handle_alias_command(
+    workspace_root: &Path,
+    config: &Config,
+    command: AliasCommands,
+    formatter: &OutputFormatter,
+) -> Result<()> {
+    let file = |user: bool| {
+        if user {
+            crate::config::user_path()
+                .context("No user config file: neither TYF_CONFIG nor HOME is set")
+        } else {
+            Ok(workspace_root.join(crate::config::WORKSPACE_FILE))
+        }
+    };
+    match command {
+        AliasCommands::Add { name, command, user } => {
+            let command = command.trim();
+            check_alias(&name, command)?;
+            let path = file(user)?;
+            let previous = crate::config::write_alias(&path, &name, Some(command))?;
+            let verb = if previous.is_some() { "Replaced" } else { "Added" };
+            println!("{verb} alias '{name}' in {}", path.display());
+        }
+        AliasCommands::Remove { name, user } => {
+            let path = file(user)?;
+            if crate::config::write_alias(&path, &name, None)?.is_none() {
+                let hint = config
+                    .aliases()
+                    .get(name.as_str())
+                    .map_or_else(String::new, |(_, source)| format!("; it is defined in {source}"));
+                let message = format!("No alias '{name}' in {}{hint}", path.display());
+                return Err(UsageError(message).into());
+            }
+            println!("Removed alias '{name}' from {}", path.display());
+        }
+        AliasCommands::List => {
+            let aliases: Vec<AliasEntry> = config
+                .aliases()
+                .into_iter()
+                .map(|(name, (command, source))| AliasEntry {
+                    name: name.to_string(),
+                    command: command.to_string(),
+                    source: source.to_string(),
+                })
+                .collect();
+            println!("{}", formatter.format_aliases(&aliases));
+        }
+    }
+    Ok(())
+}
+
+/// Reject an alias that could never run: one named like a tyf command
+/// (which would always win), or whose command doesn't start with one.
+fn check_alias(name: &str, command: &str) -> Result<()> {
+    use clap::CommandFactory;
+
+    let cli = Cli::command();
+    if name.is_empty() || name.starts_with('-') || name.contains(char::is_whitespace) {
+        return Err(UsageError(format!("'{name}' can't be an alias name")).into());
+    }
+    if cli.find_subcommand(name).is_some() {
+        return Err(UsageError(format!("'{name}' is a tyf command; pick another name")).into());
+    }
+    let first = command.split_whitespace().next().unwrap_or_default();
+    if first == "run" {
+        return Err(UsageError("An alias can't run another alias".to_string()).into());
+    }
+    if cli.find_subcommand(first).is_none() {
+        return Err(UsageError(format!(
+            "'{first}' is not a tyf command; an alias is a tyf command line without the \
+             leading `tyf`, like \"refs --count {{1}}\""
+        ))
+        .into());
+    }
+    Ok(())
+}
+
+/// Browse workspace symbols full-screen and print the one picked.
+///
+/// Closing the browser without picking exits with status 1 and no output.
+#[cfg(unix)]
+pub async fn handle_tui_command(
+    workspace_root: &Path,
+    query: Option<&str>,
+    include_deps: bool,
+    formatter: &OutputFormatter,
+    timeout: Duration,
+    debug_log: Option<Arc<DebugLog>>,
+) -> Result<()> {
+    let mut client = connect_daemon(timeout, debug_log.as_ref()).await?;
+    let picked =
+        crate::tui::run(&mut client, workspace_root, query.unwrap_or_default(), include_deps)
+            .await?;
+    let Some(symbol) = picked else {
+        return found_any(false);
+    };
+    if let Some(ref log) = debug_log {
+        log.log_result_summary(&format!("picked '{}'", symbol.name));
+    }
+    let cache = SourceCache::from_uris([symbol.location.uri.as_str()]).await;
+    println!("{}", formatter.format_picked_symbol(&symbol, &cache));
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub async fn handle_tui_command(
+    _workspace_root: &Path,
+    _query: Option<&str>,
+    _include_deps: bool,
+    _formatter: &OutputFormatter,
+    _timeout: Duration,
+    _debug_log: Option<Arc<DebugLog>>,
+) -> Result<()> {
+    anyhow::bail!(
+        "The 'tui' command requires the background daemon, which is only supported on Unix systems"
+    )
+}
+
+/// Print `file` annotated with the types ty infers.
+///
+/// Variable types come from ty's inlay hints, function signatures from hover
+/// on each function in the document outline. The outline is fetched first,
+/// which also waits out ty's indexing before the hints are asked for.
+#[cfg(unix)]
+pub async fn handle_annotate_command(
+    workspace_root: &Path,
+    file: &Path,
+    side_by_side: bool,
+    formatter: &OutputFormatter,
+    timeout: Duration,
+    debug_log: Option<Arc<DebugLog>>,
+) -> Result<()> {
+    use crate::annotate::{function_annotation, variable_annotations};
+    use crate::cli::output::AnnotateResult;
+
+    let path = crate::lsp::uri::canonicalize(&workspace_root.join(file))
+        .with_context(|| format!("File not found: {}", file.display()))?;
+    let text = tokio::fs::read_to_string(&path)
+        .await
+        .with_context(|| format!("Failed to read file: {}", path.display()))?;
+    let file_str = path.to_string_lossy().to_string();
+
+    let mut client = connect_daemon(timeout, debug_log.as_ref()).await?;
+    let symbols = client
+        .execute_document_symbols(workspace_root.to_path_buf(), file_str.clone())
+        .await?
+        .symbols;
+
+    let mut functions = Vec::new();
+    collect_functions(&symbols, &mut functions);
+    let mut annotations = Vec::new();
+    for symbol in functions {
+        let start = &symbol.selection_range.start;
+        let hover = client
+            .execute_hover(
+                workspace_root.to_path_buf(),
+                file_str.clone(),
+                start.line,
+                start.character,
+            )
+            .await?;
+        if let Some(hover) = hover.hover {
+            let signature = OutputFormatter::extract_hover_type(&hover.contents);
+            annotations.extend(function_annotation(
+                &symbol.name,
+                start.line,
+                start.character,
+                &signature,
+            ));
+        }
+    }
+    let hints = client.execute_inlay_hints(workspace_root.to_path_buf(), file_str).await?.hints;
+    annotations.extend(variable_annotations(&text, &hints));
+    annotations.sort_by_key(|a| (a.line, a.character));
+
+    if let Some(ref log) = debug_log {
+        log.log_result_summary(&format!(
+            "{} annotation(s) from {} inlay hint(s)",
+            annotations.len(),
+            hints.len()
+        ));
+    }
+
+    let result = AnnotateResult { uri: path_to_uri(&path), annotations };
+    let cache = SourceCache::from_uris([result.uri.as_str()]).await;
+    println!("{}", formatter.format_annotate(&result, &cache, side_by_side));
+    Ok(())
+}
+
+/// Functions and methods in a document outline, outermost first.
+#[cfg(unix)]
+fn collect_functions<'a>(symbols: &'a [DocumentSymbol], out: &mut Vec<&'a DocumentSymbol>) {
+    for symbol in symbols {
+        if matches!(
+            symbol.kind,
+            SymbolKind::Function | SymbolKind::Method | SymbolKind::Constructor
+        ) {
+            out.push(symbol);
+        }
+        if let Some(children) = &symbol.children {
+            collect_functions(children, out);
+        }
+    }
+}
+
+#[cfg(not(unix))]
+pub async fn handle_annotate_command(
+    _workspace_root: &Path,
+    _file: &Path,
+    _side_by_side: bool,
+    _formatter: &OutputFormatter,
+    _timeout: Duration,
+    _debug_log: Option<Arc<DebugLog>>,
+) -> Result<()> {
+    anyhow::bail!(
+        "The 'annotate' command requires the background daemon, which is only supported on Unix systems"
+    )
+}
+
+/// Report ty's diagnostics for `files`, or for the whole workspace when empty.
+///
+/// With a baseline, only diagnostics the baseline doesn't record are reported,
+/// and only those can fail the check. `update_baseline` writes the current
+/// diagnostics into the baseline instead; when checking the whole workspace
+/// the file is rewritten from scratch, otherwise only the checked files'
+/// entries are replaced.
+///
+/// `severity` hides less severe diagnostics before anything is counted. The
+/// check fails when more than `max_errors` of the reported diagnostics are at
+/// least as severe as `error_on`.
+#[cfg(unix)]
+#[allow(clippy::too_many_arguments, clippy::too_many_lines)]
+pub async fn handle_check_command(
+    workspace_root: &Path,
+    files: &[PathBuf],
+    baseline_path: Option<&Path>,
+    update_baseline: bool,
+    severity: Option<SeverityLevel>,
+    error_on: SeverityLevel,
+    max_errors: usize,
+    formatter: &OutputFormatter,
+    timeout: Duration,
+    debug_log: Option<Arc<DebugLog>>,
+) -> Result<()> {
+    use crate::baseline::{file_key, Baseline};
+    use crate::cli::output::{CheckResult, FileDiagnostics};
+    use crate::lsp::protocol::DiagnosticSeverity;
+
+    let root = crate::lsp::uri::canonicalize(workspace_root)
+        .unwrap_or_else(|_| workspace_root.to_path_buf());
+    let paths = if files.is_empty() {
+        let config = crate::workspace::ty_config::TyConfig::load_or_default(&root);
+        crate::workspace::walker::WorkspaceWalker::new(&root, &config).python_files()
+    } else {
+        files
+            .iter()
+            .map(|file| {
+                crate::lsp::uri::canonicalize(&workspace_root.join(file))
+                    .with_context(|| format!("File not found: {}", file.display()))
+            })
+            .collect::<Result<Vec<_>>>()?
+    };
+    if paths.is_empty() {
+        anyhow::bail!("No Python files to check in {}", root.display());
+    }
+
+    let mut baseline = match baseline_path {
+        Some(_) if update_baseline && files.is_empty() => Some(Baseline::default()),
+        Some(path) if update_baseline && !path.exists() => Some(Baseline::default()),
+        Some(path) => Some(Baseline::load(path)?),
+        None => None,
+    };
+
+    let mut client = connect_daemon(timeout, debug_log.as_ref()).await?;
+    let shown = severity.map_or(DiagnosticSeverity::Hint, severity_threshold);
+    let mut result = CheckResult {
+        files: Vec::new(),
+        files_checked: paths.len(),
+        baselined: 0,
+        fixed: 0,
+        below_severity: 0,
+    };
+    for path in &paths {
+        let diagnostics = client
+            .execute_diagnostics(workspace_root.to_path_buf(), path.to_string_lossy().to_string())
+            .await?
+            .diagnostics;
+        let key = file_key(&root, path);
+        let reported = match baseline.as_mut() {
+            Some(baseline) if update_baseline => {
+                baseline.record(&key, &diagnostics);
+                continue;
+            }
+            Some(baseline) => {
+                let comparison = baseline.compare(&key, &diagnostics);
+                result.baselined += comparison.known;
+                result.fixed += comparison.fixed;
+                comparison.new.into_iter().cloned().collect()
+            }
+            None => diagnostics,
+        };
+        let (reported, hidden): (Vec<_>, Vec<_>) =
+            reported.into_iter().partition(|d| d.severity.at_least(shown));
+        result.below_severity += hidden.len();
+        if reported.is_empty() {
+            continue;
+        }
+        let file = FileDiagnostics { uri: path_to_uri(path), diagnostics: reported };
+        if formatter.is_jsonl() {
+            let cache = SourceCache::from_uris([file.uri.as_str()]).await;
+            let checked = CheckResult { files: vec![file], ..CheckResult::default() };
+            if !print_records(&formatter.format_check(&checked, &cache))? {
+                return Ok(());
+            }
+            result.files.extend(checked.files);
+        } else {
+            result.files.push(file);
+        }
+    }
+
+    if let (true, Some(baseline), Some(path)) = (update_baseline, &baseline, baseline_path) {
+        baseline.save(path)?;
+        eprintln!(
+            "Recorded {} diagnostic(s) from {} file(s) in {}",
+            baseline.len(),
+            paths.len(),
+            path.display()
+        );
+        return Ok(());
+    }
+
+    let fails = severity_threshold(error_on);
+    let failing = result
+        .files
+        .iter()
+        .flat_map(|file| &file.diagnostics)
+        .filter(|d| d.severity.at_least(fails))
+        .count();
+    if let Some(ref log) = debug_log {
+        log.log_result_summary(&format!(
+            "{failing} failing diagnostic(s) in {} of {} file(s), {} baselined, {} below --severity",
+            result.files.len(),
+            result.files_checked,
+            result.baselined,
+            result.below_severity
+        ));
+    }
+
+    if !formatter.is_jsonl() {
+        let cache = SourceCache::from_uris(result.files.iter().map(|f| f.uri.as_str())).await;
+        println!("{}", formatter.format_check(&result, &cache));
+    }
+    if failing > max_errors {
+        let what = match error_on {
+            SeverityLevel::Error => "error(s)",
+            SeverityLevel::Warning => "error(s) and warning(s)",
+            SeverityLevel::Info => "diagnostic(s)",
+        };
+        let found = if baseline.is_some() { "not in the baseline" } else { "found" };
+        if max_errors > 0 {
+            anyhow::bail!(
+                "{failing} {what} {found}, more than the --max-errors limit of {max_errors}"
+            );
+        }
+        anyhow::bail!("{failing} {what} {found}");
+    }
+    Ok(())
+}
+
+/// Files per `batch_diagnostics` request, so each stays well inside the timeout.
+#[cfg(unix)]
+const CHECK_ALL_CHUNK: usize = 64;
+
+/// Check every Python file in the workspace and print per-file counts.
+///
+/// Files are sent to the daemon in chunks of [`CHECK_ALL_CHUNK`]; the daemon
+/// checks up to `jobs` of them at once. Fails when any error is found.
+#[cfg(unix)]
+pub async fn handle_check_all_command(
+    workspace_root: &Path,
+    jobs: usize,
+    formatter: &OutputFormatter,
+    timeout: Duration,
+    debug_log: Option<Arc<DebugLog>>,
+) -> Result<()> {
+    use crate::cli::output::{CheckAllResult, FileDiagnostics};
+    use crate::lsp::protocol::DiagnosticSeverity;
+
+    let root = crate::lsp::uri::canonicalize(workspace_root)
+        .unwrap_or_else(|_| workspace_root.to_path_buf());
+    let config = crate::workspace::ty_config::TyConfig::load_or_default(&root);
+    let paths = crate::workspace::walker::WorkspaceWalker::new(&root, &config).python_files();
+    if paths.is_empty() {
+        anyhow::bail!("No Python files to check in {}", root.display());
+    }
+
+    let mut client = connect_daemon(timeout, debug_log.as_ref()).await?;
+    let mut result =
+        CheckAllResult { files: Vec::new(), files_checked: paths.len(), failed: Vec::new() };
+    for chunk in paths.chunks(CHECK_ALL_CHUNK) {
+        let entries = client
+            .execute_batch_diagnostics(workspace_root.to_path_buf(), chunk.to_vec(), jobs)
+            .await?
+            .entries;
+        let mut checked =
+            CheckAllResult { files: Vec::new(), files_checked: chunk.len(), failed: Vec::new() };
+        for entry in entries {
+            let uri = path_to_uri(&entry.file);
+            if let Some(error) = entry.error {
+                checked.failed.push((uri, error));
+            } else if !entry.diagnostics.is_empty() {
+                checked.files.push(FileDiagnostics { uri, diagnostics: entry.diagnostics });
+            }
+        }
+        if formatter.is_jsonl() && !print_records(&formatter.format_check_all(&checked))? {
+            return Ok(());
+        }
+        result.files.append(&mut checked.files);
+        result.failed.append(&mut checked.failed);
+    }
+    result.files_checked -= result.failed.len();
+
+    let errors = result
+        .files
+        .iter()
+        .flat_map(|file| &file.diagnostics)
+        .filter(|d| d.severity == DiagnosticSeverity::Error)
+        .count();
+    if let Some(ref log) = debug_log {
+        log.log_result_summary(&format!(
+            "{errors} error(s) in {} of {} file(s), {} could not be checked",
+            result.files.len(),
+            result.files_checked,
+            result.failed.len()
+        ));
+    }
+
+    if !formatter.is_jsonl() {
+        println!("{}", formatter.format_check_all(&result));
+    }
+    if errors > 0 {
+        anyhow::bail!("{errors} error(s) found");
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub async fn handle_check_all_command(
+    _workspace_root: &Path,
+    _jobs: usize,
+    _formatter: &OutputFormatter,
+    _timeout: Duration,
+    _debug_log: Option<Arc<DebugLog>>,
+) -> Result<()> {
+    anyhow::bail!(
+        "The 'check-all' command requires the background daemon, which is only supported on Unix systems"
+    )
+}
+
+/// Files per `batch_document_symbols` request, so each stays well inside the timeout.
+#[cfg(unix)]
+const STATS_CHUNK: usize = 64;
+
+/// Count the lines, classes, functions and methods of every Python file in
+/// the workspace, or of the file or directory `scope`.
+///
+/// Outlines are fetched in batches of [`STATS_CHUNK`], up to `jobs` at once
+/// on the daemon side, and counted by [`crate::code_stats::count`].
+#[cfg(unix)]
+pub async fn handle_stats_command(
+    workspace_root: &Path,
+    scope: Option<&Path>,
+    jobs: usize,
+    formatter: &OutputFormatter,
+    timeout: Duration,
+    debug_log: Option<Arc<DebugLog>>,
+) -> Result<()> {
+    use crate::cli::output::{CodeStatsResult, FileStats};
+
+    let root = crate::lsp::uri::canonicalize(workspace_root)
+        .unwrap_or_else(|_| workspace_root.to_path_buf());
+    let scope = scope
+        .map(|path| {
+            crate::lsp::uri::canonicalize(&root.join(path))
+                .with_context(|| format!("Path not found: {}", path.display()))
+        })
+        .transpose()?;
+    let paths: Vec<PathBuf> = if let Some(file) = scope.as_ref().filter(|path| path.is_file()) {
+        vec![file.clone()]
+    } else {
+        let config = crate::workspace::ty_config::TyConfig::load_or_default(&root);
+        crate::workspace::walker::WorkspaceWalker::new(&root, &config)
+            .python_files()
+            .into_iter()
+            .filter(|path| scope.as_ref().is_none_or(|scope| path.starts_with(scope)))
+            .collect()
+    };
+    if paths.is_empty() {
+        let dir = scope.as_deref().unwrap_or(&root);
+        anyhow::bail!("No Python files to count in {}", dir.display());
+    }
+
+    let mut client = connect_daemon(timeout, debug_log.as_ref()).await?;
+    let mut result = CodeStatsResult { files: Vec::new(), failed: Vec::new() };
+    for chunk in paths.chunks(STATS_CHUNK) {
+        let entries = client
+            .execute_batch_document_symbols(workspace_root.to_path_buf(), chunk.to_vec(), jobs)
+            .await?
+            .entries;
+        let mut counted = CodeStatsResult { files: Vec::new(), failed: Vec::new() };
+        for entry in entries {
+            let uri = path_to_uri(&entry.file);
+            if let Some(error) = entry.error {
+                counted.failed.push((uri, error));
+                continue;
+            }
+            match tokio::fs::read_to_string(&entry.file).await {
+                Ok(text) => counted.files.push(FileStats {
+                    uri,
+                    lines: text.lines().count(),
+                    counts: crate::code_stats::count(&entry.symbols),
+                }),
+                Err(e) => counted.failed.push((uri, e.to_string())),
+            }
+        }
+        if formatter.is_jsonl() && !print_records(&formatter.format_code_stats(&counted))? {
+            return Ok(());
+        }
+        result.files.append(&mut counted.files);
+        result.failed.append(&mut counted.failed);
+    }
+
+    if let Some(ref log) = debug_log {
+        let (lines, _) = result.total();
+        log.log_result_summary(&format!(
+            "{lines} line(s) in {} file(s), {} could not be outlined",
+            result.files.len(),
+            result.failed.len()
+        ));
+    }
+    if !formatter.is_jsonl() {
+        println!("{}", formatter.format_code_stats(&result));
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub async fn handle_stats_command(
+    _workspace_root: &Path,
+    _scope: Option<&Path>,
+    _jobs: usize,
+    _formatter: &OutputFormatter,
+    _timeout: Duration,
+    _debug_log: Option<Arc<DebugLog>>,
+) -> Result<()> {
+    anyhow::bail!(
+        "The 'stats' command requires the background daemon, which is only supported on Unix systems"
+    )
+}
+
+/// References queries sent to the daemon per `unused` batch.
+#[cfg(unix)]
+const UNUSED_CHUNK: usize = 64;
+
+/// Report symbols defined in the workspace (or under `scope`) that nothing
+/// references.
+///
+/// Candidates come from each file's outline, minus dunders, `__all__`
+/// exports and test files (see [`crate::unused::candidates`]). Their
+/// references are resolved in batches of [`UNUSED_CHUNK`], up to `jobs` at
+/// once on the daemon side, with the declaration included so a symbol ty
+/// couldn't resolve isn't mistaken for an unused one.
+#[cfg(unix)]
+pub async fn handle_unused_command(
+    workspace_root: &Path,
+    scope: Option<&Path>,
+    jobs: usize,
+    formatter: &OutputFormatter,
+    timeout: Duration,
+    debug_log: Option<Arc<DebugLog>>,
+) -> Result<()> {
+    use crate::cli::output::{UnusedResult, UnusedSymbol};
+    use crate::unused::{all_exports, candidates, is_unused, Candidate};
+
+    let root = crate::lsp::uri::canonicalize(workspace_root)
+        .unwrap_or_else(|_| workspace_root.to_path_buf());
+    let scope = scope
+        .map(|path| {
+            crate::lsp::uri::canonicalize(&root.join(path))
+                .with_context(|| format!("Path not found: {}", path.display()))
+        })
+        .transpose()?;
+    let config = crate::workspace::ty_config::TyConfig::load_or_default(&root);
+    let paths: Vec<PathBuf> = crate::workspace::walker::WorkspaceWalker::new(&root, &config)
+        .python_files()
+        .into_iter()
+        .filter(|path| scope.as_ref().is_none_or(|scope| path.starts_with(scope)))
+        .collect();
+    if paths.is_empty() {
+        let dir = scope.as_deref().unwrap_or(&root);
+        anyhow::bail!("No Python files to scan in {}", dir.display());
+    }
+
+    let mut client = connect_daemon(timeout, debug_log.as_ref()).await?;
+    let mut found: Vec<(String, Candidate)> = Vec::new();
+    for path in &paths {
+        let uri = path_to_uri(path);
+        if is_test_file(&uri) {
+            continue;
+        }
+        let Ok(text) = tokio::fs::read_to_string(path).await else { continue };
+        let symbols = client
+            .execute_document_symbols(workspace_root.to_path_buf(), path.to_string_lossy().into())
+            .await?
+            .symbols;
+        let exports = all_exports(&text);
+        found.extend(candidates(&symbols, &exports).into_iter().map(|c| (uri.clone(), c)));
+    }
+
+    let mut result = UnusedResult {
+        symbols: Vec::new(),
+        files_scanned: paths.len(),
+        symbols_checked: found.len(),
+        unresolved: 0,
+    };
+    for (chunk_idx, chunk) in found.chunks(UNUSED_CHUNK).enumerate() {
+        let queries = chunk
+            .iter()
+            .enumerate()
+            .map(|(i, (uri, candidate))| BatchReferencesQuery {
+                label: (chunk_idx * UNUSED_CHUNK + i).to_string(),
+                file: PathBuf::from(uri_to_path(uri)),
+                line: candidate.position.line,
+                column: candidate.position.character,
+            })
+            .collect();
+        let entries = client
+            .execute_batch_references(workspace_root.to_path_buf(), queries, true, jobs)
+            .await?
+            .entries;
+        for entry in entries {
+            let Some((uri, candidate)) = entry.label.parse().ok().and_then(|i: usize| found.get(i))
+            else {
+                continue;
+            };
+            match is_unused(uri, &candidate.position, &entry.locations) {
+                Some(true) => result.symbols.push(UnusedSymbol {
+                    name: candidate.name.clone(),
+                    kind: candidate.kind.clone(),
+                    location: Location {
+                        uri: uri.clone(),
+                        range: crate::lsp::protocol::Range {
+                            start: candidate.position.clone(),
+                            end: candidate.position.clone(),
+                        },
+                    },
+                }),
+                Some(false) => {}
+                None => result.unresolved += 1,
+            }
+        }
+    }
+
+    if let Some(ref log) = debug_log {
+        log.log_result_summary(&format!(
+            "{} of {} symbol(s) unused in {} file(s), {} unresolved",
+            result.symbols.len(),
+            result.symbols_checked,
+            result.files_scanned,
+            result.unresolved
+        ));
+    }
+
+    let cache =
+        SourceCache::from_uris(result.symbols.iter().map(|s| s.location.uri.as_str())).await;
+    println!("{}", formatter.format_unused(&result, &cache));
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub async fn handle_unused_command(
+    _workspace_root: &Path,
+    _scope: Option<&Path>,
+    _jobs: usize,
+    _formatter: &OutputFormatter,
+    _timeout: Duration,
+    _debug_log: Option<Arc<DebugLog>>,
+) -> Result<()> {
+    anyhow::bail!(
+        "The 'unused' command requires the background daemon, which is only supported on Unix systems"
+    )
+}
+
+/// Handle the rdeps command: files that reference anything `file` defines.
+///
+/// The symbols looked up are those `tyf unused` checks (see
+/// [`crate::unused::candidates`]), minus the `__all__` filter: exported
+/// names are exactly the ones other files use. Their references come back
+/// from one batch, resolved up to `jobs` at once on the daemon side, and
+/// are grouped by the file they occur in.
+#[cfg(unix)]
+pub async fn handle_rdeps_command(
+    workspace_root: &Path,
+    file: &Path,
+    jobs: usize,
+    formatter: &OutputFormatter,
+    timeout: Duration,
+    debug_log: Option<Arc<DebugLog>>,
+) -> Result<()> {
+    use crate::cli::output::RdepsResult;
+
+    let path = crate::lsp::uri::canonicalize(&workspace_root.join(file))
+        .with_context(|| format!("File not found: {}", file.display()))?;
+    let uri = path_to_uri(&path);
+
+    let mut client = connect_daemon(timeout, debug_log.as_ref()).await?;
+    let symbols = client
+        .execute_document_symbols(workspace_root.to_path_buf(), path.to_string_lossy().into())
+        .await?
+        .symbols;
+    let queries: Vec<BatchReferencesQuery> = crate::unused::candidates(&symbols, &HashSet::new())
+        .into_iter()
+        .map(|candidate| BatchReferencesQuery {
+            label: candidate.name,
+            file: path.clone(),
+            line: candidate.position.line,
+            column: candidate.position.character,
+        })
+        .collect();
+    let symbols_checked = queries.len();
+    let entries = if queries.is_empty() {
+        Vec::new()
+    } else {
+        client
+            .execute_batch_references(workspace_root.to_path_buf(), queries, false, jobs)
+            .await?
+            .entries
+    };
+
+    let result =
+        RdepsResult { files: crate::rdeps::dependents(&uri, &entries), uri, symbols_checked };
+    if let Some(ref log) = debug_log {
+        log.log_result_summary(&format!(
+            "{} file(s) reference {symbols_checked} symbol(s) of {}",
+            result.files.len(),
+            path.display()
+        ));
+    }
+    println!("{}", formatter.format_rdeps(&result));
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub async fn handle_rdeps_command(
+    _workspace_root: &Path,
+    _file: &Path,
+    _jobs: usize,
+    _formatter: &OutputFormatter,
+    _timeout: Duration,
+    _debug_log: Option<Arc<DebugLog>>,
+) -> Result<()> {
+    anyhow::bail!(
+        "The 'rdeps' command requires the background daemon, which is only supported on Unix systems"
+    )
+}
+
+/// Handle the api command: the public classes and functions under a path.
+///
+/// Modules whose dotted name has a part starting with an underscore, and
+/// test files, are skipped; which names a module makes public is decided by
+/// [`crate::api::public_symbols`]. Every public name is hovered in one
+/// batch, up to `jobs` at once on the daemon side, for its signature and
+/// docstring.
+#[cfg(unix)]
+pub async fn handle_api_command(
+    workspace_root: &Path,
+    path: Option<&Path>,
+    jobs: usize,
+    formatter: &OutputFormatter,
+    timeout: Duration,
+    debug_log: Option<Arc<DebugLog>>,
+) -> Result<()> {
+    use crate::api::{is_public_module, public_symbols, ApiSymbol};
+    use crate::cli::output::{ApiItem, ApiModule, ApiResult};
+    use crate::lsp::protocol::Range;
+
+    let root = crate::lsp::uri::canonicalize(workspace_root)
+        .unwrap_or_else(|_| workspace_root.to_path_buf());
+    let scope = path
+        .map(|path| {
+            crate::lsp::uri::canonicalize(&root.join(path))
+                .with_context(|| format!("Path not found: {}", path.display()))
+        })
+        .transpose()?;
+    let config = crate::workspace::ty_config::TyConfig::load_or_default(&root);
+    let paths: Vec<PathBuf> = crate::workspace::walker::WorkspaceWalker::new(&root, &config)
+        .python_files()
+        .into_iter()
+        .filter(|path| scope.as_ref().is_none_or(|scope| path.starts_with(scope)))
+        .collect();
+    let target = scope.as_deref().map_or_else(
+        || {
+            root.file_name()
+                .map_or_else(|| root.display().to_string(), |n| n.to_string_lossy().into())
+        },
+        |scope| scope.strip_prefix(&root).unwrap_or(scope).display().to_string(),
+    );
+    if paths.is_empty() {
+        anyhow::bail!("No Python files to document in {target}");
+    }
+
+    let mut client = connect_daemon(timeout, debug_log.as_ref()).await?;
+    let mut found: Vec<(String, PathBuf, Vec<ApiSymbol>)> = Vec::new();
+    for path in &paths {
+        let import_root = crate::refactor::import_root(&root, &config, path);
+        let module = crate::grep::module_name(&import_root, path);
+        // A stub sorts after its source, which describes the same module.
+        if is_test_file(&path_to_uri(path))
+            || !is_public_module(&module)
+            || found.iter().any(|(seen, ..)| *seen == module)
+        {
+            continue;
+        }
+        let Ok(text) = tokio::fs::read_to_string(path).await else { continue };
+        let symbols = client
+            .execute_document_symbols(workspace_root.to_path_buf(), path.to_string_lossy().into())
+            .await?
+            .symbols;
+        let public = public_symbols(&symbols, &crate::unused::all_exports(&text));
+        if !public.is_empty() {
+            found.push((module, path.clone(), public));
+        }
+    }
+
+    let mut hovers = api_hovers(&mut client, workspace_root, &found, jobs).await?;
+    let mut api_item = |label: String, uri: &str, symbol: &ApiSymbol, members| ApiItem {
+        name: symbol.name.clone(),
+        kind: symbol.kind.clone(),
+        location: Location {
+            uri: uri.to_string(),
+            range: Range { start: symbol.position.clone(), end: symbol.position.clone() },
+        },
+        hover: hovers.remove(&label),
+        members,
+    };
+    let mut modules = Vec::new();
+    for (m, (module, path, symbols)) in found.into_iter().enumerate() {
+        let uri = path_to_uri(&path);
+        let mut items = Vec::new();
+        for (s, symbol) in symbols.iter().enumerate() {
+            let members = symbol
+                .members
+                .iter()
+                .enumerate()
+                .map(|(k, member)| api_item(format!("{m}.{s}.{k}"), &uri, member, Vec::new()))
+                .collect();
+            items.push(api_item(format!("{m}.{s}"), &uri, symbol, members));
+        }
+        modules.push(ApiModule { module, uri, symbols: items });
+    }
+    let result = ApiResult { target, modules };
+
+    if let Some(ref log) = debug_log {
+        let symbols: usize = result.modules.iter().map(|m| m.symbols.len()).sum();
+        log.log_result_summary(&format!(
+            "{symbols} public symbol(s) in {} module(s) of {}",
+            result.modules.len(),
+            result.target
+        ));
+    }
+    let cache = SourceCache::from_uris(result.modules.iter().map(|m| m.uri.as_str())).await;
+    println!("{}", formatter.format_api(&result, &cache));
+    Ok(())
+}
+
+/// ty's hover for every symbol `tyf api` lists, from one batch, keyed by
+/// `module.symbol` or `module.symbol.member` as indices into `found`.
+#[cfg(unix)]
+async fn api_hovers(
+    client: &mut DaemonClient,
+    workspace_root: &Path,
+    found: &[(String, PathBuf, Vec<crate::api::ApiSymbol>)],
+    jobs: usize,
+) -> Result<HashMap<String, crate::lsp::protocol::Hover>> {
+    let query = |label: String, path: &Path, symbol: &crate::api::ApiSymbol| BatchReferencesQuery {
+        label,
+        file: path.to_path_buf(),
+        line: symbol.position.line,
+        column: symbol.position.character,
+    };
+    let mut queries = Vec::new();
+    for (m, (_, path, symbols)) in found.iter().enumerate() {
+        for (s, symbol) in symbols.iter().enumerate() {
+            queries.push(query(format!("{m}.{s}"), path, symbol));
+            for (k, member) in symbol.members.iter().enumerate() {
+                queries.push(query(format!("{m}.{s}.{k}"), path, member));
+            }
+        }
+    }
+    if queries.is_empty() {
+        return Ok(HashMap::new());
+    }
+    Ok(client
+        .execute_batch_hover(workspace_root.to_path_buf(), queries, jobs)
+        .await?
+        .entries
+        .into_iter()
+        .filter_map(|entry| Some((entry.label, entry.hover?)))
+        .collect())
+}
 
-    Ok(InspectResult {
-        symbol: symbol.to_string(),
-        kind: symbol_kind,
-        definitions: all_definitions,
-        hover: inspect.hover,
-        references: inspect.references,
-    })
+#[cfg(not(unix))]
+pub async fn handle_api_command(
+    _workspace_root: &Path,
+    _path: Option<&Path>,
+    _jobs: usize,
+    _formatter: &OutputFormatter,
+    _timeout: Duration,
+    _debug_log: Option<Arc<DebugLog>>,
+) -> Result<()> {
+    anyhow::bail!(
+        "The 'api' command requires the background daemon, which is only supported on Unix systems"
+    )
 }
 
+/// Least severe diagnostic a `--severity`/`--error-on` level covers.
 #[cfg(unix)]
+const fn severity_threshold(level: SeverityLevel) -> crate::lsp::protocol::DiagnosticSeverity {
+    use crate::lsp::protocol::DiagnosticSeverity;
+    match level {
+        SeverityLevel::Error => DiagnosticSeverity::Error,
+        SeverityLevel::Warning => DiagnosticSeverity::Warning,
+        SeverityLevel::Info => DiagnosticSeverity::Hint,
+    }
+}
+
+#[cfg(not(unix))]
+#[allow(clippy::too_many_arguments)]
+pub async fn handle_check_command(
+    _workspace_root: &Path,
+    _files: &[PathBuf],
+    _baseline_path: Option<&Path>,
+    _update_baseline: bool,
+    _severity: Option<SeverityLevel>,
+    _error_on: SeverityLevel,
+    _max_errors: usize,
+    _formatter: &OutputFormatter,
+    _timeout: Duration,
+    _debug_log: Option<Arc<DebugLog>>,
+) -> Result<()> {
+    anyhow::bail!(
+        "The 'check' command requires the background daemon, which is only supported on Unix systems"
+    )
+}
+
 pub async fn handle_document_symbols_command(
     workspace_root: &Path,
     file: &Path,
@@ -1155,50 +5433,52 @@ pub async fn handle_document_symbols_command(
     timeout: Duration,
     debug_log: Option<Arc<DebugLog>>,
 ) -> Result<()> {
-    ensure_daemon_running().await?;
-    let mut client = connect_daemon(timeout, debug_log.as_ref()).await?;
-
-    let result = client
-        .execute_document_symbols(workspace_root.to_path_buf(), file.to_string_lossy().to_string())
-        .await?;
+    #[cfg(unix)]
+    let symbols = {
+        let mut client = connect_daemon(timeout, debug_log.as_ref()).await?;
+        client
+            .execute_document_symbols(
+                workspace_root.to_path_buf(),
+                file.to_string_lossy().to_string(),
+            )
+            .await?
+            .symbols
+    };
+    // Without the daemon (Windows), ask an in-process ty server directly.
+    #[cfg(not(unix))]
+    let symbols = {
+        let _ = timeout;
+        let client = TyLspClient::new(&workspace_root.to_string_lossy()).await?;
+        let path = workspace_root.join(file).to_string_lossy().to_string();
+        client.open_document(&path).await?;
+        client.document_symbols(&path).await?
+    };
 
     if let Some(ref log) = debug_log {
-        log.log_result_summary(&format!(
-            "{} symbol(s) found in {}",
-            result.symbols.len(),
-            file.display()
-        ));
+        log.log_result_summary(&format!("{} symbol(s) found in {}", symbols.len(), file.display()));
         let cmd = format!("list {}", file.display());
         log.log_reproduction_commands(workspace_root, &[], &cmd);
     }
 
-    if result.symbols.is_empty() {
+    if symbols.is_empty() {
         println!(
             "{}",
             formatter.styler().error(&format!("No symbols found in {}", file.display()))
         );
     } else {
+        // Relative paths are resolved against the workspace root, as the daemon does.
+        let source_path = workspace_root.join(file).to_string_lossy().to_string();
+        let uri = path_to_uri(Path::new(&source_path));
+        let cache = SourceCache::from_uris([uri.as_str()]).await;
         println!("Document outline for {}:\n", file.display());
-        println!("{}", formatter.format_document_symbols(&result.symbols));
+        println!("{}", formatter.format_document_symbols(&symbols, &source_path, &cache));
     }
 
     Ok(())
 }
 
-#[cfg(not(unix))]
-pub async fn handle_document_symbols_command(
-    _workspace_root: &Path,
-    _file: &Path,
-    _formatter: &OutputFormatter,
-    _timeout: Duration,
-    _debug_log: Option<Arc<DebugLog>>,
-) -> Result<()> {
-    anyhow::bail!(
-        "The 'list' command requires the background daemon, which is only supported on Unix systems"
-    )
-}
-
 #[cfg(unix)]
+#[allow(clippy::too_many_arguments)]
 pub async fn handle_members_command(
     workspace_root: &Path,
     file: Option<&Path>,
@@ -1206,15 +5486,23 @@ pub async fn handle_members_command(
     include_all: bool,
     formatter: &OutputFormatter,
     timeout: Duration,
+    zero_based: bool,
     debug_log: Option<Arc<DebugLog>>,
 ) -> Result<()> {
-    ensure_daemon_running().await?;
+    let mut client = connect_daemon(timeout, debug_log.as_ref()).await?;
 
     let mut results: Vec<crate::daemon::protocol::MembersResult> = Vec::new();
 
     for symbol in symbols {
-        let result =
-            members_single_class(workspace_root, file, symbol, include_all, timeout).await?;
+        let result = members_single_class(
+            &mut client,
+            workspace_root,
+            file,
+            symbol,
+            include_all,
+            zero_based,
+        )
+        .await?;
         results.push(result);
     }
 
@@ -1223,29 +5511,12 @@ pub async fn handle_members_command(
     let mut valid_results: Vec<crate::daemon::protocol::MembersResult> = Vec::new();
 
     for result in results {
-        match result.symbol_kind.as_ref() {
-            None => {
-                eprintln!("No symbol '{}' found in the project.", result.class_name);
-                has_output = true;
-            }
-            Some(kind) if !matches!(kind, crate::lsp::protocol::SymbolKind::Class) => {
-                let kind_name = match kind {
-                    crate::lsp::protocol::SymbolKind::Function => "a function",
-                    crate::lsp::protocol::SymbolKind::Method => "a method",
-                    crate::lsp::protocol::SymbolKind::Variable => "a variable",
-                    crate::lsp::protocol::SymbolKind::Constant => "a constant",
-                    crate::lsp::protocol::SymbolKind::Module => "a module",
-                    _ => "not a class",
-                };
-                eprintln!(
-                    "'{}' is {kind_name}, not a class. Use 'show' instead.",
-                    result.class_name
-                );
+        match not_a_class(&result) {
+            Some(problem) => {
+                eprintln!("{problem}");
                 has_output = true;
             }
-            Some(_) => {
-                valid_results.push(result);
-            }
+            None => valid_results.push(result),
         }
     }
 
@@ -1266,24 +5537,51 @@ pub async fn handle_members_command(
             // Separate error messages from valid output
             eprintln!();
         }
-        println!("{}", formatter.format_members_results(&valid_results));
+        let cache = SourceCache::from_uris(valid_results.iter().map(|r| r.file_uri.as_str())).await;
+        println!("{}", formatter.format_members_results(&valid_results, &cache));
     }
 
     Ok(())
 }
 
+/// Why a members lookup has nothing to show: the name wasn't found or isn't a class.
+#[cfg(unix)]
+pub fn not_a_class(result: &crate::daemon::protocol::MembersResult) -> Option<String> {
+    if result.expression {
+        return None;
+    }
+    let kind_name = match result.symbol_kind.as_ref() {
+        None => return Some(format!("No symbol '{}' found in the project.", result.class_name)),
+        Some(SymbolKind::Class) => return None,
+        Some(SymbolKind::Function) => "a function",
+        Some(SymbolKind::Method) => "a method",
+        Some(SymbolKind::Variable) => "a variable",
+        Some(SymbolKind::Constant) => "a constant",
+        Some(SymbolKind::Module) => "a module",
+        Some(_) => "not a class",
+    };
+    Some(format!("'{}' is {kind_name}, not a class. Use 'show' instead.", result.class_name))
+}
+
 /// Look up a single class's members via the daemon.
+///
+/// A `file:line:col` query lists the attributes of the expression there instead.
 #[cfg(unix)]
-async fn members_single_class(
+pub async fn members_single_class(
+    client: &mut DaemonClient,
     workspace_root: &Path,
     file: Option<&Path>,
     symbol: &str,
     include_all: bool,
-    timeout: Duration,
+    zero_based: bool,
 ) -> Result<crate::daemon::protocol::MembersResult> {
-    if let Some(file) = file {
+    if let Some((f, l, c)) = parse_file_position(symbol) {
+        let (line, column) = user_position_to_lsp(workspace_root, &f, l, c, zero_based).await;
+        client
+            .execute_expression_members(workspace_root.to_path_buf(), f, line, column, include_all)
+            .await
+    } else if let Some(file) = file {
         // File-based: pass directly to daemon
-        let mut client = DaemonClient::connect_with_timeout(timeout).await?;
         client
             .execute_members(
                 workspace_root.to_path_buf(),
@@ -1294,9 +5592,12 @@ async fn members_single_class(
             .await
     } else {
         // Workspace-based: find the class via workspace symbols first
-        let mut client = DaemonClient::connect_with_timeout(timeout).await?;
         let ws_result = client
-            .execute_workspace_symbols_exact(workspace_root.to_path_buf(), symbol.to_string())
+            .execute_workspace_symbols_exact(
+                workspace_root.to_path_buf(),
+                symbol.to_string(),
+                Vec::new(),
+            )
             .await?;
 
         if ws_result.symbols.is_empty() {
@@ -1306,13 +5607,13 @@ async fn members_single_class(
                 class_line: 0,
                 class_column: 0,
                 symbol_kind: None,
+                expression: false,
                 members: Vec::new(),
             });
         }
 
         let first = &ws_result.symbols[0];
-        let file_path =
-            first.location.uri.strip_prefix("file://").unwrap_or(&first.location.uri).to_string();
+        let file_path = uri_to_path(&first.location.uri);
 
         client
             .execute_members(
@@ -1326,6 +5627,7 @@ async fn members_single_class(
 }
 
 #[cfg(not(unix))]
+#[allow(clippy::too_many_arguments)]
 pub async fn handle_members_command(
     _workspace_root: &Path,
     _file: Option<&Path>,
@@ -1333,15 +5635,508 @@ pub async fn handle_members_command(
     _include_all: bool,
     _formatter: &OutputFormatter,
     _timeout: Duration,
+    _zero_based: bool,
+    _debug_log: Option<Arc<DebugLog>>,
+) -> Result<()> {
+    anyhow::bail!(
+        "The 'members' command requires the background daemon, which is only supported on Unix systems"
+    )
+}
+
+#[cfg(unix)]
+pub async fn handle_ty_info_command(
+    workspace_root: &Path,
+    formatter: &OutputFormatter,
+    timeout: Duration,
+) -> Result<()> {
+    let mut client = connect_daemon(timeout, None).await?;
+    let info = client.execute_ty_info(workspace_root.to_path_buf()).await?;
+    println!("{}", formatter.format_ty_info(&info));
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub async fn handle_ty_info_command(
+    _workspace_root: &Path,
+    _formatter: &OutputFormatter,
+    _timeout: Duration,
+) -> Result<()> {
+    anyhow::bail!(
+        "The 'ty-info' command requires the background daemon, which is only supported on Unix systems"
+    )
+}
+
+/// Run the `tyf doctor` checks and print what to do about failures.
+#[cfg(unix)]
+#[allow(clippy::too_many_lines)]
+pub async fn handle_doctor_command(
+    workspace_root: &Path,
+    formatter: &OutputFormatter,
+    timeout: Duration,
+) -> Result<()> {
+    use crate::doctor::{self, Check, DoctorReport, ScratchProject};
+    use crate::lsp::backend;
+    use crate::lsp::protocol::InitializeResult;
+    use std::time::Instant;
+
+    let root = workspace_root.to_str().context("Invalid workspace path")?;
+    let mut checks = vec![doctor::check_workspace(workspace_root)];
+
+    // The language server: found and startable, then answering a hover
+    // on a file of its own, away from the project and the daemon.
+    let spec = backend::selected(workspace_root);
+    let backend = match backend::from_spec(&spec) {
+        Ok(backend) => Some(backend),
+        Err(e) => {
+            checks.push(Check::fail(
+                "language server",
+                format!("{e:#}"),
+                format!(
+                    "Fix --backend, {} or the backend setting (see `tyf config show`)",
+                    backend::BACKEND_ENV
+                ),
+            ));
+            None
+        }
+    };
+    let command = match &backend {
+        Some(backend) => {
+            // For ty, say where it was found and which version it is.
+            let resolved = match backend::find_ty(&spec, root).await {
+                Some(found) => found.map(|ty| (ty.to_string(), ty.command.with_args(&["server"]))),
+                None => backend.resolve(root).await.map(|command| {
+                    (format!("{} via `{}`", backend.name(), command.label()), command)
+                }),
+            };
+            match resolved {
+                Ok((detail, command)) => {
+                    checks.push(Check::ok("language server", detail));
+                    Some(command.label())
+                }
+                Err(e) => {
+                    let fix = if spec.starts_with(backend::TY_PATH_PREFIX) {
+                        "Point --ty-path or the ty-path setting at a ty executable".to_string()
+                    } else if backend.name() == backend::DEFAULT_BACKEND {
+                        setup::missing_ty_hint(workspace_root)
+                    } else {
+                        format!("Install {}, or use ty with --backend ty", backend.name())
+                    };
+                    checks.push(Check::fail("language server", format!("{e:#}"), fix));
+                    None
+                }
+            }
+        }
+        None => None,
+    };
+    if let Some((backend, command)) = backend.zip(command) {
+        let name = backend.name().to_string();
+        // What the server answered `initialize` with, even if the hover then fails.
+        let mut capabilities = None;
+        let probe = async {
+            let scratch = ScratchProject::create().context("Failed to create a scratch project")?;
+            let file = scratch.file().to_string_lossy().into_owned();
+            let started = Instant::now();
+            let client =
+                TyLspClient::with_backend(&scratch.root().to_string_lossy(), backend).await?;
+            let label = client
+                .server_info()
+                .filter(|info| info.server_info.is_some())
+                .map_or_else(|| name.clone(), InitializeResult::server_label);
+            capabilities = client.server_info().cloned();
+            client.open_document(&file).await?;
+            let (line, column) = doctor::PROBE_POSITION;
+            // None: the server doesn't do hover at all.
+            let answered = if client.server_info().is_none_or(|i| i.supports("hoverProvider")) {
+                Some(client.hover(&file, line, column).await?.is_some())
+            } else {
+                None
+            };
+            anyhow::Ok((label, answered, started.elapsed()))
+        };
+        let check = match tokio::time::timeout(timeout, probe).await {
+            Ok(Ok((label, Some(true), elapsed))) => Check::ok(
+                "hover probe",
+                format!("{label} answered on a scratch file in {}ms", elapsed.as_millis()),
+            ),
+            Ok(Ok((label, Some(false), _))) => Check::warn(
+                "hover probe",
+                format!("{label} started but had no hover for a plain function call"),
+                "Queries may come back empty; see what the server supports with `tyf ty-info`",
+            ),
+            Ok(Ok((label, None, _))) => Check::warn(
+                "hover probe",
+                format!("{label} started but doesn't support hover"),
+                "show, hover, members and explain-type won't work with it; use ty for those",
+            ),
+            Ok(Err(e)) => Check::fail(
+                "hover probe",
+                format!("{e:#}"),
+                format!("Run `{command}` in a terminal to see why {name} fails"),
+            ),
+            Err(_) => Check::fail(
+                "hover probe",
+                format!("{name} did not answer within {}s", timeout.as_secs()),
+                "Retry with a longer --timeout; a first run through uvx may be downloading ty",
+            ),
+        };
+        checks.push(check);
+        checks.push(capabilities.as_ref().map_or_else(
+            || Check::skip("capabilities", "the server didn't answer initialize"),
+            doctor::check_capabilities,
+        ));
+    } else {
+        checks.push(Check::skip("hover probe", "no language server to ask"));
+        checks.push(Check::skip("capabilities", "no language server to ask"));
+    }
+
+    // The daemon: somewhere to listen, then a round trip to it (which,
+    // with --no-daemon, is to a server inside this process).
+    if crate::daemon::client::daemon_disabled() {
+        checks.push(Check::skip("socket", "--no-daemon: nothing listens"));
+    } else {
+        let socket_path = crate::daemon::client::get_socket_path()?;
+        checks.push(doctor::check_socket(&socket_path));
+    }
+    let ping = async {
+        let mut client = ensure_daemon_running().await?;
+        client.ping().await
+    };
+    checks.push(match tokio::time::timeout(timeout, ping).await {
+        Ok(Ok(ping)) => Check::ok(
+            "daemon",
+            format!(
+                "v{} (pid {}), {} workspace(s) loaded",
+                ping.version, ping.pid, ping.active_workspaces
+            ),
+        ),
+        Ok(Err(e)) => Check::fail(
+            "daemon",
+            format!("{e:#}"),
+            "Run `tyf daemon restart`; if it still fails, run `tyf daemon start --foreground` \
+             to see why it exits",
+        ),
+        Err(_) => Check::fail(
+            "daemon",
+            format!("no answer within {}s", timeout.as_secs()),
+            "Run `tyf daemon restart`",
+        ),
+    });
+
+    let report = DoctorReport { workspace: workspace_root.display().to_string(), checks };
+    println!("{}", formatter.format_doctor_report(&report));
+    match report.failures() {
+        0 => Ok(()),
+        failures => anyhow::bail!("{failures} of {} checks failed", report.checks.len()),
+    }
+}
+
+#[cfg(not(unix))]
+pub async fn handle_doctor_command(
+    _workspace_root: &Path,
+    _formatter: &OutputFormatter,
+    _timeout: Duration,
+) -> Result<()> {
+    anyhow::bail!(
+        "The 'doctor' command requires the background daemon, which is only supported on Unix systems"
+    )
+}
+
+/// Run the `tyf bench` suite against `workspace_root` and print latency percentiles.
+#[cfg(unix)]
+#[allow(clippy::too_many_lines)]
+pub async fn handle_bench_command(
+    workspace_root: &Path,
+    query: Option<&str>,
+    iterations: usize,
+    runs: usize,
+    formatter: &OutputFormatter,
+    timeout: Duration,
+) -> Result<()> {
+    use crate::bench::{BenchReport, BenchStat, REFS_BATCH_SIZE};
+    use std::time::Instant;
+
+    let mut client = connect_daemon(timeout, None).await?;
+
+    let target = if let Some(query) = query {
+        let resolved = classify_and_resolve(
+            &mut client,
+            &[query.to_string()],
+            None,
+            workspace_root,
+            false,
+            &[],
+        )
+        .await?;
+        resolved
+            .into_iter()
+            .find(|q| !q.file.is_empty())
+            .with_context(|| format!("Could not find '{query}' to benchmark"))?
+    } else {
+        pick_bench_target(&mut client, workspace_root).await?
+    };
+    let file = workspace_root.join(&target.file).to_string_lossy().into_owned();
+    let shown_file =
+        Path::new(&file).strip_prefix(workspace_root).unwrap_or_else(|_| Path::new(&file));
+    let target_label = format!(
+        "{} ({}:{}:{})",
+        target.label,
+        shown_file.display(),
+        target.line + 1,
+        target.column + 1
+    );
+
+    let workspace = workspace_root.to_path_buf();
+
+    // Untimed request: starts the daemon's ty server and checks that the
+    // target has something to hover, which the cold-start benchmark waits for.
+    let warmup =
+        client.execute_hover(workspace.clone(), file.clone(), target.line, target.column).await?;
+    if warmup.hover.is_none() {
+        anyhow::bail!(
+            "ty has no hover information for {target_label}; pass a different symbol or position"
+        );
+    }
+
+    let mut cold = Vec::with_capacity(runs);
+    for _ in 0..runs {
+        let started = Instant::now();
+        let ty = TyLspClient::new(&workspace_root.to_string_lossy()).await?;
+        ty.open_document(&file).await?;
+        while ty.hover(&file, target.line, target.column).await?.is_none() {
+            if started.elapsed() > timeout {
+                anyhow::bail!(
+                    "A fresh ty server did not answer hover within {}s",
+                    timeout.as_secs()
+                );
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        cold.push(started.elapsed());
+    }
+
+    let mut hover = Vec::with_capacity(iterations);
+    for _ in 0..iterations {
+        let started = Instant::now();
+        client.execute_hover(workspace.clone(), file.clone(), target.line, target.column).await?;
+        hover.push(started.elapsed());
+    }
+
+    let mut definition = Vec::with_capacity(iterations);
+    for _ in 0..iterations {
+        let started = Instant::now();
+        client
+            .execute_definition(workspace.clone(), file.clone(), target.line, target.column)
+            .await?;
+        definition.push(started.elapsed());
+    }
+
+    let batch: Vec<BatchReferencesQuery> = (0..REFS_BATCH_SIZE)
+        .map(|i| BatchReferencesQuery {
+            label: format!("{}#{i}", target.label),
+            file: PathBuf::from(&file),
+            line: target.line,
+            column: target.column,
+        })
+        .collect();
+    let mut refs = Vec::with_capacity(runs);
+    for _ in 0..runs {
+        let started = Instant::now();
+        client
+            .execute_batch_references(
+                workspace.clone(),
+                batch.clone(),
+                false,
+                DEFAULT_REFERENCES_CONCURRENCY,
+            )
+            .await?;
+        refs.push(started.elapsed());
+    }
+
+    let ty_version = client.execute_ty_info(workspace.clone()).await.ok().and_then(|i| i.version);
+    let report = BenchReport {
+        workspace: workspace_root.display().to_string(),
+        target: target_label,
+        ty_version,
+        results: vec![
+            BenchStat::from_samples("cold start", &cold),
+            BenchStat::from_samples("warm hover", &hover),
+            BenchStat::from_samples("definition", &definition),
+            BenchStat::from_samples(&format!("{REFS_BATCH_SIZE} batched refs"), &refs),
+        ],
+    };
+    println!("{}", formatter.format_bench_report(&report));
+    Ok(())
+}
+
+/// The first function or class in the workspace, used when `tyf bench` gets no query.
+#[cfg(unix)]
+async fn pick_bench_target(
+    client: &mut DaemonClient,
+    workspace_root: &Path,
+) -> Result<ResolvedQuery> {
+    let config = crate::workspace::ty_config::TyConfig::load_or_default(workspace_root);
+    for file in crate::bench::candidate_files(workspace_root, &config) {
+        let file = file.to_string_lossy().into_owned();
+        let Ok(result) =
+            client.execute_document_symbols(workspace_root.to_path_buf(), file.clone()).await
+        else {
+            continue;
+        };
+        let found = result
+            .symbols
+            .iter()
+            .find(|s| matches!(s.kind, SymbolKind::Function | SymbolKind::Class));
+        if let Some(symbol) = found {
+            return Ok(ResolvedQuery {
+                label: symbol.name.clone(),
+                file,
+                line: symbol.selection_range.start.line,
+                column: symbol.selection_range.start.character,
+            });
+        }
+    }
+    anyhow::bail!(
+        "No function or class found to benchmark in {}; pass a symbol name or file:line:col",
+        workspace_root.display()
+    )
+}
+
+#[cfg(not(unix))]
+pub async fn handle_bench_command(
+    _workspace_root: &Path,
+    _query: Option<&str>,
+    _iterations: usize,
+    _runs: usize,
+    _formatter: &OutputFormatter,
+    _timeout: Duration,
+) -> Result<()> {
+    anyhow::bail!(
+        "The 'bench' command requires the background daemon, which is only supported on Unix systems"
+    )
+}
+
+/// Serve MCP tools on stdio until the client disconnects.
+///
+/// Nothing else may write to stdout while serving: it carries the protocol.
+#[cfg(unix)]
+pub async fn handle_mcp_command(
+    workspace_root: &Path,
+    formatter: &OutputFormatter,
+    timeout: Duration,
+    zero_based: bool,
+    include_deps: bool,
+    debug_log: Option<Arc<DebugLog>>,
+) -> Result<()> {
+    eprintln!("tyf MCP server ready for {}", workspace_root.display());
+    let server = crate::mcp::McpServer {
+        workspace_root,
+        formatter,
+        timeout,
+        zero_based,
+        include_deps,
+        debug_log: debug_log.as_ref(),
+    };
+    server.serve().await
+}
+
+/// Run the queries of a `tyf batch` script read from stdin.
+#[cfg(unix)]
+pub async fn handle_batch_command(
+    workspace_root: &Path,
+    formatter: &OutputFormatter,
+    timeout: Duration,
+    zero_based: bool,
+    include_deps: bool,
+    debug_log: Option<Arc<DebugLog>>,
+) -> Result<()> {
+    let cwd = crate::workspace::paths::logical_cwd()?;
+    let runner = crate::batch::BatchRunner {
+        workspace_root,
+        cwd: &cwd,
+        formatter,
+        timeout,
+        zero_based,
+        include_deps,
+        debug_log: debug_log.as_ref(),
+    };
+    runner.run().await
+}
+
+/// Serve the daemon protocol on stdin and stdout (`tyf serve --stdio`).
+#[cfg(unix)]
+pub async fn handle_serve_command() -> Result<()> {
+    DaemonServer::stdio().serve_stdio().await
+}
+
+#[cfg(not(unix))]
+pub async fn handle_serve_command() -> Result<()> {
+    anyhow::bail!(
+        "The 'serve' command runs the daemon's server, which is only supported on Unix systems"
+    )
+}
+
+#[cfg(not(unix))]
+pub async fn handle_batch_command(
+    _workspace_root: &Path,
+    _formatter: &OutputFormatter,
+    _timeout: Duration,
+    _zero_based: bool,
+    _include_deps: bool,
+    _debug_log: Option<Arc<DebugLog>>,
+) -> Result<()> {
+    anyhow::bail!(
+        "The 'batch' command requires the background daemon, which is only supported on Unix systems"
+    )
+}
+
+#[cfg(not(unix))]
+pub async fn handle_mcp_command(
+    _workspace_root: &Path,
+    _formatter: &OutputFormatter,
+    _timeout: Duration,
+    _zero_based: bool,
+    _include_deps: bool,
     _debug_log: Option<Arc<DebugLog>>,
 ) -> Result<()> {
     anyhow::bail!(
-        "The 'members' command requires the background daemon, which is only supported on Unix systems"
+        "The 'mcp' command requires the background daemon, which is only supported on Unix systems"
     )
 }
 
+pub async fn handle_setup_command(
+    workspace_root: &Path,
+    installer: Option<Installer>,
+    dry_run: bool,
+) -> Result<()> {
+    let root = workspace_root.to_str().context("Invalid workspace path")?;
+    if installer.is_none() {
+        if let Ok(found) = TyLspServer::locate(root).await {
+            println!("ty is already available ({found}), nothing to install");
+            return Ok(());
+        }
+    }
+
+    let plan = setup::plan(installer, workspace_root)?;
+    println!("Installing ty {}: {}", plan.target, plan.command_line());
+    if dry_run {
+        return Ok(());
+    }
+    plan.run(workspace_root)?;
+
+    let found = TyLspServer::locate(root).await.context(
+        "ty was installed, but tyf still can't run it. Is its install directory on PATH?",
+    )?;
+    println!("ty is ready ({found})");
+    Ok(())
+}
+
 #[cfg(unix)]
-pub async fn handle_daemon_command(command: DaemonCommands) -> Result<()> {
+pub async fn handle_daemon_command(
+    command: DaemonCommands,
+    workspace_root: &Path,
+    formatter: &OutputFormatter,
+    timeout: Duration,
+) -> Result<()> {
     match command {
         DaemonCommands::Start { foreground } => {
             if foreground {
@@ -1422,53 +6217,245 @@ pub async fn handle_daemon_command(command: DaemonCommands) -> Result<()> {
             }
         }
 
+        DaemonCommands::Reload => reload_daemon_config().await?,
+
         DaemonCommands::Status => match DaemonClient::connect().await {
-            Ok(mut client) => {
-                let status = client.ping().await?;
-                let uptime_secs = status.uptime;
-                let mins = uptime_secs / 60;
-                let secs = uptime_secs % 60;
-                let uptime_str =
-                    if mins > 0 { format!("{mins}m {secs}s") } else { format!("{secs}s") };
-
-                println!("Daemon running (v{})", status.version);
-                if status.version != CLIENT_VERSION {
-                    println!(
-                        "  ⚠ Version mismatch: daemon v{}, client v{} — run `tyf daemon restart` to update",
-                        status.version, CLIENT_VERSION,
-                    );
-                }
-                println!("PID: {}", status.pid);
-                if let Some(ref cwd) = status.cwd {
-                    println!("  Working dir: {cwd}");
-                }
-                if let Some(ref sock) = status.socket_path {
-                    println!("  Unix socket: {sock}");
-                }
-                if let Some(port) = status.tcp_port {
-                    println!("  TCP: 127.0.0.1:{port}");
-                }
-                println!("  Uptime: {uptime_str}");
-                println!("  Active workspaces: {}", status.active_workspaces);
-                if !status.workspace_paths.is_empty() {
-                    for ws in &status.workspace_paths {
-                        println!("    - {ws}  (src.include: [\"**\"] overridden)");
-                    }
-                }
-            }
+            Ok(mut client) => print_daemon_status(&client.ping().await?),
             Err(_) => {
                 println!("Daemon: not running");
             }
         },
+
+        DaemonCommands::Stats => {
+            let mut client = DaemonClient::connect()
+                .await
+                .map_err(|_| DaemonFailure::wrap(anyhow::anyhow!("Daemon is not running")))?;
+            println!("{}", formatter.format_daemon_stats(&client.execute_stats().await?));
+        }
+
+        DaemonCommands::Warm { paths, wait } => {
+            warm_workspaces(&paths, wait, workspace_root, timeout).await?;
+        }
+
+        DaemonCommands::Workspaces => {
+            let mut client = DaemonClient::connect()
+                .await
+                .map_err(|_| DaemonFailure::wrap(anyhow::anyhow!("Daemon is not running")))?;
+            println!("{}", formatter.format_daemon_workspaces(&client.execute_workspaces().await?));
+        }
+
+        DaemonCommands::Drop { workspace, all: _ } => drop_workspace(workspace.as_deref()).await?,
+
+        DaemonCommands::Cache { command: CacheCommands::Clear } => {
+            match DaemonClient::connect().await {
+                Ok(mut client) => {
+                    let result = client.execute_clear_cache().await?;
+                    println!("Cleared {} cached response(s)", result.cleared);
+                }
+                Err(_) => {
+                    println!("Daemon is not running, nothing to clear");
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// `tyf daemon warm`: start the language servers of `paths`' workspaces, or
+/// of `workspace_root` when none are given.
+#[cfg(unix)]
+async fn warm_workspaces(
+    paths: &[PathBuf],
+    wait: bool,
+    workspace_root: &Path,
+    timeout: Duration,
+) -> Result<()> {
+    let workspaces = if paths.is_empty() {
+        vec![workspace_root.to_path_buf()]
+    } else {
+        paths.iter().map(|path| daemon_workspace_root(path)).collect::<Result<Vec<_>>>()?
+    };
+    let mut client = connect_daemon(timeout, None).await?;
+    let result = client.execute_warmup(workspaces, wait).await?;
+    let mut failed = 0;
+    for entry in &result.workspaces {
+        let workspace = &entry.workspace;
+        match (&entry.error, &entry.server, entry.elapsed_ms) {
+            (Some(error), _, _) => {
+                failed += 1;
+                eprintln!("{workspace}: {error}");
+            }
+            (None, Some(server), Some(ms)) if !entry.running => {
+                println!(
+                    "{workspace}: {server} ready in {:.1}s",
+                    Duration::from_millis(ms).as_secs_f64()
+                );
+            }
+            (None, Some(server), _) => println!("{workspace}: {server} ready"),
+            (None, None, _) if entry.running => println!("{workspace}: already running"),
+            (None, None, _) => println!("{workspace}: warming up in the background"),
+        }
+    }
+    if failed > 0 {
+        anyhow::bail!("Failed to warm up {failed} of {} workspace(s)", result.workspaces.len());
+    }
+    Ok(())
+}
+
+/// `tyf daemon drop`: stop the language server of `workspace`'s workspace,
+/// or of every workspace when `None`.
+#[cfg(unix)]
+async fn drop_workspace(workspace: Option<&Path>) -> Result<()> {
+    let workspace = workspace.map(daemon_workspace_root).transpose()?;
+    let Ok(mut client) = DaemonClient::connect().await else {
+        println!("Daemon is not running, nothing to drop");
+        return Ok(());
+    };
+    let result = client.execute_drop_workspace(workspace.clone()).await?;
+    match (&workspace, result.dropped.as_slice()) {
+        (Some(workspace), []) => {
+            println!("No language server is running for {}", workspace.display());
+        }
+        (Some(_), [dropped]) => println!("Dropped {dropped}"),
+        _ => println!("Dropped {} workspace(s)", result.dropped.len()),
     }
+    Ok(())
+}
 
+/// `tyf daemon reload`: have the running daemon re-read its settings.
+#[cfg(unix)]
+async fn reload_daemon_config() -> Result<()> {
+    let Ok(mut client) = DaemonClient::connect().await else {
+        println!("Daemon is not running; it reads the config when it starts");
+        return Ok(());
+    };
+    let result = client.execute_reload_config().await?;
+    println!(
+        "Reloaded config (idle timeout {}s, cache size {})",
+        result.idle_timeout, result.cache_size
+    );
+    if result.evicted > 0 {
+        println!("  Dropped {} cached response(s) over the new cache size", result.evicted);
+    }
+    for workspace in &result.restarted {
+        println!("  Backend changed, restarting on next use: {workspace}");
+    }
+    for workspace in &result.reindexed {
+        println!("  Excludes changed, rebuilding symbol index: {workspace}");
+    }
     Ok(())
 }
 
+/// The workspace root a path given to `tyf daemon warm` or `tyf daemon drop`
+/// belongs to.
+#[cfg(unix)]
+fn daemon_workspace_root(path: &Path) -> Result<PathBuf> {
+    let path = crate::lsp::uri::canonicalize(path)
+        .with_context(|| format!("Can't find workspace {}", path.display()))?;
+    let dir = if path.is_file() {
+        path.parent().map_or_else(|| path.clone(), Path::to_path_buf)
+    } else {
+        path
+    };
+    Ok(crate::workspace::detection::WorkspaceDetector::find_workspace_root(&dir).unwrap_or(dir))
+}
+
+/// Print the human-readable `tyf daemon status` report.
+#[cfg(unix)]
+fn print_daemon_status(status: &crate::daemon::protocol::PingResult) {
+    let uptime_secs = status.uptime;
+    let mins = uptime_secs / 60;
+    let secs = uptime_secs % 60;
+    let uptime_str = if mins > 0 { format!("{mins}m {secs}s") } else { format!("{secs}s") };
+
+    println!("Daemon running (v{})", status.version);
+    if status.version != CLIENT_VERSION {
+        println!(
+            "  ⚠ Version mismatch: daemon v{}, client v{} — run `tyf daemon restart` to update",
+            status.version, CLIENT_VERSION,
+        );
+    }
+    println!("PID: {}", status.pid);
+    if let Some(ref cwd) = status.cwd {
+        println!("  Working dir: {cwd}");
+    }
+    if let Some(ref sock) = status.socket_path {
+        println!("  Unix socket: {sock}");
+    }
+    if let Some(port) = status.tcp_port {
+        println!("  TCP: 127.0.0.1:{port}");
+    }
+    println!("  Uptime: {uptime_str}");
+    println!("  Cached responses: {}", status.cache_size);
+    println!("  Active workspaces: {}", status.active_workspaces);
+    if !status.workspace_paths.is_empty() {
+        for ws in &status.workspace_paths {
+            match status.ty_servers.iter().find(|s| &s.workspace == ws) {
+                Some(summary) => println!("    - {ws}  ({})", server_health(summary)),
+                None => println!("    - {ws}"),
+            }
+        }
+    }
+    if status.lsp_restarts > 0 {
+        println!("  ty restarts: {}", status.lsp_restarts);
+    }
+}
+
+/// A workspace's server in `tyf daemon status`: its label, plus whether it
+/// died and how often it was restarted.
+#[cfg(unix)]
+fn server_health(summary: &crate::daemon::protocol::TyServerSummary) -> String {
+    use std::fmt::Write as _;
+
+    let mut health = summary.server.clone();
+    if summary.exited {
+        health.push_str(", exited; restarts on the next request");
+    }
+    if summary.restarts > 0 {
+        let _ = write!(health, ", restarted {} time(s)", summary.restarts);
+    }
+    health
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[cfg(unix)]
+    #[test]
+    fn server_health_mentions_exits_and_restarts() {
+        let mut summary = crate::daemon::protocol::TyServerSummary {
+            workspace: "/proj".to_string(),
+            server: "ty 0.0.2".to_string(),
+            restarts: 0,
+            exited: false,
+        };
+        assert_eq!(server_health(&summary), "ty 0.0.2");
+        summary.restarts = 2;
+        summary.exited = true;
+        assert_eq!(
+            server_health(&summary),
+            "ty 0.0.2, exited; restarts on the next request, restarted 2 time(s)"
+        );
+    }
+
+    #[test]
+    fn test_check_alias() {
+        assert!(check_alias("hotspots", "refs --count --sort count {1}").is_ok());
+        assert!(check_alias("h", "symbols handle_").is_ok());
+        for (name, command) in [
+            ("refs", "refs --count {1}"),
+            ("h", "tyf symbols handle_"),
+            ("h", ""),
+            ("h", "run other"),
+            ("two words", "symbols x"),
+        ] {
+            let error = check_alias(name, command).unwrap_err();
+            assert!(error.downcast_ref::<UsageError>().is_some(), "{name}: {error}");
+        }
+    }
+
     #[test]
     fn test_is_test_file_test_prefix() {
         assert!(is_test_file("file:///project/test_utils.py"));
@@ -1573,6 +6560,28 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_parse_file_line() {
+        assert_eq!(parse_file_line("src/app.py:42"), Some(("src/app.py".to_string(), 42)));
+        assert_eq!(parse_file_line(r"C:\src\app.py:7"), Some((r"C:\src\app.py".to_string(), 7)));
+        assert_eq!(parse_file_line("src/app.py"), None);
+        assert_eq!(parse_file_line(":3"), None);
+    }
+
+    #[test]
+    fn test_parse_file_position_windows_paths() {
+        // The drive-letter colon must not be mistaken for a separator.
+        assert_eq!(
+            parse_file_position(r"C:\src\app.py:10:5"),
+            Some((r"C:\src\app.py".to_string(), 10, 5))
+        );
+        assert_eq!(
+            parse_file_position("c:/src/app.py:1:2"),
+            Some(("c:/src/app.py".to_string(), 1, 2))
+        );
+        assert_eq!(parse_file_position(r"C:\src\app.py:10"), None);
+    }
+
     #[test]
     fn test_parse_file_position_symbol_names() {
         assert_eq!(parse_file_position("my_function"), None);
@@ -1591,54 +6600,314 @@ mod tests {
         assert_eq!(parse_file_position("file.py:10:abc"), None);
     }
 
-    #[tokio::test]
-    async fn test_find_name_column_class() {
+    #[test]
+    fn test_input_to_lsp_one_based_default() {
+        assert_eq!(input_to_lsp(10, false), 9);
+        assert_eq!(input_to_lsp(0, false), 0);
+    }
+
+    #[test]
+    fn test_input_to_lsp_zero_based_passthrough() {
+        assert_eq!(input_to_lsp(10, true), 10);
+        assert_eq!(input_to_lsp(0, true), 0);
+    }
+
+    #[test]
+    fn test_extract_type_names_from_signature() {
+        assert_eq!(
+            extract_type_names("def create_order(payload: OrderPayload) -> Order | None"),
+            vec!["OrderPayload".to_string(), "Order".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_extract_type_names_skips_typing_and_type_vars() {
+        assert_eq!(
+            extract_type_names("def f(x: Optional[T], y: dict[str, Any]) -> list[LineItem]"),
+            vec!["LineItem".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_extract_type_names_dotted_uses_last_segment() {
+        assert_eq!(extract_type_names("x: models.Customer"), vec!["Customer".to_string()]);
+    }
+
+    #[test]
+    fn test_classify_type_definition_class_bases() {
+        let class = SymbolKind::Class;
+        assert_eq!(
+            classify_type_definition(&class, "class Payload(TypedDict, total=False):"),
+            Some("TypedDict")
+        );
+        assert_eq!(
+            classify_type_definition(&class, "class Sink(typing.Protocol):"),
+            Some("Protocol")
+        );
+        assert_eq!(classify_type_definition(&class, "class Order(Base):"), Some("class"));
+        assert_eq!(classify_type_definition(&class, "class Plain:"), Some("class"));
+    }
+
+    #[test]
+    fn test_classify_type_definition_aliases() {
+        let var = SymbolKind::Variable;
+        assert_eq!(
+            classify_type_definition(&var, "UserId = NewType('UserId', int)"),
+            Some("NewType")
+        );
+        assert_eq!(classify_type_definition(&var, "Ref = int | str"), Some("type alias"));
+        assert_eq!(classify_type_definition(&SymbolKind::Function, "def f():"), None);
+    }
+
+    #[test]
+    fn test_find_word_in_lines_skips_longer_names() {
+        let text = SourceFile::new(
+            "def pay(order: OrderItem,\n        total: Order) -> Order:\n    Order()\n".to_string(),
+        );
+        assert_eq!(find_word_in_lines(&text, "Order", (0, 1)), Some((1, 15)));
+        assert_eq!(find_word_in_lines(&text, "Order", (0, 0)), None);
+        assert_eq!(find_word_in_lines(&text, "OrderItem", (0, 2)), Some((0, 15)));
+    }
+
+    #[test]
+    fn test_type_definition_text_spans_the_statement() {
+        use crate::lsp::protocol::{Position, Range};
+
+        let text = SourceFile::new(
+            "Payload = Union[\n    Order,\n    Refund,\n]\n\nclass Order(\n    Base,\n):\n    id: int\n"
+                .to_string(),
+        );
+        let symbol = |kind, start, end| {
+            let range = |line| Range {
+                start: Position { line, character: 0 },
+                end: Position { line, character: 0 },
+            };
+            DocumentSymbol {
+                name: String::new(),
+                detail: None,
+                kind,
+                tags: None,
+                deprecated: None,
+                range: Range { start: range(start).start, end: range(end).end },
+                selection_range: range(start),
+                children: None,
+            }
+        };
+        assert_eq!(
+            type_definition_text(&text, &symbol(SymbolKind::Variable, 0, 4)),
+            "Payload = Union[\n    Order,\n    Refund,\n]"
+        );
+        assert_eq!(
+            type_definition_text(&text, &symbol(SymbolKind::Class, 5, 9)),
+            "class Order(\n    Base,\n):"
+        );
+    }
+
+    #[test]
+    fn test_definition_label_relative_to_workspace() {
+        let root = Path::new("/project");
+        assert_eq!(
+            definition_label("Config", "/project/app/settings.py", 11, root),
+            "Config (app/settings.py:12)"
+        );
+    }
+
+    #[test]
+    fn test_definition_label_outside_workspace_keeps_path() {
+        let root = Path::new("/project");
+        assert_eq!(
+            definition_label("Config", "/other/lib/config.py", 0, root),
+            "Config (/other/lib/config.py:1)"
+        );
+    }
+
+    #[test]
+    fn test_parse_match_choice() {
+        assert_eq!(parse_match_choice("\n", 3), Some(MatchChoice::Select(1)));
+        assert_eq!(parse_match_choice(" 3 \n", 3), Some(MatchChoice::Select(3)));
+        assert_eq!(parse_match_choice("All", 3), Some(MatchChoice::All));
+        assert_eq!(parse_match_choice("4", 3), None);
+        assert_eq!(parse_match_choice("0", 3), None);
+        assert_eq!(parse_match_choice("second", 3), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_choose_matches() {
+        let root = Path::new("/project");
+        let config = |file: &str| SymbolMatch {
+            file: file.to_string(),
+            line: 4,
+            column: 6,
+            symbol: Some(SymbolInformation {
+                name: "Config".to_string(),
+                kind: SymbolKind::Class,
+                tags: None,
+                deprecated: None,
+                location: Location {
+                    uri: path_to_uri(Path::new(file)),
+                    range: crate::lsp::protocol::Range {
+                        start: crate::lsp::protocol::Position { line: 4, character: 0 },
+                        end: crate::lsp::protocol::Position { line: 4, character: 12 },
+                    },
+                },
+                container_name: None,
+            }),
+        };
+        let both = || vec![config("/project/app/config.py"), config("/project/billing/config.py")];
+
+        let all = choose_matches("Config", both(), MatchChoice::All, root).unwrap();
+        let labels: Vec<&str> = all.iter().map(|(label, _)| label.as_str()).collect();
+        assert_eq!(labels, ["Config (app/config.py:5)", "Config (billing/config.py:5)"]);
+
+        let second = choose_matches("Config", both(), MatchChoice::Select(2), root).unwrap();
+        assert_eq!(second.len(), 1);
+        assert_eq!(second[0].0, "Config");
+        assert_eq!(second[0].1[0].file, "/project/billing/config.py");
+
+        let Err(err) = choose_matches("Config", both(), MatchChoice::Select(3), root) else {
+            panic!("--select 3 of 2 matches should fail");
+        };
+        assert!(err.to_string().contains("matches 2 symbols"), "got: {err}");
+
+        // A single match is kept whatever was asked for.
+        let one = vec![config("/project/app/config.py")];
+        let kept = choose_matches("Config", one, MatchChoice::Select(3), root).unwrap();
+        assert_eq!((kept.len(), kept[0].1.len()), (1, 1));
+    }
+
+    /// `find_name_column` in `text`, searching from its first line.
+    fn name_column(text: &str, name: &str) -> Option<(u32, u32)> {
+        let sources = SourceCache::from_entries([("/p/test.py".to_string(), text.to_string())]);
+        find_name_column(&sources, "/p/test.py", 0, name)
+    }
+
+    #[test]
+    fn test_find_name_column_class() {
         // "class Animal:" — "Animal" starts at line 0 column 6
-        let dir = tempfile::tempdir().unwrap();
-        let file = dir.path().join("test.py");
-        std::fs::write(&file, "class Animal:\n    pass\n").unwrap();
-        assert_eq!(find_name_column(file.to_str().unwrap(), 0, "Animal").await, Some((0, 6)));
+        assert_eq!(name_column("class Animal:\n    pass\n", "Animal"), Some((0, 6)));
     }
 
-    #[tokio::test]
-    async fn test_find_name_column_function() {
+    #[test]
+    fn test_find_name_column_function() {
         // "def create_dog(name):" — "create_dog" starts at line 0 column 4
-        let dir = tempfile::tempdir().unwrap();
-        let file = dir.path().join("test.py");
-        std::fs::write(&file, "def create_dog(name):\n    pass\n").unwrap();
-        assert_eq!(find_name_column(file.to_str().unwrap(), 0, "create_dog").await, Some((0, 4)));
+        assert_eq!(name_column("def create_dog(name):\n    pass\n", "create_dog"), Some((0, 4)));
+    }
+
+    #[test]
+    fn test_find_name_column_after_non_ascii_text() {
+        // "ñ" is 2 bytes but 1 UTF-16 unit, so the byte offset (9) must become column 8
+        assert_eq!(name_column("año = 1; Animal = 2\n", "Animal"), Some((0, 9)));
     }
 
     #[tokio::test]
-    async fn test_find_name_column_not_found() {
+    async fn test_user_position_to_lsp_counts_characters() {
         let dir = tempfile::tempdir().unwrap();
-        let file = dir.path().join("test.py");
-        std::fs::write(&file, "x = 1\n").unwrap();
-        assert_eq!(find_name_column(file.to_str().unwrap(), 0, "Animal").await, None);
+        std::fs::write(dir.path().join("m.py"), "x = \"🎉\"; target = 1\n").unwrap();
+        // 1-based character column 12 ("t") is UTF-16 column 12 (0-based) after the emoji
+        assert_eq!(user_position_to_lsp(dir.path(), "m.py", 1, 12, false).await, (0, 12));
+        assert_eq!(user_position_to_lsp(dir.path(), "m.py", 0, 11, true).await, (0, 12));
+        // Unreadable files pass the column through
+        assert_eq!(user_position_to_lsp(dir.path(), "missing.py", 3, 5, false).await, (2, 4));
     }
 
-    #[tokio::test]
-    async fn test_find_name_column_nonexistent_file() {
-        assert_eq!(find_name_column("/nonexistent/file.py", 0, "Animal").await, None);
+    #[test]
+    fn test_text_position_to_lsp_uses_the_given_text() {
+        let buffer = "import os\nx = \"🎉\"; target = 1\n";
+        assert_eq!(text_position_to_lsp(buffer, 2, 12, false), (1, 12));
+        assert_eq!(text_position_to_lsp(buffer, 9, 3, false), (8, 2), "past the end");
     }
 
-    #[tokio::test]
-    async fn test_find_name_column_decorated_class() {
+    #[test]
+    fn test_find_name_column_not_found() {
+        assert_eq!(name_column("x = 1\n", "Animal"), None);
+    }
+
+    #[test]
+    fn test_find_name_column_nonexistent_file() {
+        assert_eq!(
+            find_name_column(&SourceCache::new(), "/nonexistent/file.py", 0, "Animal"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_find_name_column_decorated_class() {
         // Workspace symbol points at line 0 (@dataclass), but name is on line 1
-        let dir = tempfile::tempdir().unwrap();
-        let file = dir.path().join("test.py");
-        std::fs::write(&file, "@dataclass\nclass Config:\n    host: str\n").unwrap();
-        assert_eq!(find_name_column(file.to_str().unwrap(), 0, "Config").await, Some((1, 6)));
+        assert_eq!(
+            name_column("@dataclass\nclass Config:\n    host: str\n", "Config"),
+            Some((1, 6))
+        );
     }
 
-    #[tokio::test]
-    async fn test_find_name_column_multi_decorator() {
+    #[test]
+    fn test_find_name_column_multi_decorator() {
         // Multiple decorators stacked
-        let dir = tempfile::tempdir().unwrap();
-        let file = dir.path().join("test.py");
-        std::fs::write(&file, "@some_decorator\n@another_decorator\ndef my_func():\n    pass\n")
-            .unwrap();
-        assert_eq!(find_name_column(file.to_str().unwrap(), 0, "my_func").await, Some((2, 4)));
+        let text = "@some_decorator\n@another_decorator\ndef my_func():\n    pass\n";
+        assert_eq!(name_column(text, "my_func"), Some((2, 4)));
+    }
+
+    #[test]
+    fn test_definition_body_spans_decorators_and_body() {
+        use crate::lsp::protocol::{Position, Range};
+
+        let text = SourceFile::new(
+            "import os\n\n@cache\ndef load(path):\n    return os.read(path)\n\n\nLIMIT = 3\n"
+                .to_string(),
+        );
+        let range = |line, start, end_line, end| Range {
+            start: Position { line, character: start },
+            end: Position { line: end_line, character: end },
+        };
+        let outline = vec![DocumentSymbol {
+            name: "load".to_string(),
+            detail: None,
+            kind: SymbolKind::Function,
+            tags: None,
+            deprecated: None,
+            range: range(3, 0, 6, 0),
+            selection_range: range(3, 4, 3, 8),
+            children: None,
+        }];
+        let at = |line, character| Location {
+            uri: "file:///app.py".to_string(),
+            range: range(line, character, line, character + 4),
+        };
+
+        let body = definition_body(&outline, &text, at(3, 4));
+        assert_eq!(body.lines, (2, 4));
+        assert_eq!(body.source, "@cache\ndef load(path):\n    return os.read(path)");
+        assert_eq!(body.kind, Some(SymbolKind::Function));
+
+        let constant = definition_body(&outline, &text, at(7, 0));
+        assert_eq!((constant.lines, constant.source.as_str()), ((7, 7), "LIMIT = 3"));
+        assert_eq!(constant.kind, None);
+    }
+
+    #[test]
+    fn test_sort_locations() {
+        use crate::lsp::protocol::{Position, Range};
+
+        let loc = |uri: &str, line| Location {
+            uri: format!("file:///{uri}"),
+            range: Range {
+                start: Position { line, character: 0 },
+                end: Position { line, character: 5 },
+            },
+        };
+        let reported = vec![loc("b.py", 9), loc("a.py", 4), loc("b.py", 2), loc("c.py", 1)];
+        let sorted = |sort| {
+            let mut locations = reported.clone();
+            sort_locations(&mut locations, sort);
+            locations
+                .iter()
+                .map(|l| format!("{}:{}", &l.uri[8..], l.range.start.line))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(sorted(ReferenceSort::Path), ["a.py:4", "b.py:2", "b.py:9", "c.py:1"]);
+        assert_eq!(sorted(ReferenceSort::Line), ["b.py:2", "b.py:9", "a.py:4", "c.py:1"]);
+        assert_eq!(sorted(ReferenceSort::Count), ["b.py:2", "b.py:9", "a.py:4", "c.py:1"]);
     }
 
     #[test]
@@ -1745,6 +7014,51 @@ mod tests {
         assert_eq!(locations[0].range.start.character, 0, "first occurrence should be preserved");
     }
 
+    #[test]
+    fn test_unique_references_across_queries() {
+        use crate::lsp::protocol::{Position, Range};
+
+        let location = |uri: &str, line, character| Location {
+            uri: uri.to_string(),
+            range: Range {
+                start: Position { line, character },
+                end: Position { line, character: character + 5 },
+            },
+        };
+        let entry = |label: &str, locations| BatchReferencesEntry {
+            label: label.to_string(),
+            locations,
+            files: Vec::new(),
+            remaining: 0,
+        };
+        let shared = vec![location("file:///a.py", 1, 0), location("file:///b.py", 3, 4)];
+        let entries = vec![
+            entry("Order", shared.clone()),
+            entry("OrderAlias", shared.into_iter().rev().collect()),
+            entry(
+                "Customer",
+                vec![
+                    location("file:///a.py", 1, 0),
+                    location("file:///a.py", 1, 12),
+                    location("file:///c.py", 7, 0),
+                ],
+            ),
+        ];
+        let unique = unique_references(entries);
+        let summary: Vec<(&str, Vec<(u32, u32)>)> = unique
+            .iter()
+            .map(|e| {
+                let positions =
+                    e.locations.iter().map(|l| (l.range.start.line, l.range.start.character));
+                (e.label.as_str(), positions.collect())
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            [("Order, OrderAlias", vec![(1, 0), (3, 4)]), ("Customer", vec![(1, 12), (7, 0)])]
+        );
+    }
+
     #[test]
     fn test_count_unique_files_distinct() {
         use crate::lsp::protocol::{Position, Range};
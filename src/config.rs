@@ -0,0 +1,528 @@
+//! tyf's own settings: defaults for command-line flags and the daemon.
+//!
+//! Settings come from, most specific first: the command line, `.ty-find.toml`
+//! in the workspace root, `[tool.ty-find]` in its `pyproject.toml`, and the
+//! user config file (see [`user_path`]). Each key is taken from the most
+//! specific source that sets it; `exclude` patterns from every source apply
+//! together, and `aliases` are merged by name.
+//!
+//! ```toml
+//! # .ty-find.toml
+//! format = "json"
+//! timeout = 60
+//! backend = "pyright"
+//! exclude = ["build", "vendor"]
+//! roots = ["packages/api", "packages/worker"]
+//!
+//! [aliases]
+//! handlers = "symbols handle_ --kind function"
+//! ```
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+
+/// The workspace config file, in the workspace root.
+pub const WORKSPACE_FILE: &str = ".ty-find.toml";
+
+/// Environment variable naming the user config file, overriding the default
+/// location.
+pub const CONFIG_ENV: &str = "TYF_CONFIG";
+
+/// How long the daemon waits with nothing to do before it exits, unless the
+/// user config sets `idle-timeout`.
+pub const DEFAULT_IDLE_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// Environment variable overriding `idle-timeout` for a daemon started with
+/// it, which `--daemon-ttl` sets.
+pub const DAEMON_TTL_ENV: &str = "TY_FIND_DAEMON_TTL";
+
+/// How many responses the daemon keeps cached across all workspaces, unless
+/// the user config sets `cache-size`.
+pub const DEFAULT_CACHE_SIZE: usize = 4096;
+
+/// The settings one source sets; keys it leaves out are `None` or empty.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Settings {
+    /// Default for `--format`
+    pub format: Option<String>,
+    /// Default for `--timeout`, in seconds
+    pub timeout: Option<u64>,
+    /// Seconds the daemon stays up with no workspace in use, or a duration
+    /// like `"30m"`; only read from the user config, since one daemon serves
+    /// every workspace
+    #[serde(default, deserialize_with = "seconds")]
+    pub idle_timeout: Option<u64>,
+    /// Hover, definition and document symbol answers the daemon keeps
+    /// cached; only read from the user config, like `idle_timeout`
+    pub cache_size: Option<usize>,
+    /// Default for `--backend`
+    pub backend: Option<String>,
+    /// Default for `--ty-path`; relative paths are relative to the workspace root
+    pub ty_path: Option<PathBuf>,
+    /// Patterns left out of the workspace, added to ty's `src.exclude`
+    #[serde(default)]
+    pub exclude: Vec<String>,
+    /// Package directories of a monorepo, relative to the workspace root,
+    /// served by one language server as its workspace folders
+    pub roots: Option<Vec<String>>,
+    /// Commands run by name, e.g. `handlers = "symbols handle_ --kind function"`
+    #[serde(default)]
+    pub aliases: BTreeMap<String, String>,
+}
+
+/// Where a layer of settings came from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Source {
+    User(PathBuf),
+    Pyproject(PathBuf),
+    Workspace(PathBuf),
+    /// An environment variable, e.g. `TYF_BACKEND`.
+    Environment(&'static str),
+    CommandLine,
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::User(path) | Self::Workspace(path) => write!(f, "{}", path.display()),
+            Self::Pyproject(path) => write!(f, "{} [tool.ty-find]", path.display()),
+            Self::Environment(name) => f.write_str(name),
+            Self::CommandLine => f.write_str("command line"),
+        }
+    }
+}
+
+/// The settings from one source.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Layer {
+    pub source: Source,
+    pub settings: Settings,
+}
+
+/// Every source of settings for a workspace, least specific first.
+#[derive(Debug, Clone, Default)]
+pub struct Config {
+    layers: Vec<Layer>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct RawPyproject {
+    #[serde(default)]
+    tool: RawTool,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct RawTool {
+    #[serde(rename = "ty-find")]
+    ty_find: Option<Settings>,
+}
+
+/// The user config file: `TYF_CONFIG` if set, else `ty-find/config.toml`
+/// under `$XDG_CONFIG_HOME` or `~/.config` (`%APPDATA%` on Windows).
+pub fn user_path() -> Option<PathBuf> {
+    if let Some(path) = std::env::var_os(CONFIG_ENV).filter(|p| !p.is_empty()) {
+        return Some(PathBuf::from(path));
+    }
+    let dir = if cfg!(windows) {
+        std::env::var_os("APPDATA").map(PathBuf::from)
+    } else {
+        std::env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .filter(|dir| dir.is_absolute())
+            .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".config")))
+    };
+    dir.map(|dir| dir.join("ty-find").join("config.toml"))
+}
+
+/// A duration given as seconds (`300`) or with a unit: `90s`, `30m`, `2h`.
+pub fn parse_duration(text: &str) -> Result<Duration, String> {
+    let text = text.trim();
+    let split = text.find(|c: char| !c.is_ascii_digit()).unwrap_or(text.len());
+    let (number, unit) = text.split_at(split);
+    let scale = match unit.trim() {
+        "" | "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        _ => return Err(format!("'{text}' is not a duration; use seconds or e.g. 90s, 30m, 2h")),
+    };
+    match number.parse::<u64>() {
+        Ok(0) => Err("a duration must be longer than 0".to_string()),
+        Ok(n) => Ok(Duration::from_secs(n.saturating_mul(scale))),
+        Err(_) => Err(format!("'{text}' is not a duration; use seconds or e.g. 90s, 30m, 2h")),
+    }
+}
+
+/// Deserialize a number of seconds, or a duration [`parse_duration`] reads.
+fn seconds<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<Option<u64>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Raw {
+        Seconds(u64),
+        Text(String),
+    }
+    match Raw::deserialize(deserializer)? {
+        Raw::Seconds(secs) => Ok(Some(secs)),
+        Raw::Text(text) => parse_duration(&text)
+            .map(|duration| Some(duration.as_secs()))
+            .map_err(serde::de::Error::custom),
+    }
+}
+
+/// Parse `path` as a config file, `None` if it doesn't exist.
+fn read_file(path: &Path) -> Result<Option<Settings>> {
+    if !path.is_file() {
+        return Ok(None);
+    }
+    let text = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    let settings =
+        toml::from_str(&text).with_context(|| format!("Invalid config in {}", path.display()))?;
+    Ok(Some(settings))
+}
+
+/// `[tool.ty-find]` in the `pyproject.toml` at `path`, if both exist.
+fn read_pyproject(path: &Path) -> Result<Option<Settings>> {
+    if !path.is_file() {
+        return Ok(None);
+    }
+    let text = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    let raw: RawPyproject = toml::from_str(&text)
+        .with_context(|| format!("Invalid [tool.ty-find] config in {}", path.display()))?;
+    Ok(raw.tool.ty_find)
+}
+
+/// Set the alias `name` to `command` in the config file at `path`, or remove
+/// it when `command` is `None`, keeping the rest of the file as written.
+///
+/// The file (and its directory) is created if needed; removing an alias
+/// that isn't there leaves it untouched. Returns the command `name` stood
+/// for before, if any.
+pub fn write_alias(path: &Path, name: &str, command: Option<&str>) -> Result<Option<String>> {
+    let text = match std::fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e).with_context(|| format!("Failed to read {}", path.display())),
+    };
+    let mut doc: toml_edit::DocumentMut =
+        text.parse().with_context(|| format!("Invalid config in {}", path.display()))?;
+    let not_a_table = || format!("`aliases` in {} is not a table", path.display());
+    let previous = match command {
+        Some(command) => doc
+            .entry("aliases")
+            .or_insert_with(toml_edit::table)
+            .as_table_like_mut()
+            .with_context(not_a_table)?
+            .insert(name, toml_edit::value(command)),
+        None => match doc.get_mut("aliases") {
+            Some(aliases) => aliases.as_table_like_mut().with_context(not_a_table)?.remove(name),
+            None => None,
+        },
+    };
+    if command.is_none() && previous.is_none() {
+        return Ok(None);
+    }
+    if let Some(parent) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create {}", parent.display()))?;
+    }
+    let written = doc.to_string();
+    // A table added to an empty document comes with a blank line before it.
+    let written = if text.trim().is_empty() { written.trim_start() } else { &written };
+    std::fs::write(path, written).with_context(|| format!("Failed to write {}", path.display()))?;
+    Ok(previous.and_then(|item| item.as_str().map(String::from)))
+}
+
+impl Config {
+    /// Read the user config and `workspace_root`'s config files.
+    ///
+    /// Missing files are skipped; malformed ones, or ones with unknown keys,
+    /// are errors.
+    pub fn load(workspace_root: &Path) -> Result<Self> {
+        Self::load_with_user(workspace_root, user_path().as_deref())
+    }
+
+    /// Like [`Config::load`], but falls back to no configuration (and logs
+    /// why) so a broken config file never stops a lookup.
+    pub fn load_or_default(workspace_root: &Path) -> Self {
+        Self::load(workspace_root).unwrap_or_else(|e| {
+            tracing::warn!("Ignoring tyf configuration: {e:#}");
+            Self::default()
+        })
+    }
+
+    /// Only the user config file, for settings that don't belong to a workspace.
+    pub fn user() -> Result<Self> {
+        let mut config = Self::default();
+        if let Some(path) = user_path() {
+            config.push_file(Source::User(path), read_file)?;
+        }
+        Ok(config)
+    }
+
+    fn load_with_user(workspace_root: &Path, user: Option<&Path>) -> Result<Self> {
+        let mut config = Self::default();
+        if let Some(path) = user {
+            config.push_file(Source::User(path.to_path_buf()), read_file)?;
+        }
+        let pyproject = workspace_root.join("pyproject.toml");
+        config.push_file(Source::Pyproject(pyproject), read_pyproject)?;
+        config.push_file(Source::Workspace(workspace_root.join(WORKSPACE_FILE)), read_file)?;
+        Ok(config)
+    }
+
+    fn push_file(
+        &mut self,
+        source: Source,
+        read: fn(&Path) -> Result<Option<Settings>>,
+    ) -> Result<()> {
+        let path = match &source {
+            Source::User(path) | Source::Pyproject(path) | Source::Workspace(path) => path,
+            Source::Environment(_) | Source::CommandLine => return Ok(()),
+        };
+        if let Some(settings) = read(path)? {
+            self.layers.push(Layer { source, settings });
+        }
+        Ok(())
+    }
+
+    /// The same config with `settings` from `source` taking precedence.
+    #[must_use]
+    pub fn with_layer(mut self, source: Source, settings: Settings) -> Self {
+        self.layers.push(Layer { source, settings });
+        self
+    }
+
+    /// The layers read, least specific first.
+    pub fn layers(&self) -> &[Layer] {
+        &self.layers
+    }
+
+    /// The value the most specific layer sets for a key, and that layer's source.
+    pub fn lookup<'a, T>(
+        &'a self,
+        get: impl Fn(&'a Settings) -> Option<T>,
+    ) -> Option<(T, &'a Source)> {
+        self.layers.iter().rev().find_map(|layer| get(&layer.settings).map(|v| (v, &layer.source)))
+    }
+
+    pub fn format(&self) -> Option<&str> {
+        self.lookup(|s| s.format.as_deref()).map(|(format, _)| format)
+    }
+
+    pub fn timeout(&self) -> Option<Duration> {
+        self.lookup(|s| s.timeout).map(|(secs, _)| Duration::from_secs(secs))
+    }
+
+    pub fn backend(&self) -> Option<&str> {
+        self.lookup(|s| s.backend.as_deref()).map(|(backend, _)| backend)
+    }
+
+    /// The ty executable to run, with a relative `ty-path` resolved against
+    /// `workspace_root`.
+    pub fn ty_path(&self, workspace_root: &Path) -> Option<PathBuf> {
+        self.lookup(|s| s.ty_path.as_deref()).map(|(path, _)| workspace_root.join(path))
+    }
+
+    /// `idle-timeout` from `TY_FIND_DAEMON_TTL` or else the user config, and
+    /// where it was set.
+    pub fn idle_timeout(&self) -> (Duration, Option<&Source>) {
+        const FROM_ENV: &Source = &Source::Environment(DAEMON_TTL_ENV);
+        if let Ok(value) = std::env::var(DAEMON_TTL_ENV) {
+            match parse_duration(&value) {
+                Ok(ttl) => return (ttl, Some(FROM_ENV)),
+                Err(e) => tracing::warn!("Ignoring {DAEMON_TTL_ENV}: {e}"),
+            }
+        }
+        self.user_setting(|s| s.idle_timeout)
+            .map_or((DEFAULT_IDLE_TIMEOUT, None), |(secs, source)| {
+                (Duration::from_secs(secs), Some(source))
+            })
+    }
+
+    /// `cache-size` from the user config, and where it was set.
+    pub fn cache_size(&self) -> (usize, Option<&Source>) {
+        self.user_setting(|s| s.cache_size)
+            .map_or((DEFAULT_CACHE_SIZE, None), |(size, source)| (size, Some(source)))
+    }
+
+    /// Like [`Config::lookup`], for a daemon-wide setting only the user
+    /// config may set.
+    fn user_setting<T>(&self, get: impl Fn(&Settings) -> Option<T>) -> Option<(T, &Source)> {
+        self.layers
+            .iter()
+            .rev()
+            .filter(|layer| matches!(layer.source, Source::User(_)))
+            .find_map(|layer| get(&layer.settings).map(|value| (value, &layer.source)))
+    }
+
+    /// The `roots` of a multi-root workspace, as paths under `workspace_root`;
+    /// empty for an ordinary workspace.
+    pub fn roots(&self, workspace_root: &Path) -> Vec<PathBuf> {
+        self.lookup(|s| s.roots.as_deref())
+            .map(|(roots, _)| roots.iter().map(|root| workspace_root.join(root)).collect())
+            .unwrap_or_default()
+    }
+
+    /// `exclude` patterns from every layer.
+    pub fn exclude(&self) -> Vec<String> {
+        self.layers.iter().flat_map(|layer| layer.settings.exclude.iter().cloned()).collect()
+    }
+
+    /// Aliases by name, each with its command and where it was defined.
+    pub fn aliases(&self) -> BTreeMap<&str, (&str, &Source)> {
+        let mut aliases = BTreeMap::new();
+        for layer in &self.layers {
+            for (name, command) in &layer.settings.aliases {
+                aliases.insert(name.as_str(), (command.as_str(), &layer.source));
+            }
+        }
+        aliases
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_workspace_file_overrides_pyproject_and_user() {
+        let dir = tempfile::tempdir().unwrap();
+        let user = dir.path().join("user.toml");
+        std::fs::write(
+            &user,
+            "format = \"csv\"\ntimeout = 10\nidle-timeout = 900\nexclude = [\"scratch\"]\n\
+             [aliases]\nh = \"symbols handle_\"\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.path().join("pyproject.toml"),
+            "[project]\nname = \"app\"\n\n[tool.ty-find]\ntimeout = 20\nbackend = \"pylsp\"\n\
+             idle-timeout = 5\ncache-size = 10\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.path().join(WORKSPACE_FILE),
+            "timeout = 30\nty-path = \".tools/ty\"\nexclude = [\"build\"]\n\
+             [aliases]\nh = \"find --fuzzy handle\"\n",
+        )
+        .unwrap();
+
+        let config = Config::load_with_user(dir.path(), Some(&user)).unwrap();
+        assert_eq!(config.layers().len(), 3);
+        assert_eq!(config.format(), Some("csv"));
+        assert_eq!(config.timeout(), Some(Duration::from_secs(30)));
+        assert_eq!(config.backend(), Some("pylsp"));
+        assert_eq!(config.ty_path(dir.path()), Some(dir.path().join(".tools/ty")));
+        assert_eq!(config.exclude(), ["scratch", "build"]);
+        let (idle, source) = config.idle_timeout();
+        assert_eq!(idle, Duration::from_secs(900), "only the user config sets idle-timeout");
+        assert_eq!(source, Some(&Source::User(user)));
+        assert_eq!(config.cache_size(), (DEFAULT_CACHE_SIZE, None));
+
+        let aliases = config.aliases();
+        let (command, source) = aliases["h"];
+        assert_eq!(command, "find --fuzzy handle");
+        assert_eq!(source, &Source::Workspace(dir.path().join(WORKSPACE_FILE)));
+
+        let config = config.with_layer(
+            Source::CommandLine,
+            Settings { format: Some("json".to_string()), ..Settings::default() },
+        );
+        assert_eq!(config.lookup(|s| s.format.as_deref()), Some(("json", &Source::CommandLine)));
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("300"), Ok(Duration::from_secs(300)));
+        assert_eq!(parse_duration("90s"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse_duration("30m"), Ok(Duration::from_secs(1800)));
+        assert_eq!(parse_duration(" 2 h"), Ok(Duration::from_secs(7200)));
+        for bad in ["", "0", "0m", "m", "10x", "1.5h", "-5"] {
+            assert!(parse_duration(bad).is_err(), "{bad:?}");
+        }
+
+        let settings: Settings = toml::from_str("idle-timeout = \"30m\"").unwrap();
+        assert_eq!(settings.idle_timeout, Some(1800));
+        let settings: Settings = toml::from_str("idle-timeout = 900").unwrap();
+        assert_eq!(settings.idle_timeout, Some(900));
+        assert!(toml::from_str::<Settings>("idle-timeout = \"soon\"").is_err());
+    }
+
+    #[test]
+    fn test_missing_files_give_defaults() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("pyproject.toml"), "[project]\nname = \"app\"\n").unwrap();
+        let config =
+            Config::load_with_user(dir.path(), Some(&dir.path().join("none.toml"))).unwrap();
+        assert!(config.layers().is_empty());
+        assert_eq!(config.timeout(), None);
+        assert_eq!(config.idle_timeout(), (DEFAULT_IDLE_TIMEOUT, None));
+        assert_eq!(config.cache_size(), (DEFAULT_CACHE_SIZE, None));
+        assert!(config.aliases().is_empty());
+    }
+
+    #[test]
+    fn test_roots_are_relative_to_the_workspace() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(Config::load_with_user(dir.path(), None).unwrap().roots(dir.path()).is_empty());
+
+        std::fs::write(
+            dir.path().join("pyproject.toml"),
+            "[tool.ty-find]\nroots = [\"packages/api\", \"packages/worker\"]\n",
+        )
+        .unwrap();
+        let config = Config::load_with_user(dir.path(), None).unwrap();
+        assert_eq!(
+            config.roots(dir.path()),
+            [dir.path().join("packages/api"), dir.path().join("packages/worker")]
+        );
+    }
+
+    #[test]
+    fn test_write_alias_keeps_the_rest_of_the_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(WORKSPACE_FILE);
+        std::fs::write(&path, "# team defaults\ntimeout = 30  # ty is slow here\n").unwrap();
+
+        let hot = "refs --count --sort count {1}";
+        assert_eq!(write_alias(&path, "hot", Some(hot)).unwrap(), None);
+        assert_eq!(write_alias(&path, "h", Some("symbols handle_")).unwrap(), None);
+        let previous = write_alias(&path, "h", Some("find handle")).unwrap();
+        assert_eq!(previous.as_deref(), Some("symbols handle_"));
+        let text = std::fs::read_to_string(&path).unwrap();
+        assert!(text.starts_with("# team defaults\ntimeout = 30  # ty is slow here\n"), "{text}");
+
+        let config = Config::load_with_user(dir.path(), None).unwrap();
+        assert_eq!(config.timeout(), Some(Duration::from_secs(30)));
+        let aliases = config.aliases();
+        assert_eq!(aliases["hot"].0, hot);
+        assert_eq!(aliases["h"].0, "find handle");
+
+        assert_eq!(write_alias(&path, "h", None).unwrap().as_deref(), Some("find handle"));
+        assert_eq!(write_alias(&path, "h", None).unwrap(), None);
+        let config = Config::load_with_user(dir.path(), None).unwrap();
+        assert_eq!(config.aliases().keys().copied().collect::<Vec<_>>(), ["hot"]);
+
+        let nested = dir.path().join("user/ty-find/config.toml");
+        write_alias(&nested, "h", Some("symbols handle_")).unwrap();
+        let text = std::fs::read_to_string(&nested).unwrap();
+        assert_eq!(text, "[aliases]\nh = \"symbols handle_\"\n");
+        assert_eq!(read_file(&nested).unwrap().unwrap().aliases["h"], "symbols handle_");
+    }
+
+    #[test]
+    fn test_unknown_keys_are_errors() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join(WORKSPACE_FILE), "timeout = 30\ntimout = 5\n").unwrap();
+        let error = Config::load_with_user(dir.path(), None).unwrap_err();
+        assert!(format!("{error:#}").contains("unknown field `timout`"), "{error:#}");
+        assert!(Config::load_or_default(dir.path()).layers().is_empty());
+    }
+}
@@ -0,0 +1,313 @@
+//! Response cache for file-scoped queries.
+//!
+//! Editor plugins and agents tend to ask the daemon the same question many
+//! times a minute: hover over the symbol under the cursor, jump to its
+//! definition, outline the current file. While the file is unchanged ty's
+//! answer is too, so the daemon keeps the serialized result of hover,
+//! definition and document symbol requests keyed by workspace, file and
+//! position, and answers repeats without going to ty. When full, the least
+//! recently used entry makes room for the new one.
+//!
+//! Every entry records the size and mtime of the file it was asked about
+//! (and, for definitions, of the file the result points into). A lookup
+//! re-stats those files and drops the entry if any of them changed. Hovers
+//! are also keyed by the workspace's index generation, since a change in
+//! another module can change an inferred type. Empty
+//! results are never cached, since ty returns those while it is still
+//! indexing.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+use serde_json::Value;
+
+use crate::config::DEFAULT_CACHE_SIZE;
+use crate::daemon::index::FileStamp;
+
+/// The request a cached response answers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CachedQuery {
+    /// A hover's inferred type can change with any file, so it is only
+    /// valid for the workspace index generation it was asked in.
+    Hover {
+        line: u32,
+        column: u32,
+        generation: u64,
+    },
+    Definition {
+        line: u32,
+        column: u32,
+    },
+    Definitions {
+        line: u32,
+        column: u32,
+    },
+    DocumentSymbols,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct CacheKey {
+    workspace: PathBuf,
+    file: PathBuf,
+    query: CachedQuery,
+}
+
+struct CacheEntry {
+    /// Files the response depends on, with their stamps when it was cached.
+    depends_on: Vec<(PathBuf, Option<FileStamp>)>,
+    value: Value,
+    /// Tick of the last insert or hit, for least-recently-used eviction.
+    last_used: u64,
+}
+
+/// Cached hover, definition and document symbol responses.
+pub struct ResponseCache {
+    entries: Mutex<HashMap<CacheKey, CacheEntry>>,
+    /// Source of `last_used` ticks.
+    clock: AtomicU64,
+    /// Upper bound on cached responses across all workspaces.
+    capacity: AtomicUsize,
+}
+
+impl ResponseCache {
+    pub fn new() -> Self {
+        Self::with_capacity(DEFAULT_CACHE_SIZE)
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            entries: Mutex::new(HashMap::new()),
+            clock: AtomicU64::new(0),
+            capacity: AtomicUsize::new(capacity),
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity.load(Ordering::Relaxed)
+    }
+
+    /// Keep at most `capacity` responses from now on, dropping the least
+    /// recently used ones over it; returns how many were dropped.
+    pub fn set_capacity(&self, capacity: usize) -> usize {
+        self.capacity.store(capacity, Ordering::Relaxed);
+        let mut entries = self.entries.lock().expect("cache mutex poisoned");
+        let excess = entries.len().saturating_sub(capacity);
+        if excess > 0 {
+            let mut by_age: Vec<(u64, CacheKey)> =
+                entries.iter().map(|(key, entry)| (entry.last_used, key.clone())).collect();
+            by_age.sort_unstable_by_key(|(last_used, _)| *last_used);
+            for (_, key) in by_age.into_iter().take(excess) {
+                entries.remove(&key);
+            }
+        }
+        excess
+    }
+
+    fn tick(&self) -> u64 {
+        self.clock.fetch_add(1, Ordering::Relaxed)
+    }
+
+    /// The cached response for `query` on `file`, if none of the files it
+    /// depends on changed since it was stored.
+    pub fn get(&self, workspace: &Path, file: &Path, query: CachedQuery) -> Option<Value> {
+        let key = Self::key(workspace, file, query);
+        let (depends_on, value) = {
+            let entries = self.entries.lock().expect("cache mutex poisoned");
+            let entry = entries.get(&key)?;
+            (entry.depends_on.clone(), entry.value.clone())
+        };
+        // Stat outside the lock so a slow filesystem doesn't serialize requests.
+        let fresh = depends_on.iter().all(|(path, stamp)| FileStamp::of(path) == *stamp);
+        let mut entries = self.entries.lock().expect("cache mutex poisoned");
+        if fresh {
+            if let Some(entry) = entries.get_mut(&key) {
+                entry.last_used = self.tick();
+            }
+            return Some(value);
+        }
+        entries.remove(&key);
+        None
+    }
+
+    /// Store the response for `query` on `file`.
+    ///
+    /// `also_depends_on` lists other files whose changes should invalidate the
+    /// entry, such as the target of a definition.
+    pub fn insert(
+        &self,
+        workspace: &Path,
+        file: &Path,
+        query: CachedQuery,
+        value: Value,
+        also_depends_on: &[PathBuf],
+    ) {
+        let mut depends_on = vec![(file.to_path_buf(), FileStamp::of(file))];
+        for path in also_depends_on {
+            if depends_on.iter().all(|(p, _)| p != path) {
+                depends_on.push((path.clone(), FileStamp::of(path)));
+            }
+        }
+        let key = Self::key(workspace, file, query);
+        let last_used = self.tick();
+        let mut entries = self.entries.lock().expect("cache mutex poisoned");
+        if entries.len() >= self.capacity() && !entries.contains_key(&key) {
+            let oldest =
+                entries.iter().min_by_key(|(_, entry)| entry.last_used).map(|(k, _)| k.clone());
+            if let Some(oldest) = oldest {
+                entries.remove(&oldest);
+            }
+        }
+        entries.insert(key, CacheEntry { depends_on, value, last_used });
+    }
+
+    /// Number of cached responses.
+    pub fn len(&self) -> usize {
+        self.entries.lock().expect("cache mutex poisoned").len()
+    }
+
+    /// Cached responses for `workspace` and roughly how many bytes they take.
+    pub fn footprint(&self, workspace: &Path) -> (usize, u64) {
+        let entries = self.entries.lock().expect("cache mutex poisoned");
+        let mut bytes = 0;
+        let mut count = 0;
+        for (key, entry) in entries.iter().filter(|(key, _)| key.workspace == workspace) {
+            count += 1;
+            bytes += key.file.as_os_str().len()
+                + entry.depends_on.iter().map(|(path, _)| path.as_os_str().len()).sum::<usize>()
+                + entry.value.to_string().len();
+        }
+        (count, bytes as u64)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Drop every cached response; returns how many there were.
+    pub fn clear(&self) -> usize {
+        let mut entries = self.entries.lock().expect("cache mutex poisoned");
+        let cleared = entries.len();
+        entries.clear();
+        cleared
+    }
+
+    /// Drop entries for workspaces not in `active`; returns how many were dropped.
+    pub fn retain(&self, active: &[PathBuf]) -> usize {
+        let mut entries = self.entries.lock().expect("cache mutex poisoned");
+        let before = entries.len();
+        entries.retain(|key, _| active.contains(&key.workspace));
+        before - entries.len()
+    }
+
+    fn key(workspace: &Path, file: &Path, query: CachedQuery) -> CacheKey {
+        // Match the pool, which keys ty servers by canonical workspace path.
+        let workspace =
+            crate::lsp::uri::canonicalize(workspace).unwrap_or_else(|_| workspace.into());
+        CacheKey { workspace, file: file.to_path_buf(), query }
+    }
+}
+
+impl Default for ResponseCache {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hit_until_file_changes() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let file = dir.path().join("a.py");
+        std::fs::write(&file, "x = 1\n").expect("write");
+        let cache = ResponseCache::new();
+        let query = CachedQuery::Hover { line: 0, column: 0, generation: 0 };
+
+        assert!(cache.get(dir.path(), &file, query).is_none());
+        cache.insert(dir.path(), &file, query, serde_json::json!({"hover": "int"}), &[]);
+        assert_eq!(cache.get(dir.path(), &file, query), Some(serde_json::json!({"hover": "int"})));
+        let moved = CachedQuery::Hover { line: 0, column: 1, generation: 0 };
+        assert!(cache.get(dir.path(), &file, moved).is_none());
+        let next = CachedQuery::Hover { line: 0, column: 0, generation: 1 };
+        assert!(cache.get(dir.path(), &file, next).is_none(), "another file changed since");
+
+        std::fs::write(&file, "x: str = 'one'\n").expect("write");
+        assert!(cache.get(dir.path(), &file, query).is_none());
+    }
+
+    #[test]
+    fn test_dependency_change_invalidates() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let file = dir.path().join("a.py");
+        let target = dir.path().join("b.py");
+        std::fs::write(&file, "from b import f\nf()\n").expect("write");
+        std::fs::write(&target, "def f(): ...\n").expect("write");
+        let cache = ResponseCache::new();
+        let query = CachedQuery::Definition { line: 1, column: 0 };
+
+        cache.insert(dir.path(), &file, query, Value::Null, std::slice::from_ref(&target));
+        assert!(cache.get(dir.path(), &file, query).is_some());
+
+        std::fs::write(&target, "\n\ndef f(): ...\n").expect("write");
+        assert!(cache.get(dir.path(), &file, query).is_none());
+    }
+
+    #[test]
+    fn test_evicts_least_recently_used() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let file = dir.path().join("a.py");
+        std::fs::write(&file, "x = 1\n").expect("write");
+        let cache = ResponseCache::with_capacity(2);
+        let at = |line| CachedQuery::Hover { line, column: 0, generation: 0 };
+
+        cache.insert(dir.path(), &file, at(0), Value::Null, &[]);
+        cache.insert(dir.path(), &file, at(1), Value::Null, &[]);
+        assert!(cache.get(dir.path(), &file, at(0)).is_some());
+        cache.insert(dir.path(), &file, at(2), Value::Null, &[]);
+
+        assert_eq!(cache.len(), 2);
+        assert!(cache.get(dir.path(), &file, at(0)).is_some());
+        assert!(cache.get(dir.path(), &file, at(1)).is_none());
+        assert!(cache.get(dir.path(), &file, at(2)).is_some());
+
+        assert_eq!(cache.clear(), 2);
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn test_shrinking_drops_least_recently_used() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let file = dir.path().join("a.py");
+        std::fs::write(&file, "x = 1\n").expect("write");
+        let cache = ResponseCache::with_capacity(3);
+        let at = |line| CachedQuery::Hover { line, column: 0, generation: 0 };
+        for line in 0..3 {
+            cache.insert(dir.path(), &file, at(line), Value::Null, &[]);
+        }
+        assert!(cache.get(dir.path(), &file, at(0)).is_some());
+
+        assert_eq!(cache.set_capacity(1), 2);
+        assert_eq!(cache.capacity(), 1);
+        assert!(cache.get(dir.path(), &file, at(0)).is_some());
+        assert_eq!(cache.set_capacity(10), 0);
+        assert_eq!(cache.len(), 1);
+    }
+
+    #[test]
+    fn test_retain_drops_inactive_workspaces() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let file = dir.path().join("a.py");
+        std::fs::write(&file, "x = 1\n").expect("write");
+        let cache = ResponseCache::new();
+        cache.insert(dir.path(), &file, CachedQuery::DocumentSymbols, Value::Null, &[]);
+
+        let root = crate::lsp::uri::canonicalize(dir.path()).expect("canonicalize");
+        assert_eq!(cache.retain(&[root]), 0);
+        assert_eq!(cache.retain(&[]), 1);
+        assert!(cache.get(dir.path(), &file, CachedQuery::DocumentSymbols).is_none());
+    }
+}
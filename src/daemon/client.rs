@@ -10,22 +10,41 @@
 use anyhow::{Context, Result};
 use serde::de::DeserializeOwned;
 use serde_json::Value;
-use std::path::PathBuf;
-use std::sync::Arc;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, OnceLock};
 use std::time::{Duration, Instant};
 use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
 use tokio::net::{TcpStream, UnixStream};
 use tokio::time::timeout;
 
 use super::pidfile::{self, PidfileData};
+use super::server::DaemonServer;
 use crate::debug::DebugLog;
+use crate::lsp::backend::BACKEND_ENV;
+use crate::lsp::server::TyNotFound;
+use crate::lsp::trace::{Direction, WireTrace};
+use crate::workspace::filter::PathFilter;
+use crate::workspace::page::ResultPage;
 
 use super::protocol::{
-    BatchReferencesParams, BatchReferencesQuery, BatchReferencesResult, DaemonRequest,
-    DaemonResponse, DefinitionParams, DefinitionResult, DocumentSymbolsParams,
-    DocumentSymbolsResult, HoverParams, HoverResult, InspectParams, InspectResult, MembersParams,
-    MembersResult, Method, PingParams, PingResult, ReferencesParams, ReferencesResult,
-    ShutdownParams, ShutdownResult, WorkspaceSymbolsParams, WorkspaceSymbolsResult,
+    protocol_compatible, protocol_mismatch_message, BatchDefinitionsParams, BatchDefinitionsResult,
+    BatchDiagnosticsParams, BatchDiagnosticsResult, BatchDocumentSymbolsParams,
+    BatchDocumentSymbolsResult, BatchHoverParams, BatchHoverResult, BatchReferencesParams,
+    BatchReferencesQuery, BatchReferencesResult, CancelParams, CancelResult, ClearCacheParams,
+    ClearCacheResult, DaemonError, DaemonNotification, DaemonRequest, DaemonResponse,
+    DefinitionParams, DefinitionResult, DiagnosticsParams, DiagnosticsResult,
+    DocumentHighlightsParams, DocumentHighlightsResult, DocumentSymbolsParams,
+    DocumentSymbolsResult, DropWorkspaceParams, DropWorkspaceResult, ExplainLineParams,
+    ExplainLineResult, ExpressionMembersParams, FileChange, FilesChangedParams, FilesChangedResult,
+    GotoKind, GotoParams, GotoResult, HoverParams, HoverResult, InlayHintsParams, InlayHintsResult,
+    InspectManyParams, InspectManyResult, InspectParams, InspectResult, LineIdentifier,
+    MembersParams, MembersResult, Method, PingParams, PingResult, ReferencesParams,
+    ReferencesResult, ReloadConfigParams, ReloadConfigResult, ShutdownParams, ShutdownResult,
+    StatsParams, StatsResult, SymbolKind, TyInfoParams, TyInfoResult, WarmupParams, WarmupResult,
+    WorkProgress, WorkspaceSymbolsParams, WorkspaceSymbolsResult, WorkspacesParams,
+    WorkspacesResult, DEFAULT_HOVER_CONCURRENCY, DEFAULT_INSPECT_CONCURRENCY,
+    DEFAULT_REFERENCES_CONCURRENCY, PROTOCOL_VERSION,
 };
 
 /// Default timeout for daemon operations (30 seconds).
@@ -40,8 +59,49 @@ const MAX_STARTUP_RETRIES: usize = 20;
 /// Delay between startup retry attempts (100ms).
 const STARTUP_RETRY_DELAY: Duration = Duration::from_millis(100);
 
+/// How the daemon is named in the `--trace-lsp` trace.
+const DAEMON_PEER: &str = "daemon";
+
+/// The daemon could not be started or reached, or failed to answer.
+///
+/// Wraps the underlying error, keeping its message and causes, so the CLI can
+/// tell daemon trouble apart from a query that simply found nothing.
+#[derive(Debug)]
+pub struct DaemonFailure(anyhow::Error);
+
+impl DaemonFailure {
+    /// `error` marked as a daemon failure, unless it says ty is missing.
+    pub fn wrap(error: anyhow::Error) -> anyhow::Error {
+        if error.chain().any(|e| e.is::<TyNotFound>() || e.is::<Self>()) {
+            return error;
+        }
+        Self(error).into()
+    }
+}
+
+impl fmt::Display for DaemonFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for DaemonFailure {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.0.source()
+    }
+}
+
 /// Transport layer abstraction — both `AsyncRead` and `AsyncWrite`.
 ///
+/// Shows the progress updates the daemon sends while a request runs.
+pub trait ProgressSink: Send {
+    /// Show the latest update.
+    fn update(&mut self, progress: &WorkProgress);
+
+    /// The request is over; take down whatever [`update`](Self::update) showed.
+    fn finish(&mut self);
+}
+
 /// Object-safe supertrait alias so we can store `Box<dyn DaemonTransport>`.
 trait DaemonTransport: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin + Send {}
 impl<T: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin + Send> DaemonTransport for T {}
@@ -73,11 +133,30 @@ pub struct DaemonClient {
     /// Connection to the daemon (Unix socket or TCP stream).
     stream: Box<dyn DaemonTransport>,
 
-    /// Timeout for daemon operations.
+    /// Timeout for daemon operations; workspace-wide methods get a multiple
+    /// of it (see [`Method::timeout_scale`]).
     timeout: Duration,
 
     /// Optional debug log for tracing RPC requests/responses.
     debug_log: Option<Arc<DebugLog>>,
+
+    /// LSP backend to ask for, from `TYF_BACKEND` (which `--backend` sets).
+    backend: Option<String>,
+
+    /// Filter sent with references and workspace symbol requests.
+    path_filter: PathFilter,
+
+    /// Unsaved text standing in for one file (`--stdin-path`), sent with
+    /// hover and definition requests about that file.
+    buffer: Option<(PathBuf, String)>,
+
+    /// Where to show the workspace's progress while requests run; `None`
+    /// doesn't ask the daemon for it.
+    progress: Option<Box<dyn ProgressSink>>,
+
+    /// The server, when it runs inside this process (`--no-daemon`), for
+    /// [`connect_another`](Self::connect_another) to connect to again.
+    in_process: Option<Arc<DaemonServer>>,
 }
 
 impl DaemonClient {
@@ -116,7 +195,33 @@ impl DaemonClient {
 
         tracing::debug!("Connected to daemon via Unix socket (legacy, no pidfile)");
 
-        Ok(Self { stream: Box::new(stream), timeout, debug_log: None })
+        Ok(Self::new(Box::new(stream), timeout))
+    }
+
+    fn new(stream: Box<dyn DaemonTransport>, timeout: Duration) -> Self {
+        let backend = std::env::var(BACKEND_ENV).ok().filter(|spec| !spec.trim().is_empty());
+        Self {
+            stream,
+            timeout,
+            debug_log: None,
+            backend,
+            path_filter: PathFilter::default(),
+            buffer: None,
+            progress: None,
+            in_process: None,
+        }
+    }
+
+    /// Connect to a daemon server started inside this process, instead of
+    /// the background daemon (`--no-daemon`).
+    ///
+    /// The server, and the ty servers it starts, last as long as this client
+    /// and the ones [`connect_another`](Self::connect_another) makes from it.
+    pub fn in_process(timeout: Duration) -> Self {
+        let server = Arc::new(DaemonServer::stdio());
+        let mut client = Self::new(Box::new(server.connect_in_process()), timeout);
+        client.in_process = Some(server);
+        client
     }
 
     /// Connect using pidfile data: try Unix socket first, TCP fallback.
@@ -125,7 +230,7 @@ impl DaemonClient {
         match UnixStream::connect(&data.socket).await {
             Ok(stream) => {
                 tracing::debug!("Connected to daemon via Unix socket");
-                return Ok(Self { stream: Box::new(stream), timeout, debug_log: None });
+                return Ok(Self::new(Box::new(stream), timeout));
             }
             Err(e) => {
                 // EPERM (sandbox), ECONNREFUSED, or ENOENT → fall back to TCP.
@@ -142,7 +247,61 @@ impl DaemonClient {
 
         tracing::info!("Connected to daemon via TCP fallback ({addr})");
 
-        Ok(Self { stream: Box::new(stream), timeout, debug_log: None })
+        Ok(Self::new(Box::new(stream), timeout))
+    }
+
+    /// Open another connection to the daemon with this one's settings, for
+    /// requests sent while this one is receiving a streamed result.
+    pub async fn connect_another(&self) -> Result<Self> {
+        let mut client = match &self.in_process {
+            Some(server) => {
+                let mut client = Self::new(Box::new(server.connect_in_process()), self.timeout);
+                client.in_process = Some(Arc::clone(server));
+                client
+            }
+            None => Self::connect_with_timeout(self.timeout).await?,
+        };
+        client.debug_log.clone_from(&self.debug_log);
+        client.backend.clone_from(&self.backend);
+        client.path_filter = self.path_filter.clone();
+        client.buffer.clone_from(&self.buffer);
+        Ok(client)
+    }
+
+    /// Change the timeout applied to subsequent requests.
+    pub const fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+
+    /// Have the daemon drop references and workspace symbols in files
+    /// `filter` rejects, for the requests that follow.
+    pub fn set_path_filter(&mut self, filter: PathFilter) {
+        self.path_filter = filter;
+    }
+
+    /// Keep symbol lookups to `workspace` unless dependencies were asked
+    /// for, which lets the daemon answer them from its index.
+    fn workspace_prefix(&self, workspace: &Path) -> Option<PathBuf> {
+        self.path_filter.default_excludes.then(|| workspace.to_path_buf())
+    }
+
+    /// Have the daemon use `text` instead of the contents of `file` for the
+    /// hover and definition requests that follow.
+    pub fn set_buffer(&mut self, file: PathBuf, text: String) {
+        self.buffer = Some((file, text));
+    }
+
+    /// The text set with [`set_buffer`](Self::set_buffer) if `file` names
+    /// the same file; relative paths are taken from `workspace`.
+    pub fn buffer_text(&self, workspace: &Path, file: &str) -> Option<&str> {
+        let (path, text) = self.buffer.as_ref()?;
+        (workspace.join(path) == workspace.join(file)).then_some(text.as_str())
+    }
+
+    /// Have the daemon report the workspace's progress (indexing, long
+    /// searches) during the requests that follow, and show it on `sink`.
+    pub fn set_progress(&mut self, sink: Box<dyn ProgressSink>) {
+        self.progress = Some(sink);
     }
 
     /// Attach a debug log for tracing RPC requests and responses.
@@ -152,13 +311,34 @@ impl DaemonClient {
 
     /// Send a JSON-RPC request to the daemon and wait for response.
     pub async fn send_request(&mut self, method: Method, params: Value) -> Result<DaemonResponse> {
+        self.send(method, params, None).await
+    }
+
+    /// [`send_request`](Self::send_request), asking the daemon to stream
+    /// the result when `on_part` is given: it gets each part that arrives
+    /// ahead of the response, which then holds the rest.
+    async fn send(
+        &mut self,
+        method: Method,
+        params: Value,
+        on_part: Option<&mut (dyn FnMut(Value) + Send)>,
+    ) -> Result<DaemonResponse> {
         let mut request = DaemonRequest::new(method, params);
         // Set debug flag so the daemon includes raw LSP trace in the response
         request.debug = self.debug_log.is_some();
+        request.backend.clone_from(&self.backend);
+        request.progress = self.progress.is_some();
+        request.stream = on_part.is_some();
+        let trace = WireTrace::active();
+        request.trace = trace.is_some();
+        let id = request.id;
 
         // Serialize request to JSON
         let request_json =
             serde_json::to_string(&request).context("Failed to serialize request")?;
+        if let Some(trace) = trace {
+            trace.record(DAEMON_PEER, Direction::Sent, &request_json);
+        }
 
         // Log the outgoing RPC request
         if let Some(ref log) = self.debug_log {
@@ -172,7 +352,8 @@ impl DaemonClient {
         let message = format!("Content-Length: {}\r\n\r\n{request_json}", request_json.len());
 
         // Send request with timeout
-        let response = timeout(self.timeout, async {
+        let limit = self.timeout * method.timeout_scale();
+        let response = timeout(limit, async {
             self.stream
                 .write_all(message.as_bytes())
                 .await
@@ -181,10 +362,15 @@ impl DaemonClient {
             tracing::debug!("Sent request: method={}", method.as_str());
 
             // Read response
-            self.read_response().await
+            self.read_response(id, on_part).await
         })
-        .await
-        .context("Request timed out")??;
+        .await;
+        if let Some(progress) = self.progress.as_mut() {
+            progress.finish();
+        }
+        let response = response.with_context(|| {
+            format!("Request timed out after {}s (raise it with --timeout)", limit.as_secs())
+        })??;
 
         // Log the incoming RPC response
         if let Some(ref log) = self.debug_log {
@@ -213,40 +399,54 @@ impl DaemonClient {
     /// \r\n
     /// {"jsonrpc":"2.0",...}
     /// ```
-    async fn read_response(&mut self) -> Result<DaemonResponse> {
+    ///
+    /// Notifications arriving first, framed the same way, go to the progress
+    /// sink, the `--trace-lsp` trace or, for parts of request `id`'s result,
+    /// to `on_part`.
+    async fn read_response(
+        &mut self,
+        id: u64,
+        mut on_part: Option<&mut (dyn FnMut(Value) + Send)>,
+    ) -> Result<DaemonResponse> {
+        // One reader for every frame, so none is lost in its buffer.
         let mut reader = BufReader::new(&mut self.stream);
+        loop {
+            let body = read_frame(&mut reader).await?;
+            let message: Value =
+                serde_json::from_slice(&body).context("Failed to parse JSON response")?;
+            let trace = WireTrace::active();
+            if message.get("method").is_some() {
+                let notification: DaemonNotification =
+                    serde_json::from_value(message).context("Failed to parse notification")?;
+                if let Some(forwarded) = notification.as_trace() {
+                    if let Some(trace) = trace {
+                        trace.record_forwarded(&forwarded);
+                    }
+                    continue;
+                }
+                if let Some(trace) = trace {
+                    trace.record(DAEMON_PEER, Direction::Received, &String::from_utf8_lossy(&body));
+                }
+                if let Some(update) = notification.as_progress() {
+                    if let Some(sink) = self.progress.as_mut() {
+                        sink.update(&update);
+                    }
+                } else if let Some((part_of, part)) = notification.into_partial() {
+                    if let Some(on_part) = on_part.as_deref_mut().filter(|_| part_of == id) {
+                        on_part(part);
+                    }
+                }
+                continue;
+            }
 
-        // Read Content-Length header
-        let mut header_line = String::new();
-        reader.read_line(&mut header_line).await.context("Failed to read Content-Length header")?;
-
-        // Parse content length
-        let content_length = header_line
-            .trim()
-            .strip_prefix("Content-Length: ")
-            .context("Invalid header: missing Content-Length")?
-            .parse::<usize>()
-            .context("Invalid Content-Length value")?;
-
-        // Read empty line
-        let mut empty_line = String::new();
-        reader.read_line(&mut empty_line).await.context("Failed to read header separator")?;
-
-        if !empty_line.trim().is_empty() {
-            anyhow::bail!("Expected empty line after Content-Length header");
+            if let Some(trace) = trace {
+                trace.record(DAEMON_PEER, Direction::Received, &String::from_utf8_lossy(&body));
+            }
+            let response: DaemonResponse =
+                serde_json::from_value(message).context("Failed to parse JSON response")?;
+            tracing::debug!("Received response: id={}", response.id);
+            return Ok(response);
         }
-
-        // Read response body
-        let mut body = vec![0u8; content_length];
-        reader.read_exact(&mut body).await.context("Failed to read response body")?;
-
-        // Parse JSON response
-        let response: DaemonResponse =
-            serde_json::from_slice(&body).context("Failed to parse JSON response")?;
-
-        tracing::debug!("Received response: id={}", response.id);
-
-        Ok(response)
     }
 
     /// Send a typed request and deserialize the response.
@@ -256,14 +456,61 @@ impl DaemonClient {
         &mut self,
         method: Method,
         params: P,
+    ) -> Result<R> {
+        self.execute_with(method, params, None).await
+    }
+
+    /// [`execute`](Self::execute), passing each part of the result the
+    /// daemon streams ahead of the response to `on_part`; the returned
+    /// result holds the rest.
+    async fn execute_streaming<P: serde::Serialize, R: DeserializeOwned>(
+        &mut self,
+        method: Method,
+        params: P,
+        on_part: &mut (dyn FnMut(R) + Send),
+    ) -> Result<R> {
+        let mut bad_part = None;
+        let mut forward = |part: Value| match serde_json::from_value(part) {
+            Ok(part) => on_part(part),
+            Err(e) => {
+                bad_part.get_or_insert(e);
+            }
+        };
+        let result = self.execute_with(method, params, Some(&mut forward)).await;
+        if let Some(e) = bad_part {
+            return Err(e).with_context(|| {
+                format!("Failed to deserialize {} partial result", method.as_str())
+            });
+        }
+        result
+    }
+
+    async fn execute_with<P: serde::Serialize, R: DeserializeOwned>(
+        &mut self,
+        method: Method,
+        params: P,
+        on_part: Option<&mut (dyn FnMut(Value) + Send)>,
     ) -> Result<R> {
         let params_value = serde_json::to_value(params)
             .with_context(|| format!("Failed to serialize {} params", method.as_str()))?;
 
-        let response = self.send_request(method, params_value).await?;
+        let response =
+            self.send(method, params_value, on_part).await.map_err(DaemonFailure::wrap)?;
+
+        // Check before looking at the payload: a daemon speaking another
+        // protocol may have sent something we'd fail to deserialize.
+        if !protocol_compatible(method, response.protocol) {
+            return Err(DaemonFailure::wrap(anyhow::anyhow!(
+                "{}",
+                protocol_mismatch_message(PROTOCOL_VERSION, response.protocol)
+            )));
+        }
 
         if let Some(error) = response.error {
-            anyhow::bail!("Daemon error: {}", error.message);
+            if error.code == DaemonError::TY_NOT_FOUND {
+                return Err(TyNotFound(error.message).into());
+            }
+            return Err(DaemonFailure::wrap(anyhow::anyhow!("Daemon error: {}", error.message)));
         }
 
         let result = response.result.context("Response missing result field")?;
@@ -280,10 +527,63 @@ impl DaemonClient {
         line: u32,
         column: u32,
     ) -> Result<HoverResult> {
-        let params = HoverParams { workspace, file: PathBuf::from(file), line, column };
+        let content = self.buffer_text(&workspace, &file).map(str::to_string);
+        let params = HoverParams { workspace, file: PathBuf::from(file), line, column, content };
         self.execute(Method::Hover, params).await
     }
 
+    /// Execute a batch definitions request (multiple positions in one RPC call).
+    pub async fn execute_batch_definitions(
+        &mut self,
+        workspace: PathBuf,
+        queries: Vec<BatchReferencesQuery>,
+        concurrency: usize,
+    ) -> Result<BatchDefinitionsResult> {
+        let params = BatchDefinitionsParams { workspace, queries, concurrency };
+        self.execute(Method::BatchDefinitions, params).await
+    }
+
+    /// Execute a goto request: each of `kinds` at each of `queries`.
+    pub async fn execute_goto(
+        &mut self,
+        workspace: PathBuf,
+        queries: Vec<BatchReferencesQuery>,
+        kinds: Vec<GotoKind>,
+    ) -> Result<GotoResult> {
+        let params = GotoParams { workspace, queries, kinds };
+        self.execute(Method::Goto, params).await
+    }
+
+    /// Execute an explain line request: hover and definitions for each of
+    /// `identifiers` on `line` of `file`, and the diagnostics on that line.
+    pub async fn execute_explain_line(
+        &mut self,
+        workspace: PathBuf,
+        file: PathBuf,
+        line: u32,
+        identifiers: Vec<LineIdentifier>,
+    ) -> Result<ExplainLineResult> {
+        let params = ExplainLineParams {
+            workspace,
+            file,
+            line,
+            identifiers,
+            concurrency: DEFAULT_HOVER_CONCURRENCY,
+        };
+        self.execute(Method::ExplainLine, params).await
+    }
+
+    /// Execute a batch hover request (multiple positions in one RPC call).
+    pub async fn execute_batch_hover(
+        &mut self,
+        workspace: PathBuf,
+        queries: Vec<BatchReferencesQuery>,
+        concurrency: usize,
+    ) -> Result<BatchHoverResult> {
+        let params = BatchHoverParams { workspace, queries, concurrency };
+        self.execute(Method::BatchHover, params).await
+    }
+
     /// Execute a definition request.
     pub async fn execute_definition(
         &mut self,
@@ -292,39 +592,53 @@ impl DaemonClient {
         line: u32,
         column: u32,
     ) -> Result<DefinitionResult> {
-        let params = DefinitionParams { workspace, file: PathBuf::from(file), line, column };
+        let content = self.buffer_text(&workspace, &file).map(str::to_string);
+        let params =
+            DefinitionParams { workspace, file: PathBuf::from(file), line, column, content };
         self.execute(Method::Definition, params).await
     }
 
-    /// Execute a workspace symbols request.
+    /// Execute a workspace symbols request, keeping only symbols of `kinds`
+    /// (any kind when empty).
     pub async fn execute_workspace_symbols(
         &mut self,
         workspace: PathBuf,
         query: String,
+        kinds: Vec<SymbolKind>,
     ) -> Result<WorkspaceSymbolsResult> {
+        let path_prefix = self.workspace_prefix(&workspace);
         let params = WorkspaceSymbolsParams {
             workspace,
             query,
-            limit: None,
+            page: ResultPage::default(),
             exact_name: None,
             container_name: None,
+            kinds,
+            path_prefix,
+            paths: self.path_filter.clone(),
         };
         self.execute(Method::WorkspaceSymbols, params).await
     }
 
-    /// Execute a workspace symbols request filtered to exact name matches.
+    /// Execute a workspace symbols request filtered to exact name matches of
+    /// `kinds` (any kind when empty).
     pub async fn execute_workspace_symbols_exact(
         &mut self,
         workspace: PathBuf,
         query: String,
+        kinds: Vec<SymbolKind>,
     ) -> Result<WorkspaceSymbolsResult> {
         let exact_name = Some(query.clone());
+        let path_prefix = self.workspace_prefix(&workspace);
         let params = WorkspaceSymbolsParams {
             workspace,
             query,
-            limit: None,
+            page: ResultPage::default(),
             exact_name,
             container_name: None,
+            kinds,
+            path_prefix,
+            paths: self.path_filter.clone(),
         };
         self.execute(Method::WorkspaceSymbols, params).await
     }
@@ -339,16 +653,69 @@ impl DaemonClient {
         symbol_name: String,
         container: String,
     ) -> Result<WorkspaceSymbolsResult> {
+        let path_prefix = self.workspace_prefix(&workspace);
         let params = WorkspaceSymbolsParams {
             workspace,
             query: symbol_name.clone(),
-            limit: None,
+            page: ResultPage::default(),
             exact_name: Some(symbol_name),
             container_name: Some(container),
+            kinds: Vec::new(),
+            path_prefix,
+            paths: self.path_filter.clone(),
+        };
+        self.execute(Method::WorkspaceSymbols, params).await
+    }
+
+    /// Execute a workspace symbols request narrowed down by the daemon: only
+    /// symbols of `kinds` (any kind when empty) defined under `path_prefix`,
+    /// only those on `page`.
+    pub async fn execute_symbol_search(
+        &mut self,
+        workspace: PathBuf,
+        query: String,
+        kinds: Vec<SymbolKind>,
+        path_prefix: Option<PathBuf>,
+        page: ResultPage,
+    ) -> Result<WorkspaceSymbolsResult> {
+        let params = WorkspaceSymbolsParams {
+            workspace,
+            query,
+            page,
+            exact_name: None,
+            container_name: None,
+            kinds,
+            path_prefix,
+            paths: self.path_filter.clone(),
         };
         self.execute(Method::WorkspaceSymbols, params).await
     }
 
+    /// [`execute_symbol_search`](Self::execute_symbol_search), passing the
+    /// symbols to `on_part` in parts as they arrive. The returned result
+    /// holds the last part and how many symbols follow the page.
+    pub async fn stream_symbol_search(
+        &mut self,
+        workspace: PathBuf,
+        query: String,
+        kinds: Vec<SymbolKind>,
+        path_prefix: Option<PathBuf>,
+        page: ResultPage,
+        mut on_part: impl FnMut(WorkspaceSymbolsResult) + Send,
+    ) -> Result<WorkspaceSymbolsResult> {
+        let params = WorkspaceSymbolsParams {
+            workspace,
+            query,
+            page,
+            exact_name: None,
+            container_name: None,
+            kinds,
+            path_prefix,
+            paths: self.path_filter.clone(),
+        };
+        self.execute_streaming(Method::WorkspaceSymbols, params, &mut on_part).await
+    }
+
     /// Execute a document symbols request.
     pub async fn execute_document_symbols(
         &mut self,
@@ -374,6 +741,7 @@ impl DaemonClient {
             line,
             column,
             include_declaration,
+            paths: self.path_filter.clone(),
         };
         self.execute(Method::References, params).await
     }
@@ -384,11 +752,109 @@ impl DaemonClient {
         workspace: PathBuf,
         queries: Vec<BatchReferencesQuery>,
         include_declaration: bool,
+        concurrency: usize,
     ) -> Result<BatchReferencesResult> {
-        let params = BatchReferencesParams { workspace, queries, include_declaration };
+        let params = BatchReferencesParams {
+            workspace,
+            queries,
+            include_declaration,
+            concurrency,
+            paths: self.path_filter.clone(),
+            count_only: false,
+            workspace_only: false,
+            page: ResultPage::default(),
+        };
         self.execute(Method::BatchReferences, params).await
     }
 
+    /// Execute a batch references request for one `page` of each label's
+    /// references, leaving out those in dependencies when `workspace_only`.
+    pub async fn execute_batch_references_page(
+        &mut self,
+        workspace: PathBuf,
+        queries: Vec<BatchReferencesQuery>,
+        include_declaration: bool,
+        workspace_only: bool,
+        page: ResultPage,
+    ) -> Result<BatchReferencesResult> {
+        let params = BatchReferencesParams {
+            workspace,
+            queries,
+            include_declaration,
+            concurrency: DEFAULT_REFERENCES_CONCURRENCY,
+            paths: self.path_filter.clone(),
+            count_only: false,
+            workspace_only,
+            page,
+        };
+        self.execute(Method::BatchReferences, params).await
+    }
+
+    /// [`execute_batch_references_page`](Self::execute_batch_references_page),
+    /// passing the entries to `on_part`, in query order, as soon as every
+    /// query of their label is done. The returned result holds those that
+    /// weren't sent ahead.
+    pub async fn stream_batch_references_page(
+        &mut self,
+        workspace: PathBuf,
+        queries: Vec<BatchReferencesQuery>,
+        include_declaration: bool,
+        workspace_only: bool,
+        page: ResultPage,
+        mut on_part: impl FnMut(BatchReferencesResult) + Send,
+    ) -> Result<BatchReferencesResult> {
+        let params = BatchReferencesParams {
+            workspace,
+            queries,
+            include_declaration,
+            concurrency: DEFAULT_REFERENCES_CONCURRENCY,
+            paths: self.path_filter.clone(),
+            count_only: false,
+            workspace_only,
+            page,
+        };
+        self.execute_streaming(Method::BatchReferences, params, &mut on_part).await
+    }
+
+    /// Execute a batch references request answered with counts per file
+    /// instead of locations, one entry per label.
+    pub async fn execute_batch_reference_counts(
+        &mut self,
+        workspace: PathBuf,
+        queries: Vec<BatchReferencesQuery>,
+        include_declaration: bool,
+        concurrency: usize,
+    ) -> Result<BatchReferencesResult> {
+        let params = BatchReferencesParams {
+            workspace,
+            queries,
+            include_declaration,
+            concurrency,
+            paths: self.path_filter.clone(),
+            count_only: true,
+            workspace_only: false,
+            page: ResultPage::default(),
+        };
+        self.execute(Method::BatchReferences, params).await
+    }
+
+    /// Execute an inspect many request: hover, and optionally references, at
+    /// each of `queries`, several at once.
+    pub async fn execute_inspect_many(
+        &mut self,
+        workspace: PathBuf,
+        queries: Vec<BatchReferencesQuery>,
+        include_references: bool,
+    ) -> Result<InspectManyResult> {
+        let params = InspectManyParams {
+            workspace,
+            queries,
+            include_references,
+            concurrency: DEFAULT_INSPECT_CONCURRENCY,
+        };
+        self.execute(Method::InspectMany, params).await
+    }
+
     /// Execute an inspect request (hover, and optionally references, in one call).
     pub async fn execute_inspect(
         &mut self,
@@ -421,6 +887,138 @@ impl DaemonClient {
         self.execute(Method::Members, params).await
     }
 
+    /// Execute an expression members request (attributes of the expression at a position).
+    pub async fn execute_expression_members(
+        &mut self,
+        workspace: PathBuf,
+        file: String,
+        line: u32,
+        column: u32,
+        include_all: bool,
+    ) -> Result<MembersResult> {
+        let params = ExpressionMembersParams {
+            workspace,
+            file: PathBuf::from(file),
+            line,
+            column,
+            include_all,
+        };
+        self.execute(Method::ExpressionMembers, params).await
+    }
+
+    /// Execute a diagnostics request (type errors and warnings for one file).
+    pub async fn execute_diagnostics(
+        &mut self,
+        workspace: PathBuf,
+        file: String,
+    ) -> Result<DiagnosticsResult> {
+        let params = DiagnosticsParams { workspace, file: PathBuf::from(file) };
+        self.execute(Method::Diagnostics, params).await
+    }
+
+    /// Execute a batch diagnostics request, checking up to `concurrency` files at once.
+    pub async fn execute_batch_diagnostics(
+        &mut self,
+        workspace: PathBuf,
+        files: Vec<PathBuf>,
+        concurrency: usize,
+    ) -> Result<BatchDiagnosticsResult> {
+        let params = BatchDiagnosticsParams { workspace, files, concurrency };
+        self.execute(Method::BatchDiagnostics, params).await
+    }
+
+    /// Execute a batch document symbols request, outlining up to `concurrency` files at once.
+    pub async fn execute_batch_document_symbols(
+        &mut self,
+        workspace: PathBuf,
+        files: Vec<PathBuf>,
+        concurrency: usize,
+    ) -> Result<BatchDocumentSymbolsResult> {
+        let params = BatchDocumentSymbolsParams { workspace, files, concurrency };
+        self.execute(Method::BatchDocumentSymbols, params).await
+    }
+
+    /// Tell the daemon which files changed on disk.
+    pub async fn execute_files_changed(
+        &mut self,
+        workspace: PathBuf,
+        changes: Vec<FileChange>,
+    ) -> Result<FilesChangedResult> {
+        let params = FilesChangedParams { workspace, changes };
+        self.execute(Method::FilesChanged, params).await
+    }
+
+    /// Execute an inlay hints request (inferred types for a whole file).
+    pub async fn execute_inlay_hints(
+        &mut self,
+        workspace: PathBuf,
+        file: String,
+    ) -> Result<InlayHintsResult> {
+        let params = InlayHintsParams { workspace, file: PathBuf::from(file) };
+        self.execute(Method::InlayHints, params).await
+    }
+
+    /// Execute a document highlights request (occurrences of a symbol in its file).
+    pub async fn execute_document_highlights(
+        &mut self,
+        workspace: PathBuf,
+        file: String,
+        line: u32,
+        column: u32,
+    ) -> Result<DocumentHighlightsResult> {
+        let params =
+            DocumentHighlightsParams { workspace, file: PathBuf::from(file), line, column };
+        self.execute(Method::DocumentHighlights, params).await
+    }
+
+    /// Execute a ty-info request (server version and capabilities).
+    pub async fn execute_ty_info(&mut self, workspace: PathBuf) -> Result<TyInfoResult> {
+        self.execute(Method::TyInfo, TyInfoParams { workspace }).await
+    }
+
+    /// Ask the daemon for its request counters and per-workspace footprint.
+    pub async fn execute_stats(&mut self) -> Result<StatsResult> {
+        self.execute(Method::Stats, StatsParams {}).await
+    }
+
+    /// List the workspaces the daemon currently holds a language server for.
+    pub async fn execute_workspaces(&mut self) -> Result<WorkspacesResult> {
+        self.execute(Method::Workspaces, WorkspacesParams {}).await
+    }
+
+    /// Stop the language server of one workspace, or of every workspace
+    /// when `workspace` is `None`, without stopping the daemon.
+    pub async fn execute_drop_workspace(
+        &mut self,
+        workspace: Option<PathBuf>,
+    ) -> Result<DropWorkspaceResult> {
+        self.execute(Method::DropWorkspace, DropWorkspaceParams { workspace }).await
+    }
+
+    /// Drop the daemon's cached responses; returns how many there were.
+    pub async fn execute_clear_cache(&mut self) -> Result<ClearCacheResult> {
+        self.execute(Method::ClearCache, ClearCacheParams {}).await
+    }
+
+    /// Have the daemon re-read its configuration.
+    pub async fn execute_reload_config(&mut self) -> Result<ReloadConfigResult> {
+        self.execute(Method::ReloadConfig, ReloadConfigParams {}).await
+    }
+
+    /// Start language servers for `workspaces`; with `wait`, answer once they're ready.
+    pub async fn execute_warmup(
+        &mut self,
+        workspaces: Vec<PathBuf>,
+        wait: bool,
+    ) -> Result<WarmupResult> {
+        self.execute(Method::Warmup, WarmupParams { workspaces, wait }).await
+    }
+
+    /// Abandon the in-flight requests of process `pid`; returns how many there were.
+    pub async fn execute_cancel(&mut self, pid: u32) -> Result<CancelResult> {
+        self.execute(Method::Cancel, CancelParams { pid }).await
+    }
+
     /// Send a ping request to check daemon health.
     pub async fn ping(&mut self) -> Result<PingResult> {
         self.execute(Method::Ping, PingParams {}).await
@@ -434,15 +1032,76 @@ impl DaemonClient {
     }
 }
 
+/// Ask a running daemon to abandon this process's in-flight requests.
+///
+/// Called when the CLI is interrupted, so a slow query doesn't keep ty busy
+/// after nobody is waiting for it. Never starts a daemon, and gives up after
+/// a short wait.
+pub async fn cancel_own_requests() {
+    if daemon_disabled() {
+        // Requests served in this process end with it.
+        return;
+    }
+    let wait = Duration::from_millis(500);
+    let cancel = async {
+        let mut client = DaemonClient::connect_with_timeout(wait).await?;
+        client.execute_cancel(std::process::id()).await
+    };
+    match timeout(wait, cancel).await {
+        Ok(Ok(result)) => tracing::debug!("Cancelled {} daemon request(s)", result.cancelled),
+        Ok(Err(e)) => tracing::debug!("Failed to cancel daemon requests: {e:#}"),
+        Err(_) => tracing::debug!("Timed out cancelling daemon requests"),
+    }
+}
+
 /// Version of the current binary, used to detect stale daemons after upgrades.
 pub const CLIENT_VERSION: &str = env!("CARGO_PKG_VERSION");
 
+/// Read the body of one Content-Length framed message.
+async fn read_frame<R: tokio::io::AsyncBufRead + Unpin>(reader: &mut R) -> Result<Vec<u8>> {
+    // Read Content-Length header
+    let mut header_line = String::new();
+    reader.read_line(&mut header_line).await.context("Failed to read Content-Length header")?;
+
+    // Parse content length
+    let content_length = header_line
+        .trim()
+        .strip_prefix("Content-Length: ")
+        .context("Invalid header: missing Content-Length")?
+        .parse::<usize>()
+        .context("Invalid Content-Length value")?;
+
+    // Read empty line
+    let mut empty_line = String::new();
+    reader.read_line(&mut empty_line).await.context("Failed to read header separator")?;
+
+    if !empty_line.trim().is_empty() {
+        anyhow::bail!("Expected empty line after Content-Length header");
+    }
+
+    // Read response body
+    let mut body = vec![0u8; content_length];
+    reader.read_exact(&mut body).await.context("Failed to read response body")?;
+    Ok(body)
+}
+
 /// Ensure the daemon is running, starting it if necessary.
 ///
-/// If an existing daemon is running but was built from a different version of
-/// the binary (e.g. after `pip install --upgrade`), it is shut down and a fresh
-/// one is spawned so the user always talks to a daemon matching their CLI.
-pub async fn ensure_daemon_running() -> Result<()> {
+/// The initial ping doubles as a handshake: if an existing daemon was built
+/// from a different version of the binary (e.g. after `pip install --upgrade`)
+/// or speaks a different protocol version, it is shut down and a fresh one is
+/// spawned so the user always talks to a daemon matching their CLI.
+///
+/// Returns the connection that passed the handshake, so a command can send
+/// all of its requests over it instead of reconnecting.
+///
+/// With `TY_FIND_NO_DAEMON` set, no daemon is started or used: the client
+/// talks to a server inside this process instead.
+pub async fn ensure_daemon_running() -> Result<DaemonClient> {
+    if daemon_disabled() {
+        tracing::debug!("{NO_DAEMON_ENV} is set, serving requests in this process");
+        return Ok(DaemonClient::in_process(DEFAULT_TIMEOUT));
+    }
     let socket_path = get_socket_path()?;
     let pidfile_path = pidfile::get_pidfile_path()?;
 
@@ -454,15 +1113,20 @@ pub async fn ensure_daemon_running() -> Result<()> {
             Ok(mut client) => {
                 // Verify the running daemon has the same version as this binary.
                 match client.ping().await {
-                    Ok(ping) if ping.version == CLIENT_VERSION => {
+                    Ok(ping)
+                        if ping.version == CLIENT_VERSION && ping.protocol == PROTOCOL_VERSION =>
+                    {
                         tracing::debug!("Daemon already running (v{})", ping.version);
-                        return Ok(());
+                        return Ok(client);
                     }
                     Ok(ping) => {
                         tracing::warn!(
-                            "Daemon version mismatch: daemon v{}, client v{} — restarting",
+                            "Daemon version mismatch: daemon v{} (protocol v{}), client v{} \
+                             (protocol v{}) — restarting",
                             ping.version,
+                            ping.protocol,
                             CLIENT_VERSION,
+                            PROTOCOL_VERSION,
                         );
                         // Best-effort shutdown; ignore errors (e.g. if it already exited).
                         let _ = client.shutdown().await;
@@ -500,9 +1164,9 @@ pub async fn ensure_daemon_running() -> Result<()> {
         let ready = pidfile_path.exists() || socket_path.exists();
         if ready {
             match timeout(Duration::from_millis(500), DaemonClient::connect()).await {
-                Ok(Ok(_)) => {
+                Ok(Ok(client)) => {
                     tracing::info!("Daemon started successfully");
-                    return Ok(());
+                    return Ok(client);
                 }
                 Ok(Err(e)) => {
                     tracing::debug!("Connection attempt {} failed: {e}", i + 1);
@@ -525,11 +1189,17 @@ pub fn spawn_daemon() -> Result<()> {
     let exe = std::env::current_exe().context("Failed to get current executable path")?;
 
     // Spawn daemon process with --foreground so the child actually runs
-    // the server instead of spawning yet another process.
+    // the server instead of spawning yet another process, on the socket
+    // this client looks for.
+    // The backend is asked for per request; the daemon's own default stays
+    // each workspace's configured one.
     let child = Command::new(exe)
         .arg("daemon")
         .arg("start")
         .arg("--foreground")
+        .arg("--socket")
+        .arg(get_socket_path()?)
+        .env_remove(BACKEND_ENV)
         .stdin(Stdio::null())
         .stdout(Stdio::null())
         .stderr(Stdio::null())
@@ -541,19 +1211,76 @@ pub fn spawn_daemon() -> Result<()> {
     Ok(())
 }
 
+/// Socket path to use instead of the default, which `--socket` sets.
+pub const SOCKET_ENV: &str = "TY_FIND_SOCKET";
+
+/// Which daemon to use, `user` (the default) or `project`, which
+/// `--daemon-scope` sets.
+pub const SCOPE_ENV: &str = "TY_FIND_DAEMON_SCOPE";
+
+/// The process id a daemon started now watches, stopping once that process
+/// exits, which `--kill-after` sets to the shell that ran tyf.
+pub const OWNER_ENV: &str = "TY_FIND_DAEMON_OWNER";
+
+/// Serve requests inside this process instead of the background daemon when
+/// set (to anything but `0` or `false`), which `--no-daemon` does.
+pub const NO_DAEMON_ENV: &str = "TY_FIND_NO_DAEMON";
+
+/// Whether `TY_FIND_NO_DAEMON` asks to do without the background daemon.
+pub fn daemon_disabled() -> bool {
+    std::env::var(NO_DAEMON_ENV).is_ok_and(|value| {
+        !matches!(value.trim().to_ascii_lowercase().as_str(), "" | "0" | "false")
+    })
+}
+
+/// The socket of the current workspace's own daemon, once
+/// [`scope_to_workspace`] picked one.
+static PROJECT_SOCKET: OnceLock<PathBuf> = OnceLock::new();
+
+/// Use the daemon of `workspace_root` instead of the user's, if
+/// `TY_FIND_DAEMON_SCOPE` asks for one daemon per project.
+///
+/// Called once the workspace is known; a socket given with `--socket` (or
+/// `TY_FIND_SOCKET`) still wins.
+pub fn scope_to_workspace(workspace_root: &Path) {
+    let scope = std::env::var(SCOPE_ENV).unwrap_or_default();
+    if scope.trim().eq_ignore_ascii_case("project") {
+        if let Ok(path) = default_socket_path(Some(workspace_root)) {
+            let _ = PROJECT_SOCKET.set(path);
+        }
+    }
+}
+
 /// Get the path to the daemon socket.
 ///
-/// Returns `/tmp/ty-find-{uid}.sock` on Unix systems where {uid} is the
-/// current user ID. This ensures each user has their own daemon instance.
+/// `TY_FIND_SOCKET` when set, else the current project's socket under
+/// [`scope_to_workspace`], else `/tmp/ty-find-{uid}.sock` on Unix systems
+/// where {uid} is the current user ID. This ensures each user has their own
+/// daemon instance.
+pub fn get_socket_path() -> Result<PathBuf> {
+    if let Some(path) = std::env::var_os(SOCKET_ENV).filter(|p| !p.is_empty()) {
+        return Ok(PathBuf::from(path));
+    }
+    if let Some(path) = PROJECT_SOCKET.get() {
+        return Ok(path.clone());
+    }
+    default_socket_path(None)
+}
+
+/// `/tmp/ty-find-{uid}.sock`, or for a project
+/// `/tmp/ty-find-{uid}-{hash}.sock` with a hash of its root.
 #[allow(unsafe_code)]
 #[allow(clippy::unnecessary_wraps)] // Returns Err on non-Unix platforms
-pub fn get_socket_path() -> Result<PathBuf> {
+fn default_socket_path(workspace_root: Option<&Path>) -> Result<PathBuf> {
     #[cfg(unix)]
     {
         // SAFETY: `libc::getuid()` is a simple syscall that returns the real
         // user ID. It has no preconditions and cannot cause UB.
         let uid = unsafe { libc::getuid() };
-        let socket_name = format!("ty-find-{uid}.sock");
+        let socket_name = match workspace_root {
+            Some(root) => format!("ty-find-{uid}-{:016x}.sock", path_hash(root)),
+            None => format!("ty-find-{uid}.sock"),
+        };
         let socket_path = PathBuf::from("/tmp").join(socket_name);
         Ok(socket_path)
     }
@@ -561,10 +1288,19 @@ pub fn get_socket_path() -> Result<PathBuf> {
     #[cfg(not(unix))]
     {
         // Windows named pipe support would go here
+        let _ = workspace_root;
         anyhow::bail!("Daemon mode is only supported on Unix systems")
     }
 }
 
+/// FNV-1a hash of `path`, the same from one tyf version to the next so that
+/// an upgraded client still finds (and replaces) the project's old daemon.
+fn path_hash(path: &Path) -> u64 {
+    path.to_string_lossy().bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -596,6 +1332,19 @@ mod tests {
         }
     }
 
+    #[test]
+    #[cfg(unix)]
+    fn test_project_socket_path_is_stable_per_workspace() {
+        let api = default_socket_path(Some(Path::new("/src/api"))).unwrap();
+        assert_eq!(api, default_socket_path(Some(Path::new("/src/api"))).unwrap());
+        assert_ne!(api, default_socket_path(Some(Path::new("/src/web"))).unwrap());
+        assert_ne!(api, default_socket_path(None).unwrap());
+        assert!(api.to_string_lossy().ends_with(".sock"));
+        // FNV-1a's published test vector: the hash must not change between
+        // versions, or upgraded clients would start a second daemon.
+        assert_eq!(path_hash(Path::new("a")), 0xaf63_dc4c_8601_ec8c);
+    }
+
     #[test]
     fn test_daemon_request_creation() {
         let params = HoverParams {
@@ -603,6 +1352,7 @@ mod tests {
             file: PathBuf::from("file.py"),
             line: 10,
             column: 5,
+            content: None,
         };
 
         let params_value = serde_json::to_value(params).expect("should serialize params");
@@ -793,6 +1543,27 @@ mod tests {
         handle.await.expect("server task");
     }
 
+    #[tokio::test]
+    async fn test_unversioned_daemon_gets_clear_restart_message() {
+        // The fake daemon predates protocol versioning and answers every
+        // request with a ping-shaped result.
+        let (handle, data) = spawn_fake_daemon("0.0.1-old").await;
+
+        let mut client = DaemonClient::connect_with_pidfile(&data, DEFAULT_TIMEOUT)
+            .await
+            .expect("should connect via TCP fallback");
+
+        let err = client
+            .execute_ty_info(PathBuf::from("/workspace"))
+            .await
+            .expect_err("protocol mismatch should fail");
+        let message = err.to_string();
+        assert!(message.contains("running daemon speaks v0"), "unexpected: {message}");
+        assert!(message.contains("tyf daemon restart"), "unexpected: {message}");
+
+        handle.await.expect("server task");
+    }
+
     #[tokio::test]
     async fn test_version_match_detected() {
         let (handle, data) = spawn_fake_daemon(CLIENT_VERSION).await;
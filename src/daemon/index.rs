@@ -0,0 +1,713 @@
+//! Daemon-maintained symbol index.
+//!
+//! ty's `workspace/symbol` is correct but not instant: the first query after
+//! startup waits for ty to finish indexing, and every query pays for a fuzzy
+//! match over the whole project. The daemon therefore keeps its own index of
+//! top-level definitions (classes, functions, methods, module and class
+//! attributes) per workspace, built by a shallow line scanner rather than a
+//! full parser.
+//!
+//! The index is built on a background thread the first time a workspace is
+//! queried and then kept current by re-statting the tree every
+//! [`REFRESH_INTERVAL`] and rescanning only files whose size or mtime changed.
+//! Lookups also re-stat the files they are about to return, so an edit is
+//! visible on the very next query. While the index is still cold, lookups
+//! return `None` and the server falls back to ty.
+
+use std::collections::HashMap;
+use std::fs;
+use std::ops::ControlFlow;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock, Weak};
+use std::time::{Duration, SystemTime};
+
+use crate::lsp::position::{byte_to_column, LSP_UNIT};
+use crate::lsp::protocol::{Location, Position, Range, SymbolInformation, SymbolKind};
+use crate::lsp::uri::path_to_uri;
+use crate::workspace::navigation::is_identifier_char;
+use crate::workspace::ty_config::TyConfig;
+use crate::workspace::walker::WorkspaceWalker;
+
+/// How often the background thread re-stats the workspace.
+const REFRESH_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Python keywords that can be followed by `:` or `=` at the start of a line.
+const KEYWORDS: &[&str] = &[
+    "async", "await", "case", "class", "def", "del", "elif", "else", "except", "finally", "for",
+    "from", "global", "if", "import", "lambda", "match", "nonlocal", "pass", "raise", "return",
+    "try", "while", "with", "yield",
+];
+
+/// Size and modification time used to detect changed files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FileStamp {
+    modified: Option<SystemTime>,
+    len: u64,
+}
+
+impl FileStamp {
+    pub fn of(path: &Path) -> Option<Self> {
+        let meta = fs::metadata(path).ok()?;
+        Some(Self { modified: meta.modified().ok(), len: meta.len() })
+    }
+}
+
+struct FileEntry {
+    stamp: FileStamp,
+    symbols: Vec<SymbolInformation>,
+}
+
+/// Indexed state of one workspace.
+#[derive(Default)]
+struct IndexState {
+    files: HashMap<PathBuf, FileEntry>,
+    /// Symbol name → files defining a symbol of that name.
+    by_name: HashMap<String, Vec<PathBuf>>,
+}
+
+impl IndexState {
+    fn insert(&mut self, path: PathBuf, entry: FileEntry) {
+        self.remove(&path);
+        for symbol in &entry.symbols {
+            let files = self.by_name.entry(symbol.name.clone()).or_default();
+            if !files.contains(&path) {
+                files.push(path.clone());
+            }
+        }
+        self.files.insert(path, entry);
+    }
+
+    fn remove(&mut self, path: &Path) {
+        let Some(old) = self.files.remove(path) else {
+            return;
+        };
+        for symbol in &old.symbols {
+            if let Some(files) = self.by_name.get_mut(&symbol.name) {
+                files.retain(|p| p != path);
+                if files.is_empty() {
+                    self.by_name.remove(&symbol.name);
+                }
+            }
+        }
+    }
+
+    fn symbols_named<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a SymbolInformation> {
+        self.by_name
+            .get(name)
+            .into_iter()
+            .flatten()
+            .filter_map(|path| self.files.get(path))
+            .flat_map(move |entry| entry.symbols.iter().filter(move |s| s.name == name))
+    }
+}
+
+/// Symbol index for a single workspace.
+pub struct WorkspaceIndex {
+    root: PathBuf,
+    config: TyConfig,
+    /// `None` until the initial build has finished.
+    state: RwLock<Option<IndexState>>,
+    /// Bumped whenever a workspace file is seen to change.
+    generation: AtomicU64,
+}
+
+impl WorkspaceIndex {
+    fn new(root: PathBuf) -> Self {
+        let config = TyConfig::load_or_default(&root);
+        Self { root, config, state: RwLock::new(None), generation: AtomicU64::new(0) }
+    }
+
+    /// How many times workspace files were seen to change, by a refresh or
+    /// [`files_changed`](Self::files_changed). Answers that can depend on
+    /// any file, like an inferred type, stay valid while it stays the same.
+    pub fn generation(&self) -> u64 {
+        self.generation.load(Ordering::Relaxed)
+    }
+
+    /// Note that workspace files changed, ahead of the next refresh.
+    pub fn files_changed(&self) {
+        self.generation.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Whether the initial build has finished.
+    pub fn is_ready(&self) -> bool {
+        self.state.read().map(|s| s.is_some()).unwrap_or(false)
+    }
+
+    /// Files and symbols indexed so far, and roughly how many bytes they take.
+    pub fn footprint(&self) -> (usize, usize, u64) {
+        let Ok(state) = self.state.read() else {
+            return (0, 0, 0);
+        };
+        let Some(state) = state.as_ref() else {
+            return (0, 0, 0);
+        };
+        let path_bytes = |path: &Path| path.as_os_str().len() + size_of::<PathBuf>();
+        let mut symbols = 0;
+        let mut bytes = 0;
+        for (path, entry) in &state.files {
+            symbols += entry.symbols.len();
+            bytes += path_bytes(path) + size_of::<FileEntry>();
+            bytes += entry
+                .symbols
+                .iter()
+                .map(|symbol| {
+                    size_of::<SymbolInformation>()
+                        + symbol.name.len()
+                        + symbol.location.uri.len()
+                        + symbol.container_name.as_ref().map_or(0, String::len)
+                })
+                .sum::<usize>();
+        }
+        for (name, files) in &state.by_name {
+            bytes += name.len() + size_of::<String>();
+            bytes += files.iter().map(|path| path_bytes(path)).sum::<usize>();
+        }
+        (state.files.len(), symbols, bytes as u64)
+    }
+
+    /// Symbols named exactly `name`, or `None` while the index is cold.
+    pub fn lookup_exact(&self, name: &str) -> Option<Vec<SymbolInformation>> {
+        let candidates = self.candidate_files(|n| n == name)?;
+        self.revalidate(&candidates);
+        let state = self.state.read().ok()?;
+        let state = state.as_ref()?;
+        Some(state.symbols_named(name).cloned().collect())
+    }
+
+    /// Symbols whose name fuzzily matches `query`, or `None` while the index is cold.
+    ///
+    /// Matching is a case-insensitive subsequence test, like ty's. Exact
+    /// matches come first, then prefix matches, then the rest, each group
+    /// sorted by name.
+    pub fn lookup_fuzzy(&self, query: &str) -> Option<Vec<SymbolInformation>> {
+        let query = query.to_lowercase();
+        let candidates = self.candidate_files(|n| is_subsequence(&query, &n.to_lowercase()))?;
+        self.revalidate(&candidates);
+        let state = self.state.read().ok()?;
+        let state = state.as_ref()?;
+
+        let mut names: Vec<(u8, &str)> = state
+            .by_name
+            .keys()
+            .filter_map(|name| {
+                let lower = name.to_lowercase();
+                let rank = if lower == query {
+                    0
+                } else if lower.starts_with(&query) {
+                    1
+                } else if is_subsequence(&query, &lower) {
+                    2
+                } else {
+                    return None;
+                };
+                Some((rank, name.as_str()))
+            })
+            .collect();
+        names.sort_unstable();
+        Some(names.into_iter().flat_map(|(_, name)| state.symbols_named(name).cloned()).collect())
+    }
+
+    /// Files defining at least one name accepted by `matches`.
+    fn candidate_files(&self, matches: impl Fn(&str) -> bool) -> Option<Vec<PathBuf>> {
+        let state = self.state.read().ok()?;
+        let state = state.as_ref()?;
+        let mut files: Vec<PathBuf> = state
+            .by_name
+            .iter()
+            .filter(|(name, _)| matches(name))
+            .flat_map(|(_, files)| files.iter().cloned())
+            .collect();
+        files.sort_unstable();
+        files.dedup();
+        Some(files)
+    }
+
+    /// Rescan any of `paths` that changed since they were indexed.
+    fn revalidate(&self, paths: &[PathBuf]) {
+        let stale: Vec<(PathBuf, Option<FileStamp>)> = {
+            let Ok(state) = self.state.read() else { return };
+            let Some(state) = state.as_ref() else { return };
+            paths
+                .iter()
+                .filter_map(|path| {
+                    let stamp = FileStamp::of(path);
+                    let current = state.files.get(path).map(|e| e.stamp);
+                    (stamp != current).then(|| (path.clone(), stamp))
+                })
+                .collect()
+        };
+        if stale.is_empty() {
+            return;
+        }
+        let updates: Vec<(PathBuf, Option<FileEntry>)> = stale
+            .into_iter()
+            .map(|(path, stamp)| (path.clone(), scan_file(&path, stamp)))
+            .collect();
+        self.apply(updates);
+    }
+
+    /// Walk the workspace and rescan new or changed files, dropping deleted ones.
+    fn refresh(&self) {
+        let on_disk = walk_python_files(&self.root, &self.config);
+
+        let (changed, removed): (Vec<(PathBuf, FileStamp)>, Vec<PathBuf>) = {
+            let Ok(state) = self.state.read() else { return };
+            let empty = IndexState::default();
+            let state = state.as_ref().unwrap_or(&empty);
+            let changed = on_disk
+                .iter()
+                .filter(|&(path, stamp)| state.files.get(path).map(|e| e.stamp) != Some(*stamp))
+                .map(|(path, stamp)| (path.clone(), *stamp))
+                .collect();
+            let removed =
+                state.files.keys().filter(|path| !on_disk.contains_key(*path)).cloned().collect();
+            (changed, removed)
+        };
+
+        let mut updates: Vec<(PathBuf, Option<FileEntry>)> = changed
+            .into_iter()
+            .map(|(path, stamp)| {
+                let entry = scan_file(&path, Some(stamp));
+                (path, entry)
+            })
+            .collect();
+        updates.extend(removed.into_iter().map(|path| (path, None)));
+        let was_cold = !self.is_ready();
+        let count = updates.len();
+        self.apply(updates);
+        if was_cold {
+            tracing::info!("Symbol index ready for {} ({count} files)", self.root.display());
+        } else if count > 0 {
+            tracing::debug!("Symbol index refreshed {count} files in {}", self.root.display());
+        }
+    }
+
+    fn apply(&self, updates: Vec<(PathBuf, Option<FileEntry>)>) {
+        if !updates.is_empty() {
+            self.files_changed();
+        }
+        let Ok(mut state) = self.state.write() else { return };
+        let state = state.get_or_insert_with(IndexState::default);
+        for (path, entry) in updates {
+            match entry {
+                Some(entry) => state.insert(path, entry),
+                None => state.remove(&path),
+            }
+        }
+    }
+}
+
+/// Symbol indexes for every workspace the daemon serves.
+pub struct SymbolIndexes {
+    indexes: Mutex<HashMap<PathBuf, Arc<WorkspaceIndex>>>,
+}
+
+impl SymbolIndexes {
+    pub fn new() -> Self {
+        Self { indexes: Mutex::new(HashMap::new()) }
+    }
+
+    /// The index for `workspace`, starting a background build if there is none yet.
+    pub fn get_or_start(&self, workspace: &Path) -> Arc<WorkspaceIndex> {
+        let root = crate::lsp::uri::canonicalize(workspace).unwrap_or_else(|_| workspace.into());
+        let mut indexes = self.indexes.lock().expect("index mutex poisoned");
+        if let Some(index) = indexes.get(&root) {
+            return Arc::clone(index);
+        }
+
+        let index = Arc::new(WorkspaceIndex::new(root.clone()));
+        let weak = Arc::downgrade(&index);
+        let spawned = std::thread::Builder::new()
+            .name("tyf-symbol-index".to_string())
+            .spawn(move || maintain(&weak));
+        if let Err(e) = spawned {
+            tracing::warn!("Failed to start symbol index thread: {e}");
+        }
+        indexes.insert(root, Arc::clone(&index));
+        index
+    }
+
+    /// The index for `workspace`, if one was started.
+    pub fn get(&self, workspace: &Path) -> Option<Arc<WorkspaceIndex>> {
+        let indexes = self.indexes.lock().expect("index mutex poisoned");
+        indexes.get(workspace).cloned()
+    }
+
+    /// Drop indexes for workspaces not in `active`; returns how many were dropped.
+    ///
+    /// The maintenance thread exits on its next tick once its index is gone.
+    pub fn retain(&self, active: &[PathBuf]) -> usize {
+        let mut indexes = self.indexes.lock().expect("index mutex poisoned");
+        let before = indexes.len();
+        indexes.retain(|root, _| active.contains(root));
+        before - indexes.len()
+    }
+
+    /// Drop the indexes built with settings that have changed since, such
+    /// as `exclude`; returns their workspaces. The next request rebuilds them.
+    pub fn reload(&self) -> Vec<PathBuf> {
+        let mut indexes = self.indexes.lock().expect("index mutex poisoned");
+        let stale: Vec<PathBuf> = indexes
+            .iter()
+            .filter(|(root, index)| index.config != TyConfig::load_or_default(root))
+            .map(|(root, _)| root.clone())
+            .collect();
+        for root in &stale {
+            indexes.remove(root);
+        }
+        stale
+    }
+}
+
+impl Default for SymbolIndexes {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Build the index, then keep refreshing it for as long as someone holds it.
+fn maintain(index: &Weak<WorkspaceIndex>) {
+    loop {
+        match index.upgrade() {
+            Some(index) => index.refresh(),
+            None => return,
+        }
+        std::thread::sleep(REFRESH_INTERVAL);
+    }
+}
+
+/// Every Python file of the workspace (see [`WorkspaceWalker`]), with its stamp.
+pub fn walk_python_files(root: &Path, config: &TyConfig) -> HashMap<PathBuf, FileStamp> {
+    let mut files = HashMap::new();
+    WorkspaceWalker::new(root, config).walk(|path| {
+        if let Some(stamp) = FileStamp::of(path) {
+            files.insert(path.to_path_buf(), stamp);
+        }
+        ControlFlow::Continue(())
+    });
+    files
+}
+
+/// Read and scan one file; `None` if it vanished or is not valid UTF-8.
+fn scan_file(path: &Path, stamp: Option<FileStamp>) -> Option<FileEntry> {
+    let stamp = stamp?;
+    let text = fs::read_to_string(path).ok()?;
+    Some(FileEntry { stamp, symbols: scan_source(&text, &path_to_uri(path)) })
+}
+
+/// Lexer state carried from one physical line to the next.
+#[derive(Default)]
+struct LineLexer {
+    /// Closing delimiter of an open triple-quoted string.
+    triple: Option<&'static str>,
+    /// Open `(`, `[` and `{` count.
+    depth: usize,
+    /// The previous line ended with a backslash.
+    backslash: bool,
+}
+
+impl LineLexer {
+    /// Whether the next line continues the current logical line.
+    fn in_continuation(&self) -> bool {
+        self.triple.is_some() || self.depth > 0 || self.backslash
+    }
+
+    fn feed(&mut self, line: &str) {
+        let bytes = line.as_bytes();
+        let mut i = 0;
+        self.backslash = false;
+        while i < bytes.len() {
+            if let Some(close) = self.triple {
+                match line[i..].find(close) {
+                    Some(offset) => {
+                        i += offset + 3;
+                        self.triple = None;
+                    }
+                    None => return,
+                }
+                continue;
+            }
+            match bytes[i] {
+                b'#' => return,
+                b'(' | b'[' | b'{' => self.depth += 1,
+                b')' | b']' | b'}' => self.depth = self.depth.saturating_sub(1),
+                b'\\' if i + 1 == bytes.len() => self.backslash = true,
+                quote @ (b'"' | b'\'') => {
+                    if bytes[i..].starts_with(&[quote; 3]) {
+                        self.triple = Some(if quote == b'"' { "\"\"\"" } else { "'''" });
+                        i += 3;
+                        continue;
+                    }
+                    // Single-line string: skip to the closing quote.
+                    i += 1;
+                    while i < bytes.len() && bytes[i] != quote {
+                        i += if bytes[i] == b'\\' { 2 } else { 1 };
+                    }
+                }
+                _ => {}
+            }
+            i += 1;
+        }
+    }
+}
+
+enum Scope {
+    Class { indent: usize, name: String },
+    Function { indent: usize },
+}
+
+impl Scope {
+    fn indent(&self) -> usize {
+        match self {
+            Self::Class { indent, .. } | Self::Function { indent } => *indent,
+        }
+    }
+}
+
+/// Extract module- and class-level definitions from Python source.
+///
+/// This is a shallow scan, not a parse: it tracks strings, brackets and
+/// indentation well enough to find `class`/`def` statements and simple
+/// assignments, and skips function bodies entirely.
+pub fn scan_source(text: &str, uri: &str) -> Vec<SymbolInformation> {
+    let mut symbols: Vec<SymbolInformation> = Vec::new();
+    let mut scopes: Vec<Scope> = Vec::new();
+    let mut lexer = LineLexer::default();
+
+    for (line_no, line) in text.lines().enumerate() {
+        if lexer.in_continuation() {
+            lexer.feed(line);
+            continue;
+        }
+        lexer.feed(line);
+
+        let stripped = line.trim_start();
+        if stripped.is_empty() || stripped.starts_with('#') {
+            continue;
+        }
+        let indent = indent_width(line);
+        while scopes.last().is_some_and(|scope| indent <= scope.indent()) {
+            scopes.pop();
+        }
+        let class = match scopes.last() {
+            Some(Scope::Function { .. }) => continue,
+            Some(Scope::Class { name, .. }) => Some(name.clone()),
+            None => None,
+        };
+
+        let offset = line.len() - stripped.len();
+        let statement = stripped.strip_prefix("async ").map_or(stripped, str::trim_start);
+        let offset = offset + (stripped.len() - statement.len());
+
+        let (kind, name_at, scope) = if let Some(rest) = keyword_rest(statement, "class") {
+            let at = offset + (statement.len() - rest.len());
+            let Some(name) = identifier(rest) else { continue };
+            (SymbolKind::Class, at, Some(Scope::Class { indent, name: name.to_string() }))
+        } else if let Some(rest) = keyword_rest(statement, "def") {
+            let at = offset + (statement.len() - rest.len());
+            let kind = if class.is_some() { SymbolKind::Method } else { SymbolKind::Function };
+            (kind, at, Some(Scope::Function { indent }))
+        } else {
+            let Some(name) = identifier(statement) else { continue };
+            let after = statement[name.len()..].trim_start();
+            let is_assignment = (after.starts_with('=') && !after.starts_with("=="))
+                || (after.starts_with(':') && !after.starts_with(":="));
+            if !is_assignment || KEYWORDS.contains(&name) {
+                continue;
+            }
+            // Only the first binding of a name in a scope is its definition.
+            let seen = symbols.iter().any(|s| s.name == name && s.container_name == class);
+            if seen {
+                continue;
+            }
+            let is_constant = name.chars().any(char::is_alphabetic)
+                && name.chars().all(|c| c.is_uppercase() || c.is_ascii_digit() || c == '_');
+            let kind = if is_constant { SymbolKind::Constant } else { SymbolKind::Variable };
+            (kind, offset, None)
+        };
+
+        let Some(name) = identifier(&line[name_at..]) else { continue };
+        let start = byte_to_column(line, name_at, LSP_UNIT);
+        let end = byte_to_column(line, name_at + name.len(), LSP_UNIT);
+        #[allow(clippy::cast_possible_truncation)]
+        let line_no = line_no as u32;
+        symbols.push(SymbolInformation {
+            name: name.to_string(),
+            kind,
+            tags: None,
+            deprecated: None,
+            location: Location {
+                uri: uri.to_string(),
+                range: Range {
+                    start: Position { line: line_no, character: start },
+                    end: Position { line: line_no, character: end },
+                },
+            },
+            container_name: class,
+        });
+        if let Some(scope) = scope {
+            scopes.push(scope);
+        }
+    }
+    symbols
+}
+
+/// Text after `keyword` and the whitespace following it, if `statement` starts with it.
+fn keyword_rest<'a>(statement: &'a str, keyword: &str) -> Option<&'a str> {
+    let rest = statement.strip_prefix(keyword)?;
+    let trimmed = rest.trim_start();
+    (trimmed.len() < rest.len()).then_some(trimmed)
+}
+
+/// The Python identifier at the start of `text`.
+fn identifier(text: &str) -> Option<&str> {
+    let end =
+        text.char_indices().find(|&(_, c)| !is_identifier_char(c)).map_or(text.len(), |(i, _)| i);
+    let ident = &text[..end];
+    (!ident.is_empty() && !ident.starts_with(|c: char| c.is_ascii_digit())).then_some(ident)
+}
+
+/// Indentation width, expanding tabs to the next multiple of 8 as the Python tokenizer does.
+fn indent_width(line: &str) -> usize {
+    let mut width = 0;
+    for c in line.chars() {
+        match c {
+            ' ' => width += 1,
+            '\t' => width = (width / 8 + 1) * 8,
+            _ => break,
+        }
+    }
+    width
+}
+
+fn is_subsequence(needle: &str, haystack: &str) -> bool {
+    let mut chars = haystack.chars();
+    needle.chars().all(|n| chars.any(|h| h == n))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(symbols: &[SymbolInformation]) -> Vec<(String, SymbolKind, Option<String>)> {
+        symbols.iter().map(|s| (s.name.clone(), s.kind.clone(), s.container_name.clone())).collect()
+    }
+
+    #[test]
+    fn test_scan_finds_top_level_and_class_definitions() {
+        let source = r#"import os
+
+MAX_SIZE = 10
+counter: int = 0
+
+@dataclass
+class Calculator:
+    """A calculator.
+
+    x = 1 inside a docstring is not a symbol
+    """
+    precision: int = 2
+
+    def add(self, a, b):
+        total = a + b
+        return total
+
+    async def fetch(self):
+        pass
+
+def helper(
+    a,
+    b,
+):
+    nested = 1
+
+async def main():
+    pass
+"#;
+        let symbols = scan_source(source, "file:///ws/example.py");
+        let calc = Some("Calculator".to_string());
+        assert_eq!(
+            names(&symbols),
+            vec![
+                ("MAX_SIZE".to_string(), SymbolKind::Constant, None),
+                ("counter".to_string(), SymbolKind::Variable, None),
+                ("Calculator".to_string(), SymbolKind::Class, None),
+                ("precision".to_string(), SymbolKind::Variable, calc.clone()),
+                ("add".to_string(), SymbolKind::Method, calc.clone()),
+                ("fetch".to_string(), SymbolKind::Method, calc),
+                ("helper".to_string(), SymbolKind::Function, None),
+                ("main".to_string(), SymbolKind::Function, None),
+            ]
+        );
+
+        let add = &symbols[4];
+        assert_eq!(add.location.range.start.line, 13);
+        assert_eq!(add.location.range.start.character, 8);
+        assert_eq!(add.location.range.end.character, 11);
+    }
+
+    #[test]
+    fn test_scan_skips_keywords_and_comparisons() {
+        let source = "if x == 1:\n    pass\nelse:\n    y = 2\ntry:\n    pass\nfinally:\n    pass\n";
+        let symbols = scan_source(source, "file:///ws/a.py");
+        // `y` is bound at module level even though it sits inside `else:`.
+        assert_eq!(names(&symbols), vec![("y".to_string(), SymbolKind::Variable, None)]);
+    }
+
+    #[test]
+    fn test_scan_keeps_first_binding_only() {
+        let symbols = scan_source("x = 1\nx = 2\n", "file:///ws/a.py");
+        assert_eq!(symbols.len(), 1);
+        assert_eq!(symbols[0].location.range.start.line, 0);
+    }
+
+    #[test]
+    fn test_scan_uses_utf16_columns() {
+        let symbols = scan_source("class Ünïcode: pass\nclass 𝒳ray: pass\n", "file:///ws/a.py");
+        assert_eq!(symbols[0].name, "Ünïcode");
+        assert_eq!(symbols[0].location.range.end.character, 13);
+        assert_eq!(symbols[1].location.range.start.character, 6);
+        // U+1D4B3 is two UTF-16 code units.
+        assert_eq!(symbols[1].location.range.end.character, 6 + 2 + 3);
+    }
+
+    #[test]
+    fn test_index_lookup_and_refresh() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let root = dir.path().canonicalize().expect("canonicalize");
+        fs::create_dir_all(root.join("pkg")).expect("mkdir");
+        fs::create_dir_all(root.join("node_modules/dep")).expect("mkdir");
+        fs::write(root.join("pkg/models.py"), "class User:\n    def save(self): pass\n")
+            .expect("write");
+        fs::write(root.join("node_modules/dep/x.py"), "class User: pass\n").expect("write");
+
+        let index = WorkspaceIndex::new(root.clone());
+        assert!(index.lookup_exact("User").is_none(), "cold index answers nothing");
+        index.refresh();
+
+        let users = index.lookup_exact("User").expect("ready");
+        assert_eq!(users.len(), 1, "node_modules is excluded");
+        let fuzzy = index.lookup_fuzzy("sav").expect("ready");
+        assert_eq!(fuzzy[0].name, "save");
+        assert_eq!(fuzzy[0].container_name.as_deref(), Some("User"));
+
+        // Edits are picked up on lookup without waiting for a refresh.
+        fs::write(root.join("pkg/models.py"), "class Account: pass\n").expect("write");
+        assert!(index.lookup_exact("User").expect("ready").is_empty());
+
+        // New files are picked up by the next refresh.
+        fs::write(root.join("pkg/more.py"), "def create_user(): pass\n").expect("write");
+        let generation = index.generation();
+        index.refresh();
+        assert_eq!(index.lookup_exact("create_user").expect("ready").len(), 1);
+        assert!(index.generation() > generation, "a change moves the generation on");
+
+        let generation = index.generation();
+        index.refresh();
+        assert_eq!(index.generation(), generation, "nothing changed");
+        index.files_changed();
+        assert_eq!(index.generation(), generation + 1);
+    }
+}
@@ -4,11 +4,14 @@
 //! between CLI invocations, enabling fast response times (<100ms) for
 //! subsequent requests.
 
+pub mod cache;
 pub mod client;
+pub mod index;
 pub mod pidfile;
 pub mod pool;
 pub mod protocol;
 pub mod server;
+pub mod stats;
 
 // Re-export main types for convenience
 #[allow(unused_imports)]
@@ -100,24 +100,14 @@ impl PidfileData {
     }
 }
 
-/// Get the path to the pidfile for the current user.
+/// Get the path to the pidfile of the daemon [`get_socket_path`] names.
 ///
-/// Returns `/tmp/ty-find-{uid}.pid` on Unix systems.
-#[allow(unsafe_code)]
-#[allow(clippy::unnecessary_wraps)] // Returns Err on non-Unix platforms
+/// Returns `/tmp/ty-find-{uid}.pid` on Unix systems by default; the socket
+/// path with a `.pid` extension in general.
+///
+/// [`get_socket_path`]: super::client::get_socket_path
 pub fn get_pidfile_path() -> Result<PathBuf> {
-    #[cfg(unix)]
-    {
-        // SAFETY: `libc::getuid()` is a simple syscall that returns the real
-        // user ID. It has no preconditions and cannot cause UB.
-        let uid = unsafe { libc::getuid() };
-        Ok(PathBuf::from(format!("/tmp/ty-find-{uid}.pid")))
-    }
-
-    #[cfg(not(unix))]
-    {
-        anyhow::bail!("Pidfile is only supported on Unix systems")
-    }
+    Ok(super::client::get_socket_path()?.with_extension("pid"))
 }
 
 /// Remove the pidfile if it exists. Errors are logged but not propagated.
@@ -3,6 +3,11 @@
 //! This module manages a pool of `TyLspClient` instances, one per workspace.
 //! Each client maintains a persistent connection to a ty LSP server process,
 //! allowing for fast response times on subsequent requests.
+//!
+//! A multi-root workspace (a monorepo whose config lists its package
+//! directories as `roots`) gets one client with every root as a workspace
+//! folder. Requests for a workspace inside one of those roots are routed to
+//! that client instead of starting a server of their own.
 
 #![allow(dead_code)]
 
@@ -11,8 +16,14 @@ use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
+use tokio::sync::broadcast;
 
-use crate::lsp::client::TyLspClient;
+use crate::config::Config;
+use crate::lsp::backend;
+use crate::lsp::client::{ServerFeeds, TyLspClient};
+use crate::lsp::protocol::WorkProgress;
+use crate::lsp::trace::WireMessage;
+use crate::workspace::detection::WorkspaceDetector;
 
 /// Entry in the LSP client pool, tracking the client and its last access time.
 struct PoolEntry {
@@ -20,6 +31,23 @@ struct PoolEntry {
     client: Arc<TyLspClient>,
     /// Last time this client was accessed
     last_access: Instant,
+    /// The backend spec the client's server was started from, e.g. `ty`.
+    backend: String,
+}
+
+/// A client in the pool, as listed by [`LspClientPool::pooled`].
+pub struct PooledClient {
+    pub workspace: PathBuf,
+    pub client: Arc<TyLspClient>,
+    /// The backend spec the client's server was started from, e.g. `ty`.
+    pub backend: String,
+    /// Time since a request last used the client.
+    pub idle: Duration,
+}
+
+/// How a client's server ended, for logs: `exited (signal: 9 (SIGKILL))`.
+fn exit_description(client: &TyLspClient) -> String {
+    client.exit_status().map_or_else(|| "exited".to_string(), |status| format!("exited ({status})"))
 }
 
 /// Manages a pool of LSP clients, one per workspace.
@@ -54,6 +82,24 @@ struct PoolEntry {
 pub struct LspClientPool {
     /// Map of workspace paths to LSP client entries
     entries: Arc<Mutex<HashMap<PathBuf, PoolEntry>>>,
+    /// Backend specs requested with `--backend`, by workspace; other
+    /// workspaces use their configured backend.
+    backends: Mutex<HashMap<PathBuf, String>>,
+    /// How many times a server was started for each workspace.
+    starts: Mutex<HashMap<PathBuf, u64>>,
+    /// How many times each workspace's server exited and was dropped so the
+    /// next request starts a new one.
+    restarts: Mutex<HashMap<PathBuf, u64>>,
+    /// The pool key each requested workspace resolved to: itself, or the
+    /// multi-root workspace listing it among its `roots`.
+    routes: Mutex<HashMap<PathBuf, PathBuf>>,
+    /// Held while a workspace's server starts, by workspace; an entry goes
+    /// once the last request waiting on it is done.
+    starting: Mutex<HashMap<PathBuf, Arc<tokio::sync::Mutex<()>>>>,
+    /// Where each workspace's servers send their progress updates and
+    /// messages; kept across restarts so subscribers don't miss the new
+    /// server's indexing.
+    feeds: Mutex<HashMap<PathBuf, ServerFeeds>>,
 }
 
 impl LspClientPool {
@@ -67,7 +113,15 @@ impl LspClientPool {
     /// let pool = LspClientPool::new();
     /// ```
     pub fn new() -> Self {
-        Self { entries: Arc::new(Mutex::new(HashMap::new())) }
+        Self {
+            entries: Arc::new(Mutex::new(HashMap::new())),
+            backends: Mutex::new(HashMap::new()),
+            starts: Mutex::new(HashMap::new()),
+            restarts: Mutex::new(HashMap::new()),
+            routes: Mutex::new(HashMap::new()),
+            starting: Mutex::new(HashMap::new()),
+            feeds: Mutex::new(HashMap::new()),
+        }
     }
 
     /// Gets an existing LSP client for the workspace, or creates a new one if it doesn't exist.
@@ -106,43 +160,233 @@ impl LspClientPool {
     /// # }
     /// ```
     pub async fn get_or_create(&self, workspace: PathBuf) -> Result<Arc<TyLspClient>> {
-        // Fast path: return existing client without any async work.
-        {
-            let mut entries = self.entries.lock().expect("pool mutex poisoned");
-            if let Some(entry) = entries.get_mut(&workspace) {
-                entry.last_access = Instant::now();
-                return Ok(Arc::clone(&entry.client));
-            }
+        let workspace = self.route(&workspace);
+        if let Some(client) = self.live_client(&workspace) {
+            return Ok(client);
         }
-        // Lock is dropped here — no MutexGuard held across the `.await` below.
 
-        // Slow path: create a new LSP client (spawns a `ty` process).
+        // Slow path: one start per workspace at a time. Requests arriving
+        // while ty starts wait here and then share its client, instead of
+        // each spawning a `ty` process of its own.
+        let gate = Arc::clone(
+            self.starting
+                .lock()
+                .expect("pool mutex poisoned")
+                .entry(workspace.clone())
+                .or_default(),
+        );
+        let started = {
+            let _starting = gate.lock().await;
+            match self.live_client(&workspace) {
+                Some(client) => Ok(client),
+                None => self.start(workspace.clone()).await,
+            }
+        };
+        self.release_start_gate(&workspace, &gate);
+        started
+    }
+
+    /// Start a server for the (routed) `workspace` and pool its client.
+    async fn start(&self, workspace: PathBuf) -> Result<Arc<TyLspClient>> {
         let workspace_str = workspace.to_str().context("Invalid workspace path")?;
+        let spec = self.backend_for(&workspace);
+        let folders = workspace_folders(&workspace);
+        let feeds = self.feeds(&workspace);
         let client =
-            TyLspClient::new(workspace_str).await.context("Failed to create LSP client")?;
+            TyLspClient::with_feeds(workspace_str, &folders, backend::from_spec(&spec)?, feeds)
+                .await
+                .context("Failed to create LSP client")?;
         let client_arc = Arc::new(client);
 
-        // Re-check: another task may have created a client for this workspace
-        // while we were awaiting. Use theirs if so (ours gets dropped).
+        *self.starts.lock().expect("pool mutex poisoned").entry(workspace.clone()).or_default() +=
+            1;
+        self.entries.lock().expect("pool mutex poisoned").insert(
+            workspace,
+            PoolEntry {
+                client: Arc::clone(&client_arc),
+                last_access: Instant::now(),
+                backend: spec,
+            },
+        );
+        Ok(client_arc)
+    }
+
+    /// Forget `workspace`'s start gate unless another request still waits on
+    /// it. Requests take their clone of a gate under the same lock, so none
+    /// can pick it up after it was checked.
+    fn release_start_gate(&self, workspace: &Path, gate: &Arc<tokio::sync::Mutex<()>>) {
+        let mut starting = self.starting.lock().expect("pool mutex poisoned");
+        // The map's reference and the caller's.
+        let unused = starting
+            .get(workspace)
+            .is_some_and(|held| Arc::ptr_eq(held, gate) && Arc::strong_count(gate) == 2);
+        if unused {
+            starting.remove(workspace);
+        }
+    }
+
+    /// Receive the progress updates of `workspace`'s server from now on,
+    /// including those of a server that hasn't started yet.
+    pub fn subscribe_progress(&self, workspace: &Path) -> broadcast::Receiver<WorkProgress> {
+        self.feeds(&self.route(workspace)).progress.subscribe()
+    }
+
+    /// Receive every message exchanged with `workspace`'s server from now on.
+    pub fn subscribe_wire(&self, workspace: &Path) -> broadcast::Receiver<WireMessage> {
+        self.feeds(&self.route(workspace)).wire.subscribe()
+    }
+
+    /// The feeds of the (routed) `workspace`'s servers.
+    fn feeds(&self, workspace: &Path) -> ServerFeeds {
+        self.feeds
+            .lock()
+            .expect("pool mutex poisoned")
+            .entry(workspace.to_path_buf())
+            .or_default()
+            .clone()
+    }
+
+    /// The running client for the (routed) `workspace`, touching its last
+    /// access time. A client whose server has exited is dropped, so the
+    /// caller starts a new one.
+    fn live_client(&self, workspace: &Path) -> Option<Arc<TyLspClient>> {
+        let mut entries = self.entries.lock().expect("pool mutex poisoned");
+        let entry = entries.get_mut(workspace)?;
+        if !entry.client.has_exited() {
+            entry.last_access = Instant::now();
+            return Some(Arc::clone(&entry.client));
+        }
+        tracing::warn!(
+            "ty server for {} {}; restarting it",
+            workspace.display(),
+            exit_description(&entry.client)
+        );
+        entries.remove(workspace);
+        self.count_restart(workspace);
+        None
+    }
+
+    fn count_restart(&self, workspace: &Path) {
+        *self
+            .restarts
+            .lock()
+            .expect("pool mutex poisoned")
+            .entry(workspace.to_path_buf())
+            .or_default() += 1;
+    }
+
+    /// Drop the clients whose server has exited, returning how many.
+    ///
+    /// The next request for one of those workspaces starts a new server;
+    /// until then `daemon status` no longer lists it.
+    pub fn evict_exited(&self) -> usize {
+        let mut entries = self.entries.lock().expect("pool mutex poisoned");
+        let mut evicted = 0;
+        entries.retain(|workspace, entry| {
+            let exited = entry.client.has_exited();
+            if exited {
+                tracing::warn!(
+                    "ty server for {} {}",
+                    workspace.display(),
+                    exit_description(&entry.client)
+                );
+                self.count_restart(workspace);
+                evicted += 1;
+            }
+            !exited
+        });
+        evicted
+    }
+
+    /// Record the backend a request for `workspace` asks for: `spec`, or the
+    /// workspace's configured one when `None`.
+    ///
+    /// A running server of another backend is dropped, so the next
+    /// [`get_or_create`](Self::get_or_create) starts the requested one.
+    /// Returns whether a server was dropped.
+    pub fn request_backend(&self, workspace: &Path, spec: Option<&str>) -> bool {
+        let workspace = self.route(workspace);
         {
-            let mut entries = self.entries.lock().expect("pool mutex poisoned");
-            if let Some(entry) = entries.get_mut(&workspace) {
-                entry.last_access = Instant::now();
-                return Ok(Arc::clone(&entry.client));
+            let mut backends = self.backends.lock().expect("pool mutex poisoned");
+            match spec {
+                Some(spec) => backends.insert(workspace.clone(), spec.to_string()),
+                None => backends.remove(&workspace),
+            };
+        }
+        let running = {
+            let entries = self.entries.lock().expect("pool mutex poisoned");
+            entries.get(&workspace).map(|entry| entry.backend.clone())
+        };
+        match running {
+            Some(running) if running != self.backend_for(&workspace) => {
+                tracing::info!("Switching {} away from {running}", workspace.display());
+                self.remove(&workspace);
+                true
             }
-            entries.insert(
-                workspace,
-                PoolEntry { client: Arc::clone(&client_arc), last_access: Instant::now() },
-            );
+            _ => false,
         }
+    }
 
-        Ok(client_arc)
+    /// The pool key of `workspace`: the multi-root workspace it is a root of,
+    /// else the workspace itself.
+    ///
+    /// Keys are canonical paths, so a workspace reached through a symlink
+    /// shares one ty server with its real path. Each workspace is resolved
+    /// once; a changed `roots` setting takes effect on [`reload`](Self::reload)
+    /// or when the daemon restarts.
+    pub fn route(&self, workspace: &Path) -> PathBuf {
+        let workspace =
+            crate::lsp::uri::canonicalize(workspace).unwrap_or_else(|_| workspace.to_path_buf());
+        if let Some(key) = self.routes.lock().expect("pool mutex poisoned").get(&workspace) {
+            return key.clone();
+        }
+        let key = multi_root_owner(&workspace).unwrap_or_else(|| workspace.clone());
+        if key != workspace {
+            tracing::info!("Serving {} from multi-root {}", workspace.display(), key.display());
+        }
+        self.routes.lock().expect("pool mutex poisoned").insert(workspace, key.clone());
+        key
+    }
+
+    /// Re-read the settings the pool holds on to: forget how workspaces were
+    /// routed, so a changed `roots` applies from their next request, and drop
+    /// the servers whose configured backend changed (e.g. a new `ty-path`),
+    /// returning their workspaces. Every other server keeps running.
+    pub fn reload(&self) -> Vec<PathBuf> {
+        self.routes.lock().expect("pool mutex poisoned").clear();
+        let running: Vec<(PathBuf, String)> = {
+            let entries = self.entries.lock().expect("pool mutex poisoned");
+            entries
+                .iter()
+                .map(|(workspace, entry)| (workspace.clone(), entry.backend.clone()))
+                .collect()
+        };
+        // Config files are read outside the lock.
+        let stale: Vec<PathBuf> = running
+            .into_iter()
+            .filter(|(workspace, spec)| *spec != self.backend_for(workspace))
+            .map(|(workspace, _)| workspace)
+            .collect();
+        let mut entries = self.entries.lock().expect("pool mutex poisoned");
+        for workspace in &stale {
+            tracing::info!("Backend of {} changed; its server restarts", workspace.display());
+            entries.remove(workspace);
+        }
+        stale
+    }
+
+    /// The backend spec to start `workspace`'s server from.
+    fn backend_for(&self, workspace: &Path) -> String {
+        let requested = self.backends.lock().expect("pool mutex poisoned").get(workspace).cloned();
+        requested.unwrap_or_else(|| backend::selected(workspace))
     }
 
     /// Removes the LSP client for the specified workspace from the pool.
     ///
     /// This will shut down the LSP server connection for that workspace.
-    /// If the workspace is not in the pool, this is a no-op.
+    /// If the workspace is not in the pool, this is a no-op. Returns the
+    /// workspace whose client was removed: `workspace`, or the multi-root
+    /// workspace it is routed to.
     ///
     /// # Arguments
     ///
@@ -159,9 +403,16 @@ impl LspClientPool {
     ///
     /// pool.remove(&workspace);
     /// ```
-    pub fn remove(&self, workspace: &Path) {
+    pub fn remove(&self, workspace: &Path) -> Option<PathBuf> {
+        let workspace = self.route(workspace);
         let mut entries = self.entries.lock().expect("pool mutex poisoned");
-        entries.remove(workspace);
+        entries.remove(&workspace).map(|_| workspace)
+    }
+
+    /// Removes every client, returning their workspaces.
+    pub fn clear(&self) -> Vec<PathBuf> {
+        let mut entries = self.entries.lock().expect("pool mutex poisoned");
+        entries.drain().map(|(workspace, _)| workspace).collect()
     }
 
     /// Removes all LSP clients that haven't been accessed within the specified timeout.
@@ -210,7 +461,8 @@ impl LspClientPool {
 
     /// Returns a list of all active workspace paths in the pool.
     ///
-    /// The workspaces are returned in arbitrary order.
+    /// The workspaces are returned in arbitrary order. Workspaces routed to
+    /// an active multi-root workspace count as active too.
     ///
     /// # Returns
     ///
@@ -230,7 +482,55 @@ impl LspClientPool {
     /// ```
     pub fn active_workspaces(&self) -> Vec<PathBuf> {
         let entries = self.entries.lock().expect("pool mutex poisoned");
-        entries.keys().cloned().collect()
+        let routes = self.routes.lock().expect("pool mutex poisoned");
+        let packages = routes
+            .iter()
+            .filter(|(from, to)| from != to && entries.contains_key(*to))
+            .map(|(from, _)| from.clone());
+        entries.keys().cloned().chain(packages).collect()
+    }
+
+    /// Whether a language server is running for `workspace`.
+    pub fn contains(&self, workspace: &Path) -> bool {
+        let workspace = self.route(workspace);
+        self.entries.lock().expect("pool mutex poisoned").contains_key(&workspace)
+    }
+
+    /// Returns every active workspace together with its client.
+    ///
+    /// The clients are cloned `Arc`s, so callers can inspect them after the
+    /// pool lock has been released.
+    pub fn clients(&self) -> Vec<(PathBuf, Arc<TyLspClient>)> {
+        let entries = self.entries.lock().expect("pool mutex poisoned");
+        entries.iter().map(|(path, entry)| (path.clone(), Arc::clone(&entry.client))).collect()
+    }
+
+    /// Every client with its workspace, backend spec and time since last use.
+    pub fn pooled(&self) -> Vec<PooledClient> {
+        let entries = self.entries.lock().expect("pool mutex poisoned");
+        entries
+            .iter()
+            .map(|(workspace, entry)| PooledClient {
+                workspace: workspace.clone(),
+                client: Arc::clone(&entry.client),
+                backend: entry.backend.clone(),
+                idle: entry.last_access.elapsed(),
+            })
+            .collect()
+    }
+
+    /// How many times a server was started for each workspace since the pool
+    /// was created, including workspaces that have since gone idle.
+    pub fn starts(&self) -> HashMap<PathBuf, u64> {
+        self.starts.lock().expect("pool mutex poisoned").clone()
+    }
+
+    /// How many times each workspace's server exited and had to be replaced.
+    ///
+    /// Unlike [`starts`](Self::starts), servers stopped on purpose (gone
+    /// idle, switched backends, dropped) don't count.
+    pub fn restarts(&self) -> HashMap<PathBuf, u64> {
+        self.restarts.lock().expect("pool mutex poisoned").clone()
     }
 
     /// Returns the number of active LSP clients in the pool.
@@ -263,6 +563,35 @@ impl LspClientPool {
     }
 }
 
+/// The `roots` `workspace` is configured with, canonicalized; roots that
+/// don't exist are skipped.
+fn workspace_folders(workspace: &Path) -> Vec<PathBuf> {
+    Config::load_or_default(workspace)
+        .roots(workspace)
+        .into_iter()
+        .filter_map(|root| match crate::lsp::uri::canonicalize(&root) {
+            Ok(root) => Some(root),
+            Err(e) => {
+                tracing::warn!("Ignoring root {}: {e}", root.display());
+                None
+            }
+        })
+        .collect()
+}
+
+/// The nearest enclosing workspace that lists `workspace`, or a directory
+/// containing it, among its `roots`.
+fn multi_root_owner(workspace: &Path) -> Option<PathBuf> {
+    let mut dir = workspace.parent().map(Path::to_path_buf);
+    while let Some(root) = dir.as_deref().and_then(WorkspaceDetector::find_workspace_root) {
+        if workspace_folders(&root).iter().any(|folder| workspace.starts_with(folder)) {
+            return Some(root);
+        }
+        dir = root.parent().map(Path::to_path_buf);
+    }
+    None
+}
+
 impl Default for LspClientPool {
     fn default() -> Self {
         Self::new()
@@ -294,8 +623,40 @@ mod tests {
         let workspace = PathBuf::from("/nonexistent");
 
         // Should not panic
-        pool.remove(&workspace);
+        assert_eq!(pool.remove(&workspace), None);
         assert_eq!(pool.len(), 0);
+        assert!(pool.clear().is_empty());
+        assert!(pool.pooled().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_failed_start_leaves_no_start_gate() {
+        let dir = tempfile::tempdir().unwrap();
+        let pool = LspClientPool::new();
+        pool.request_backend(dir.path(), Some(""));
+        assert!(pool.get_or_create(dir.path().to_path_buf()).await.is_err());
+        assert!(pool.starting.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_routes_package_to_multi_root_workspace() {
+        let dir = tempfile::tempdir().unwrap();
+        let mono = crate::lsp::uri::canonicalize(dir.path()).unwrap();
+        std::fs::write(mono.join("pyproject.toml"), "[tool.ty-find]\nroots = [\"packages/api\"]\n")
+            .unwrap();
+        let api = mono.join("packages/api");
+        let other = mono.join("packages/other");
+        for package in [&api, &other] {
+            std::fs::create_dir_all(package).unwrap();
+            std::fs::write(package.join("pyproject.toml"), "").unwrap();
+        }
+
+        let pool = LspClientPool::new();
+        assert_eq!(pool.route(&api), mono);
+        assert_eq!(pool.route(&api.join("src")), mono);
+        assert_eq!(pool.route(&other), other);
+        assert_eq!(pool.route(&mono), mono);
+        assert_eq!(workspace_folders(&mono), [api]);
     }
 
     #[test]
@@ -8,11 +8,42 @@
 
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use serde_repr::{Deserialize_repr, Serialize_repr};
 use std::path::PathBuf;
 
+use crate::lsp::trace::WireMessage;
+use crate::workspace::filter::PathFilter;
+use crate::workspace::page::ResultPage;
+
 // Re-export LSP types that are used in responses
-pub use crate::lsp::protocol::{DocumentSymbol, Hover, Location, Range, SymbolInformation};
+pub use crate::lsp::protocol::{
+    Diagnostic, DiagnosticSeverity, DocumentHighlight, DocumentHighlightKind, DocumentSymbol,
+    FileChangeType, GotoKind, Hover, InlayHint, Location, SymbolInformation, SymbolKind,
+    WorkProgress,
+};
+
+/// Version of the CLI ↔ daemon protocol spoken by this binary.
+///
+/// Bump it whenever a change to methods, params or results would make an older
+/// peer misread a message. Adding optional (`#[serde(default)]`) fields does not
+/// need a bump. Peers from before versioning existed send no version, which
+/// deserializes as 0.
+pub const PROTOCOL_VERSION: u32 = 5;
+
+/// Compatibility rule: both sides must speak exactly the same protocol version.
+///
+/// `ping` and `shutdown` are exempt so any client can always inspect and stop
+/// any daemon (that's how a stale daemon gets replaced).
+pub fn protocol_compatible(method: Method, peer: u32) -> bool {
+    matches!(method, Method::Ping | Method::Shutdown) || peer == PROTOCOL_VERSION
+}
+
+/// User-facing explanation of a protocol mismatch.
+pub fn protocol_mismatch_message(client: u32, daemon: u32) -> String {
+    format!(
+        "tyf speaks daemon protocol v{client} but the running daemon speaks v{daemon}. \
+         Restart your daemon with `tyf daemon restart`"
+    )
+}
 
 /// JSON-RPC 2.0 request from CLI to daemon.
 ///
@@ -47,6 +78,35 @@ pub struct DaemonRequest {
     /// When true, the daemon includes raw LSP request/response in the response.
     #[serde(default, skip_serializing_if = "is_false")]
     pub debug: bool,
+
+    /// Protocol version of the client (0 for clients that predate versioning).
+    #[serde(default)]
+    pub protocol: u32,
+
+    /// LSP backend the client asks for (`--backend`); `None` means the
+    /// workspace's configured one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub backend: Option<String>,
+
+    /// Process id of the client, so a `cancel` from the same process can
+    /// abandon this request.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pid: Option<u32>,
+
+    /// When true, the daemon sends [`DaemonNotification`]s about the
+    /// workspace's progress before the response.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub progress: bool,
+
+    /// When true, the daemon may send parts of the result it has ready as
+    /// `partial` [`DaemonNotification`]s; the response then holds the rest.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub stream: bool,
+
+    /// When true, the daemon forwards the messages it exchanges with the
+    /// workspace's language server as `trace` [`DaemonNotification`]s.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub trace: bool,
 }
 
 #[allow(clippy::trivially_copy_pass_by_ref)]
@@ -54,6 +114,11 @@ fn is_false(v: &bool) -> bool {
     !v
 }
 
+#[allow(clippy::trivially_copy_pass_by_ref)]
+const fn is_zero(v: &usize) -> bool {
+    *v == 0
+}
+
 impl DaemonRequest {
     /// Create a new daemon request with auto-generated ID.
     pub fn new(method: Method, params: Value) -> Self {
@@ -66,12 +131,141 @@ impl DaemonRequest {
             method,
             params,
             debug: false,
+            protocol: PROTOCOL_VERSION,
+            backend: None,
+            pid: Some(std::process::id()),
+            progress: false,
+            stream: false,
+            trace: false,
         }
     }
 
     /// Create a request with a specific ID.
     pub fn with_id(id: u64, method: Method, params: Value) -> Self {
-        Self { jsonrpc: "2.0".to_string(), id, method, params, debug: false }
+        Self {
+            jsonrpc: "2.0".to_string(),
+            id,
+            method,
+            params,
+            debug: false,
+            protocol: PROTOCOL_VERSION,
+            backend: None,
+            pid: Some(std::process::id()),
+            progress: false,
+            stream: false,
+            trace: false,
+        }
+    }
+}
+
+/// JSON-RPC 2.0 notification from daemon to CLI.
+///
+/// Sent while a request that asked for `progress`, `stream` or `trace` is
+/// being handled. It has no `id`, which tells it apart from the response
+/// that follows.
+///
+/// # Example
+/// ```json
+/// {
+///   "jsonrpc": "2.0",
+///   "method": "progress",
+///   "params": {"title": "Indexing", "message": "42/100 files", "percentage": 42, "done": false}
+/// }
+/// ```
+///
+/// A `partial` notification carries part of a result, in the shape of the
+/// whole, and the id of the request it belongs to:
+/// ```json
+/// {
+///   "jsonrpc": "2.0",
+///   "method": "partial",
+///   "params": {"id": 7, "result": {"entries": [{"label": "Order", "locations": []}]}}
+/// }
+/// ```
+///
+/// A `trace` notification carries one message exchanged with the language
+/// server:
+/// ```json
+/// {
+///   "jsonrpc": "2.0",
+///   "method": "trace",
+///   "params": {"server": "ty", "direction": "sent", "body": "{\"jsonrpc\":\"2.0\",...}"}
+/// }
+/// ```
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DaemonNotification {
+    /// JSON-RPC version (always "2.0")
+    pub jsonrpc: String,
+
+    /// Notification name: `progress`, `partial` or `trace`.
+    pub method: String,
+
+    pub params: Value,
+}
+
+impl DaemonNotification {
+    /// Method name of progress notifications.
+    pub const PROGRESS: &'static str = "progress";
+
+    /// A notification forwarding one of the language server's progress updates.
+    pub fn progress(update: &WorkProgress) -> Self {
+        Self {
+            jsonrpc: "2.0".to_string(),
+            method: Self::PROGRESS.to_string(),
+            params: serde_json::to_value(update).unwrap_or_default(),
+        }
+    }
+
+    /// Method name of partial result notifications.
+    pub const PARTIAL: &'static str = "partial";
+
+    /// A notification carrying `part` of the result of request `id`.
+    pub fn partial(id: u64, part: Value) -> Self {
+        Self {
+            jsonrpc: "2.0".to_string(),
+            method: Self::PARTIAL.to_string(),
+            params: Value::Object(serde_json::Map::from_iter([
+                ("id".to_string(), Value::from(id)),
+                ("result".to_string(), part),
+            ])),
+        }
+    }
+
+    /// The request id and part of its result a partial result notification
+    /// carries; `None` for others.
+    pub fn into_partial(self) -> Option<(u64, Value)> {
+        if self.method != Self::PARTIAL {
+            return None;
+        }
+        let id = self.params.get("id")?.as_u64()?;
+        let mut params = self.params;
+        Some((id, params.get_mut("result")?.take()))
+    }
+
+    /// Method name of wire trace notifications.
+    pub const TRACE: &'static str = "trace";
+
+    /// A notification forwarding a message exchanged with the language server.
+    pub fn trace(message: &WireMessage) -> Self {
+        Self {
+            jsonrpc: "2.0".to_string(),
+            method: Self::TRACE.to_string(),
+            params: serde_json::to_value(message).unwrap_or_default(),
+        }
+    }
+
+    /// The message a trace notification carries; `None` for others.
+    pub fn as_trace(&self) -> Option<WireMessage> {
+        (self.method == Self::TRACE)
+            .then(|| serde_json::from_value(self.params.clone()).ok())
+            .flatten()
+    }
+
+    /// The update a progress notification carries; `None` for others.
+    pub fn as_progress(&self) -> Option<WorkProgress> {
+        (self.method == Self::PROGRESS)
+            .then(|| serde_json::from_value(self.params.clone()).ok())
+            .flatten()
     }
 }
 
@@ -122,6 +316,10 @@ pub struct DaemonResponse {
     /// Raw LSP request/response trace (only when request had `debug: true`)
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub debug_trace: Option<DebugTrace>,
+
+    /// Protocol version of the daemon (0 for daemons that predate versioning).
+    #[serde(default)]
+    pub protocol: u32,
 }
 
 /// Captured LSP exchange for debug logging.
@@ -150,15 +348,24 @@ impl DaemonResponse {
             result: Some(result),
             error: None,
             debug_trace: None,
+            protocol: PROTOCOL_VERSION,
         }
     }
 
     /// Create an error response.
     pub fn error(id: u64, error: DaemonError) -> Self {
-        Self { jsonrpc: "2.0".to_string(), id, result: None, error: Some(error), debug_trace: None }
+        Self {
+            jsonrpc: "2.0".to_string(),
+            id,
+            result: None,
+            error: Some(error),
+            debug_trace: None,
+            protocol: PROTOCOL_VERSION,
+        }
     }
 
     /// Attach a debug trace to the response.
+    #[must_use]
     pub fn with_debug_trace(mut self, trace: Option<DebugTrace>) -> Self {
         self.debug_trace = trace;
         self
@@ -265,6 +472,28 @@ impl DaemonError {
         let symbol = symbol.into();
         Self::with_data(-32004, "Symbol not found", serde_json::json!({"symbol": symbol}))
     }
+
+    /// ty not installed error (-32005)
+    pub fn ty_not_found(msg: impl Into<String>) -> Self {
+        Self::new(Self::TY_NOT_FOUND, msg)
+    }
+
+    /// Code of [`Self::ty_not_found`], checked by the client to offer `tyf setup`.
+    pub const TY_NOT_FOUND: i32 = -32005;
+
+    /// Request abandoned by a `cancel` (-32800, LSP's `RequestCancelled`)
+    pub fn request_cancelled() -> Self {
+        Self::new(-32800, "Request cancelled")
+    }
+
+    /// Protocol version mismatch error (-32006)
+    pub fn protocol_mismatch(client: u32) -> Self {
+        Self::with_data(
+            -32006,
+            protocol_mismatch_message(client, PROTOCOL_VERSION),
+            serde_json::json!({"client": client, "daemon": PROTOCOL_VERSION}),
+        )
+    }
 }
 
 /// Supported daemon methods.
@@ -276,15 +505,30 @@ pub enum Method {
     /// Get hover information (type, docs) at a position
     Hover,
 
+    /// Get hover information for multiple positions in one call (several in flight at once)
+    BatchHover,
+
     /// Go to definition at a position
     Definition,
 
+    /// Go to definition for multiple positions in one call (several in flight at once)
+    BatchDefinitions,
+
+    /// Ask where the symbols at positions are defined, declared, typed and implemented
+    Goto,
+
+    /// Hover and definitions for every identifier on a line, plus its diagnostics
+    ExplainLine,
+
     /// Search for symbols across the workspace
     WorkspaceSymbols,
 
     /// Get document outline (all symbols in a file)
     DocumentSymbols,
 
+    /// Get the outlines of many files in one call (several in flight at once)
+    BatchDocumentSymbols,
+
     /// Find all references to a symbol at a position
     References,
 
@@ -294,12 +538,54 @@ pub enum Method {
     /// Inspect a symbol: hover + references in one call (parallelized server-side)
     Inspect,
 
+    /// Inspect many positions in one call (several in flight at once)
+    InspectMany,
+
     /// Get class members (methods, properties, class variables) with type signatures
     Members,
 
+    /// Get the attributes of the expression at a position, via completion
+    ExpressionMembers,
+
     /// Get diagnostics (type errors, warnings) for a file
     Diagnostics,
 
+    /// Get diagnostics for many files in one call (several in flight at once)
+    BatchDiagnostics,
+
+    /// Get inlay hints (inferred variable types) for a whole file
+    InlayHints,
+
+    /// Find the occurrences of the symbol at a position within its file
+    DocumentHighlights,
+
+    /// Tell ty about files that changed on disk (used by `--watch`)
+    FilesChanged,
+
+    /// Report the ty server version and advertised LSP capabilities for a workspace
+    TyInfo,
+
+    /// Drop every cached hover, definition and document symbol response
+    ClearCache,
+
+    /// Re-read the config files, keeping the language servers it doesn't affect
+    ReloadConfig,
+
+    /// Start language servers and indexes for workspaces ahead of their first query
+    Warmup,
+
+    /// Request counts and latencies, language server restarts and memory use
+    Stats,
+
+    /// List the workspaces with a running language server
+    Workspaces,
+
+    /// Stop the language server of one workspace, or of all, leaving the daemon running
+    DropWorkspace,
+
+    /// Abandon the in-flight requests of a client process (sent on Ctrl-C)
+    Cancel,
+
     /// Health check - verify daemon is responsive
     Ping,
 
@@ -312,18 +598,57 @@ impl Method {
     pub fn as_str(self) -> &'static str {
         match self {
             Self::Hover => "hover",
+            Self::BatchHover => "batch_hover",
             Self::Definition => "definition",
+            Self::BatchDefinitions => "batch_definitions",
+            Self::Goto => "goto",
+            Self::ExplainLine => "explain_line",
             Self::WorkspaceSymbols => "workspace_symbols",
             Self::DocumentSymbols => "document_symbols",
+            Self::BatchDocumentSymbols => "batch_document_symbols",
             Self::References => "references",
             Self::BatchReferences => "batch_references",
             Self::Inspect => "inspect",
+            Self::InspectMany => "inspect_many",
             Self::Members => "members",
+            Self::ExpressionMembers => "expression_members",
             Self::Diagnostics => "diagnostics",
+            Self::BatchDiagnostics => "batch_diagnostics",
+            Self::InlayHints => "inlay_hints",
+            Self::DocumentHighlights => "document_highlights",
+            Self::FilesChanged => "files_changed",
+            Self::TyInfo => "ty_info",
+            Self::ClearCache => "clear_cache",
+            Self::ReloadConfig => "reload_config",
+            Self::Warmup => "warmup",
+            Self::Stats => "stats",
+            Self::Workspaces => "workspaces",
+            Self::DropWorkspace => "drop_workspace",
+            Self::Cancel => "cancel",
             Self::Ping => "ping",
             Self::Shutdown => "shutdown",
         }
     }
+
+    /// How many times the client's `--timeout` this method may take.
+    ///
+    /// Reference searches, batch checks and warmup scan the whole workspace,
+    /// which on a large repo takes far longer than a single hover.
+    pub const fn timeout_scale(self) -> u32 {
+        match self {
+            Self::References
+            | Self::BatchReferences
+            | Self::BatchHover
+            | Self::BatchDefinitions
+            | Self::ExplainLine
+            | Self::Inspect
+            | Self::InspectMany
+            | Self::BatchDiagnostics
+            | Self::BatchDocumentSymbols
+            | Self::Warmup => 4,
+            _ => 1,
+        }
+    }
 }
 
 // ============================================================================
@@ -346,6 +671,11 @@ pub struct HoverParams {
 
     /// Column number (0-based)
     pub column: u32,
+
+    /// Text to use for `file` instead of reading it from disk, e.g. an
+    /// unsaved editor buffer
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content: Option<String>,
 }
 
 /// Parameters for definition request.
@@ -364,6 +694,11 @@ pub struct DefinitionParams {
 
     /// Column number (0-based)
     pub column: u32,
+
+    /// Text to use for `file` instead of reading it from disk, e.g. an
+    /// unsaved editor buffer
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content: Option<String>,
 }
 
 /// Parameters for workspace symbols request.
@@ -377,9 +712,9 @@ pub struct WorkspaceSymbolsParams {
     /// Search query (can be fuzzy)
     pub query: String,
 
-    /// Maximum number of results to return (optional)
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub limit: Option<usize>,
+    /// Which of the matching symbols to return
+    #[serde(flatten)]
+    pub page: ResultPage,
 
     /// If set, only return symbols whose name exactly matches this string.
     /// The query is still sent to the LSP server for fuzzy matching, but
@@ -391,6 +726,19 @@ pub struct WorkspaceSymbolsParams {
     /// this string. Used for dotted notation like `Class.method`.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub container_name: Option<String>,
+
+    /// If non-empty, only return symbols of these kinds.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub kinds: Vec<SymbolKind>,
+
+    /// If set, only return symbols defined in files under this directory
+    /// (or in this file).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path_prefix: Option<PathBuf>,
+
+    /// `--include` / `--exclude` filter on the files symbols are defined in
+    #[serde(default, skip_serializing_if = "PathFilter::is_empty")]
+    pub paths: PathFilter,
 }
 
 /// Parameters for document symbols request.
@@ -424,9 +772,13 @@ pub struct ReferencesParams {
 
     /// Whether to include the declaration in results
     pub include_declaration: bool,
+
+    /// `--include` / `--exclude` filter on the files of the references
+    #[serde(default, skip_serializing_if = "PathFilter::is_empty")]
+    pub paths: PathFilter,
 }
 
-/// A single query in a batch references request.
+/// A single query in a batch references, hover or definitions request.
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct BatchReferencesQuery {
     /// Display label for output grouping (e.g. symbol name or `file:line:col`)
@@ -442,11 +794,15 @@ pub struct BatchReferencesQuery {
     pub column: u32,
 }
 
+/// Default for [`BatchReferencesParams::concurrency`].
+pub const DEFAULT_REFERENCES_CONCURRENCY: usize = 1;
+
 /// Parameters for batch references request.
 ///
-/// Sends multiple reference queries in one RPC call. The daemon processes
-/// them sequentially on the same LSP client, avoiding per-query connection
-/// overhead.
+/// Sends multiple reference queries in one RPC call, avoiding per-query
+/// connection overhead. The daemon keeps up to `concurrency` queries in
+/// flight on the same LSP client; older clients omit the field and get them
+/// one at a time.
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct BatchReferencesParams {
     /// Workspace root directory
@@ -457,6 +813,33 @@ pub struct BatchReferencesParams {
 
     /// Whether to include the declaration in results
     pub include_declaration: bool,
+
+    /// How many queries to resolve at once
+    #[serde(default = "default_references_concurrency")]
+    pub concurrency: usize,
+
+    /// `--include` / `--exclude` filter on the files of the references
+    #[serde(default, skip_serializing_if = "PathFilter::is_empty")]
+    pub paths: PathFilter,
+
+    /// Answer with the number of references per file instead of the
+    /// locations, merging queries that share a label
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub count_only: bool,
+
+    /// Leave out references outside the workspace (in dependencies)
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub workspace_only: bool,
+
+    /// Which references of each label to return. Unless it is every result,
+    /// queries sharing a label are merged and their references sorted by
+    /// file and position, so that successive pages line up.
+    #[serde(flatten)]
+    pub page: ResultPage,
+}
+
+const fn default_references_concurrency() -> usize {
+    DEFAULT_REFERENCES_CONCURRENCY
 }
 
 /// Parameters for inspect request.
@@ -482,6 +865,34 @@ pub struct InspectParams {
     pub include_references: bool,
 }
 
+/// Default for [`InspectManyParams::concurrency`].
+pub const DEFAULT_INSPECT_CONCURRENCY: usize = 8;
+
+/// Parameters for inspect many request.
+///
+/// Each query is inspected as by an inspect request; like batch hover, up to
+/// `concurrency` of them are in flight on the LSP client at once.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct InspectManyParams {
+    /// Workspace root directory
+    pub workspace: PathBuf,
+
+    /// Positions to inspect, labelled for matching up the answers
+    pub queries: Vec<BatchReferencesQuery>,
+
+    /// Whether to include references (can be slow on large codebases)
+    #[serde(default)]
+    pub include_references: bool,
+
+    /// How many positions to inspect at once
+    #[serde(default = "default_inspect_concurrency")]
+    pub concurrency: usize,
+}
+
+const fn default_inspect_concurrency() -> usize {
+    DEFAULT_INSPECT_CONCURRENCY
+}
+
 /// Parameters for members request.
 ///
 /// Returns the public interface of a class: methods, properties, and class
@@ -502,6 +913,29 @@ pub struct MembersParams {
     pub include_all: bool,
 }
 
+/// Parameters for expression members request.
+///
+/// Returns the attributes of whatever the expression at a position is
+/// inferred to be: an instance, a module, a union of types.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ExpressionMembersParams {
+    /// Workspace root directory
+    pub workspace: PathBuf,
+
+    /// File path (absolute or relative to workspace)
+    pub file: PathBuf,
+
+    /// Line number (0-based)
+    pub line: u32,
+
+    /// Column number (0-based)
+    pub column: u32,
+
+    /// Include private and dunder members (excluded by default)
+    #[serde(default)]
+    pub include_all: bool,
+}
+
 /// Parameters for diagnostics request.
 ///
 /// Returns type errors and warnings for a file.
@@ -514,175 +948,470 @@ pub struct DiagnosticsParams {
     pub file: PathBuf,
 }
 
-/// Parameters for ping request.
-///
-/// Health check with no parameters.
-#[derive(Serialize, Deserialize, Debug, Clone)]
-pub struct PingParams {}
+/// Default for [`BatchDiagnosticsParams::concurrency`].
+pub const DEFAULT_DIAGNOSTICS_CONCURRENCY: usize = 8;
 
-/// Parameters for shutdown request.
+/// Default for [`BatchDefinitionsParams::concurrency`].
+pub const DEFAULT_DEFINITIONS_CONCURRENCY: usize = 8;
+
+/// Parameters for batch definitions request.
 ///
-/// Graceful shutdown with no parameters.
+/// Mirrors batch references: the queries are resolved in one RPC call, up
+/// to `concurrency` at once on the same LSP client.
 #[derive(Serialize, Deserialize, Debug, Clone)]
-pub struct ShutdownParams {}
+pub struct BatchDefinitionsParams {
+    /// Workspace root directory
+    pub workspace: PathBuf,
 
-// ============================================================================
-// Response result types for each method
-// ============================================================================
+    /// Queries to resolve
+    pub queries: Vec<BatchReferencesQuery>,
 
-/// Result of a hover request.
-#[derive(Serialize, Deserialize, Debug, Clone)]
-pub struct HoverResult {
-    /// Hover information (if found)
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub hover: Option<Hover>,
+    /// How many queries to resolve at once
+    #[serde(default = "default_definitions_concurrency")]
+    pub concurrency: usize,
 }
 
-/// Result of a definition request.
-#[derive(Serialize, Deserialize, Debug, Clone)]
-pub struct DefinitionResult {
-    /// Definition location (if found)
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub location: Option<Location>,
+const fn default_definitions_concurrency() -> usize {
+    DEFAULT_DEFINITIONS_CONCURRENCY
 }
 
-/// Result of a workspace symbols request.
+/// Parameters for goto request.
+///
+/// Each query is asked every one of `kinds`, one after another.
 #[derive(Serialize, Deserialize, Debug, Clone)]
-pub struct WorkspaceSymbolsResult {
-    /// List of matching symbols
-    pub symbols: Vec<SymbolInformation>,
-}
+pub struct GotoParams {
+    /// Workspace root directory
+    pub workspace: PathBuf,
 
-/// Result of a document symbols request.
-#[derive(Serialize, Deserialize, Debug, Clone)]
-pub struct DocumentSymbolsResult {
-    /// Hierarchical symbol tree
-    pub symbols: Vec<DocumentSymbol>,
-}
+    /// Positions to ask about
+    pub queries: Vec<BatchReferencesQuery>,
 
-/// Result of a references request.
-#[derive(Serialize, Deserialize, Debug, Clone)]
-pub struct ReferencesResult {
-    /// List of reference locations
-    pub locations: Vec<Location>,
+    /// Which requests to make at each position
+    pub kinds: Vec<GotoKind>,
 }
 
-/// A single result entry in a batch references response.
+/// Parameters for explain line request.
+///
+/// Like batch hover, up to `concurrency` identifiers are looked up at once;
+/// the file's diagnostics are fetched alongside them.
 #[derive(Serialize, Deserialize, Debug, Clone)]
-pub struct BatchReferencesEntry {
-    /// Display label matching the query
-    pub label: String,
+pub struct ExplainLineParams {
+    /// Workspace root directory
+    pub workspace: PathBuf,
 
-    /// Reference locations found
-    pub locations: Vec<Location>,
-}
+    /// File containing the line
+    pub file: PathBuf,
 
-/// Result of a batch references request.
-#[derive(Serialize, Deserialize, Debug, Clone)]
-pub struct BatchReferencesResult {
-    /// Results for each query, in the same order as the request
-    pub entries: Vec<BatchReferencesEntry>,
-}
+    /// Line to explain (0-based)
+    pub line: u32,
 
-/// Result of an inspect request (hover + references combined).
-#[derive(Serialize, Deserialize, Debug, Clone)]
-pub struct InspectResult {
-    /// Hover information (if found)
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub hover: Option<Hover>,
+    /// The identifiers on the line, in source order
+    pub identifiers: Vec<LineIdentifier>,
 
-    /// Reference locations
-    pub references: Vec<Location>,
+    /// How many identifiers to look up at once
+    #[serde(default = "default_hover_concurrency")]
+    pub concurrency: usize,
 }
 
-/// Information about a single class member.
+/// An identifier on the line of an explain line request.
 #[derive(Serialize, Deserialize, Debug, Clone)]
-pub struct MemberInfo {
-    /// Member name (e.g. `calculate_total`, `name`, `MAX_RETRIES`)
+pub struct LineIdentifier {
     pub name: String,
 
-    /// LSP symbol kind (Method, Property, Variable, etc.)
-    pub kind: crate::lsp::protocol::SymbolKind,
-
-    /// Type signature from hover (e.g. "def add(self, a, b) -> int")
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub signature: Option<String>,
+    /// Column the identifier starts at (0-based)
+    pub column: u32,
+}
 
-    /// Line number (0-based)
-    pub line: u32,
+/// Default for [`BatchHoverParams::concurrency`].
+pub const DEFAULT_HOVER_CONCURRENCY: usize = 8;
+
+/// Parameters for batch hover request.
+///
+/// Like batch diagnostics, the daemon keeps up to `concurrency` hovers in
+/// flight on the LSP client at once.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BatchHoverParams {
+    /// Workspace root directory
+    pub workspace: PathBuf,
+
+    /// Positions to hover, labelled for matching up the answers
+    pub queries: Vec<BatchReferencesQuery>,
+
+    /// How many hovers to run at once
+    #[serde(default = "default_hover_concurrency")]
+    pub concurrency: usize,
+}
+
+const fn default_hover_concurrency() -> usize {
+    DEFAULT_HOVER_CONCURRENCY
+}
+
+/// Parameters for batch diagnostics request.
+///
+/// Unlike batch references, the daemon keeps up to `concurrency` files in
+/// flight on the LSP client at once: ty checks files in parallel, and
+/// responses are routed by request ID.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BatchDiagnosticsParams {
+    /// Workspace root directory
+    pub workspace: PathBuf,
+
+    /// File paths (absolute or relative to workspace)
+    pub files: Vec<PathBuf>,
+
+    /// How many files to check at once
+    #[serde(default = "default_diagnostics_concurrency")]
+    pub concurrency: usize,
+}
+
+const fn default_diagnostics_concurrency() -> usize {
+    DEFAULT_DIAGNOSTICS_CONCURRENCY
+}
+
+/// Default for [`BatchDocumentSymbolsParams::concurrency`].
+pub const DEFAULT_DOCUMENT_SYMBOLS_CONCURRENCY: usize = 8;
+
+/// Parameters for batch document symbols request.
+///
+/// Like batch diagnostics, up to `concurrency` files are outlined at once.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BatchDocumentSymbolsParams {
+    /// Workspace root directory
+    pub workspace: PathBuf,
+
+    /// File paths (absolute or relative to workspace)
+    pub files: Vec<PathBuf>,
+
+    /// How many files to outline at once
+    #[serde(default = "default_document_symbols_concurrency")]
+    pub concurrency: usize,
+}
+
+const fn default_document_symbols_concurrency() -> usize {
+    DEFAULT_DOCUMENT_SYMBOLS_CONCURRENCY
+}
+
+/// One file that changed on disk.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FileChange {
+    /// Absolute file path
+    pub file: PathBuf,
+
+    /// Created, changed or deleted
+    pub change: FileChangeType,
+}
+
+/// Parameters for files changed request.
+///
+/// Open documents among the files are sent their new contents with
+/// `textDocument/didChange` and `didSave`; deleted ones are closed.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FilesChangedParams {
+    /// Workspace root directory
+    pub workspace: PathBuf,
+
+    /// The files that changed
+    pub changes: Vec<FileChange>,
+}
+
+/// Parameters for inlay hints request.
+///
+/// Returns the hints for the whole file.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct InlayHintsParams {
+    /// Workspace root directory
+    pub workspace: PathBuf,
+
+    /// File path (absolute or relative to workspace)
+    pub file: PathBuf,
+}
+
+/// Parameters for document highlights request.
+///
+/// Returns the occurrences of the symbol at a position within the same file.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DocumentHighlightsParams {
+    /// Workspace root directory
+    pub workspace: PathBuf,
+
+    /// File path (absolute or relative to workspace)
+    pub file: PathBuf,
+
+    /// Line number (0-based)
+    pub line: u32,
 
     /// Column number (0-based)
     pub column: u32,
 }
 
-/// Result of a members request.
+/// Parameters for ty-info request.
+///
+/// Starts the workspace's ty server if needed and reports what it advertised.
 #[derive(Serialize, Deserialize, Debug, Clone)]
-pub struct MembersResult {
-    /// The class name
-    pub class_name: String,
+pub struct TyInfoParams {
+    /// Workspace root directory
+    pub workspace: PathBuf,
+}
 
-    /// File URI (file:///...)
-    pub file_uri: String,
+/// Parameters for ping request.
+///
+/// Health check with no parameters.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PingParams {}
 
-    /// Class definition line (0-based)
-    pub class_line: u32,
+/// Parameters for shutdown request.
+///
+/// Graceful shutdown with no parameters.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ShutdownParams {}
 
-    /// Class definition column (0-based)
-    pub class_column: u32,
+/// Parameters for clear-cache request.
+///
+/// Clears the cache of every workspace; takes no parameters.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ClearCacheParams {}
 
-    /// The kind of the resolved symbol (None if not found)
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub symbol_kind: Option<crate::lsp::protocol::SymbolKind>,
+/// Parameters for reload-config request.
+///
+/// Reloads the settings of every workspace; takes no parameters.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ReloadConfigParams {}
+
+/// Parameters for stats request.
+///
+/// Reports on the whole daemon; takes no parameters.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct StatsParams {}
+
+/// Parameters for workspaces request.
+///
+/// Lists every workspace; takes no parameters.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct WorkspacesParams {}
+
+/// Parameters for drop-workspace request.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DropWorkspaceParams {
+    /// Workspace whose language server to stop; `None` stops every one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub workspace: Option<PathBuf>,
+}
 
-    /// Class members grouped by kind
-    pub members: Vec<MemberInfo>,
+/// Parameters for cancel request.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CancelParams {
+    /// Process id whose in-flight requests to abandon
+    pub pid: u32,
 }
 
-/// A single diagnostic message.
+/// Parameters for warmup request.
 #[derive(Serialize, Deserialize, Debug, Clone)]
-pub struct Diagnostic {
-    /// Range where the diagnostic applies
-    pub range: Range,
+pub struct WarmupParams {
+    /// Workspace roots to start language servers for
+    pub workspaces: Vec<PathBuf>,
 
-    /// Severity level
-    pub severity: DiagnosticSeverity,
+    /// Answer once every workspace is warm instead of right away
+    #[serde(default)]
+    pub wait: bool,
+}
 
-    /// Diagnostic code (optional)
+// ============================================================================
+// Response result types for each method
+// ============================================================================
+
+/// Result of a hover request.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct HoverResult {
+    /// Hover information (if found)
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub code: Option<String>,
+    pub hover: Option<Hover>,
+}
 
-    /// Source of the diagnostic (e.g., "ty", "pyright")
+/// Result of a definition request.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DefinitionResult {
+    /// Definition location (if found)
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub source: Option<String>,
+    pub location: Option<Location>,
+}
 
-    /// Diagnostic message
-    pub message: String,
+/// Result of a workspace symbols request.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct WorkspaceSymbolsResult {
+    /// List of matching symbols
+    pub symbols: Vec<SymbolInformation>,
 
-    /// Related information (optional)
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub related_information: Option<Vec<DiagnosticRelatedInformation>>,
+    /// Matching symbols after the requested page, which were not sent
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub remaining: usize,
 }
 
-/// Severity level of a diagnostic.
-#[derive(Serialize_repr, Deserialize_repr, Debug, Clone, Copy, PartialEq, Eq)]
-#[repr(u8)]
-pub enum DiagnosticSeverity {
-    Error = 1,
-    Warning = 2,
-    Information = 3,
-    Hint = 4,
+/// Result of a document symbols request.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DocumentSymbolsResult {
+    /// Hierarchical symbol tree
+    pub symbols: Vec<DocumentSymbol>,
 }
 
-/// Related information for a diagnostic.
+/// Result of a references request.
 #[derive(Serialize, Deserialize, Debug, Clone)]
-pub struct DiagnosticRelatedInformation {
-    /// Location of related information
-    pub location: Location,
+pub struct ReferencesResult {
+    /// List of reference locations
+    pub locations: Vec<Location>,
+}
 
-    /// Message describing the relation
-    pub message: String,
+/// A single result entry in a batch references response.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BatchReferencesEntry {
+    /// Display label matching the query
+    pub label: String,
+
+    /// Reference locations found (none with `count_only`)
+    pub locations: Vec<Location>,
+
+    /// With `count_only`, the number of references in each file, most first
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub files: Vec<FileReferenceCount>,
+
+    /// References after the requested page, which were not sent
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub remaining: usize,
+}
+
+/// How many of a query's references are in one file.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct FileReferenceCount {
+    /// File URI
+    pub uri: String,
+
+    /// References in the file
+    pub count: usize,
+}
+
+/// Result of a batch references request.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BatchReferencesResult {
+    /// Results for each query, in the same order as the request
+    pub entries: Vec<BatchReferencesEntry>,
+}
+
+/// A single result entry in a batch definitions response.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BatchDefinitionsEntry {
+    /// Display label matching the query
+    pub label: String,
+
+    /// Every definition ty gives for the position
+    pub locations: Vec<Location>,
+}
+
+/// Result of a batch definitions request.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BatchDefinitionsResult {
+    /// Results for each query, in the same order as the request
+    pub entries: Vec<BatchDefinitionsEntry>,
+}
+
+/// The answer to one request of a goto call.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct GotoEntry {
+    /// Display label matching the query
+    pub label: String,
+
+    /// Which request this answers
+    pub kind: GotoKind,
+
+    /// The locations the server gave
+    pub locations: Vec<Location>,
+
+    /// The server doesn't advertise this request, so it wasn't asked
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub unsupported: bool,
+}
+
+/// Result of a goto request.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct GotoResult {
+    /// One entry per query and kind, in query order, kinds in request order
+    pub entries: Vec<GotoEntry>,
+}
+
+/// What ty says about one identifier of an explain line request.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ExplainedIdentifier {
+    pub name: String,
+
+    /// Column the identifier starts at (0-based)
+    pub column: u32,
+
+    /// Hover information (if any)
+    pub hover: Option<Hover>,
+
+    /// Where the identifier is defined
+    pub definitions: Vec<Location>,
+}
+
+/// Result of an explain line request.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ExplainLineResult {
+    /// One entry per identifier, in the same order as the request
+    pub identifiers: Vec<ExplainedIdentifier>,
+
+    /// The file's diagnostics whose range includes the line
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+/// A single result entry in a batch hover response.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BatchHoverEntry {
+    /// Display label matching the query
+    pub label: String,
+
+    /// Hover information (if any)
+    pub hover: Option<Hover>,
+}
+
+/// Result of a batch hover request.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BatchHoverResult {
+    /// Results for each query, in the same order as the request
+    pub entries: Vec<BatchHoverEntry>,
+}
+
+/// Result of an inspect request (hover + references combined).
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct InspectResult {
+    /// Hover information (if found)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hover: Option<Hover>,
+
+    /// Reference locations
+    pub references: Vec<Location>,
+}
+
+/// A single result entry in an inspect many response.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct InspectManyEntry {
+    /// Display label matching the query
+    pub label: String,
+
+    /// Hover information (if found)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hover: Option<Hover>,
+
+    /// Reference locations
+    pub references: Vec<Location>,
+}
+
+/// Result of an inspect many request.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct InspectManyResult {
+    /// Results for each query, in the same order as the request
+    pub entries: Vec<InspectManyEntry>,
 }
 
+pub use crate::lsp::members::{MemberInfo, MembersResult};
+
 /// Result of a diagnostics request.
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct DiagnosticsResult {
@@ -690,6 +1419,77 @@ pub struct DiagnosticsResult {
     pub diagnostics: Vec<Diagnostic>,
 }
 
+/// Diagnostics for one file of a batch diagnostics request.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BatchDiagnosticsEntry {
+    /// The file as given in the request
+    pub file: PathBuf,
+
+    /// Diagnostics for the file (empty when it couldn't be checked)
+    pub diagnostics: Vec<Diagnostic>,
+
+    /// Why the file couldn't be checked, e.g. it was deleted meanwhile
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Result of a batch diagnostics request.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BatchDiagnosticsResult {
+    /// Results for each file, in the same order as the request
+    pub entries: Vec<BatchDiagnosticsEntry>,
+}
+
+/// The outline of one file of a batch document symbols request.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BatchDocumentSymbolsEntry {
+    /// The file as given in the request
+    pub file: PathBuf,
+
+    /// Hierarchical symbol tree (empty when the file couldn't be outlined)
+    pub symbols: Vec<DocumentSymbol>,
+
+    /// Why the file couldn't be outlined, e.g. it was deleted meanwhile
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Result of a batch document symbols request.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BatchDocumentSymbolsResult {
+    /// Results for each file, in the same order as the request
+    pub entries: Vec<BatchDocumentSymbolsEntry>,
+}
+
+/// Result of a files changed request.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FilesChangedResult {
+    /// How many of the files were open in ty and got closed (deleted files,
+    /// or files that could no longer be read)
+    pub closed: usize,
+
+    /// How many of the files were open in ty and were sent their new contents
+    #[serde(default)]
+    pub updated: usize,
+}
+
+/// Result of an inlay hints request.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct InlayHintsResult {
+    /// Hints in the order ty returned them
+    pub hints: Vec<InlayHint>,
+}
+
+/// Result of a document highlights request.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DocumentHighlightsResult {
+    /// `file://` URI of the file the occurrences are in
+    pub uri: String,
+
+    /// Occurrences of the symbol, in the order the server sent them
+    pub highlights: Vec<DocumentHighlight>,
+}
+
 /// Result of a ping request.
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct PingResult {
@@ -727,6 +1527,175 @@ pub struct PingResult {
     /// Daemon process working directory
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub cwd: Option<String>,
+
+    /// ty server identity per loaded workspace
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub ty_servers: Vec<TyServerSummary>,
+
+    /// Daemon protocol version ([`PROTOCOL_VERSION`]); 0 for daemons that predate it
+    #[serde(default)]
+    pub protocol: u32,
+
+    /// Language server restarts since the daemon started, over all workspaces
+    #[serde(default)]
+    pub lsp_restarts: u64,
+}
+
+/// Result of a stats request.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct StatsResult {
+    /// Daemon uptime in seconds
+    pub uptime: u64,
+
+    /// Requests answered since the daemon started, across all methods
+    pub requests: u64,
+
+    /// Counters per method that has been called, busiest first
+    pub methods: Vec<MethodStats>,
+
+    /// Language servers started since the daemon started
+    pub lsp_starts: u64,
+
+    /// Starts of a server for a workspace that already had one before (after
+    /// it went idle or switched backends)
+    pub lsp_restarts: u64,
+
+    /// One entry per loaded workspace
+    pub workspaces: Vec<WorkspaceStats>,
+}
+
+/// Request counters for one daemon method.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct MethodStats {
+    /// Method name, e.g. `hover`
+    pub method: String,
+
+    /// Requests answered
+    pub calls: u64,
+
+    /// Requests answered with an error
+    pub errors: u64,
+
+    /// Time spent on all of them, in milliseconds
+    pub total_ms: u64,
+
+    /// Slowest request, in milliseconds
+    pub max_ms: u64,
+
+    /// Latency histogram; the last bucket has no upper bound
+    pub buckets: Vec<LatencyBucket>,
+}
+
+impl MethodStats {
+    /// Mean latency in milliseconds.
+    pub fn mean_ms(&self) -> f64 {
+        if self.calls == 0 {
+            return 0.0;
+        }
+        #[allow(clippy::cast_precision_loss)] // Fine for a displayed average
+        let mean = self.total_ms as f64 / self.calls as f64;
+        mean
+    }
+
+    /// The histogram bucket the `q` quantile (0.0–1.0) of latencies falls in.
+    pub fn percentile(&self, q: f64) -> Option<&LatencyBucket> {
+        #[allow(clippy::cast_precision_loss, clippy::cast_possible_truncation)]
+        #[allow(clippy::cast_sign_loss)]
+        let rank = ((self.calls as f64) * q).ceil().max(1.0) as u64;
+        let mut seen = 0;
+        self.buckets.iter().find(|bucket| {
+            seen += bucket.count;
+            seen >= rank
+        })
+    }
+}
+
+/// One bucket of a latency histogram.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct LatencyBucket {
+    /// Upper bound in milliseconds (inclusive); `None` for the slowest bucket
+    pub le_ms: Option<u64>,
+
+    /// Requests that took at most `le_ms` and more than the previous bound
+    pub count: u64,
+}
+
+/// What the daemon holds for one workspace.
+///
+/// Memory figures are estimates: the server's resident size where the OS
+/// reports it, plus the approximate size of the daemon's own index and cache.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct WorkspaceStats {
+    /// Workspace root directory
+    pub workspace: String,
+
+    /// Times its language server was started again after the first time
+    pub lsp_restarts: u64,
+
+    /// Process ID of its language server
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub server_pid: Option<u32>,
+
+    /// Resident memory of its language server in bytes (Linux only)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub server_rss_bytes: Option<u64>,
+
+    /// Files in the daemon's symbol index
+    pub index_files: usize,
+
+    /// Symbols in the daemon's symbol index
+    pub index_symbols: usize,
+
+    /// Approximate size of the symbol index in bytes
+    pub index_bytes: u64,
+
+    /// Cached responses for this workspace
+    pub cache_entries: usize,
+
+    /// Approximate size of those cached responses in bytes
+    pub cache_bytes: u64,
+
+    /// Sum of the known sizes above
+    pub estimated_bytes: u64,
+}
+
+/// ty server identity for one loaded workspace (reported by ping).
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TyServerSummary {
+    /// Workspace root directory
+    pub workspace: String,
+
+    /// Server label from `serverInfo`, e.g. `ty 0.0.1-alpha.20`
+    pub server: String,
+
+    /// Times the workspace's server was restarted after exiting
+    #[serde(default)]
+    pub restarts: u64,
+
+    /// Whether the server has exited; the next request starts a new one
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub exited: bool,
+}
+
+/// Result of a ty-info request.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TyInfoResult {
+    /// Workspace root directory
+    pub workspace: String,
+
+    /// Server name from `serverInfo` (absent if the server didn't send one)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+
+    /// Server version from `serverInfo`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+
+    /// Advertised `*Provider` capabilities, sorted
+    pub providers: Vec<String>,
+
+    /// Raw `capabilities` object from the initialize response
+    pub capabilities: Value,
 }
 
 /// Result of a shutdown request.
@@ -736,6 +1705,111 @@ pub struct ShutdownResult {
     pub message: String,
 }
 
+/// Result of a cancel request.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CancelResult {
+    /// Number of requests that were abandoned
+    pub cancelled: usize,
+}
+
+/// Result of a workspaces request.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct WorkspacesResult {
+    /// Workspaces with a language server, sorted by path
+    pub workspaces: Vec<WorkspaceEntry>,
+}
+
+/// One workspace with a running (or crashed) language server.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct WorkspaceEntry {
+    /// Workspace root directory
+    pub workspace: String,
+
+    /// Backend spec its server was started from, e.g. `ty`
+    pub backend: String,
+
+    /// Server label from `serverInfo`, e.g. `ty 0.0.1-alpha.20`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub server: Option<String>,
+
+    /// Process ID of its language server
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub server_pid: Option<u32>,
+
+    /// Seconds since a request last used it
+    pub idle_secs: u64,
+
+    /// Times its language server was restarted after exiting
+    #[serde(default)]
+    pub restarts: u64,
+
+    /// Whether the server has exited; the next request starts a new one
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub exited: bool,
+}
+
+/// Result of a drop-workspace request.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DropWorkspaceResult {
+    /// Workspaces whose language server was stopped
+    pub dropped: Vec<String>,
+}
+
+/// Result of a clear-cache request.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ClearCacheResult {
+    /// Number of cached responses dropped
+    pub cleared: usize,
+}
+
+/// Result of a reload-config request: the settings now in effect and what
+/// changed with them.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ReloadConfigResult {
+    /// Seconds the daemon now stays up with no workspace in use
+    pub idle_timeout: u64,
+    /// Responses the cache now holds at most
+    pub cache_size: usize,
+    /// Cached responses dropped to fit a smaller `cache_size`
+    pub evicted: usize,
+    /// Workspaces whose language server was stopped because their backend
+    /// changed; the next request starts the new one
+    pub restarted: Vec<String>,
+    /// Workspaces whose symbol index is rebuilt because their excludes changed
+    pub reindexed: Vec<String>,
+}
+
+/// Result of a warmup request.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct WarmupResult {
+    /// One entry per requested workspace, in request order
+    pub workspaces: Vec<WarmupEntry>,
+}
+
+/// How warming one workspace went.
+///
+/// Without `wait` only `workspace` and `running` are known when the daemon answers.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct WarmupEntry {
+    /// Workspace root path
+    pub workspace: String,
+
+    /// Whether its language server was already running
+    pub running: bool,
+
+    /// Name and version of its language server, once warm
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub server: Option<String>,
+
+    /// How long warming took, in milliseconds
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub elapsed_ms: Option<u64>,
+
+    /// Why the workspace couldn't be warmed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -786,6 +1860,45 @@ mod tests {
         assert!(!json.contains("\"result\""));
     }
 
+    #[test]
+    fn test_progress_notification_roundtrip() {
+        let update = WorkProgress {
+            title: "Indexing".to_string(),
+            message: Some("12/40 files".to_string()),
+            percentage: Some(30),
+            done: false,
+        };
+        let json = serde_json::to_value(DaemonNotification::progress(&update)).unwrap();
+        assert!(json.get("id").is_none());
+
+        let notification: DaemonNotification = serde_json::from_value(json).unwrap();
+        assert_eq!(notification.as_progress(), Some(update));
+        assert!(notification.into_partial().is_none());
+    }
+
+    #[test]
+    fn test_trace_notification_roundtrip() {
+        let message = WireMessage {
+            server: "ty".to_string(),
+            direction: crate::lsp::trace::Direction::Received,
+            body: r#"{"jsonrpc":"2.0","id":3,"result":null}"#.to_string(),
+        };
+        let json = serde_json::to_string(&DaemonNotification::trace(&message)).unwrap();
+        let notification: DaemonNotification = serde_json::from_str(&json).unwrap();
+        assert_eq!(notification.as_trace(), Some(message));
+        assert!(notification.as_progress().is_none());
+    }
+
+    #[test]
+    fn test_partial_notification_roundtrip() {
+        let part = json!({"symbols": [], "remaining": 0});
+        let json = serde_json::to_string(&DaemonNotification::partial(7, part.clone())).unwrap();
+
+        let notification: DaemonNotification = serde_json::from_str(&json).unwrap();
+        assert!(notification.as_progress().is_none());
+        assert_eq!(notification.into_partial(), Some((7, part)));
+    }
+
     #[test]
     fn test_method_serialization() {
         assert_eq!(serde_json::to_string(&Method::Hover).unwrap(), "\"hover\"");
@@ -803,11 +1916,19 @@ mod tests {
             file: PathBuf::from("file.py"),
             line: 10,
             column: 5,
+            content: None,
         };
 
         let json = serde_json::to_value(&params).unwrap();
         assert_eq!(json["line"], 10);
         assert_eq!(json["column"], 5);
+        assert!(json.get("content").is_none(), "no buffer means the file on disk");
+
+        let buffer = HoverParams { content: Some("x = 1\n".to_string()), ..params };
+        let json = serde_json::to_value(&buffer).unwrap();
+        assert_eq!(json["content"], "x = 1\n");
+        let back: HoverParams = serde_json::from_value(json).unwrap();
+        assert_eq!(back.content.as_deref(), Some("x = 1\n"));
     }
 
     #[test]
@@ -817,6 +1938,8 @@ mod tests {
         assert_eq!(DaemonError::method_not_found("test").code, -32601);
         assert_eq!(DaemonError::file_not_found("test").code, -32000);
         assert_eq!(DaemonError::workspace_not_found("test").code, -32001);
+        assert_eq!(DaemonError::ty_not_found("test").code, -32005);
+        assert_eq!(DaemonError::protocol_mismatch(0).code, -32006);
     }
 
     #[test]
@@ -840,6 +1963,14 @@ mod tests {
             workspace_paths: vec!["/path/to/ws1".to_string(), "/path/to/ws2".to_string()],
             pid: 12345,
             cwd: Some("/home/user".to_string()),
+            ty_servers: vec![TyServerSummary {
+                workspace: "/path/to/ws1".to_string(),
+                server: "ty 0.0.1-alpha.20".to_string(),
+                restarts: 1,
+                exited: false,
+            }],
+            protocol: PROTOCOL_VERSION,
+            lsp_restarts: 1,
         };
 
         let json = serde_json::to_value(&result).unwrap();
@@ -848,6 +1979,47 @@ mod tests {
         // Roundtrip
         let parsed: PingResult = serde_json::from_value(json).unwrap();
         assert_eq!(parsed.version, "0.1.11");
+        assert_eq!(parsed.ty_servers[0].server, "ty 0.0.1-alpha.20");
+        assert_eq!(parsed.ty_servers[0].restarts, 1);
+        assert_eq!(parsed.lsp_restarts, 1);
+    }
+
+    #[test]
+    fn test_ping_result_without_ty_servers_deserializes() {
+        let parsed: PingResult = serde_json::from_value(json!({
+            "status": "running",
+            "version": "0.4.0",
+            "uptime": 1,
+            "active_workspaces": 0,
+            "cache_size": 0
+        }))
+        .unwrap();
+        assert!(parsed.ty_servers.is_empty());
+        assert_eq!(parsed.protocol, 0, "daemons without versioning report protocol 0");
+        assert_eq!(parsed.lsp_restarts, 0);
+    }
+
+    #[test]
+    fn test_unversioned_request_deserializes_as_protocol_zero() {
+        let request: DaemonRequest = serde_json::from_value(json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "definition",
+            "params": {}
+        }))
+        .unwrap();
+        assert_eq!(request.protocol, 0);
+        assert_eq!(DaemonRequest::new(Method::Ping, json!({})).protocol, PROTOCOL_VERSION);
+    }
+
+    #[test]
+    fn test_protocol_compatibility_rules() {
+        assert!(protocol_compatible(Method::Definition, PROTOCOL_VERSION));
+        assert!(!protocol_compatible(Method::Definition, 0));
+        assert!(!protocol_compatible(Method::References, PROTOCOL_VERSION + 1));
+        // ping/shutdown always work so stale daemons can be detected and replaced
+        assert!(protocol_compatible(Method::Ping, 0));
+        assert!(protocol_compatible(Method::Shutdown, PROTOCOL_VERSION + 1));
     }
 
     #[test]
@@ -871,28 +2043,27 @@ mod tests {
 
     #[test]
     fn test_members_result_roundtrip() {
-        use crate::lsp::protocol::SymbolKind;
-
         let result = MembersResult {
             class_name: "Animal".to_string(),
             file_uri: "file:///src/models.py".to_string(),
             class_line: 5,
             class_column: 0,
             symbol_kind: Some(SymbolKind::Class),
+            expression: false,
             members: vec![
                 MemberInfo {
                     name: "speak".to_string(),
                     kind: SymbolKind::Method,
                     signature: Some("speak(self) -> str".to_string()),
-                    line: 10,
-                    column: 4,
+                    line: Some(10),
+                    column: Some(4),
                 },
                 MemberInfo {
                     name: "name".to_string(),
                     kind: SymbolKind::Property,
                     signature: Some("name: str".to_string()),
-                    line: 7,
-                    column: 4,
+                    line: Some(7),
+                    column: Some(4),
                 },
             ],
         };
@@ -939,11 +2110,84 @@ mod tests {
                 },
             ],
             include_declaration: true,
+            concurrency: 4,
+            paths: PathFilter::new(Vec::new(), vec!["tests/".to_string()], false),
+            count_only: true,
+            workspace_only: true,
+            page: ResultPage::new(20, Some(10)),
         };
         let json = serde_json::to_string(&params).unwrap();
+        assert!(json.contains(r#""offset":20,"limit":10"#), "page is flattened: {json}");
         let parsed: BatchReferencesParams = serde_json::from_str(&json).unwrap();
         assert_eq!(parsed.queries.len(), 2);
         assert!(parsed.include_declaration);
+        assert_eq!(parsed.concurrency, 4);
+        assert_eq!(parsed.paths, params.paths);
+        assert!(parsed.count_only);
+        assert!(parsed.workspace_only);
+        assert_eq!(parsed.page, params.page);
+    }
+
+    #[test]
+    fn test_batch_references_params_default_concurrency() {
+        let json = r#"{"workspace":"/ws","queries":[],"include_declaration":false}"#;
+        let parsed: BatchReferencesParams = serde_json::from_str(json).unwrap();
+        assert_eq!(parsed.concurrency, DEFAULT_REFERENCES_CONCURRENCY);
+        assert!(parsed.paths.is_empty(), "older clients send no path filter");
+        assert!(!parsed.count_only);
+    }
+
+    #[test]
+    fn test_batch_diagnostics_params_default_concurrency() {
+        let parsed: BatchDiagnosticsParams =
+            serde_json::from_value(json!({"workspace": "/workspace", "files": ["a.py"]})).unwrap();
+        assert_eq!(parsed.files, [PathBuf::from("a.py")]);
+        assert_eq!(parsed.concurrency, DEFAULT_DIAGNOSTICS_CONCURRENCY);
+    }
+
+    #[test]
+    fn test_batch_document_symbols_roundtrip() {
+        let parsed: BatchDocumentSymbolsParams =
+            serde_json::from_value(json!({"workspace": "/workspace", "files": ["a.py"]})).unwrap();
+        assert_eq!(parsed.files, [PathBuf::from("a.py")]);
+        assert_eq!(parsed.concurrency, DEFAULT_DOCUMENT_SYMBOLS_CONCURRENCY);
+
+        let result: BatchDocumentSymbolsResult = serde_json::from_value(json!({
+            "entries": [{"file": "gone.py", "symbols": [], "error": "File not found"}],
+        }))
+        .unwrap();
+        assert_eq!(result.entries[0].error.as_deref(), Some("File not found"));
+        let entry = BatchDocumentSymbolsEntry { file: "a.py".into(), symbols: vec![], error: None };
+        assert_eq!(serde_json::to_value(entry).unwrap(), json!({"file": "a.py", "symbols": []}));
+    }
+
+    #[test]
+    fn test_batch_definitions_roundtrip() {
+        let parsed: BatchDefinitionsParams = serde_json::from_value(json!({
+            "workspace": "/workspace",
+            "queries": [{"label": "save", "file": "app.py", "line": 4, "column": 8}],
+        }))
+        .unwrap();
+        assert_eq!(parsed.queries[0].line, 4);
+        assert_eq!(parsed.concurrency, DEFAULT_DEFINITIONS_CONCURRENCY);
+
+        let result: BatchDefinitionsResult = serde_json::from_value(json!({
+            "entries": [{"label": "save", "locations": []}],
+        }))
+        .unwrap();
+        assert_eq!(result.entries[0].label, "save");
+        assert!(result.entries[0].locations.is_empty());
+    }
+
+    #[test]
+    fn test_batch_hover_params_default_concurrency() {
+        let parsed: BatchHoverParams = serde_json::from_value(json!({
+            "workspace": "/workspace",
+            "queries": [{"label": "User", "file": "app.py", "line": 2, "column": 6}],
+        }))
+        .unwrap();
+        assert_eq!(parsed.queries[0].label, "User");
+        assert_eq!(parsed.concurrency, DEFAULT_HOVER_CONCURRENCY);
     }
 
     #[test]
@@ -960,9 +2204,12 @@ mod tests {
                         end: Position { line: 0, character: 3 },
                     },
                 }],
+                files: Vec::new(),
+                remaining: 0,
             }],
         };
         let json = serde_json::to_string(&result).unwrap();
+        assert!(!json.contains("files"), "listings carry no counts: {json}");
         let parsed: BatchReferencesResult = serde_json::from_str(&json).unwrap();
         assert_eq!(parsed.entries.len(), 1);
         assert_eq!(parsed.entries[0].locations.len(), 1);
@@ -1034,15 +2281,61 @@ mod tests {
         assert_eq!(Method::Definition.as_str(), "definition");
         assert_eq!(Method::WorkspaceSymbols.as_str(), "workspace_symbols");
         assert_eq!(Method::DocumentSymbols.as_str(), "document_symbols");
+        assert_eq!(Method::BatchDocumentSymbols.as_str(), "batch_document_symbols");
         assert_eq!(Method::References.as_str(), "references");
         assert_eq!(Method::BatchReferences.as_str(), "batch_references");
+        assert_eq!(Method::BatchHover.as_str(), "batch_hover");
+        assert_eq!(Method::BatchDefinitions.as_str(), "batch_definitions");
         assert_eq!(Method::Inspect.as_str(), "inspect");
+        assert_eq!(Method::InspectMany.as_str(), "inspect_many");
         assert_eq!(Method::Members.as_str(), "members");
+        assert_eq!(Method::ExpressionMembers.as_str(), "expression_members");
         assert_eq!(Method::Diagnostics.as_str(), "diagnostics");
+        assert_eq!(Method::BatchDiagnostics.as_str(), "batch_diagnostics");
+        assert_eq!(Method::FilesChanged.as_str(), "files_changed");
+        assert_eq!(Method::ClearCache.as_str(), "clear_cache");
+        assert_eq!(Method::ReloadConfig.as_str(), "reload_config");
+        assert_eq!(Method::Warmup.as_str(), "warmup");
+        assert_eq!(Method::Stats.as_str(), "stats");
+        assert_eq!(Method::Workspaces.as_str(), "workspaces");
+        assert_eq!(Method::DropWorkspace.as_str(), "drop_workspace");
+        assert_eq!(Method::DocumentHighlights.as_str(), "document_highlights");
+        assert_eq!(Method::Cancel.as_str(), "cancel");
         assert_eq!(Method::Ping.as_str(), "ping");
         assert_eq!(Method::Shutdown.as_str(), "shutdown");
     }
 
+    #[test]
+    fn test_drop_workspace_params_default_to_all() {
+        let all: DropWorkspaceParams = serde_json::from_value(json!({})).unwrap();
+        assert!(all.workspace.is_none());
+        let one =
+            serde_json::to_value(DropWorkspaceParams { workspace: Some(PathBuf::from("/proj")) })
+                .unwrap();
+        assert_eq!(one, json!({"workspace": "/proj"}));
+    }
+
+    #[test]
+    fn test_requests_carry_the_client_pid() {
+        let request = DaemonRequest::new(Method::Hover, json!({}));
+        assert_eq!(request.pid, Some(std::process::id()));
+
+        // Requests from clients that predate cancellation have no pid.
+        let old: DaemonRequest = serde_json::from_value(
+            json!({"jsonrpc": "2.0", "id": 1, "method": "ping", "params": {}}),
+        )
+        .unwrap();
+        assert_eq!(old.pid, None);
+    }
+
+    #[test]
+    fn test_workspace_wide_methods_get_longer_timeouts() {
+        assert_eq!(Method::Hover.timeout_scale(), 1);
+        assert_eq!(Method::Ping.timeout_scale(), 1);
+        assert!(Method::References.timeout_scale() > 1);
+        assert!(Method::BatchDiagnostics.timeout_scale() > 1);
+    }
+
     #[test]
     fn test_all_method_variants_deserialize() {
         let variants = [
@@ -1050,11 +2343,22 @@ mod tests {
             "definition",
             "workspace_symbols",
             "document_symbols",
+            "batch_document_symbols",
             "references",
             "batch_references",
             "inspect",
             "members",
+            "expression_members",
             "diagnostics",
+            "batch_diagnostics",
+            "files_changed",
+            "ty_info",
+            "clear_cache",
+            "reload_config",
+            "warmup",
+            "workspaces",
+            "drop_workspace",
+            "cancel",
             "ping",
             "shutdown",
         ];
@@ -12,29 +12,64 @@
 
 use anyhow::{Context, Result};
 use serde_json::Value;
+use std::collections::{HashMap, HashSet};
 use std::net::SocketAddr;
 use std::path::PathBuf;
-use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
 use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader};
 use tokio::net::{TcpListener, UnixListener};
-use tokio::sync::broadcast;
+use tokio::sync::{broadcast, mpsc, Notify};
 
+use crate::config::Config;
+use crate::daemon::cache::{CachedQuery, ResponseCache};
+use crate::daemon::client::OWNER_ENV;
+use crate::daemon::index::SymbolIndexes;
 use crate::daemon::pidfile::{self, PidfileData};
 use crate::daemon::pool::LspClientPool;
 use crate::daemon::protocol::{
-    BatchReferencesEntry, BatchReferencesParams, BatchReferencesResult, DaemonError, DaemonRequest,
-    DaemonResponse, DefinitionParams, DefinitionResult, DiagnosticsResult, DocumentSymbolsParams,
-    DocumentSymbolsResult, HoverParams, HoverResult, InspectParams, InspectResult, MemberInfo,
-    MembersParams, MembersResult, Method, PingResult, ReferencesParams, ReferencesResult,
-    ShutdownResult, WorkspaceSymbolsParams, WorkspaceSymbolsResult,
+    protocol_compatible, BatchDefinitionsEntry, BatchDefinitionsParams, BatchDefinitionsResult,
+    BatchDiagnosticsEntry, BatchDiagnosticsParams, BatchDiagnosticsResult,
+    BatchDocumentSymbolsEntry, BatchDocumentSymbolsParams, BatchDocumentSymbolsResult,
+    BatchHoverEntry, BatchHoverParams, BatchHoverResult, BatchReferencesEntry,
+    BatchReferencesParams, BatchReferencesQuery, BatchReferencesResult, CancelParams, CancelResult,
+    ClearCacheResult, DaemonError, DaemonNotification, DaemonRequest, DaemonResponse,
+    DefinitionParams, DefinitionResult, DiagnosticsParams, DiagnosticsResult, DocumentHighlight,
+    DocumentHighlightsParams, DocumentHighlightsResult, DocumentSymbolsParams,
+    DocumentSymbolsResult, DropWorkspaceParams, DropWorkspaceResult, ExplainLineParams,
+    ExplainLineResult, ExplainedIdentifier, ExpressionMembersParams, FileReferenceCount,
+    FilesChangedParams, FilesChangedResult, GotoEntry, GotoParams, GotoResult, HoverParams,
+    HoverResult, InlayHintsParams, InlayHintsResult, InspectManyEntry, InspectManyParams,
+    InspectManyResult, InspectParams, InspectResult, MembersParams, Method, PingResult,
+    ReferencesParams, ReferencesResult, ReloadConfigResult, ShutdownResult, StatsResult,
+    TyInfoParams, TyInfoResult, TyServerSummary, WarmupEntry, WarmupParams, WarmupResult,
+    WorkProgress, WorkspaceEntry, WorkspaceStats, WorkspaceSymbolsParams, WorkspaceSymbolsResult,
+    WorkspacesResult, PROTOCOL_VERSION,
 };
+use crate::daemon::stats::{process_rss, Metrics};
 use crate::lsp::client::TyLspClient;
-use crate::lsp::protocol::{DocumentSymbol, Hover, Location, SymbolKind};
+use crate::lsp::members;
+use crate::lsp::protocol::{DocumentSymbol, FileChangeType, Hover, InitializeResult, Location};
+use crate::lsp::server::{is_server_exited, TyNotFound};
+use crate::lsp::trace::WireMessage;
+use crate::lsp::uri::uri_to_path;
+use crate::lsp::warmup::{
+    hover_with_warmup, with_warmup, workspace_symbols_with_warmup, WARMUP_DELAYS,
+};
+use crate::workspace::origin::retain_workspace_locations;
+use crate::workspace::page::ResultPage;
+
+/// Most workspace symbols sent in one part of a streamed answer.
+const SYMBOLS_PER_PART: usize = 100;
 
-/// Default warmup delays (ms) for LSP operations that may return empty on cold start.
-/// Total: 100 + 200 + 400 + 800 = 1500ms.
-const WARMUP_DELAYS: [u64; 4] = [100, 200, 400, 800];
+/// How often a daemon started with `--kill-after` checks that the process it
+/// belongs to is still running.
+const OWNER_CHECK_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Bytes an in-process connection buffers in each direction before the
+/// writer waits for the reader.
+const IN_PROCESS_BUFFER: usize = 64 * 1024;
 
 /// The daemon server that handles client connections and LSP requests.
 pub struct DaemonServer {
@@ -59,14 +94,34 @@ pub struct DaemonServer {
 
     /// Time when the daemon started
     start_time: Instant,
+
+    /// The user config's `idle-timeout`, as last read.
+    idle_timeout: Mutex<Duration>,
+
+    /// Symbol indexes built and maintained by the daemon (one per workspace).
+    symbol_indexes: SymbolIndexes,
+
+    /// Hover, definition and document symbol responses, valid until the file changes.
+    response_cache: ResponseCache,
+
+    /// Request counts and latencies by method, for `tyf daemon stats`.
+    metrics: Metrics,
+
+    /// Requests being handled, by client process id and request id; a
+    /// `cancel` from that process notifies them to stop.
+    in_flight: Mutex<HashMap<(u32, u64), Arc<Notify>>>,
 }
 
 impl DaemonServer {
     /// Create a new daemon server with the specified socket path.
     pub fn new(socket_path: PathBuf) -> Self {
         let (shutdown_tx, _) = broadcast::channel(1);
-        let pidfile_path =
-            pidfile::get_pidfile_path().unwrap_or_else(|_| socket_path.with_extension("pid"));
+        // Next to the socket, where clients of this socket look for it.
+        let pidfile_path = socket_path.with_extension("pid");
+        let user_config = Config::user().unwrap_or_else(|e| {
+            tracing::warn!("Ignoring user config: {e:#}");
+            Config::default()
+        });
 
         Self {
             socket_path,
@@ -75,9 +130,20 @@ impl DaemonServer {
             lsp_pool: Arc::new(LspClientPool::new()),
             shutdown_tx,
             start_time: Instant::now(),
+            idle_timeout: Mutex::new(user_config.idle_timeout().0),
+            symbol_indexes: SymbolIndexes::new(),
+            response_cache: ResponseCache::with_capacity(user_config.cache_size().0),
+            metrics: Metrics::new(),
+            in_flight: Mutex::new(HashMap::new()),
         }
     }
 
+    /// Create a server for [`serve_stdio`](Self::serve_stdio), which has no
+    /// socket.
+    pub fn stdio() -> Self {
+        Self::new(PathBuf::new())
+    }
+
     /// Get the socket path for the current user.
     ///
     /// Delegates to the canonical implementation in [`super::client::get_socket_path`].
@@ -92,24 +158,46 @@ impl DaemonServer {
         self.write_pidfile()?;
 
         let server = Arc::new(self);
-        let local = tokio::task::LocalSet::new();
+        let mut shutdown_rx = server.shutdown_tx.subscribe();
 
-        Self::spawn_accept_loops(&server, &local, unix_listener, tcp_listener);
+        Self::spawn_accept_loops(&server, unix_listener, tcp_listener);
 
-        // Wait for shutdown signal (this drives all spawned tasks)
-        let server_clone = Arc::clone(&server);
-        local
-            .run_until(async move {
-                let mut shutdown_rx = server_clone.shutdown_tx.subscribe();
-                let _ = shutdown_rx.recv().await;
-                tracing::info!("Shutdown signal received");
-            })
-            .await;
+        let _ = shutdown_rx.recv().await;
+        tracing::info!("Shutdown signal received");
 
         server.cleanup().await?;
         Ok(())
     }
 
+    /// Serve the daemon protocol to one client over this process's stdin
+    /// and stdout (`tyf serve --stdio`), until stdin closes or the client
+    /// sends `shutdown`.
+    ///
+    /// Nothing is bound and no pidfile is written, so this runs where
+    /// sockets can't be opened and next to a background daemon. The ty
+    /// servers it starts stop with the process.
+    pub async fn serve_stdio(self) -> Result<()> {
+        let stdio = tokio::io::join(tokio::io::stdin(), tokio::io::stdout());
+        Arc::new(self).handle_connection(stdio).await
+    }
+
+    /// Open a connection to this server from inside the process
+    /// (`--no-daemon`), served like one over the socket.
+    ///
+    /// As with [`serve_stdio`](Self::serve_stdio), nothing is bound and no
+    /// pidfile is written; the ty servers it starts stop once the server
+    /// and its connections are dropped.
+    pub fn connect_in_process(self: &Arc<Self>) -> tokio::io::DuplexStream {
+        let (client, stream) = tokio::io::duplex(IN_PROCESS_BUFFER);
+        let conn = Arc::clone(self);
+        tokio::spawn(async move {
+            if let Err(err) = conn.handle_connection(stream).await {
+                tracing::error!("In-process connection error: {err}");
+            }
+        });
+        client
+    }
+
     /// Bind both Unix socket and TCP listeners.
     async fn bind_listeners(&mut self) -> Result<(UnixListener, TcpListener)> {
         // Remove existing socket file if it exists
@@ -153,23 +241,30 @@ impl DaemonServer {
         Ok(())
     }
 
-    /// Spawn idle timeout and both accept loops on the `LocalSet`.
+    /// Spawn idle timeout and both accept loops.
+    ///
+    /// Every connection gets a task of its own on the multi-threaded
+    /// runtime, so requests from several CLI invocations are handled in
+    /// parallel rather than taking turns on one thread.
     fn spawn_accept_loops(
         server: &Arc<Self>,
-        local: &tokio::task::LocalSet,
         unix_listener: UnixListener,
         tcp_listener: TcpListener,
     ) {
-        // NOTE: Using LocalSet because LspClientPool uses std::sync::Mutex
-        // internally and spawn_local avoids Send requirements.
-
         // Idle timeout
         let s = Arc::clone(server);
-        local.spawn_local(async move { s.idle_timeout_task().await });
+        tokio::spawn(async move { s.idle_timeout_task().await });
+
+        // The process whose exit stops the daemon, with --kill-after
+        if let Some(owner) = std::env::var(OWNER_ENV).ok().and_then(|pid| pid.parse().ok()) {
+            tracing::info!("Stopping once process {owner} exits");
+            let s = Arc::clone(server);
+            tokio::spawn(async move { s.owner_task(owner).await });
+        }
 
         // Unix socket accept loop
         let s = Arc::clone(server);
-        local.spawn_local(async move {
+        tokio::spawn(async move {
             let mut shutdown_rx = s.shutdown_tx.subscribe();
             loop {
                 tokio::select! {
@@ -177,7 +272,7 @@ impl DaemonServer {
                         match result {
                             Ok((stream, _addr)) => {
                                 let conn = Arc::clone(&s);
-                                tokio::task::spawn_local(async move {
+                                tokio::spawn(async move {
                                     if let Err(err) = conn.handle_connection(stream).await {
                                         tracing::error!("Unix connection error: {err}");
                                     }
@@ -196,7 +291,7 @@ impl DaemonServer {
 
         // TCP accept loop
         let s = Arc::clone(server);
-        local.spawn_local(async move {
+        tokio::spawn(async move {
             let mut shutdown_rx = s.shutdown_tx.subscribe();
             loop {
                 tokio::select! {
@@ -205,7 +300,7 @@ impl DaemonServer {
                             Ok((stream, addr)) => {
                                 tracing::debug!("TCP connection from {addr}");
                                 let conn = Arc::clone(&s);
-                                tokio::task::spawn_local(async move {
+                                tokio::spawn(async move {
                                     if let Err(err) = conn.handle_connection(stream).await {
                                         tracing::error!("TCP connection error: {err}");
                                     }
@@ -278,43 +373,184 @@ impl DaemonServer {
             };
 
             tracing::debug!("Received request: {:?}", request.method);
+            let method = request.method;
+
+            // Process the request, unless its client cancels it first
+            let (partials, parts) = Partials::channel(request.stream);
+            let feeds = Feeds {
+                updates: self.progress_updates(&request),
+                parts,
+                wire: self.wire_messages(&request),
+            };
+            let handled = if feeds.any() {
+                self.handle_with_notifications(request, &partials, feeds, &mut writer).await?
+            } else {
+                self.handle_cancellable(request, &partials).await
+            };
+            let Some(response) = handled else {
+                // The client is exiting and won't read the response.
+                break;
+            };
 
-            // Process the request
-            let response = self.handle_request(request).await;
-
-            // Send response with Content-Length framing
-            let response_json =
-                serde_json::to_string(&response).context("Failed to serialize response")?;
-            let framed = format!("Content-Length: {}\r\n\r\n{response_json}", response_json.len());
-            writer.write_all(framed.as_bytes()).await.context("Failed to write response")?;
-            writer.flush().await.context("Failed to flush response")?;
+            write_frame(&mut writer, &response).await.context("Failed to write response")?;
 
             tracing::debug!("Sent response for request ID {}", response.id);
+            if method == Method::Shutdown {
+                break;
+            }
         }
 
         Ok(())
     }
 
+    /// The progress updates to forward while `request` is handled: those of
+    /// its workspace, if it asked for them and names one.
+    fn progress_updates(
+        &self,
+        request: &DaemonRequest,
+    ) -> Option<broadcast::Receiver<WorkProgress>> {
+        let workspace = request.params.get("workspace").and_then(Value::as_str)?;
+        request.progress.then(|| self.lsp_pool.subscribe_progress(std::path::Path::new(workspace)))
+    }
+
+    /// The language server messages to forward while `request` is handled:
+    /// those of its workspace, if it asked for a trace and names one.
+    fn wire_messages(&self, request: &DaemonRequest) -> Option<broadcast::Receiver<WireMessage>> {
+        let workspace = request.params.get("workspace").and_then(Value::as_str)?;
+        request.trace.then(|| self.lsp_pool.subscribe_wire(std::path::Path::new(workspace)))
+    }
+
+    /// [`handle_cancellable`](Self::handle_cancellable), writing a
+    /// notification for each of the `feeds` while it runs: `progress` for
+    /// updates, `partial` for parts and `trace` for wire messages.
+    async fn handle_with_notifications<W: AsyncWrite + Unpin>(
+        &self,
+        request: DaemonRequest,
+        partials: &Partials,
+        feeds: Feeds,
+        writer: &mut W,
+    ) -> Result<Option<DaemonResponse>> {
+        let Feeds { mut updates, mut parts, mut wire } = feeds;
+        let id = request.id;
+        let handling = self.handle_cancellable(request, partials);
+        tokio::pin!(handling);
+        let response = loop {
+            tokio::select! {
+                response = &mut handling => break response,
+                Some(update) = next_update(updates.as_mut()) => {
+                    write_frame(writer, &DaemonNotification::progress(&update))
+                        .await
+                        .context("Failed to write progress notification")?;
+                }
+                Some(part) = next_part(parts.as_mut()) => {
+                    write_frame(writer, &DaemonNotification::partial(id, part))
+                        .await
+                        .context("Failed to write partial result")?;
+                }
+                Some(message) = next_wire_message(wire.as_mut()) => {
+                    write_frame(writer, &DaemonNotification::trace(&message))
+                        .await
+                        .context("Failed to write trace notification")?;
+                }
+            }
+        };
+        // So do the last messages with the server.
+        while let Some(message) = wire.as_mut().and_then(|wire| wire.try_recv().ok()) {
+            write_frame(writer, &DaemonNotification::trace(&message))
+                .await
+                .context("Failed to write trace notification")?;
+        }
+        // Parts sent just before the handler returned still go ahead of the
+        // response, which holds only what they don't.
+        while let Some(part) = parts.as_mut().and_then(|parts| parts.try_recv().ok()) {
+            write_frame(writer, &DaemonNotification::partial(id, part))
+                .await
+                .context("Failed to write partial result")?;
+        }
+        Ok(response)
+    }
+
+    /// [`handle_request`](Self::handle_request), abandoned with `None` when
+    /// the client process sends a `cancel` while it runs.
+    ///
+    /// Dropping the handler drops its pending LSP requests, which tells ty to
+    /// stop working on them.
+    async fn handle_cancellable(
+        &self,
+        request: DaemonRequest,
+        partials: &Partials,
+    ) -> Option<DaemonResponse> {
+        let Some(pid) = request.pid.filter(|_| request.method != Method::Cancel) else {
+            return Some(self.handle_request(request, partials).await);
+        };
+        let key = (pid, request.id);
+        let cancelled = Arc::new(Notify::new());
+        self.in_flight
+            .lock()
+            .expect("in_flight mutex poisoned")
+            .insert(key, Arc::clone(&cancelled));
+        let method = request.method;
+        let response = tokio::select! {
+            response = self.handle_request(request, partials) => Some(response),
+            () = cancelled.notified() => None,
+        };
+        self.in_flight.lock().expect("in_flight mutex poisoned").remove(&key);
+        if response.is_none() {
+            tracing::info!("Cancelled {} request {} from pid {pid}", method.as_str(), key.1);
+        }
+        response
+    }
+
     /// Process a single JSON-RPC request and return a response.
-    async fn handle_request(&self, request: DaemonRequest) -> DaemonResponse {
+    ///
+    /// A streaming handler sends what it has ready early to `partials`.
+    async fn handle_request(&self, request: DaemonRequest, partials: &Partials) -> DaemonResponse {
         let want_debug = request.debug;
         let lsp_method = Self::daemon_to_lsp_method(request.method);
         // Clone params for debug trace (only when debug is requested)
         let debug_params = if want_debug { Some(request.params.clone()) } else { None };
 
-        let result = match request.method {
-            Method::Hover => self.handle_hover(request.params).await,
-            Method::Definition => self.handle_definition(request.params).await,
-            Method::WorkspaceSymbols => self.handle_workspace_symbols(request.params).await,
-            Method::DocumentSymbols => self.handle_document_symbols(request.params).await,
-            Method::References => self.handle_references(request.params).await,
-            Method::BatchReferences => self.handle_batch_references(request.params).await,
-            Method::Inspect => self.handle_inspect(request.params).await,
-            Method::Members => self.handle_members(request.params).await,
-            Method::Diagnostics => self.handle_diagnostics(request.params).await,
-            Method::Ping => self.handle_ping(request.params).await,
-            Method::Shutdown => self.handle_shutdown(request.params).await,
-        };
+        if !protocol_compatible(request.method, request.protocol) {
+            tracing::warn!(
+                "Rejecting {} from client speaking protocol v{}",
+                request.method.as_str(),
+                request.protocol
+            );
+            return DaemonResponse::error(
+                request.id,
+                DaemonError::protocol_mismatch(request.protocol),
+            );
+        }
+
+        // Start the backend this request asks for, if the workspace's server
+        // is another one; answers cached from the old server no longer apply.
+        // A drop names the workspace only to stop its server, whichever it is.
+        let warmed = request.params.get("workspaces").and_then(Value::as_array);
+        let workspaces = request
+            .params
+            .get("workspace")
+            .filter(|_| request.method != Method::DropWorkspace)
+            .into_iter()
+            .chain(warmed.into_iter().flatten());
+        for workspace in workspaces.filter_map(Value::as_str) {
+            if self
+                .lsp_pool
+                .request_backend(std::path::Path::new(workspace), request.backend.as_deref())
+            {
+                self.response_cache.clear();
+            }
+        }
+
+        let started = Instant::now();
+        let mut result = self.dispatch(request.method, request.params.clone(), partials).await;
+        // A retry would repeat the parts the client already has.
+        if result.as_ref().is_err_and(is_server_exited) && !partials.sent() {
+            // ty died mid-request; the pool starts a fresh server on the next
+            // lookup, so one retry usually succeeds.
+            tracing::warn!("ty exited during {}; retrying once", request.method.as_str());
+            result = self.dispatch(request.method, request.params, partials).await;
+        }
+        self.metrics.record(request.method, started.elapsed(), result.is_ok());
 
         let debug_trace = if want_debug {
             lsp_method.map(|method| {
@@ -334,11 +570,53 @@ impl DaemonServer {
 
         let response = match result {
             Ok(value) => DaemonResponse::success(request.id, value),
-            Err(e) => DaemonResponse::error(request.id, DaemonError::internal_error(e.to_string())),
+            Err(e) => {
+                let error = match e.chain().find_map(|c| c.downcast_ref::<TyNotFound>()) {
+                    Some(missing) => DaemonError::ty_not_found(missing.0.clone()),
+                    None => DaemonError::internal_error(format!("{e:#}")),
+                };
+                DaemonResponse::error(request.id, error)
+            }
         };
         response.with_debug_trace(debug_trace)
     }
 
+    /// Run the handler for `method`.
+    async fn dispatch(&self, method: Method, params: Value, partials: &Partials) -> Result<Value> {
+        match method {
+            Method::Hover => self.handle_hover(params).await,
+            Method::BatchHover => self.handle_batch_hover(params).await,
+            Method::Definition => self.handle_definition(params).await,
+            Method::BatchDefinitions => self.handle_batch_definitions(params).await,
+            Method::Goto => self.handle_goto(params).await,
+            Method::ExplainLine => self.handle_explain_line(params).await,
+            Method::WorkspaceSymbols => self.handle_workspace_symbols(params, partials).await,
+            Method::DocumentSymbols => self.handle_document_symbols(params).await,
+            Method::References => self.handle_references(params).await,
+            Method::BatchReferences => self.handle_batch_references(params, partials).await,
+            Method::Inspect => self.handle_inspect(params).await,
+            Method::InspectMany => self.handle_inspect_many(params).await,
+            Method::Members => self.handle_members(params).await,
+            Method::ExpressionMembers => self.handle_expression_members(params).await,
+            Method::Diagnostics => self.handle_diagnostics(params).await,
+            Method::BatchDiagnostics => self.handle_batch_diagnostics(params).await,
+            Method::BatchDocumentSymbols => self.handle_batch_document_symbols(params).await,
+            Method::InlayHints => self.handle_inlay_hints(params).await,
+            Method::DocumentHighlights => self.handle_document_highlights(params).await,
+            Method::FilesChanged => self.handle_files_changed(params).await,
+            Method::TyInfo => self.handle_ty_info(params).await,
+            Method::ClearCache => self.handle_clear_cache(params).await,
+            Method::ReloadConfig => self.handle_reload_config(params).await,
+            Method::Warmup => self.handle_warmup(params).await,
+            Method::Stats => self.handle_stats(params).await,
+            Method::Workspaces => self.handle_workspaces(params).await,
+            Method::DropWorkspace => self.handle_drop_workspace(params).await,
+            Method::Cancel => self.handle_cancel(params).await,
+            Method::Ping => self.handle_ping(params).await,
+            Method::Shutdown => self.handle_shutdown(params).await,
+        }
+    }
+
     /// Resolve a file path against the workspace root.
     ///
     /// If the file path is relative, it is joined with the workspace root to
@@ -353,17 +631,55 @@ impl DaemonServer {
         }
     }
 
+    /// Put a document opened with a request's unsaved content back to the
+    /// file on disk, or close it if there is no such file.
+    ///
+    /// Answers about buffers are never cached: the cache is keyed on the
+    /// file's stamp, which the buffer doesn't change.
+    async fn restore_document(client: &TyLspClient, file: &str) -> Result<()> {
+        if client.reload_document(file).await.is_err() {
+            client.close_document(file).await?;
+        }
+        Ok(())
+    }
+
     /// Map daemon method to the primary underlying LSP method.
     fn daemon_to_lsp_method(method: Method) -> Option<&'static str> {
         match method {
-            Method::Hover => Some("textDocument/hover"),
-            Method::Definition => Some("textDocument/definition"),
+            Method::Hover | Method::BatchHover => Some("textDocument/hover"),
+            Method::Definition | Method::BatchDefinitions => Some("textDocument/definition"),
+            Method::ExplainLine => {
+                Some("textDocument/hover + textDocument/definition + textDocument/diagnostic")
+            }
+            Method::Goto => {
+                Some("textDocument/definition + declaration + typeDefinition + implementation")
+            }
             Method::References | Method::BatchReferences => Some("textDocument/references"),
             Method::WorkspaceSymbols => Some("workspace/symbol"),
-            Method::DocumentSymbols => Some("textDocument/documentSymbol"),
-            Method::Inspect => Some("textDocument/hover + textDocument/references"),
+            Method::DocumentSymbols | Method::BatchDocumentSymbols => {
+                Some("textDocument/documentSymbol")
+            }
+            Method::Inspect | Method::InspectMany => {
+                Some("textDocument/hover + textDocument/references")
+            }
             Method::Members => Some("textDocument/documentSymbol + textDocument/hover"),
-            Method::Ping | Method::Shutdown | Method::Diagnostics => None,
+            Method::ExpressionMembers => Some("textDocument/completion"),
+            Method::Diagnostics | Method::BatchDiagnostics => Some("textDocument/diagnostic"),
+            Method::InlayHints => Some("textDocument/inlayHint"),
+            Method::DocumentHighlights => Some("textDocument/documentHighlight"),
+            Method::FilesChanged => {
+                Some("textDocument/didChange + didClose + workspace/didChangeWatchedFiles")
+            }
+            Method::TyInfo => Some("initialize"),
+            Method::Warmup => Some("initialize + workspace/symbol"),
+            Method::Cancel => Some("$/cancelRequest"),
+            Method::ClearCache
+            | Method::ReloadConfig
+            | Method::Stats
+            | Method::Workspaces
+            | Method::DropWorkspace
+            | Method::Ping
+            | Method::Shutdown => None,
         }
     }
 
@@ -376,14 +692,92 @@ impl DaemonServer {
 
         let resolved = Self::resolve_file(&params.workspace, params.file);
         let file_str = resolved.to_string_lossy().to_string();
+        if let Some(content) = params.content {
+            client.change_document(&file_str, content).await?;
+            let hover = hover_with_warmup(&client, &file_str, params.line, params.column).await;
+            Self::restore_document(&client, &file_str).await?;
+            return Ok(serde_json::to_value(HoverResult { hover: hover? })?);
+        }
+
+        let generation = self.symbol_indexes.get_or_start(&params.workspace).generation();
+        let query = CachedQuery::Hover { line: params.line, column: params.column, generation };
+        if let Some(cached) = self.response_cache.get(&params.workspace, &resolved, query) {
+            return Ok(cached);
+        }
         client.open_document(&file_str).await?;
 
-        let hover = Self::hover_with_warmup(&client, &file_str, params.line, params.column).await?;
+        let hover = hover_with_warmup(&client, &file_str, params.line, params.column).await?;
+
+        let cacheable = hover.is_some();
+        let value = serde_json::to_value(HoverResult { hover })?;
+        if cacheable {
+            self.response_cache.insert(&params.workspace, &resolved, query, value.clone(), &[]);
+        }
+        Ok(value)
+    }
+
+    /// Handle a batch hover request.
+    ///
+    /// Cached answers are used as they are; the other positions are kept up
+    /// to `concurrency` at once in flight on the one LSP client, and their
+    /// answers cached as a single hover's would be.
+    async fn handle_batch_hover(&self, params: Value) -> Result<Value> {
+        let params: BatchHoverParams =
+            serde_json::from_value(params).context("Invalid batch hover parameters")?;
+
+        let client = self.lsp_pool.get_or_create(params.workspace.clone()).await?;
+
+        let generation = self.symbol_indexes.get_or_start(&params.workspace).generation();
+        let concurrency = params.concurrency.max(1);
+        let mut results: Vec<Option<BatchHoverEntry>> = vec![None; params.queries.len()];
+        let mut in_flight = tokio::task::JoinSet::new();
+        for (idx, q) in params.queries.into_iter().enumerate() {
+            let resolved = Self::resolve_file(&params.workspace, q.file);
+            let query = CachedQuery::Hover { line: q.line, column: q.column, generation };
+            if let Some(cached) = self.response_cache.get(&params.workspace, &resolved, query) {
+                let cached: HoverResult = serde_json::from_value(cached)?;
+                results[idx] = Some(BatchHoverEntry { label: q.label, hover: cached.hover });
+                continue;
+            }
+            if in_flight.len() >= concurrency {
+                if let Some(done) = in_flight.join_next().await {
+                    let (idx, entry) = done.context("Hover task failed")?;
+                    results[idx] = Some(self.cache_batch_hover(&params.workspace, entry?)?);
+                }
+            }
+            let client = Arc::clone(&client);
+            in_flight.spawn(async move {
+                let file_str = resolved.to_string_lossy().to_string();
+                let entry = async {
+                    client.open_document(&file_str).await?;
+                    let hover = hover_with_warmup(&client, &file_str, q.line, q.column).await?;
+                    anyhow::Ok((resolved, query, BatchHoverEntry { label: q.label, hover }))
+                };
+                (idx, entry.await)
+            });
+        }
+        while let Some(done) = in_flight.join_next().await {
+            let (idx, entry) = done.context("Hover task failed")?;
+            results[idx] = Some(self.cache_batch_hover(&params.workspace, entry?)?);
+        }
 
-        let result = HoverResult { hover };
+        let result = BatchHoverResult { entries: results.into_iter().flatten().collect() };
         Ok(serde_json::to_value(result)?)
     }
 
+    /// Cache the answer to one position of a batch hover, if there is one.
+    fn cache_batch_hover(
+        &self,
+        workspace: &std::path::Path,
+        (file, query, entry): (PathBuf, CachedQuery, BatchHoverEntry),
+    ) -> Result<BatchHoverEntry> {
+        if entry.hover.is_some() {
+            let value = serde_json::to_value(HoverResult { hover: entry.hover.clone() })?;
+            self.response_cache.insert(workspace, &file, query, value, &[]);
+        }
+        Ok(entry)
+    }
+
     /// Handle a definition request.
     async fn handle_definition(&self, params: Value) -> Result<Value> {
         let params: DefinitionParams =
@@ -393,32 +787,265 @@ impl DaemonServer {
 
         let resolved = Self::resolve_file(&params.workspace, params.file);
         let file_str = resolved.to_string_lossy().to_string();
+        let definition = || {
+            with_warmup(
+                "definition",
+                &WARMUP_DELAYS,
+                |locs: &Vec<Location>| !locs.is_empty(),
+                || client.goto_definition(&file_str, params.line, params.column),
+                None, // Definition lookups are position-based, rg check not applicable
+            )
+        };
+        if let Some(content) = params.content {
+            client.change_document(&file_str, content).await?;
+            let locations = definition().await;
+            Self::restore_document(&client, &file_str).await?;
+            let location = locations?.into_iter().next();
+            return Ok(serde_json::to_value(DefinitionResult { location })?);
+        }
+
+        let query = CachedQuery::Definition { line: params.line, column: params.column };
+        if let Some(cached) = self.response_cache.get(&params.workspace, &resolved, query) {
+            return Ok(cached);
+        }
         client.open_document(&file_str).await?;
-        let locations = with_warmup(
-            "definition",
-            &WARMUP_DELAYS,
-            |locs: &Vec<Location>| !locs.is_empty(),
-            || client.goto_definition(&file_str, params.line, params.column),
-            None, // Definition lookups are position-based, rg check not applicable
-        )
-        .await?;
+        let locations = definition().await?;
 
         let location = locations.into_iter().next();
-        let result = DefinitionResult { location };
+        // A definition moves when the file it points into is edited, too.
+        let target = location.as_ref().map(|loc| PathBuf::from(uri_to_path(&loc.uri)));
+        let value = serde_json::to_value(DefinitionResult { location })?;
+        if let Some(target) = target {
+            self.response_cache.insert(
+                &params.workspace,
+                &resolved,
+                query,
+                value.clone(),
+                std::slice::from_ref(&target),
+            );
+        }
+        Ok(value)
+    }
+
+    /// Handle a goto request.
+    ///
+    /// Requests the server doesn't advertise are answered as unsupported
+    /// rather than failing the call, so the others still come back.
+    async fn handle_goto(&self, params: Value) -> Result<Value> {
+        let params: GotoParams =
+            serde_json::from_value(params).context("Invalid goto parameters")?;
+
+        let client = self.lsp_pool.get_or_create(params.workspace.clone()).await?;
+
+        let mut entries = Vec::with_capacity(params.queries.len() * params.kinds.len());
+        for q in params.queries {
+            let resolved = Self::resolve_file(&params.workspace, q.file);
+            let file_str = resolved.to_string_lossy().to_string();
+            client.open_document(&file_str).await?;
+            for &kind in &params.kinds {
+                let unsupported = !client.supports(kind.provider());
+                let locations = if unsupported {
+                    Vec::new()
+                } else {
+                    client.goto(kind, &file_str, q.line, q.column).await?
+                };
+                entries.push(GotoEntry { label: q.label.clone(), kind, locations, unsupported });
+            }
+        }
+        Ok(serde_json::to_value(GotoResult { entries })?)
+    }
+
+    /// Handle an explain line request.
+    ///
+    /// The file's diagnostics are fetched while the identifiers are hovered
+    /// and their definitions looked up, up to `concurrency` at once.
+    async fn handle_explain_line(&self, params: Value) -> Result<Value> {
+        let params: ExplainLineParams =
+            serde_json::from_value(params).context("Invalid explain line parameters")?;
+
+        let client = self.lsp_pool.get_or_create(params.workspace.clone()).await?;
+
+        let resolved = Self::resolve_file(&params.workspace, params.file);
+        let file_str = resolved.to_string_lossy().to_string();
+        client.open_document(&file_str).await?;
+        let diagnostics = {
+            let client = Arc::clone(&client);
+            let file_str = file_str.clone();
+            tokio::spawn(async move { client.diagnostics(&file_str).await })
+        };
+
+        let concurrency = params.concurrency.max(1);
+        let mut results: Vec<Option<ExplainedIdentifier>> = vec![None; params.identifiers.len()];
+        let mut in_flight = tokio::task::JoinSet::new();
+        for (idx, identifier) in params.identifiers.into_iter().enumerate() {
+            if in_flight.len() >= concurrency {
+                if let Some(done) = in_flight.join_next().await {
+                    let (idx, entry) = done.context("Explain line task failed")?;
+                    results[idx] = Some(entry?);
+                }
+            }
+            let client = Arc::clone(&client);
+            let file_str = file_str.clone();
+            let line = params.line;
+            in_flight.spawn(async move {
+                let entry = async {
+                    let column = identifier.column;
+                    let hover = hover_with_warmup(&client, &file_str, line, column).await?;
+                    let definitions = client.goto_definition(&file_str, line, column).await?;
+                    anyhow::Ok(ExplainedIdentifier {
+                        name: identifier.name,
+                        column,
+                        hover,
+                        definitions,
+                    })
+                };
+                (idx, entry.await)
+            });
+        }
+        while let Some(done) = in_flight.join_next().await {
+            let (idx, entry) = done.context("Explain line task failed")?;
+            results[idx] = Some(entry?);
+        }
+
+        let diagnostics = diagnostics
+            .await
+            .context("Diagnostics task failed")??
+            .into_iter()
+            .filter(|d| (d.range.start.line..=d.range.end.line).contains(&params.line))
+            .collect();
+        let result =
+            ExplainLineResult { identifiers: results.into_iter().flatten().collect(), diagnostics };
+        Ok(serde_json::to_value(result)?)
+    }
+
+    /// Handle a batch definitions request.
+    ///
+    /// Like batch hover: cached answers are used as they are, and the other
+    /// positions are kept up to `concurrency` at once in flight on the one LSP
+    /// client. An answer is cached until its file or a file it points into
+    /// changes.
+    async fn handle_batch_definitions(&self, params: Value) -> Result<Value> {
+        let params: BatchDefinitionsParams =
+            serde_json::from_value(params).context("Invalid batch definitions parameters")?;
+
+        let client = self.lsp_pool.get_or_create(params.workspace.clone()).await?;
+
+        let concurrency = params.concurrency.max(1);
+        let mut results: Vec<Option<BatchDefinitionsEntry>> = vec![None; params.queries.len()];
+        let mut in_flight = tokio::task::JoinSet::new();
+        for (idx, q) in params.queries.into_iter().enumerate() {
+            let resolved = Self::resolve_file(&params.workspace, q.file);
+            let query = CachedQuery::Definitions { line: q.line, column: q.column };
+            if let Some(cached) = self.response_cache.get(&params.workspace, &resolved, query) {
+                let locations: Vec<Location> = serde_json::from_value(cached)?;
+                results[idx] = Some(BatchDefinitionsEntry { label: q.label, locations });
+                continue;
+            }
+            if in_flight.len() >= concurrency {
+                if let Some(done) = in_flight.join_next().await {
+                    let (idx, entry) = done.context("Definition task failed")?;
+                    results[idx] = Some(self.cache_batch_definitions(&params.workspace, entry?)?);
+                }
+            }
+            let client = Arc::clone(&client);
+            in_flight.spawn(async move {
+                let file_str = resolved.to_string_lossy().to_string();
+                let entry = async {
+                    client.open_document(&file_str).await?;
+                    let locations = with_warmup(
+                        "batch definitions",
+                        &WARMUP_DELAYS,
+                        |locs: &Vec<Location>| !locs.is_empty(),
+                        || client.goto_definition(&file_str, q.line, q.column),
+                        None, // Definition lookups are position-based, rg check not applicable
+                    )
+                    .await?;
+                    anyhow::Ok((
+                        resolved,
+                        query,
+                        BatchDefinitionsEntry { label: q.label, locations },
+                    ))
+                };
+                (idx, entry.await)
+            });
+        }
+        while let Some(done) = in_flight.join_next().await {
+            let (idx, entry) = done.context("Definition task failed")?;
+            results[idx] = Some(self.cache_batch_definitions(&params.workspace, entry?)?);
+        }
+
+        let result = BatchDefinitionsResult { entries: results.into_iter().flatten().collect() };
         Ok(serde_json::to_value(result)?)
     }
 
+    /// Cache the answer to one position of a batch definitions request, if
+    /// there is one.
+    fn cache_batch_definitions(
+        &self,
+        workspace: &std::path::Path,
+        (file, query, entry): (PathBuf, CachedQuery, BatchDefinitionsEntry),
+    ) -> Result<BatchDefinitionsEntry> {
+        if !entry.locations.is_empty() {
+            let targets: Vec<PathBuf> =
+                entry.locations.iter().map(|loc| PathBuf::from(uri_to_path(&loc.uri))).collect();
+            let value = serde_json::to_value(&entry.locations)?;
+            self.response_cache.insert(workspace, &file, query, value, &targets);
+        }
+        Ok(entry)
+    }
+
     /// Handle a workspace symbols request.
-    async fn handle_workspace_symbols(&self, params: Value) -> Result<Value> {
+    async fn handle_workspace_symbols(&self, params: Value, partials: &Partials) -> Result<Value> {
         let params: WorkspaceSymbolsParams =
             serde_json::from_value(params).context("Invalid workspace symbols parameters")?;
 
-        let workspace = params.workspace;
+        let workspace = params.workspace.clone();
+        // Always touch the pool so an index-only answer still counts as activity.
         let client = self.lsp_pool.get_or_create(workspace.clone()).await?;
 
-        let mut symbols =
-            Self::workspace_symbols_with_warmup(&client, &params.query, &workspace).await?;
+        // Answer from the daemon's own index when it has a match; fall back to
+        // ty while the index is cold or when it knows nothing by that name.
+        // The index only holds workspace files, so it can't answer a search
+        // that reaches into dependencies.
+        let in_workspace = params.path_prefix.as_ref().is_some_and(|p| p.starts_with(&workspace));
+        let index = self.symbol_indexes.get_or_start(&workspace);
+        let indexed = if in_workspace {
+            match params.exact_name {
+                Some(ref exact_name) => index.lookup_exact(exact_name),
+                None => index.lookup_fuzzy(&params.query),
+            }
+        } else {
+            None
+        }
+        .map(|symbols| Self::filter_symbols(symbols, &params))
+        .filter(|symbols| !symbols.is_empty());
+
+        let mut symbols = if let Some(symbols) = indexed {
+            symbols
+        } else {
+            let symbols = workspace_symbols_with_warmup(&client, &params.query, &workspace).await?;
+            Self::filter_symbols(symbols, &params)
+        };
+
+        let remaining = params.page.apply(&mut symbols);
+        if partials.enabled() {
+            // ty answers in one message; sending it on in parts lets the
+            // client print the first symbols while the rest are on the way.
+            while symbols.len() > SYMBOLS_PER_PART {
+                let rest = symbols.split_off(SYMBOLS_PER_PART);
+                partials.send(&WorkspaceSymbolsResult { symbols, remaining: 0 })?;
+                symbols = rest;
+            }
+        }
+        let result = WorkspaceSymbolsResult { symbols, remaining };
+        Ok(serde_json::to_value(result)?)
+    }
 
+    /// Apply the name, container, kind and path filters of a workspace symbols request.
+    fn filter_symbols(
+        mut symbols: Vec<crate::lsp::protocol::SymbolInformation>,
+        params: &WorkspaceSymbolsParams,
+    ) -> Vec<crate::lsp::protocol::SymbolInformation> {
         // Filter by exact name if specified (avoids serializing thousands of fuzzy matches)
         if let Some(ref exact_name) = params.exact_name {
             symbols.retain(|s| s.name == *exact_name);
@@ -429,13 +1056,24 @@ impl DaemonServer {
             symbols.retain(|s| s.container_name.as_deref() == Some(container.as_str()));
         }
 
-        // Apply limit if specified
-        if let Some(limit) = params.limit {
-            symbols.truncate(limit);
+        if !params.kinds.is_empty() {
+            symbols.retain(|s| params.kinds.contains(&s.kind));
         }
 
-        let result = WorkspaceSymbolsResult { symbols };
-        Ok(serde_json::to_value(result)?)
+        if let Some(ref prefix) = params.path_prefix {
+            symbols.retain(|s| {
+                std::path::Path::new(&crate::lsp::uri::uri_to_path(&s.location.uri))
+                    .starts_with(prefix)
+            });
+        }
+
+        if !params.paths.is_empty() {
+            symbols.retain(|s| {
+                let path = crate::lsp::uri::uri_to_path(&s.location.uri);
+                params.paths.allows(std::path::Path::new(&path), &params.workspace)
+            });
+        }
+        symbols
     }
 
     /// Handle a document symbols request.
@@ -446,6 +1084,10 @@ impl DaemonServer {
         let client = self.lsp_pool.get_or_create(params.workspace.clone()).await?;
 
         let resolved = Self::resolve_file(&params.workspace, params.file);
+        let query = CachedQuery::DocumentSymbols;
+        if let Some(cached) = self.response_cache.get(&params.workspace, &resolved, query) {
+            return Ok(cached);
+        }
         let file_str = resolved.to_string_lossy().to_string();
         client.open_document(&file_str).await?;
         let symbols = with_warmup(
@@ -457,8 +1099,12 @@ impl DaemonServer {
         )
         .await?;
 
-        let result = DocumentSymbolsResult { symbols };
-        Ok(serde_json::to_value(result)?)
+        let cacheable = !symbols.is_empty();
+        let value = serde_json::to_value(DocumentSymbolsResult { symbols })?;
+        if cacheable {
+            self.response_cache.insert(&params.workspace, &resolved, query, value.clone(), &[]);
+        }
+        Ok(value)
     }
 
     /// Handle a references request.
@@ -471,7 +1117,7 @@ impl DaemonServer {
         let resolved = Self::resolve_file(&params.workspace, params.file);
         let file_str = resolved.to_string_lossy().to_string();
         client.open_document(&file_str).await?;
-        let locations = with_warmup(
+        let mut locations = with_warmup(
             "references",
             &WARMUP_DELAYS,
             |locs: &Vec<Location>| !locs.is_empty(),
@@ -486,38 +1132,200 @@ impl DaemonServer {
             None, // References are position-based, rg check not applicable
         )
         .await?;
+        params.paths.retain_locations(&mut locations, &params.workspace);
 
         let result = ReferencesResult { locations };
         Ok(serde_json::to_value(result)?)
     }
 
     /// Handle a batch references request (multiple queries, one connection).
-    async fn handle_batch_references(&self, params: Value) -> Result<Value> {
+    ///
+    /// Up to `concurrency` queries are in flight at once; entries come back
+    /// in query order.
+    async fn handle_batch_references(&self, params: Value, partials: &Partials) -> Result<Value> {
         let params: BatchReferencesParams =
             serde_json::from_value(params).context("Invalid batch references parameters")?;
 
         let client = self.lsp_pool.get_or_create(params.workspace.clone()).await?;
 
-        let mut entries = Vec::with_capacity(params.queries.len());
-        for q in &params.queries {
+        // Entries sharing a label are merged, so a label can only be sent
+        // ahead once all its queries are done, and only if they are adjacent.
+        let labels: Vec<String> = params.queries.iter().map(|q| q.label.clone()).collect();
+        let streaming = partials.enabled() && !params.count_only && labels_adjacent(&labels);
+        let mut streamed = 0;
+
+        let concurrency = params.concurrency.max(1);
+        let mut results: Vec<Option<BatchReferencesEntry>> = vec![None; params.queries.len()];
+        let mut in_flight = tokio::task::JoinSet::new();
+        for (idx, q) in params.queries.iter().cloned().enumerate() {
+            if in_flight.len() >= concurrency {
+                if let Some(done) = in_flight.join_next().await {
+                    let (idx, entry) = done.context("References task failed")?;
+                    results[idx] = Some(entry?);
+                    if streaming {
+                        Self::send_ready_references(
+                            &mut results,
+                            &labels,
+                            &mut streamed,
+                            &params,
+                            partials,
+                        )?;
+                    }
+                }
+            }
             let resolved = Self::resolve_file(&params.workspace, q.file.clone());
-            let file_str = resolved.to_string_lossy().to_string();
-            client.open_document(&file_str).await?;
-            let locations = with_warmup(
-                "batch references",
-                &WARMUP_DELAYS,
-                |locs: &Vec<Location>| !locs.is_empty(),
-                || client.find_references(&file_str, q.line, q.column, params.include_declaration),
-                None, // Batch references are position-based, rg check not applicable
-            )
-            .await?;
-            entries.push(BatchReferencesEntry { label: q.label.clone(), locations });
+            let client = Arc::clone(&client);
+            let include_declaration = params.include_declaration;
+            in_flight.spawn(async move {
+                (idx, Self::references_entry(&client, q, &resolved, include_declaration).await)
+            });
+        }
+        while let Some(done) = in_flight.join_next().await {
+            let (idx, entry) = done.context("References task failed")?;
+            results[idx] = Some(entry?);
+            if streaming {
+                Self::send_ready_references(
+                    &mut results,
+                    &labels,
+                    &mut streamed,
+                    &params,
+                    partials,
+                )?;
+            }
         }
 
+        let entries = Self::finish_references(results.into_iter().flatten().collect(), &params);
         let result = BatchReferencesResult { entries };
         Ok(serde_json::to_value(result)?)
     }
 
+    /// Narrow each entry's references to the paths (and workspace) `params`
+    /// ask for, then count or page them.
+    fn finish_references(
+        mut entries: Vec<BatchReferencesEntry>,
+        params: &BatchReferencesParams,
+    ) -> Vec<BatchReferencesEntry> {
+        for entry in &mut entries {
+            params.paths.retain_locations(&mut entry.locations, &params.workspace);
+            if params.workspace_only {
+                retain_workspace_locations(&mut entry.locations, &params.workspace);
+            }
+        }
+        if params.count_only {
+            entries = Self::count_references(entries);
+        } else if !params.page.is_all() {
+            entries = Self::page_references(entries, params.page);
+        }
+        entries
+    }
+
+    /// Send the entries of the labels from `results[*next]` on whose queries
+    /// are all done, in query order, moving `next` past them.
+    fn send_ready_references(
+        results: &mut [Option<BatchReferencesEntry>],
+        labels: &[String],
+        next: &mut usize,
+        params: &BatchReferencesParams,
+        partials: &Partials,
+    ) -> Result<()> {
+        let mut ready = Vec::new();
+        while *next < results.len() {
+            let label = &labels[*next];
+            let end =
+                labels[*next..].iter().position(|l| l != label).map_or(labels.len(), |n| *next + n);
+            if results[*next..end].iter().any(Option::is_none) {
+                break;
+            }
+            ready.extend(results[*next..end].iter_mut().filter_map(Option::take));
+            *next = end;
+        }
+        if ready.is_empty() {
+            return Ok(());
+        }
+        partials.send(&BatchReferencesResult { entries: Self::finish_references(ready, params) })
+    }
+
+    async fn references_entry(
+        client: &TyLspClient,
+        query: BatchReferencesQuery,
+        resolved: &std::path::Path,
+        include_declaration: bool,
+    ) -> Result<BatchReferencesEntry> {
+        let file_str = resolved.to_string_lossy().to_string();
+        client.open_document(&file_str).await?;
+        let locations = with_warmup(
+            "batch references",
+            &WARMUP_DELAYS,
+            |locs: &Vec<Location>| !locs.is_empty(),
+            || client.find_references(&file_str, query.line, query.column, include_declaration),
+            None, // Batch references are position-based, rg check not applicable
+        )
+        .await?;
+        Ok(BatchReferencesEntry { label: query.label, locations, files: Vec::new(), remaining: 0 })
+    }
+
+    /// Merge the entries sharing a label and keep one `page` of each one's
+    /// references, recording how many follow it.
+    ///
+    /// A line referenced by several entries is kept once, as the CLI does
+    /// when it lists them, and references are sorted by file and position so
+    /// the same page comes back every time.
+    fn page_references(
+        entries: Vec<BatchReferencesEntry>,
+        page: ResultPage,
+    ) -> Vec<BatchReferencesEntry> {
+        let mut merged: Vec<BatchReferencesEntry> = Vec::new();
+        for entry in entries {
+            match merged.iter_mut().find(|m| m.label == entry.label) {
+                Some(existing) => existing.locations.extend(entry.locations),
+                None => merged.push(entry),
+            }
+        }
+        for entry in &mut merged {
+            let locations = &mut entry.locations;
+            locations.sort_by(|a, b| {
+                let key = |l: &Location| (l.range.start.line, l.range.start.character);
+                a.uri.cmp(&b.uri).then_with(|| key(a).cmp(&key(b)))
+            });
+            locations.dedup_by(|a, b| a.uri == b.uri && a.range.start.line == b.range.start.line);
+            entry.remaining = page.apply(locations);
+        }
+        merged
+    }
+
+    /// Replace the locations of each label with its reference count per file.
+    ///
+    /// Entries sharing a label are merged, and a line referenced by several
+    /// of them is counted once, as the CLI does when it lists them.
+    fn count_references(entries: Vec<BatchReferencesEntry>) -> Vec<BatchReferencesEntry> {
+        let mut merged: Vec<(String, HashSet<(String, u32)>)> = Vec::new();
+        for entry in entries {
+            let index =
+                merged.iter().position(|(label, _)| *label == entry.label).unwrap_or_else(|| {
+                    merged.push((entry.label.clone(), HashSet::new()));
+                    merged.len() - 1
+                });
+            merged[index]
+                .1
+                .extend(entry.locations.into_iter().map(|l| (l.uri, l.range.start.line)));
+        }
+        merged
+            .into_iter()
+            .map(|(label, lines)| {
+                let mut per_file: HashMap<String, usize> = HashMap::new();
+                for (uri, _) in lines {
+                    *per_file.entry(uri).or_default() += 1;
+                }
+                let mut files: Vec<FileReferenceCount> = per_file
+                    .into_iter()
+                    .map(|(uri, count)| FileReferenceCount { uri, count })
+                    .collect();
+                files.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.uri.cmp(&b.uri)));
+                BatchReferencesEntry { label, locations: Vec::new(), files, remaining: 0 }
+            })
+            .collect()
+    }
+
     /// Handle an inspect request (hover, and optionally references).
     ///
     /// Requests are sequential because the LSP client communicates through a
@@ -530,322 +1338,646 @@ impl DaemonServer {
 
         let resolved = Self::resolve_file(&params.workspace, params.file);
         let file_str = resolved.to_string_lossy().to_string();
-        client.open_document(&file_str).await?;
-
-        let hover = Self::hover_with_warmup(&client, &file_str, params.line, params.column).await?;
-
-        let references = if params.include_references {
-            client.find_references(&file_str, params.line, params.column, false).await?
-        } else {
-            Vec::new()
-        };
+        let (hover, references) = Self::inspect_position(
+            &client,
+            &file_str,
+            params.line,
+            params.column,
+            params.include_references,
+        )
+        .await?;
 
         let result = InspectResult { hover, references };
         Ok(serde_json::to_value(result)?)
     }
 
-    /// Handle a members request.
+    /// Handle an inspect many request.
     ///
-    /// Retrieves document symbols for the file, finds the target class,
-    /// extracts its children, and calls hover on each to get type signatures.
-    /// This is N+1 LSP calls per class (1 documentSymbol + N hovers).
-    async fn handle_members(&self, params: Value) -> Result<Value> {
-        let params: MembersParams =
-            serde_json::from_value(params).context("Invalid members parameters")?;
+    /// Like batch hover, up to `concurrency` positions are in flight on the
+    /// one LSP client at once, each inspected as by an inspect request.
+    async fn handle_inspect_many(&self, params: Value) -> Result<Value> {
+        let params: InspectManyParams =
+            serde_json::from_value(params).context("Invalid inspect many parameters")?;
 
         let client = self.lsp_pool.get_or_create(params.workspace.clone()).await?;
 
-        let resolved = Self::resolve_file(&params.workspace, params.file);
-        let file_str = resolved.to_string_lossy().to_string();
-        client.open_document(&file_str).await?;
-
-        let doc_symbols = client.document_symbols(&file_str).await?;
+        let concurrency = params.concurrency.max(1);
+        let mut results: Vec<Option<InspectManyEntry>> = vec![None; params.queries.len()];
+        let mut in_flight = tokio::task::JoinSet::new();
+        for (idx, q) in params.queries.into_iter().enumerate() {
+            if in_flight.len() >= concurrency {
+                if let Some(done) = in_flight.join_next().await {
+                    let (idx, entry) = done.context("Inspect task failed")?;
+                    results[idx] = Some(entry?);
+                }
+            }
+            let file_str =
+                Self::resolve_file(&params.workspace, q.file).to_string_lossy().to_string();
+            let client = Arc::clone(&client);
+            let include_references = params.include_references;
+            in_flight.spawn(async move {
+                let entry = async {
+                    let (hover, references) = Self::inspect_position(
+                        &client,
+                        &file_str,
+                        q.line,
+                        q.column,
+                        include_references,
+                    )
+                    .await?;
+                    anyhow::Ok(InspectManyEntry { label: q.label, hover, references })
+                };
+                (idx, entry.await)
+            });
+        }
+        while let Some(done) = in_flight.join_next().await {
+            let (idx, entry) = done.context("Inspect task failed")?;
+            results[idx] = Some(entry?);
+        }
 
-        // Find the target class anywhere in the symbol tree (may be nested)
-        let target = Self::find_symbol_recursive(&doc_symbols, &params.class_name);
+        let result = InspectManyResult { entries: results.into_iter().flatten().collect() };
+        Ok(serde_json::to_value(result)?)
+    }
 
-        let Some(class_sym) = target else {
-            // Symbol not found in file
-            let result = MembersResult {
-                class_name: params.class_name,
-                file_uri: file_str,
-                class_line: 0,
-                class_column: 0,
-                symbol_kind: None,
-                members: Vec::new(),
-            };
-            return Ok(serde_json::to_value(result)?);
+    /// Hover, and optionally references, at one position.
+    async fn inspect_position(
+        client: &TyLspClient,
+        file: &str,
+        line: u32,
+        column: u32,
+        include_references: bool,
+    ) -> Result<(Option<Hover>, Vec<Location>)> {
+        client.open_document(file).await?;
+        let hover = hover_with_warmup(client, file, line, column).await?;
+        let references = if include_references {
+            client.find_references(file, line, column, false).await?
+        } else {
+            Vec::new()
         };
+        Ok((hover, references))
+    }
 
-        // Check that it's actually a class
-        if !matches!(class_sym.kind, SymbolKind::Class) {
-            let result = MembersResult {
-                class_name: params.class_name,
-                file_uri: file_str,
-                class_line: class_sym.selection_range.start.line,
-                class_column: class_sym.selection_range.start.character,
-                symbol_kind: Some(class_sym.kind.clone()),
-                members: Vec::new(),
-            };
-            return Ok(serde_json::to_value(result)?);
-        }
+    /// Handle a members request.
+    ///
+    /// Retrieves document symbols for the file, finds the target class,
+    /// extracts its children, and calls hover on each to get type signatures.
+    /// This is N+1 LSP calls per class (1 documentSymbol + N hovers).
+    async fn handle_members(&self, params: Value) -> Result<Value> {
+        let params: MembersParams =
+            serde_json::from_value(params).context("Invalid members parameters")?;
 
-        let children = class_sym.children.as_deref().unwrap_or(&[]);
+        let client = self.lsp_pool.get_or_create(params.workspace.clone()).await?;
 
-        // Filter members based on include_all flag
-        let filtered: Vec<_> = children
-            .iter()
-            .filter(|child| {
-                if params.include_all {
-                    return true;
-                }
-                // Exclude private (_prefixed) and dunder (__dunder__) members
-                !child.name.starts_with('_')
-            })
-            .collect();
+        let resolved = Self::resolve_file(&params.workspace, params.file);
+        let file_str = resolved.to_string_lossy().to_string();
+        client.open_document(&file_str).await?;
 
-        // Get hover info for each member (N LSP calls — sequential, single pipe)
-        let mut members = Vec::with_capacity(filtered.len());
-        for child in &filtered {
-            let hover_line = child.selection_range.start.line;
-            let hover_col = child.selection_range.start.character;
-            let hover = Self::hover_with_warmup(&client, &file_str, hover_line, hover_col).await?;
-
-            let signature =
-                hover.as_ref().map(|h| Self::extract_member_signature(&h.contents, &child.name));
-
-            members.push(MemberInfo {
-                name: child.name.clone(),
-                kind: child.kind.clone(),
-                signature,
-                line: child.selection_range.start.line,
-                column: child.selection_range.start.character,
-            });
-        }
+        let result =
+            members::class_members(&client, &file_str, &params.class_name, params.include_all)
+                .await?;
+        Ok(serde_json::to_value(result)?)
+    }
 
-        let result = MembersResult {
-            class_name: params.class_name,
-            file_uri: file_str,
-            class_line: class_sym.selection_range.start.line,
-            class_column: class_sym.selection_range.start.character,
-            symbol_kind: Some(class_sym.kind.clone()),
-            members,
-        };
+    /// Handle an expression members request.
+    async fn handle_expression_members(&self, params: Value) -> Result<Value> {
+        let params: ExpressionMembersParams =
+            serde_json::from_value(params).context("Invalid expression members parameters")?;
+
+        let client = self.lsp_pool.get_or_create(params.workspace.clone()).await?;
+
+        let resolved = Self::resolve_file(&params.workspace, params.file);
+        let file_str = resolved.to_string_lossy().to_string();
+        client.open_document(&file_str).await?;
+
+        let result = members::expression_members(
+            &client,
+            &file_str,
+            params.line,
+            params.column,
+            params.include_all,
+        )
+        .await?;
         Ok(serde_json::to_value(result)?)
     }
 
-    /// Recursively search document symbols for a symbol with the given name.
+    /// Handle a diagnostics request.
     ///
-    /// `document_symbols` returns a hierarchical tree — classes nested inside
-    /// other classes or functions only appear as children, not at the top level.
-    fn find_symbol_recursive<'a>(
-        symbols: &'a [crate::lsp::protocol::DocumentSymbol],
-        name: &str,
-    ) -> Option<&'a crate::lsp::protocol::DocumentSymbol> {
-        for s in symbols {
-            if s.name == name {
-                return Some(s);
-            }
-            if let Some(children) = &s.children {
-                if let Some(found) = Self::find_symbol_recursive(children, name) {
-                    return Some(found);
+    /// Not retried while ty warms up: an empty list is a real answer for a
+    /// clean file, and ty computes pulled diagnostics on demand anyway.
+    async fn handle_diagnostics(&self, params: Value) -> Result<Value> {
+        let params: DiagnosticsParams =
+            serde_json::from_value(params).context("Invalid diagnostics parameters")?;
+
+        let client = self.lsp_pool.get_or_create(params.workspace.clone()).await?;
+
+        let resolved = Self::resolve_file(&params.workspace, params.file);
+        let file_str = resolved.to_string_lossy().to_string();
+        client.open_document(&file_str).await?;
+        let diagnostics = client.diagnostics(&file_str).await?;
+
+        let result = DiagnosticsResult { diagnostics };
+        Ok(serde_json::to_value(result)?)
+    }
+
+    /// Handle a batch diagnostics request.
+    ///
+    /// Keeps up to `concurrency` files in flight on the one LSP client, so ty
+    /// can check them in parallel. A file that fails (deleted, unreadable) is
+    /// reported in its entry rather than failing the whole batch.
+    async fn handle_batch_diagnostics(&self, params: Value) -> Result<Value> {
+        let params: BatchDiagnosticsParams =
+            serde_json::from_value(params).context("Invalid batch diagnostics parameters")?;
+
+        let client = self.lsp_pool.get_or_create(params.workspace.clone()).await?;
+
+        let concurrency = params.concurrency.max(1);
+        let mut results: Vec<Option<BatchDiagnosticsEntry>> = vec![None; params.files.len()];
+        let mut in_flight = tokio::task::JoinSet::new();
+        for (idx, file) in params.files.iter().enumerate() {
+            if in_flight.len() >= concurrency {
+                if let Some(done) = in_flight.join_next().await {
+                    let (idx, entry) = done.context("Diagnostics task failed")?;
+                    results[idx] = Some(entry);
                 }
             }
+            let resolved = Self::resolve_file(&params.workspace, file.clone());
+            let client = Arc::clone(&client);
+            let file = file.clone();
+            in_flight.spawn(async move {
+                (idx, Self::file_diagnostics_entry(&client, file, &resolved).await)
+            });
         }
-        None
+        while let Some(done) = in_flight.join_next().await {
+            let (idx, entry) = done.context("Diagnostics task failed")?;
+            results[idx] = Some(entry);
+        }
+
+        let result = BatchDiagnosticsResult { entries: results.into_iter().flatten().collect() };
+        Ok(serde_json::to_value(result)?)
     }
 
-    /// Extract a clean member signature from hover contents.
-    ///
-    /// ty's hover markdown looks like:
-    ///   ```python\ndef method(self, x: int) -> str\n```\n---\nDocstring
-    ///
-    /// We want just the signature: `method(self, x: int) -> str`
-    ///
-    /// `member_name` is used to prefix bare type signatures (e.g. class
-    /// variables where ty returns just the type like `int`).
-    fn extract_member_signature(
-        contents: &crate::lsp::protocol::HoverContents,
-        member_name: &str,
-    ) -> String {
-        use crate::lsp::protocol::HoverContents;
-
-        let full = match contents {
-            HoverContents::Scalar(s) => s.clone(),
-            HoverContents::Markup(markup) => markup.value.clone(),
-            HoverContents::MarkedString(ms) => ms.value.clone(),
-            HoverContents::Array(arr) => {
-                use crate::lsp::protocol::MarkedStringOrString;
-                arr.iter()
-                    .map(|item| match item {
-                        MarkedStringOrString::String(s) => s.clone(),
-                        MarkedStringOrString::MarkedString(ms) => ms.value.clone(),
-                    })
-                    .collect::<Vec<_>>()
-                    .join("\n")
-            }
+    async fn file_diagnostics_entry(
+        client: &TyLspClient,
+        file: PathBuf,
+        resolved: &std::path::Path,
+    ) -> BatchDiagnosticsEntry {
+        let file_str = resolved.to_string_lossy().to_string();
+        let diagnostics = match client.open_document(&file_str).await {
+            Ok(_) => client.diagnostics(&file_str).await,
+            Err(e) => Err(e),
         };
+        match diagnostics {
+            Ok(diagnostics) => BatchDiagnosticsEntry { file, diagnostics, error: None },
+            Err(e) => BatchDiagnosticsEntry {
+                file,
+                diagnostics: Vec::new(),
+                error: Some(format!("{e:#}")),
+            },
+        }
+    }
 
-        // Strip docstring (everything after "\n---")
-        let type_part = match full.find("\n---") {
-            Some(pos) => &full[..pos],
-            None => &full,
-        };
+    /// Handle a batch document symbols request.
+    ///
+    /// Outlines come from the response cache where it has them; the rest are
+    /// requested like batch diagnostics, up to `concurrency` at once, and
+    /// cached the same way a single document symbols request caches them.
+    async fn handle_batch_document_symbols(&self, params: Value) -> Result<Value> {
+        let params: BatchDocumentSymbolsParams =
+            serde_json::from_value(params).context("Invalid batch document symbols parameters")?;
 
-        // Strip markdown code fences
-        let trimmed = type_part.trim();
-        let cleaned = trimmed
-            .strip_prefix("```python")
-            .or_else(|| trimmed.strip_prefix("```xml"))
-            .or_else(|| trimmed.strip_prefix("```text"))
-            .or_else(|| trimmed.strip_prefix("```"))
-            .unwrap_or(trimmed);
-
-        let cleaned = cleaned.trim().strip_suffix("```").unwrap_or(cleaned).trim();
-
-        // Strip leading `def ` for method signatures — show just `name(params) -> ret`
-        let cleaned = cleaned.strip_prefix("def ").unwrap_or(cleaned);
-
-        // Strip leading `(method) `, `(property) `, etc. prefixes ty may add
-        let cleaned = if let Some(rest) = cleaned.strip_prefix('(') {
-            if let Some(pos) = rest.find(") ") {
-                let after = &rest[pos + 2..];
-                after.strip_prefix("def ").unwrap_or(after)
-            } else {
-                cleaned
+        let client = self.lsp_pool.get_or_create(params.workspace.clone()).await?;
+
+        let query = CachedQuery::DocumentSymbols;
+        let concurrency = params.concurrency.max(1);
+        let mut results: Vec<Option<BatchDocumentSymbolsEntry>> = vec![None; params.files.len()];
+        let mut fetched = Vec::new();
+        let mut in_flight = tokio::task::JoinSet::new();
+        for (idx, file) in params.files.iter().enumerate() {
+            let resolved = Self::resolve_file(&params.workspace, file.clone());
+            let cached = self
+                .response_cache
+                .get(&params.workspace, &resolved, query)
+                .and_then(|value| serde_json::from_value::<DocumentSymbolsResult>(value).ok());
+            if let Some(cached) = cached {
+                let entry = BatchDocumentSymbolsEntry {
+                    file: file.clone(),
+                    symbols: cached.symbols,
+                    error: None,
+                };
+                results[idx] = Some(entry);
+                continue;
             }
-        } else {
-            cleaned
-        };
+            if in_flight.len() >= concurrency {
+                if let Some(done) = in_flight.join_next().await {
+                    let (idx, resolved, entry) = done.context("Document symbols task failed")?;
+                    fetched.push((idx, resolved));
+                    results[idx] = Some(entry);
+                }
+            }
+            let client = Arc::clone(&client);
+            let file = file.clone();
+            in_flight.spawn(async move {
+                let entry = Self::file_symbols_entry(&client, file, &resolved).await;
+                (idx, resolved, entry)
+            });
+        }
+        while let Some(done) = in_flight.join_next().await {
+            let (idx, resolved, entry) = done.context("Document symbols task failed")?;
+            fetched.push((idx, resolved));
+            results[idx] = Some(entry);
+        }
 
-        // Collapse multi-line signatures (ty wraps long parameter lists)
-        // e.g. "assist(\n    self,\n    task: str\n) -> str" → "assist(self, task: str) -> str"
-        let cleaned = Self::collapse_signature(cleaned);
+        for (idx, resolved) in fetched {
+            let Some(entry) = &results[idx] else { continue };
+            if entry.error.is_none() && !entry.symbols.is_empty() {
+                let value =
+                    serde_json::to_value(DocumentSymbolsResult { symbols: entry.symbols.clone() })?;
+                self.response_cache.insert(&params.workspace, &resolved, query, value, &[]);
+            }
+        }
 
-        // If the signature is a bare type (no name prefix, no parens), prepend
-        // the member name so it reads like `registry: dict[str, X]` instead of
-        // just `dict[str, X]`.
-        if !cleaned.contains('(') && !cleaned.contains(':') && cleaned != member_name {
-            format!("{member_name}: {cleaned}")
-        } else {
-            cleaned
+        let result =
+            BatchDocumentSymbolsResult { entries: results.into_iter().flatten().collect() };
+        Ok(serde_json::to_value(result)?)
+    }
+
+    async fn file_symbols_entry(
+        client: &TyLspClient,
+        file: PathBuf,
+        resolved: &std::path::Path,
+    ) -> BatchDocumentSymbolsEntry {
+        let file_str = resolved.to_string_lossy().to_string();
+        let symbols = match client.open_document(&file_str).await {
+            Ok(_) => client.document_symbols(&file_str).await,
+            Err(e) => Err(e),
+        };
+        match symbols {
+            Ok(symbols) => BatchDocumentSymbolsEntry { file, symbols, error: None },
+            Err(e) => BatchDocumentSymbolsEntry {
+                file,
+                symbols: Vec::new(),
+                error: Some(format!("{e:#}")),
+            },
         }
     }
 
-    /// Collapse a multi-line signature into a single line.
-    ///
-    /// ty formats long signatures like:
-    /// ```text
-    /// assist(
-    ///     self,
-    ///     task: str,
-    ///     duration_minutes: int = 30
-    /// ) -> str
-    /// ```
+    /// Handle a files changed request.
     ///
-    /// This collapses them to: `assist(self, task: str, duration_minutes: int = 30) -> str`
-    fn collapse_signature(sig: &str) -> String {
-        if !sig.contains('\n') {
-            return sig.to_string();
-        }
-        // Replace newlines + surrounding whitespace with a single space,
-        // then clean up spaces around parentheses and commas.
-        let collapsed: String = sig.lines().map(str::trim).collect::<Vec<_>>().join(" ");
-        // Normalize "( " → "(", " )" → ")", ",  " → ", "
-        let collapsed = collapsed.replace("( ", "(").replace(" )", ")");
-        // Collapse multiple spaces
-        let mut result = String::with_capacity(collapsed.len());
-        let mut prev_space = false;
-        for ch in collapsed.chars() {
-            if ch == ' ' {
-                if !prev_space {
-                    result.push(ch);
+    /// Closes the changed files that are open, so ty reads them from disk
+    /// again, and reports every change as a watched-file event. Cached
+    /// responses are keyed on file stamps, except hovers, which go stale
+    /// with the workspace's generation.
+    async fn handle_files_changed(&self, params: Value) -> Result<Value> {
+        let params: FilesChangedParams =
+            serde_json::from_value(params).context("Invalid files changed parameters")?;
+
+        let client = self.lsp_pool.get_or_create(params.workspace.clone()).await?;
+
+        let files: Vec<String> = params
+            .changes
+            .iter()
+            .map(|c| {
+                Self::resolve_file(&params.workspace, c.file.clone()).to_string_lossy().to_string()
+            })
+            .collect();
+        let (mut closed, mut updated) = (0, 0);
+        for (file, change) in files.iter().zip(&params.changes) {
+            // Open documents follow their file with didChange; a file that is
+            // gone or no longer readable as Python is closed instead.
+            let reloaded = match change.change {
+                FileChangeType::Deleted => None,
+                FileChangeType::Created | FileChangeType::Changed => {
+                    client.reload_document(file).await.ok()
                 }
-                prev_space = true;
-            } else {
-                prev_space = false;
-                result.push(ch);
+            };
+            match reloaded {
+                Some(true) => updated += 1,
+                Some(false) => {}
+                None => closed += usize::from(client.close_document(file).await?),
             }
         }
-        result
+        let events: Vec<(&str, _)> =
+            files.iter().map(String::as_str).zip(params.changes.iter().map(|c| c.change)).collect();
+        client.notify_files_changed(&events).await?;
+        self.symbol_indexes.get_or_start(&params.workspace).files_changed();
+
+        let result = FilesChangedResult { closed, updated };
+        Ok(serde_json::to_value(result)?)
     }
 
-    /// Handle a diagnostics request.
-    #[allow(clippy::unused_async)] // Matches async handler interface
-    async fn handle_diagnostics(&self, _params: Value) -> Result<Value> {
-        // Diagnostics are not yet implemented in the LSP client
-        // Return empty diagnostics for now
-        let result = DiagnosticsResult { diagnostics: vec![] };
+    /// Handle an inlay hints request.
+    async fn handle_inlay_hints(&self, params: Value) -> Result<Value> {
+        let params: InlayHintsParams =
+            serde_json::from_value(params).context("Invalid inlay hints parameters")?;
+
+        let client = self.lsp_pool.get_or_create(params.workspace.clone()).await?;
+
+        let resolved = Self::resolve_file(&params.workspace, params.file);
+        let file_str = resolved.to_string_lossy().to_string();
+        let text = tokio::fs::read_to_string(&resolved)
+            .await
+            .with_context(|| format!("Failed to read file: {file_str}"))?;
+        let line_count = u32::try_from(text.lines().count()).unwrap_or(u32::MAX);
+        client.open_document(&file_str).await?;
+        let hints = client.inlay_hints(&file_str, line_count + 1).await?;
+
+        let result = InlayHintsResult { hints };
+        Ok(serde_json::to_value(result)?)
+    }
+
+    /// Handle a document highlights request.
+    async fn handle_document_highlights(&self, params: Value) -> Result<Value> {
+        let params: DocumentHighlightsParams =
+            serde_json::from_value(params).context("Invalid document highlights parameters")?;
+
+        let client = self.lsp_pool.get_or_create(params.workspace.clone()).await?;
+
+        let resolved = Self::resolve_file(&params.workspace, params.file);
+        let file_str = resolved.to_string_lossy().to_string();
+        let canonical = crate::lsp::uri::canonicalize(&resolved)
+            .with_context(|| format!("Failed to resolve path: {file_str}"))?;
+        client.open_document(&file_str).await?;
+        let highlights = with_warmup(
+            "document highlights",
+            &WARMUP_DELAYS,
+            |highlights: &Vec<DocumentHighlight>| !highlights.is_empty(),
+            || client.document_highlights(&file_str, params.line, params.column),
+            None,
+        )
+        .await?;
+
+        let result =
+            DocumentHighlightsResult { uri: crate::lsp::uri::path_to_uri(&canonical), highlights };
+        Ok(serde_json::to_value(result)?)
+    }
+
+    /// Handle a ty-info request.
+    async fn handle_ty_info(&self, params: Value) -> Result<Value> {
+        let params: TyInfoParams =
+            serde_json::from_value(params).context("Invalid ty-info parameters")?;
+
+        let client = self.lsp_pool.get_or_create(params.workspace.clone()).await?;
+        let info = client.server_info().cloned().unwrap_or_default();
+
+        let result = TyInfoResult {
+            workspace: params.workspace.to_string_lossy().to_string(),
+            providers: info.supported_providers(),
+            name: info.server_info.as_ref().map(|s| s.name.clone()),
+            version: info.server_info.and_then(|s| s.version),
+            capabilities: info.capabilities,
+        };
         Ok(serde_json::to_value(result)?)
     }
 
     /// Handle a ping request.
     #[allow(clippy::unused_async)] // Matches async handler interface
     async fn handle_ping(&self, _params: Value) -> Result<Value> {
-        let workspace_paths: Vec<String> = self
-            .lsp_pool
-            .active_workspaces()
-            .into_iter()
-            .map(|p| p.to_string_lossy().to_string())
+        let clients = self.lsp_pool.clients();
+        let restarts = self.lsp_pool.restarts();
+        let workspace_paths: Vec<String> =
+            clients.iter().map(|(p, _)| p.to_string_lossy().to_string()).collect();
+        let ty_servers = clients
+            .iter()
+            .filter_map(|(path, client)| {
+                client.server_info().map(|info| TyServerSummary {
+                    workspace: path.to_string_lossy().to_string(),
+                    server: info.server_label(),
+                    restarts: restarts.get(path).copied().unwrap_or(0),
+                    exited: client.has_exited(),
+                })
+            })
             .collect();
 
         let cwd = std::env::current_dir().ok().map(|p| p.to_string_lossy().to_string());
+        // A server on stdio has no socket or port.
+        let listening = !self.socket_path.as_os_str().is_empty();
 
         let result = PingResult {
             status: "running".to_string(),
             version: env!("CARGO_PKG_VERSION").to_string(),
             uptime: self.start_time.elapsed().as_secs(),
             active_workspaces: workspace_paths.len(),
-            cache_size: 0, // Cache not yet implemented
-            socket_path: Some(self.socket_path.to_string_lossy().into_owned()),
-            tcp_port: Some(self.tcp_port),
+            cache_size: self.response_cache.len(),
+            socket_path: listening.then(|| self.socket_path.to_string_lossy().into_owned()),
+            tcp_port: listening.then_some(self.tcp_port),
             workspace_paths,
             pid: std::process::id(),
             cwd,
+            ty_servers,
+            protocol: PROTOCOL_VERSION,
+            lsp_restarts: restarts.values().sum(),
         };
         Ok(serde_json::to_value(result)?)
     }
 
-    /// Hover with retry on cold start.
-    ///
-    /// The ty LSP server may return null hover when a document was recently
-    /// opened and analysis hasn't completed. Retry with back-off.
-    async fn hover_with_warmup(
-        client: &TyLspClient,
-        file: &str,
-        line: u32,
-        column: u32,
-    ) -> Result<Option<Hover>> {
-        with_warmup(
-            "hover",
-            &WARMUP_DELAYS,
-            |h: &Option<Hover>| h.is_some(),
-            || client.hover(file, line, column),
-            None, // No symbol name available for position-based hover
-        )
-        .await
+    /// Handle a cancel request: stop every in-flight request of a process.
+    #[allow(clippy::unused_async)] // Matches async handler interface
+    async fn handle_cancel(&self, params: Value) -> Result<Value> {
+        let params: CancelParams =
+            serde_json::from_value(params).context("Invalid cancel parameters")?;
+        let in_flight = self.in_flight.lock().expect("in_flight mutex poisoned");
+        let mut cancelled = 0;
+        for ((pid, _), notify) in in_flight.iter() {
+            if *pid == params.pid {
+                // Stores a permit if the request hasn't started waiting yet.
+                notify.notify_one();
+                cancelled += 1;
+            }
+        }
+        Ok(serde_json::to_value(CancelResult { cancelled })?)
+    }
+
+    /// Handle a workspaces request: every pooled client, sorted by workspace.
+    #[allow(clippy::unused_async)] // Matches async handler interface
+    async fn handle_workspaces(&self, _params: Value) -> Result<Value> {
+        let restarts = self.lsp_pool.restarts();
+        let mut workspaces: Vec<WorkspaceEntry> = self
+            .lsp_pool
+            .pooled()
+            .into_iter()
+            .map(|pooled| WorkspaceEntry {
+                server: pooled.client.server_info().map(InitializeResult::server_label),
+                server_pid: pooled.client.server_pid(),
+                idle_secs: pooled.idle.as_secs(),
+                restarts: restarts.get(&pooled.workspace).copied().unwrap_or(0),
+                exited: pooled.client.has_exited(),
+                workspace: pooled.workspace.to_string_lossy().into_owned(),
+                backend: pooled.backend,
+            })
+            .collect();
+        workspaces.sort_by(|a, b| a.workspace.cmp(&b.workspace));
+        Ok(serde_json::to_value(WorkspacesResult { workspaces })?)
+    }
+
+    /// Handle a drop-workspace request: stop one workspace's language
+    /// server (or every one) and forget its symbol index and cached
+    /// responses, leaving the other workspaces running.
+    #[allow(clippy::unused_async)] // Matches async handler interface
+    async fn handle_drop_workspace(&self, params: Value) -> Result<Value> {
+        let params: DropWorkspaceParams =
+            serde_json::from_value(params).context("Invalid drop workspace parameters")?;
+        let dropped: Vec<PathBuf> = match params.workspace {
+            Some(workspace) => self.lsp_pool.remove(&workspace).into_iter().collect(),
+            None => self.lsp_pool.clear(),
+        };
+        if !dropped.is_empty() {
+            let active = self.lsp_pool.active_workspaces();
+            self.symbol_indexes.retain(&active);
+            self.response_cache.retain(&active);
+        }
+        for workspace in &dropped {
+            tracing::info!("Dropped the language server of {}", workspace.display());
+        }
+        let dropped = dropped.iter().map(|w| w.to_string_lossy().into_owned()).collect();
+        Ok(serde_json::to_value(DropWorkspaceResult { dropped })?)
+    }
+
+    /// Handle a stats request.
+    #[allow(clippy::unused_async)] // Matches async handler interface
+    async fn handle_stats(&self, _params: Value) -> Result<Value> {
+        let methods = self.metrics.snapshot();
+        let starts = self.lsp_pool.starts();
+        let restarts = |starts: u64| starts.saturating_sub(1);
+        let mut workspaces: Vec<WorkspaceStats> = self
+            .lsp_pool
+            .clients()
+            .into_iter()
+            .map(|(path, client)| {
+                let server_pid = client.server_pid();
+                let server_rss_bytes = server_pid.and_then(process_rss);
+                let (index_files, index_symbols, index_bytes) =
+                    self.symbol_indexes.get(&path).map_or((0, 0, 0), |index| index.footprint());
+                let (cache_entries, cache_bytes) = self.response_cache.footprint(&path);
+                WorkspaceStats {
+                    workspace: path.to_string_lossy().into_owned(),
+                    lsp_restarts: starts.get(&path).copied().map_or(0, restarts),
+                    server_pid,
+                    server_rss_bytes,
+                    index_files,
+                    index_symbols,
+                    index_bytes,
+                    cache_entries,
+                    cache_bytes,
+                    estimated_bytes: server_rss_bytes.unwrap_or(0) + index_bytes + cache_bytes,
+                }
+            })
+            .collect();
+        workspaces.sort_by(|a, b| a.workspace.cmp(&b.workspace));
+
+        let result = StatsResult {
+            uptime: self.start_time.elapsed().as_secs(),
+            requests: methods.iter().map(|m| m.calls).sum(),
+            methods,
+            lsp_starts: starts.values().sum(),
+            lsp_restarts: starts.values().copied().map(restarts).sum(),
+            workspaces,
+        };
+        Ok(serde_json::to_value(result)?)
     }
 
-    /// Workspace symbols with retry on cold start.
+    /// Handle a clear-cache request.
+    #[allow(clippy::unused_async)] // Matches async handler interface
+    async fn handle_clear_cache(&self, _params: Value) -> Result<Value> {
+        let cleared = self.response_cache.clear();
+        tracing::info!("Cleared {cleared} cached responses");
+        Ok(serde_json::to_value(ClearCacheResult { cleared })?)
+    }
+
+    /// Handle a reload-config request.
     ///
-    /// On cold start the ty LSP server may not have finished indexing the
-    /// workspace yet, returning zero symbols. Retry with back-off.
+    /// Re-reads the daemon's settings from the user config and each
+    /// workspace's settings, affecting only what changed: the language server
+    /// of a workspace whose backend changed is stopped, and a symbol index
+    /// built with other excludes is rebuilt. Every other server keeps running.
+    #[allow(clippy::unused_async)] // Matches async handler interface
+    async fn handle_reload_config(&self, _params: Value) -> Result<Value> {
+        let user = Config::user().context("Failed to reload the user config")?;
+        let (idle_timeout, _) = user.idle_timeout();
+        let (cache_size, _) = user.cache_size();
+        *self.idle_timeout.lock().expect("idle timeout mutex poisoned") = idle_timeout;
+        let evicted = self.response_cache.set_capacity(cache_size);
+
+        let restarted = self.lsp_pool.reload();
+        // Answers from a server that was stopped may differ from its successor's.
+        self.response_cache.retain(&self.lsp_pool.active_workspaces());
+        let reindexed = self.symbol_indexes.reload();
+        tracing::info!(
+            "Reloaded config: idle timeout {idle_timeout:?}, cache size {cache_size}, {} server(s) \
+             restarted, {} index(es) rebuilt",
+            restarted.len(),
+            reindexed.len()
+        );
+
+        let display = |paths: Vec<PathBuf>| -> Vec<String> {
+            paths.into_iter().map(|path| path.to_string_lossy().into_owned()).collect()
+        };
+        Ok(serde_json::to_value(ReloadConfigResult {
+            idle_timeout: idle_timeout.as_secs(),
+            cache_size,
+            evicted,
+            restarted: display(restarted),
+            reindexed: display(reindexed),
+        })?)
+    }
+
+    /// Handle a warmup request.
     ///
-    /// Uses ripgrep as a circuit-breaker: after the first empty result, if `rg`
-    /// confirms the symbol doesn't exist in any `.py` file, skips retries.
-    async fn workspace_symbols_with_warmup(
-        client: &TyLspClient,
-        query: &str,
-        workspace_root: &std::path::Path,
-    ) -> Result<Vec<crate::lsp::protocol::SymbolInformation>> {
-        with_warmup(
-            "workspace symbols",
-            &WARMUP_DELAYS,
-            |syms: &Vec<crate::lsp::protocol::SymbolInformation>| !syms.is_empty(),
-            || client.workspace_symbols(query),
-            Some(RgCheck { symbol: query, workspace_root }),
-        )
-        .await
+    /// Starts each workspace's language server and the daemon's symbol index
+    /// for it. Unless the client waits, it is answered right away and the
+    /// servers keep starting in the background.
+    async fn handle_warmup(&self, params: Value) -> Result<Value> {
+        let params: WarmupParams =
+            serde_json::from_value(params).context("Invalid warmup parameters")?;
+
+        let mut entries = Vec::new();
+        let mut warming = tokio::task::JoinSet::new();
+        for (idx, workspace) in params.workspaces.into_iter().enumerate() {
+            self.symbol_indexes.get_or_start(&workspace);
+            entries.push(WarmupEntry {
+                workspace: workspace.to_string_lossy().into_owned(),
+                running: self.lsp_pool.contains(&workspace),
+                server: None,
+                elapsed_ms: None,
+                error: None,
+            });
+            let pool = Arc::clone(&self.lsp_pool);
+            warming.spawn(async move {
+                let start = Instant::now();
+                let outcome = Self::warm_workspace(&pool, workspace.clone()).await;
+                if let Err(e) = &outcome {
+                    tracing::warn!("Failed to warm up {}: {e:#}", workspace.display());
+                }
+                (idx, outcome, start.elapsed())
+            });
+        }
+
+        if params.wait {
+            while let Some(done) = warming.join_next().await {
+                let (idx, outcome, elapsed) = done.context("Warmup task failed")?;
+                let entry = &mut entries[idx];
+                entry.elapsed_ms = Some(u64::try_from(elapsed.as_millis()).unwrap_or(u64::MAX));
+                match outcome {
+                    Ok(server) => entry.server = Some(server),
+                    Err(e) => entry.error = Some(format!("{e:#}")),
+                }
+            }
+        } else {
+            warming.detach_all();
+        }
+
+        Ok(serde_json::to_value(WarmupResult { workspaces: entries })?)
+    }
+
+    /// Start `workspace`'s language server and have it index the project;
+    /// returns the server's label.
+    async fn warm_workspace(pool: &LspClientPool, workspace: PathBuf) -> Result<String> {
+        let client = pool.get_or_create(workspace).await?;
+        // Answering any workspace symbol query makes the server read every
+        // file; ask for a name nothing has so the answer stays small.
+        if client.server_info().is_none_or(|info| info.supports("workspaceSymbolProvider")) {
+            client.workspace_symbols("__tyf_warmup__").await?;
+        }
+        Ok(client.server_info().map_or_else(|| "ty".to_string(), InitializeResult::server_label))
     }
 
     /// Handle a shutdown request.
@@ -861,18 +1993,33 @@ impl DaemonServer {
     }
 
     /// Idle timeout task that shuts down the daemon after inactivity.
+    ///
+    /// The timeout is the user config's `idle-timeout`, read at startup and
+    /// again by `reload_config`.
     async fn idle_timeout_task(&self) {
-        let idle_timeout = Duration::from_secs(300); // 5 minutes
-        let check_interval = Duration::from_secs(60); // 1 minute
-
         loop {
+            let idle_timeout = *self.idle_timeout.lock().expect("idle timeout mutex poisoned");
+            let check_interval = idle_timeout.min(Duration::from_secs(60));
             tokio::time::sleep(check_interval).await;
 
+            let crashed = self.lsp_pool.evict_exited();
+            if crashed > 0 {
+                tracing::info!("Dropped {crashed} LSP clients whose server exited");
+            }
+
             // Clean up idle LSP clients
             let removed = self.lsp_pool.cleanup_idle(idle_timeout);
             if removed > 0 {
                 tracing::info!("Removed {removed} idle LSP clients");
             }
+            let dropped = self.symbol_indexes.retain(&self.lsp_pool.active_workspaces());
+            if dropped > 0 {
+                tracing::info!("Dropped {dropped} idle symbol indexes");
+            }
+            let evicted = self.response_cache.retain(&self.lsp_pool.active_workspaces());
+            if evicted > 0 {
+                tracing::debug!("Evicted {evicted} cached responses for idle workspaces");
+            }
 
             // Check if daemon should shut down (all clients idle)
             if self.lsp_pool.is_empty() && self.start_time.elapsed() > idle_timeout {
@@ -883,6 +2030,18 @@ impl DaemonServer {
         }
     }
 
+    /// Shut the daemon down once process `owner` exits (`--kill-after`).
+    async fn owner_task(&self, owner: u32) {
+        loop {
+            tokio::time::sleep(OWNER_CHECK_INTERVAL).await;
+            if !process_exists(owner) {
+                tracing::info!("Process {owner} exited, shutting down");
+                let _ = self.shutdown_tx.send(());
+                break;
+            }
+        }
+    }
+
     /// Graceful shutdown cleanup.
     #[allow(clippy::unused_async)] // Called from async context
     async fn cleanup(&self) -> Result<()> {
@@ -900,91 +2059,156 @@ impl DaemonServer {
     }
 }
 
-/// Context for the optional ripgrep-based early termination check.
-///
-/// When provided, `with_warmup` will run `rg` after the first empty result
-/// to check whether the symbol text exists in any `.py` file. If it doesn't,
-/// retries are skipped — the symbol provably does not exist.
-struct RgCheck<'a> {
-    symbol: &'a str,
-    workspace_root: &'a std::path::Path,
+/// Send a framed error response to the client.
+async fn send_error_response<W: AsyncWrite + Unpin>(
+    writer: &mut W,
+    error: DaemonError,
+) -> Result<()> {
+    write_frame(writer, &DaemonResponse::error(0, error)).await
 }
 
-/// Retry an LSP operation with exponential back-off when it returns an "empty" result.
-///
-/// On cold start the ty LSP server may not have finished indexing a document
-/// or the workspace, causing operations to return empty/null results. This
-/// helper retries with the given delays until `is_ready` returns `true`.
-///
-/// When `rg_check` is provided, uses ripgrep as a fast negative filter after the
-/// first empty result: if the symbol doesn't appear in any `.py` file, bail
-/// immediately instead of retrying.
-async fn with_warmup<T, F, Fut>(
-    description: &str,
-    delays: &[u64],
-    is_ready: impl Fn(&T) -> bool,
-    mut operation: F,
-    rg_check: Option<RgCheck<'_>>,
-) -> Result<T>
-where
-    F: FnMut() -> Fut,
-    Fut: std::future::Future<Output = Result<T>>,
-{
-    let result = operation().await?;
-    if is_ready(&result) {
-        return Ok(result);
-    }
-
-    // After the first empty result, check if the symbol exists in the workspace
-    // using ripgrep. If rg confirms it doesn't exist, skip all retries.
-    // Run in spawn_blocking to avoid blocking the tokio runtime.
-    if let Some(ref check) = rg_check {
-        let symbol = check.symbol.to_owned();
-        let workspace = check.workspace_root.to_owned();
-        let exists = tokio::task::spawn_blocking(move || {
-            crate::ripgrep::symbol_might_exist_in_workspace(&symbol, &workspace)
-        })
-        .await
-        .unwrap_or(true); // If spawn_blocking panics, conservatively continue retries
-
-        if !exists {
-            tracing::debug!(
-                "{description}: rg confirms symbol '{}' not found, skipping retries",
-                check.symbol
-            );
-            return Ok(result);
+/// Whether the queries of each label are adjacent in `labels`, so entries
+/// merged by label can be sent in query order.
+fn labels_adjacent(labels: &[String]) -> bool {
+    let mut seen = HashSet::new();
+    labels
+        .iter()
+        .enumerate()
+        .all(|(i, label)| (i > 0 && labels[i - 1] == *label) || seen.insert(label.as_str()))
+}
+
+/// The next of `updates`, skipping over any it fell behind on; `None` once
+/// there are no more, or without `updates`.
+async fn next_update(
+    updates: Option<&mut broadcast::Receiver<WorkProgress>>,
+) -> Option<WorkProgress> {
+    let updates = updates?;
+    loop {
+        match updates.recv().await {
+            Ok(update) => return Some(update),
+            // Missed updates are superseded by the next one.
+            Err(broadcast::error::RecvError::Lagged(_)) => {}
+            Err(broadcast::error::RecvError::Closed) => return None,
         }
     }
+}
 
-    for delay_ms in delays {
-        tracing::debug!("{description} not ready, retrying in {delay_ms}ms...");
-        tokio::time::sleep(Duration::from_millis(*delay_ms)).await;
-        let result = operation().await?;
-        if is_ready(&result) {
-            return Ok(result);
+/// The next of `wire` messages, skipping over any it fell behind on; `None`
+/// once there are no more, or without `wire`.
+async fn next_wire_message(
+    wire: Option<&mut broadcast::Receiver<WireMessage>>,
+) -> Option<WireMessage> {
+    let wire = wire?;
+    loop {
+        match wire.recv().await {
+            Ok(message) => return Some(message),
+            Err(broadcast::error::RecvError::Lagged(missed)) => {
+                tracing::debug!("Trace fell behind by {missed} messages");
+            }
+            Err(broadcast::error::RecvError::Closed) => return None,
         }
     }
+}
+
+/// What a request gets notifications about while it's handled.
+struct Feeds {
+    /// Progress updates of its workspace, if it asked for `progress`
+    updates: Option<broadcast::Receiver<WorkProgress>>,
+    /// Parts of its result, if it asked for `stream`
+    parts: Option<mpsc::UnboundedReceiver<Value>>,
+    /// Messages with its workspace's server, if it asked for `trace`
+    wire: Option<broadcast::Receiver<WireMessage>>,
+}
+
+impl Feeds {
+    const fn any(&self) -> bool {
+        self.updates.is_some() || self.parts.is_some() || self.wire.is_some()
+    }
+}
 
-    tracing::debug!("{description} still not ready after retries");
-    operation().await
+/// The next of `parts`; `None` once there are no more, or without `parts`.
+async fn next_part(parts: Option<&mut mpsc::UnboundedReceiver<Value>>) -> Option<Value> {
+    parts?.recv().await
 }
 
-/// Send a framed error response to the client.
-async fn send_error_response<W: AsyncWrite + Unpin>(
+/// Where a handler sends the parts of its result it has ready early, when
+/// its request asked for `stream`; the response then holds the rest.
+#[derive(Default)]
+struct Partials {
+    sender: Option<mpsc::UnboundedSender<Value>>,
+    /// Set once a part went out.
+    sent: AtomicBool,
+}
+
+impl Partials {
+    /// Partials for a request, with the receiving end of its parts if it
+    /// asked for `stream`.
+    fn channel(stream: bool) -> (Self, Option<mpsc::UnboundedReceiver<Value>>) {
+        if !stream {
+            return (Self::default(), None);
+        }
+        let (sender, parts) = mpsc::unbounded_channel();
+        (Self { sender: Some(sender), sent: AtomicBool::new(false) }, Some(parts))
+    }
+
+    /// Whether the request asked for its result in parts.
+    const fn enabled(&self) -> bool {
+        self.sender.is_some()
+    }
+
+    /// Whether any part went out.
+    fn sent(&self) -> bool {
+        self.sent.load(Ordering::SeqCst)
+    }
+
+    /// Send `part` of the result ahead of the response.
+    fn send<T: serde::Serialize>(&self, part: &T) -> Result<()> {
+        if let Some(sender) = &self.sender {
+            // The connection is gone if nobody receives; the response fails too.
+            let _ = sender.send(serde_json::to_value(part)?);
+            self.sent.store(true, Ordering::SeqCst);
+        }
+        Ok(())
+    }
+}
+
+/// Write a response or notification with Content-Length framing.
+async fn write_frame<W: AsyncWrite + Unpin, T: serde::Serialize>(
     writer: &mut W,
-    error: DaemonError,
+    message: &T,
 ) -> Result<()> {
-    let error_response = DaemonResponse::error(0, error);
-    let response_json = serde_json::to_string(&error_response)?;
-    let framed = format!("Content-Length: {}\r\n\r\n{response_json}", response_json.len());
+    let json = serde_json::to_string(message).context("Failed to serialize message")?;
+    let framed = format!("Content-Length: {}\r\n\r\n{json}", json.len());
     writer.write_all(framed.as_bytes()).await?;
     writer.flush().await?;
     Ok(())
 }
 
+/// Whether a process with id `pid` exists, whether or not it is ours to signal.
+fn process_exists(pid: u32) -> bool {
+    let Ok(pid) = libc::pid_t::try_from(pid) else {
+        return false;
+    };
+    // SAFETY: signal 0 only checks that `pid` exists and may be signalled;
+    // nothing is sent.
+    #[allow(unsafe_code)]
+    let result = unsafe { libc::kill(pid, 0) };
+    result == 0 || std::io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::workspace::filter::PathFilter;
+
+    #[test]
+    fn test_process_exists() {
+        assert!(process_exists(std::process::id()));
+        let mut child = std::process::Command::new("true").spawn().unwrap();
+        let pid = child.id();
+        child.wait().unwrap();
+        assert!(!process_exists(pid), "reaped child");
+    }
 
     #[test]
     fn test_get_socket_path() {
@@ -1014,6 +2238,25 @@ mod tests {
         assert!(value["uptime"].as_u64().unwrap() < 5);
     }
 
+    #[tokio::test]
+    async fn test_rejects_requests_from_other_protocol_versions() {
+        let server = DaemonServer::new(PathBuf::from("/tmp/test-ty-find-protocol.sock"));
+
+        let mut request = DaemonRequest::with_id(7, Method::Definition, serde_json::json!({}));
+        request.protocol = 0;
+        let response = server.handle_request(request, &Partials::default()).await;
+        let error = response.error.expect("mismatched protocol should be rejected");
+        assert_eq!(error.code, -32006);
+        assert!(error.message.contains("tyf daemon restart"), "unexpected: {}", error.message);
+        assert_eq!(response.protocol, PROTOCOL_VERSION);
+
+        // Ping still answers, reporting the daemon's protocol for the handshake
+        let mut ping = DaemonRequest::with_id(8, Method::Ping, serde_json::json!({}));
+        ping.protocol = 0;
+        let response = server.handle_request(ping, &Partials::default()).await;
+        assert_eq!(response.result.expect("ping result")["protocol"], PROTOCOL_VERSION);
+    }
+
     #[tokio::test]
     async fn test_ping_includes_transport_info() {
         let socket_path = PathBuf::from("/tmp/test-ty-find-transport.sock");
@@ -1028,298 +2271,126 @@ mod tests {
         assert!(value["tcp_port"].is_number());
     }
 
-    #[test]
-    fn test_find_symbol_recursive_top_level() {
-        use crate::lsp::protocol::{DocumentSymbol, Position, Range, SymbolKind};
-
-        let range = Range {
-            start: Position { line: 0, character: 0 },
-            end: Position { line: 5, character: 0 },
-        };
-        let symbols = vec![DocumentSymbol {
-            name: "Animal".to_string(),
-            detail: None,
-            kind: SymbolKind::Class,
-            tags: None,
-            deprecated: None,
-            range: range.clone(),
-            selection_range: range,
-            children: None,
-        }];
-
-        let found = DaemonServer::find_symbol_recursive(&symbols, "Animal");
-        assert!(found.is_some());
-        assert_eq!(found.unwrap().name, "Animal");
-
-        let not_found = DaemonServer::find_symbol_recursive(&symbols, "Dog");
-        assert!(not_found.is_none());
+    #[tokio::test]
+    async fn test_stdio_server_reports_no_transport() {
+        let server = DaemonServer::stdio();
+        let value = server.handle_ping(serde_json::json!({})).await.expect("ping should succeed");
+        assert!(value["socket_path"].is_null());
+        assert!(value["tcp_port"].is_null());
     }
 
     #[test]
-    fn test_find_symbol_recursive_nested() {
-        use crate::lsp::protocol::{DocumentSymbol, Position, Range, SymbolKind};
+    fn test_count_references_merges_labels_per_file() {
+        use crate::lsp::protocol::{Position, Range};
 
-        let range = Range {
-            start: Position { line: 0, character: 0 },
-            end: Position { line: 20, character: 0 },
-        };
-        let inner_range = Range {
-            start: Position { line: 10, character: 4 },
-            end: Position { line: 15, character: 0 },
+        let loc = |uri: &str, line: u32| {
+            let pos = Position { line, character: 0 };
+            Location { uri: uri.to_string(), range: Range { start: pos.clone(), end: pos } }
         };
-
-        let nested_class = DocumentSymbol {
-            name: "InnerWidget".to_string(),
-            detail: None,
-            kind: SymbolKind::Class,
-            tags: None,
-            deprecated: None,
-            range: inner_range.clone(),
-            selection_range: inner_range,
-            children: None,
-        };
-
-        let outer_class = DocumentSymbol {
-            name: "OuterPanel".to_string(),
-            detail: None,
-            kind: SymbolKind::Class,
-            tags: None,
-            deprecated: None,
-            range: range.clone(),
-            selection_range: range,
-            children: Some(vec![nested_class]),
+        let entry = |label: &str, locations| BatchReferencesEntry {
+            label: label.to_string(),
+            locations,
+            files: Vec::new(),
+            remaining: 0,
         };
-
-        let symbols = vec![outer_class];
-
-        // Should find the nested class
-        let found = DaemonServer::find_symbol_recursive(&symbols, "InnerWidget");
-        assert!(found.is_some());
-        assert_eq!(found.unwrap().name, "InnerWidget");
-
-        // Should still find the outer class
-        let found = DaemonServer::find_symbol_recursive(&symbols, "OuterPanel");
-        assert!(found.is_some());
-    }
-
-    #[test]
-    fn test_extract_member_signature_method() {
-        use crate::lsp::protocol::{HoverContents, MarkupContent, MarkupKind};
-
-        let contents = HoverContents::Markup(MarkupContent {
-            kind: MarkupKind::Markdown,
-            value: "```python\ndef speak(self) -> str\n```".to_string(),
-        });
-        let sig = DaemonServer::extract_member_signature(&contents, "speak");
-        assert_eq!(sig, "speak(self) -> str");
-    }
-
-    #[test]
-    fn test_extract_member_signature_property() {
-        use crate::lsp::protocol::{HoverContents, MarkupContent, MarkupKind};
-
-        let contents = HoverContents::Markup(MarkupContent {
-            kind: MarkupKind::Markdown,
-            value: "```python\n(property) name: str\n```".to_string(),
-        });
-        let sig = DaemonServer::extract_member_signature(&contents, "name");
-        assert_eq!(sig, "name: str");
-    }
-
-    #[test]
-    fn test_extract_member_signature_with_docstring() {
-        use crate::lsp::protocol::{HoverContents, MarkupContent, MarkupKind};
-
-        let contents = HoverContents::Markup(MarkupContent {
-            kind: MarkupKind::Markdown,
-            value: "```python\ndef describe(self) -> str\n```\n---\nDescribe the animal."
-                .to_string(),
-        });
-        let sig = DaemonServer::extract_member_signature(&contents, "describe");
-        assert_eq!(sig, "describe(self) -> str");
-        assert!(!sig.contains("Describe"));
-    }
-
-    #[test]
-    fn test_extract_member_signature_class_variable() {
-        use crate::lsp::protocol::{HoverContents, MarkupContent, MarkupKind};
-
-        let contents = HoverContents::Markup(MarkupContent {
-            kind: MarkupKind::Markdown,
-            value: "```python\nMAX_LEGS: int\n```".to_string(),
-        });
-        let sig = DaemonServer::extract_member_signature(&contents, "MAX_LEGS");
-        assert_eq!(sig, "MAX_LEGS: int");
-    }
-
-    #[test]
-    fn test_extract_member_signature_scalar() {
-        use crate::lsp::protocol::HoverContents;
-
-        let contents = HoverContents::Scalar("int".to_string());
-        let sig = DaemonServer::extract_member_signature(&contents, "count");
-        assert_eq!(sig, "count: int");
-    }
-
-    #[test]
-    fn test_extract_member_signature_multiline() {
-        use crate::lsp::protocol::{HoverContents, MarkupContent, MarkupKind};
-
-        let contents = HoverContents::Markup(MarkupContent {
-            kind: MarkupKind::Markdown,
-            value: "```python\ndef assist(\n    self,\n    task: str,\n    duration_minutes: int = 30\n) -> str\n```".to_string(),
-        });
-        let sig = DaemonServer::extract_member_signature(&contents, "assist");
-        assert_eq!(sig, "assist(self, task: str, duration_minutes: int = 30) -> str");
-    }
-
-    #[test]
-    fn test_extract_member_signature_bare_type() {
-        use crate::lsp::protocol::{HoverContents, MarkupContent, MarkupKind};
-
-        let contents = HoverContents::Markup(MarkupContent {
-            kind: MarkupKind::Markdown,
-            value: "```python\ndict[str, ServiceDog]\n```".to_string(),
-        });
-        let sig = DaemonServer::extract_member_signature(&contents, "registry");
-        assert_eq!(sig, "registry: dict[str, ServiceDog]");
-    }
-
-    #[test]
-    fn test_extract_member_signature_bare_property() {
-        use crate::lsp::protocol::{HoverContents, MarkupContent, MarkupKind};
-
-        let contents = HoverContents::Markup(MarkupContent {
-            kind: MarkupKind::Markdown,
-            value: "```python\nproperty\n```".to_string(),
-        });
-        let sig = DaemonServer::extract_member_signature(&contents, "is_certified");
-        assert_eq!(sig, "is_certified: property");
-    }
-
-    #[test]
-    fn test_extract_member_signature_xml_fence() {
-        use crate::lsp::protocol::{HoverContents, MarkupContent, MarkupKind};
-
-        let contents = HoverContents::Markup(MarkupContent {
-            kind: MarkupKind::Markdown,
-            value: "```xml\n<element>text</element>\n```".to_string(),
-        });
-        let sig = DaemonServer::extract_member_signature(&contents, "config");
-        // The XML content doesn't start with def/class, so it should be treated as bare type
-        assert!(sig.contains("config"));
-    }
-
-    #[test]
-    fn test_extract_member_signature_text_fence() {
-        use crate::lsp::protocol::{HoverContents, MarkupContent, MarkupKind};
-
-        let contents = HoverContents::Markup(MarkupContent {
-            kind: MarkupKind::Markdown,
-            value: "```text\nstr | None\n```".to_string(),
-        });
-        let sig = DaemonServer::extract_member_signature(&contents, "value");
-        assert_eq!(sig, "value: str | None");
-    }
-
-    #[test]
-    fn test_extract_member_signature_method_prefix() {
-        use crate::lsp::protocol::{HoverContents, MarkupContent, MarkupKind};
-
-        let contents = HoverContents::Markup(MarkupContent {
-            kind: MarkupKind::Markdown,
-            value: "```python\n(method) def foo(self) -> int\n```".to_string(),
-        });
-        let sig = DaemonServer::extract_member_signature(&contents, "foo");
-        assert_eq!(sig, "foo(self) -> int");
-    }
-
-    #[test]
-    fn test_extract_member_signature_array_hover() {
-        use crate::lsp::protocol::{HoverContents, MarkedString, MarkedStringOrString};
-
-        let contents = HoverContents::Array(vec![
-            MarkedStringOrString::MarkedString(MarkedString {
-                language: "python".to_string(),
-                value: "def greet(self) -> str".to_string(),
-            }),
-            MarkedStringOrString::String("A greeting method".to_string()),
+        let counted = DaemonServer::count_references(vec![
+            entry("save", vec![loc("file:///a.py", 1), loc("file:///b.py", 4)]),
+            entry("User", vec![]),
+            // A second occurrence of the same name finds the same references again
+            entry("save", vec![loc("file:///b.py", 4), loc("file:///b.py", 9)]),
         ]);
-        let sig = DaemonServer::extract_member_signature(&contents, "greet");
-        // Array hover concatenates all entries; the function signature is extracted and
-        // the plain-text description is appended after it.
-        assert_eq!(sig, "greet(self) -> str A greeting method");
+        assert_eq!(counted.len(), 2);
+        assert_eq!(counted[0].label, "save");
+        assert!(counted[0].locations.is_empty());
+        let files: Vec<(&str, usize)> =
+            counted[0].files.iter().map(|f| (f.uri.as_str(), f.count)).collect();
+        assert_eq!(files, [("file:///b.py", 2), ("file:///a.py", 1)]);
+        assert!(counted[1].files.is_empty());
     }
 
     #[test]
-    fn test_collapse_signature_single_line() {
-        let sig = "def foo(self, x: int) -> str";
-        let result = DaemonServer::collapse_signature(sig);
-        assert_eq!(result, sig);
-    }
+    fn test_page_references_merges_labels_and_sorts() {
+        use crate::lsp::protocol::{Position, Range};
 
-    #[test]
-    fn test_collapse_signature_multiline_complex() {
-        let sig = "def process(\n    self,\n    data: list[dict[str, Any]],\n    timeout: int = 30,\n    retries: int = 3\n) -> Result[str, Error]";
-        let result = DaemonServer::collapse_signature(sig);
-        assert_eq!(
-            result,
-            "def process(self, data: list[dict[str, Any]], timeout: int = 30, retries: int = 3) -> Result[str, Error]"
-        );
+        let loc = |uri: &str, line: u32| {
+            let pos = Position { line, character: 0 };
+            Location { uri: uri.to_string(), range: Range { start: pos.clone(), end: pos } }
+        };
+        let entry = |label: &str, locations| BatchReferencesEntry {
+            label: label.to_string(),
+            locations,
+            files: Vec::new(),
+            remaining: 0,
+        };
+        let entries = vec![
+            entry("save", vec![loc("file:///b.py", 4), loc("file:///a.py", 7)]),
+            entry("save", vec![loc("file:///b.py", 4), loc("file:///a.py", 1)]),
+            entry("User", vec![loc("file:///c.py", 2)]),
+        ];
+        let paged = DaemonServer::page_references(entries, ResultPage::new(1, Some(1)));
+        assert_eq!(paged.len(), 2);
+        let lines: Vec<(&str, u32)> =
+            paged[0].locations.iter().map(|l| (l.uri.as_str(), l.range.start.line)).collect();
+        assert_eq!(lines, [("file:///a.py", 7)]);
+        assert_eq!(paged[0].remaining, 1);
+        assert!(paged[1].locations.is_empty());
+        assert_eq!(paged[1].remaining, 0);
     }
 
     #[test]
-    fn test_find_symbol_recursive_empty_tree() {
-        let symbols: Vec<crate::lsp::protocol::DocumentSymbol> = vec![];
-        let found = DaemonServer::find_symbol_recursive(&symbols, "anything");
-        assert!(found.is_none());
+    fn test_labels_adjacent() {
+        let labels = |names: &[&str]| names.iter().map(ToString::to_string).collect::<Vec<_>>();
+        assert!(labels_adjacent(&labels(&["a", "a", "b", "c", "c"])));
+        assert!(!labels_adjacent(&labels(&["a", "b", "a"])));
+        assert!(labels_adjacent(&[]));
     }
 
     #[test]
-    fn test_find_symbol_recursive_deeply_nested() {
-        use crate::lsp::protocol::{DocumentSymbol, Position, Range, SymbolKind};
-
-        let range = Range {
-            start: Position { line: 0, character: 0 },
-            end: Position { line: 30, character: 0 },
-        };
+    fn test_filter_symbols_by_kind_and_path() {
+        use crate::lsp::protocol::{Position, Range, SymbolInformation, SymbolKind};
 
-        let deepest = DocumentSymbol {
-            name: "deep_method".to_string(),
-            detail: None,
-            kind: SymbolKind::Method,
+        let symbol = |name: &str, kind: SymbolKind, uri: &str| SymbolInformation {
+            name: name.to_string(),
+            kind,
             tags: None,
             deprecated: None,
-            range: range.clone(),
-            selection_range: range.clone(),
-            children: None,
+            location: Location {
+                uri: uri.to_string(),
+                range: Range {
+                    start: Position { line: 0, character: 0 },
+                    end: Position { line: 0, character: 1 },
+                },
+            },
+            container_name: None,
         };
-
-        let middle = DocumentSymbol {
-            name: "InnerClass".to_string(),
-            detail: None,
-            kind: SymbolKind::Class,
-            tags: None,
-            deprecated: None,
-            range: range.clone(),
-            selection_range: range.clone(),
-            children: Some(vec![deepest]),
+        let symbols = vec![
+            symbol("User", SymbolKind::Class, "file:///proj/app/models.py"),
+            symbol("user_id", SymbolKind::Variable, "file:///proj/app/models.py"),
+            symbol("UserView", SymbolKind::Class, "file:///proj/web/views.py"),
+            symbol("Users", SymbolKind::Class, "file:///proj/application.py"),
+        ];
+        let params = WorkspaceSymbolsParams {
+            workspace: PathBuf::from("/proj"),
+            query: "user".to_string(),
+            page: ResultPage::default(),
+            exact_name: None,
+            container_name: None,
+            kinds: vec![SymbolKind::Class, SymbolKind::Function],
+            path_prefix: Some(PathBuf::from("/proj/app")),
+            paths: PathFilter::default(),
         };
 
-        let outer = DocumentSymbol {
-            name: "OuterClass".to_string(),
-            detail: None,
-            kind: SymbolKind::Class,
-            tags: None,
-            deprecated: None,
-            range: range.clone(),
-            selection_range: range,
-            children: Some(vec![middle]),
-        };
+        let kept = DaemonServer::filter_symbols(symbols.clone(), &params);
+        let names: Vec<&str> = kept.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, ["User"]);
 
-        let symbols = vec![outer];
-        let found = DaemonServer::find_symbol_recursive(&symbols, "deep_method");
-        assert!(found.is_some());
-        assert_eq!(found.unwrap().name, "deep_method");
+        let params = WorkspaceSymbolsParams {
+            path_prefix: None,
+            paths: PathFilter::new(Vec::new(), vec!["web/".to_string()], false),
+            ..params
+        };
+        let kept = DaemonServer::filter_symbols(symbols, &params);
+        let names: Vec<&str> = kept.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, ["User", "Users"]);
     }
 }
@@ -0,0 +1,120 @@
+//! Request metrics the daemon keeps for `tyf daemon stats`.
+//!
+//! Every request is counted under its method together with how long it took
+//! and whether it failed. Latencies go into fixed buckets rather than being
+//! stored, so the daemon's memory use doesn't grow with its uptime; the
+//! percentiles `tyf daemon stats` prints are the upper bounds of the bucket
+//! they fall in.
+
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::daemon::protocol::{LatencyBucket, Method, MethodStats};
+
+/// Upper bounds of the latency buckets, in milliseconds; slower requests land
+/// in a final unbounded bucket.
+pub const BUCKET_BOUNDS_MS: [u64; 10] = [1, 5, 10, 25, 50, 100, 250, 500, 1000, 5000];
+
+#[derive(Default)]
+struct Counters {
+    calls: u64,
+    errors: u64,
+    total_ms: u64,
+    max_ms: u64,
+    /// One count per bound in [`BUCKET_BOUNDS_MS`], plus the unbounded bucket.
+    buckets: [u64; BUCKET_BOUNDS_MS.len() + 1],
+}
+
+/// Per-method request counters and latency histograms.
+#[derive(Default)]
+pub struct Metrics {
+    methods: Mutex<HashMap<Method, Counters>>,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Count one `method` request that took `elapsed`.
+    pub fn record(&self, method: Method, elapsed: Duration, ok: bool) {
+        let ms = u64::try_from(elapsed.as_millis()).unwrap_or(u64::MAX);
+        let bucket = BUCKET_BOUNDS_MS.iter().position(|&bound| ms <= bound);
+        let mut methods = self.methods.lock().expect("metrics mutex poisoned");
+        let counters = methods.entry(method).or_default();
+        counters.calls += 1;
+        counters.errors += u64::from(!ok);
+        counters.total_ms = counters.total_ms.saturating_add(ms);
+        counters.max_ms = counters.max_ms.max(ms);
+        counters.buckets[bucket.unwrap_or(BUCKET_BOUNDS_MS.len())] += 1;
+    }
+
+    /// Counters of every method called so far, busiest first.
+    pub fn snapshot(&self) -> Vec<MethodStats> {
+        let methods = self.methods.lock().expect("metrics mutex poisoned");
+        let mut stats: Vec<MethodStats> = methods
+            .iter()
+            .map(|(method, counters)| MethodStats {
+                method: method.as_str().to_string(),
+                calls: counters.calls,
+                errors: counters.errors,
+                total_ms: counters.total_ms,
+                max_ms: counters.max_ms,
+                buckets: counters
+                    .buckets
+                    .iter()
+                    .enumerate()
+                    .map(|(i, &count)| LatencyBucket {
+                        le_ms: BUCKET_BOUNDS_MS.get(i).copied(),
+                        count,
+                    })
+                    .collect(),
+            })
+            .collect();
+        stats.sort_by(|a, b| b.calls.cmp(&a.calls).then_with(|| a.method.cmp(&b.method)));
+        stats
+    }
+}
+
+/// Resident memory of process `pid` in bytes, where the OS lets us read it
+/// cheaply (Linux `/proc`).
+pub fn process_rss(pid: u32) -> Option<u64> {
+    let status =
+        std::fs::read_to_string(Path::new("/proc").join(pid.to_string()).join("status")).ok()?;
+    let line = status.lines().find_map(|line| line.strip_prefix("VmRSS:"))?;
+    let kib: u64 = line.trim().strip_suffix("kB")?.trim().parse().ok()?;
+    Some(kib * 1024)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn records_calls_errors_and_buckets() {
+        let metrics = Metrics::new();
+        metrics.record(Method::Hover, Duration::from_millis(3), true);
+        metrics.record(Method::Hover, Duration::from_millis(40), false);
+        metrics.record(Method::Hover, Duration::from_secs(9), true);
+        metrics.record(Method::Ping, Duration::ZERO, true);
+
+        let stats = metrics.snapshot();
+        assert_eq!(stats.len(), 2);
+        let hover = &stats[0];
+        assert_eq!((hover.method.as_str(), hover.calls, hover.errors), ("hover", 3, 1));
+        assert_eq!(hover.max_ms, 9000);
+        let counts: Vec<(Option<u64>, u64)> =
+            hover.buckets.iter().filter(|b| b.count > 0).map(|b| (b.le_ms, b.count)).collect();
+        assert_eq!(counts, [(Some(5), 1), (Some(50), 1), (None, 1)]);
+        assert_eq!(hover.percentile(0.5).map(|b| b.le_ms), Some(Some(50)));
+        assert_eq!(hover.percentile(0.95).map(|b| b.le_ms), Some(None));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn reads_own_rss() {
+        assert!(process_rss(std::process::id()).is_some_and(|bytes| bytes > 0));
+    }
+}
@@ -7,6 +7,8 @@ use std::time::Instant;
 
 use anyhow::{Context, Result};
 
+use crate::lsp::uri::path_to_uri;
+
 /// A debug log writer that captures the full request lifecycle to a temp file.
 ///
 /// When `--debug` is passed, a `DebugLog` is created and threaded through the
@@ -19,7 +21,7 @@ pub struct DebugLog {
 }
 
 impl DebugLog {
-    /// Create a new debug log file in `/tmp/tyf-debug-{timestamp}-{pid}.log`.
+    /// Create a new debug log file `tyf-debug-{timestamp}-{pid}.log` in the system temp dir.
     pub fn create() -> Result<Self> {
         use std::sync::atomic::{AtomicU64, Ordering};
         static COUNTER: AtomicU64 = AtomicU64::new(0);
@@ -31,7 +33,7 @@ impl DebugLog {
         let pid = std::process::id();
         let seq = COUNTER.fetch_add(1, Ordering::Relaxed);
 
-        let path = PathBuf::from(format!("/tmp/tyf-debug-{timestamp}-{pid}-{seq}.log"));
+        let path = std::env::temp_dir().join(format!("tyf-debug-{timestamp}-{pid}-{seq}.log"));
         let file = File::create(&path)
             .with_context(|| format!("Failed to create debug log at {}", path.display()))?;
         let writer = Mutex::new(BufWriter::new(file));
@@ -86,6 +88,11 @@ impl DebugLog {
         self.write_raw(&format!("           Detection method: {detection_method}\n"));
     }
 
+    /// Log the ty project configuration picked up for the workspace.
+    pub fn log_ty_config(&self, summary: &str) {
+        self.write_line(&format!("ty configuration: {summary}"));
+    }
+
     /// Log daemon connection details.
     pub fn log_daemon_connection(&self, socket_path: &str, connected: bool, error: Option<&str>) {
         self.write_line("Daemon connection:");
@@ -194,8 +201,11 @@ impl DebugLog {
         column: u32,
         lsp_method: &str,
     ) {
-        let file_uri =
-            if file.starts_with("file://") { file.to_string() } else { format!("file://{file}") };
+        let file_uri = if file.starts_with("file://") {
+            file.to_string()
+        } else {
+            path_to_uri(Path::new(file))
+        };
 
         let init_params = serde_json::json!({
             "jsonrpc": "2.0",
@@ -203,7 +213,7 @@ impl DebugLog {
             "method": "initialize",
             "params": {
                 "processId": null,
-                "rootUri": format!("file://{}", workspace_root.display()),
+                "rootUri": path_to_uri(workspace_root),
                 "capabilities": {}
             }
         });
@@ -0,0 +1,256 @@
+//! Checks and report of `tyf doctor`.
+//!
+//! The checks that talk to ty or the daemon live in
+//! `commands::handle_doctor_command`; this module holds the report and the
+//! checks that only look at the filesystem.
+
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+use crate::cli::output::TYF_PROVIDERS;
+use crate::lsp::protocol::InitializeResult;
+use crate::workspace::detection::WorkspaceDetector;
+use crate::workspace::ty_config::TyConfig;
+
+/// The file the hover probe asks about, and the position of `greet` in its
+/// last line (0-based).
+pub const PROBE_SOURCE: &str = "\
+def greet(name: str) -> str:
+    return \"Hello, \" + name
+
+
+greet(\"doctor\")
+";
+pub const PROBE_POSITION: (u32, u32) = (4, 0);
+
+/// Outcome of one check.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    Ok,
+    /// Works, but something is likely to surprise the user.
+    Warn,
+    Fail,
+    /// Not run because an earlier check failed.
+    Skip,
+}
+
+impl Status {
+    pub const fn label(self) -> &'static str {
+        match self {
+            Self::Ok => "ok",
+            Self::Warn => "warn",
+            Self::Fail => "FAIL",
+            Self::Skip => "skip",
+        }
+    }
+}
+
+/// One line of the `tyf doctor` report.
+#[derive(Debug, Clone, Serialize)]
+pub struct Check {
+    pub name: &'static str,
+    pub status: Status,
+    pub detail: String,
+    /// What to do about a failure or warning.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fix: Option<String>,
+}
+
+impl Check {
+    pub fn ok(name: &'static str, detail: impl Into<String>) -> Self {
+        Self { name, status: Status::Ok, detail: detail.into(), fix: None }
+    }
+
+    pub fn warn(name: &'static str, detail: impl Into<String>, fix: impl Into<String>) -> Self {
+        Self { name, status: Status::Warn, detail: detail.into(), fix: Some(fix.into()) }
+    }
+
+    pub fn fail(name: &'static str, detail: impl Into<String>, fix: impl Into<String>) -> Self {
+        Self { name, status: Status::Fail, detail: detail.into(), fix: Some(fix.into()) }
+    }
+
+    pub fn skip(name: &'static str, detail: impl Into<String>) -> Self {
+        Self { name, status: Status::Skip, detail: detail.into(), fix: None }
+    }
+}
+
+/// Everything `tyf doctor` reports.
+#[derive(Debug, Clone, Serialize)]
+pub struct DoctorReport {
+    pub workspace: String,
+    pub checks: Vec<Check>,
+}
+
+impl DoctorReport {
+    /// Number of failed checks.
+    pub fn failures(&self) -> usize {
+        self.checks.iter().filter(|c| c.status == Status::Fail).count()
+    }
+}
+
+/// How the workspace root was found, and whether its ty settings load.
+pub fn check_workspace(workspace_root: &Path) -> Check {
+    const NAME: &str = "workspace";
+    let detection = WorkspaceDetector::describe_detection(workspace_root);
+    if let Err(e) = TyConfig::load(workspace_root) {
+        return Check::warn(
+            NAME,
+            format!("{} ({detection}); its ty settings are invalid: {e:#}", workspace_root.display()),
+            "Fix [tool.ty] in pyproject.toml or ty.toml; until then tyf searches with ty's defaults",
+        );
+    }
+    if WorkspaceDetector::find_workspace_root(workspace_root).as_deref() != Some(workspace_root) {
+        return Check::warn(
+            NAME,
+            format!("{} (no project markers found)", workspace_root.display()),
+            "Run tyf from the project root, or pass --workspace <PATH>",
+        );
+    }
+    match WorkspaceDetector::find_environment(workspace_root) {
+        Some(environment) => Check::ok(
+            NAME,
+            format!("{} ({detection}); environment {environment}", workspace_root.display()),
+        ),
+        None => Check::ok(NAME, format!("{} ({detection})", workspace_root.display())),
+    }
+}
+
+/// Whether the daemon can create `socket_path`: its directory exists and is
+/// writable by this user.
+pub fn check_socket(socket_path: &Path) -> Check {
+    const NAME: &str = "socket";
+    let dir = socket_path.parent().unwrap_or_else(|| Path::new("/"));
+    let probe = dir.join(format!(".ty-find-doctor-{}", std::process::id()));
+    match std::fs::OpenOptions::new().write(true).create_new(true).open(&probe) {
+        Ok(_) => {
+            let _ = std::fs::remove_file(&probe);
+            let state = if socket_path.exists() { "exists" } else { "not created yet" };
+            Check::ok(NAME, format!("{} ({state})", socket_path.display()))
+        }
+        Err(e) => Check::fail(
+            NAME,
+            format!("can't create files in {}: {e}", dir.display()),
+            format!("Make {} writable for your user; the daemon listens there", dir.display()),
+        ),
+    }
+}
+
+/// Whether the server advertised every LSP feature some tyf command needs,
+/// in the capabilities it answered `initialize` with.
+pub fn check_capabilities(info: &InitializeResult) -> Check {
+    const NAME: &str = "capabilities";
+    let missing: Vec<String> = TYF_PROVIDERS
+        .iter()
+        .filter(|(provider, _)| !info.supports(provider))
+        .map(|(provider, commands)| format!("{provider} ({commands})"))
+        .collect();
+    if missing.is_empty() {
+        return Check::ok(
+            NAME,
+            format!(
+                "{} supports all {} features tyf uses",
+                info.server_label(),
+                TYF_PROVIDERS.len()
+            ),
+        );
+    }
+    let fix = if info.is_ty() {
+        "Upgrade ty for the commands listed; `tyf ty-info` shows what it supports"
+    } else {
+        "Use ty (--backend ty) for the commands listed; `tyf ty-info` shows what this server supports"
+    };
+    Check::warn(NAME, format!("{} lacks {}", info.server_label(), missing.join(", ")), fix)
+}
+
+/// A throwaway project holding [`PROBE_SOURCE`], removed on drop.
+pub struct ScratchProject {
+    root: PathBuf,
+}
+
+impl ScratchProject {
+    pub fn create() -> std::io::Result<Self> {
+        let root = std::env::temp_dir().join(format!("ty-find-doctor-{}", std::process::id()));
+        std::fs::create_dir_all(&root)?;
+        let project = Self { root };
+        std::fs::write(project.root.join("pyproject.toml"), "[project]\nname = \"probe\"\n")?;
+        std::fs::write(project.file(), PROBE_SOURCE)?;
+        Ok(project)
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// The file holding [`PROBE_SOURCE`].
+    pub fn file(&self) -> PathBuf {
+        self.root.join("probe.py")
+    }
+}
+
+impl Drop for ScratchProject {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.root);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lsp::protocol::ServerInfo;
+
+    #[test]
+    fn test_check_capabilities() {
+        let all: serde_json::Map<_, _> =
+            TYF_PROVIDERS.iter().map(|(provider, _)| (provider.to_string(), true.into())).collect();
+        let info = InitializeResult { capabilities: all.clone().into(), server_info: None };
+        assert_eq!(check_capabilities(&info).status, Status::Ok);
+
+        let mut some = all;
+        some.remove("referencesProvider");
+        let info = InitializeResult {
+            capabilities: some.into(),
+            server_info: Some(ServerInfo { name: "pylsp".to_string(), version: None }),
+        };
+        let check = check_capabilities(&info);
+        assert_eq!(check.status, Status::Warn);
+        assert_eq!(check.detail, "pylsp lacks referencesProvider (refs, show --references)");
+        assert!(check.fix.unwrap().contains("--backend ty"));
+    }
+
+    #[test]
+    fn test_check_workspace() {
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(check_workspace(dir.path()).status, Status::Warn);
+
+        std::fs::write(dir.path().join("pyproject.toml"), "[project]\nname = \"x\"\n").unwrap();
+        let check = check_workspace(dir.path());
+        assert_eq!(check.status, Status::Ok);
+        assert!(check.detail.contains("found pyproject.toml"), "{}", check.detail);
+
+        std::fs::write(dir.path().join("pyproject.toml"), "[tool.ty.src]\ninclude = 3\n").unwrap();
+        let check = check_workspace(dir.path());
+        assert_eq!(check.status, Status::Warn);
+        assert!(check.detail.contains("invalid"), "{}", check.detail);
+    }
+
+    #[test]
+    fn test_check_socket() {
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(check_socket(&dir.path().join("tyf.sock")).status, Status::Ok);
+        assert_eq!(check_socket(&dir.path().join("missing/tyf.sock")).status, Status::Fail);
+    }
+
+    #[test]
+    fn test_scratch_project_is_removed() {
+        let project = ScratchProject::create().unwrap();
+        let root = project.root().to_path_buf();
+        let source = std::fs::read_to_string(project.file()).unwrap();
+        let (line, column) = PROBE_POSITION;
+        let probed = source.lines().nth(line as usize).unwrap();
+        assert!(probed[column as usize..].starts_with("greet"));
+        drop(project);
+        assert!(!root.exists());
+    }
+}
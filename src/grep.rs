@@ -0,0 +1,136 @@
+//! Text matching and target naming for `tyf grep`: where a literal pattern
+//! occurs in a file, and whether a resolved name is the one asked for.
+
+use std::path::Path;
+
+use crate::lsp::position::{byte_to_column, LSP_UNIT};
+use crate::workspace::navigation::{find_words, is_identifier_char};
+
+/// One occurrence of the pattern in a file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextMatch {
+    /// 0-based line
+    pub line: u32,
+    /// 0-based LSP column of each identifier inside the match, in order.
+    ///
+    /// These are the positions asked for a definition; a match containing no
+    /// identifier can't resolve to anything and is not reported.
+    pub identifiers: Vec<u32>,
+}
+
+/// Every non-overlapping occurrence of `pattern` in `text`.
+///
+/// The pattern is a literal string. Where it starts or ends with an identifier
+/// character, the match must not continue an identifier on that side, so
+/// `save` does not match inside `autosave` or `save_all`.
+pub fn find_text_matches(text: &str, pattern: &str) -> Vec<TextMatch> {
+    let mut matches = Vec::new();
+    if pattern.is_empty() {
+        return matches;
+    }
+    for (idx, line) in text.lines().enumerate() {
+        let Ok(line_no) = u32::try_from(idx) else { break };
+        for start in find_words(line, pattern) {
+            let identifiers = identifier_starts(line, start, start + pattern.len())
+                .map(|byte| byte_to_column(line, byte, LSP_UNIT))
+                .collect::<Vec<_>>();
+            if !identifiers.is_empty() {
+                matches.push(TextMatch { line: line_no, identifiers });
+            }
+        }
+    }
+    matches
+}
+
+/// Byte offsets of identifiers that begin inside `line[start..end]`.
+///
+/// An identifier cut off by the start of the match still counts, from `start`.
+fn identifier_starts(line: &str, start: usize, end: usize) -> impl Iterator<Item = usize> + '_ {
+    let mut previous_is_identifier = false;
+    line[start..end].char_indices().filter_map(move |(offset, c)| {
+        let begins = is_identifier_char(c) && !previous_is_identifier;
+        previous_is_identifier = is_identifier_char(c);
+        // Digits can't start a Python identifier (e.g. the `2` in `x2 = 2`).
+        (begins && !c.is_ascii_digit()).then_some(start + offset)
+    })
+}
+
+/// Dotted module name of `file` relative to the workspace `root`,
+/// e.g. `pkg/models.py` → `pkg.models` and `pkg/__init__.py` → `pkg`.
+pub fn module_name(root: &Path, file: &Path) -> String {
+    let relative = file.strip_prefix(root).unwrap_or(file).with_extension("");
+    let mut parts: Vec<String> = relative
+        .components()
+        .filter_map(|c| match c {
+            std::path::Component::Normal(part) => Some(part.to_string_lossy().into_owned()),
+            _ => None,
+        })
+        .collect();
+    if parts.last().is_some_and(|last| last == "__init__") {
+        parts.pop();
+    }
+    parts.join(".")
+}
+
+/// Whether the fully qualified `qualified` name is what the user meant by `target`.
+///
+/// The target may leave out leading segments: `User.save`, `models.User.save`
+/// and `app.models.User.save` all name `app.models.User.save`. Omitting them
+/// also covers `src/` layouts, whose module names start with `src.`.
+pub fn names_target(qualified: &str, target: &str) -> bool {
+    qualified == target
+        || qualified.strip_suffix(target).is_some_and(|prefix| prefix.ends_with('.'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_matches_respect_identifier_boundaries() {
+        let text = "user.save()\nautosave = save_all\nsave\n";
+        let matches = find_text_matches(text, "save");
+        assert_eq!(
+            matches,
+            vec![
+                TextMatch { line: 0, identifiers: vec![5] },
+                TextMatch { line: 2, identifiers: vec![0] },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_matches_list_every_identifier() {
+        let text = "    return user.save(force=True)\n";
+        let matches = find_text_matches(text, "user.save(");
+        assert_eq!(matches, vec![TextMatch { line: 0, identifiers: vec![11, 16] }]);
+
+        // Punctuation-only matches have nothing to resolve.
+        assert!(find_text_matches(text, "(").is_empty());
+    }
+
+    #[test]
+    fn test_match_columns_are_utf16() {
+        let matches = find_text_matches("s = '🐍'; save()\n", "save");
+        assert_eq!(matches, vec![TextMatch { line: 0, identifiers: vec![10] }]);
+    }
+
+    #[test]
+    fn test_module_name() {
+        let root = PathBuf::from("/ws");
+        assert_eq!(module_name(&root, Path::new("/ws/pkg/models.py")), "pkg.models");
+        assert_eq!(module_name(&root, Path::new("/ws/pkg/__init__.py")), "pkg");
+        assert_eq!(module_name(&root, Path::new("/ws/stubs/api.pyi")), "stubs.api");
+    }
+
+    #[test]
+    fn test_names_target_accepts_suffixes_on_segment_boundaries() {
+        let qualified = "src.app.models.User.save";
+        assert!(names_target(qualified, "User.save"));
+        assert!(names_target(qualified, "app.models.User.save"));
+        assert!(names_target(qualified, qualified));
+        assert!(!names_target(qualified, "ser.save"));
+        assert!(!names_target(qualified, "User"));
+    }
+}
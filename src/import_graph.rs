@@ -0,0 +1,113 @@
+//! Module naming and import resolution for `tyf imports`: maps workspace
+//! files to module names, and imported modules back to files.
+
+use std::collections::{BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
+
+use crate::refactor::imports::ImportedModule;
+
+/// The workspace's modules by dotted name.
+#[derive(Debug, Default)]
+pub struct ModuleIndex {
+    by_name: HashMap<String, PathBuf>,
+    by_path: HashMap<PathBuf, String>,
+}
+
+impl ModuleIndex {
+    /// Index `files` as `(module name, path)` pairs; a later file with the
+    /// same module name doesn't replace an earlier one.
+    pub fn new(files: impl IntoIterator<Item = (String, PathBuf)>) -> Self {
+        let mut index = Self::default();
+        for (name, path) in files {
+            if name.is_empty() || index.by_name.contains_key(&name) {
+                continue;
+            }
+            index.by_path.insert(path.clone(), name.clone());
+            index.by_name.insert(name, path);
+        }
+        index
+    }
+
+    /// The file of the module `name`.
+    pub fn path(&self, name: &str) -> Option<&Path> {
+        self.by_name.get(name).map(PathBuf::as_path)
+    }
+
+    /// The module name of `path`.
+    pub fn name(&self, path: &Path) -> Option<&str> {
+        self.by_path.get(path).map(String::as_str)
+    }
+
+    /// The workspace modules an import depends on; empty when it names
+    /// nothing in the workspace.
+    ///
+    /// `import a.b.c` depends on the deepest of `a.b.c`, `a.b` and `a` that
+    /// exists. `from a import b` depends on `a.b` when that is a module, and
+    /// on `a` for every name that isn't.
+    pub fn resolve(&self, import: &ImportedModule) -> Vec<String> {
+        if import.names.is_empty() {
+            let mut module = import.module.as_str();
+            loop {
+                if self.by_name.contains_key(module) {
+                    return vec![module.to_string()];
+                }
+                match module.rsplit_once('.') {
+                    Some((parent, _)) => module = parent,
+                    None => return Vec::new(),
+                }
+            }
+        }
+        let mut found = BTreeSet::new();
+        for name in &import.names {
+            let submodule = format!("{}.{name}", import.module);
+            if self.by_name.contains_key(&submodule) {
+                found.insert(submodule);
+            } else if self.by_name.contains_key(&import.module) {
+                found.insert(import.module.clone());
+            }
+        }
+        found.into_iter().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn index() -> ModuleIndex {
+        ModuleIndex::new(
+            ["app", "app.models", "app.api.views"]
+                .map(|name| (name.to_string(), PathBuf::from(format!("{name}.py")))),
+        )
+    }
+
+    fn import(module: &str, names: &[&str]) -> ImportedModule {
+        ImportedModule {
+            module: module.to_string(),
+            names: names.iter().map(ToString::to_string).collect(),
+            line: 0,
+            probe: (0, 0),
+        }
+    }
+
+    #[test]
+    fn test_resolves_plain_imports_to_deepest_module() {
+        let index = index();
+        assert_eq!(index.resolve(&import("app.models", &[])), vec!["app.models"]);
+        assert_eq!(index.resolve(&import("app.api.views", &[])), vec!["app.api.views"]);
+        assert_eq!(index.resolve(&import("app.missing.mod", &[])), vec!["app"]);
+        assert!(index.resolve(&import("os.path", &[])).is_empty());
+    }
+
+    #[test]
+    fn test_resolves_from_imports_to_submodules() {
+        let index = index();
+        assert_eq!(
+            index.resolve(&import("app", &["models", "settings"])),
+            vec!["app", "app.models"]
+        );
+        assert_eq!(index.resolve(&import("app.api", &["views"])), vec!["app.api.views"]);
+        assert!(index.resolve(&import("app.api", &["helpers"])).is_empty());
+        assert!(index.resolve(&import("typing", &["Any"])).is_empty());
+    }
+}
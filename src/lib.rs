@@ -0,0 +1,47 @@
+//! Type-aware Python code navigation, powered by ty.
+//!
+//! The library behind the `tyf` command. [`Navigator`] answers definition,
+//! references, hover, symbol and class member queries for one workspace
+//! from a ty language server it starts itself:
+//!
+//! ```no_run
+//! # async fn run() -> anyhow::Result<()> {
+//! let nav = ty_find::Navigator::new("/path/to/project").await?;
+//! for symbol in nav.symbols("OrderService").await? {
+//!     let file = ty_find::lsp::uri::uri_to_path(&symbol.location.uri);
+//!     let start = symbol.location.range.start;
+//!     let hover = nav.hover(file, start.line, start.character).await?;
+//!     println!("{}: {:?}", symbol.name, hover.map(|h| h.contents));
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! # Features
+//!
+//! - `lsp-client` (default): the in-process ty client ([`lsp::client`]) and
+//!   [`Navigator`].
+//! - `daemon` (default, Unix only): the [`daemon`] module, whose
+//!   [`daemon::client::DaemonClient`] talks to the background server `tyf`
+//!   keeps running, sharing its warm ty servers and caches. Implies
+//!   `lsp-client`.
+//! - `cli` (default): builds the `tyf` binary, and pulls in what only it
+//!   uses (clap, terminal colors, the TUI). Implies `daemon`; turn default
+//!   features off to use the library without them.
+//!
+//! With neither, the crate still offers the LSP types in [`lsp::protocol`]
+//! and the workspace helpers in [`workspace`].
+
+pub mod config;
+#[cfg(all(unix, feature = "daemon"))]
+pub mod daemon;
+pub mod debug;
+pub mod lsp;
+#[cfg(feature = "lsp-client")]
+mod navigator;
+#[cfg(feature = "lsp-client")]
+mod ripgrep;
+pub mod workspace;
+
+#[cfg(feature = "lsp-client")]
+pub use navigator::Navigator;
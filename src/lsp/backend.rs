@@ -0,0 +1,332 @@
+//! The language server tyf talks to: ty by default, or another Python LSP
+//! server such as pyright, pylsp or jedi-language-server.
+//!
+//! A backend knows how to find and start its server and what to tell it
+//! during `initialize`. Which requests the server can answer is negotiated
+//! from the capabilities it advertises in return, whatever the backend.
+//!
+//! The backend is picked per workspace: `--backend` (or `TYF_BACKEND`), else
+//! the `backend` setting in the workspace's or user's config, else ty. A
+//! `ty-path` setting or `--ty-path` picks ty at that path, spelled
+//! `ty:<path>` as a backend spec so it reaches the daemon like `--backend`.
+
+use anyhow::{Context, Result};
+use serde_json::Value;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::Arc;
+
+use crate::config::Config;
+use crate::lsp::server::{FoundTy, TyLspServer};
+use crate::workspace::ty_config::TyConfig;
+
+/// Environment variable naming the backend, as `--backend` does.
+pub const BACKEND_ENV: &str = "TYF_BACKEND";
+
+/// The backend used when nothing else is configured.
+pub const DEFAULT_BACKEND: &str = "ty";
+
+/// Prefix of a backend spec that runs ty from a given path, e.g. `ty:/opt/ty/bin/ty`.
+pub const TY_PATH_PREFIX: &str = "ty:";
+
+/// The backend spec for ty at `path`.
+pub fn ty_spec(path: &Path) -> String {
+    format!("{TY_PATH_PREFIX}{}", path.display())
+}
+
+/// A command line that starts a program, e.g. `uvx ty`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServerCommand {
+    pub program: PathBuf,
+    pub args: Vec<String>,
+}
+
+impl ServerCommand {
+    pub fn new(program: impl Into<PathBuf>, args: &[&str]) -> Self {
+        Self { program: program.into(), args: args.iter().map(ToString::to_string).collect() }
+    }
+
+    /// The same command with `args` appended.
+    #[must_use]
+    pub fn with_args(mut self, args: &[&str]) -> Self {
+        self.args.extend(args.iter().map(ToString::to_string));
+        self
+    }
+
+    /// The command line as a user would type it.
+    pub fn label(&self) -> String {
+        std::iter::once(self.program.display().to_string())
+            .chain(self.args.iter().cloned())
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    pub fn build(&self) -> tokio::process::Command {
+        let mut command = tokio::process::Command::new(&self.program);
+        command.args(&self.args);
+        command
+    }
+}
+
+/// Future returned by [`LspBackend::resolve`].
+pub type ResolveFuture<'a> = Pin<Box<dyn Future<Output = Result<ServerCommand>> + Send + 'a>>;
+
+/// A language server tyf can drive over stdio.
+pub trait LspBackend: Send + Sync {
+    /// Short name used in messages, e.g. `pyright`.
+    fn name(&self) -> &str;
+
+    /// Find the command line that starts the server for `workspace_root`.
+    fn resolve<'a>(&'a self, workspace_root: &'a str) -> ResolveFuture<'a>;
+
+    /// `initializationOptions` sent with `initialize`.
+    fn initialization_options(&self, _config: &TyConfig) -> Value {
+        Value::Null
+    }
+
+    /// Answer to a `workspace/configuration` request for `section`.
+    fn configuration(&self, _section: Option<&str>) -> Value {
+        Value::Null
+    }
+}
+
+/// ty, at a configured path, or found on `PATH`, in the workspace's `.venv`,
+/// or run through `uvx`.
+#[derive(Debug, Clone, Default)]
+pub struct TyBackend {
+    pub path: Option<PathBuf>,
+}
+
+impl TyBackend {
+    /// Find the `ty` to run, see [`TyLspServer::discover`].
+    pub async fn discover(&self, workspace_root: &str) -> Result<FoundTy> {
+        TyLspServer::discover(workspace_root, self.path.as_deref()).await
+    }
+}
+
+impl LspBackend for TyBackend {
+    fn name(&self) -> &'static str {
+        "ty"
+    }
+
+    fn resolve<'a>(&'a self, workspace_root: &'a str) -> ResolveFuture<'a> {
+        Box::pin(async move {
+            let ty = self.discover(workspace_root).await?;
+            tracing::debug!("Using {ty}");
+            Ok(ty.command.with_args(&["server"]))
+        })
+    }
+
+    /// Mirrors the project's own ty settings, see [`TyConfig::lsp_configuration`].
+    fn initialization_options(&self, config: &TyConfig) -> Value {
+        serde_json::json!({ "configuration": config.lsp_configuration() })
+    }
+}
+
+/// pyright's language server, `pyright-langserver --stdio`.
+pub struct PyrightBackend;
+
+impl LspBackend for PyrightBackend {
+    fn name(&self) -> &'static str {
+        "pyright"
+    }
+
+    fn resolve<'a>(&'a self, workspace_root: &'a str) -> ResolveFuture<'a> {
+        Box::pin(async move {
+            let program = find_program("pyright-langserver", workspace_root).context(
+                "pyright-langserver not found on PATH or in the workspace's .venv \
+                 (install it with `npm install -g pyright` or `pip install pyright`)",
+            )?;
+            Ok(ServerCommand::new(program, &["--stdio"]))
+        })
+    }
+
+    fn configuration(&self, section: Option<&str>) -> Value {
+        // Let pyright find the environment and read installed packages'
+        // code, so third-party types resolve as they would in an editor.
+        match section {
+            Some("python.analysis") => serde_json::json!({
+                "autoSearchPaths": true,
+                "useLibraryCodeForTypes": true,
+                "diagnosticMode": "openFilesOnly",
+            }),
+            _ => Value::Null,
+        }
+    }
+}
+
+/// python-lsp-server, `pylsp`.
+pub struct PylspBackend;
+
+impl LspBackend for PylspBackend {
+    fn name(&self) -> &'static str {
+        "pylsp"
+    }
+
+    fn resolve<'a>(&'a self, workspace_root: &'a str) -> ResolveFuture<'a> {
+        Box::pin(async move {
+            let program = find_program("pylsp", workspace_root).context(
+                "pylsp not found on PATH or in the workspace's .venv \
+                 (install it with `pip install python-lsp-server`)",
+            )?;
+            Ok(ServerCommand::new(program, &[]))
+        })
+    }
+}
+
+/// jedi-language-server.
+pub struct JediBackend;
+
+impl LspBackend for JediBackend {
+    fn name(&self) -> &'static str {
+        "jedi"
+    }
+
+    fn resolve<'a>(&'a self, workspace_root: &'a str) -> ResolveFuture<'a> {
+        Box::pin(async move {
+            let program = find_program("jedi-language-server", workspace_root).context(
+                "jedi-language-server not found on PATH or in the workspace's .venv \
+                 (install it with `pip install jedi-language-server`)",
+            )?;
+            Ok(ServerCommand::new(program, &[]))
+        })
+    }
+}
+
+/// Any other server, given as the command line that starts it on stdio.
+pub struct CommandBackend {
+    name: String,
+    command: ServerCommand,
+}
+
+impl LspBackend for CommandBackend {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn resolve<'a>(&'a self, _workspace_root: &'a str) -> ResolveFuture<'a> {
+        Box::pin(async move { Ok(self.command.clone()) })
+    }
+}
+
+/// The backend a `--backend` value names.
+///
+/// `ty`, `pyright`, `pylsp` and `jedi` (or their server's program name) are
+/// built in; anything else is a command line, split on whitespace.
+pub fn from_spec(spec: &str) -> Result<Arc<dyn LspBackend>> {
+    let backend: Arc<dyn LspBackend> = match spec.trim() {
+        "" => anyhow::bail!("The LSP backend is empty; use ty, pyright, pylsp, jedi or a command"),
+        "ty" => Arc::new(TyBackend::default()),
+        spec if spec.starts_with(TY_PATH_PREFIX) => Arc::new(ty_backend(spec)?),
+        "pyright" | "pyright-langserver" => Arc::new(PyrightBackend),
+        "pylsp" | "python-lsp-server" => Arc::new(PylspBackend),
+        "jedi" | "jedi-language-server" => Arc::new(JediBackend),
+        command => {
+            let mut words = command.split_whitespace();
+            let program = words.next().unwrap_or_default();
+            let name = Path::new(program)
+                .file_name()
+                .map_or_else(|| program.to_string(), |name| name.to_string_lossy().into_owned());
+            let args: Vec<&str> = words.collect();
+            Arc::new(CommandBackend { name, command: ServerCommand::new(program, &args) })
+        }
+    };
+    Ok(backend)
+}
+
+/// The [`TyBackend`] a `ty` or `ty:<path>` spec names; other backends are an error.
+fn ty_backend(spec: &str) -> Result<TyBackend> {
+    let path = match spec.trim() {
+        "ty" => None,
+        spec => {
+            let path = spec.strip_prefix(TY_PATH_PREFIX).context("Not a ty backend")?.trim();
+            anyhow::ensure!(!path.is_empty(), "The ty path in backend '{spec}' is empty");
+            Some(PathBuf::from(path))
+        }
+    };
+    Ok(TyBackend { path })
+}
+
+/// Where the ty backend `spec` names would be run from for `workspace_root`,
+/// or `None` if `spec` is another backend.
+pub async fn find_ty(spec: &str, workspace_root: &str) -> Option<Result<FoundTy>> {
+    let backend = ty_backend(spec).ok()?;
+    Some(backend.discover(workspace_root).await)
+}
+
+/// The backend spec for `workspace_root`: `TYF_BACKEND`, else the
+/// `backend` setting in its config, else ty. ty comes from the config's
+/// `ty-path` when it sets one.
+pub fn selected(workspace_root: &Path) -> String {
+    if let Some(spec) = std::env::var(BACKEND_ENV).ok().filter(|spec| !spec.trim().is_empty()) {
+        return spec;
+    }
+    let config = Config::load_or_default(workspace_root);
+    match config.backend() {
+        Some(spec) if spec.trim() != DEFAULT_BACKEND => spec.to_string(),
+        _ => config
+            .ty_path(workspace_root)
+            .map_or_else(|| DEFAULT_BACKEND.to_string(), |path| ty_spec(&path)),
+    }
+}
+
+/// `name` in the workspace's `.venv`, else on `PATH`.
+fn find_program(name: &str, workspace_root: &str) -> Option<PathBuf> {
+    let venv = Path::new(workspace_root).join(".venv");
+    let venv_bin = if cfg!(windows) { venv.join("Scripts") } else { venv.join("bin") };
+    std::iter::once(venv_bin)
+        .chain(std::env::var_os("PATH").iter().flat_map(std::env::split_paths))
+        .map(|dir| dir.join(name))
+        .find(|path| path.is_file())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_spec_knows_builtin_backends() {
+        assert_eq!(from_spec("ty").unwrap().name(), "ty");
+        assert_eq!(from_spec("pyright-langserver").unwrap().name(), "pyright");
+        assert_eq!(from_spec("pylsp").unwrap().name(), "pylsp");
+        assert_eq!(from_spec("jedi").unwrap().name(), "jedi");
+        assert!(from_spec("  ").is_err());
+    }
+
+    #[test]
+    fn test_ty_path_specs_name_ty() {
+        let spec = ty_spec(Path::new("/opt/ty/bin/ty"));
+        assert_eq!(spec, "ty:/opt/ty/bin/ty");
+        assert_eq!(from_spec(&spec).unwrap().name(), "ty");
+        assert_eq!(ty_backend(&spec).unwrap().path, Some(PathBuf::from("/opt/ty/bin/ty")));
+        assert_eq!(ty_backend("ty").unwrap().path, None);
+        assert!(ty_backend("pyright").is_err());
+        assert!(from_spec("ty:").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_configured_ty_path_is_not_replaced() {
+        let dir = tempfile::tempdir().unwrap();
+        let missing = dir.path().join("no-such-ty");
+        let root = dir.path().to_str().unwrap();
+        let error = find_ty(&ty_spec(&missing), root).await.unwrap().unwrap_err();
+        assert!(format!("{error:#}").contains("no-such-ty"), "{error:#}");
+        assert!(find_ty("pylsp", root).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_from_spec_runs_other_commands() {
+        let backend = from_spec("/opt/lsp/bin/my-server --stdio --verbose").unwrap();
+        assert_eq!(backend.name(), "my-server");
+        let command = backend.resolve("/tmp").await.unwrap();
+        assert_eq!(command.label(), "/opt/lsp/bin/my-server --stdio --verbose");
+    }
+
+    #[test]
+    fn test_ty_gets_its_configuration() {
+        let options = TyBackend::default().initialization_options(&TyConfig::default());
+        assert_eq!(options["configuration"]["src"]["include"], serde_json::json!(["**"]));
+        assert_eq!(PylspBackend.initialization_options(&TyConfig::default()), Value::Null);
+    }
+}
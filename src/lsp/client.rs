@@ -1,29 +1,114 @@
 use anyhow::{Context, Result};
 use serde::de::DeserializeOwned;
 use serde_json::Value;
-use std::collections::{HashMap, HashSet};
-use std::sync::atomic::{AtomicU64, Ordering};
-use std::sync::{Arc, Mutex};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
 use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
-use tokio::sync::oneshot;
+use tokio::sync::{broadcast, oneshot};
 
+/// How long [`TyLspClient::diagnostics`] waits for pushed diagnostics.
+const PUBLISH_WAIT: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// Progress updates a subscriber can fall behind by before missing some.
+pub const PROGRESS_CAPACITY: usize = 64;
+
+/// Where a client sends what it hears from its server besides answers, for
+/// others (the daemon's clients) to follow.
+#[derive(Clone)]
+pub struct ServerFeeds {
+    /// Updates from the server's `$/progress` notifications
+    pub progress: broadcast::Sender<WorkProgress>,
+    /// Every message exchanged with the server, for `--trace-lsp`
+    pub wire: broadcast::Sender<WireMessage>,
+}
+
+impl ServerFeeds {
+    pub fn new() -> Self {
+        Self {
+            progress: broadcast::channel(PROGRESS_CAPACITY).0,
+            wire: broadcast::channel(WIRE_CAPACITY).0,
+        }
+    }
+}
+
+impl Default for ServerFeeds {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+use crate::lsp::backend::{self, LspBackend};
+use crate::lsp::documents::{supports_incremental_sync, FileStamp, OpenDocument};
+use crate::lsp::language;
+use crate::lsp::position::{
+    convert_positions, position_documents, ColumnUnit, LSP_UNIT, POSITION_ENCODINGS,
+};
 use crate::lsp::protocol::{
-    DocumentSymbol, DocumentSymbolParams, GotoDefinitionParams, Hover, HoverParams, LSPRequest,
-    LSPResponse, Location, Position, ReferenceContext, ReferenceParams, SymbolInformation,
-    TextDocumentIdentifier, TextDocumentPositionParams, WorkspaceSymbolParams,
+    CompletionItem, CompletionParams, CompletionResponse, Diagnostic, DocumentDiagnosticParams,
+    DocumentDiagnosticReport, DocumentHighlight, DocumentHighlightParams, DocumentSymbol,
+    DocumentSymbolParams, FileChangeType, FileEvent, GotoDefinitionParams, GotoKind, Hover,
+    HoverParams, InitializeResult, InlayHint, InlayHintParams, LSPRequest, LSPResponse, Location,
+    Position, PublishDiagnosticsParams, Range, ReferenceContext, ReferenceParams,
+    SymbolInformation, TextDocumentIdentifier, TextDocumentPositionParams, WorkProgress,
+    WorkspaceSymbolParams,
 };
-use crate::lsp::server::TyLspServer;
+use crate::lsp::server::{ServerExited, TyLspServer};
+use crate::lsp::trace::{Direction, WireMessage, WireTap, WIRE_CAPACITY};
+use crate::lsp::uri;
+use crate::workspace::detection::WorkspaceDetector;
+use crate::workspace::paths::UriNormalizer;
+use crate::workspace::source_cache::SourceFile;
+use crate::workspace::ty_config::TyConfig;
+
+/// Open documents by URI.
+type OpenDocuments = HashMap<String, OpenDocument>;
 
 pub struct TyLspClient {
-    /// Kept alive so the child process is killed when the client is dropped.
-    _server: TyLspServer,
-    stdin: tokio::sync::Mutex<tokio::process::ChildStdin>,
+    /// The server process; killed when the client is dropped.
+    server: TyLspServer,
+    /// The language server this client drives.
+    backend: Arc<dyn LspBackend>,
+    stdin: Arc<tokio::sync::Mutex<tokio::process::ChildStdin>>,
     request_id: AtomicU64,
     pending_requests: Arc<Mutex<HashMap<u64, oneshot::Sender<LSPResponse>>>>,
-    /// URIs of documents already sent via `textDocument/didOpen`.
+    /// Set once the server's stdout closes, i.e. the process is gone.
+    exited: Arc<AtomicBool>,
+    /// Documents sent via `textDocument/didOpen`, by URI, with the text and
+    /// version the server has for them.
     /// Duplicate opens violate LSP protocol and can cause the server to
     /// re-analyze the file, returning null hover during the re-analysis window.
-    opened_documents: Mutex<HashSet<String>>,
+    ///
+    /// An async mutex, held while the document notifications are written, so
+    /// concurrent requests send them in version order and none queries a
+    /// document before its `didOpen` has gone out.
+    opened_documents: tokio::sync::Mutex<OpenDocuments>,
+    /// Latest diagnostics the server pushed with `textDocument/publishDiagnostics`, by URI.
+    published_diagnostics: Arc<Mutex<HashMap<String, Vec<Diagnostic>>>>,
+    /// Updates from the server's `$/progress` notifications.
+    progress: broadcast::Sender<WorkProgress>,
+    /// Every message exchanged with the server.
+    wire: WireTap,
+    /// Server identity and capabilities from the `initialize` response.
+    server_info: OnceLock<InitializeResult>,
+    /// Canonicalizes result URIs so symlinked spellings of one file dedup.
+    uris: UriNormalizer,
+}
+
+/// Cancels an LSP request whose caller stopped waiting for it.
+struct CancelOnDrop<'a> {
+    client: &'a TyLspClient,
+    /// The request still awaiting its response; `None` once it is answered.
+    id: Option<u64>,
+}
+
+impl Drop for CancelOnDrop<'_> {
+    fn drop(&mut self) {
+        if let Some(id) = self.id {
+            self.client.cancel_request(id);
+        }
+    }
 }
 
 /// Build a `file://` URI from a file path, canonicalizing it first.
@@ -31,7 +116,137 @@ async fn file_uri(file_path: &str) -> Result<String> {
     let canonical = tokio::fs::canonicalize(file_path)
         .await
         .with_context(|| format!("Failed to resolve path: {file_path}"))?;
-    Ok(format!("file://{}", canonical.display()))
+    Ok(uri::path_to_uri(&uri::simplify(canonical)))
+}
+
+/// Read a Python file for `didOpen`/`didChange`: its language id and text.
+///
+/// Refuses non-Python files here rather than letting ty silently return nothing.
+async fn read_python_source(file_path: &str) -> Result<(&'static str, String)> {
+    let bytes = tokio::fs::read(file_path)
+        .await
+        .with_context(|| format!("Failed to read file: {file_path}"))?;
+    let head = &bytes[..bytes.len().min(language::SNIFF_LEN)];
+    let language_id = language::check_python_source(Path::new(file_path), head)?;
+    let text =
+        String::from_utf8(bytes).with_context(|| format!("{file_path} is not valid UTF-8 text"))?;
+    Ok((language_id, text))
+}
+
+/// Store the diagnostics of a `textDocument/publishDiagnostics` notification,
+/// replacing what was published for that file before.
+fn record_published_diagnostics(
+    store: &Mutex<HashMap<String, Vec<Diagnostic>>>,
+    mut notification: Value,
+) {
+    let params = serde_json::from_value::<PublishDiagnosticsParams>(notification["params"].take());
+    match params {
+        Ok(params) => {
+            store
+                .lock()
+                .expect("published_diagnostics mutex poisoned")
+                .insert(params.uri, params.diagnostics);
+        }
+        Err(e) => tracing::debug!("Failed to parse publishDiagnostics: {e}"),
+    }
+}
+
+/// The update a `$/progress` notification reports, or `None` if it isn't
+/// work done progress.
+///
+/// `titles` holds the title of each task in progress, by token, from its
+/// `begin` until its `end`.
+fn work_progress(
+    titles: &mut HashMap<String, String>,
+    notification: &Value,
+) -> Option<WorkProgress> {
+    let params = &notification["params"];
+    let token = params["token"].to_string();
+    let value = &params["value"];
+    let title = match value["kind"].as_str()? {
+        "begin" => {
+            let title = value["title"].as_str().unwrap_or_default().to_string();
+            titles.insert(token, title.clone());
+            title
+        }
+        "report" => titles.get(&token).cloned().unwrap_or_default(),
+        "end" => titles.remove(&token).unwrap_or_default(),
+        _ => return None,
+    };
+    Some(WorkProgress {
+        title,
+        message: value["message"].as_str().map(str::to_string),
+        percentage: value["percentage"].as_u64().and_then(|p| u32::try_from(p).ok()),
+        done: value["kind"] == "end",
+    })
+}
+
+/// Handles the notifications and requests the server sends on its own.
+struct ServerMessages {
+    published_diagnostics: Arc<Mutex<HashMap<String, Vec<Diagnostic>>>>,
+    progress: broadcast::Sender<WorkProgress>,
+    /// Titles of the tasks in progress, by `$/progress` token.
+    progress_titles: HashMap<String, String>,
+    backend: Arc<dyn LspBackend>,
+    stdin: Arc<tokio::sync::Mutex<tokio::process::ChildStdin>>,
+    wire: WireTap,
+}
+
+impl ServerMessages {
+    fn handle(&mut self, message: Value) {
+        let method = message.get("method").and_then(|m| m.as_str()).unwrap_or("unknown");
+        if method == "textDocument/publishDiagnostics" {
+            record_published_diagnostics(&self.published_diagnostics, message);
+        } else if method == "$/progress" {
+            if let Some(update) = work_progress(&mut self.progress_titles, &message) {
+                // Nobody listening is fine.
+                let _ = self.progress.send(update);
+            }
+        } else if message.get("id").is_some() {
+            answer_server_request(&self.backend, &self.stdin, &self.wire, &message);
+        } else {
+            tracing::debug!("Skipping server-initiated message: {method}");
+        }
+    }
+}
+
+/// Write one framed LSP message to the server's stdin.
+async fn write_message(
+    stdin: &tokio::sync::Mutex<tokio::process::ChildStdin>,
+    wire: &WireTap,
+    content: &str,
+) -> Result<()> {
+    wire.record(Direction::Sent, content);
+    let message = format!("Content-Length: {}\r\n\r\n{content}", content.len());
+    let mut stdin = stdin.lock().await;
+    stdin.write_all(message.as_bytes()).await.context("Failed to write to LSP stdin")?;
+    stdin.flush().await.context("Failed to flush LSP stdin")?;
+    Ok(())
+}
+
+/// Answer a server-initiated request.
+///
+/// Servers like pyright wait for the answer before analysing. It's written
+/// from its own task so reading the server's output never waits on stdin.
+fn answer_server_request(
+    backend: &Arc<dyn LspBackend>,
+    stdin: &Arc<tokio::sync::Mutex<tokio::process::ChildStdin>>,
+    wire: &WireTap,
+    request: &Value,
+) {
+    let reply = serde_json::json!({
+        "jsonrpc": "2.0",
+        "id": request["id"],
+        "result": server_request_result(backend.as_ref(), request),
+    })
+    .to_string();
+    let stdin = Arc::clone(stdin);
+    let wire = wire.clone();
+    tokio::spawn(async move {
+        if let Err(e) = write_message(&stdin, &wire, &reply).await {
+            tracing::debug!("Failed to answer server request: {e}");
+        }
+    });
 }
 
 /// Parse an LSP response that returns an array of items.
@@ -44,22 +259,46 @@ fn parse_response_array<T: DeserializeOwned>(response: LSPResponse) -> Result<Ve
     }
 }
 
-/// Build the `InitializeParams` JSON for the ty LSP server.
+/// Build the `InitializeParams` JSON for the language server.
+///
+/// The client capabilities are the same for every backend; the
+/// `initializationOptions` are the backend's own. For ty they mirror the
+/// project's ty settings (see [`TyConfig::lsp_configuration`]), falling back
+/// to `src.include = ["**"]` when the project doesn't restrict its sources.
 ///
-/// Includes `initializationOptions.configuration.src.include = ["**"]` to
-/// override any restrictive `[tool.ty.src]` settings in `pyproject.toml`,
-/// ensuring tyf can search the entire workspace.
-fn build_init_params(workspace_root: &str) -> serde_json::Value {
-    serde_json::json!({
+/// `folders` are the roots of a multi-root workspace, sent as its
+/// `workspaceFolders`; an ordinary workspace has none and only a root.
+fn build_init_params(
+    workspace_root: &str,
+    folders: &[PathBuf],
+    backend: &dyn LspBackend,
+    config: &TyConfig,
+) -> serde_json::Value {
+    let mut params = serde_json::json!({
         "processId": std::process::id(),
         "rootPath": workspace_root,
-        "rootUri": format!("file://{workspace_root}"),
+        "rootUri": uri::path_to_uri(Path::new(workspace_root)),
         "capabilities": {
+            "general": {
+                "positionEncodings": POSITION_ENCODINGS
+            },
             "textDocument": {
                 "definition": {
                     "dynamicRegistration": false,
                     "linkSupport": false
                 },
+                "declaration": {
+                    "dynamicRegistration": false,
+                    "linkSupport": false
+                },
+                "typeDefinition": {
+                    "dynamicRegistration": false,
+                    "linkSupport": false
+                },
+                "implementation": {
+                    "dynamicRegistration": false,
+                    "linkSupport": false
+                },
                 "hover": {
                     "dynamicRegistration": false,
                     "contentFormat": ["markdown", "plaintext"]
@@ -70,63 +309,183 @@ fn build_init_params(workspace_root: &str) -> serde_json::Value {
                 "documentSymbol": {
                     "dynamicRegistration": false,
                     "hierarchicalDocumentSymbolSupport": true
+                },
+                "diagnostic": {
+                    "dynamicRegistration": false,
+                    "relatedDocumentSupport": false
+                },
+                "publishDiagnostics": {
+                    "relatedInformation": true
+                },
+                "inlayHint": {
+                    "dynamicRegistration": false
+                },
+                "documentHighlight": {
+                    "dynamicRegistration": false
+                },
+                "completion": {
+                    "dynamicRegistration": false,
+                    "completionItem": {
+                        "labelDetailsSupport": true
+                    }
                 }
             },
             "workspace": {
                 "symbol": {
                     "dynamicRegistration": false
-                }
+                },
+                "configuration": true
+            },
+            "window": {
+                "workDoneProgress": true
             }
         },
-        "initializationOptions": {
-            "configuration": {
-                "src": {
-                    "include": ["**"]
-                }
-            }
-        }
-    })
+        "initializationOptions": backend.initialization_options(config)
+    });
+    if !folders.is_empty() {
+        let folders: Vec<Value> = folders
+            .iter()
+            .map(|folder| {
+                let name = folder.file_name().map(|name| name.to_string_lossy());
+                serde_json::json!({
+                    "uri": uri::path_to_uri(folder),
+                    "name": name.unwrap_or_else(|| folder.to_string_lossy()),
+                })
+            })
+            .collect();
+        params["workspaceFolders"] = Value::Array(folders);
+        params["capabilities"]["workspace"]["workspaceFolders"] = Value::Bool(true);
+    }
+    params
+}
+
+/// The result to answer a server-initiated request with.
+///
+/// Only `workspace/configuration` gets a real answer, from the backend;
+/// everything else (`client/registerCapability`, progress tokens) is
+/// acknowledged with `null`.
+fn server_request_result(backend: &dyn LspBackend, request: &Value) -> Value {
+    if request["method"] != "workspace/configuration" {
+        return Value::Null;
+    }
+    let items = request["params"]["items"].as_array().map(Vec::as_slice).unwrap_or_default();
+    items.iter().map(|item| backend.configuration(item["section"].as_str())).collect()
 }
 
 impl TyLspClient {
+    /// Start the workspace's language server: the one `TYF_BACKEND` or the
+    /// workspace's `[tool.ty-find]` configuration names, else ty.
     pub async fn new(workspace_root: &str) -> Result<Self> {
-        let mut server =
-            TyLspServer::start(workspace_root).await.context("Failed to start ty LSP server")?;
+        let spec = backend::selected(Path::new(workspace_root));
+        Self::with_backend(workspace_root, backend::from_spec(&spec)?).await
+    }
+
+    /// Start the language server of `backend` for the workspace.
+    pub async fn with_backend(workspace_root: &str, backend: Arc<dyn LspBackend>) -> Result<Self> {
+        Self::with_folders(workspace_root, &[], backend).await
+    }
+
+    /// Start the language server of `backend` for a multi-root workspace,
+    /// with each of `folders` as a workspace folder.
+    pub async fn with_folders(
+        workspace_root: &str,
+        folders: &[PathBuf],
+        backend: Arc<dyn LspBackend>,
+    ) -> Result<Self> {
+        Self::with_feeds(workspace_root, folders, backend, ServerFeeds::new()).await
+    }
+
+    /// [`with_folders`](Self::with_folders), sending the server's progress
+    /// updates and messages to `feeds`, so subscribers hear about startup
+    /// indexing too.
+    pub async fn with_feeds(
+        workspace_root: &str,
+        folders: &[PathBuf],
+        backend: Arc<dyn LspBackend>,
+        feeds: ServerFeeds,
+    ) -> Result<Self> {
+        let mut server = TyLspServer::start(backend.as_ref(), workspace_root)
+            .await
+            .with_context(|| format!("Failed to start {} LSP server", backend.name()))?;
 
         let stdin = server.take_stdin();
         let stdout = server.take_stdout();
 
+        let wire = WireTap::new(backend.name().to_string(), feeds.wire);
         let client = Self {
-            _server: server,
-            stdin: tokio::sync::Mutex::new(stdin),
+            server,
+            backend,
+            stdin: Arc::new(tokio::sync::Mutex::new(stdin)),
             request_id: AtomicU64::new(1),
             pending_requests: Arc::new(Mutex::new(HashMap::new())),
-            opened_documents: Mutex::new(HashSet::new()),
+            exited: Arc::new(AtomicBool::new(false)),
+            opened_documents: tokio::sync::Mutex::new(HashMap::new()),
+            published_diagnostics: Arc::new(Mutex::new(HashMap::new())),
+            progress: feeds.progress,
+            wire,
+            server_info: OnceLock::new(),
+            uris: UriNormalizer::new(),
         };
 
         // Must start reading responses before sending initialize,
         // otherwise the initialize response is never consumed and we deadlock.
         client.start_response_handler(stdout);
         tracing::debug!("Sending LSP initialize request...");
-        tracing::debug!(
-            "overriding ty src.include to [\"**\"] (ignoring pyproject.toml restrictions)"
-        );
-        client.initialize(workspace_root).await.context("Failed to initialize LSP session")?;
+        let mut config = TyConfig::load_or_default(Path::new(workspace_root));
+        // Point ty at the project's environment unless its settings already
+        // do, so imports of installed packages resolve.
+        if config.python.is_none() && client.backend.name() == backend::DEFAULT_BACKEND {
+            let root = PathBuf::from(workspace_root);
+            let found =
+                tokio::task::spawn_blocking(move || WorkspaceDetector::find_environment(&root))
+                    .await
+                    .ok()
+                    .flatten();
+            if let Some(environment) = found {
+                tracing::debug!("Python environment: {environment}");
+                config.python = Some(environment.prefix.display().to_string());
+            }
+        }
+        tracing::debug!("ty configuration: {}", config.describe());
+        client
+            .initialize(workspace_root, folders, &config)
+            .await
+            .context("Failed to initialize LSP session")?;
         tracing::debug!("LSP client initialized successfully");
         Ok(client)
     }
 
-    async fn initialize(&self, workspace_root: &str) -> Result<()> {
-        let init_params = build_init_params(workspace_root);
+    async fn initialize(
+        &self,
+        workspace_root: &str,
+        folders: &[PathBuf],
+        config: &TyConfig,
+    ) -> Result<()> {
+        let init_params = build_init_params(workspace_root, folders, self.backend.as_ref(), config);
 
-        let _response = self.send_request("initialize", init_params).await?;
+        let response = self.send_request("initialize", init_params).await?;
+        let info: InitializeResult = response
+            .result
+            .map(serde_json::from_value)
+            .transpose()
+            .context("Failed to parse initialize response")?
+            .unwrap_or_default();
+        tracing::debug!(
+            "{} supports: {}",
+            info.server_label(),
+            info.supported_providers().join(", ")
+        );
+        let _ = self.server_info.set(info);
 
         self.send_notification("initialized", serde_json::json!({})).await?;
 
         // Readiness probe: ensure the server can process requests after
         // initialization. Without this, the first real request often arrives
-        // before the server is ready, returning empty/null results.
-        self.send_request("workspace/symbol", serde_json::json!({"query": ""})).await?;
+        // before the server is ready, returning empty/null results. Servers
+        // without workspace symbols (pylsp) are taken as ready.
+        if self.server_info().is_none_or(|info| info.supports("workspaceSymbolProvider")) {
+            self.send_request("workspace/symbol", serde_json::json!({"query": ""})).await?;
+        }
 
         Ok(())
     }
@@ -135,6 +494,8 @@ impl TyLspClient {
     ///
     /// Returns `true` if this was the first `didOpen` for this URI.
     /// Returns `false` if the document was already open (no notification sent).
+    /// An open document whose file changed on disk since it was read is
+    /// brought up to date with `didChange` first.
     ///
     /// LSP protocol requires exactly one `didOpen` per document. Sending it
     /// again causes the server to re-analyze from scratch, which can make
@@ -142,41 +503,374 @@ impl TyLspClient {
     pub async fn open_document(&self, file_path: &str) -> Result<bool> {
         let uri = file_uri(file_path).await?;
 
-        {
-            let mut opened = self.opened_documents.lock().expect("opened_documents mutex poisoned");
-            if !opened.insert(uri.clone()) {
+        let mut opened = self.opened_documents.lock().await;
+        if let Some(doc) = opened.get(&uri) {
+            if doc.stamp.is_some() && doc.stamp != FileStamp::of(Path::new(file_path)).await {
+                tracing::debug!("open_document: {uri} changed on disk, sending didChange");
+                self.reload_open_document(&mut opened, &uri, file_path).await?;
+            } else {
                 tracing::debug!("open_document: already open, skipping didOpen for {uri}");
-                return Ok(false);
             }
+            return Ok(false);
         }
 
-        let text = tokio::fs::read_to_string(file_path)
-            .await
-            .with_context(|| format!("Failed to read file: {file_path}"))?;
+        let stamp = FileStamp::of(Path::new(file_path)).await;
+        let (language_id, text) = read_python_source(file_path).await?;
+        opened.insert(uri.clone(), OpenDocument::new(text.clone(), stamp));
+        self.send_did_open(&uri, language_id, text).await?;
+        Ok(true)
+    }
+
+    /// Replace the server's copy of a document with `text`, e.g. an editor
+    /// buffer that hasn't been saved, and return the new version.
+    ///
+    /// Opens the document with `text` if it isn't open yet. Otherwise sends
+    /// `textDocument/didChange`, with only the changed span when the server
+    /// accepts incremental changes. The document is then no longer compared
+    /// against the file on disk until [`reload_document`](Self::reload_document)
+    /// or [`close_document`](Self::close_document).
+    ///
+    /// `file_path` need not exist: an editor buffer that was never saved is
+    /// opened under the URI of its absolute path.
+    pub async fn change_document(&self, file_path: &str, text: String) -> Result<i32> {
+        let uri = match file_uri(file_path).await {
+            Ok(uri) => uri,
+            Err(_) => uri::path_to_uri(Path::new(file_path)),
+        };
+        let mut opened = self.opened_documents.lock().await;
+        self.push_document(&mut opened, &uri, file_path, text, None).await
+    }
+
+    /// Bring an open document up to date with its file on disk and tell the
+    /// server it was saved. Returns `false` (and sends nothing) if the
+    /// document isn't open.
+    pub async fn reload_document(&self, file_path: &str) -> Result<bool> {
+        let uri = file_uri(file_path).await?;
+        let mut opened = self.opened_documents.lock().await;
+        if !opened.contains_key(&uri) {
+            return Ok(false);
+        }
+        self.reload_open_document(&mut opened, &uri, file_path).await?;
+        Ok(true)
+    }
+
+    /// The URI requests about `file_path` use: that of the file on disk, or
+    /// of the unsaved document [`change_document`](Self::change_document)
+    /// opened under that path.
+    async fn document_uri(&self, file_path: &str) -> Result<String> {
+        match file_uri(file_path).await {
+            Ok(uri) => Ok(uri),
+            Err(e) => {
+                let uri = uri::path_to_uri(Path::new(file_path));
+                if self.opened_documents.lock().await.contains_key(&uri) {
+                    Ok(uri)
+                } else {
+                    Err(e)
+                }
+            }
+        }
+    }
+
+    /// Send `textDocument/didSave` for an open document; returns whether it was open.
+    pub async fn save_document(&self, file_path: &str) -> Result<bool> {
+        let uri = file_uri(file_path).await?;
+        let opened = self.opened_documents.lock().await;
+        self.send_did_save(&opened, &uri).await
+    }
+
+    /// Close a document opened with [`open_document`](Self::open_document).
+    ///
+    /// Returns whether it was open. The server goes back to reading the file
+    /// from disk, and the next `open_document` sends its current contents.
+    /// Works for files that no longer exist.
+    pub async fn close_document(&self, file_path: &str) -> Result<bool> {
+        let uri = match file_uri(file_path).await {
+            Ok(uri) => uri,
+            Err(_) => uri::path_to_uri(Path::new(file_path)),
+        };
+        let mut opened = self.opened_documents.lock().await;
+        if opened.remove(&uri).is_none() {
+            return Ok(false);
+        }
+        self.published_diagnostics
+            .lock()
+            .expect("published_diagnostics mutex poisoned")
+            .remove(&uri);
 
+        self.send_notification(
+            "textDocument/didClose",
+            serde_json::json!({ "textDocument": { "uri": uri } }),
+        )
+        .await?;
+        Ok(true)
+    }
+
+    /// Re-read an open document from disk, then send the change and `didSave`.
+    async fn reload_open_document(
+        &self,
+        opened: &mut OpenDocuments,
+        uri: &str,
+        file_path: &str,
+    ) -> Result<()> {
+        let stamp = FileStamp::of(Path::new(file_path)).await;
+        let (_, text) = read_python_source(file_path).await?;
+        self.push_document(opened, uri, file_path, text, stamp).await?;
+        self.send_did_save(opened, uri).await?;
+        Ok(())
+    }
+
+    /// Open `uri` with `text`, or send the change to it if it is open.
+    async fn push_document(
+        &self,
+        opened: &mut OpenDocuments,
+        uri: &str,
+        file_path: &str,
+        text: String,
+        stamp: Option<FileStamp>,
+    ) -> Result<i32> {
+        let head = &text.as_bytes()[..text.len().min(language::SNIFF_LEN)];
+        let language_id = language::check_python_source(Path::new(file_path), head)?;
+        let Some(doc) = opened.get_mut(uri) else {
+            opened.insert(uri.to_string(), OpenDocument::new(text.clone(), stamp));
+            self.send_did_open(uri, language_id, text).await?;
+            return Ok(1);
+        };
+        // Incremental changes are measured in UTF-16; a server counting in
+        // another unit gets the whole text instead.
+        let incremental = self.server_unit() == LSP_UNIT
+            && self
+                .server_info
+                .get()
+                .is_some_and(|info| supports_incremental_sync(&info.capabilities));
+        let (version, change) = doc.update(text, stamp, incremental);
+        self.send_notification(
+            "textDocument/didChange",
+            serde_json::json!({
+                "textDocument": { "uri": uri, "version": version },
+                "contentChanges": [change]
+            }),
+        )
+        .await?;
+        Ok(version)
+    }
+
+    async fn send_did_open(&self, uri: &str, language_id: &str, text: String) -> Result<()> {
         self.send_notification(
             "textDocument/didOpen",
             serde_json::json!({
                 "textDocument": {
                     "uri": uri,
-                    "languageId": "python",
+                    "languageId": language_id,
                     "version": 1,
                     "text": text
                 }
             }),
         )
-        .await?;
+        .await
+    }
 
+    /// Send `didSave` for `uri`, with its text if the server asks for it;
+    /// returns whether it was open.
+    async fn send_did_save(&self, opened: &OpenDocuments, uri: &str) -> Result<bool> {
+        let Some(doc) = opened.get(uri) else { return Ok(false) };
+        let include_text = self.server_info.get().is_some_and(|info| {
+            info.capabilities["textDocumentSync"]["save"]["includeText"] == true
+        });
+        let mut params = serde_json::json!({ "textDocument": { "uri": uri } });
+        if include_text {
+            params["text"] = Value::String(doc.text.clone());
+        }
+        self.send_notification("textDocument/didSave", params).await?;
         Ok(true)
     }
 
+    /// Open a document that exists only in memory, such as an edited copy of
+    /// a file, under a URI no other request uses.
+    ///
+    /// Unlike [`open_document`](Self::open_document) nothing is read from disk
+    /// or tracked; the caller closes it with
+    /// [`close_scratch_document`](Self::close_scratch_document).
+    pub async fn open_scratch_document(&self, uri: &str, text: &str) -> Result<()> {
+        self.send_notification(
+            "textDocument/didOpen",
+            serde_json::json!({
+                "textDocument": {
+                    "uri": uri,
+                    "languageId": "python",
+                    "version": 1,
+                    "text": text
+                }
+            }),
+        )
+        .await
+    }
+
+    /// Close a document opened with [`open_scratch_document`](Self::open_scratch_document).
+    pub async fn close_scratch_document(&self, uri: &str) -> Result<()> {
+        self.published_diagnostics
+            .lock()
+            .expect("published_diagnostics mutex poisoned")
+            .remove(uri);
+        self.send_notification(
+            "textDocument/didClose",
+            serde_json::json!({ "textDocument": { "uri": uri } }),
+        )
+        .await
+    }
+
+    /// Tell the server that files changed on disk behind its back.
+    pub async fn notify_files_changed(&self, changes: &[(&str, FileChangeType)]) -> Result<()> {
+        if changes.is_empty() {
+            return Ok(());
+        }
+        let mut events = Vec::with_capacity(changes.len());
+        for &(file_path, change) in changes {
+            let uri = match file_uri(file_path).await {
+                Ok(uri) => uri,
+                Err(_) => uri::path_to_uri(Path::new(file_path)),
+            };
+            events.push(FileEvent { uri, change });
+        }
+        self.send_notification(
+            "workspace/didChangeWatchedFiles",
+            serde_json::json!({ "changes": events }),
+        )
+        .await
+    }
+
+    /// Process ID of the language server, while it is running.
+    pub fn server_pid(&self) -> Option<u32> {
+        self.server.pid()
+    }
+
+    /// The language server this client drives.
+    pub fn backend(&self) -> &dyn LspBackend {
+        self.backend.as_ref()
+    }
+
+    /// Whether the server process has gone away, either because its output
+    /// closed or because the process itself exited; every request on this
+    /// client fails with [`ServerExited`] from then on.
+    pub fn has_exited(&self) -> bool {
+        self.exited.load(Ordering::SeqCst) || self.server.exit_status().is_some()
+    }
+
+    /// How the server process ended, once it has.
+    pub fn exit_status(&self) -> Option<std::process::ExitStatus> {
+        self.server.exit_status()
+    }
+
+    /// Receive the server's progress updates from now on.
+    pub fn subscribe_progress(&self) -> broadcast::Receiver<WorkProgress> {
+        self.progress.subscribe()
+    }
+
+    /// Server identity and capabilities captured during `initialize`.
+    pub fn server_info(&self) -> Option<&InitializeResult> {
+        self.server_info.get()
+    }
+
+    /// Unit of `Position::character` on the wire: the `positionEncoding` the
+    /// server chose in `initialize`, UTF-16 if it chose none.
+    fn server_unit(&self) -> ColumnUnit {
+        self.server_info
+            .get()
+            .and_then(|info| info.capabilities["positionEncoding"].as_str())
+            .and_then(ColumnUnit::from_encoding)
+            .unwrap_or(LSP_UNIT)
+    }
+
+    /// Re-express the positions in `value` from one unit in another.
+    ///
+    /// Line text comes from the open documents, or else from the files on
+    /// disk, which are read on a blocking task; `uri` is the document
+    /// positions outside any `Location` are in.
+    async fn recode_positions(
+        &self,
+        value: &mut Value,
+        uri: &str,
+        from: ColumnUnit,
+        to: ColumnUnit,
+    ) {
+        if from == to {
+            return;
+        }
+        let documents = position_documents(value, uri);
+        let mut files: HashMap<String, SourceFile> = {
+            let opened = self.opened_documents.lock().await;
+            documents
+                .iter()
+                .filter_map(|uri| {
+                    opened.get(uri).map(|doc| (uri.clone(), SourceFile::new(doc.text.clone())))
+                })
+                .collect()
+        };
+        let unopened: Vec<String> =
+            documents.into_iter().filter(|uri| !files.contains_key(uri)).collect();
+        if !unopened.is_empty() {
+            let read = tokio::task::spawn_blocking(move || {
+                unopened
+                    .into_iter()
+                    .filter_map(|uri| {
+                        let file = SourceFile::read(Path::new(&uri::uri_to_path(&uri)))?;
+                        Some((uri, file))
+                    })
+                    .collect::<Vec<_>>()
+            });
+            files.extend(read.await.unwrap_or_default());
+        }
+        convert_positions(value, uri, from, to, &mut |uri, line| {
+            files.get(uri)?.line(line as usize).map(str::to_string)
+        });
+    }
+
+    /// Fail with a precise message when the server didn't advertise
+    /// `provider`, instead of sending `method` and waiting for an error or a
+    /// timeout.
+    ///
+    /// If no capabilities were captured (older servers, tests), the request is
+    /// attempted anyway and any failure surfaces from the server itself.
+    fn require_capability(&self, provider: &str, method: &str, feature: &str) -> Result<()> {
+        match self.server_info.get() {
+            Some(info) if !info.supports(provider) => anyhow::bail!(
+                "{} doesn't support {method}, needed to {feature}; {} (`tyf ty-info` lists what it \
+                 supports)",
+                info.server_label(),
+                if info.is_ty() { "upgrade ty" } else { "use ty for this (--backend ty)" }
+            ),
+            _ => Ok(()),
+        }
+    }
+
     pub async fn goto_definition(
         &self,
         file_path: &str,
         line: u32,
         character: u32,
     ) -> Result<Vec<Location>> {
-        let uri = file_uri(file_path).await?;
+        self.goto(GotoKind::Definition, file_path, line, character).await
+    }
+
+    /// Whether the server advertised `provider`; assumed until it has
+    /// answered `initialize`.
+    pub fn supports(&self, provider: &str) -> bool {
+        self.server_info().is_none_or(|info| info.supports(provider))
+    }
+
+    /// Ask one of the "go to" requests about a position.
+    pub async fn goto(
+        &self,
+        kind: GotoKind,
+        file_path: &str,
+        line: u32,
+        character: u32,
+    ) -> Result<Vec<Location>> {
+        self.require_capability(
+            kind.provider(),
+            kind.method(),
+            &format!("go to a {}", kind.label()),
+        )?;
+        let uri = self.document_uri(file_path).await?;
 
         let params = GotoDefinitionParams {
             text_document_position_params: TextDocumentPositionParams {
@@ -187,20 +881,18 @@ impl TyLspClient {
             partial_result_token: None,
         };
 
-        let response =
-            self.send_request("textDocument/definition", serde_json::to_value(params)?).await?;
+        let response = self.send_request(kind.method(), serde_json::to_value(params)?).await?;
 
-        // Definition can return a single Location or an array of Locations
-        match response.result {
+        // The answer can be a single Location or an array of Locations
+        let mut locations: Vec<Location> = match response.result {
             Some(Value::Array(arr)) => serde_json::from_value(Value::Array(arr))
-                .context("Failed to parse definition locations"),
-            Some(value @ Value::Object(_)) => {
-                let loc: Location =
-                    serde_json::from_value(value).context("Failed to parse definition location")?;
-                Ok(vec![loc])
-            }
-            _ => Ok(vec![]),
-        }
+                .with_context(|| format!("Failed to parse {} locations", kind.label()))?,
+            Some(value @ Value::Object(_)) => vec![serde_json::from_value(value)
+                .with_context(|| format!("Failed to parse {} location", kind.label()))?],
+            _ => vec![],
+        };
+        self.uris.normalize_locations(&mut locations);
+        Ok(locations)
     }
 
     pub async fn find_references(
@@ -210,6 +902,11 @@ impl TyLspClient {
         character: u32,
         include_declaration: bool,
     ) -> Result<Vec<Location>> {
+        self.require_capability(
+            "referencesProvider",
+            "textDocument/references",
+            "find references",
+        )?;
         let uri = file_uri(file_path).await?;
 
         let params = ReferenceParams {
@@ -225,11 +922,78 @@ impl TyLspClient {
         let response =
             self.send_request("textDocument/references", serde_json::to_value(params)?).await?;
 
+        let mut locations: Vec<Location> = parse_response_array(response)?;
+        self.uris.normalize_locations(&mut locations);
+        Ok(locations)
+    }
+
+    /// Occurrences of the symbol at a position within its own file.
+    pub async fn document_highlights(
+        &self,
+        file_path: &str,
+        line: u32,
+        character: u32,
+    ) -> Result<Vec<DocumentHighlight>> {
+        self.require_capability(
+            "documentHighlightProvider",
+            "textDocument/documentHighlight",
+            "find occurrences",
+        )?;
+        let uri = file_uri(file_path).await?;
+
+        let params = DocumentHighlightParams {
+            text_document_position_params: TextDocumentPositionParams {
+                text_document: TextDocumentIdentifier { uri },
+                position: Position { line, character },
+            },
+            work_done_token: None,
+            partial_result_token: None,
+        };
+
+        let response = self
+            .send_request("textDocument/documentHighlight", serde_json::to_value(params)?)
+            .await?;
         parse_response_array(response)
     }
 
+    /// Completion items at a position of the open document `uri`.
+    ///
+    /// Takes a URI rather than a path so it also works on scratch documents.
+    pub async fn completion(
+        &self,
+        uri: &str,
+        line: u32,
+        character: u32,
+    ) -> Result<Vec<CompletionItem>> {
+        self.require_capability(
+            "completionProvider",
+            "textDocument/completion",
+            "list members of an expression",
+        )?;
+
+        let params = CompletionParams {
+            text_document_position_params: TextDocumentPositionParams {
+                text_document: TextDocumentIdentifier { uri: uri.to_string() },
+                position: Position { line, character },
+            },
+            work_done_token: None,
+        };
+
+        let response =
+            self.send_request("textDocument/completion", serde_json::to_value(params)?).await?;
+        match response.result {
+            Some(value) if !value.is_null() => {
+                let completions: CompletionResponse =
+                    serde_json::from_value(value).context("Failed to parse completion response")?;
+                Ok(completions.into_items())
+            }
+            _ => Ok(Vec::new()),
+        }
+    }
+
     pub async fn hover(&self, file_path: &str, line: u32, character: u32) -> Result<Option<Hover>> {
-        let uri = file_uri(file_path).await?;
+        self.require_capability("hoverProvider", "textDocument/hover", "show types and docs")?;
+        let uri = self.document_uri(file_path).await?;
 
         let params = HoverParams {
             text_document_position_params: TextDocumentPositionParams {
@@ -253,6 +1017,7 @@ impl TyLspClient {
     }
 
     pub async fn workspace_symbols(&self, query: &str) -> Result<Vec<SymbolInformation>> {
+        self.require_capability("workspaceSymbolProvider", "workspace/symbol", "look up symbols")?;
         let params = WorkspaceSymbolParams {
             query: query.to_string(),
             work_done_token: None,
@@ -261,10 +1026,19 @@ impl TyLspClient {
 
         let response = self.send_request("workspace/symbol", serde_json::to_value(params)?).await?;
 
-        parse_response_array(response)
+        let mut symbols: Vec<SymbolInformation> = parse_response_array(response)?;
+        for symbol in &mut symbols {
+            symbol.location.uri = self.uris.normalize(&symbol.location.uri);
+        }
+        Ok(symbols)
     }
 
     pub async fn document_symbols(&self, file_path: &str) -> Result<Vec<DocumentSymbol>> {
+        self.require_capability(
+            "documentSymbolProvider",
+            "textDocument/documentSymbol",
+            "list a file's symbols",
+        )?;
         let uri = file_uri(file_path).await?;
 
         let params = DocumentSymbolParams {
@@ -279,7 +1053,118 @@ impl TyLspClient {
         parse_response_array(response)
     }
 
-    async fn send_request(&self, method: &str, params: Value) -> Result<LSPResponse> {
+    /// Type errors and warnings for a file.
+    ///
+    /// Pulled with `textDocument/diagnostic` when the server supports it.
+    /// Otherwise the file is opened and the diagnostics the server pushes
+    /// for it with `textDocument/publishDiagnostics` are used.
+    pub async fn diagnostics(&self, file_path: &str) -> Result<Vec<Diagnostic>> {
+        let pull = self.server_info.get().is_none_or(|info| info.supports("diagnosticProvider"));
+        if !pull {
+            return self.published_diagnostics(file_path).await;
+        }
+        let uri = file_uri(file_path).await?;
+
+        let params = DocumentDiagnosticParams {
+            text_document: TextDocumentIdentifier { uri },
+            previous_result_id: None,
+        };
+
+        let response =
+            self.send_request("textDocument/diagnostic", serde_json::to_value(params)?).await?;
+        if let Some(error) = response.error {
+            anyhow::bail!("Failed to get diagnostics for {file_path}: {}", error.message);
+        }
+
+        let mut report: DocumentDiagnosticReport = response
+            .result
+            .filter(|value| !value.is_null())
+            .map(serde_json::from_value)
+            .transpose()
+            .context("Failed to parse diagnostics response")?
+            .unwrap_or_default();
+        for diagnostic in &mut report.items {
+            for related in diagnostic.related_information.iter_mut().flatten() {
+                related.location.uri = self.uris.normalize(&related.location.uri);
+            }
+        }
+        Ok(report.items)
+    }
+
+    /// Diagnostics the server pushed for a file, waiting up to [`PUBLISH_WAIT`].
+    ///
+    /// Servers publish after analyzing a newly opened file. One that has
+    /// nothing to report may not publish at all, so running out of time
+    /// means no diagnostics rather than an error.
+    async fn published_diagnostics(&self, file_path: &str) -> Result<Vec<Diagnostic>> {
+        let uri = file_uri(file_path).await?;
+        self.open_document(file_path).await?;
+        let deadline = tokio::time::Instant::now() + PUBLISH_WAIT;
+        loop {
+            let published = self
+                .published_diagnostics
+                .lock()
+                .expect("published_diagnostics mutex poisoned")
+                .get(&uri)
+                .cloned();
+            if let Some(mut diagnostics) = published {
+                let unit = self.server_unit();
+                if unit != LSP_UNIT {
+                    let mut value = serde_json::to_value(&diagnostics)?;
+                    self.recode_positions(&mut value, &uri, unit, LSP_UNIT).await;
+                    diagnostics = serde_json::from_value(value)
+                        .context("Failed to parse published diagnostics")?;
+                }
+                for diagnostic in &mut diagnostics {
+                    for related in diagnostic.related_information.iter_mut().flatten() {
+                        related.location.uri = self.uris.normalize(&related.location.uri);
+                    }
+                }
+                return Ok(diagnostics);
+            }
+            if tokio::time::Instant::now() >= deadline {
+                tracing::debug!("No diagnostics published for {uri} within {PUBLISH_WAIT:?}");
+                return Ok(Vec::new());
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        }
+    }
+
+    /// Inlay hints (inferred types, parameter names) for the whole of a file.
+    ///
+    /// `line_count` bounds the requested range; the end line is exclusive.
+    pub async fn inlay_hints(&self, file_path: &str, line_count: u32) -> Result<Vec<InlayHint>> {
+        self.require_capability(
+            "inlayHintProvider",
+            "textDocument/inlayHint",
+            "infer annotations",
+        )?;
+        let uri = file_uri(file_path).await?;
+
+        let params = InlayHintParams {
+            text_document: TextDocumentIdentifier { uri },
+            range: Range {
+                start: Position { line: 0, character: 0 },
+                end: Position { line: line_count, character: 0 },
+            },
+            work_done_token: None,
+        };
+
+        let response =
+            self.send_request("textDocument/inlayHint", serde_json::to_value(params)?).await?;
+
+        parse_response_array(response)
+    }
+
+    async fn send_request(&self, method: &str, mut params: Value) -> Result<LSPResponse> {
+        if self.has_exited() {
+            return Err(ServerExited.into());
+        }
+        let unit = self.server_unit();
+        let document = params["textDocument"]["uri"].as_str().unwrap_or_default().to_string();
+        if unit != LSP_UNIT {
+            self.recode_positions(&mut params, &document, LSP_UNIT, unit).await;
+        }
         let id = self.request_id.fetch_add(1, Ordering::SeqCst);
         let (tx, rx) = oneshot::channel();
 
@@ -297,9 +1182,25 @@ impl TyLspClient {
         };
 
         tracing::debug!("Sending LSP request: {method} (id: {id})");
-        self.send_message(&request).await?;
+        // Tells the server to stop if this future is dropped before the answer
+        // arrives, e.g. because the daemon request was cancelled.
+        let mut guard = CancelOnDrop { client: self, id: Some(id) };
+        if let Err(e) = self.send_message(&request).await {
+            guard.id = None;
+            // A closed stdin means the server is gone, even if the response
+            // handler hasn't seen its stdout close yet.
+            self.pending_requests.lock().expect("pending_requests mutex poisoned").remove(&id);
+            tracing::debug!("Failed to send {method} (id: {id}): {e:#}");
+            return Err(ServerExited.into());
+        }
 
-        let response = rx.await.context("LSP response channel closed unexpectedly")?;
+        // The response handler drops every pending sender when the server exits.
+        let response = rx.await.map_err(|_| ServerExited);
+        guard.id = None;
+        let mut response = response?;
+        if let (Some(result), true) = (response.result.as_mut(), unit != LSP_UNIT) {
+            self.recode_positions(result, &document, unit, LSP_UNIT).await;
+        }
 
         if let Some(ref error) = response.error {
             tracing::debug!("LSP error response for {method} (id: {id}): {error:?}");
@@ -310,6 +1211,28 @@ impl TyLspClient {
         Ok(response)
     }
 
+    /// Abandon request `id`: forget its response and send `$/cancelRequest`.
+    ///
+    /// Called from `Drop`, so the notification is written by a spawned task.
+    fn cancel_request(&self, id: u64) {
+        self.pending_requests.lock().expect("pending_requests mutex poisoned").remove(&id);
+        if self.has_exited() {
+            return;
+        }
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else { return };
+        tracing::debug!("Cancelling LSP request {id}");
+        let notification = serde_json::json!({
+            "jsonrpc": "2.0",
+            "method": "$/cancelRequest",
+            "params": { "id": id }
+        });
+        let stdin = Arc::clone(&self.stdin);
+        let wire = self.wire.clone();
+        runtime.spawn(async move {
+            let _ = write_message(&stdin, &wire, &notification.to_string()).await;
+        });
+    }
+
     async fn send_notification(&self, method: &str, params: Value) -> Result<()> {
         let notification = serde_json::json!({
             "jsonrpc": "2.0",
@@ -326,15 +1249,20 @@ impl TyLspClient {
     }
 
     async fn send_raw_message(&self, content: &str) -> Result<()> {
-        let message = format!("Content-Length: {}\r\n\r\n{content}", content.len());
-        let mut stdin = self.stdin.lock().await;
-        stdin.write_all(message.as_bytes()).await.context("Failed to write to LSP stdin")?;
-        stdin.flush().await.context("Failed to flush LSP stdin")?;
-        Ok(())
+        write_message(&self.stdin, &self.wire, content).await
     }
 
     fn start_response_handler(&self, stdout: BufReader<tokio::process::ChildStdout>) {
         let pending_requests = Arc::clone(&self.pending_requests);
+        let exited = Arc::clone(&self.exited);
+        let mut server_messages = ServerMessages {
+            published_diagnostics: Arc::clone(&self.published_diagnostics),
+            progress: self.progress.clone(),
+            progress_titles: HashMap::new(),
+            backend: Arc::clone(&self.backend),
+            stdin: Arc::clone(&self.stdin),
+            wire: self.wire.clone(),
+        };
 
         // JoinHandle intentionally not stored — the task exits naturally when
         // the server's stdout closes (EOF), which happens when TyLspServer is
@@ -363,6 +1291,9 @@ impl TyLspClient {
                                 let mut content = vec![0; len];
                                 if stdout.read_exact(&mut content).await.is_ok() {
                                     if let Ok(response_str) = String::from_utf8(content) {
+                                        server_messages
+                                            .wire
+                                            .record(Direction::Received, &response_str);
                                         // Parse as generic JSON first to distinguish
                                         // responses from server-initiated messages.
                                         // Server notifications and requests have a "method"
@@ -375,13 +1306,7 @@ impl TyLspClient {
                                         match serde_json::from_str::<Value>(&response_str) {
                                             Ok(value) => {
                                                 if value.get("method").is_some() {
-                                                    let method = value
-                                                        .get("method")
-                                                        .and_then(|m| m.as_str())
-                                                        .unwrap_or("unknown");
-                                                    tracing::debug!(
-                                                        "Skipping server-initiated message: {method}"
-                                                    );
+                                                    server_messages.handle(value);
                                                     continue;
                                                 }
                                                 if let Ok(response) =
@@ -428,6 +1353,11 @@ impl TyLspClient {
                     }
                 }
             }
+
+            // Nothing will answer the requests still waiting; fail them now
+            // instead of leaving their callers hanging until they time out.
+            exited.store(true, Ordering::SeqCst);
+            pending_requests.lock().expect("pending_requests mutex poisoned").clear();
         });
     }
 }
@@ -440,14 +1370,24 @@ mod tests {
 
     #[test]
     fn initialize_params_include_src_override() {
-        let params = build_init_params("/tmp/test");
+        let params = build_init_params(
+            "/tmp/test",
+            &[],
+            &backend::TyBackend::default(),
+            &TyConfig::default(),
+        );
         let include = &params["initializationOptions"]["configuration"]["src"]["include"];
         assert_eq!(include, &serde_json::json!(["**"]));
     }
 
     #[test]
     fn initialize_params_no_other_overrides() {
-        let params = build_init_params("/tmp/test");
+        let params = build_init_params(
+            "/tmp/test",
+            &[],
+            &backend::TyBackend::default(),
+            &TyConfig::default(),
+        );
         let config = &params["initializationOptions"]["configuration"];
         // Only src should be present — no environment, rules, or other overrides
         let obj = config.as_object().expect("configuration should be an object");
@@ -455,6 +1395,76 @@ mod tests {
         assert!(obj.contains_key("src"));
     }
 
+    #[test]
+    fn initialize_params_use_project_ty_config() {
+        let config = TyConfig {
+            include: vec!["src".to_string()],
+            python_version: Some("3.12".to_string()),
+            ..TyConfig::default()
+        };
+        let params = build_init_params("/tmp/test", &[], &backend::TyBackend::default(), &config);
+        let configuration = &params["initializationOptions"]["configuration"];
+        assert_eq!(configuration["src"]["include"], serde_json::json!(["src"]));
+        assert_eq!(configuration["environment"]["python-version"], "3.12");
+    }
+
+    #[test]
+    fn initialize_params_list_workspace_folders() {
+        let params = build_init_params(
+            "/tmp/test",
+            &[],
+            &backend::TyBackend::default(),
+            &TyConfig::default(),
+        );
+        assert!(params.get("workspaceFolders").is_none());
+
+        let folders = [PathBuf::from("/mono/packages/api"), PathBuf::from("/mono/packages/worker")];
+        let params = build_init_params(
+            "/mono",
+            &folders,
+            &backend::TyBackend::default(),
+            &TyConfig::default(),
+        );
+        assert_eq!(
+            params["workspaceFolders"],
+            json!([
+                {"uri": "file:///mono/packages/api", "name": "api"},
+                {"uri": "file:///mono/packages/worker", "name": "worker"},
+            ])
+        );
+        assert_eq!(params["capabilities"]["workspace"]["workspaceFolders"], true);
+    }
+
+    #[test]
+    fn initialize_params_prefer_utf16_positions() {
+        let params = build_init_params(
+            "/tmp/test",
+            &[],
+            &backend::TyBackend::default(),
+            &TyConfig::default(),
+        );
+        assert_eq!(
+            params["capabilities"]["general"]["positionEncodings"],
+            json!(["utf-16", "utf-8", "utf-32"])
+        );
+    }
+
+    #[test]
+    fn server_requests_get_backend_configuration() {
+        let request = json!({
+            "jsonrpc": "2.0",
+            "id": 3,
+            "method": "workspace/configuration",
+            "params": {"items": [{"section": "python.analysis"}, {"section": "python"}]}
+        });
+        let result = server_request_result(&backend::PyrightBackend, &request);
+        assert_eq!(result[0]["useLibraryCodeForTypes"], true);
+        assert_eq!(result[1], Value::Null);
+
+        let register = json!({"jsonrpc": "2.0", "id": 4, "method": "client/registerCapability"});
+        assert_eq!(server_request_result(&backend::TyBackend::default(), &register), Value::Null);
+    }
+
     #[test]
     fn test_parse_response_array_with_locations() {
         let response = LSPResponse {
@@ -516,6 +1526,69 @@ mod tests {
         assert!(locations.is_empty());
     }
 
+    #[test]
+    fn test_record_published_diagnostics_replaces_earlier_ones() {
+        let store = Mutex::new(HashMap::new());
+        let notification = |messages: &[&str]| {
+            let diagnostics: Vec<Value> = messages
+                .iter()
+                .map(|message| {
+                    json!({
+                        "range": {
+                            "start": {"line": 0, "character": 0},
+                            "end": {"line": 0, "character": 1}
+                        },
+                        "severity": 1,
+                        "message": message
+                    })
+                })
+                .collect();
+            json!({
+                "jsonrpc": "2.0",
+                "method": "textDocument/publishDiagnostics",
+                "params": {"uri": "file:///app.py", "diagnostics": diagnostics}
+            })
+        };
+
+        record_published_diagnostics(&store, notification(&["a", "b"]));
+        record_published_diagnostics(&store, notification(&["c"]));
+        let store = store.into_inner().unwrap();
+        let messages: Vec<&str> =
+            store["file:///app.py"].iter().map(|d| d.message.as_str()).collect();
+        assert_eq!(messages, ["c"]);
+    }
+
+    #[test]
+    fn test_work_progress_carries_title_until_end() {
+        let notification =
+            |value: Value| json!({"method": "$/progress", "params": {"token": 7, "value": value}});
+        let mut titles = HashMap::new();
+
+        let begin = work_progress(
+            &mut titles,
+            &notification(json!({"kind": "begin", "title": "Indexing"})),
+        );
+        assert_eq!(begin.map(|p| p.title), Some("Indexing".to_string()));
+
+        let report = work_progress(
+            &mut titles,
+            &notification(json!({"kind": "report", "message": "models.py", "percentage": 40})),
+        )
+        .unwrap();
+        assert_eq!(report.title, "Indexing");
+        assert_eq!(report.message.as_deref(), Some("models.py"));
+        assert_eq!((report.percentage, report.done), (Some(40), false));
+
+        let end = work_progress(&mut titles, &notification(json!({"kind": "end"}))).unwrap();
+        assert_eq!((end.title.as_str(), end.done), ("Indexing", true));
+        assert!(titles.is_empty());
+
+        // Partial result progress has no `kind`.
+        assert!(
+            work_progress(&mut titles, &notification(json!([{"uri": "file:///a.py"}]))).is_none()
+        );
+    }
+
     #[tokio::test]
     async fn test_file_uri_valid_path() {
         let dir = tempfile::tempdir().unwrap();
@@ -0,0 +1,167 @@
+//! Documents the client has open in the language server.
+//!
+//! Each open document keeps the text the server last saw, its version, and
+//! the size and modification time of the file it was read from. That is
+//! enough to notice a file edited on disk after it was opened and to send
+//! the server only the part that changed (`textDocument/didChange`) instead
+//! of closing and reopening it.
+
+use std::path::Path;
+use std::time::SystemTime;
+
+use serde_json::Value;
+
+use crate::lsp::position::{byte_to_column, LSP_UNIT};
+use crate::lsp::protocol::{Position, Range};
+
+/// Size and modification time of a file, compared to spot edits on disk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FileStamp {
+    len: u64,
+    modified: Option<SystemTime>,
+}
+
+impl FileStamp {
+    /// Stamp of `path` as it is now; `None` if it can't be read.
+    pub async fn of(path: &Path) -> Option<Self> {
+        let metadata = tokio::fs::metadata(path).await.ok()?;
+        Some(Self { len: metadata.len(), modified: metadata.modified().ok() })
+    }
+}
+
+/// A document open in the server.
+#[derive(Debug)]
+pub struct OpenDocument {
+    /// Version of the last `didOpen`/`didChange`; starts at 1.
+    pub version: i32,
+    /// The text the server has for the document.
+    pub text: String,
+    /// Stamp of the file `text` was read from; `None` for text pushed by a
+    /// caller, which is not compared against the disk.
+    pub stamp: Option<FileStamp>,
+}
+
+impl OpenDocument {
+    pub const fn new(text: String, stamp: Option<FileStamp>) -> Self {
+        Self { version: 1, text, stamp }
+    }
+
+    /// Replace the text with `text`, returning the new version and the
+    /// `contentChanges` entry describing the edit.
+    ///
+    /// With `incremental`, the change covers only the span between the
+    /// common prefix and suffix of the old and new text; otherwise it is the
+    /// whole document.
+    pub fn update(
+        &mut self,
+        text: String,
+        stamp: Option<FileStamp>,
+        incremental: bool,
+    ) -> (i32, Value) {
+        let change = if incremental {
+            let (range, inserted) = changed_span(&self.text, &text);
+            serde_json::json!({ "range": range, "text": inserted })
+        } else {
+            serde_json::json!({ "text": text })
+        };
+        self.version += 1;
+        self.text = text;
+        self.stamp = stamp;
+        (self.version, change)
+    }
+}
+
+/// Whether a server's `textDocumentSync` capability accepts incremental
+/// changes (`TextDocumentSyncKind.Incremental`, 2).
+pub fn supports_incremental_sync(capabilities: &Value) -> bool {
+    let sync = &capabilities["textDocumentSync"];
+    sync.as_u64().or_else(|| sync["change"].as_u64()) == Some(2)
+}
+
+/// The range of `old` that differs from `new`, and the text replacing it.
+fn changed_span<'a>(old: &str, new: &'a str) -> (Range, &'a str) {
+    let prefix = old
+        .char_indices()
+        .zip(new.chars())
+        .find(|((_, a), b)| a != b)
+        .map_or_else(|| old.len().min(new.len()), |((i, _), _)| i);
+    let max_suffix = old.len().min(new.len()) - prefix;
+    let suffix = old[prefix..]
+        .chars()
+        .rev()
+        .zip(new[prefix..].chars().rev())
+        .take_while(|(a, b)| a == b)
+        .map(|(a, _)| a.len_utf8())
+        .scan(0, |total, len| {
+            *total += len;
+            Some(*total)
+        })
+        .take_while(|&total| total <= max_suffix)
+        .last()
+        .unwrap_or(0);
+    let range =
+        Range { start: position_at(old, prefix), end: position_at(old, old.len() - suffix) };
+    (range, &new[prefix..new.len() - suffix])
+}
+
+/// LSP position of byte offset `byte` in `text`.
+fn position_at(text: &str, byte: usize) -> Position {
+    let before = &text[..byte];
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    let line_end = text[line_start..].find('\n').map_or(text.len(), |i| line_start + i);
+    let line = u32::try_from(before.matches('\n').count()).unwrap_or(u32::MAX);
+    let character = byte_to_column(&text[line_start..line_end], byte - line_start, LSP_UNIT);
+    Position { line, character }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn span(old: &str, new: &str) -> ((u32, u32, u32, u32), String) {
+        let (range, text) = changed_span(old, new);
+        let r = (range.start.line, range.start.character, range.end.line, range.end.character);
+        (r, text.to_string())
+    }
+
+    #[test]
+    fn changed_span_covers_only_the_edit() {
+        assert_eq!(span("x = 1\ny = 2\n", "x = 1\ny = 3\n"), ((1, 4, 1, 5), "3".to_string()));
+        assert_eq!(span("a\n", "a\nb\n"), ((1, 0, 1, 0), "b\n".to_string()));
+        assert_eq!(span("a\nb\n", "a\n"), ((1, 0, 2, 0), String::new()));
+        assert_eq!(span("same", "same"), ((0, 4, 0, 4), String::new()));
+        // Repeated characters: the suffix must not overlap the prefix.
+        assert_eq!(span("aaa", "aaaa"), ((0, 3, 0, 3), "a".to_string()));
+    }
+
+    #[test]
+    fn changed_span_counts_utf16_units() {
+        // 🎉 is two UTF-16 units, é one.
+        assert_eq!(span("s = '🎉é'\n", "s = '🎉e'\n"), ((0, 7, 0, 8), "e".to_string()));
+    }
+
+    #[test]
+    fn update_bumps_the_version() {
+        let mut doc = OpenDocument::new("x = 1\n".to_string(), None);
+        let (version, change) = doc.update("x = 2\n".to_string(), None, true);
+        assert_eq!(version, 2);
+        assert_eq!(change["text"], "2");
+        assert_eq!(change["range"]["start"], json!({"line": 0, "character": 4}));
+
+        let (version, change) = doc.update("y = 2\n".to_string(), None, false);
+        assert_eq!(version, 3);
+        assert_eq!(change, json!({"text": "y = 2\n"}));
+        assert_eq!(doc.text, "y = 2\n");
+    }
+
+    #[test]
+    fn reads_sync_kind() {
+        assert!(supports_incremental_sync(&json!({"textDocumentSync": 2})));
+        assert!(supports_incremental_sync(
+            &json!({"textDocumentSync": {"openClose": true, "change": 2}})
+        ));
+        assert!(!supports_incremental_sync(&json!({"textDocumentSync": 1})));
+        assert!(!supports_incremental_sync(&json!({})));
+    }
+}
@@ -0,0 +1,124 @@
+//! Recognizing the files tyf can hand to ty.
+//!
+//! ty only analyzes Python. Sending it anything else (a README, a compiled
+//! `.pyc`, a Rust file picked by a typo) produces empty results that look like
+//! "symbol not found", so files are checked up front instead.
+
+use std::io::Read;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+
+/// Extensions ty analyzes.
+const PYTHON_EXTENSIONS: [&str; 3] = ["py", "pyi", "pyw"];
+
+/// How many leading bytes are inspected for binary content and a shebang.
+pub const SNIFF_LEN: usize = 8192;
+
+/// LSP `languageId` for `path`, from its extension.
+///
+/// The LSP spec has a single identifier for Python source, stubs (`.pyi`) and
+/// windowed scripts (`.pyw`) alike. Returns `None` for other extensions.
+pub fn language_id(path: &Path) -> Option<&'static str> {
+    let ext = path.extension()?.to_str()?.to_ascii_lowercase();
+    PYTHON_EXTENSIONS.contains(&ext.as_str()).then_some("python")
+}
+
+/// Whether the leading bytes of a file look binary (contain a NUL byte).
+fn looks_binary(head: &[u8]) -> bool {
+    head.contains(&0)
+}
+
+/// Whether the leading bytes start with a Python shebang (`#!/usr/bin/env python3`).
+fn has_python_shebang(head: &[u8]) -> bool {
+    head.strip_prefix(b"#!").is_some_and(|rest| {
+        let first_line = rest.split(|&b| b == b'\n').next().unwrap_or_default();
+        String::from_utf8_lossy(first_line).contains("python")
+    })
+}
+
+/// Check that `path` is a Python file ty can analyze, returning its `languageId`.
+///
+/// Accepts `.py`, `.pyi` and `.pyw` files, and extensionless scripts with a
+/// Python shebang. Rejects binary files even with a Python extension.
+pub fn check_python_file(path: &Path) -> Result<&'static str> {
+    let mut head = Vec::new();
+    std::fs::File::open(path)
+        .and_then(|file| file.take(SNIFF_LEN as u64).read_to_end(&mut head))
+        .with_context(|| format!("Failed to read file: {}", path.display()))?;
+    check_python_source(path, &head)
+}
+
+/// Like [`check_python_file`], for a file whose leading bytes are already in memory.
+pub fn check_python_source(path: &Path, head: &[u8]) -> Result<&'static str> {
+    if looks_binary(head) {
+        anyhow::bail!(
+            "{} looks like a binary file, not Python source; tyf only reads .py, .pyi and .pyw files",
+            path.display()
+        );
+    }
+    if let Some(id) = language_id(path) {
+        return Ok(id);
+    }
+    match path.extension() {
+        None if has_python_shebang(head) => Ok("python"),
+        Some(ext) => anyhow::bail!(
+            "{} is not a Python file (.{}); tyf only reads .py, .pyi and .pyw files",
+            path.display(),
+            ext.to_string_lossy()
+        ),
+        None => anyhow::bail!(
+            "{} is not a Python file (no .py extension or python shebang)",
+            path.display()
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_language_id_for_python_extensions() {
+        assert_eq!(language_id(Path::new("app.py")), Some("python"));
+        assert_eq!(language_id(Path::new("stubs/os.pyi")), Some("python"));
+        assert_eq!(language_id(Path::new("gui.PYW")), Some("python"));
+        assert_eq!(language_id(Path::new("README.md")), None);
+        assert_eq!(language_id(Path::new("Makefile")), None);
+    }
+
+    #[test]
+    fn test_rejects_other_extensions() {
+        let err = check_python_source(Path::new("src/main.rs"), b"fn main() {}").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "src/main.rs is not a Python file (.rs); tyf only reads .py, .pyi and .pyw files"
+        );
+    }
+
+    #[test]
+    fn test_rejects_binary_content_with_python_extension() {
+        let err = check_python_source(Path::new("mod.py"), b"\x00\x01\x02garbage").unwrap_err();
+        assert!(err.to_string().contains("looks like a binary file"), "unexpected: {err}");
+    }
+
+    #[test]
+    fn test_accepts_extensionless_script_with_python_shebang() {
+        assert_eq!(
+            check_python_source(Path::new("bin/tool"), b"#!/usr/bin/env python3\nimport os\n")
+                .unwrap(),
+            "python"
+        );
+        assert!(check_python_source(Path::new("bin/tool"), b"#!/bin/sh\necho hi\n").is_err());
+    }
+
+    #[test]
+    fn test_check_python_file_reads_from_disk() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("data.bin");
+        std::fs::write(&path, [0u8, 159, 146, 150]).unwrap();
+
+        assert!(check_python_file(&path).is_err());
+        assert!(check_python_file(&dir.path().join("missing.py")).is_err());
+    }
+}
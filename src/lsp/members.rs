@@ -0,0 +1,774 @@
+//! Class members with their signatures, as listed by `tyf members`.
+//!
+//! A class is looked up by name and its members read from the document
+//! symbols of the file that defines it. Any other expression, given by
+//! position, gets the attributes of its inferred type from ty's completions
+//! after a `.` typed behind it.
+
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::lsp::client::TyLspClient;
+use crate::lsp::position::{byte_to_column, column_to_byte, ColumnUnit};
+use crate::lsp::protocol::{
+    CompletionItem, CompletionItemKind, DocumentSymbol, HoverContents, MarkedStringOrString,
+    Position, SymbolKind,
+};
+use crate::lsp::uri;
+use crate::lsp::warmup::{hover_with_warmup, with_warmup, WARMUP_DELAYS};
+use crate::workspace::navigation::is_identifier_char;
+
+/// Information about a single class member.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct MemberInfo {
+    /// Member name (e.g. `calculate_total`, `name`, `MAX_RETRIES`)
+    pub name: String,
+
+    /// LSP symbol kind (Method, Property, Variable, etc.)
+    pub kind: SymbolKind,
+
+    /// Type signature from hover (e.g. "def add(self, a, b) -> int")
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub signature: Option<String>,
+
+    /// Line number (0-based); unknown for members of an expression
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub line: Option<u32>,
+
+    /// Column number (0-based); unknown for members of an expression
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub column: Option<u32>,
+}
+
+/// Result of a members request.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct MembersResult {
+    /// The class name
+    pub class_name: String,
+
+    /// File URI (file:///...)
+    pub file_uri: String,
+
+    /// Class definition line, or the expression's line (0-based)
+    pub class_line: u32,
+
+    /// Class definition column, or the expression's column (0-based)
+    pub class_column: u32,
+
+    /// The kind of the resolved symbol (None if not found)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub symbol_kind: Option<SymbolKind>,
+
+    /// Whether this lists the attributes of an expression rather than of a
+    /// class found by name
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub expression: bool,
+
+    /// Class members grouped by kind
+    pub members: Vec<MemberInfo>,
+}
+
+/// List the members of `class_name` defined in `file`, which must be open.
+///
+/// Retrieves document symbols for the file, finds the target class,
+/// extracts its children, and calls hover on each to get type signatures.
+/// This is N+1 LSP calls per class (1 documentSymbol + N hovers).
+///
+/// When the file has no such symbol, or it isn't a class, the result has no
+/// members and `symbol_kind` tells which.
+pub async fn class_members(
+    client: &TyLspClient,
+    file: &str,
+    class_name: &str,
+    include_all: bool,
+) -> Result<MembersResult> {
+    let doc_symbols = client.document_symbols(file).await?;
+
+    // Find the target class anywhere in the symbol tree (may be nested)
+    let target = find_symbol_recursive(&doc_symbols, class_name);
+
+    let Some(class_sym) = target else {
+        // Symbol not found in file
+        let result = MembersResult {
+            class_name: class_name.to_string(),
+            file_uri: file.to_string(),
+            class_line: 0,
+            class_column: 0,
+            symbol_kind: None,
+            expression: false,
+            members: Vec::new(),
+        };
+        return Ok(result);
+    };
+
+    // Check that it's actually a class
+    if !matches!(class_sym.kind, SymbolKind::Class) {
+        let result = MembersResult {
+            class_name: class_name.to_string(),
+            file_uri: file.to_string(),
+            class_line: class_sym.selection_range.start.line,
+            class_column: class_sym.selection_range.start.character,
+            symbol_kind: Some(class_sym.kind.clone()),
+            expression: false,
+            members: Vec::new(),
+        };
+        return Ok(result);
+    }
+
+    let children = class_sym.children.as_deref().unwrap_or(&[]);
+
+    // Filter members based on include_all flag
+    let filtered: Vec<_> = children
+        .iter()
+        .filter(|child| {
+            if include_all {
+                return true;
+            }
+            // Exclude private (_prefixed) and dunder (__dunder__) members
+            !child.name.starts_with('_')
+        })
+        .collect();
+
+    // Get hover info for each member (N LSP calls — sequential, single pipe)
+    let mut members = Vec::with_capacity(filtered.len());
+    for child in &filtered {
+        let hover_line = child.selection_range.start.line;
+        let hover_col = child.selection_range.start.character;
+        let hover = hover_with_warmup(client, file, hover_line, hover_col).await?;
+
+        let signature = hover.as_ref().map(|h| extract_member_signature(&h.contents, &child.name));
+
+        members.push(MemberInfo {
+            name: child.name.clone(),
+            kind: child.kind.clone(),
+            signature,
+            line: Some(child.selection_range.start.line),
+            column: Some(child.selection_range.start.character),
+        });
+    }
+
+    let result = MembersResult {
+        class_name: class_name.to_string(),
+        file_uri: file.to_string(),
+        class_line: class_sym.selection_range.start.line,
+        class_column: class_sym.selection_range.start.character,
+        symbol_kind: Some(class_sym.kind.clone()),
+        expression: false,
+        members,
+    };
+    Ok(result)
+}
+
+/// Tells apart the scratch copies of concurrent [`expression_members`] requests.
+static SCRATCH_ID: AtomicU64 = AtomicU64::new(0);
+
+/// List the attributes of the expression at `line`/`column` (0-based, UTF-16)
+/// in `file`: whatever ty infers it to be, be it an instance, a module or a union.
+///
+/// Asks ty for completions after a `.` typed right behind the expression, in
+/// a scratch copy of the file next to it, so the file itself stays as it is.
+/// The expression ends with the identifier or closing bracket at the position.
+pub async fn expression_members(
+    client: &TyLspClient,
+    file: &str,
+    line: u32,
+    column: u32,
+    include_all: bool,
+) -> Result<MembersResult> {
+    let text = tokio::fs::read_to_string(file)
+        .await
+        .with_context(|| format!("Failed to read file: {file}"))?;
+    let mut result = MembersResult {
+        class_name: format!("{file}:{}:{}", line + 1, column + 1),
+        file_uri: file.to_string(),
+        class_line: line,
+        class_column: column,
+        symbol_kind: None,
+        expression: false,
+        members: Vec::new(),
+    };
+    // Nothing to complete on: reported like a name that wasn't found
+    let Some((expression, scratch_text, dot)) = insert_member_access(&text, line, column) else {
+        return Ok(result);
+    };
+    result.class_name = expression;
+    result.expression = true;
+
+    let path = Path::new(file);
+    let id = SCRATCH_ID.fetch_add(1, Ordering::Relaxed);
+    let scratch_name = format!(".tyf-members-{}-{id}.py", std::process::id());
+    let scratch_uri = uri::path_to_uri(&path.with_file_name(scratch_name));
+    client.open_scratch_document(&scratch_uri, &scratch_text).await?;
+    let items = with_warmup(
+        "completion",
+        &WARMUP_DELAYS,
+        |items: &Vec<CompletionItem>| !items.is_empty(),
+        || client.completion(&scratch_uri, dot.line, dot.character),
+        None,
+    )
+    .await;
+    client.close_scratch_document(&scratch_uri).await?;
+
+    result.members = items?
+        .into_iter()
+        .filter(|item| include_all || !item.label.starts_with('_'))
+        .filter_map(completion_member)
+        .collect();
+    Ok(result)
+}
+
+/// Where a `.` goes after the expression at `line`/`column`: the expression's
+/// source text, the text with the `.` inserted, and the position right after it.
+///
+/// `None` when the position isn't on an identifier or a closing bracket.
+fn insert_member_access(text: &str, line: u32, column: u32) -> Option<(String, String, Position)> {
+    let line_start: usize = text.split_inclusive('\n').take(line as usize).map(str::len).sum();
+    let line_text = text[line_start..].lines().next()?;
+    let at = column_to_byte(line_text, column, ColumnUnit::Utf16);
+    let ch = line_text[at..].chars().next()?;
+    let end = if is_identifier_char(ch) {
+        line_text[at..].find(|ch| !is_identifier_char(ch)).map_or(line_text.len(), |len| at + len)
+    } else if matches!(ch, ')' | ']' | '}') {
+        at + 1
+    } else {
+        return None;
+    };
+    let start = expression_start(&line_text[..end]);
+
+    let insert = line_start + end;
+    let scratch = format!("{}.{}", &text[..insert], &text[insert..]);
+    let dot = Position { line, character: byte_to_column(line_text, end, ColumnUnit::Utf16) + 1 };
+    Some((line_text[start..end].to_string(), scratch, dot))
+}
+
+/// Byte offset where the expression ending at the end of `prefix` starts:
+/// back over identifiers, dots and balanced brackets.
+fn expression_start(prefix: &str) -> usize {
+    let mut depth = 0usize;
+    for (i, ch) in prefix.char_indices().rev() {
+        match ch {
+            ')' | ']' | '}' => depth += 1,
+            '(' | '[' | '{' if depth > 0 => depth -= 1,
+            _ if depth > 0 || is_identifier_char(ch) || ch == '.' => {}
+            _ => return i + ch.len_utf8(),
+        }
+    }
+    0
+}
+
+/// A completion item as a member; `None` for keywords and snippets.
+fn completion_member(item: CompletionItem) -> Option<MemberInfo> {
+    let kind = match item.kind {
+        Some(CompletionItemKind::Keyword | CompletionItemKind::Snippet) => return None,
+        Some(CompletionItemKind::Method) => SymbolKind::Method,
+        Some(CompletionItemKind::Function) => SymbolKind::Function,
+        Some(CompletionItemKind::Constructor) => SymbolKind::Constructor,
+        Some(CompletionItemKind::Field) => SymbolKind::Field,
+        Some(CompletionItemKind::Class) => SymbolKind::Class,
+        Some(CompletionItemKind::Interface) => SymbolKind::Interface,
+        Some(CompletionItemKind::Module) => SymbolKind::Module,
+        Some(CompletionItemKind::Property) => SymbolKind::Property,
+        Some(CompletionItemKind::Enum) => SymbolKind::Enum,
+        Some(CompletionItemKind::EnumMember) => SymbolKind::EnumMember,
+        Some(CompletionItemKind::Constant) => SymbolKind::Constant,
+        Some(CompletionItemKind::Struct) => SymbolKind::Struct,
+        Some(CompletionItemKind::TypeParameter) => SymbolKind::TypeParameter,
+        _ => SymbolKind::Variable,
+    };
+    let detail = item.detail.or_else(|| {
+        let details = item.label_details?;
+        details.description.or(details.detail)
+    });
+    let signature = detail
+        .filter(|detail| !detail.is_empty())
+        .map(|detail| extract_member_signature(&HoverContents::Scalar(detail), &item.label));
+    Some(MemberInfo { name: item.label, kind, signature, line: None, column: None })
+}
+
+/// Recursively search document symbols for a symbol with the given name.
+///
+/// `document_symbols` returns a hierarchical tree — classes nested inside
+/// other classes or functions only appear as children, not at the top level.
+pub fn find_symbol_recursive<'a>(
+    symbols: &'a [DocumentSymbol],
+    name: &str,
+) -> Option<&'a DocumentSymbol> {
+    for s in symbols {
+        if s.name == name {
+            return Some(s);
+        }
+        if let Some(children) = &s.children {
+            if let Some(found) = find_symbol_recursive(children, name) {
+                return Some(found);
+            }
+        }
+    }
+    None
+}
+
+/// Extract a clean member signature from hover contents.
+///
+/// ty's hover markdown looks like:
+///   ```python\ndef method(self, x: int) -> str\n```\n---\nDocstring
+///
+/// We want just the signature: `method(self, x: int) -> str`
+///
+/// `member_name` is used to prefix bare type signatures (e.g. class
+/// variables where ty returns just the type like `int`).
+pub fn extract_member_signature(contents: &HoverContents, member_name: &str) -> String {
+    let full = match contents {
+        HoverContents::Scalar(s) => s.clone(),
+        HoverContents::Markup(markup) => markup.value.clone(),
+        HoverContents::MarkedString(ms) => ms.value.clone(),
+        HoverContents::Array(arr) => arr
+            .iter()
+            .map(|item| match item {
+                MarkedStringOrString::String(s) => s.clone(),
+                MarkedStringOrString::MarkedString(ms) => ms.value.clone(),
+            })
+            .collect::<Vec<_>>()
+            .join("\n"),
+    };
+
+    // Strip docstring (everything after "\n---")
+    let type_part = match full.find("\n---") {
+        Some(pos) => &full[..pos],
+        None => &full,
+    };
+
+    // Strip markdown code fences
+    let trimmed = type_part.trim();
+    let cleaned = trimmed
+        .strip_prefix("```python")
+        .or_else(|| trimmed.strip_prefix("```xml"))
+        .or_else(|| trimmed.strip_prefix("```text"))
+        .or_else(|| trimmed.strip_prefix("```"))
+        .unwrap_or(trimmed);
+
+    let cleaned = cleaned.trim().strip_suffix("```").unwrap_or(cleaned).trim();
+
+    // Strip leading `def ` for method signatures — show just `name(params) -> ret`
+    let cleaned = cleaned.strip_prefix("def ").unwrap_or(cleaned);
+
+    // Strip leading `(method) `, `(property) `, etc. prefixes ty may add
+    let cleaned = if let Some(rest) = cleaned.strip_prefix('(') {
+        if let Some(pos) = rest.find(") ") {
+            let after = &rest[pos + 2..];
+            after.strip_prefix("def ").unwrap_or(after)
+        } else {
+            cleaned
+        }
+    } else {
+        cleaned
+    };
+
+    // Collapse multi-line signatures (ty wraps long parameter lists)
+    // e.g. "assist(\n    self,\n    task: str\n) -> str" → "assist(self, task: str) -> str"
+    let cleaned = collapse_signature(cleaned);
+
+    // If the signature is a bare type (no name prefix, no parens), prepend
+    // the member name so it reads like `registry: dict[str, X]` instead of
+    // just `dict[str, X]`.
+    if !cleaned.contains('(') && !cleaned.contains(':') && cleaned != member_name {
+        format!("{member_name}: {cleaned}")
+    } else {
+        cleaned
+    }
+}
+
+/// Collapse a multi-line signature into a single line.
+///
+/// ty formats long signatures like:
+/// ```text
+/// assist(
+///     self,
+///     task: str,
+///     duration_minutes: int = 30
+/// ) -> str
+/// ```
+///
+/// This collapses them to: `assist(self, task: str, duration_minutes: int = 30) -> str`
+fn collapse_signature(sig: &str) -> String {
+    if !sig.contains('\n') {
+        return sig.to_string();
+    }
+    // Replace newlines + surrounding whitespace with a single space,
+    // then clean up spaces around parentheses and commas.
+    let collapsed: String = sig.lines().map(str::trim).collect::<Vec<_>>().join(" ");
+    // Normalize "( " → "(", " )" → ")", ",  " → ", "
+    let collapsed = collapsed.replace("( ", "(").replace(" )", ")");
+    // Collapse multiple spaces
+    let mut result = String::with_capacity(collapsed.len());
+    let mut prev_space = false;
+    for ch in collapsed.chars() {
+        if ch == ' ' {
+            if !prev_space {
+                result.push(ch);
+            }
+            prev_space = true;
+        } else {
+            prev_space = false;
+            result.push(ch);
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_insert_member_access_after_identifier() {
+        let text = "import os\ntotal = order.items[0].price\n";
+        // On `items`: the expression is `order.items`
+        let (expression, scratch, dot) = insert_member_access(text, 1, 15).unwrap();
+        assert_eq!(expression, "order.items");
+        assert_eq!(scratch, "import os\ntotal = order.items.[0].price\n");
+        assert_eq!((dot.line, dot.character), (1, 20));
+
+        // On the closing bracket: the whole subscript
+        let (expression, _, dot) = insert_member_access(text, 1, 21).unwrap();
+        assert_eq!(expression, "order.items[0]");
+        assert_eq!(dot.character, 23);
+    }
+
+    #[test]
+    fn test_insert_member_access_rejects_other_positions() {
+        assert!(insert_member_access("x = 1\n", 0, 2).is_none());
+        assert!(insert_member_access("x = 1\n", 0, 40).is_none());
+        assert!(insert_member_access("x = 1\n", 5, 0).is_none());
+    }
+
+    #[test]
+    fn test_insert_member_access_utf16_columns() {
+        // "é" is one UTF-16 unit but two UTF-8 bytes
+        let (expression, scratch, dot) = insert_member_access("s = 'é'; café.x\n", 0, 9).unwrap();
+        assert_eq!(expression, "café");
+        assert_eq!(scratch, "s = 'é'; café..x\n");
+        assert_eq!(dot.character, 14);
+    }
+
+    #[test]
+    fn test_completion_member_kinds_and_signature() {
+        let item = |label: &str, kind, detail: Option<&str>| CompletionItem {
+            label: label.to_string(),
+            kind,
+            detail: detail.map(str::to_string),
+            label_details: None,
+        };
+        let method = completion_member(item(
+            "total",
+            Some(CompletionItemKind::Method),
+            Some("def total(self) -> int"),
+        ))
+        .unwrap();
+        assert!(matches!(method.kind, SymbolKind::Method));
+        assert_eq!(method.signature.as_deref(), Some("total(self) -> int"));
+        assert_eq!(method.line, None);
+
+        let field = completion_member(item("name", None, Some("str"))).unwrap();
+        assert!(matches!(field.kind, SymbolKind::Variable));
+        assert_eq!(field.signature.as_deref(), Some("name: str"));
+
+        assert!(completion_member(item("if", Some(CompletionItemKind::Keyword), None)).is_none());
+    }
+
+    #[test]
+    fn test_find_symbol_recursive_top_level() {
+        use crate::lsp::protocol::{DocumentSymbol, Position, Range, SymbolKind};
+
+        let range = Range {
+            start: Position { line: 0, character: 0 },
+            end: Position { line: 5, character: 0 },
+        };
+        let symbols = vec![DocumentSymbol {
+            name: "Animal".to_string(),
+            detail: None,
+            kind: SymbolKind::Class,
+            tags: None,
+            deprecated: None,
+            range: range.clone(),
+            selection_range: range,
+            children: None,
+        }];
+
+        let found = find_symbol_recursive(&symbols, "Animal");
+        assert!(found.is_some());
+        assert_eq!(found.unwrap().name, "Animal");
+
+        let not_found = find_symbol_recursive(&symbols, "Dog");
+        assert!(not_found.is_none());
+    }
+
+    #[test]
+    fn test_find_symbol_recursive_nested() {
+        use crate::lsp::protocol::{DocumentSymbol, Position, Range, SymbolKind};
+
+        let range = Range {
+            start: Position { line: 0, character: 0 },
+            end: Position { line: 20, character: 0 },
+        };
+        let inner_range = Range {
+            start: Position { line: 10, character: 4 },
+            end: Position { line: 15, character: 0 },
+        };
+
+        let nested_class = DocumentSymbol {
+            name: "InnerWidget".to_string(),
+            detail: None,
+            kind: SymbolKind::Class,
+            tags: None,
+            deprecated: None,
+            range: inner_range.clone(),
+            selection_range: inner_range,
+            children: None,
+        };
+
+        let outer_class = DocumentSymbol {
+            name: "OuterPanel".to_string(),
+            detail: None,
+            kind: SymbolKind::Class,
+            tags: None,
+            deprecated: None,
+            range: range.clone(),
+            selection_range: range,
+            children: Some(vec![nested_class]),
+        };
+
+        let symbols = vec![outer_class];
+
+        // Should find the nested class
+        let found = find_symbol_recursive(&symbols, "InnerWidget");
+        assert!(found.is_some());
+        assert_eq!(found.unwrap().name, "InnerWidget");
+
+        // Should still find the outer class
+        let found = find_symbol_recursive(&symbols, "OuterPanel");
+        assert!(found.is_some());
+    }
+
+    #[test]
+    fn test_extract_member_signature_method() {
+        use crate::lsp::protocol::{HoverContents, MarkupContent, MarkupKind};
+
+        let contents = HoverContents::Markup(MarkupContent {
+            kind: MarkupKind::Markdown,
+            value: "```python\ndef speak(self) -> str\n```".to_string(),
+        });
+        let sig = extract_member_signature(&contents, "speak");
+        assert_eq!(sig, "speak(self) -> str");
+    }
+
+    #[test]
+    fn test_extract_member_signature_property() {
+        use crate::lsp::protocol::{HoverContents, MarkupContent, MarkupKind};
+
+        let contents = HoverContents::Markup(MarkupContent {
+            kind: MarkupKind::Markdown,
+            value: "```python\n(property) name: str\n```".to_string(),
+        });
+        let sig = extract_member_signature(&contents, "name");
+        assert_eq!(sig, "name: str");
+    }
+
+    #[test]
+    fn test_extract_member_signature_with_docstring() {
+        use crate::lsp::protocol::{HoverContents, MarkupContent, MarkupKind};
+
+        let contents = HoverContents::Markup(MarkupContent {
+            kind: MarkupKind::Markdown,
+            value: "```python\ndef describe(self) -> str\n```\n---\nDescribe the animal."
+                .to_string(),
+        });
+        let sig = extract_member_signature(&contents, "describe");
+        assert_eq!(sig, "describe(self) -> str");
+        assert!(!sig.contains("Describe"));
+    }
+
+    #[test]
+    fn test_extract_member_signature_class_variable() {
+        use crate::lsp::protocol::{HoverContents, MarkupContent, MarkupKind};
+
+        let contents = HoverContents::Markup(MarkupContent {
+            kind: MarkupKind::Markdown,
+            value: "```python\nMAX_LEGS: int\n```".to_string(),
+        });
+        let sig = extract_member_signature(&contents, "MAX_LEGS");
+        assert_eq!(sig, "MAX_LEGS: int");
+    }
+
+    #[test]
+    fn test_extract_member_signature_scalar() {
+        use crate::lsp::protocol::HoverContents;
+
+        let contents = HoverContents::Scalar("int".to_string());
+        let sig = extract_member_signature(&contents, "count");
+        assert_eq!(sig, "count: int");
+    }
+
+    #[test]
+    fn test_extract_member_signature_multiline() {
+        use crate::lsp::protocol::{HoverContents, MarkupContent, MarkupKind};
+
+        let contents = HoverContents::Markup(MarkupContent {
+            kind: MarkupKind::Markdown,
+            value: "```python\ndef assist(\n    self,\n    task: str,\n    duration_minutes: int = 30\n) -> str\n```".to_string(),
+        });
+        let sig = extract_member_signature(&contents, "assist");
+        assert_eq!(sig, "assist(self, task: str, duration_minutes: int = 30) -> str");
+    }
+
+    #[test]
+    fn test_extract_member_signature_bare_type() {
+        use crate::lsp::protocol::{HoverContents, MarkupContent, MarkupKind};
+
+        let contents = HoverContents::Markup(MarkupContent {
+            kind: MarkupKind::Markdown,
+            value: "```python\ndict[str, ServiceDog]\n```".to_string(),
+        });
+        let sig = extract_member_signature(&contents, "registry");
+        assert_eq!(sig, "registry: dict[str, ServiceDog]");
+    }
+
+    #[test]
+    fn test_extract_member_signature_bare_property() {
+        use crate::lsp::protocol::{HoverContents, MarkupContent, MarkupKind};
+
+        let contents = HoverContents::Markup(MarkupContent {
+            kind: MarkupKind::Markdown,
+            value: "```python\nproperty\n```".to_string(),
+        });
+        let sig = extract_member_signature(&contents, "is_certified");
+        assert_eq!(sig, "is_certified: property");
+    }
+
+    #[test]
+    fn test_extract_member_signature_xml_fence() {
+        use crate::lsp::protocol::{HoverContents, MarkupContent, MarkupKind};
+
+        let contents = HoverContents::Markup(MarkupContent {
+            kind: MarkupKind::Markdown,
+            value: "```xml\n<element>text</element>\n```".to_string(),
+        });
+        let sig = extract_member_signature(&contents, "config");
+        // The XML content doesn't start with def/class, so it should be treated as bare type
+        assert!(sig.contains("config"));
+    }
+
+    #[test]
+    fn test_extract_member_signature_text_fence() {
+        use crate::lsp::protocol::{HoverContents, MarkupContent, MarkupKind};
+
+        let contents = HoverContents::Markup(MarkupContent {
+            kind: MarkupKind::Markdown,
+            value: "```text\nstr | None\n```".to_string(),
+        });
+        let sig = extract_member_signature(&contents, "value");
+        assert_eq!(sig, "value: str | None");
+    }
+
+    #[test]
+    fn test_extract_member_signature_method_prefix() {
+        use crate::lsp::protocol::{HoverContents, MarkupContent, MarkupKind};
+
+        let contents = HoverContents::Markup(MarkupContent {
+            kind: MarkupKind::Markdown,
+            value: "```python\n(method) def foo(self) -> int\n```".to_string(),
+        });
+        let sig = extract_member_signature(&contents, "foo");
+        assert_eq!(sig, "foo(self) -> int");
+    }
+
+    #[test]
+    fn test_extract_member_signature_array_hover() {
+        use crate::lsp::protocol::{HoverContents, MarkedString, MarkedStringOrString};
+
+        let contents = HoverContents::Array(vec![
+            MarkedStringOrString::MarkedString(MarkedString {
+                language: "python".to_string(),
+                value: "def greet(self) -> str".to_string(),
+            }),
+            MarkedStringOrString::String("A greeting method".to_string()),
+        ]);
+        let sig = extract_member_signature(&contents, "greet");
+        // Array hover concatenates all entries; the function signature is extracted and
+        // the plain-text description is appended after it.
+        assert_eq!(sig, "greet(self) -> str A greeting method");
+    }
+
+    #[test]
+    fn test_collapse_signature_single_line() {
+        let sig = "def foo(self, x: int) -> str";
+        let result = collapse_signature(sig);
+        assert_eq!(result, sig);
+    }
+
+    #[test]
+    fn test_collapse_signature_multiline_complex() {
+        let sig = "def process(\n    self,\n    data: list[dict[str, Any]],\n    timeout: int = 30,\n    retries: int = 3\n) -> Result[str, Error]";
+        let result = collapse_signature(sig);
+        assert_eq!(
+            result,
+            "def process(self, data: list[dict[str, Any]], timeout: int = 30, retries: int = 3) -> Result[str, Error]"
+        );
+    }
+
+    #[test]
+    fn test_find_symbol_recursive_empty_tree() {
+        let symbols: Vec<DocumentSymbol> = vec![];
+        let found = find_symbol_recursive(&symbols, "anything");
+        assert!(found.is_none());
+    }
+
+    #[test]
+    fn test_find_symbol_recursive_deeply_nested() {
+        use crate::lsp::protocol::{DocumentSymbol, Position, Range, SymbolKind};
+
+        let range = Range {
+            start: Position { line: 0, character: 0 },
+            end: Position { line: 30, character: 0 },
+        };
+
+        let deepest = DocumentSymbol {
+            name: "deep_method".to_string(),
+            detail: None,
+            kind: SymbolKind::Method,
+            tags: None,
+            deprecated: None,
+            range: range.clone(),
+            selection_range: range.clone(),
+            children: None,
+        };
+
+        let middle = DocumentSymbol {
+            name: "InnerClass".to_string(),
+            detail: None,
+            kind: SymbolKind::Class,
+            tags: None,
+            deprecated: None,
+            range: range.clone(),
+            selection_range: range.clone(),
+            children: Some(vec![deepest]),
+        };
+
+        let outer = DocumentSymbol {
+            name: "OuterClass".to_string(),
+            detail: None,
+            kind: SymbolKind::Class,
+            tags: None,
+            deprecated: None,
+            range: range.clone(),
+            selection_range: range,
+            children: Some(vec![middle]),
+        };
+
+        let symbols = vec![outer];
+        let found = find_symbol_recursive(&symbols, "deep_method");
+        assert!(found.is_some());
+        assert_eq!(found.unwrap().name, "deep_method");
+    }
+}
@@ -1,3 +1,18 @@
+#[cfg(feature = "lsp-client")]
+pub mod backend;
+#[cfg(feature = "lsp-client")]
 pub mod client;
+#[cfg(feature = "lsp-client")]
+pub mod documents;
+pub mod language;
+#[cfg(feature = "lsp-client")]
+pub mod members;
+pub mod position;
 pub mod protocol;
+#[cfg(feature = "lsp-client")]
 pub mod server;
+#[cfg(feature = "lsp-client")]
+pub mod trace;
+pub mod uri;
+#[cfg(feature = "lsp-client")]
+pub mod warmup;
@@ -0,0 +1,281 @@
+//! Column conversions between the units tyf has to juggle.
+//!
+//! - LSP positions count UTF-16 code units (the protocol default).
+//! - Rust string searching and slicing (`str::find`, `&line[..]`) works in UTF-8 bytes.
+//! - Users and editors count characters (Unicode scalar values).
+//!
+//! For ASCII lines all three agree, which is why mixing them up goes unnoticed
+//! until a line contains an accented string, a CJK identifier, or an emoji.
+//!
+//! Inside tyf, LSP positions are always UTF-16. A server may negotiate another
+//! `positionEncoding` in `initialize`; the client then re-expresses positions
+//! on their way to and from it with [`convert_positions`].
+
+use std::collections::HashSet;
+
+use serde_json::{Map, Value};
+
+/// The unit a column number is expressed in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColumnUnit {
+    /// UTF-8 bytes (Rust string offsets)
+    Utf8,
+    /// UTF-16 code units (LSP)
+    Utf16,
+    /// Unicode scalar values (what users type and see)
+    Char,
+}
+
+impl ColumnUnit {
+    /// The unit an LSP `PositionEncodingKind` names.
+    pub fn from_encoding(kind: &str) -> Option<Self> {
+        match kind {
+            "utf-8" => Some(Self::Utf8),
+            "utf-16" => Some(Self::Utf16),
+            "utf-32" => Some(Self::Char),
+            _ => None,
+        }
+    }
+}
+
+/// Unit used for `Position::character` in tyf, and on the wire unless the
+/// server negotiates otherwise.
+pub const LSP_UNIT: ColumnUnit = ColumnUnit::Utf16;
+
+/// The `positionEncodings` offered to the server, in order of preference.
+pub const POSITION_ENCODINGS: [&str; 3] = ["utf-16", "utf-8", "utf-32"];
+
+/// Unit used for columns the user types and tyf prints.
+pub const DISPLAY_UNIT: ColumnUnit = ColumnUnit::Char;
+
+fn width(ch: char, unit: ColumnUnit) -> usize {
+    match unit {
+        ColumnUnit::Utf8 => ch.len_utf8(),
+        ColumnUnit::Utf16 => ch.len_utf16(),
+        ColumnUnit::Char => 1,
+    }
+}
+
+/// Byte offset in `line` for `column` expressed in `unit`.
+///
+/// Columns past the end of the line clamp to `line.len()`; a column that falls
+/// inside a multi-unit character snaps to the start of that character.
+pub fn column_to_byte(line: &str, column: u32, unit: ColumnUnit) -> usize {
+    let target = column as usize;
+    let mut seen = 0;
+    for (byte, ch) in line.char_indices() {
+        let next = seen + width(ch, unit);
+        if next > target {
+            return byte;
+        }
+        seen = next;
+    }
+    line.len()
+}
+
+/// Column (in `unit`) of the byte offset `byte` in `line`.
+///
+/// Offsets past the end of the line count the whole line; an offset inside a
+/// multi-byte character counts the characters before it.
+pub fn byte_to_column(line: &str, byte: usize, unit: ColumnUnit) -> u32 {
+    let column: usize = line
+        .char_indices()
+        .take_while(|&(i, ch)| i + ch.len_utf8() <= byte)
+        .map(|(_, ch)| width(ch, unit))
+        .sum();
+    u32::try_from(column).unwrap_or(u32::MAX)
+}
+
+/// Re-express `column` from one unit in another, using the text of its line.
+pub fn convert_column(line: &str, column: u32, from: ColumnUnit, to: ColumnUnit) -> u32 {
+    if from == to {
+        return column;
+    }
+    let byte = column_to_byte(line, column, from);
+    // Columns beyond the end of the line are kept as an overshoot rather than
+    // silently clamped, so out-of-range input still reaches the server as-is.
+    let line_len = byte_to_column(line, line.len(), from);
+    let overshoot = column.saturating_sub(line_len);
+    byte_to_column(line, byte, to) + overshoot
+}
+
+/// The document an LSP object is about, if it names one.
+fn document_uri(object: &Map<String, Value>) -> Option<&str> {
+    object
+        .get("uri")
+        .or_else(|| object.get("targetUri"))
+        .or_else(|| object.get("textDocument").and_then(|doc| doc.get("uri")))
+        .and_then(Value::as_str)
+}
+
+/// Re-express every LSP position in `value` from one unit in another.
+///
+/// A position is any object with numeric `line` and `character` fields. Its
+/// line is looked up with `line_text(uri, line)`, where `uri` is that of the
+/// nearest enclosing object naming a document (a `Location`, or request
+/// params with a `textDocument`), else `uri`, the document the whole message
+/// is about. Positions whose line can't be found are left as they are.
+pub fn convert_positions(
+    value: &mut Value,
+    uri: &str,
+    from: ColumnUnit,
+    to: ColumnUnit,
+    line_text: &mut dyn FnMut(&str, u32) -> Option<String>,
+) {
+    if from == to {
+        return;
+    }
+    match value {
+        Value::Array(items) => {
+            for item in items {
+                convert_positions(item, uri, from, to, line_text);
+            }
+        }
+        Value::Object(object) => {
+            let line = object.get("line").and_then(Value::as_u64);
+            let character = object.get("character").and_then(Value::as_u64);
+            if let (Some(line), Some(character)) = (line, character) {
+                let (Ok(line), Ok(character)) = (u32::try_from(line), u32::try_from(character))
+                else {
+                    return;
+                };
+                if let Some(text) = line_text(uri, line) {
+                    let column = convert_column(&text, character, from, to);
+                    object.insert("character".to_string(), column.into());
+                }
+                return;
+            }
+            let uri = document_uri(object).unwrap_or(uri).to_string();
+            for child in object.values_mut() {
+                convert_positions(child, &uri, from, to, line_text);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// The documents whose lines [`convert_positions`] looks up for `value`, so
+/// their text can be loaded before converting.
+pub fn position_documents(value: &Value, uri: &str) -> HashSet<String> {
+    let mut documents = HashSet::new();
+    collect_position_documents(value, uri, &mut documents);
+    documents
+}
+
+fn collect_position_documents(value: &Value, uri: &str, documents: &mut HashSet<String>) {
+    match value {
+        Value::Array(items) => {
+            for item in items {
+                collect_position_documents(item, uri, documents);
+            }
+        }
+        Value::Object(object) => {
+            if object.get("line").is_some_and(Value::is_u64)
+                && object.get("character").is_some_and(Value::is_u64)
+            {
+                documents.insert(uri.to_string());
+                return;
+            }
+            let uri = document_uri(object).unwrap_or(uri);
+            for child in object.values() {
+                collect_position_documents(child, uri, documents);
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    // "é" is 2 bytes / 1 UTF-16 unit; "🎉" is 4 bytes / 2 UTF-16 units; "名" is 3 bytes / 1 unit.
+    const LINE: &str = "s = \"é🎉\"; 名前 = 1";
+
+    #[test]
+    fn test_ascii_units_agree() {
+        let line = "def greet():";
+        for unit in [ColumnUnit::Utf8, ColumnUnit::Utf16, ColumnUnit::Char] {
+            assert_eq!(column_to_byte(line, 4, unit), 4);
+            assert_eq!(byte_to_column(line, 4, unit), 4);
+        }
+    }
+
+    #[test]
+    fn test_byte_to_column_after_multibyte_chars() {
+        let byte = LINE.find("名前").unwrap();
+        assert_eq!(byte, 14);
+        assert_eq!(byte_to_column(LINE, byte, ColumnUnit::Utf8), 14);
+        assert_eq!(byte_to_column(LINE, byte, ColumnUnit::Utf16), 11);
+        assert_eq!(byte_to_column(LINE, byte, ColumnUnit::Char), 10);
+    }
+
+    #[test]
+    fn test_column_to_byte_roundtrip() {
+        let byte = LINE.find("名前").unwrap();
+        assert_eq!(column_to_byte(LINE, 11, ColumnUnit::Utf16), byte);
+        assert_eq!(column_to_byte(LINE, 10, ColumnUnit::Char), byte);
+    }
+
+    #[test]
+    fn test_column_inside_surrogate_pair_snaps_to_char_start() {
+        let emoji = LINE.find('🎉').unwrap();
+        // UTF-16 column 7 is the low surrogate of 🎉
+        assert_eq!(column_to_byte(LINE, 7, ColumnUnit::Utf16), emoji);
+    }
+
+    #[test]
+    fn test_convert_column_lsp_to_display() {
+        assert_eq!(convert_column(LINE, 11, LSP_UNIT, DISPLAY_UNIT), 10);
+        assert_eq!(convert_column(LINE, 10, DISPLAY_UNIT, LSP_UNIT), 11);
+    }
+
+    #[test]
+    fn test_convert_column_past_end_of_line_keeps_overshoot() {
+        let line = "é";
+        assert_eq!(convert_column(line, 5, ColumnUnit::Char, ColumnUnit::Utf8), 6);
+    }
+
+    #[test]
+    fn test_encoding_names() {
+        assert_eq!(ColumnUnit::from_encoding("utf-8"), Some(ColumnUnit::Utf8));
+        assert_eq!(ColumnUnit::from_encoding("utf-16"), Some(ColumnUnit::Utf16));
+        assert_eq!(ColumnUnit::from_encoding("utf-32"), Some(ColumnUnit::Char));
+        assert_eq!(ColumnUnit::from_encoding("latin-1"), None);
+    }
+
+    #[test]
+    fn test_convert_positions_uses_the_enclosing_document() {
+        let other = "名 = 1";
+        let mut lines = |uri: &str, line: u32| match (uri, line) {
+            ("file:///a.py", 0) => Some(LINE.to_string()),
+            ("file:///b.py", 0) => Some(other.to_string()),
+            _ => None,
+        };
+        // A hover-like answer about a.py, with a location in b.py and a
+        // position on a line that isn't known.
+        let mut result = json!({
+            "range": {"start": {"line": 0, "character": 14}, "end": {"line": 3, "character": 9}},
+            "locations": [{"uri": "file:///b.py", "range": {
+                "start": {"line": 0, "character": 4}, "end": {"line": 0, "character": 5}
+            }}]
+        });
+        let mut documents: Vec<String> =
+            position_documents(&result, "file:///a.py").into_iter().collect();
+        documents.sort();
+        assert_eq!(documents, ["file:///a.py", "file:///b.py"]);
+        convert_positions(&mut result, "file:///a.py", ColumnUnit::Utf8, LSP_UNIT, &mut lines);
+        assert_eq!(result["range"]["start"], json!({"line": 0, "character": 11}));
+        assert_eq!(result["range"]["end"], json!({"line": 3, "character": 9}));
+        assert_eq!(result["locations"][0]["range"]["start"]["character"], 2);
+        assert_eq!(result["locations"][0]["range"]["end"]["character"], 3);
+
+        let mut params = json!({
+            "textDocument": {"uri": "file:///b.py"},
+            "position": {"line": 0, "character": 2}
+        });
+        convert_positions(&mut params, "", LSP_UNIT, ColumnUnit::Utf8, &mut lines);
+        assert_eq!(params["position"]["character"], 4);
+    }
+}
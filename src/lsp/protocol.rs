@@ -41,6 +41,65 @@ pub struct GotoDefinitionParams {
     pub partial_result_token: Option<String>,
 }
 
+/// A "go to" request about the symbol at a position.
+///
+/// All take [`GotoDefinitionParams`] and answer with locations: where the
+/// symbol is defined, declared (in a stub, if it has one), where its type
+/// is defined, and what implements it.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum GotoKind {
+    Definition,
+    Declaration,
+    TypeDefinition,
+    Implementation,
+}
+
+impl GotoKind {
+    pub const ALL: [Self; 4] =
+        [Self::Definition, Self::Declaration, Self::TypeDefinition, Self::Implementation];
+
+    /// The LSP method, e.g. `textDocument/typeDefinition`.
+    pub const fn method(self) -> &'static str {
+        match self {
+            Self::Definition => "textDocument/definition",
+            Self::Declaration => "textDocument/declaration",
+            Self::TypeDefinition => "textDocument/typeDefinition",
+            Self::Implementation => "textDocument/implementation",
+        }
+    }
+
+    /// The capability a server advertises for the request.
+    pub const fn provider(self) -> &'static str {
+        match self {
+            Self::Definition => "definitionProvider",
+            Self::Declaration => "declarationProvider",
+            Self::TypeDefinition => "typeDefinitionProvider",
+            Self::Implementation => "implementationProvider",
+        }
+    }
+
+    /// Name in JSON, e.g. `type_definition`.
+    pub const fn key(self) -> &'static str {
+        match self {
+            Self::Definition => "definition",
+            Self::Declaration => "declaration",
+            Self::TypeDefinition => "type_definition",
+            Self::Implementation => "implementation",
+        }
+    }
+
+    /// Name for messages and output, e.g. `type definition`.
+    pub const fn label(self) -> &'static str {
+        match self {
+            Self::Definition => "definition",
+            Self::Declaration => "declaration",
+            Self::TypeDefinition => "type definition",
+            Self::Implementation => "implementation",
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct LSPRequest {
     pub jsonrpc: String,
@@ -228,10 +287,436 @@ pub struct DocumentSymbolParams {
     pub partial_result_token: Option<String>,
 }
 
+// Pull diagnostics request params
+#[derive(Serialize, Deserialize)]
+pub struct DocumentDiagnosticParams {
+    #[serde(rename = "textDocument")]
+    pub text_document: TextDocumentIdentifier,
+    #[serde(rename = "previousResultId", skip_serializing_if = "Option::is_none")]
+    pub previous_result_id: Option<String>,
+}
+
+/// Response to `textDocument/diagnostic`.
+///
+/// tyf never sends a `previousResultId`, so the server always answers with a
+/// full report; `items` is all that's needed from it.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct DocumentDiagnosticReport {
+    #[serde(default)]
+    pub items: Vec<Diagnostic>,
+}
+
+/// Params of a `textDocument/publishDiagnostics` notification: all current
+/// diagnostics of one file, pushed by the server.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PublishDiagnosticsParams {
+    pub uri: String,
+    #[serde(default)]
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+/// Where a long-running server task (indexing, a workspace-wide search)
+/// stands, from its `$/progress` notifications.
+///
+/// `report` and `end` notifications don't repeat the title, so the client
+/// fills it in from the task's `begin`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct WorkProgress {
+    pub title: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+    /// Percent done, when the server knows it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub percentage: Option<u32>,
+    /// Set on the task's last update.
+    #[serde(default)]
+    pub done: bool,
+}
+
+/// How a watched file changed, for `workspace/didChangeWatchedFiles`.
+#[derive(Serialize_repr, Deserialize_repr, Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u8)]
+pub enum FileChangeType {
+    Created = 1,
+    Changed = 2,
+    Deleted = 3,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct FileEvent {
+    pub uri: String,
+    #[serde(rename = "type")]
+    pub change: FileChangeType,
+}
+
+// Diagnostics support
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Diagnostic {
+    /// Range where the diagnostic applies
+    pub range: Range,
+
+    /// Severity level; LSP leaves it to the client when omitted, tyf treats it as an error
+    #[serde(default)]
+    pub severity: DiagnosticSeverity,
+
+    /// Diagnostic code, e.g. `unresolved-reference`
+    #[serde(default, skip_serializing_if = "Option::is_none", deserialize_with = "code_as_string")]
+    pub code: Option<String>,
+
+    /// Source of the diagnostic (e.g., "ty", "pyright")
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+
+    /// Diagnostic message
+    pub message: String,
+
+    /// Related information (optional)
+    #[serde(rename = "relatedInformation", default, skip_serializing_if = "Option::is_none")]
+    pub related_information: Option<Vec<DiagnosticRelatedInformation>>,
+}
+
+/// LSP allows a diagnostic code to be a number or a string.
+fn code_as_string<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<String>, D::Error> {
+    Ok(match Option::<serde_json::Value>::deserialize(deserializer)? {
+        Some(serde_json::Value::String(code)) => Some(code),
+        Some(serde_json::Value::Number(code)) => Some(code.to_string()),
+        _ => None,
+    })
+}
+
+#[derive(Serialize_repr, Deserialize_repr, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[repr(u8)]
+pub enum DiagnosticSeverity {
+    #[default]
+    Error = 1,
+    Warning = 2,
+    Information = 3,
+    Hint = 4,
+}
+
+impl DiagnosticSeverity {
+    /// Whether this severity is `threshold` or more severe.
+    pub const fn at_least(self, threshold: Self) -> bool {
+        self as u8 <= threshold as u8
+    }
+
+    /// Lowercase label used in output, e.g. `error`.
+    pub const fn label(self) -> &'static str {
+        match self {
+            Self::Error => "error",
+            Self::Warning => "warning",
+            Self::Information => "info",
+            Self::Hint => "hint",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct DiagnosticRelatedInformation {
+    /// Location of related information
+    pub location: Location,
+
+    /// Message describing the relation
+    pub message: String,
+}
+
+// Inlay hint request params
+#[derive(Serialize, Deserialize)]
+pub struct InlayHintParams {
+    #[serde(rename = "textDocument")]
+    pub text_document: TextDocumentIdentifier,
+    pub range: Range,
+    #[serde(rename = "workDoneToken", skip_serializing_if = "Option::is_none")]
+    pub work_done_token: Option<String>,
+}
+
+// Inlay hint support
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct InlayHint {
+    pub position: Position,
+    pub label: InlayHintLabel,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kind: Option<InlayHintKind>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(untagged)]
+pub enum InlayHintLabel {
+    String(String),
+    Parts(Vec<InlayHintLabelPart>),
+}
+
+impl InlayHintLabel {
+    /// The label as displayed, with parts joined.
+    pub fn text(&self) -> String {
+        match self {
+            Self::String(text) => text.clone(),
+            Self::Parts(parts) => parts.iter().map(|part| part.value.as_str()).collect(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct InlayHintLabelPart {
+    pub value: String,
+}
+
+#[derive(Serialize_repr, Deserialize_repr, Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u8)]
+pub enum InlayHintKind {
+    Type = 1,
+    Parameter = 2,
+}
+
+// Document highlight request params
+#[derive(Serialize, Deserialize)]
+pub struct DocumentHighlightParams {
+    #[serde(flatten)]
+    pub text_document_position_params: TextDocumentPositionParams,
+    #[serde(rename = "workDoneToken", skip_serializing_if = "Option::is_none")]
+    pub work_done_token: Option<String>,
+    #[serde(rename = "partialResultToken", skip_serializing_if = "Option::is_none")]
+    pub partial_result_token: Option<String>,
+}
+
+/// One occurrence of a symbol in the document asked about.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct DocumentHighlight {
+    pub range: Range,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kind: Option<DocumentHighlightKind>,
+}
+
+/// Whether an occurrence reads or writes the symbol; `Text` when the server can't tell.
+#[derive(Serialize_repr, Deserialize_repr, Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u8)]
+pub enum DocumentHighlightKind {
+    Text = 1,
+    Read = 2,
+    Write = 3,
+}
+
+// Completion request params
+#[derive(Serialize, Deserialize)]
+pub struct CompletionParams {
+    #[serde(flatten)]
+    pub text_document_position_params: TextDocumentPositionParams,
+    #[serde(rename = "workDoneToken", skip_serializing_if = "Option::is_none")]
+    pub work_done_token: Option<String>,
+}
+
+/// A completion response: a bare list of items, or a list marked incomplete.
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(untagged)]
+pub enum CompletionResponse {
+    Array(Vec<CompletionItem>),
+    List(CompletionList),
+}
+
+impl CompletionResponse {
+    pub fn into_items(self) -> Vec<CompletionItem> {
+        match self {
+            Self::Array(items) => items,
+            Self::List(list) => list.items,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CompletionList {
+    #[serde(rename = "isIncomplete", default)]
+    pub is_incomplete: bool,
+    pub items: Vec<CompletionItem>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct CompletionItem {
+    pub label: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kind: Option<CompletionItemKind>,
+    /// Type or signature of the item, e.g. `def total(self) -> int`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+    #[serde(rename = "labelDetails", default, skip_serializing_if = "Option::is_none")]
+    pub label_details: Option<CompletionItemLabelDetails>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct CompletionItemLabelDetails {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+}
+
+#[derive(Serialize_repr, Deserialize_repr, Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u8)]
+pub enum CompletionItemKind {
+    Text = 1,
+    Method = 2,
+    Function = 3,
+    Constructor = 4,
+    Field = 5,
+    Variable = 6,
+    Class = 7,
+    Interface = 8,
+    Module = 9,
+    Property = 10,
+    Unit = 11,
+    Value = 12,
+    Enum = 13,
+    Keyword = 14,
+    Snippet = 15,
+    Color = 16,
+    File = 17,
+    Reference = 18,
+    Folder = 19,
+    EnumMember = 20,
+    Constant = 21,
+    Struct = 22,
+    Event = 23,
+    Operator = 24,
+    TypeParameter = 25,
+}
+
+// Initialize response: server identity and advertised capabilities
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct InitializeResult {
+    #[serde(default)]
+    pub capabilities: serde_json::Value,
+    #[serde(rename = "serverInfo", default, skip_serializing_if = "Option::is_none")]
+    pub server_info: Option<ServerInfo>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ServerInfo {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+}
+
+impl InitializeResult {
+    /// Whether the server advertised `provider` (e.g. `referencesProvider`).
+    ///
+    /// A provider counts as supported when present and not `false`/`null`;
+    /// LSP allows either `true` or an options object.
+    pub fn supports(&self, provider: &str) -> bool {
+        self.capabilities.get(provider).is_some_and(|v| !v.is_null() && *v != false)
+    }
+
+    /// Names of all advertised `*Provider` capabilities, sorted.
+    pub fn supported_providers(&self) -> Vec<String> {
+        let mut providers: Vec<String> = self
+            .capabilities
+            .as_object()
+            .into_iter()
+            .flatten()
+            .filter(|(name, _)| name.ends_with("Provider"))
+            .filter(|(name, _)| self.supports(name))
+            .map(|(name, _)| name.clone())
+            .collect();
+        providers.sort();
+        providers
+    }
+
+    /// Whether the server is ty, which it is taken to be when it doesn't say,
+    /// like in [`server_label`](Self::server_label).
+    pub fn is_ty(&self) -> bool {
+        self.server_info.as_ref().is_none_or(|info| info.name == "ty")
+    }
+
+    /// Human-readable server label, e.g. `ty 0.0.1-alpha.20`.
+    pub fn server_label(&self) -> String {
+        match &self.server_info {
+            Some(ServerInfo { name, version: Some(version) }) => format!("{name} {version}"),
+            Some(ServerInfo { name, version: None }) => name.clone(),
+            None => "ty".to_string(),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_diagnostic_defaults_and_numeric_codes() {
+        let report: DocumentDiagnosticReport = serde_json::from_value(serde_json::json!({
+            "kind": "full",
+            "items": [
+                {
+                    "range": {"start": {"line": 0, "character": 0}, "end": {"line": 0, "character": 3}},
+                    "severity": 2,
+                    "code": "unused-ignore-comment",
+                    "source": "ty",
+                    "message": "Unused blanket `type: ignore` directive"
+                },
+                {
+                    "range": {"start": {"line": 4, "character": 1}, "end": {"line": 4, "character": 2}},
+                    "code": 42,
+                    "message": "no severity"
+                }
+            ]
+        }))
+        .unwrap();
+        assert_eq!(report.items.len(), 2);
+        assert_eq!(report.items[0].severity, DiagnosticSeverity::Warning);
+        assert_eq!(report.items[0].code.as_deref(), Some("unused-ignore-comment"));
+        assert_eq!(report.items[1].severity, DiagnosticSeverity::Error);
+        assert_eq!(report.items[1].code.as_deref(), Some("42"));
+    }
+
+    #[test]
+    fn test_inlay_hint_labels() {
+        let hints: Vec<InlayHint> = serde_json::from_value(serde_json::json!([
+            {"position": {"line": 0, "character": 1}, "label": ": int", "kind": 1},
+            {
+                "position": {"line": 2, "character": 5},
+                "label": [{"value": ": "}, {"value": "list[str]", "location": {
+                    "uri": "file:///stdlib/builtins.pyi",
+                    "range": {"start": {"line": 0, "character": 0}, "end": {"line": 0, "character": 4}}
+                }}]
+            }
+        ]))
+        .unwrap();
+        assert_eq!(hints[0].label.text(), ": int");
+        assert_eq!(hints[0].kind, Some(InlayHintKind::Type));
+        assert_eq!(hints[1].label.text(), ": list[str]");
+        assert_eq!(hints[1].kind, None);
+    }
+
+    #[test]
+    fn test_initialize_result_supports_true_and_options() {
+        let result: InitializeResult = serde_json::from_value(serde_json::json!({
+            "capabilities": {
+                "hoverProvider": true,
+                "referencesProvider": {"workDoneProgress": false},
+                "renameProvider": false,
+                "positionEncoding": "utf-16"
+            },
+            "serverInfo": {"name": "ty", "version": "0.0.1-alpha.20"}
+        }))
+        .unwrap();
+        assert!(result.supports("hoverProvider"));
+        assert!(result.supports("referencesProvider"));
+        assert!(!result.supports("renameProvider"));
+        assert!(!result.supports("definitionProvider"));
+        assert_eq!(result.supported_providers(), vec!["hoverProvider", "referencesProvider"]);
+        assert_eq!(result.server_label(), "ty 0.0.1-alpha.20");
+        assert!(result.is_ty());
+    }
+
+    #[test]
+    fn test_initialize_result_without_server_info() {
+        let result: InitializeResult =
+            serde_json::from_value(serde_json::json!({"capabilities": {}})).unwrap();
+        assert_eq!(result.server_label(), "ty");
+        assert!(result.is_ty());
+        assert!(result.supported_providers().is_empty());
+    }
+
     #[test]
     fn test_symbol_kind_deserialize_from_integer() {
         let json = r"12";
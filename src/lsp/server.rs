@@ -1,124 +1,250 @@
 use anyhow::{Context, Result};
-use std::process::Stdio;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::process::{ExitStatus, Stdio};
+use std::sync::Mutex;
 use tokio::io::BufReader;
 use tokio::process::{Child, Command};
 
-/// Describes how to invoke `ty` — either directly or via `uvx`.
-enum TyCommand {
-    Direct,
+use crate::lsp::backend::{LspBackend, ServerCommand};
+
+/// `ty` could not be found or run.
+///
+/// Kept as a distinct error type (and daemon error code) so the CLI can offer
+/// to install ty instead of reporting a generic "Failed to create LSP client".
+#[derive(Debug)]
+pub struct TyNotFound(pub String);
+
+impl fmt::Display for TyNotFound {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "ty is not available: {}", self.0)
+    }
+}
+
+impl std::error::Error for TyNotFound {}
+
+/// Whether `error` (or anything in its chain) is a [`TyNotFound`].
+pub fn is_ty_not_found(error: &anyhow::Error) -> bool {
+    error.chain().any(<dyn std::error::Error>::is::<TyNotFound>)
+}
+
+/// The language server process exited while requests were outstanding.
+///
+/// The daemon restarts the workspace's server and retries the request once
+/// when it sees this, rather than failing every query until the idle sweep
+/// drops the dead client.
+#[derive(Debug)]
+pub struct ServerExited;
+
+impl fmt::Display for ServerExited {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("the language server exited while handling the request")
+    }
+}
+
+impl std::error::Error for ServerExited {}
+
+/// Whether `error` (or anything in its chain) is a [`ServerExited`].
+pub fn is_server_exited(error: &anyhow::Error) -> bool {
+    error.chain().any(<dyn std::error::Error>::is::<ServerExited>)
+}
+
+/// Path of `ty` inside the workspace's `.venv`, where `uv add --dev ty` puts it.
+fn venv_ty(workspace_root: &str) -> PathBuf {
+    let venv = Path::new(workspace_root).join(".venv");
+    if cfg!(windows) {
+        venv.join("Scripts").join("ty.exe")
+    } else {
+        venv.join("bin").join("ty")
+    }
+}
+
+/// The first `ty` executable in a `PATH` directory.
+fn path_ty() -> Option<PathBuf> {
+    let name = format!("ty{}", std::env::consts::EXE_SUFFIX);
+    std::env::var_os("PATH")
+        .iter()
+        .flat_map(std::env::split_paths)
+        .map(|dir| dir.join(&name))
+        .find(|path| path.is_file())
+}
+
+/// Run `<cmd> --version`, returning the trimmed version on success.
+async fn probe_version(ty_cmd: &ServerCommand) -> Option<String> {
+    let output = ty_cmd.build().arg("--version").output().await.ok()?;
+    output.status.success().then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Where [`TyLspServer::discover`] found ty.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TySource {
+    /// `--ty-path`, or `ty-path` in the config.
+    Configured,
+    Path,
+    Venv,
     Uvx,
 }
 
-impl TyCommand {
-    fn build(&self) -> Command {
-        match self {
-            Self::Direct => Command::new("ty"),
-            Self::Uvx => {
-                let mut cmd = Command::new("uvx");
-                cmd.arg("ty");
-                cmd
-            }
-        }
+impl fmt::Display for TySource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Configured => "the configured ty-path",
+            Self::Path => "PATH",
+            Self::Venv => "the workspace's .venv",
+            Self::Uvx => "uvx",
+        })
     }
+}
 
-    fn label(&self) -> &'static str {
-        match self {
-            Self::Direct => "ty",
-            Self::Uvx => "uvx ty",
-        }
+/// A working `ty` invocation, where it was found and the version it reports.
+#[derive(Debug, Clone)]
+pub struct FoundTy {
+    pub command: ServerCommand,
+    pub source: TySource,
+    /// `ty --version` output, e.g. `ty 0.0.1`.
+    pub version: String,
+}
+
+impl fmt::Display for FoundTy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} from {} (`{}`)", self.version, self.source, self.command.label())
     }
 }
 
 #[allow(dead_code)]
 pub struct TyLspServer {
-    process: Child,
+    /// Behind a mutex so [`exit_status`](Self::exit_status) can poll the
+    /// child through a shared client.
+    process: Mutex<Child>,
     workspace_root: String,
 }
 
 #[allow(dead_code)]
 impl TyLspServer {
-    /// Try to find a working `ty` invocation. Checks `ty` on PATH first,
-    /// then falls back to `uvx ty`.
-    async fn resolve_ty_command() -> Result<TyCommand> {
-        // Try direct `ty` first
-        if let Ok(output) = Command::new("ty").arg("--version").output().await {
-            if output.status.success() {
-                let version = String::from_utf8_lossy(&output.stdout);
-                tracing::debug!("Found ty on PATH: {}", version.trim());
-                return Ok(TyCommand::Direct);
+    /// Find a working `ty` invocation.
+    ///
+    /// An explicit `ty_path` is the only candidate, so a wrong one is
+    /// reported rather than silently replaced. Otherwise tries `ty` on PATH,
+    /// then the workspace's `.venv`, then `uvx ty`.
+    pub async fn discover(workspace_root: &str, ty_path: Option<&Path>) -> Result<FoundTy> {
+        let found = |command, source, version| FoundTy { command, source, version };
+
+        if let Some(path) = ty_path {
+            let command = ServerCommand::new(path, &[]);
+            let version = probe_version(&command).await.with_context(|| {
+                format!(
+                    "The configured ty-path '{}' did not run `ty --version` (set with --ty-path \
+                     or ty-path, see `tyf config show`)",
+                    path.display()
+                )
+            })?;
+            return Ok(found(command, TySource::Configured, version));
+        }
+
+        if let Some(path) = path_ty() {
+            let command = ServerCommand::new(path, &[]);
+            if let Some(version) = probe_version(&command).await {
+                return Ok(found(command, TySource::Path, version));
+            }
+        }
+
+        let venv = venv_ty(workspace_root);
+        if venv.is_file() {
+            let command = ServerCommand::new(venv, &[]);
+            if let Some(version) = probe_version(&command).await {
+                return Ok(found(command, TySource::Venv, version));
             }
         }
 
-        tracing::debug!("ty not found on PATH, trying uvx...");
+        tracing::debug!("ty not found on PATH or in .venv, trying uvx...");
 
-        // Fall back to `uvx ty`
-        let uvx_output = Command::new("uvx").arg("ty").arg("--version").output().await.context(
-            "Neither 'ty' nor 'uvx' found on PATH. \
-                 Install ty with: uv add --dev ty",
-        )?;
+        let Ok(uvx_output) = Command::new("uvx").arg("ty").arg("--version").output().await else {
+            return Err(TyNotFound("neither 'ty' nor 'uvx' found on PATH".to_string()).into());
+        };
 
         if uvx_output.status.success() {
-            let version = String::from_utf8_lossy(&uvx_output.stdout);
-            tracing::debug!("Found ty via uvx: {}", version.trim());
-            return Ok(TyCommand::Uvx);
+            let version = String::from_utf8_lossy(&uvx_output.stdout).trim().to_string();
+            return Ok(found(ServerCommand::new("uvx", &["ty"]), TySource::Uvx, version));
         }
 
         let stderr = String::from_utf8_lossy(&uvx_output.stderr);
-        anyhow::bail!(
-            "ty is not available. Tried 'ty' and 'uvx ty' but neither worked.\n\
-             Install it with: uv add --dev ty\n\
-             uvx ty --version stderr: {}",
+        Err(TyNotFound(format!(
+            "tried 'ty', '.venv' and 'uvx ty' but none worked (uvx ty --version: {})",
             stderr.trim()
-        )
+        ))
+        .into())
+    }
+
+    /// The `ty` invocation tyf would use for `workspace_root`, e.g. `"uvx ty"`.
+    pub async fn locate(workspace_root: &str) -> Result<String> {
+        Ok(Self::discover(workspace_root, None).await?.command.label())
     }
 
-    pub async fn start(workspace_root: &str) -> Result<Self> {
-        tracing::debug!("Checking ty availability...");
-        let ty_cmd = Self::resolve_ty_command().await?;
+    /// Start the language server of `backend` in `workspace_root`.
+    pub async fn start(backend: &dyn LspBackend, workspace_root: &str) -> Result<Self> {
+        tracing::debug!("Checking {} availability...", backend.name());
+        let command = backend.resolve(workspace_root).await?;
 
         tracing::debug!(
-            "Starting ty LSP server via '{}' in workspace: {workspace_root}",
-            ty_cmd.label(),
+            "Starting {} LSP server via '{}' in workspace: {workspace_root}",
+            backend.name(),
+            command.label(),
         );
 
-        let process = ty_cmd
+        let process = command
             .build()
-            .arg("server")
             .current_dir(workspace_root)
             .stdin(Stdio::piped())
             .stdout(Stdio::piped())
             .stderr(Stdio::piped())
             .spawn()
             .with_context(|| {
-                format!(
-                    "Failed to spawn '{} server' in workspace '{workspace_root}'",
-                    ty_cmd.label(),
-                )
+                format!("Failed to spawn '{}' in workspace '{workspace_root}'", command.label())
             })?;
 
-        tracing::debug!("ty LSP server process started (pid: {:?})", process.id());
+        tracing::debug!("{} LSP server process started (pid: {:?})", backend.name(), process.id());
+
+        Ok(Self { process: Mutex::new(process), workspace_root: workspace_root.to_string() })
+    }
+
+    fn process(&mut self) -> &mut Child {
+        self.process.get_mut().expect("server process mutex poisoned")
+    }
+
+    /// Process ID of the server, while it is running.
+    pub fn pid(&self) -> Option<u32> {
+        self.process.lock().expect("server process mutex poisoned").id()
+    }
 
-        Ok(Self { process, workspace_root: workspace_root.to_string() })
+    /// How the server process ended, or `None` while it is still running.
+    ///
+    /// Polls the child without blocking, so a crash is seen even before
+    /// the client notices its output closing.
+    pub fn exit_status(&self) -> Option<ExitStatus> {
+        self.process.lock().expect("server process mutex poisoned").try_wait().ok().flatten()
     }
 
     pub fn take_stdin(&mut self) -> tokio::process::ChildStdin {
-        self.process.stdin.take().expect("ty LSP server stdin not available (already taken)")
+        self.process().stdin.take().expect("ty LSP server stdin not available (already taken)")
     }
 
     pub fn take_stdout(&mut self) -> BufReader<tokio::process::ChildStdout> {
         BufReader::new(
-            self.process.stdout.take().expect("ty LSP server stdout not available (already taken)"),
+            self.process()
+                .stdout
+                .take()
+                .expect("ty LSP server stdout not available (already taken)"),
         )
     }
 
     pub async fn shutdown(&mut self) -> Result<()> {
-        self.process.kill().await?;
+        self.process().kill().await?;
         Ok(())
     }
 }
 
 impl Drop for TyLspServer {
     fn drop(&mut self) {
-        let _ = self.process.start_kill();
+        let _ = self.process().start_kill();
     }
 }
@@ -0,0 +1,194 @@
+//! The JSON-RPC trace written with `--trace-lsp`: every message exchanged
+//! with the language server and with the daemon, in the order they passed.
+//!
+//! Each line holds the time since the trace started, a direction marker
+//! (`-->` for messages tyf sent, `<--` for those it received), the peer and
+//! the message itself:
+//!
+//! ```text
+//! [  0.412s] --> daemon {"jsonrpc":"2.0","id":1,"method":"hover",...}
+//! [  0.415s] --> ty (daemon) {"jsonrpc":"2.0","id":7,"method":"textDocument/hover",...}
+//! ```
+//!
+//! Language server traffic of a daemon request happens in the daemon, which
+//! forwards it as `trace` notifications to clients that ask for it; those
+//! lines say `(daemon)`. Other requests the daemon serves for the same
+//! workspace at the same time show up there too.
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use std::time::Instant;
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+
+/// Wire messages a subscriber can fall behind by before missing some.
+pub const WIRE_CAPACITY: usize = 1024;
+
+/// The trace of this process, once `--trace-lsp` started one.
+static TRACE: OnceLock<WireTrace> = OnceLock::new();
+
+/// Which way a message went, seen from tyf.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Direction {
+    Sent,
+    Received,
+}
+
+impl Direction {
+    const fn marker(self) -> &'static str {
+        match self {
+            Self::Sent => "-->",
+            Self::Received => "<--",
+        }
+    }
+}
+
+/// One message exchanged with a language server, as forwarded by the daemon.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct WireMessage {
+    /// Name of the language server, e.g. `ty`
+    pub server: String,
+    pub direction: Direction,
+    /// The JSON-RPC message, without its `Content-Length` header
+    pub body: String,
+}
+
+/// A trace file and what goes into it.
+pub struct WireTrace {
+    writer: Mutex<BufWriter<File>>,
+    path: PathBuf,
+    start: Instant,
+    /// Messages longer than this many bytes are cut short.
+    max_bytes: Option<usize>,
+}
+
+impl WireTrace {
+    /// Start this process's trace: in `path`, or a new
+    /// `tyf-trace-{timestamp}-{pid}.log` in the temp dir without one.
+    pub fn start(path: Option<&Path>, max_bytes: Option<usize>) -> Result<&'static Self> {
+        let path = path.map_or_else(
+            || {
+                let timestamp = std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .map(|d| d.as_secs())
+                    .unwrap_or(0);
+                let pid = std::process::id();
+                std::env::temp_dir().join(format!("tyf-trace-{timestamp}-{pid}.log"))
+            },
+            Path::to_path_buf,
+        );
+        let file = File::create(&path)
+            .with_context(|| format!("Failed to create LSP trace at {}", path.display()))?;
+        let trace = Self {
+            writer: Mutex::new(BufWriter::new(file)),
+            path,
+            start: Instant::now(),
+            max_bytes,
+        };
+        if TRACE.set(trace).is_err() {
+            anyhow::bail!("An LSP trace is already being written");
+        }
+        TRACE.get().context("LSP trace missing after it was started")
+    }
+
+    /// This process's trace, if one was started.
+    pub fn active() -> Option<&'static Self> {
+        TRACE.get()
+    }
+
+    /// Path to the trace file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Log one message exchanged with `peer`.
+    pub fn record(&self, peer: &str, direction: Direction, body: &str) {
+        let elapsed = self.start.elapsed();
+        let (secs, millis) = (elapsed.as_secs(), elapsed.subsec_millis());
+        let (shown, cut) = truncate(body, self.max_bytes);
+        let mut writer = self.writer.lock().expect("trace writer mutex poisoned");
+        let _ = write!(writer, "[{secs:>3}.{millis:03}s] {} {peer} {shown}", direction.marker());
+        if cut > 0 {
+            let _ = write!(writer, " ... ({cut} more bytes)");
+        }
+        let _ = writeln!(writer);
+    }
+
+    /// Log a message forwarded by the daemon.
+    pub fn record_forwarded(&self, message: &WireMessage) {
+        self.record(&format!("{} (daemon)", message.server), message.direction, &message.body);
+    }
+
+    pub fn flush(&self) {
+        let _ = self.writer.lock().expect("trace writer mutex poisoned").flush();
+    }
+}
+
+/// `body` cut to at most `max_bytes` on a character boundary, and how many
+/// bytes were left out.
+fn truncate(body: &str, max_bytes: Option<usize>) -> (&str, usize) {
+    let Some(mut end) = max_bytes.filter(|&max| max < body.len()) else { return (body, 0) };
+    while !body.is_char_boundary(end) {
+        end -= 1;
+    }
+    (&body[..end], body.len() - end)
+}
+
+/// Taps the messages a client exchanges with its language server: into
+/// this process's trace, if there is one, and to any subscriber (the daemon
+/// forwards them to clients tracing a request).
+#[derive(Clone)]
+pub struct WireTap {
+    server: String,
+    subscribers: broadcast::Sender<WireMessage>,
+}
+
+impl WireTap {
+    pub const fn new(server: String, subscribers: broadcast::Sender<WireMessage>) -> Self {
+        Self { server, subscribers }
+    }
+
+    pub fn record(&self, direction: Direction, body: &str) {
+        if let Some(trace) = WireTrace::active() {
+            trace.record(&self.server, direction, body);
+        }
+        if self.subscribers.receiver_count() > 0 {
+            let message =
+                WireMessage { server: self.server.clone(), direction, body: body.to_string() };
+            // Subscribers may have gone in the meantime.
+            let _ = self.subscribers.send(message);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn truncate_keeps_whole_characters() {
+        assert_eq!(truncate("hello", None), ("hello", 0));
+        assert_eq!(truncate("hello", Some(10)), ("hello", 0));
+        assert_eq!(truncate("hello", Some(2)), ("he", 3));
+        // `é` is two bytes; cutting inside it keeps neither.
+        assert_eq!(truncate("café", Some(4)), ("caf", 2));
+    }
+
+    #[test]
+    fn wire_tap_feeds_subscribers() {
+        let (sender, mut messages) = broadcast::channel(WIRE_CAPACITY);
+        let tap = WireTap::new("ty".to_string(), sender);
+        tap.record(Direction::Sent, r#"{"id":1}"#);
+        let message = messages.try_recv().unwrap();
+        assert_eq!(message.server, "ty");
+        assert_eq!(message.direction, Direction::Sent);
+        assert_eq!(message.body, r#"{"id":1}"#);
+        let json = serde_json::to_value(&message).unwrap();
+        assert_eq!(json["direction"], "sent");
+    }
+}
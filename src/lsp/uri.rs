@@ -0,0 +1,154 @@
+//! Conversions between filesystem paths and `file://` URIs.
+//!
+//! On Unix these are a prefix away from each other. Windows needs more care:
+//! drive letters get an extra slash (`file:///C:/src/app.py`), UNC shares put
+//! the server in the authority (`file://server/share/app.py`), separators flip,
+//! and `canonicalize` returns verbatim paths (`\\?\C:\src`) that ty never
+//! reports back. Every URI ↔ path conversion goes through here so the rules
+//! live in one place.
+
+use std::path::{Path, PathBuf};
+
+/// Whether `s` starts with a drive letter, e.g. `C:` or `c:/`.
+fn has_drive_letter(s: &str) -> bool {
+    let bytes = s.as_bytes();
+    bytes.len() >= 2 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':'
+}
+
+/// Strip the `\\?\` verbatim prefix Windows `canonicalize` adds.
+///
+/// `\\?\C:\src` becomes `C:\src` and `\\?\UNC\server\share` becomes
+/// `\\server\share`; anything else is returned unchanged.
+fn strip_verbatim_str(s: &str) -> std::borrow::Cow<'_, str> {
+    if let Some(unc) = s.strip_prefix(r"\\?\UNC\") {
+        return format!(r"\\{unc}").into();
+    }
+    match s.strip_prefix(r"\\?\") {
+        Some(rest) if has_drive_letter(rest) => rest.into(),
+        _ => s.into(),
+    }
+}
+
+/// Drop the `\\?\` verbatim prefix from a canonicalized path (no-op on Unix).
+pub fn simplify(path: PathBuf) -> PathBuf {
+    if !cfg!(windows) {
+        return path;
+    }
+    match path.to_str().map(strip_verbatim_str) {
+        Some(std::borrow::Cow::Owned(simplified)) => PathBuf::from(simplified),
+        _ => path,
+    }
+}
+
+/// `std::fs::canonicalize` without the Windows verbatim prefix.
+pub fn canonicalize(path: &Path) -> std::io::Result<PathBuf> {
+    path.canonicalize().map(simplify)
+}
+
+fn path_str_to_uri(path: &str, windows: bool) -> String {
+    if !windows {
+        return format!("file://{path}");
+    }
+    let path = strip_verbatim_str(path).replace('\\', "/");
+    if has_drive_letter(&path) {
+        format!("file:///{path}")
+    } else if let Some(unc) = path.strip_prefix("//") {
+        format!("file://{unc}")
+    } else {
+        format!("file://{path}")
+    }
+}
+
+fn uri_to_path_str(uri: &str, windows: bool) -> String {
+    let Some(rest) = uri.strip_prefix("file://") else {
+        return uri.to_string();
+    };
+    if !windows {
+        return rest.to_string();
+    }
+    if let Some(local) = rest.strip_prefix('/') {
+        // Some clients percent-encode the drive colon (`file:///c%3A/...`).
+        let local = match local.get(1..4) {
+            Some(colon) if colon.eq_ignore_ascii_case("%3A") => {
+                format!("{}:{}", &local[..1], &local[4..])
+            }
+            _ => local.to_string(),
+        };
+        if has_drive_letter(&local) {
+            return local.replace('/', "\\");
+        }
+        return format!("/{local}").replace('/', "\\");
+    }
+    // `file://server/share/...` is a UNC path.
+    format!(r"\\{}", rest.replace('/', "\\"))
+}
+
+/// `file://` URI for an absolute path.
+pub fn path_to_uri(path: &Path) -> String {
+    path_str_to_uri(&path.to_string_lossy(), cfg!(windows))
+}
+
+/// Filesystem path behind a `file://` URI; other URIs are returned unchanged.
+pub fn uri_to_path(uri: &str) -> String {
+    uri_to_path_str(uri, cfg!(windows))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unix_paths_round_trip() {
+        let uri = path_str_to_uri("/home/user/app.py", false);
+        assert_eq!(uri, "file:///home/user/app.py");
+        assert_eq!(uri_to_path_str(&uri, false), "/home/user/app.py");
+    }
+
+    #[test]
+    fn test_non_file_uris_pass_through() {
+        assert_eq!(uri_to_path_str("untitled:Untitled-1", true), "untitled:Untitled-1");
+        assert_eq!(uri_to_path_str("untitled:Untitled-1", false), "untitled:Untitled-1");
+    }
+
+    #[test]
+    fn test_windows_drive_letter_uri() {
+        assert_eq!(path_str_to_uri(r"C:\src\app.py", true), "file:///C:/src/app.py");
+        assert_eq!(uri_to_path_str("file:///C:/src/app.py", true), r"C:\src\app.py");
+        assert_eq!(uri_to_path_str("file:///c%3A/src/app.py", true), r"c:\src\app.py");
+    }
+
+    #[test]
+    fn test_windows_verbatim_prefix_is_dropped() {
+        assert_eq!(path_str_to_uri(r"\\?\C:\src\app.py", true), "file:///C:/src/app.py");
+        assert_eq!(strip_verbatim_str(r"\\?\D:\work"), r"D:\work");
+        assert_eq!(strip_verbatim_str(r"\\?\UNC\server\share\x.py"), r"\\server\share\x.py");
+        assert_eq!(strip_verbatim_str(r"C:\already\plain"), r"C:\already\plain");
+    }
+
+    #[test]
+    fn test_windows_unc_paths() {
+        let uri = path_str_to_uri(r"\\?\UNC\server\share\app.py", true);
+        assert_eq!(uri, "file://server/share/app.py");
+        assert_eq!(uri_to_path_str(&uri, true), r"\\server\share\app.py");
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn test_canonical_windows_paths_round_trip() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let file = dir.path().join("app.py");
+        std::fs::write(&file, "x = 1\n").expect("Failed to write file");
+
+        let canonical = canonicalize(&file).expect("Failed to canonicalize");
+        assert!(!canonical.to_string_lossy().starts_with(r"\\?\"), "{}", canonical.display());
+        let uri = path_to_uri(&canonical);
+        assert!(uri.starts_with("file:///") && uri[8..].as_bytes()[1] == b':', "{uri}");
+        assert_eq!(Path::new(&uri_to_path(&uri)), canonical);
+    }
+
+    #[test]
+    fn test_unix_backslashes_are_left_alone() {
+        // Backslash is a legal filename character on Unix.
+        assert_eq!(path_str_to_uri(r"/tmp/odd\name.py", false), r"file:///tmp/odd\name.py");
+    }
+}
@@ -0,0 +1,125 @@
+//! Retries for LSP requests that come back empty while ty is still starting.
+
+use anyhow::Result;
+use std::path::Path;
+use std::time::Duration;
+
+use crate::lsp::client::TyLspClient;
+use crate::lsp::protocol::{Hover, SymbolInformation};
+use crate::workspace::ty_config::TyConfig;
+
+/// Default warmup delays (ms) for LSP operations that may return empty on cold start.
+/// Total: 100 + 200 + 400 + 800 = 1500ms.
+pub const WARMUP_DELAYS: [u64; 4] = [100, 200, 400, 800];
+
+/// Hover with retry on cold start.
+///
+/// The ty LSP server may return null hover when a document was recently
+/// opened and analysis hasn't completed. Retry with back-off.
+pub async fn hover_with_warmup(
+    client: &TyLspClient,
+    file: &str,
+    line: u32,
+    column: u32,
+) -> Result<Option<Hover>> {
+    with_warmup(
+        "hover",
+        &WARMUP_DELAYS,
+        |h: &Option<Hover>| h.is_some(),
+        || client.hover(file, line, column),
+        None, // No symbol name available for position-based hover
+    )
+    .await
+}
+
+/// Workspace symbols with retry on cold start.
+///
+/// On cold start the ty LSP server may not have finished indexing the
+/// workspace yet, returning zero symbols. Retry with back-off.
+///
+/// Uses ripgrep as a circuit-breaker: after the first empty result, if `rg`
+/// confirms the symbol doesn't exist in any `.py` file, skips retries.
+pub async fn workspace_symbols_with_warmup(
+    client: &TyLspClient,
+    query: &str,
+    workspace_root: &Path,
+) -> Result<Vec<SymbolInformation>> {
+    with_warmup(
+        "workspace symbols",
+        &WARMUP_DELAYS,
+        |syms: &Vec<SymbolInformation>| !syms.is_empty(),
+        || client.workspace_symbols(query),
+        Some(RgCheck { symbol: query, workspace_root }),
+    )
+    .await
+}
+
+/// Context for the optional ripgrep-based early termination check.
+///
+/// When provided, `with_warmup` will run `rg` after the first empty result
+/// to check whether the symbol text exists in any `.py` file. If it doesn't,
+/// retries are skipped — the symbol provably does not exist.
+pub struct RgCheck<'a> {
+    pub symbol: &'a str,
+    pub workspace_root: &'a Path,
+}
+
+/// Retry an LSP operation with exponential back-off when it returns an "empty" result.
+///
+/// On cold start the ty LSP server may not have finished indexing a document
+/// or the workspace, causing operations to return empty/null results. This
+/// helper retries with the given delays until `is_ready` returns `true`.
+///
+/// When `rg_check` is provided, uses ripgrep as a fast negative filter after the
+/// first empty result: if the symbol doesn't appear in any `.py` file, bail
+/// immediately instead of retrying.
+pub async fn with_warmup<T, F, Fut>(
+    description: &str,
+    delays: &[u64],
+    is_ready: impl Fn(&T) -> bool,
+    mut operation: F,
+    rg_check: Option<RgCheck<'_>>,
+) -> Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: std::future::Future<Output = Result<T>>,
+{
+    let result = operation().await?;
+    if is_ready(&result) {
+        return Ok(result);
+    }
+
+    // After the first empty result, check if the symbol exists in the workspace
+    // using ripgrep. If rg confirms it doesn't exist, skip all retries.
+    // Run in spawn_blocking to avoid blocking the tokio runtime.
+    if let Some(ref check) = rg_check {
+        let symbol = check.symbol.to_owned();
+        let workspace = check.workspace_root.to_owned();
+        let exists = tokio::task::spawn_blocking(move || {
+            let config = TyConfig::load_or_default(&workspace);
+            crate::ripgrep::symbol_might_exist_in_workspace(&symbol, &workspace, &config)
+        })
+        .await
+        .unwrap_or(true); // If spawn_blocking panics, conservatively continue retries
+
+        if !exists {
+            tracing::debug!(
+                "{description}: rg confirms symbol '{}' not found, skipping retries",
+                check.symbol
+            );
+            return Ok(result);
+        }
+    }
+
+    for delay_ms in delays {
+        tracing::debug!("{description} not ready, retrying in {delay_ms}ms...");
+        tokio::time::sleep(Duration::from_millis(*delay_ms)).await;
+        let result = operation().await?;
+        if is_ready(&result) {
+            return Ok(result);
+        }
+    }
+
+    tracing::debug!("{description} still not ready after retries");
+    operation().await
+}
@@ -1,38 +1,129 @@
 use anyhow::{Context, Result};
-use clap::{CommandFactory, Parser};
+use clap::error::{ContextKind, ContextValue, ErrorKind};
+use clap::{CommandFactory, ValueEnum};
+use std::collections::BTreeMap;
+use std::ffi::OsString;
 use std::fmt::Write;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::time::Duration;
 
+#[cfg(unix)]
+mod annotate;
+#[cfg(unix)]
+mod api;
+#[cfg(unix)]
+mod baseline;
+#[cfg(unix)]
+mod batch;
+#[cfg(unix)]
+mod bench;
+#[cfg(unix)]
+mod blame;
+#[cfg(unix)]
+mod chain;
 mod cli;
+#[cfg(unix)]
+mod code_stats;
 mod commands;
 #[cfg(unix)]
-mod daemon;
-mod debug;
-mod lsp;
-mod ripgrep;
-mod workspace;
+mod doctor;
+#[cfg(unix)]
+mod grep;
+#[cfg(unix)]
+mod import_graph;
+#[cfg(unix)]
+mod mcp;
+#[cfg(unix)]
+mod rdeps;
+#[cfg(unix)]
+mod refactor;
+mod setup;
+#[cfg(unix)]
+mod sigcheck;
+#[cfg(unix)]
+mod tui;
+#[cfg(unix)]
+mod unused;
+#[cfg(unix)]
+mod watch;
 
-use cli::args::{Cli, Commands};
+#[cfg(unix)]
+use ty_find::daemon;
+use ty_find::{config, debug, lsp, workspace};
+
+use cli::args::{Cli, Commands, ConfigCommands, FormatFlag, OutputFormat};
+use cli::exit::{self, Failure, UsageError};
 use cli::output::OutputFormatter;
+use cli::output_sinks::{open_in_editor, FileSink, LocationList, OutputSink, Redirect};
 use cli::style::{Styler, UseColor};
+use config::{Config, Settings, Source};
 #[cfg(unix)]
 use daemon::client::DEFAULT_TIMEOUT;
 #[cfg(not(unix))]
 const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
 use debug::DebugLog;
+use lsp::language::check_python_file;
+use lsp::server::is_ty_not_found;
+use lsp::trace::WireTrace;
 use workspace::detection::WorkspaceDetector;
+use workspace::filter::PathFilter;
+use workspace::page::ResultPage;
+use workspace::paths::{logical_cwd, PathMapper};
+use workspace::ty_config::TyConfig;
+
+fn main() {
+    // Answer `COMPLETE=<shell> tyf ...` completion requests, if this is one.
+    clap_complete::CompleteEnv::with_factory(Cli::command).complete();
 
-#[tokio::main]
-async fn main() {
-    let cli = Cli::parse();
+    // Parse before anything else so `--help`, `--version` and usage errors
+    // exit without starting a runtime.
+    let mut cli = parse_args();
+    let mut json_errors = matches!(cli.format, Some(FormatFlag::Json | FormatFlag::Jsonl));
 
     if cli.verbose {
         tracing_subscriber::fmt().with_env_filter("ty_find=debug").init();
     }
 
-    let use_color = UseColor::resolve(&cli.color);
+    // ty clients and the daemon client read the backend from the environment,
+    // so `--backend` is the same as setting `TYF_BACKEND`.
+    if let Some(backend) = &cli.backend {
+        std::env::set_var(lsp::backend::BACKEND_ENV, backend);
+    }
+    // `--ty-path` is relative to where tyf runs, not to the daemon.
+    if let Some(path) = cli.ty_path.take() {
+        let path = std::path::absolute(&path).unwrap_or(path);
+        std::env::set_var(lsp::backend::BACKEND_ENV, lsp::backend::ty_spec(&path));
+        cli.ty_path = Some(path);
+    }
+
+    // Likewise the daemon's socket, which daemons spawned later are given too.
+    #[cfg(unix)]
+    {
+        if let Some(path) = &cli.socket {
+            let path = std::path::absolute(path).unwrap_or_else(|_| path.clone());
+            std::env::set_var(daemon::client::SOCKET_ENV, path);
+        }
+        if let Some(scope) = cli.daemon_scope {
+            std::env::set_var(daemon::client::SCOPE_ENV, scope.name());
+        }
+        if cli.no_daemon {
+            std::env::set_var(daemon::client::NO_DAEMON_ENV, "1");
+        }
+        if let Some(ttl) = cli.daemon_ttl {
+            std::env::set_var(config::DAEMON_TTL_ENV, ttl.as_secs().to_string());
+        }
+        if cli.kill_after {
+            std::env::set_var(
+                daemon::client::OWNER_ENV,
+                std::os::unix::process::parent_id().to_string(),
+            );
+        }
+    }
+
+    // Output bound for a file is never colored, whatever stdout is.
+    let use_color =
+        if cli.output_file.is_some() { UseColor::No } else { UseColor::resolve(&cli.color) };
     let styler = Styler::new(use_color);
 
     // Create debug log early so we can print its path even on error
@@ -47,19 +138,271 @@ async fn main() {
     } else {
         None
     };
+    // Likewise the wire trace, before anything talks to ty or the daemon
+    let wire_trace = cli.trace_lsp.as_ref().and_then(|path| {
+        WireTrace::start(path.as_deref(), cli.trace_max_bytes)
+            .inspect_err(|e| eprintln!("Warning: failed to create LSP trace: {e}"))
+            .ok()
+    });
 
-    let result = run(cli, styler, debug_log.clone()).await;
+    // Commands that never talk to ty skip the tokio runtime and workspace
+    // detection entirely, which keeps them in the single-digit milliseconds.
+    // `tyf daemon` commands keep the default Ctrl-C behaviour: a foreground
+    // daemon must not cancel requests on its own socket.
+    let cancel_on_interrupt = !matches!(cli.command, Commands::Daemon { .. });
+    let result = run_without_runtime(&cli.command).unwrap_or_else(|| {
+        tokio::runtime::Runtime::new().context("Failed to start the async runtime").and_then(
+            |runtime| {
+                runtime.block_on(async {
+                    if cancel_on_interrupt {
+                        runtime.spawn(exit_on_interrupt());
+                    }
+                    run_with_recovery(cli, styler, debug_log.clone(), &mut json_errors).await
+                })
+            },
+        )
+    });
 
     // Always print debug log path (even on error)
     if let Some(ref log) = debug_log {
         log.flush();
         eprintln!("Debug log: {}", log.path().display());
     }
+    if let Some(trace) = wire_trace {
+        trace.flush();
+        eprintln!("LSP trace: {}", trace.path().display());
+    }
 
     if let Err(e) = result {
-        eprintln!("{}", styler.error(&format!("Error: {}", format_error_chain(&e))));
+        let failure = Failure::of(&e);
+        if json_errors {
+            eprintln!("{}", exit::error_report(failure, &format!("{e:#}")));
+        } else if failure != Failure::NoResults {
+            eprintln!("{}", styler.error(&format!("Error: {}", format_error_chain(&e))));
+        }
         #[allow(clippy::exit)]
-        std::process::exit(1);
+        std::process::exit(failure.exit_code());
+    }
+}
+
+/// Wait for Ctrl-C, then cancel this process's daemon requests and exit.
+///
+/// Runs as its own task, so it fires even while the command is blocked on
+/// a prompt.
+async fn exit_on_interrupt() {
+    if tokio::signal::ctrl_c().await.is_err() {
+        return;
+    }
+    #[cfg(unix)]
+    daemon::client::cancel_own_requests().await;
+    #[allow(clippy::exit)]
+    std::process::exit(exit::INTERRUPTED);
+}
+
+/// Parse the command line, expanding an alias from the config if clap
+/// doesn't know the subcommand.
+fn parse_args() -> Cli {
+    let args: Vec<OsString> = std::env::args_os().collect();
+    let expanded = match Cli::try_parse_recording(&args) {
+        Ok(Cli { command: Commands::Run { name, .. }, .. }) => {
+            let Some(command) = cwd_aliases().remove(&name) else {
+                let message = format!("no alias named '{name}' (see `tyf alias list`)");
+                exit_on_parse_error(&Cli::command().error(ErrorKind::InvalidValue, message));
+            };
+            expand_alias(&args, "run", 2, &command).map_err(|e| format!("alias '{name}' {e}"))
+        }
+        Ok(cli) => return cli,
+        Err(error) => {
+            let alias = match error.get(ContextKind::InvalidSubcommand) {
+                Some(ContextValue::String(name))
+                    if error.kind() == ErrorKind::InvalidSubcommand =>
+                {
+                    cwd_aliases().remove(name).map(|command| (name.clone(), command))
+                }
+                _ => None,
+            };
+            let Some((name, command)) = alias else { exit_on_parse_error(&error) };
+            expand_alias(&args, &name, 1, &command).map_err(|e| format!("alias '{name}' {e}"))
+        }
+    };
+    match expanded {
+        Ok(args) => Cli::try_parse_recording(args).unwrap_or_else(|e| exit_on_parse_error(&e)),
+        Err(message) => {
+            exit_on_parse_error(&Cli::command().error(ErrorKind::WrongNumberOfValues, message))
+        }
+    }
+}
+
+/// Aliases from the config of the project the CWD is in.
+fn cwd_aliases() -> BTreeMap<String, String> {
+    let Ok((paths, _)) = logical_cwd().and_then(|cwd| resolve_workspace(None, &cwd, None)) else {
+        return BTreeMap::new();
+    };
+    let config = Config::load_or_default(paths.canonical_root());
+    config.aliases().into_iter().map(|(name, (command, _))| (name.into(), command.into())).collect()
+}
+
+/// `args` with `word` and the `len` arguments starting at it (the alias's
+/// name, or `run NAME`) replaced by the alias's `command`, split on
+/// whitespace.
+///
+/// `{1}`, `{2}`, ... in `command` are replaced by the arguments after those,
+/// in order; the arguments no placeholder takes are added at the end.
+fn expand_alias(
+    args: &[OsString],
+    word: &str,
+    len: usize,
+    command: &str,
+) -> Result<Vec<OsString>, String> {
+    let at =
+        args.iter().skip(1).position(|arg| arg == word).ok_or("isn't on the command line")? + 1;
+    let rest = args.get(at + len..).unwrap_or_default();
+    let mut used = vec![false; rest.len()];
+    let mut expanded = args[..at].to_vec();
+    for word in command.split_whitespace() {
+        expanded.push(fill_placeholders(word, rest, &mut used)?);
+    }
+    expanded.extend(rest.iter().zip(&used).filter(|(_, used)| !**used).map(|(arg, _)| arg.clone()));
+    Ok(expanded)
+}
+
+/// `word` of an alias's command with its `{N}` placeholders replaced by the
+/// `N`th of `args`, marking each one taken in `used`.
+fn fill_placeholders(word: &str, args: &[OsString], used: &mut [bool]) -> Result<OsString, String> {
+    let mut filled = OsString::new();
+    let mut rest = word;
+    while let Some(open) = rest.find('{') {
+        let Some(close) = rest[open..].find('}').map(|close| open + close) else { break };
+        let Some(n) = rest[open + 1..close].parse::<usize>().ok().filter(|&n| n > 0) else {
+            // Not a placeholder, like the braces of a glob's `{a,b}`.
+            filled.push(&rest[..=close]);
+            rest = &rest[close + 1..];
+            continue;
+        };
+        let arg = args.get(n - 1).ok_or_else(|| {
+            format!("needs at least {n} argument(s) for {{{n}}}, got {}", args.len())
+        })?;
+        used[n - 1] = true;
+        filled.push(&rest[..open]);
+        filled.push(arg);
+        rest = &rest[close + 1..];
+    }
+    filled.push(rest);
+    Ok(filled)
+}
+
+/// The workspace's config with the command line's flags on top.
+fn load_config(cli: &Cli, workspace_root: &Path) -> Result<Config> {
+    let config = Config::load(workspace_root).map_err(|e| UsageError(format!("{e:#}")))?;
+    let backend_env = lsp::backend::BACKEND_ENV;
+    let config = match std::env::var(backend_env).ok().filter(|spec| !spec.trim().is_empty()) {
+        Some(backend) if cli.backend.is_none() && cli.ty_path.is_none() => config.with_layer(
+            Source::Environment(backend_env),
+            Settings { backend: Some(backend), ..Settings::default() },
+        ),
+        _ => config,
+    };
+    let flags = Settings {
+        format: cli
+            .format
+            .as_ref()
+            .and_then(ValueEnum::to_possible_value)
+            .map(|v| v.get_name().to_string()),
+        timeout: cli.timeout,
+        backend: cli.backend.clone(),
+        ty_path: cli.ty_path.clone(),
+        ..Settings::default()
+    };
+    Ok(config.with_layer(Source::CommandLine, flags))
+}
+
+/// `--format`, else the config's `format`, else human.
+fn output_format(flag: Option<OutputFormat>, config: &Config) -> Result<OutputFormat> {
+    if let Some(format) = flag {
+        return Ok(format);
+    }
+    let Some((name, source)) = config.lookup(|s| s.format.as_deref()) else {
+        return Ok(OutputFormat::Human);
+    };
+    match OutputFormat::from_str(name, true) {
+        Ok(OutputFormat::Dot) | Err(_) => Err(UsageError(format!(
+            "Unsupported format '{name}' in {source}; use human, json, jsonl, csv or paths"
+        ))
+        .into()),
+        Ok(format) => Ok(format),
+    }
+}
+
+/// Report a command line clap rejected and exit with the usage status.
+///
+/// `--help` and `--version` also arrive here and are printed as usual. With
+/// `--format json` a usage error is reported as JSON like any other failure.
+fn exit_on_parse_error(error: &clap::Error) -> ! {
+    let args: Vec<String> = std::env::args().collect();
+    if error.use_stderr() && exit::wants_json(&args) {
+        eprintln!("{}", exit::error_report(Failure::Usage, &exit::usage_message(error)));
+        #[allow(clippy::exit)]
+        std::process::exit(Failure::Usage.exit_code());
+    }
+    error.exit()
+}
+
+/// Run `command` synchronously if it needs neither ty nor a workspace.
+///
+/// Returns `None` for commands that must go through [`run`].
+fn run_without_runtime(command: &Commands) -> Option<Result<()>> {
+    match command {
+        Commands::GenerateDocs { output_dir } => {
+            Some(cli::generate_docs::generate_docs(&Cli::command(), output_dir))
+        }
+        Commands::Completions { shell } => Some(cli::completions::print_script(*shell)),
+        _ => None,
+    }
+}
+
+/// [`run`], offering to install ty if the command failed because it is missing.
+///
+/// `json_errors` is set once the output format is known, including one
+/// taken from the config.
+async fn run_with_recovery(
+    cli: Cli,
+    styler: Styler,
+    debug_log: Option<Arc<DebugLog>>,
+    json_errors: &mut bool,
+) -> Result<()> {
+    let workspace = cli.workspace.clone();
+    match run(cli, styler, debug_log.clone(), json_errors).await {
+        Err(e) if is_ty_not_found(&e) => {
+            recover_missing_ty(e, workspace.as_deref(), styler, debug_log, json_errors).await
+        }
+        result => result,
+    }
+}
+
+/// Offer to install ty after a command failed because it is missing.
+///
+/// On an interactive terminal the user is asked to run the install `tyf setup`
+/// would pick, and the original command is retried afterwards. Otherwise the
+/// error is returned with a hint naming that command.
+async fn recover_missing_ty(
+    error: anyhow::Error,
+    workspace: Option<&Path>,
+    styler: Styler,
+    debug_log: Option<Arc<DebugLog>>,
+    json_errors: &mut bool,
+) -> Result<()> {
+    let Ok((paths, _)) = logical_cwd().and_then(|cwd| resolve_workspace(workspace, &cwd, None))
+    else {
+        return Err(error);
+    };
+    let root = paths.canonical_root();
+    match setup::plan(None, root) {
+        Ok(plan) if setup::confirm(&plan) => {
+            plan.run(root)?;
+            eprintln!("{}", styler.dim("ty installed, retrying"));
+            run(parse_args(), styler, debug_log, json_errors).await
+        }
+        _ => Err(error.context(setup::missing_ty_hint(root))),
     }
 }
 
@@ -74,70 +417,623 @@ fn format_error_chain(error: &anyhow::Error) -> String {
 }
 
 /// Resolve the workspace root directory and describe the detection method.
-fn resolve_workspace(explicit: Option<&Path>, cwd: &Path) -> Result<(PathBuf, String)> {
+///
+/// Without `--workspace`, detection walks up from the queried file when there
+/// is one (so `tyf list ../other/app.py` uses `other`'s project), and from the
+/// CWD otherwise. The returned mapper keeps both the root as the user reached
+/// it (possibly through symlinks) and its canonical form.
+fn resolve_workspace(
+    explicit: Option<&Path>,
+    cwd: &Path,
+    target_file: Option<&Path>,
+) -> Result<(PathMapper, String)> {
     if let Some(ws) = explicit {
-        let root = ws.canonicalize().context("Failed to canonicalize workspace path")?;
-        return Ok((root, "explicit --workspace flag".to_string()));
+        return Ok((PathMapper::new(&cwd.join(ws))?, "explicit --workspace flag".to_string()));
+    }
+
+    if let Some(file) = target_file {
+        let start = file.parent().filter(|p| p.is_dir());
+        if let Some(detected) = start.and_then(WorkspaceDetector::find_workspace_root) {
+            let method = format!(
+                "{} (walked up from {})",
+                WorkspaceDetector::describe_detection(&detected),
+                file.display()
+            );
+            return Ok((PathMapper::new(&detected)?, method));
+        }
     }
 
     if let Some(detected) = WorkspaceDetector::find_workspace_root(cwd) {
         let method = WorkspaceDetector::describe_detection(&detected);
-        let root = detected.canonicalize().context("Failed to canonicalize workspace path")?;
-        Ok((root, method))
+        Ok((PathMapper::new(&detected)?, method))
     } else {
-        let root = cwd.canonicalize().context("Failed to canonicalize workspace path")?;
-        Ok((root, "no project markers found, using CWD".to_string()))
+        Ok((PathMapper::new(cwd)?, "no project markers found, using CWD".to_string()))
+    }
+}
+
+/// The file a command is about, if any: `--file`, the `list` argument, or the
+/// path part of a `file:line:col` query.
+fn target_file(command: &Commands) -> Option<PathBuf> {
+    let position_file =
+        |query: &str| commands::parse_file_position(query).map(|(file, _, _)| PathBuf::from(file));
+    match command {
+        Commands::Find { file, .. } => file.clone(),
+        Commands::Show { file, within, .. } => file.clone().or_else(|| within.clone()),
+        Commands::Members { file, symbols, .. } => {
+            file.clone().or_else(|| symbols.iter().find_map(|q| position_file(q)))
+        }
+        Commands::References { file, queries, .. }
+        | Commands::Definition { file, queries, .. }
+        | Commands::Stubs { file, queries, .. }
+        | Commands::Context { file, queries }
+        | Commands::Hover { file, queries, .. }
+        | Commands::Occurrences { file, queries, .. } => {
+            file.clone().or_else(|| queries.iter().find_map(|q| position_file(q)))
+        }
+        Commands::ExplainType { file, query, .. } | Commands::SigCheck { file, query, .. } => {
+            file.clone().or_else(|| position_file(query))
+        }
+        Commands::Bench { query, .. } => query.as_deref().and_then(position_file),
+        Commands::Explain { location } => {
+            commands::parse_file_line(location).map(|(file, _)| PathBuf::from(file))
+        }
+        Commands::Symbols { within, .. }
+        | Commands::Unused { path: within, .. }
+        | Commands::Api { path: within, .. }
+        | Commands::Stats { path: within, .. } => within.clone(),
+        Commands::DocumentSymbols { file }
+        | Commands::Annotate { file, .. }
+        | Commands::Where { file, .. }
+        | Commands::Rdeps { file, .. } => Some(file.clone()),
+        Commands::Check { files, .. } => files.first().cloned(),
+        Commands::Move { old, .. } => Some(old.clone()),
+        Commands::Moved { new, .. } => Some(new.clone()),
+        Commands::Imports { target } => commands::imports_target_path(target),
+        Commands::Daemon { .. }
+        | Commands::CheckAll { .. }
+        | Commands::Grep { .. }
+        | Commands::TyInfo
+        | Commands::Doctor
+        | Commands::Config { .. }
+        | Commands::Alias { .. }
+        | Commands::Run { .. }
+        | Commands::Mcp
+        | Commands::Batch
+        | Commands::Serve { .. }
+        | Commands::Tui { .. }
+        | Commands::Setup { .. }
+        | Commands::Completions { .. }
+        | Commands::GenerateDocs { .. } => None,
+    }
+}
+
+/// Make file arguments that exist relative to the CWD absolute.
+///
+/// The daemon resolves relative paths against the workspace root, which is
+/// wrong when tyf runs from a subdirectory. Paths that don't exist relative to
+/// the CWD are left alone so workspace-relative spellings keep working.
+fn absolutize_file_args(command: &mut Commands, cwd: &Path) {
+    let fix_path = |path: &mut PathBuf| {
+        if path.is_relative() && cwd.join(&*path).exists() {
+            *path = cwd.join(&*path);
+        }
+    };
+    let fix_query = |query: &mut String| {
+        if let Some((file, line, column)) = commands::parse_file_position(query) {
+            let path = Path::new(&file);
+            if path.is_relative() && cwd.join(path).exists() {
+                *query = format!("{}:{line}:{column}", cwd.join(path).display());
+            }
+        }
+    };
+    match command {
+        Commands::Find { file, .. } => {
+            file.iter_mut().for_each(fix_path);
+        }
+        Commands::Show { file, within, .. } => {
+            file.iter_mut().for_each(fix_path);
+            within.iter_mut().for_each(fix_path);
+        }
+        Commands::Members { file, symbols, .. } => {
+            file.iter_mut().for_each(fix_path);
+            symbols.iter_mut().for_each(fix_query);
+        }
+        Commands::Definition { file, queries, stdin_path }
+        | Commands::Hover { file, queries, stdin_path, .. } => {
+            file.iter_mut().for_each(fix_path);
+            queries.iter_mut().for_each(fix_query);
+            stdin_path.iter_mut().for_each(fix_path);
+        }
+        Commands::References { file, queries, .. }
+        | Commands::Stubs { file, queries, .. }
+        | Commands::Context { file, queries }
+        | Commands::Occurrences { file, queries, .. } => {
+            file.iter_mut().for_each(fix_path);
+            queries.iter_mut().for_each(fix_query);
+        }
+        Commands::ExplainType { file, query, .. } | Commands::SigCheck { file, query, .. } => {
+            file.iter_mut().for_each(fix_path);
+            fix_query(query);
+        }
+        Commands::Bench { query, .. } => query.iter_mut().for_each(fix_query),
+        Commands::Explain { location } => {
+            if let Some((file, line)) = commands::parse_file_line(location) {
+                let mut path = PathBuf::from(file);
+                fix_path(&mut path);
+                *location = format!("{}:{line}", path.display());
+            }
+        }
+        Commands::Symbols { within, .. }
+        | Commands::Unused { path: within, .. }
+        | Commands::Api { path: within, .. }
+        | Commands::Stats { path: within, .. } => {
+            within.iter_mut().for_each(fix_path);
+        }
+        Commands::DocumentSymbols { file }
+        | Commands::Annotate { file, .. }
+        | Commands::Where { file, .. }
+        | Commands::Rdeps { file, .. } => fix_path(file),
+        Commands::Check { files, baseline, .. } => {
+            files.iter_mut().for_each(fix_path);
+            baseline.iter_mut().for_each(fix_path);
+        }
+        Commands::Move { old, new, .. } => {
+            fix_path(old);
+            // The destination doesn't exist yet; resolve it from the CWD
+            // when the directory it goes into does.
+            let parent = new.parent().unwrap_or_else(|| Path::new(""));
+            if new.is_relative() && cwd.join(parent).is_dir() {
+                *new = cwd.join(&*new);
+            }
+        }
+        Commands::Moved { old, new, .. } => {
+            fix_path(new);
+            // Likewise the old path, which no longer exists.
+            let parent = old.parent().unwrap_or_else(|| Path::new(""));
+            if old.is_relative() && cwd.join(parent).is_dir() {
+                *old = cwd.join(&*old);
+            }
+        }
+        Commands::Imports { target } => {
+            if let Some(mut path) = commands::imports_target_path(target) {
+                fix_path(&mut path);
+                *target = path.to_string_lossy().into_owned();
+            }
+        }
+        Commands::Daemon { .. }
+        | Commands::CheckAll { .. }
+        | Commands::Grep { .. }
+        | Commands::TyInfo
+        | Commands::Doctor
+        | Commands::Config { .. }
+        | Commands::Alias { .. }
+        | Commands::Run { .. }
+        | Commands::Mcp
+        | Commands::Batch
+        | Commands::Serve { .. }
+        | Commands::Tui { .. }
+        | Commands::Setup { .. }
+        | Commands::Completions { .. }
+        | Commands::GenerateDocs { .. } => {}
     }
 }
 
-async fn run(cli: Cli, styler: Styler, debug_log: Option<Arc<DebugLog>>) -> Result<()> {
+/// Record in the debug log which workspace root was picked, and how, and
+/// the ty configuration found there.
+fn log_workspace(
+    log: &DebugLog,
+    cwd: &Path,
+    workspace_root: &Path,
+    requested: Option<&Path>,
+    detection_method: &str,
+) {
+    log.log_workspace_resolution(cwd, workspace_root, requested, detection_method);
+    log.log_ty_config(
+        &TyConfig::load(workspace_root).map_or_else(
+            |e| format!("invalid ({e:#}), using defaults"),
+            |config| config.describe(),
+        ),
+    );
+}
+
+async fn run(
+    mut cli: Cli,
+    styler: Styler,
+    debug_log: Option<Arc<DebugLog>>,
+    json_errors: &mut bool,
+) -> Result<()> {
     // Log CLI args
     if let Some(ref log) = debug_log {
         let args: Vec<String> = std::env::args().collect();
         log.log_cli_args(&args);
     }
 
-    let cwd = std::env::current_dir().context("Failed to get current directory")?;
-    let (workspace_root, detection_method) = resolve_workspace(cli.workspace.as_deref(), &cwd)?;
+    // Work in the user's spelling of the CWD so symlinked checkouts are
+    // reported the way they were reached; the daemon and ty only ever see the
+    // canonical workspace root.
+    let cwd = logical_cwd()?;
+    absolutize_file_args(&mut cli.command, &cwd);
+    let target = target_file(&cli.command);
+    let (paths, detection_method) =
+        resolve_workspace(cli.workspace.as_deref(), &cwd, target.as_deref())?;
+    let workspace_root = paths.canonical_root().to_path_buf();
+    #[cfg(unix)]
+    daemon::client::scope_to_workspace(&workspace_root);
+
+    // Let the user know when the queried file pulled us into a different project
+    // than the one the CWD belongs to; `--verbose` always says which root and why.
+    if cli.verbose {
+        eprintln!(
+            "{}",
+            styler
+                .dim(&format!("Workspace root: {} ({detection_method})", workspace_root.display()))
+        );
+        let spec = lsp::backend::selected(&workspace_root);
+        let root = workspace_root.to_string_lossy();
+        if let Some(Ok(ty)) = lsp::backend::find_ty(&spec, &root).await {
+            eprintln!("{}", styler.dim(&format!("ty: {ty}")));
+        }
+    } else if cli.workspace.is_none() {
+        if let Some(ref file) = target {
+            let (cwd_root, _) = resolve_workspace(None, &cwd, None)?;
+            if cwd_root.canonical_root() != workspace_root {
+                eprintln!(
+                    "{}",
+                    styler.dim(&format!(
+                        "Using workspace root {} (detected from {})",
+                        workspace_root.display(),
+                        file.display()
+                    ))
+                );
+            }
+        }
+    }
+
+    // Catch non-Python targets before they reach ty, which would just return
+    // empty results. Missing files are left to the command's own error.
+    if let Some(file) = target.as_deref().map(|f| workspace_root.join(f)).filter(|f| f.is_file()) {
+        check_python_file(&file)?;
+    }
 
     // Log workspace resolution
     if let Some(ref log) = debug_log {
-        log.log_workspace_resolution(
-            &cwd,
+        log_workspace(log, &cwd, &workspace_root, cli.workspace.as_deref(), &detection_method);
+    }
+
+    let config = load_config(&cli, &workspace_root)?;
+    let list = cli.format.as_ref().and_then(FormatFlag::location_list);
+    let format = output_format(cli.format.as_ref().map(FormatFlag::output_format), &config)?;
+    *json_errors = list.is_none() && matches!(format, OutputFormat::Json | OutputFormat::Jsonl);
+    check_output_options(&cli, &format, list)?;
+    // JSON output comes in an envelope saying what it answers, except for
+    // batch, whose results are always JSON lines, whatever --format says.
+    let envelope = format == OutputFormat::Json && !matches!(cli.command, Commands::Batch);
+    let format = if matches!(cli.command, Commands::Batch) { OutputFormat::Json } else { format };
+    let (context_lines, group_by, sort) = match cli.command {
+        Commands::References { context, group_by, sort, .. } => (context, group_by, sort),
+        _ => (0, None, None),
+    };
+    let formatter = OutputFormatter::with_detail(format, cli.detail, styler)
+        .with_zero_based(cli.zero_based)
+        .with_ranges(cli.with_ranges)
+        .with_context_lines(context_lines)
+        .with_reference_groups(group_by, sort)
+        .with_origin_labels(workspace_root.clone(), cli.include_deps)
+        .with_path_mapper(paths, cwd)
+        .with_envelope(envelope.then(|| std::mem::take(&mut cli.invocation)));
+    let timeout = config.timeout().unwrap_or(DEFAULT_TIMEOUT);
+    let path_filter = PathFilter::new(cli.include.clone(), cli.exclude.clone(), cli.include_deps);
+
+    if cli.watch {
+        return watch_command(
+            &cli.command,
             &workspace_root,
-            cli.workspace.as_deref(),
-            &detection_method,
-        );
+            &config,
+            &formatter,
+            timeout,
+            cli.zero_based,
+            cli.include_deps,
+            &path_filter,
+            styler,
+            debug_log.as_ref(),
+        )
+        .await;
     }
 
-    let formatter = OutputFormatter::with_detail(cli.format, cli.detail, styler);
-    let timeout = cli.timeout.map_or(DEFAULT_TIMEOUT, Duration::from_secs);
+    let redirect = redirect_output(cli.output_file.as_deref(), list)?;
+    let result = dispatch_command(
+        cli.command,
+        &workspace_root,
+        &config,
+        &formatter,
+        timeout,
+        cli.zero_based,
+        cli.include_deps,
+        &path_filter,
+        debug_log.as_ref(),
+    )
+    .await;
+    finish_output(result, redirect, cli.output_file.filter(|_| cli.open), list)
+}
 
-    dispatch_command(cli.command, &workspace_root, &formatter, timeout, debug_log.as_ref()).await?;
+/// Send stdout to `file` and/or turn it into an editor's `list`, if either
+/// was asked for.
+fn redirect_output(file: Option<&Path>, list: Option<LocationList>) -> Result<Option<Redirect>> {
+    if file.is_none() && list.is_none() {
+        return Ok(None);
+    }
+    let sink = match file {
+        Some(file) => Some(Box::new(FileSink::create(file)?) as Box<dyn OutputSink>),
+        None => None,
+    };
+    Redirect::start(sink, list).map(Some)
+}
 
+/// Finish the command whose outcome is `result`: whatever it printed, even
+/// before failing, goes where it was redirected, and on success the output
+/// file is opened in the editor with `--open`.
+fn finish_output(
+    result: Result<()>,
+    redirect: Option<Redirect>,
+    open: Option<PathBuf>,
+    list: Option<LocationList>,
+) -> Result<()> {
+    let finished = redirect.map_or(Ok(()), Redirect::finish);
+    result?;
+    finished?;
+    open.map_or(Ok(()), |file| open_in_editor(&file, list))
+}
+
+/// Reject output options `cli.command` can't honour.
+fn check_output_options(
+    cli: &Cli,
+    format: &OutputFormat,
+    list: Option<LocationList>,
+) -> Result<()> {
+    if *format == OutputFormat::Dot && !matches!(cli.command, Commands::Imports { .. }) {
+        return Err(
+            UsageError("--format dot is only supported by `tyf imports`".to_string()).into()
+        );
+    }
+    if cli.with_ranges
+        && !matches!(format, OutputFormat::Json | OutputFormat::Jsonl | OutputFormat::Csv)
+    {
+        return Err(UsageError(
+            "--with-ranges only changes json, jsonl and csv output".to_string(),
+        )
+        .into());
+    }
+    if (list.is_some() || cli.output_file.is_some()) && (cli.watch || is_interactive(&cli.command))
+    {
+        return Err(UsageError(
+            "--output-file, --format vim-qf and --format emacs need a command that prints its \
+             results once"
+                .to_string(),
+        )
+        .into());
+    }
     Ok(())
 }
 
+/// Whether `command` talks to the terminal or a client rather than printing
+/// results, so its output can't go to a file or an editor.
+const fn is_interactive(command: &Commands) -> bool {
+    matches!(
+        command,
+        Commands::Tui { .. } | Commands::Mcp | Commands::Serve { .. } | Commands::Daemon { .. }
+    )
+}
+
+/// Run `command`, then again each time a Python file in the workspace changes.
+///
+/// Before each re-run the daemon is told which files changed. A failing run
+/// is reported and watching goes on; only a missing ty ends it, so the usual
+/// install offer still applies.
+#[cfg(unix)]
+#[allow(clippy::too_many_arguments)]
+async fn watch_command(
+    command: &Commands,
+    workspace_root: &Path,
+    config: &Config,
+    formatter: &OutputFormatter,
+    timeout: Duration,
+    zero_based: bool,
+    include_deps: bool,
+    path_filter: &PathFilter,
+    styler: Styler,
+    debug_log: Option<&Arc<DebugLog>>,
+) -> Result<()> {
+    if let Some(name) = unwatchable(command) {
+        return Err(UsageError(format!(
+            "--watch re-runs queries and can't be used with `tyf {name}`"
+        ))
+        .into());
+    }
+    let mut watcher = watch::Watcher::new(workspace_root);
+    loop {
+        let run = dispatch_command(
+            command.clone(),
+            workspace_root,
+            config,
+            formatter,
+            timeout,
+            zero_based,
+            include_deps,
+            path_filter,
+            debug_log,
+        )
+        .await;
+        match run {
+            Err(e) if is_ty_not_found(&e) => return Err(e),
+            Err(e) if Failure::of(&e) == Failure::NoResults => {}
+            Err(e) => {
+                eprintln!("{}", styler.error(&format!("Error: {}", format_error_chain(&e))));
+            }
+            Ok(()) => {}
+        }
+        eprintln!("{}", styler.dim("Watching for changes (Ctrl-C to stop)"));
+
+        let changes = watcher.next_changes().await;
+        let names: Vec<String> = changes
+            .iter()
+            .map(|c| c.file.strip_prefix(workspace_root).unwrap_or(&c.file).display().to_string())
+            .collect();
+        commands::notify_files_changed(workspace_root, changes, timeout, debug_log).await?;
+        let shown = names.iter().take(3).cloned().collect::<Vec<_>>().join(", ");
+        let more = names.len().saturating_sub(3);
+        let summary = if more > 0 { format!("{shown} and {more} more") } else { shown };
+        eprintln!("\n{}\n", styler.heading(&format!("\u{2500}\u{2500} Changed: {summary}")));
+    }
+}
+
+#[cfg(not(unix))]
+#[allow(clippy::too_many_arguments)]
+async fn watch_command(
+    _command: &Commands,
+    _workspace_root: &Path,
+    _config: &Config,
+    _formatter: &OutputFormatter,
+    _timeout: Duration,
+    _zero_based: bool,
+    _include_deps: bool,
+    _path_filter: &PathFilter,
+    _styler: Styler,
+    _debug_log: Option<&Arc<DebugLog>>,
+) -> Result<()> {
+    anyhow::bail!("--watch requires the background daemon, which is only supported on Unix systems")
+}
+
+/// Name of a command that `--watch` must not repeat: one that changes
+/// files, manages the daemon, or installs things.
+#[cfg_attr(not(unix), allow(dead_code))]
+const fn unwatchable(command: &Commands) -> Option<&'static str> {
+    match command {
+        Commands::Move { apply: true, .. } => Some("move --apply"),
+        Commands::Moved { apply: true, .. } => Some("moved --apply"),
+        Commands::Daemon { .. } => Some("daemon"),
+        Commands::Setup { .. } => Some("setup"),
+        Commands::Bench { .. } => Some("bench"),
+        Commands::Doctor => Some("doctor"),
+        Commands::Config { .. } => Some("config"),
+        Commands::Alias { .. } => Some("alias"),
+        Commands::Mcp => Some("mcp"),
+        Commands::Batch => Some("batch"),
+        Commands::Serve { .. } => Some("serve"),
+        Commands::Tui { .. } => Some("tui"),
+        Commands::GenerateDocs { .. } => Some("generate-docs"),
+        _ => None,
+    }
+}
+
+#[allow(clippy::too_many_arguments, clippy::too_many_lines)]
 async fn dispatch_command(
     command: Commands,
     workspace_root: &Path,
+    config: &Config,
     formatter: &OutputFormatter,
     timeout: Duration,
+    zero_based: bool,
+    include_deps: bool,
+    path_filter: &PathFilter,
     debug_log: Option<&Arc<DebugLog>>,
 ) -> Result<()> {
     match command {
-        Commands::Find { file, symbols, fuzzy } => {
+        Commands::Find { file, symbols, fuzzy, kind, limit, offset } => {
             commands::handle_find_command(
                 workspace_root,
                 file.as_deref(),
                 &symbols,
                 fuzzy,
+                &kind,
+                ResultPage::new(offset, limit),
+                include_deps,
+                path_filter,
+                formatter,
+                timeout,
+                debug_log.cloned(),
+            )
+            .await?;
+        }
+        Commands::Symbols { query, kind, limit, offset, within } => {
+            commands::handle_symbols_command(
+                workspace_root,
+                &query,
+                &kind,
+                ResultPage::new(offset, limit),
+                within.as_deref(),
+                include_deps,
+                path_filter,
                 formatter,
                 timeout,
                 debug_log.cloned(),
             )
             .await?;
         }
+        Commands::Definition { queries, file, stdin_path } => {
+            commands::handle_definition_command(
+                workspace_root,
+                file.as_deref(),
+                &queries,
+                stdin_path.as_deref(),
+                formatter,
+                timeout,
+                zero_based,
+                debug_log.cloned(),
+            )
+            .await?;
+        }
+        Commands::Context { queries, file } => {
+            commands::handle_context_command(
+                workspace_root,
+                file.as_deref(),
+                &queries,
+                formatter,
+                timeout,
+                zero_based,
+                debug_log.cloned(),
+            )
+            .await?;
+        }
+        Commands::Stubs { queries, file, decl, type_definition, implementation } => {
+            commands::handle_stubs_command(
+                workspace_root,
+                file.as_deref(),
+                &queries,
+                &commands::goto_kinds(decl, type_definition, implementation),
+                formatter,
+                timeout,
+                zero_based,
+                debug_log.cloned(),
+            )
+            .await?;
+        }
+        Commands::Hover { queries, file, line, column, stdin_path } => {
+            commands::handle_hover_command(
+                workspace_root,
+                file.as_deref(),
+                &queries,
+                line.zip(column),
+                stdin_path.as_deref(),
+                formatter,
+                timeout,
+                zero_based,
+                debug_log.cloned(),
+            )
+            .await?;
+        }
+        Commands::Occurrences { queries, file, line, column } => {
+            commands::handle_occurrences_command(
+                workspace_root,
+                file.as_deref(),
+                &queries,
+                line.zip(column),
+                formatter,
+                timeout,
+                zero_based,
+                debug_log.cloned(),
+            )
+            .await?;
+        }
         Commands::References {
             queries,
             file,
@@ -146,9 +1042,26 @@ async fn dispatch_command(
             stdin,
             include_declaration,
             references_limit,
+            max_results,
+            limit,
+            offset,
             tests,
+            context: _,
+            kind,
+            count,
+            count_by_file,
+            unique,
+            git,
+            group_by: _,
+            sort,
         } => {
             let position = line.zip(column);
+            let page = ResultPage::new(offset, Some(limit.unwrap_or(max_results)));
+            let count = match (count, count_by_file) {
+                (_, true) => Some(commands::CountBy::File),
+                (true, false) => Some(commands::CountBy::Symbol),
+                (false, false) => None,
+            };
             commands::handle_references_command(
                 workspace_root,
                 file.as_deref(),
@@ -157,9 +1070,18 @@ async fn dispatch_command(
                 stdin,
                 include_declaration,
                 references_limit,
+                page,
+                count,
+                unique,
+                git,
+                sort,
                 formatter,
                 timeout,
                 tests,
+                zero_based,
+                include_deps,
+                &kind,
+                path_filter,
                 debug_log.cloned(),
             )
             .await?;
@@ -172,6 +1094,20 @@ async fn dispatch_command(
                 all,
                 formatter,
                 timeout,
+                zero_based,
+                debug_log.cloned(),
+            )
+            .await?;
+        }
+        Commands::ExplainType { query, file, depth } => {
+            commands::handle_explain_type_command(
+                workspace_root,
+                file.as_deref(),
+                &query,
+                depth,
+                formatter,
+                timeout,
+                zero_based,
                 debug_log.cloned(),
             )
             .await?;
@@ -186,13 +1122,31 @@ async fn dispatch_command(
             )
             .await?;
         }
-        Commands::Show { file, symbols, doc, references, references_limit, tests, all } => {
+        Commands::Show {
+            file,
+            within,
+            select,
+            all_matches,
+            symbols,
+            doc,
+            references,
+            references_limit,
+            tests,
+            all,
+        } => {
             let show_doc = doc || all;
             let show_refs = references || all;
             let show_tests = tests || all;
+            let choice = match (select, all_matches) {
+                (Some(n), _) => commands::MatchChoice::Select(n as usize),
+                (None, true) => commands::MatchChoice::All,
+                (None, false) => commands::MatchChoice::Ask,
+            };
             commands::handle_show_command(
                 workspace_root,
                 file.as_deref(),
+                within.as_deref(),
+                choice,
                 &symbols,
                 formatter,
                 timeout,
@@ -207,7 +1161,8 @@ async fn dispatch_command(
         Commands::Daemon { command } => {
             #[cfg(unix)]
             {
-                commands::handle_daemon_command(command).await?;
+                commands::handle_daemon_command(command, workspace_root, formatter, timeout)
+                    .await?;
             }
             #[cfg(not(unix))]
             {
@@ -215,11 +1170,410 @@ async fn dispatch_command(
                 anyhow::bail!("Daemon commands are only supported on Unix systems");
             }
         }
-        Commands::GenerateDocs { output_dir } => {
-            let cmd = Cli::command();
-            cli::generate_docs::generate_docs(&cmd, &output_dir)?;
+        Commands::Grep { pattern, resolves_to } => {
+            commands::handle_grep_command(
+                workspace_root,
+                &pattern,
+                &resolves_to,
+                formatter,
+                timeout,
+                debug_log.cloned(),
+            )
+            .await?;
+        }
+        Commands::Explain { location } => {
+            commands::handle_explain_command(
+                workspace_root,
+                &location,
+                formatter,
+                timeout,
+                zero_based,
+                debug_log.cloned(),
+            )
+            .await?;
+        }
+        Commands::Where { expression, file, line } => {
+            commands::handle_where_command(
+                workspace_root,
+                &file,
+                line,
+                &expression,
+                formatter,
+                timeout,
+                zero_based,
+                debug_log.cloned(),
+            )
+            .await?;
+        }
+        Commands::Annotate { file, side_by_side } => {
+            commands::handle_annotate_command(
+                workspace_root,
+                &file,
+                side_by_side,
+                formatter,
+                timeout,
+                debug_log.cloned(),
+            )
+            .await?;
+        }
+        Commands::Imports { target } => {
+            commands::handle_imports_command(
+                workspace_root,
+                &target,
+                formatter,
+                timeout,
+                debug_log.cloned(),
+            )
+            .await?;
+        }
+        Commands::Rdeps { file, jobs } => {
+            commands::handle_rdeps_command(
+                workspace_root,
+                &file,
+                jobs,
+                formatter,
+                timeout,
+                debug_log.cloned(),
+            )
+            .await?;
+        }
+        Commands::Api { path, jobs } => {
+            commands::handle_api_command(
+                workspace_root,
+                path.as_deref(),
+                jobs,
+                formatter,
+                timeout,
+                debug_log.cloned(),
+            )
+            .await?;
+        }
+        Commands::Stats { path, jobs } => {
+            commands::handle_stats_command(
+                workspace_root,
+                path.as_deref(),
+                jobs,
+                formatter,
+                timeout,
+                debug_log.cloned(),
+            )
+            .await?;
+        }
+        Commands::Check { files, baseline, update_baseline, severity, error_on, max_errors } => {
+            commands::handle_check_command(
+                workspace_root,
+                &files,
+                baseline.as_deref(),
+                update_baseline,
+                severity,
+                error_on,
+                max_errors,
+                formatter,
+                timeout,
+                debug_log.cloned(),
+            )
+            .await?;
+        }
+        Commands::CheckAll { jobs } => {
+            commands::handle_check_all_command(
+                workspace_root,
+                jobs,
+                formatter,
+                timeout,
+                debug_log.cloned(),
+            )
+            .await?;
+        }
+        Commands::Unused { path, jobs } => {
+            commands::handle_unused_command(
+                workspace_root,
+                path.as_deref(),
+                jobs,
+                formatter,
+                timeout,
+                debug_log.cloned(),
+            )
+            .await?;
+        }
+        Commands::Tui { query } => {
+            commands::handle_tui_command(
+                workspace_root,
+                query.as_deref(),
+                include_deps,
+                formatter,
+                timeout,
+                debug_log.cloned(),
+            )
+            .await?;
+        }
+        Commands::SigCheck { query, new_sig, file } => {
+            commands::handle_sig_check_command(
+                workspace_root,
+                file.as_deref(),
+                &query,
+                &new_sig,
+                formatter,
+                timeout,
+                zero_based,
+                debug_log.cloned(),
+            )
+            .await?;
+        }
+        Commands::Move { old, new, apply } => {
+            commands::handle_move_command(
+                workspace_root,
+                &old,
+                &new,
+                apply,
+                formatter,
+                timeout,
+                debug_log.cloned(),
+            )
+            .await?;
+        }
+        Commands::Moved { old, new, apply } => {
+            commands::handle_moved_command(
+                workspace_root,
+                &old,
+                &new,
+                apply,
+                formatter,
+                timeout,
+                debug_log.cloned(),
+            )
+            .await?;
+        }
+        Commands::TyInfo => {
+            commands::handle_ty_info_command(workspace_root, formatter, timeout).await?;
+        }
+        Commands::Doctor => {
+            commands::handle_doctor_command(workspace_root, formatter, timeout).await?;
+        }
+        Commands::Config { command: ConfigCommands::Show } => {
+            commands::handle_config_show_command(workspace_root, config, timeout, formatter);
+        }
+        Commands::Alias { command } => {
+            commands::handle_alias_command(workspace_root, config, command, formatter)?;
+        }
+        Commands::Run { name, .. } => {
+            // parse_args() has already replaced `run NAME` with the alias's
+            // command; what's left is an alias whose command is `run`.
+            return Err(UsageError(format!("An alias can't run another alias ('{name}')")).into());
+        }
+        Commands::Bench { query, iterations, runs } => {
+            commands::handle_bench_command(
+                workspace_root,
+                query.as_deref(),
+                iterations,
+                runs,
+                formatter,
+                timeout,
+            )
+            .await?;
+        }
+        Commands::Mcp => {
+            commands::handle_mcp_command(
+                workspace_root,
+                formatter,
+                timeout,
+                zero_based,
+                include_deps,
+                debug_log.cloned(),
+            )
+            .await?;
+        }
+        Commands::Batch => {
+            commands::handle_batch_command(
+                workspace_root,
+                formatter,
+                timeout,
+                zero_based,
+                include_deps,
+                debug_log.cloned(),
+            )
+            .await?;
+        }
+        Commands::Serve { .. } => commands::handle_serve_command().await?,
+        Commands::Setup { installer, dry_run } => {
+            commands::handle_setup_command(workspace_root, installer, dry_run).await?;
+        }
+        Commands::Completions { .. } | Commands::GenerateDocs { .. } => {
+            if let Some(result) = run_without_runtime(&command) {
+                result?;
+            }
         }
     }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    #[test]
+    fn test_watch_refuses_commands_that_change_things() {
+        let cli = Cli::try_parse_from(["tyf", "move", "a.py", "b.py", "--apply"]).unwrap();
+        assert_eq!(unwatchable(&cli.command), Some("move --apply"));
+        let cli = Cli::try_parse_from(["tyf", "move", "a.py", "b.py"]).unwrap();
+        assert_eq!(unwatchable(&cli.command), None);
+        let cli = Cli::try_parse_from(["tyf", "moved", "a.py", "b.py", "--apply"]).unwrap();
+        assert_eq!(unwatchable(&cli.command), Some("moved --apply"));
+        let cli = Cli::try_parse_from(["tyf", "refs", "my_func"]).unwrap();
+        assert_eq!(unwatchable(&cli.command), None);
+    }
+
+    #[test]
+    fn test_resolve_workspace_walks_up_from_target_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let project = dir.path().join("project");
+        let pkg = project.join("pkg");
+        std::fs::create_dir_all(&pkg).unwrap();
+        std::fs::write(project.join("pyproject.toml"), "").unwrap();
+        let file = pkg.join("app.py");
+        std::fs::write(&file, "x = 1\n").unwrap();
+        let elsewhere = dir.path().join("elsewhere");
+        std::fs::create_dir(&elsewhere).unwrap();
+
+        let (root, method) = resolve_workspace(None, &elsewhere, Some(&file)).unwrap();
+        assert_eq!(root.canonical_root(), lsp::uri::canonicalize(&project).unwrap());
+        assert!(method.contains("walked up from"), "unexpected method: {method}");
+    }
+
+    #[test]
+    fn test_resolve_workspace_explicit_wins_over_target_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("app.py");
+        std::fs::write(&file, "x = 1\n").unwrap();
+
+        let (root, method) =
+            resolve_workspace(Some(dir.path()), dir.path(), Some(Path::new("/nonexistent/a.py")))
+                .unwrap();
+        assert_eq!(root.canonical_root(), lsp::uri::canonicalize(dir.path()).unwrap());
+        assert_eq!(method, "explicit --workspace flag");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_resolve_workspace_through_symlinked_cwd() {
+        let dir = tempfile::tempdir().unwrap();
+        let real = dir.path().join("real");
+        std::fs::create_dir(&real).unwrap();
+        std::fs::write(real.join("pyproject.toml"), "").unwrap();
+        let link = dir.path().join("link");
+        std::os::unix::fs::symlink(&real, &link).unwrap();
+
+        let (paths, _) = resolve_workspace(None, &link, None).unwrap();
+        assert_eq!(paths.canonical_root(), real.canonicalize().unwrap());
+        assert_eq!(paths.to_user(&paths.canonical_root().join("a.py")), link.join("a.py"));
+    }
+
+    /// Runs outside any tokio runtime, so this fails if the command needs one.
+    #[test]
+    fn test_generate_docs_runs_without_runtime() {
+        let dir = tempfile::tempdir().unwrap();
+        let command = Commands::GenerateDocs { output_dir: dir.path().to_path_buf() };
+
+        run_without_runtime(&command).expect("handled without a runtime").unwrap();
+        assert!(dir.path().read_dir().unwrap().next().is_some(), "docs should be written");
+        assert!(run_without_runtime(&Commands::TyInfo).is_none());
+    }
+
+    #[test]
+    fn test_absolutize_file_args_only_rewrites_existing_paths() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("app.py"), "x = 1\n").unwrap();
+
+        let mut command = Commands::References {
+            queries: vec!["app.py:1:1".to_string(), "missing.py:2:3".to_string()],
+            file: None,
+            line: None,
+            column: None,
+            stdin: false,
+            include_declaration: false,
+            references_limit: 0,
+            max_results: 0,
+            limit: None,
+            offset: 0,
+            tests: false,
+            context: 0,
+            kind: Vec::new(),
+            count: false,
+            count_by_file: false,
+            unique: false,
+            git: false,
+            group_by: None,
+            sort: None,
+        };
+        absolutize_file_args(&mut command, dir.path());
+
+        let Commands::References { queries, .. } = &command else { unreachable!() };
+        assert_eq!(queries[0], format!("{}:1:1", dir.path().join("app.py").display()));
+        assert_eq!(queries[1], "missing.py:2:3");
+        assert_eq!(target_file(&command), Some(dir.path().join("app.py")));
+    }
+
+    #[test]
+    fn test_expand_alias_replaces_the_unknown_subcommand() {
+        let args: Vec<OsString> =
+            ["tyf", "--format", "csv", "handlers", "--limit", "5"].map(OsString::from).into();
+        let expanded = expand_alias(&args, "handlers", 1, "symbols handle_ --kind function");
+        let expanded = expanded.unwrap();
+        assert_eq!(
+            expanded,
+            ["tyf", "--format", "csv", "symbols", "handle_", "--kind", "function", "--limit", "5"]
+                .map(OsString::from)
+        );
+        let cli = Cli::try_parse_from(expanded).unwrap();
+        assert!(matches!(cli.command, Commands::Symbols { limit: Some(5), .. }));
+    }
+
+    #[test]
+    fn test_expand_alias_fills_in_placeholders() {
+        let command = "refs --count --sort count {1} --file {2}.py";
+        let args: Vec<OsString> =
+            ["tyf", "run", "hot", "handler_*", "app", "--limit", "3"].map(OsString::from).into();
+        assert_eq!(
+            expand_alias(&args, "run", 2, command).unwrap(),
+            [
+                "tyf",
+                "refs",
+                "--count",
+                "--sort",
+                "count",
+                "handler_*",
+                "--file",
+                "app.py",
+                "--limit",
+                "3"
+            ]
+            .map(OsString::from)
+        );
+
+        // Braces that aren't placeholders are left alone.
+        let args: Vec<OsString> = ["tyf", "hot", "x"].map(OsString::from).into();
+        let expanded = expand_alias(&args, "hot", 1, "grep {a,b} {1}{1}").unwrap();
+        assert_eq!(expanded, ["tyf", "grep", "{a,b}", "xx"].map(OsString::from));
+
+        let error = expand_alias(&args, "hot", 1, "refs {1} {2}").unwrap_err();
+        assert_eq!(error, "needs at least 2 argument(s) for {2}, got 1");
+    }
+
+    #[test]
+    fn test_output_format_falls_back_to_the_config() {
+        let with_format = |format: &str| {
+            Config::default().with_layer(
+                Source::Workspace(PathBuf::from("/proj/.ty-find.toml")),
+                Settings { format: Some(format.to_string()), ..Settings::default() },
+            )
+        };
+        assert!(output_format(None, &Config::default()).unwrap() == OutputFormat::Human);
+        assert!(output_format(None, &with_format("jsonl")).unwrap() == OutputFormat::Jsonl);
+        let flag = output_format(Some(OutputFormat::Csv), &with_format("json")).unwrap();
+        assert!(flag == OutputFormat::Csv);
+        let Err(error) = output_format(None, &with_format("dot")) else { panic!("dot") };
+        assert_eq!(Failure::of(&error), Failure::Usage);
+        assert!(error.to_string().contains("in /proj/.ty-find.toml"), "{error}");
+    }
+}
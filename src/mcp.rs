@@ -0,0 +1,403 @@
+//! Model Context Protocol server for `tyf mcp`.
+//!
+//! Speaks MCP's JSON-RPC over stdio, one message per line, and exposes tyf's
+//! lookups as tools. Tool calls go through the daemon like the CLI commands
+//! do, so an agent shares the warm ty servers with the terminal, and results
+//! are rendered by the same formatter (`--format` picks which).
+
+use anyhow::{Context, Result};
+use serde_json::{json, Value};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+use crate::cli::output::{OutputFormatter, SourceCache};
+use crate::commands::{self, ReferenceOptions};
+use crate::debug::DebugLog;
+use crate::workspace::origin::Origin;
+use crate::workspace::page::ResultPage;
+
+/// MCP revisions this server speaks, newest first.
+const PROTOCOL_VERSIONS: &[&str] = &["2025-06-18", "2025-03-26", "2024-11-05"];
+
+/// Most references listed per query, as `refs` does by default.
+const REFERENCES_LIMIT: usize = 20;
+
+/// A tool call's settings, taken from the `tyf mcp` command line.
+pub struct McpServer<'a> {
+    pub workspace_root: &'a Path,
+    pub formatter: &'a OutputFormatter,
+    pub timeout: Duration,
+    pub zero_based: bool,
+    pub include_deps: bool,
+    pub debug_log: Option<&'a Arc<DebugLog>>,
+}
+
+impl McpServer<'_> {
+    /// Answer requests from stdin until it closes.
+    pub async fn serve(&self) -> Result<()> {
+        let mut lines = BufReader::new(tokio::io::stdin()).lines();
+        let mut stdout = tokio::io::stdout();
+        while let Some(line) = lines.next_line().await.context("Failed to read from stdin")? {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let response = match serde_json::from_str::<Value>(&line) {
+                Ok(message) => self.handle_message(message).await,
+                Err(e) => Some(error_response(&Value::Null, -32700, &format!("Parse error: {e}"))),
+            };
+            if let Some(response) = response {
+                let mut text = response.to_string();
+                text.push('\n');
+                stdout.write_all(text.as_bytes()).await.context("Failed to write to stdout")?;
+                stdout.flush().await.context("Failed to flush stdout")?;
+            }
+        }
+        Ok(())
+    }
+
+    /// The response to one message; notifications get none.
+    pub async fn handle_message(&self, message: Value) -> Option<Value> {
+        let id = message.get("id").cloned()?;
+        let method = message.get("method").and_then(Value::as_str).unwrap_or_default();
+        let params = message.get("params").cloned().unwrap_or(Value::Null);
+        let result = match method {
+            "initialize" => initialize(&params),
+            "ping" => json!({}),
+            "tools/list" => json!({ "tools": tool_definitions() }),
+            "tools/call" => self.call_tool(&params).await,
+            _ => return Some(error_response(&id, -32601, &format!("Method not found: {method}"))),
+        };
+        Some(json!({ "jsonrpc": "2.0", "id": id, "result": result }))
+    }
+
+    /// Run a tool; failures are reported in the result, as MCP asks, so the
+    /// agent sees them.
+    async fn call_tool(&self, params: &Value) -> Value {
+        let name = params.get("name").and_then(Value::as_str).unwrap_or_default();
+        let args = params.get("arguments").cloned().unwrap_or_else(|| json!({}));
+        let (text, is_error) = match self.run_tool(name, &args).await {
+            Ok(text) => (text, false),
+            Err(e) => (format!("{e:#}"), true),
+        };
+        json!({ "content": [{ "type": "text", "text": text }], "isError": is_error })
+    }
+
+    #[allow(clippy::too_many_lines)]
+    async fn run_tool(&self, name: &str, args: &Value) -> Result<String> {
+        if !tool_definitions().iter().any(|tool| tool["name"] == name) {
+            anyhow::bail!("Unknown tool: {name}");
+        }
+        let root = self.workspace_root;
+        let file = optional_path(args, "file");
+        let mut client = commands::connect_daemon(self.timeout, self.debug_log).await?;
+        match name {
+            "definition" => {
+                let queries = string_list(args, "queries")?;
+                let results = commands::collect_definitions(
+                    &mut client,
+                    root,
+                    file.as_deref(),
+                    &queries,
+                    self.zero_based,
+                )
+                .await?;
+                let cache = SourceCache::from_uris(
+                    results.iter().flat_map(|(_, locs)| locs).map(|l| l.uri.as_str()),
+                )
+                .await;
+                Ok(self.formatter.format_find_results(&results, &cache))
+            }
+            "references" => {
+                let queries = string_list(args, "queries")?;
+                let limit = args.get("limit").and_then(Value::as_u64).map(usize::try_from);
+                let options = ReferenceOptions {
+                    include_declaration: true,
+                    include_deps: self.include_deps,
+                    zero_based: self.zero_based,
+                    references_limit: limit.transpose()?.unwrap_or(REFERENCES_LIMIT),
+                    page: ResultPage::new(0, Some(10_000)),
+                    show_tests: args.get("include_tests").and_then(Value::as_bool).unwrap_or(false),
+                    kinds: Vec::new(),
+                    unique: false,
+                    git: false,
+                    sort: None,
+                };
+                let results = commands::collect_references(
+                    &mut client,
+                    root,
+                    file.as_deref(),
+                    &queries,
+                    options,
+                )
+                .await?;
+                let cache = SourceCache::from_uris(results.iter().flat_map(|r| {
+                    let main = r.displayed.iter().map(|e| e.location.uri.as_str());
+                    let test = r
+                        .test_references
+                        .iter()
+                        .flat_map(|t| t.displayed.iter().map(|e| e.location.uri.as_str()));
+                    main.chain(test)
+                }))
+                .await;
+                Ok(self.formatter.format_enriched_references_results(&results, &cache))
+            }
+            "hover" => {
+                let queries = string_list(args, "queries")?;
+                let entries = commands::collect_hover_entries(
+                    &mut client,
+                    root,
+                    file.as_deref(),
+                    &queries,
+                    self.zero_based,
+                )
+                .await?;
+                let cache = SourceCache::from_uris(
+                    entries.iter().filter_map(|e| e.location.as_ref()).map(|l| l.uri.as_str()),
+                )
+                .await;
+                Ok(self.formatter.format_hover_results(&entries, &cache))
+            }
+            "symbols" => {
+                let query = string_list(args, "query")?.join(" ");
+                let mut found = client
+                    .execute_workspace_symbols(root.to_path_buf(), query.clone(), Vec::new())
+                    .await?
+                    .symbols;
+                if !self.include_deps {
+                    found.retain(|s| Origin::classify(&s.location.uri, root) == Origin::Workspace);
+                }
+                if found.is_empty() {
+                    return Ok(format!("No results found matching '{query}'"));
+                }
+                let cache =
+                    SourceCache::from_uris(found.iter().map(|s| s.location.uri.as_str())).await;
+                Ok(self.formatter.format_workspace_symbols(&found, &cache))
+            }
+            _ => {
+                let classes = string_list(args, "classes")?;
+                let include_all = args.get("include_all").and_then(Value::as_bool).unwrap_or(false);
+                let mut problems = Vec::new();
+                let mut results = Vec::new();
+                for class in &classes {
+                    let result = commands::members_single_class(
+                        &mut client,
+                        root,
+                        file.as_deref(),
+                        class,
+                        include_all,
+                        self.zero_based,
+                    )
+                    .await?;
+                    match commands::not_a_class(&result) {
+                        Some(problem) => problems.push(problem),
+                        None => results.push(result),
+                    }
+                }
+                let cache =
+                    SourceCache::from_uris(results.iter().map(|r| r.file_uri.as_str())).await;
+                let mut sections = problems;
+                if !results.is_empty() {
+                    sections.push(self.formatter.format_members_results(&results, &cache));
+                }
+                Ok(sections.join("\n\n"))
+            }
+        }
+    }
+}
+
+/// The `initialize` result, agreeing on the client's MCP revision when we
+/// speak it and offering our newest otherwise.
+fn initialize(params: &Value) -> Value {
+    let requested = params.get("protocolVersion").and_then(Value::as_str);
+    let version =
+        requested.filter(|v| PROTOCOL_VERSIONS.contains(v)).unwrap_or(PROTOCOL_VERSIONS[0]);
+    json!({
+        "protocolVersion": version,
+        "capabilities": { "tools": { "listChanged": false } },
+        "serverInfo": { "name": "tyf", "version": env!("CARGO_PKG_VERSION") },
+        "instructions": "Type-aware navigation of the Python project tyf was started in, \
+            backed by ty. Name symbols plainly (`create_order`), as Class.member \
+            (`OrderService.submit`), or by position (`src/orders.py:42:9`, 1-based). \
+            Paths are relative to the project root.",
+    })
+}
+
+fn error_response(id: &Value, code: i64, message: &str) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "error": { "code": code, "message": message } })
+}
+
+/// The tools offered by `tools/list`.
+fn tool_definitions() -> Vec<Value> {
+    let queries = json!({
+        "type": "array",
+        "items": { "type": "string" },
+        "description": "Symbol names, Class.member names, or file:line:col positions",
+    });
+    let file = json!({
+        "type": "string",
+        "description": "Only look for the names in this file",
+    });
+    vec![
+        json!({
+            "name": "definition",
+            "description": "Where symbols are defined",
+            "inputSchema": {
+                "type": "object",
+                "properties": { "queries": queries, "file": file },
+                "required": ["queries"],
+            },
+        }),
+        json!({
+            "name": "references",
+            "description": "Every usage of symbols across the project, with the enclosing \
+                function or class of each. Test files are left out unless include_tests is set.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "queries": queries,
+                    "file": file,
+                    "limit": {
+                        "type": "integer",
+                        "minimum": 0,
+                        "description": "Most references listed per query (default 20, 0 = all)",
+                    },
+                    "include_tests": { "type": "boolean", "description": "Also list usages in tests" },
+                },
+                "required": ["queries"],
+            },
+        }),
+        json!({
+            "name": "hover",
+            "description": "Type signature and docstring of symbols",
+            "inputSchema": {
+                "type": "object",
+                "properties": { "queries": queries, "file": file },
+                "required": ["queries"],
+            },
+        }),
+        json!({
+            "name": "symbols",
+            "description": "Search the project's functions, classes and variables by partial name",
+            "inputSchema": {
+                "type": "object",
+                "properties": { "query": { "type": "string", "description": "Part of a name" } },
+                "required": ["query"],
+            },
+        }),
+        json!({
+            "name": "members",
+            "description": "Public interface of classes: methods, properties and class \
+                variables with their signatures. A `file:line:col` in place of a class lists \
+                the attributes of the expression there (an instance, module or union)",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "classes": { "type": "array", "items": { "type": "string" } },
+                    "file": file,
+                    "include_all": {
+                        "type": "boolean",
+                        "description": "Also list private and dunder members",
+                    },
+                },
+                "required": ["classes"],
+            },
+        }),
+    ]
+}
+
+/// A required argument holding a string or a list of strings.
+fn string_list(args: &Value, key: &str) -> Result<Vec<String>> {
+    let values = match args.get(key) {
+        Some(Value::String(s)) => vec![s.clone()],
+        Some(Value::Array(items)) => {
+            items.iter().filter_map(Value::as_str).map(str::to_string).collect()
+        }
+        _ => Vec::new(),
+    };
+    if values.iter().all(|v| v.trim().is_empty()) {
+        anyhow::bail!("Missing required argument '{key}'");
+    }
+    Ok(values)
+}
+
+fn optional_path(args: &Value, key: &str) -> Option<PathBuf> {
+    args.get(key).and_then(Value::as_str).filter(|s| !s.is_empty()).map(PathBuf::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::args::OutputFormat;
+
+    fn server(formatter: &OutputFormatter) -> McpServer<'_> {
+        McpServer {
+            workspace_root: Path::new("/proj"),
+            formatter,
+            timeout: Duration::from_secs(1),
+            zero_based: false,
+            include_deps: false,
+            debug_log: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_initialize_negotiates_version() {
+        let formatter = OutputFormatter::new(OutputFormat::Human);
+        let server = server(&formatter);
+        let request = json!({
+            "jsonrpc": "2.0", "id": 1, "method": "initialize",
+            "params": { "protocolVersion": "2024-11-05", "capabilities": {} },
+        });
+        let response = server.handle_message(request).await.unwrap();
+        assert_eq!(response["id"], 1);
+        assert_eq!(response["result"]["protocolVersion"], "2024-11-05");
+        assert_eq!(response["result"]["serverInfo"]["name"], "tyf");
+
+        let request = json!({
+            "jsonrpc": "2.0", "id": 2, "method": "initialize",
+            "params": { "protocolVersion": "1999-01-01" },
+        });
+        let response = server.handle_message(request).await.unwrap();
+        assert_eq!(response["result"]["protocolVersion"], PROTOCOL_VERSIONS[0]);
+    }
+
+    #[tokio::test]
+    async fn test_lists_tools_and_rejects_unknown_requests() {
+        let formatter = OutputFormatter::new(OutputFormat::Human);
+        let server = server(&formatter);
+        let list = json!({ "jsonrpc": "2.0", "id": "a", "method": "tools/list" });
+        let response = server.handle_message(list).await.unwrap();
+        let names: Vec<&str> = response["result"]["tools"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|tool| tool["name"].as_str().unwrap())
+            .collect();
+        assert_eq!(names, ["definition", "references", "hover", "symbols", "members"]);
+
+        let notification = json!({ "jsonrpc": "2.0", "method": "notifications/initialized" });
+        assert!(server.handle_message(notification).await.is_none());
+
+        let unknown = json!({ "jsonrpc": "2.0", "id": 3, "method": "resources/list" });
+        let response = server.handle_message(unknown).await.unwrap();
+        assert_eq!(response["error"]["code"], -32601);
+
+        let call = json!({
+            "jsonrpc": "2.0", "id": 4, "method": "tools/call",
+            "params": { "name": "rename", "arguments": {} },
+        });
+        let response = server.handle_message(call).await.unwrap();
+        assert_eq!(response["result"]["isError"], true);
+        assert_eq!(response["result"]["content"][0]["text"], "Unknown tool: rename");
+    }
+
+    #[test]
+    fn test_string_list_accepts_one_or_many() {
+        assert_eq!(string_list(&json!({ "q": "a" }), "q").unwrap(), ["a"]);
+        assert_eq!(string_list(&json!({ "q": ["a", "b"] }), "q").unwrap(), ["a", "b"]);
+        assert!(string_list(&json!({ "q": [] }), "q").is_err());
+        assert!(string_list(&json!({}), "q").is_err());
+    }
+}
@@ -0,0 +1,138 @@
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::lsp::backend::LspBackend;
+use crate::lsp::client::TyLspClient;
+use crate::lsp::members::{self, MembersResult};
+use crate::lsp::protocol::{Hover, Location, SymbolInformation};
+use crate::lsp::warmup::{
+    hover_with_warmup, with_warmup, workspace_symbols_with_warmup, WARMUP_DELAYS,
+};
+
+/// Type-aware navigation of one Python workspace, answered by ty.
+///
+/// Starts its own ty language server and keeps it for as long as the
+/// `Navigator` lives; dropping it stops the server. Requests retry briefly
+/// while ty is still analysing the workspace, as the tyf daemon does.
+///
+/// Files may be given relative to the workspace root. Positions are 0-based
+/// lines and characters, as in LSP.
+pub struct Navigator {
+    workspace_root: PathBuf,
+    client: TyLspClient,
+}
+
+impl Navigator {
+    /// Start ty for the workspace at `workspace_root`.
+    ///
+    /// Fails when ty can't be found or doesn't start; ty is looked up in the
+    /// workspace's virtualenv first, then on `PATH`. A workspace configured
+    /// for another backend, or `TYF_BACKEND`, gets that server instead.
+    pub async fn new(workspace_root: impl AsRef<Path>) -> Result<Self> {
+        let workspace_root = workspace_root.as_ref().to_path_buf();
+        let root = workspace_root.to_str().context("Invalid workspace path")?;
+        let client = TyLspClient::new(root).await?;
+        Ok(Self { workspace_root, client })
+    }
+
+    /// Start `backend`'s language server for the workspace, e.g.
+    /// `lsp::backend::from_spec("pyright")?`.
+    pub async fn with_backend(
+        workspace_root: impl AsRef<Path>,
+        backend: Arc<dyn LspBackend>,
+    ) -> Result<Self> {
+        let workspace_root = workspace_root.as_ref().to_path_buf();
+        let root = workspace_root.to_str().context("Invalid workspace path")?;
+        let client = TyLspClient::with_backend(root, backend).await?;
+        Ok(Self { workspace_root, client })
+    }
+
+    /// The workspace this navigator answers for.
+    pub fn workspace_root(&self) -> &Path {
+        &self.workspace_root
+    }
+
+    /// The underlying LSP client, for requests the navigator doesn't wrap.
+    pub const fn lsp_client(&self) -> &TyLspClient {
+        &self.client
+    }
+
+    /// Where the symbol at a position is defined.
+    pub async fn definition(
+        &self,
+        file: impl AsRef<Path>,
+        line: u32,
+        character: u32,
+    ) -> Result<Vec<Location>> {
+        let file = self.open(file.as_ref()).await?;
+        with_warmup(
+            "definition",
+            &WARMUP_DELAYS,
+            |locs: &Vec<Location>| !locs.is_empty(),
+            || self.client.goto_definition(&file, line, character),
+            None,
+        )
+        .await
+    }
+
+    /// Every usage of the symbol at a position, optionally with its definition.
+    pub async fn references(
+        &self,
+        file: impl AsRef<Path>,
+        line: u32,
+        character: u32,
+        include_declaration: bool,
+    ) -> Result<Vec<Location>> {
+        let file = self.open(file.as_ref()).await?;
+        with_warmup(
+            "references",
+            &WARMUP_DELAYS,
+            |locs: &Vec<Location>| !locs.is_empty(),
+            || self.client.find_references(&file, line, character, include_declaration),
+            None,
+        )
+        .await
+    }
+
+    /// Type signature and docstring of the symbol at a position.
+    pub async fn hover(
+        &self,
+        file: impl AsRef<Path>,
+        line: u32,
+        character: u32,
+    ) -> Result<Option<Hover>> {
+        let file = self.open(file.as_ref()).await?;
+        hover_with_warmup(&self.client, &file, line, character).await
+    }
+
+    /// Symbols in the workspace and its dependencies whose names fuzzily
+    /// match `query`.
+    pub async fn symbols(&self, query: &str) -> Result<Vec<SymbolInformation>> {
+        workspace_symbols_with_warmup(&self.client, query, &self.workspace_root).await
+    }
+
+    /// Methods, properties and class variables of the class `class_name`
+    /// defined in `file`, with their signatures.
+    ///
+    /// Private and dunder members are left out unless `include_all` is set.
+    /// When `file` has no such symbol, or it isn't a class, the result has
+    /// no members and `symbol_kind` tells which.
+    pub async fn members(
+        &self,
+        file: impl AsRef<Path>,
+        class_name: &str,
+        include_all: bool,
+    ) -> Result<MembersResult> {
+        let file = self.open(file.as_ref()).await?;
+        members::class_members(&self.client, &file, class_name, include_all).await
+    }
+
+    /// Resolve `file` against the workspace root and open it in ty.
+    async fn open(&self, file: &Path) -> Result<String> {
+        let file = self.workspace_root.join(file);
+        let file = file.to_str().context("Invalid file path")?.to_string();
+        self.client.open_document(&file).await?;
+        Ok(file)
+    }
+}
@@ -0,0 +1,80 @@
+//! Grouping for `tyf rdeps`: the references to a file's definitions, turned
+//! into the files that depend on it.
+
+use std::collections::BTreeMap;
+
+use crate::cli::output::DependentFile;
+use crate::daemon::protocol::BatchReferencesEntry;
+
+/// Files other than `uri` that reference the symbols in `entries`, sorted
+/// by URI.
+///
+/// Each entry's label is the symbol it was queried for; a file lists every
+/// symbol it references once, in query order.
+pub fn dependents(uri: &str, entries: &[BatchReferencesEntry]) -> Vec<DependentFile> {
+    let mut files: BTreeMap<&str, DependentFile> = BTreeMap::new();
+    for entry in entries {
+        for location in entry.locations.iter().filter(|l| l.uri != uri) {
+            let file = files.entry(location.uri.as_str()).or_insert_with(|| DependentFile {
+                uri: location.uri.clone(),
+                symbols: Vec::new(),
+                references: 0,
+            });
+            file.references += 1;
+            if !file.symbols.contains(&entry.label) {
+                file.symbols.push(entry.label.clone());
+            }
+        }
+    }
+    files.into_values().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lsp::protocol::{Location, Position, Range};
+
+    fn location(uri: &str, line: u32) -> Location {
+        let position = Position { line, character: 0 };
+        Location { uri: uri.to_string(), range: Range { start: position.clone(), end: position } }
+    }
+
+    fn entry(label: &str, locations: Vec<Location>) -> BatchReferencesEntry {
+        BatchReferencesEntry {
+            label: label.to_string(),
+            locations,
+            files: Vec::new(),
+            remaining: 0,
+        }
+    }
+
+    #[test]
+    fn dependents_group_references_by_file() {
+        let own = "file:///proj/app/models.py";
+        let entries = vec![
+            entry(
+                "Order",
+                vec![
+                    location(own, 20),
+                    location("file:///proj/tests/test_orders.py", 3),
+                    location("file:///proj/app/views.py", 1),
+                    location("file:///proj/app/views.py", 9),
+                ],
+            ),
+            entry("Order.total", vec![location("file:///proj/app/views.py", 12)]),
+            entry("helper", vec![location(own, 40)]),
+        ];
+        let files = dependents(own, &entries);
+        let summary: Vec<(&str, usize, Vec<&str>)> = files
+            .iter()
+            .map(|f| (f.uri.as_str(), f.references, f.symbols.iter().map(String::as_str).collect()))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                ("file:///proj/app/views.py", 3, vec!["Order", "Order.total"]),
+                ("file:///proj/tests/test_orders.py", 1, vec!["Order"]),
+            ]
+        );
+    }
+}
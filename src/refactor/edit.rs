@@ -0,0 +1,146 @@
+//! Line-based edits to source files, with a unified diff preview.
+//!
+//! Refactoring commands describe their changes as [`LineEdit`]s per file,
+//! preview them with [`unified_diff`] and write them with [`apply`]. Edits
+//! replace whole lines, which is all import rewriting needs and keeps the
+//! diff trivial to produce.
+
+use std::fmt::Write;
+
+/// Replace one line of a file with zero or more lines.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LineEdit {
+    /// 0-based line
+    pub line: usize,
+    /// The line's replacement, without line endings. Empty deletes the line.
+    pub new_lines: Vec<String>,
+}
+
+impl LineEdit {
+    pub fn replace(line: usize, new_line: String) -> Self {
+        Self { line, new_lines: vec![new_line] }
+    }
+}
+
+/// `text` with `edits` applied.
+///
+/// Each replaced line keeps its original line ending, so `\r\n` files stay
+/// `\r\n`. Edits for lines past the end are ignored.
+pub fn apply(text: &str, edits: &[LineEdit]) -> String {
+    let mut output = String::with_capacity(text.len());
+    let mut edits = edits.iter().peekable();
+    for (idx, line) in text.split_inclusive('\n').enumerate() {
+        let Some(edit) = edits.next_if(|edit| edit.line == idx) else {
+            output.push_str(line);
+            continue;
+        };
+        let content = line.trim_end_matches(['\r', '\n']);
+        let ending = &line[content.len()..];
+        let ending = if ending.is_empty() { "\n" } else { ending };
+        for (i, new_line) in edit.new_lines.iter().enumerate() {
+            output.push_str(new_line);
+            // Don't add a newline the file didn't have at its end.
+            if i + 1 < edit.new_lines.len() || !line[content.len()..].is_empty() {
+                output.push_str(ending);
+            }
+        }
+    }
+    output
+}
+
+/// Unified diff of `edits` to `text`, with `CONTEXT` lines around each change.
+///
+/// `old_label` and `new_label` name the file in the `---`/`+++` headers, so a
+/// moved file can show both of its paths. Returns an empty string when there
+/// are no edits.
+pub fn unified_diff(old_label: &str, new_label: &str, text: &str, edits: &[LineEdit]) -> String {
+    const CONTEXT: usize = 3;
+
+    let lines: Vec<&str> = text.lines().collect();
+    let edits: Vec<&LineEdit> = edits.iter().filter(|e| e.line < lines.len()).collect();
+    if edits.is_empty() {
+        return String::new();
+    }
+
+    // Group edits whose context windows touch into hunks.
+    let mut hunks: Vec<Vec<&LineEdit>> = Vec::new();
+    for edit in edits {
+        match hunks.last_mut() {
+            Some(hunk) if hunk.last().is_some_and(|last| edit.line <= last.line + 2 * CONTEXT) => {
+                hunk.push(edit);
+            }
+            _ => hunks.push(vec![edit]),
+        }
+    }
+
+    let mut output = format!("--- a/{old_label}\n+++ b/{new_label}\n");
+    // Lines that earlier hunks added and removed, to number the new side.
+    let (mut added, mut removed) = (0, 0);
+    for hunk in hunks {
+        let (Some(first), Some(last)) = (hunk.first(), hunk.last()) else { continue };
+        let start = first.line.saturating_sub(CONTEXT);
+        let end = (last.line + CONTEXT + 1).min(lines.len());
+        let mut body = String::new();
+        let mut new_count = 0;
+        let mut edits = hunk.iter().peekable();
+        for (idx, line) in lines.iter().enumerate().take(end).skip(start) {
+            if let Some(edit) = edits.next_if(|edit| edit.line == idx) {
+                let _ = writeln!(body, "-{line}");
+                for new_line in &edit.new_lines {
+                    let _ = writeln!(body, "+{new_line}");
+                }
+                new_count += edit.new_lines.len();
+            } else {
+                let _ = writeln!(body, " {line}");
+                new_count += 1;
+            }
+        }
+        let old_count = end - start;
+        let new_start = start + added - removed;
+        let _ = write!(
+            output,
+            "@@ -{},{old_count} +{},{new_count} @@\n{body}",
+            start + 1,
+            new_start + 1
+        );
+        added += new_count;
+        removed += old_count;
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_apply_keeps_line_endings() {
+        let text = "a\r\nb\r\nc";
+        let edits = [
+            LineEdit { line: 0, new_lines: vec!["x".into(), "y".into()] },
+            LineEdit { line: 1, new_lines: Vec::new() },
+            LineEdit::replace(2, "z".into()),
+        ];
+        assert_eq!(apply(text, &edits), "x\r\ny\r\nz");
+    }
+
+    #[test]
+    fn test_unified_diff_merges_nearby_edits() {
+        let text = (1..=20).fold(String::new(), |mut text, n| {
+            let _ = writeln!(text, "line {n}");
+            text
+        });
+        let edits = [
+            LineEdit::replace(1, "two".into()),
+            LineEdit { line: 4, new_lines: vec!["five".into(), "five b".into()] },
+            LineEdit { line: 15, new_lines: Vec::new() },
+        ];
+        let diff = unified_diff("old.py", "new.py", &text, &edits);
+        assert!(diff
+            .starts_with("--- a/old.py\n+++ b/new.py\n@@ -1,8 +1,9 @@\n line 1\n-line 2\n+two\n"));
+        assert!(diff.contains("-line 5\n+five\n+five b\n line 6\n"), "got:\n{diff}");
+        assert!(diff.contains("@@ -13,7 +14,6 @@\n line 13\n"), "got:\n{diff}");
+        assert!(diff.ends_with("-line 16\n line 17\n line 18\n line 19\n"), "got:\n{diff}");
+        assert!(unified_diff("a", "a", &text, &[]).is_empty());
+    }
+}
@@ -0,0 +1,506 @@
+//! Reading and rewriting Python imports, for `tyf move` and `tyf imports`.
+//!
+//! Import statements are found in the text, line by line; this is not a
+//! Python parser, but it handles the forms imports take in practice: plain
+//! and aliased imports, `from` imports with parenthesized or backslash
+//! continued name lists, relative imports, and trailing comments.
+
+use super::edit::LineEdit;
+use crate::workspace::navigation::{find_words, is_identifier_char};
+
+/// A module changing its dotted name, e.g. `app.utils` → `app.core.utils`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModuleMove {
+    pub old: String,
+    pub new: String,
+}
+
+impl ModuleMove {
+    fn old_last(&self) -> &str {
+        self.old.rsplit('.').next().unwrap_or(&self.old)
+    }
+}
+
+/// An import statement that refers to the moved module, and its rewrite.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImportRewrite {
+    /// Replacements for the statement's lines.
+    pub edits: Vec<LineEdit>,
+    /// 0-based line and byte offset of the module's name in the statement,
+    /// where ty can be asked what the import resolves to.
+    pub probe: (usize, usize),
+    /// Whether this is a plain `import old.module`, so the file also spells
+    /// the module out in qualified names (`old.module.func()`).
+    pub qualified: bool,
+}
+
+/// A module an import statement names.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImportedModule {
+    /// Absolute dotted name, with relative imports resolved.
+    pub module: String,
+    /// Names a `from` import takes from the module; each may be a submodule.
+    pub names: Vec<String>,
+    /// 0-based line of the statement.
+    pub line: usize,
+    /// 0-based line and byte offset of the module's last name, where ty can
+    /// be asked what the import resolves to.
+    pub probe: (usize, usize),
+}
+
+/// One import statement in a file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Statement {
+    /// First and last 0-based line.
+    first: usize,
+    last: usize,
+    indent: String,
+    /// The statement on one line, without comments or continuations.
+    code: String,
+    /// Trailing comment of the first line, including `#`.
+    comment: Option<String>,
+    /// Whether a `from` import's names are in parentheses.
+    parenthesized: bool,
+}
+
+/// Split a line into code and `# comment`, ignoring `#` inside quotes.
+fn split_comment(line: &str) -> (&str, Option<&str>) {
+    let mut quote: Option<char> = None;
+    for (i, c) in line.char_indices() {
+        match (quote, c) {
+            (None, '#') => return (line[..i].trim_end(), Some(&line[i..])),
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(q), c) if c == q => quote = None,
+            _ => {}
+        }
+    }
+    (line.trim_end(), None)
+}
+
+/// The import statements in `text`, skipping triple-quoted strings.
+fn statements(text: &str) -> Vec<Statement> {
+    let lines: Vec<&str> = text.lines().collect();
+    let mut found = Vec::new();
+    let mut in_string: Option<&str> = None;
+    let mut idx = 0;
+    while idx < lines.len() {
+        let line = lines[idx];
+        let trimmed = line.trim_start();
+        if let Some(delimiter) = in_string {
+            if line.matches(delimiter).count() % 2 == 1 {
+                in_string = None;
+            }
+            idx += 1;
+            continue;
+        }
+        if !(trimmed.starts_with("import ") || trimmed.starts_with("from ")) {
+            in_string = ["\"\"\"", "'''"]
+                .into_iter()
+                .find(|delimiter| line.matches(delimiter).count() % 2 == 1);
+            idx += 1;
+            continue;
+        }
+
+        let (code, comment) = split_comment(trimmed);
+        let mut joined = code.trim_end_matches('\\').trim_end().to_string();
+        let parenthesized = code.contains('(');
+        let mut last = idx;
+        let mut open = (parenthesized && !code.contains(')')) || code.ends_with('\\');
+        while open && last + 1 < lines.len() {
+            last += 1;
+            let (more, _) = split_comment(lines[last].trim());
+            joined.push(' ');
+            joined.push_str(more.trim_end_matches('\\').trim_end());
+            open = if parenthesized { !more.contains(')') } else { more.ends_with('\\') };
+        }
+        found.push(Statement {
+            first: idx,
+            last,
+            indent: line[..line.len() - trimmed.len()].to_string(),
+            code: joined,
+            comment: comment.map(str::to_string),
+            parenthesized,
+        });
+        idx = last + 1;
+    }
+    found
+}
+
+/// The absolute module a `from` import names, given the importing file's package.
+///
+/// `from ..models import User` in package `app.api` names `app.models`.
+fn absolute_module(module: &str, package: &str) -> Option<String> {
+    let level = module.len() - module.trim_start_matches('.').len();
+    let rest = &module[level..];
+    if level == 0 {
+        return Some(rest.to_string());
+    }
+    let mut parts: Vec<&str> = package.split('.').filter(|p| !p.is_empty()).collect();
+    for _ in 1..level {
+        parts.pop()?;
+    }
+    if !rest.is_empty() {
+        parts.push(rest);
+    }
+    (!parts.is_empty()).then(|| parts.join("."))
+}
+
+/// An imported name: `name` or `name as alias`.
+fn split_alias(item: &str) -> (&str, Option<&str>) {
+    match item.split_once(" as ") {
+        Some((name, alias)) => (name.trim(), Some(alias.trim())),
+        None => (item.trim(), None),
+    }
+}
+
+/// Replace a statement's lines with `new_lines`.
+fn replace_statement(statement: &Statement, new_lines: Vec<String>) -> Vec<LineEdit> {
+    let mut edits = vec![LineEdit { line: statement.first, new_lines }];
+    edits.extend(
+        (statement.first + 1..=statement.last).map(|line| LineEdit { line, new_lines: Vec::new() }),
+    );
+    edits
+}
+
+/// `import new.module as alias`, or `from new import module as alias`.
+fn import_line(module: &str, alias: Option<&str>) -> String {
+    let alias = alias.map(|a| format!(" as {a}")).unwrap_or_default();
+    match module.rsplit_once('.') {
+        Some((parent, last)) => format!("from {parent} import {last}{alias}"),
+        None => format!("import {module}{alias}"),
+    }
+}
+
+/// Rewrites for every import of the moved module in a file.
+///
+/// `package` is the dotted package the file belongs to, for resolving
+/// relative imports (`app.api` for both `app/api/views.py` and
+/// `app/api/__init__.py`). Rewritten relative imports become absolute.
+pub fn rewrite_imports(text: &str, package: &str, change: &ModuleMove) -> Vec<ImportRewrite> {
+    let lines: Vec<&str> = text.lines().collect();
+    let mut rewrites = Vec::new();
+    for statement in statements(text) {
+        let first_line = lines[statement.first];
+        let comment = statement.comment.as_deref().map(|c| format!("  {c}")).unwrap_or_default();
+        if let Some(items) = statement.code.strip_prefix("import ") {
+            let mut qualified = false;
+            let mut probe = None;
+            let items: Vec<String> = items
+                .split(',')
+                .map(|item| {
+                    let (module, alias) = split_alias(item);
+                    if module != change.old {
+                        return item.trim().to_string();
+                    }
+                    qualified |= alias.is_none();
+                    probe = probe.or_else(|| {
+                        let start = first_line.find(module)?;
+                        Some((statement.first, start + module.len() - change.old_last().len()))
+                    });
+                    alias.map_or_else(|| change.new.clone(), |a| format!("{} as {a}", change.new))
+                })
+                .collect();
+            if let Some(probe) = probe {
+                let line = format!("{}import {}{comment}", statement.indent, items.join(", "));
+                rewrites.push(ImportRewrite {
+                    edits: replace_statement(&statement, vec![line]),
+                    probe,
+                    qualified,
+                });
+            }
+            continue;
+        }
+
+        let Some((module, names)) =
+            statement.code.strip_prefix("from ").and_then(|rest| rest.split_once(" import "))
+        else {
+            continue;
+        };
+        let module = module.trim();
+        let Some(absolute) = absolute_module(module, package) else { continue };
+
+        if absolute == change.old {
+            // `from old.module import name`: only the module changes.
+            let Some(start) = first_line.find(module) else { continue };
+            let mut line = first_line.to_string();
+            line.replace_range(start..start + module.len(), &change.new);
+            let probe = (statement.first, start + module.len() - change.old_last().len());
+            rewrites.push(ImportRewrite {
+                edits: vec![LineEdit::replace(statement.first, line)],
+                probe,
+                qualified: false,
+            });
+            continue;
+        }
+
+        // `from old import module`: the moved module is one of the names.
+        let names = names.trim().trim_start_matches('(').trim_end_matches(')');
+        let items: Vec<&str> = names.split(',').map(str::trim).filter(|i| !i.is_empty()).collect();
+        let Some(position) = items.iter().position(|item| {
+            let (name, _) = split_alias(item);
+            format!("{absolute}.{name}") == change.old
+        }) else {
+            continue;
+        };
+        let (name, alias) = split_alias(items[position]);
+        let Some(probe) = (statement.first..=statement.last)
+            .find_map(|line| find_words(lines[line], name).next().map(|byte| (line, byte)))
+        else {
+            continue;
+        };
+        // Keep the name the file uses for the module, so its code still works.
+        let new_last = change.new.rsplit('.').next().unwrap_or(&change.new);
+        let alias = alias.or_else(|| (new_last != name).then_some(name));
+        let moved = format!("{}{}", statement.indent, import_line(&change.new, alias));
+
+        let remaining: Vec<&str> = items
+            .iter()
+            .enumerate()
+            .filter(|&(i, _)| i != position)
+            .map(|(_, item)| *item)
+            .collect();
+        let new_lines = if remaining.is_empty() {
+            vec![format!("{moved}{comment}")]
+        } else if statement.parenthesized && statement.last > statement.first {
+            let mut kept = vec![format!("{}from {module} import ({comment}", statement.indent)];
+            kept.extend(remaining.iter().map(|item| format!("{}    {item},", statement.indent)));
+            kept.push(format!("{})", statement.indent));
+            kept.push(moved);
+            kept
+        } else {
+            let kept = format!(
+                "{}from {module} import {}{comment}",
+                statement.indent,
+                remaining.join(", ")
+            );
+            vec![kept, moved]
+        };
+        rewrites.push(ImportRewrite {
+            edits: replace_statement(&statement, new_lines),
+            probe,
+            qualified: false,
+        });
+    }
+    rewrites
+}
+
+/// Rewrites of qualified uses like `old.module.func()` outside import lines.
+///
+/// Needed where a file does `import old.module`: after the import is
+/// rewritten, the code has to spell the new name too. `skip` holds the lines
+/// of import statements, which are rewritten separately.
+pub fn rewrite_qualified_uses(text: &str, change: &ModuleMove, skip: &[usize]) -> Vec<LineEdit> {
+    let prefix = format!("{}.", change.old);
+    text.lines()
+        .enumerate()
+        .filter(|(idx, line)| !skip.contains(idx) && line.contains(&prefix))
+        .filter_map(|(idx, line)| {
+            let mut rewritten = String::with_capacity(line.len());
+            let mut rest = line;
+            let mut changed = false;
+            while let Some(i) = rest.find(&prefix) {
+                let before = rest[..i].chars().next_back();
+                rewritten.push_str(&rest[..i]);
+                if before.is_some_and(|c| is_identifier_char(c) || c == '.') {
+                    rewritten.push_str(&prefix);
+                } else {
+                    rewritten.push_str(&change.new);
+                    rewritten.push('.');
+                    changed = true;
+                }
+                rest = &rest[i + prefix.len()..];
+            }
+            rewritten.push_str(rest);
+            changed.then(|| LineEdit::replace(idx, rewritten))
+        })
+        .collect()
+}
+
+/// The modules the import statements in `text` name, in file order.
+///
+/// `package` resolves relative imports, as for [`rewrite_imports`]. Each
+/// module of a plain `import a, b` is listed separately; `from __future__`
+/// imports are left out.
+pub fn imported_modules(text: &str, package: &str) -> Vec<ImportedModule> {
+    let lines: Vec<&str> = text.lines().collect();
+    let probe = |statement: &Statement, module: &str| {
+        let last = module.rsplit('.').next().unwrap_or(module);
+        (statement.first..=statement.last).find_map(|line| {
+            let start = lines[line].find(module)?;
+            Some((line, start + module.len() - last.len()))
+        })
+    };
+    let mut found = Vec::new();
+    for statement in statements(text) {
+        if let Some(items) = statement.code.strip_prefix("import ") {
+            for item in items.split(',') {
+                let (module, _) = split_alias(item);
+                if module.is_empty() {
+                    continue;
+                }
+                found.push(ImportedModule {
+                    module: module.to_string(),
+                    names: Vec::new(),
+                    line: statement.first,
+                    probe: probe(&statement, module).unwrap_or((statement.first, 0)),
+                });
+            }
+            continue;
+        }
+
+        let Some((module, names)) =
+            statement.code.strip_prefix("from ").and_then(|rest| rest.split_once(" import "))
+        else {
+            continue;
+        };
+        let module = module.trim();
+        let Some(absolute) = absolute_module(module, package) else { continue };
+        if absolute == "__future__" {
+            continue;
+        }
+        let names = names.trim().trim_start_matches('(').trim_end_matches(')');
+        found.push(ImportedModule {
+            module: absolute,
+            names: names
+                .split(',')
+                .map(|item| split_alias(item).0.to_string())
+                .filter(|name| !name.is_empty() && name != "*")
+                .collect(),
+            line: statement.first,
+            probe: probe(&statement, module).unwrap_or((statement.first, 0)),
+        });
+    }
+    found
+}
+
+/// Make the relative imports of a moving file absolute.
+///
+/// `from .helpers import x` in `app/utils.py` means `app.helpers`; once the
+/// file lives in another package it would mean something else, so it is
+/// rewritten to `from app.helpers import x`. `package` is the file's package
+/// before the move.
+pub fn absolutize_relative_imports(text: &str, package: &str) -> Vec<LineEdit> {
+    let lines: Vec<&str> = text.lines().collect();
+    statements(text)
+        .into_iter()
+        .filter_map(|statement| {
+            let rest = statement.code.strip_prefix("from ")?;
+            let (module, _) = rest.split_once(" import ")?;
+            let module = module.trim();
+            if !module.starts_with('.') {
+                return None;
+            }
+            let absolute = absolute_module(module, package)?;
+            let first_line = lines[statement.first];
+            let start = first_line.find(module)?;
+            let mut line = first_line.to_string();
+            line.replace_range(start..start + module.len(), &absolute);
+            Some(LineEdit::replace(statement.first, line))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::refactor::edit::apply;
+
+    fn change() -> ModuleMove {
+        ModuleMove { old: "app.utils".to_string(), new: "app.core.helpers".to_string() }
+    }
+
+    fn rewrite(text: &str, package: &str) -> String {
+        let edits: Vec<LineEdit> =
+            rewrite_imports(text, package, &change()).into_iter().flat_map(|r| r.edits).collect();
+        apply(text, &edits)
+    }
+
+    #[test]
+    fn test_rewrites_module_imports() {
+        let text = "import os, app.utils\nimport app.utils as u  # helpers\nfrom app.utils import slugify, (x)\nimport app.utilsx\n";
+        assert_eq!(
+            rewrite(text, "app"),
+            "import os, app.core.helpers\nimport app.core.helpers as u  # helpers\nfrom app.core.helpers import slugify, (x)\nimport app.utilsx\n"
+        );
+        let rewrites = rewrite_imports(text, "app", &change());
+        assert_eq!(rewrites[0].probe, (0, 15));
+        assert!(rewrites[0].qualified);
+        assert!(!rewrites[1].qualified);
+    }
+
+    #[test]
+    fn test_rewrites_imports_of_the_module_by_name() {
+        let text = "from app import utils\nfrom app import (\n    models,\n    utils,  # moved\n)\nfrom . import utils as u, views\n";
+        assert_eq!(
+            rewrite(text, "app.api"),
+            "from app.core import helpers as utils\nfrom app import (\n    models,\n)\nfrom app.core import helpers as utils\nfrom . import utils as u, views\n"
+        );
+        assert_eq!(
+            rewrite("from . import utils as u, views\n", "app"),
+            "from . import views\nfrom app.core import helpers as u\n"
+        );
+    }
+
+    #[test]
+    fn test_rewrites_relative_imports_as_absolute() {
+        assert_eq!(
+            rewrite("    from ..utils import slugify\n", "app.api"),
+            "    from app.core.helpers import slugify\n"
+        );
+        assert_eq!(
+            rewrite("from .utils import slugify\n", "app.api"),
+            "from .utils import slugify\n"
+        );
+    }
+
+    #[test]
+    fn test_skips_docstrings() {
+        let text = "\"\"\"Usage:\n\nimport app.utils\n\"\"\"\nimport app.utils\n";
+        let rewrites = rewrite_imports(text, "app", &change());
+        assert_eq!(rewrites.len(), 1);
+        assert_eq!(rewrites[0].probe.0, 4);
+    }
+
+    #[test]
+    fn test_rewrite_qualified_uses() {
+        let text = "import app.utils\nx = app.utils.slugify(s) + myapp.utils.y\n";
+        let edits = rewrite_qualified_uses(text, &change(), &[0]);
+        assert_eq!(
+            edits,
+            vec![LineEdit::replace(1, "x = app.core.helpers.slugify(s) + myapp.utils.y".into())]
+        );
+    }
+
+    #[test]
+    fn test_imported_modules() {
+        let text = "from __future__ import annotations\n\
+                    import os, app.db as db\n\
+                    from . import (\n    models,\n    views as v,\n)\n\
+                    from ..core.helpers import *\n";
+        let found = imported_modules(text, "app.api");
+        let modules: Vec<(&str, Vec<&str>, usize)> = found
+            .iter()
+            .map(|m| (m.module.as_str(), m.names.iter().map(String::as_str).collect(), m.line))
+            .collect();
+        assert_eq!(
+            modules,
+            vec![
+                ("os", vec![], 1),
+                ("app.db", vec![], 1),
+                ("app.api", vec!["models", "views"], 2),
+                ("app.core.helpers", vec![], 6),
+            ]
+        );
+        assert_eq!(found[1].probe, (1, 15));
+        assert_eq!(found[3].probe, (6, 12));
+    }
+
+    #[test]
+    fn test_absolutize_relative_imports() {
+        let text = "from .models import User\nfrom ..base import Base\nimport os\n";
+        assert_eq!(
+            apply(text, &absolutize_relative_imports(text, "app.api")),
+            "from app.api.models import User\nfrom app.base import Base\nimport os\n"
+        );
+    }
+}
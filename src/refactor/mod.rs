@@ -0,0 +1,71 @@
+//! Source rewriting for refactoring commands (`tyf move`).
+//!
+//! [`edit`] holds the line-based edit engine that previews changes as a
+//! diff and applies them; [`imports`] finds and rewrites import statements.
+
+pub mod edit;
+pub mod imports;
+
+use std::path::{Path, PathBuf};
+
+use crate::workspace::ty_config::TyConfig;
+
+/// The directory module names of `file` are relative to.
+///
+/// That's the innermost of ty's configured first-party roots containing the
+/// file. Without configured roots ty uses the workspace root, plus `src/`
+/// when it exists.
+pub fn import_root(workspace_root: &Path, config: &TyConfig, file: &Path) -> PathBuf {
+    let mut roots: Vec<PathBuf> =
+        config.src_roots.iter().map(|root| workspace_root.join(root)).collect();
+    if roots.is_empty() {
+        roots.push(workspace_root.to_path_buf());
+        let src = workspace_root.join("src");
+        if src.is_dir() {
+            roots.push(src);
+        }
+    }
+    roots
+        .into_iter()
+        .filter(|root| file.starts_with(root))
+        .max_by_key(|root| root.components().count())
+        .unwrap_or_else(|| workspace_root.to_path_buf())
+}
+
+/// The dotted package `file` belongs to, for resolving its relative imports.
+///
+/// A package's `__init__.py` belongs to the package itself.
+pub fn package_name(import_root: &Path, file: &Path) -> String {
+    let module = crate::grep::module_name(import_root, file);
+    if file.file_stem().is_some_and(|stem| stem == "__init__") {
+        return module;
+    }
+    module.rsplit_once('.').map(|(package, _)| package.to_string()).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_import_root_prefers_src_layout() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        let config = TyConfig::default();
+        assert_eq!(import_root(root, &config, &root.join("app/utils.py")), root);
+
+        std::fs::create_dir(root.join("src")).unwrap();
+        assert_eq!(import_root(root, &config, &root.join("src/app/utils.py")), root.join("src"));
+
+        let config = TyConfig { src_roots: vec!["lib".to_string()], ..TyConfig::default() };
+        assert_eq!(import_root(root, &config, &root.join("lib/app/utils.py")), root.join("lib"));
+    }
+
+    #[test]
+    fn test_package_name() {
+        let root = Path::new("/ws");
+        assert_eq!(package_name(root, Path::new("/ws/app/api/views.py")), "app.api");
+        assert_eq!(package_name(root, Path::new("/ws/app/api/__init__.py")), "app.api");
+        assert_eq!(package_name(root, Path::new("/ws/main.py")), "");
+    }
+}
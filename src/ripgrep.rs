@@ -8,10 +8,15 @@
 //! guarantees non-existence. `rg` returning matches does NOT guarantee the
 //! symbol exists (it could be in a comment or string), so we continue retries
 //! in that case.
+//!
+//! Paths excluded by the project's ty configuration (`src.exclude`) are skipped
+//! too, so the check agrees with what the LSP server indexes.
 
 use std::path::Path;
 use std::process::Command;
 
+use crate::workspace::ty_config::TyConfig;
+
 /// Check whether a symbol name appears in any Python file under `workspace_root`.
 ///
 /// Returns `false` only when `rg` confirms the symbol does not exist (exit code 1).
@@ -20,21 +25,22 @@ use std::process::Command;
 /// - `rg` is not found on PATH
 /// - `rg` returns any error
 /// - The symbol name is empty
-pub fn symbol_might_exist_in_workspace(symbol: &str, workspace_root: &Path) -> bool {
+pub fn symbol_might_exist_in_workspace(
+    symbol: &str,
+    workspace_root: &Path,
+    config: &TyConfig,
+) -> bool {
     if symbol.is_empty() {
         tracing::debug!("rg: empty symbol name, skipping existence check");
         return true;
     }
 
-    let result = Command::new("rg")
-        .arg("--count")
-        .arg("--word-regexp")
-        .arg("--fixed-strings")
-        .arg("--type")
-        .arg("py")
-        .arg(symbol)
-        .arg(workspace_root)
-        .output();
+    let mut command = Command::new("rg");
+    command.arg("--count").arg("--word-regexp").arg("--fixed-strings").arg("--type").arg("py");
+    for glob in config.rg_exclude_globs() {
+        command.arg("--glob").arg(glob);
+    }
+    let result = command.arg(symbol).arg(workspace_root).output();
 
     match result {
         Ok(output) => {
@@ -87,13 +93,17 @@ mod tests {
     #[test]
     fn test_symbol_found_in_workspace() {
         let ws = create_test_workspace(&[("example.py", "def greet():\n    pass\n")]);
-        assert!(symbol_might_exist_in_workspace("greet", ws.path()));
+        assert!(symbol_might_exist_in_workspace("greet", ws.path(), &TyConfig::default()));
     }
 
     #[test]
     fn test_symbol_not_found_in_workspace() {
         let ws = create_test_workspace(&[("example.py", "def greet():\n    pass\n")]);
-        assert!(!symbol_might_exist_in_workspace("nonexistent_symbol_xyz", ws.path()));
+        assert!(!symbol_might_exist_in_workspace(
+            "nonexistent_symbol_xyz",
+            ws.path(),
+            &TyConfig::default()
+        ));
     }
 
     #[test]
@@ -103,7 +113,7 @@ mod tests {
             "def calculate_sum(a, b):\n    return a + b\n",
         )]);
         // "sum" should NOT match "calculate_sum" with --word-regexp
-        assert!(!symbol_might_exist_in_workspace("sum", ws.path()));
+        assert!(!symbol_might_exist_in_workspace("sum", ws.path(), &TyConfig::default()));
     }
 
     #[test]
@@ -114,20 +124,20 @@ mod tests {
         )]);
         // __init__ should match with --word-regexp --fixed-strings
         // because _ is a word character, so \b__init__\b works
-        assert!(symbol_might_exist_in_workspace("__init__", ws.path()));
+        assert!(symbol_might_exist_in_workspace("__init__", ws.path(), &TyConfig::default()));
     }
 
     #[test]
     fn test_dunder_symbol_not_present() {
         let ws = create_test_workspace(&[("example.py", "x = 1\n")]);
-        assert!(!symbol_might_exist_in_workspace("__init__", ws.path()));
+        assert!(!symbol_might_exist_in_workspace("__init__", ws.path(), &TyConfig::default()));
     }
 
     #[test]
     fn test_empty_symbol_returns_true() {
         let ws = create_test_workspace(&[("example.py", "x = 1\n")]);
         // Empty symbol should conservatively return true (skip the check)
-        assert!(symbol_might_exist_in_workspace("", ws.path()));
+        assert!(symbol_might_exist_in_workspace("", ws.path(), &TyConfig::default()));
     }
 
     #[test]
@@ -137,7 +147,7 @@ mod tests {
             ("config.json", "{\"greet\": true}\n"),
         ]);
         // Symbol only in non-Python files should not be found
-        assert!(!symbol_might_exist_in_workspace("greet", ws.path()));
+        assert!(!symbol_might_exist_in_workspace("greet", ws.path(), &TyConfig::default()));
     }
 
     #[test]
@@ -146,9 +156,20 @@ mod tests {
             create_test_workspace(&[("example.py", "# pattern: foo.*bar\ndef normal(): pass\n")]);
         // --fixed-strings prevents regex interpretation
         // "foo.*bar" as a literal should be found in the comment
-        assert!(symbol_might_exist_in_workspace("foo.*bar", ws.path()));
+        assert!(symbol_might_exist_in_workspace("foo.*bar", ws.path(), &TyConfig::default()));
         // But a symbol that doesn't exist should still return false
-        assert!(!symbol_might_exist_in_workspace("baz.*qux", ws.path()));
+        assert!(!symbol_might_exist_in_workspace("baz.*qux", ws.path(), &TyConfig::default()));
+    }
+
+    #[test]
+    fn test_respects_ty_src_exclude() {
+        let ws = create_test_workspace(&[
+            ("app/main.py", "x = 1\n"),
+            ("generated/models.py", "class Generated: pass\n"),
+        ]);
+        let config = TyConfig { exclude: vec!["generated".to_string()], ..TyConfig::default() };
+        assert!(symbol_might_exist_in_workspace("Generated", ws.path(), &TyConfig::default()));
+        assert!(!symbol_might_exist_in_workspace("Generated", ws.path(), &config));
     }
 
     #[test]
@@ -159,7 +180,7 @@ mod tests {
         fs::write(spaced_dir.join("example.py"), "def hello(): pass\n")
             .expect("Failed to write file");
 
-        assert!(symbol_might_exist_in_workspace("hello", &spaced_dir));
-        assert!(!symbol_might_exist_in_workspace("nonexistent", &spaced_dir));
+        assert!(symbol_might_exist_in_workspace("hello", &spaced_dir, &TyConfig::default()));
+        assert!(!symbol_might_exist_in_workspace("nonexistent", &spaced_dir, &TyConfig::default()));
     }
 }
@@ -0,0 +1,173 @@
+//! Guided installation of ty when it is missing (`tyf setup`).
+
+use std::io::{IsTerminal, Write};
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+use anyhow::{Context, Result};
+
+use crate::cli::args::Installer;
+
+/// Installers in the order `tyf setup` prefers them.
+const PREFERENCE: [Installer; 3] = [Installer::Uv, Installer::Pipx, Installer::Pip];
+
+/// Where to get an installer when none is available.
+const NO_INSTALLER_HELP: &str =
+    "Install uv (https://docs.astral.sh/uv/getting-started/installation/) \
+     and run `tyf setup`, or install ty yourself (https://github.com/astral-sh/ty)";
+
+/// Whether `installer` can be run (`<installer> --version` succeeds).
+fn is_installed(installer: Installer) -> bool {
+    let (program, args): (&str, &[&str]) = match installer {
+        Installer::Uv => ("uv", &["--version"]),
+        Installer::Pipx => ("pipx", &["--version"]),
+        Installer::Pip => ("python3", &["-m", "pip", "--version"]),
+    };
+    Command::new(program)
+        .args(args)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .is_ok_and(|status| status.success())
+}
+
+/// A concrete command that installs ty.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InstallPlan {
+    pub installer: Installer,
+    pub argv: Vec<&'static str>,
+    /// Where ty ends up, e.g. "as a dev dependency of the workspace".
+    pub target: &'static str,
+}
+
+impl InstallPlan {
+    /// Plan an install with `installer` for the project at `workspace_root`.
+    pub fn for_workspace(installer: Installer, workspace_root: &Path) -> Self {
+        Self::build(
+            installer,
+            workspace_root.join("pyproject.toml").is_file(),
+            std::env::var_os("VIRTUAL_ENV").is_some(),
+        )
+    }
+
+    fn build(installer: Installer, has_pyproject: bool, in_venv: bool) -> Self {
+        let (argv, target): (&[&'static str], _) = match installer {
+            Installer::Uv if has_pyproject => {
+                (&["uv", "add", "--dev", "ty"], "as a dev dependency of the workspace")
+            }
+            Installer::Uv => (&["uv", "tool", "install", "ty"], "as a uv tool"),
+            Installer::Pipx => (&["pipx", "install", "ty"], "with pipx"),
+            Installer::Pip if in_venv => {
+                (&["python3", "-m", "pip", "install", "ty"], "into the active virtualenv")
+            }
+            Installer::Pip => (
+                &["python3", "-m", "pip", "install", "--user", "ty"],
+                "into the user site-packages",
+            ),
+        };
+        Self { installer, argv: argv.to_vec(), target }
+    }
+
+    /// The command as the user would type it.
+    pub fn command_line(&self) -> String {
+        self.argv.join(" ")
+    }
+
+    /// Run the install in `workspace_root`, streaming its output to the terminal.
+    pub fn run(&self, workspace_root: &Path) -> Result<()> {
+        let status = Command::new(self.argv[0])
+            .args(&self.argv[1..])
+            .current_dir(workspace_root)
+            .status()
+            .with_context(|| format!("Failed to run '{}'", self.command_line()))?;
+        if !status.success() {
+            anyhow::bail!("'{}' failed ({status})", self.command_line());
+        }
+        Ok(())
+    }
+}
+
+/// Pick the install plan: the requested installer, or the first available one.
+pub fn plan(installer: Option<Installer>, workspace_root: &Path) -> Result<InstallPlan> {
+    let installer = match installer {
+        Some(installer) if is_installed(installer) => installer,
+        Some(installer) => {
+            let plan = InstallPlan::for_workspace(installer, workspace_root);
+            anyhow::bail!("'{}' is not available on PATH", plan.argv[0]);
+        }
+        None => PREFERENCE
+            .into_iter()
+            .find(|&installer| is_installed(installer))
+            .with_context(|| format!("No installer found. {NO_INSTALLER_HELP}"))?,
+    };
+    Ok(InstallPlan::for_workspace(installer, workspace_root))
+}
+
+/// One-line guidance for a missing ty, naming the command `tyf setup` would run.
+pub fn missing_ty_hint(workspace_root: &Path) -> String {
+    match plan(None, workspace_root) {
+        Ok(plan) => format!(
+            "tyf needs ty to answer queries. Run `tyf setup` to install it {} ({})",
+            plan.target,
+            plan.command_line()
+        ),
+        Err(_) => format!("tyf needs ty to answer queries. {NO_INSTALLER_HELP}"),
+    }
+}
+
+/// Ask on the terminal whether to run `plan`. Never prompts (and returns
+/// false) when stdin or stderr isn't a terminal, e.g. under an AI agent or in CI.
+pub fn confirm(plan: &InstallPlan) -> bool {
+    if !std::io::stdin().is_terminal() || !std::io::stderr().is_terminal() {
+        return false;
+    }
+    eprint!(
+        "ty is not installed. Install it {} with `{}`? [Y/n] ",
+        plan.target,
+        plan.command_line()
+    );
+    let _ = std::io::stderr().flush();
+    let mut answer = String::new();
+    if std::io::stdin().read_line(&mut answer).is_err() {
+        return false;
+    }
+    matches!(answer.trim().to_ascii_lowercase().as_str(), "" | "y" | "yes")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_uv_adds_dev_dependency_in_a_project() {
+        let plan = InstallPlan::build(Installer::Uv, true, false);
+        assert_eq!(plan.command_line(), "uv add --dev ty");
+        assert_eq!(plan.target, "as a dev dependency of the workspace");
+    }
+
+    #[test]
+    fn test_uv_installs_tool_outside_a_project() {
+        let plan = InstallPlan::build(Installer::Uv, false, false);
+        assert_eq!(plan.command_line(), "uv tool install ty");
+    }
+
+    #[test]
+    fn test_pip_uses_user_site_outside_a_virtualenv() {
+        assert_eq!(
+            InstallPlan::build(Installer::Pip, true, false).command_line(),
+            "python3 -m pip install --user ty"
+        );
+        assert_eq!(
+            InstallPlan::build(Installer::Pip, true, true).command_line(),
+            "python3 -m pip install ty"
+        );
+    }
+
+    #[test]
+    fn test_pipx_ignores_project_layout() {
+        assert_eq!(
+            InstallPlan::build(Installer::Pipx, true, true),
+            InstallPlan::build(Installer::Pipx, false, false)
+        );
+    }
+}
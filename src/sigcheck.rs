@@ -0,0 +1,415 @@
+//! Signature parsing and call binding for `tyf sig-check`: each call site is
+//! bound to the proposed signature the way Python would, without evaluating it.
+
+use std::collections::HashSet;
+
+use anyhow::{Context, Result};
+
+use crate::workspace::navigation::is_identifier_char;
+
+/// How a parameter can be passed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParamKind {
+    PositionalOnly,
+    PositionalOrKeyword,
+    KeywordOnly,
+    /// `*args`
+    VarPositional,
+    /// `**kwargs`
+    VarKeyword,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Param {
+    pub name: String,
+    pub kind: ParamKind,
+    pub has_default: bool,
+}
+
+/// Parameter list of a function signature.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Signature {
+    pub params: Vec<Param>,
+}
+
+/// One argument of a call, as far as binding is concerned.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CallArg {
+    Positional,
+    Keyword(String),
+    /// `*iterable`
+    Star,
+    /// `**mapping`
+    DoubleStar,
+}
+
+/// Outcome of binding a call's arguments to a signature.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Verdict {
+    Compatible,
+    /// The call would raise `TypeError`; one message per problem.
+    Breaks(Vec<String>),
+    /// The call unpacks `*args` or `**kwargs` in a way that decides whether it binds.
+    Unchecked,
+}
+
+impl Signature {
+    /// Parse a signature such as `def f(a, /, b, *, c=1, **kw) -> int`.
+    ///
+    /// The `def` and the name are optional; only the parenthesized parameter
+    /// list is read. Annotations and default values are skipped.
+    pub fn parse(text: &str) -> Result<Self> {
+        let open = text.find('(').context("Signature has no parameter list")?;
+        let close = matching_paren(text, open).context("Unbalanced parentheses in signature")?;
+
+        let mut params: Vec<Param> = Vec::new();
+        let mut keyword_only = false;
+        for piece in split_top_level(&text[open + 1..close]) {
+            let piece = piece.trim();
+            if piece.is_empty() {
+                continue;
+            }
+            if piece == "/" {
+                for param in &mut params {
+                    param.kind = ParamKind::PositionalOnly;
+                }
+                continue;
+            }
+            if piece == "*" {
+                keyword_only = true;
+                continue;
+            }
+            let (kind, rest) = if let Some(rest) = piece.strip_prefix("**") {
+                (ParamKind::VarKeyword, rest)
+            } else if let Some(rest) = piece.strip_prefix('*') {
+                keyword_only = true;
+                (ParamKind::VarPositional, rest)
+            } else if keyword_only {
+                (ParamKind::KeywordOnly, piece)
+            } else {
+                (ParamKind::PositionalOrKeyword, piece)
+            };
+            let name: String =
+                rest.trim_start().chars().take_while(|&c| is_identifier_char(c)).collect();
+            if name.is_empty() {
+                anyhow::bail!("Can't read parameter '{piece}'");
+            }
+            let has_default = top_level_assignment(rest).is_some();
+            params.push(Param { name, kind, has_default });
+        }
+        Ok(Self { params })
+    }
+
+    /// The signature as seen through a bound method: without a leading `self` or `cls`.
+    pub fn without_receiver(mut self) -> Self {
+        if self.params.first().is_some_and(|p| {
+            matches!(p.name.as_str(), "self" | "cls") && p.kind != ParamKind::KeywordOnly
+        }) {
+            self.params.remove(0);
+        }
+        self
+    }
+
+    /// Bind `args` the way Python would and report what goes wrong.
+    pub fn check_call(&self, args: &[CallArg]) -> Verdict {
+        let unpacks_positional = args.contains(&CallArg::Star);
+        let unpacks_keywords = args.contains(&CallArg::DoubleStar);
+        let accepts_var_positional = self.has(ParamKind::VarPositional);
+        let accepts_var_keyword = self.has(ParamKind::VarKeyword);
+
+        let positional: Vec<&Param> = self
+            .params
+            .iter()
+            .filter(|p| {
+                matches!(p.kind, ParamKind::PositionalOnly | ParamKind::PositionalOrKeyword)
+            })
+            .collect();
+        let given = args.iter().filter(|a| **a == CallArg::Positional).count();
+
+        let mut problems = Vec::new();
+        if given > positional.len() && !accepts_var_positional {
+            problems.push(format!(
+                "takes {} positional argument(s) but {given} were given",
+                positional.len()
+            ));
+        }
+
+        let mut bound: HashSet<&str> =
+            positional.iter().take(given).map(|p| p.name.as_str()).collect();
+        for arg in args {
+            let CallArg::Keyword(name) = arg else { continue };
+            match self.params.iter().find(|p| p.name == *name).map(|p| p.kind) {
+                Some(ParamKind::PositionalOrKeyword | ParamKind::KeywordOnly) => {
+                    if !bound.insert(name.as_str()) {
+                        problems.push(format!("got multiple values for argument '{name}'"));
+                    }
+                }
+                Some(ParamKind::PositionalOnly) if !accepts_var_keyword => {
+                    problems.push(format!("positional-only argument '{name}' passed as keyword"));
+                }
+                _ if !accepts_var_keyword => {
+                    problems.push(format!("unexpected keyword argument '{name}'"));
+                }
+                _ => {}
+            }
+        }
+
+        let missing: Vec<&str> = self
+            .params
+            .iter()
+            .filter(|p| {
+                !p.has_default
+                    && !matches!(p.kind, ParamKind::VarPositional | ParamKind::VarKeyword)
+                    && !bound.contains(p.name.as_str())
+            })
+            .map(|p| p.name.as_str())
+            .collect();
+        let unpacks = unpacks_positional || unpacks_keywords;
+        if !missing.is_empty() && !unpacks {
+            let names: Vec<String> = missing.iter().map(|n| format!("'{n}'")).collect();
+            problems.push(format!("missing required argument(s) {}", names.join(", ")));
+        }
+
+        // Unpacked values may fill the missing parameters, or overflow the fixed ones.
+        let uncertain = (unpacks && !missing.is_empty())
+            || (unpacks_positional && !accepts_var_positional)
+            || (unpacks_keywords && !accepts_var_keyword);
+        if !problems.is_empty() {
+            Verdict::Breaks(problems)
+        } else if uncertain {
+            Verdict::Unchecked
+        } else {
+            Verdict::Compatible
+        }
+    }
+
+    fn has(&self, kind: ParamKind) -> bool {
+        self.params.iter().any(|p| p.kind == kind)
+    }
+}
+
+/// Arguments of the call whose callee name starts at byte `start` of `text`.
+///
+/// Returns `None` when the name is not followed by `(`, i.e. the reference is
+/// an import, an attribute read, or the function passed as a value.
+pub fn call_arguments(text: &str, start: usize) -> Option<Vec<CallArg>> {
+    let name = text.get(start..)?;
+    let after_name = start + name.find(|c: char| !is_identifier_char(c)).unwrap_or(name.len());
+    let open = after_name + text[after_name..].find(|c: char| c != ' ' && c != '\t')?;
+    if !text[open..].starts_with('(') {
+        return None;
+    }
+    let close = matching_paren(text, open)?;
+    let args = split_top_level(&text[open + 1..close])
+        .into_iter()
+        .map(skip_leading_comments)
+        .filter(|arg| !arg.is_empty())
+        .map(|arg| {
+            if arg.starts_with("**") {
+                CallArg::DoubleStar
+            } else if arg.starts_with('*') {
+                CallArg::Star
+            } else if let Some(eq) = top_level_assignment(arg) {
+                let name = arg[..eq].trim();
+                if !name.is_empty() && name.chars().all(is_identifier_char) {
+                    CallArg::Keyword(name.to_string())
+                } else {
+                    CallArg::Positional
+                }
+            } else {
+                CallArg::Positional
+            }
+        })
+        .collect();
+    Some(args)
+}
+
+/// `arg` trimmed, without the comments a multi-line call puts after the previous comma.
+fn skip_leading_comments(mut arg: &str) -> &str {
+    loop {
+        arg = arg.trim();
+        if !arg.starts_with('#') {
+            return arg;
+        }
+        arg = arg.split_once('\n').map_or("", |(_, rest)| rest);
+    }
+}
+
+/// Characters of `text` outside strings and comments, with the bracket depth before each.
+fn structural_chars(text: &str) -> Vec<(usize, char, usize)> {
+    let mut out = Vec::new();
+    let mut depth = 0usize;
+    let mut chars = text.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '#' => while chars.next_if(|&(_, c)| c != '\n').is_some() {},
+            '\'' | '"' => {
+                let triple = text[i..].starts_with(&c.to_string().repeat(3));
+                if triple {
+                    chars.next();
+                    chars.next();
+                }
+                let closing = if triple { c.to_string().repeat(3) } else { c.to_string() };
+                while let Some((j, d)) = chars.next() {
+                    if d == '\\' {
+                        chars.next();
+                    } else if text[j..].starts_with(&closing) {
+                        for _ in 1..closing.len() {
+                            chars.next();
+                        }
+                        break;
+                    } else if d == '\n' && !triple {
+                        break;
+                    }
+                }
+            }
+            '(' | '[' | '{' => {
+                out.push((i, c, depth));
+                depth += 1;
+            }
+            ')' | ']' | '}' => {
+                depth = depth.saturating_sub(1);
+                out.push((i, c, depth));
+            }
+            _ => out.push((i, c, depth)),
+        }
+    }
+    out
+}
+
+/// Byte index of the `)` closing the `(` at byte `open`.
+fn matching_paren(text: &str, open: usize) -> Option<usize> {
+    let base = structural_chars(&text[open..]);
+    base.into_iter().skip(1).find(|&(_, c, depth)| c == ')' && depth == 0).map(|(i, _, _)| open + i)
+}
+
+/// Split on commas that are not nested in brackets, strings or comments.
+fn split_top_level(text: &str) -> Vec<&str> {
+    let mut pieces = Vec::new();
+    let mut start = 0;
+    for (i, c, depth) in structural_chars(text) {
+        if c == ',' && depth == 0 {
+            pieces.push(&text[start..i]);
+            start = i + 1;
+        }
+    }
+    pieces.push(&text[start..]);
+    pieces
+}
+
+/// Byte index of a top-level `=` that assigns rather than compares.
+fn top_level_assignment(text: &str) -> Option<usize> {
+    let chars = structural_chars(text);
+    chars.iter().enumerate().find_map(|(n, &(i, c, depth))| {
+        if c != '=' || depth != 0 {
+            return None;
+        }
+        let before = n.checked_sub(1).map(|m| chars[m].1);
+        let after = chars.get(n + 1).map(|&(_, c, _)| c);
+        let compares = matches!(before, Some('=' | '!' | '<' | '>' | ':')) || after == Some('=');
+        (!compares).then_some(i)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sig(text: &str) -> Signature {
+        Signature::parse(text).expect("valid signature")
+    }
+
+    fn call(text: &str) -> Vec<CallArg> {
+        call_arguments(text, 0).expect("a call")
+    }
+
+    #[test]
+    fn test_parse_signature_kinds_and_defaults() {
+        let parsed =
+            sig("def f(a, /, b: int = 2, *args, c, d: dict[str, int] = {}, **kw) -> None:");
+        let kinds: Vec<(&str, ParamKind, bool)> =
+            parsed.params.iter().map(|p| (p.name.as_str(), p.kind, p.has_default)).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                ("a", ParamKind::PositionalOnly, false),
+                ("b", ParamKind::PositionalOrKeyword, true),
+                ("args", ParamKind::VarPositional, false),
+                ("c", ParamKind::KeywordOnly, false),
+                ("d", ParamKind::KeywordOnly, true),
+                ("kw", ParamKind::VarKeyword, false),
+            ]
+        );
+        assert!(Signature::parse("def f").is_err());
+    }
+
+    #[test]
+    fn test_without_receiver_drops_self() {
+        let parsed = sig("def add(self, a, b)").without_receiver();
+        assert_eq!(parsed.params.len(), 2);
+        assert_eq!(parsed.params[0].name, "a");
+    }
+
+    #[test]
+    fn test_call_arguments_classifies_and_ignores_nesting() {
+        let args = call("f(1, g(2, 3), key=x == y, other='a,b', *rest, **opts)");
+        assert_eq!(
+            args,
+            vec![
+                CallArg::Positional,
+                CallArg::Positional,
+                CallArg::Keyword("key".to_string()),
+                CallArg::Keyword("other".to_string()),
+                CallArg::Star,
+                CallArg::DoubleStar,
+            ]
+        );
+        assert_eq!(call("f(x == 1)"), vec![CallArg::Positional]);
+        assert_eq!(
+            call("f(\n    a,  # first, really\n    b=2,\n)"),
+            vec![CallArg::Positional, CallArg::Keyword("b".to_string()),]
+        );
+        assert!(call_arguments("from mod import f", 16).is_none());
+        assert!(call_arguments("callback = f", 11).is_none());
+    }
+
+    #[test]
+    fn test_check_call_reports_binding_errors() {
+        let new = sig("def f(a, *, b=1)");
+        assert_eq!(new.check_call(&call("f(1)")), Verdict::Compatible);
+        assert_eq!(new.check_call(&call("f(1, b=2)")), Verdict::Compatible);
+        assert_eq!(
+            new.check_call(&call("f(1, 2)")),
+            Verdict::Breaks(vec!["takes 1 positional argument(s) but 2 were given".to_string()])
+        );
+        assert_eq!(
+            new.check_call(&call("f(1, c=2)")),
+            Verdict::Breaks(vec!["unexpected keyword argument 'c'".to_string()])
+        );
+        assert_eq!(
+            new.check_call(&call("f(b=2)")),
+            Verdict::Breaks(vec!["missing required argument(s) 'a'".to_string()])
+        );
+        assert_eq!(
+            new.check_call(&call("f(1, a=2)")),
+            Verdict::Breaks(vec!["got multiple values for argument 'a'".to_string()])
+        );
+    }
+
+    #[test]
+    fn test_check_call_positional_only_and_unpacking() {
+        let new = sig("def f(a, /, **kw)");
+        assert_eq!(new.check_call(&call("f(1, a=2)")), Verdict::Compatible);
+        assert_eq!(new.check_call(&call("f(1, **opts)")), Verdict::Compatible);
+        let strict = sig("def f(a, /)");
+        assert_eq!(
+            strict.check_call(&call("f(a=2)")),
+            Verdict::Breaks(vec![
+                "positional-only argument 'a' passed as keyword".to_string(),
+                "missing required argument(s) 'a'".to_string(),
+            ])
+        );
+        assert_eq!(strict.check_call(&call("f(*args)")), Verdict::Unchecked);
+        assert_eq!(sig("def f(a, b)").check_call(&call("f(**kw)")), Verdict::Unchecked);
+    }
+}
@@ -0,0 +1,451 @@
+//! Full-screen symbol browser for `tyf tui`.
+//!
+//! A search box on top, the matching workspace symbols on the left and the
+//! source around the selected one on the right. Typing asks the daemon for
+//! ty's workspace symbols; Enter picks the selected symbol, which
+//! `commands::handle_tui_command` prints once the screen is restored.
+//!
+//! The screen is drawn on stderr so stdout stays free for the pick, which
+//! lets `vim $(tyf tui)` and similar compositions work.
+
+use std::collections::HashMap;
+use std::io::{self, IsTerminal, Stderr};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+use ratatui::backend::CrosstermBackend;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use ratatui::crossterm::execute;
+use ratatui::crossterm::terminal::{self, EnterAlternateScreen, LeaveAlternateScreen};
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Borders, List, ListItem, ListState, Paragraph};
+use ratatui::{Frame, Terminal};
+
+use crate::cli::exit::UsageError;
+use crate::commands::collect_symbols;
+use crate::daemon::client::DaemonClient;
+use crate::lsp::protocol::SymbolInformation;
+use crate::lsp::uri::uri_to_path;
+use crate::workspace::page::ResultPage;
+
+/// How long typing must pause before the query is sent.
+const DEBOUNCE: Duration = Duration::from_millis(150);
+
+/// How long to wait for a key before redrawing.
+const POLL: Duration = Duration::from_millis(50);
+
+/// Most symbols listed for one query.
+const MAX_RESULTS: usize = 200;
+
+/// How far Page Up and Page Down move the selection.
+const PAGE: isize = 10;
+
+/// What a key press asks the browser to do.
+#[derive(Debug, PartialEq, Eq)]
+enum Action {
+    Nothing,
+    /// The query changed; search again once typing pauses.
+    Edited,
+    Pick,
+    Quit,
+}
+
+/// Browser state, kept apart from the terminal so it can be tested.
+struct App {
+    query: String,
+    /// Symbols matching `searched`.
+    results: Vec<SymbolInformation>,
+    /// The query `results` are for, once a search has run.
+    searched: Option<String>,
+    selected: usize,
+    /// Why the last search failed.
+    error: Option<String>,
+    /// File contents by URI, `None` when the file can't be read.
+    sources: HashMap<String, Option<String>>,
+    root: PathBuf,
+    color: bool,
+}
+
+impl App {
+    fn new(query: &str, root: &Path, color: bool) -> Self {
+        Self {
+            query: query.to_string(),
+            results: Vec::new(),
+            searched: None,
+            selected: 0,
+            error: None,
+            sources: HashMap::new(),
+            root: root.to_path_buf(),
+            color,
+        }
+    }
+
+    fn selected_symbol(&self) -> Option<&SymbolInformation> {
+        self.results.get(self.selected)
+    }
+
+    fn handle_key(&mut self, key: KeyEvent) -> Action {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        match key.code {
+            KeyCode::Esc => Action::Quit,
+            KeyCode::Char('c') if ctrl => Action::Quit,
+            KeyCode::Enter if self.selected_symbol().is_some() => Action::Pick,
+            KeyCode::Up => self.move_selection(-1),
+            KeyCode::Char('p' | 'k') if ctrl => self.move_selection(-1),
+            KeyCode::Down => self.move_selection(1),
+            KeyCode::Char('n' | 'j') if ctrl => self.move_selection(1),
+            KeyCode::PageUp => self.move_selection(-PAGE),
+            KeyCode::PageDown => self.move_selection(PAGE),
+            KeyCode::Char('u') if ctrl => self.edit(String::clear),
+            KeyCode::Char('w') if ctrl => self.edit(|query| {
+                let kept = query.trim_end().rfind(char::is_whitespace).map_or(0, |i| i + 1);
+                query.truncate(kept);
+            }),
+            KeyCode::Char(c) if !ctrl => self.edit(|query| query.push(c)),
+            KeyCode::Backspace => self.edit(|query| {
+                query.pop();
+            }),
+            _ => Action::Nothing,
+        }
+    }
+
+    fn move_selection(&mut self, by: isize) -> Action {
+        let last = self.results.len().saturating_sub(1);
+        self.selected = self.selected.saturating_add_signed(by).min(last);
+        Action::Nothing
+    }
+
+    fn edit(&mut self, change: impl FnOnce(&mut String)) -> Action {
+        let before = self.query.clone();
+        change(&mut self.query);
+        if self.query == before {
+            Action::Nothing
+        } else {
+            Action::Edited
+        }
+    }
+
+    /// Replace the results with the workspace symbols matching the query.
+    async fn search(&mut self, client: &mut DaemonClient, include_deps: bool) {
+        let query = self.query.trim().to_string();
+        let page = ResultPage::new(0, Some(MAX_RESULTS));
+        let found = collect_symbols(client, &self.root, &query, &[], page, None, include_deps)
+            .await
+            .map(|found| found.symbols);
+        self.selected = 0;
+        match found {
+            Ok(symbols) => {
+                self.results = symbols;
+                self.error = None;
+            }
+            Err(e) => {
+                self.results.clear();
+                self.error = Some(format!("{e:#}"));
+            }
+        }
+        self.searched = Some(query);
+    }
+
+    /// Load the selected symbol's file, once.
+    fn load_preview(&mut self) {
+        let Some(uri) = self.selected_symbol().map(|s| s.location.uri.clone()) else { return };
+        self.sources
+            .entry(uri)
+            .or_insert_with_key(|uri| std::fs::read_to_string(uri_to_path(uri)).ok());
+    }
+
+    fn display_path(&self, uri: &str) -> String {
+        let path = PathBuf::from(uri_to_path(uri));
+        path.strip_prefix(&self.root).unwrap_or(&path).display().to_string()
+    }
+
+    fn highlight(&self) -> Style {
+        if self.color {
+            Style::new().fg(Color::Black).bg(Color::Cyan).add_modifier(Modifier::BOLD)
+        } else {
+            Style::new().add_modifier(Modifier::REVERSED)
+        }
+    }
+
+    fn dim(&self) -> Style {
+        if self.color {
+            Style::new().fg(Color::DarkGray)
+        } else {
+            Style::new()
+        }
+    }
+
+    fn render(&self, frame: &mut Frame) {
+        let [input, body, status] =
+            Layout::vertical([Constraint::Length(3), Constraint::Min(1), Constraint::Length(1)])
+                .areas(frame.area());
+        let [list, preview] =
+            Layout::horizontal([Constraint::Percentage(40), Constraint::Percentage(60)])
+                .areas(body);
+
+        let search = Paragraph::new(self.query.as_str())
+            .block(Block::default().borders(Borders::ALL).title(" Search symbols "));
+        frame.render_widget(search, input);
+        let typed = u16::try_from(self.query.chars().count()).unwrap_or(u16::MAX);
+        frame.set_cursor_position((
+            input.x.saturating_add(1).saturating_add(typed).min(input.right().saturating_sub(2)),
+            input.y + 1,
+        ));
+
+        self.render_results(frame, list);
+        self.render_preview(frame, preview);
+
+        let help = "\u{2191}\u{2193} move  Enter pick  Esc quit";
+        let summary = match (&self.error, &self.searched) {
+            (Some(error), _) => Span::styled(error.clone(), Style::new().fg(Color::Red)),
+            (None, None) => Span::raw(format!("Searching\u{2026}  {help}")),
+            (None, Some(query)) if query.is_empty() => {
+                Span::raw(format!("{} symbol(s)  {help}", self.results.len()))
+            }
+            (None, Some(query)) => {
+                Span::raw(format!("{} match(es) for '{query}'  {help}", self.results.len()))
+            }
+        };
+        frame.render_widget(Paragraph::new(Line::from(summary)), status);
+    }
+
+    fn render_results(&self, frame: &mut Frame, area: Rect) {
+        let items: Vec<ListItem> = self
+            .results
+            .iter()
+            .map(|symbol| {
+                let line = symbol.location.range.start.line + 1;
+                ListItem::new(Line::from(vec![
+                    Span::styled(symbol.name.clone(), Style::new().add_modifier(Modifier::BOLD)),
+                    Span::styled(format!(" {:?} ", symbol.kind), self.dim()),
+                    Span::styled(
+                        format!("{}:{line}", self.display_path(&symbol.location.uri)),
+                        self.dim(),
+                    ),
+                ]))
+            })
+            .collect();
+        let list = List::new(items)
+            .block(Block::default().borders(Borders::ALL).title(" Symbols "))
+            .highlight_style(self.highlight());
+        let mut state =
+            ListState::default().with_selected((!self.results.is_empty()).then_some(self.selected));
+        frame.render_stateful_widget(list, area, &mut state);
+    }
+
+    fn render_preview(&self, frame: &mut Frame, area: Rect) {
+        let block = Block::default().borders(Borders::ALL);
+        let Some(symbol) = self.selected_symbol() else {
+            frame.render_widget(block.title(" Preview "), area);
+            return;
+        };
+        let title = format!(" {} ", self.display_path(&symbol.location.uri));
+        let block = block.title(title);
+        let Some(Some(text)) = self.sources.get(&symbol.location.uri) else {
+            frame.render_widget(Paragraph::new("Source not available").block(block), area);
+            return;
+        };
+        let target = symbol.location.range.start.line as usize;
+        let height = usize::from(area.height.saturating_sub(2));
+        let (first, lines) = preview_window(text, target, height);
+        let width = (first + lines.len()).to_string().len();
+        let lines: Vec<Line> = lines
+            .into_iter()
+            .enumerate()
+            .map(|(i, source)| {
+                let number = first + i;
+                let gutter = Span::styled(format!("{:>width$} ", number + 1), self.dim());
+                let style = if number == target { self.highlight() } else { Style::new() };
+                Line::from(vec![gutter, Span::styled(source.to_string(), style)])
+            })
+            .collect();
+        frame.render_widget(Paragraph::new(lines).block(block), area);
+    }
+}
+
+/// Up to `height` lines of `text` with `target` (0-based) about a third of
+/// the way down, as `(index of the first line, lines)`.
+fn preview_window(text: &str, target: usize, height: usize) -> (usize, Vec<&str>) {
+    let lines: Vec<&str> = text.lines().collect();
+    let first = target.saturating_sub(height / 3).min(lines.len().saturating_sub(height));
+    let end = (first + height).min(lines.len());
+    (first, lines[first..end].to_vec())
+}
+
+/// The terminal in raw mode on the alternate screen, restored when dropped.
+struct Screen {
+    terminal: Terminal<CrosstermBackend<Stderr>>,
+}
+
+impl Screen {
+    fn enter() -> Result<Self> {
+        terminal::enable_raw_mode().context("Failed to put the terminal in raw mode")?;
+        let terminal = execute!(io::stderr(), EnterAlternateScreen)
+            .and_then(|()| Terminal::new(CrosstermBackend::new(io::stderr())));
+        match terminal {
+            Ok(terminal) => Ok(Self { terminal }),
+            Err(e) => {
+                let _ = terminal::disable_raw_mode();
+                Err(e).context("Failed to start the full-screen browser")
+            }
+        }
+    }
+}
+
+impl Drop for Screen {
+    fn drop(&mut self) {
+        let _ = execute!(self.terminal.backend_mut(), LeaveAlternateScreen);
+        let _ = terminal::disable_raw_mode();
+        let _ = self.terminal.show_cursor();
+    }
+}
+
+/// Run the browser until a symbol is picked (`Some`) or it is closed (`None`).
+pub async fn run(
+    client: &mut DaemonClient,
+    workspace_root: &Path,
+    query: &str,
+    include_deps: bool,
+) -> Result<Option<SymbolInformation>> {
+    if !io::stderr().is_terminal() {
+        return Err(UsageError("`tyf tui` needs a terminal".to_string()).into());
+    }
+    let color = std::env::var_os("NO_COLOR").is_none();
+    let mut app = App::new(query, workspace_root, color);
+    let mut screen = Screen::enter()?;
+    screen.terminal.draw(|frame| app.render(frame)).context("Failed to draw")?;
+    app.search(client, include_deps).await;
+    let mut edited_at: Option<Instant> = None;
+    loop {
+        if edited_at.is_some_and(|at| at.elapsed() >= DEBOUNCE) {
+            edited_at = None;
+            app.search(client, include_deps).await;
+        }
+        app.load_preview();
+        screen.terminal.draw(|frame| app.render(frame)).context("Failed to draw")?;
+
+        if !event::poll(POLL).context("Failed to read the terminal")? {
+            continue;
+        }
+        let Event::Key(key) = event::read().context("Failed to read the terminal")? else {
+            continue;
+        };
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+        match app.handle_key(key) {
+            Action::Nothing => {}
+            Action::Edited => edited_at = Some(Instant::now()),
+            Action::Pick => return Ok(app.selected_symbol().cloned()),
+            Action::Quit => return Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lsp::protocol::{Location, Position, Range, SymbolKind};
+    use ratatui::backend::TestBackend;
+
+    fn symbol(name: &str, uri: &str, line: u32) -> SymbolInformation {
+        let position = Position { line, character: 4 };
+        SymbolInformation {
+            name: name.to_string(),
+            kind: SymbolKind::Function,
+            tags: None,
+            deprecated: None,
+            location: Location {
+                uri: uri.to_string(),
+                range: Range { start: position.clone(), end: position },
+            },
+            container_name: None,
+        }
+    }
+
+    fn key(code: KeyCode) -> KeyEvent {
+        KeyEvent::new(code, KeyModifiers::NONE)
+    }
+
+    fn ctrl(c: char) -> KeyEvent {
+        KeyEvent::new(KeyCode::Char(c), KeyModifiers::CONTROL)
+    }
+
+    #[test]
+    fn typing_edits_the_query() {
+        let mut app = App::new("", Path::new("/proj"), false);
+        assert_eq!(app.handle_key(key(KeyCode::Char('f'))), Action::Edited);
+        assert_eq!(app.handle_key(key(KeyCode::Char('o'))), Action::Edited);
+        assert_eq!(app.query, "fo");
+        assert_eq!(app.handle_key(key(KeyCode::Backspace)), Action::Edited);
+        assert_eq!(app.query, "f");
+
+        app.query = "save user".to_string();
+        assert_eq!(app.handle_key(ctrl('w')), Action::Edited);
+        assert_eq!(app.query, "save ");
+        assert_eq!(app.handle_key(ctrl('u')), Action::Edited);
+        assert_eq!(app.handle_key(ctrl('u')), Action::Nothing);
+        assert_eq!(app.handle_key(key(KeyCode::Backspace)), Action::Nothing);
+    }
+
+    #[test]
+    fn selection_stays_within_results() {
+        let mut app = App::new("s", Path::new("/proj"), false);
+        assert_eq!(app.handle_key(key(KeyCode::Enter)), Action::Nothing);
+        app.results = (0..3).map(|i| symbol("s", "file:///proj/a.py", i)).collect();
+        app.handle_key(key(KeyCode::Up));
+        assert_eq!(app.selected, 0);
+        app.handle_key(ctrl('n'));
+        app.handle_key(key(KeyCode::Down));
+        app.handle_key(key(KeyCode::Down));
+        assert_eq!(app.selected, 2);
+        app.handle_key(key(KeyCode::PageUp));
+        assert_eq!(app.selected, 0);
+        assert_eq!(app.handle_key(key(KeyCode::Enter)), Action::Pick);
+        assert_eq!(app.handle_key(key(KeyCode::Esc)), Action::Quit);
+        assert_eq!(app.handle_key(ctrl('c')), Action::Quit);
+    }
+
+    #[test]
+    fn preview_window_keeps_the_target_in_view() {
+        let text = (1..=50).map(|i| format!("line {i}")).collect::<Vec<_>>().join("\n");
+        let (first, lines) = preview_window(&text, 20, 9);
+        assert_eq!(first, 17);
+        assert_eq!(lines.first(), Some(&"line 18"));
+        assert_eq!(lines.len(), 9);
+
+        let (first, lines) = preview_window(&text, 1, 9);
+        assert_eq!((first, lines.len()), (0, 9));
+        let (first, lines) = preview_window(&text, 49, 9);
+        assert_eq!((first, lines.last().copied()), (41, Some("line 50")));
+        let (first, lines) = preview_window("only\n", 0, 9);
+        assert_eq!((first, lines), (0, vec!["only"]));
+    }
+
+    #[test]
+    fn renders_results_and_preview() {
+        let mut app = App::new("save", Path::new("/proj"), false);
+        app.searched = Some("save".to_string());
+        app.results = vec![symbol("save", "file:///proj/app/models.py", 1)];
+        app.sources.insert(
+            "file:///proj/app/models.py".to_string(),
+            Some("class User:\n    def save(self):\n        pass\n".to_string()),
+        );
+
+        let mut terminal = Terminal::new(TestBackend::new(80, 12)).unwrap();
+        terminal.draw(|frame| app.render(frame)).unwrap();
+        let screen: String = terminal
+            .backend()
+            .buffer()
+            .content()
+            .chunks(80)
+            .map(|row| row.iter().map(ratatui::buffer::Cell::symbol).collect::<String>() + "\n")
+            .collect();
+        assert!(screen.contains("save Function app/models.py:2"), "{screen}");
+        assert!(screen.contains("2     def save(self):"), "{screen}");
+        assert!(screen.contains("1 match(es) for 'save'"), "{screen}");
+    }
+}
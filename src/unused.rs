@@ -0,0 +1,260 @@
+//! Candidate selection for `tyf unused`: which symbols are worth asking ty
+//! about, and which of their references count as uses.
+
+use std::collections::HashSet;
+
+use crate::lsp::protocol::{DocumentSymbol, Location, Position, SymbolKind};
+
+/// A symbol whose references should be checked.
+#[derive(Debug, Clone)]
+pub struct Candidate {
+    /// `name`, or `Class.name` for members.
+    pub name: String,
+    pub kind: SymbolKind,
+    /// Where the name is declared, as ty reports it.
+    pub position: Position,
+}
+
+/// `__dunder__` names are called by Python itself, never by name.
+fn is_dunder(name: &str) -> bool {
+    name.len() > 4 && name.starts_with("__") && name.ends_with("__")
+}
+
+fn is_skipped(name: &str) -> bool {
+    name == "_" || is_dunder(name)
+}
+
+/// Symbols in a file's outline that may be unused.
+///
+/// Top-level classes, functions, variables and constants, plus the methods
+/// and properties of top-level classes. Dunder names, `_`, and names listed
+/// in the module's `__all__` are left out. Nested functions and class
+/// attributes are not checked: the first are rarely worth reporting, and
+/// the second are often only read through constructor keywords or
+/// `getattr`, which references don't see.
+pub fn candidates(symbols: &[DocumentSymbol], exports: &HashSet<String>) -> Vec<Candidate> {
+    let mut out = Vec::new();
+    for symbol in symbols {
+        if !matches!(
+            symbol.kind,
+            SymbolKind::Class | SymbolKind::Function | SymbolKind::Variable | SymbolKind::Constant
+        ) || is_skipped(&symbol.name)
+            || exports.contains(&symbol.name)
+        {
+            continue;
+        }
+        out.push(Candidate {
+            name: symbol.name.clone(),
+            kind: symbol.kind.clone(),
+            position: symbol.selection_range.start.clone(),
+        });
+        if symbol.kind != SymbolKind::Class {
+            continue;
+        }
+        for member in symbol.children.iter().flatten() {
+            if matches!(
+                member.kind,
+                SymbolKind::Method | SymbolKind::Function | SymbolKind::Property
+            ) && !is_skipped(&member.name)
+            {
+                out.push(Candidate {
+                    name: format!("{}.{}", symbol.name, member.name),
+                    kind: member.kind.clone(),
+                    position: member.selection_range.start.clone(),
+                });
+            }
+        }
+    }
+    out
+}
+
+/// Names a module exports through `__all__`.
+///
+/// Reads string literals from `__all__ = [...]`, `__all__ += [...]`,
+/// `__all__.append(...)` and `__all__.extend(...)` at the start of a line.
+/// Computed exports are not followed.
+pub fn all_exports(text: &str) -> HashSet<String> {
+    let mut names = HashSet::new();
+    let mut rest = text;
+    while let Some(idx) = rest.find("__all__") {
+        let at_line_start = rest[..idx].rsplit('\n').next().is_some_and(|s| s.trim().is_empty());
+        rest = &rest[idx + "__all__".len()..];
+        let after = rest.trim_start_matches([' ', '\t']);
+        let opens = ["=", "+=", ".append(", ".extend(", ":"].iter().any(|op| after.starts_with(op));
+        if !at_line_start || !opens || after.starts_with("==") {
+            continue;
+        }
+        let end = statement_end(rest);
+        names.extend(string_literals(&rest[..end]));
+        rest = &rest[end..];
+    }
+    names
+}
+
+/// Byte offset where the statement starting at `text` ends: the first
+/// newline outside brackets.
+fn statement_end(text: &str) -> usize {
+    let mut depth = 0usize;
+    let mut chars = text.char_indices();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' | '\'' => {
+                chars.by_ref().find(|&(_, q)| q == c || q == '\n');
+            }
+            '#' => {
+                if let Some((i, _)) = chars.by_ref().find(|&(_, q)| q == '\n') {
+                    if depth == 0 {
+                        return i;
+                    }
+                }
+            }
+            '(' | '[' | '{' => depth += 1,
+            ')' | ']' | '}' => depth = depth.saturating_sub(1),
+            '\n' if depth == 0 => return i,
+            _ => {}
+        }
+    }
+    text.len()
+}
+
+/// The contents of the quoted strings in `text`, skipping comments.
+fn string_literals(text: &str) -> Vec<String> {
+    let mut out = Vec::new();
+    let mut chars = text.char_indices();
+    while let Some((start, c)) = chars.next() {
+        match c {
+            '"' | '\'' => {
+                let closing = chars.by_ref().find(|&(_, q)| q == c || q == '\n');
+                if let Some((end, _)) = closing.filter(|&(_, q)| q == c) {
+                    out.push(text[start + 1..end].to_string());
+                }
+            }
+            '#' => {
+                chars.by_ref().find(|&(_, q)| q == '\n');
+            }
+            _ => {}
+        }
+    }
+    out
+}
+
+/// Whether `locations`, from a references query that includes the
+/// declaration, show no use of the symbol declared at `uri`/`position`.
+///
+/// `None` when the declaration itself is missing: ty could not resolve the
+/// symbol, so its references say nothing either way.
+pub fn is_unused(uri: &str, position: &Position, locations: &[Location]) -> Option<bool> {
+    let is_declaration = |location: &&Location| {
+        location.uri == uri
+            && location.range.start.line == position.line
+            && location.range.start.character <= position.character
+            && position.character <= location.range.end.character
+    };
+    let declarations = locations.iter().filter(is_declaration).count();
+    (declarations > 0).then_some(declarations == locations.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lsp::protocol::Range;
+
+    fn pos(line: u32, character: u32) -> Position {
+        Position { line, character }
+    }
+
+    fn symbol(
+        name: &str,
+        kind: SymbolKind,
+        line: u32,
+        children: Vec<DocumentSymbol>,
+    ) -> DocumentSymbol {
+        DocumentSymbol {
+            name: name.to_string(),
+            detail: None,
+            kind,
+            tags: None,
+            deprecated: None,
+            range: Range { start: pos(line, 0), end: pos(line, 10) },
+            selection_range: Range { start: pos(line, 4), end: pos(line, 4 + 3) },
+            children: (!children.is_empty()).then_some(children),
+        }
+    }
+
+    fn location(uri: &str, line: u32, start: u32, end: u32) -> Location {
+        Location {
+            uri: uri.to_string(),
+            range: Range { start: pos(line, start), end: pos(line, end) },
+        }
+    }
+
+    #[test]
+    fn candidates_skip_dunders_exports_and_nested_functions() {
+        let symbols = vec![
+            symbol(
+                "Order",
+                SymbolKind::Class,
+                0,
+                vec![
+                    symbol("__init__", SymbolKind::Method, 1, vec![]),
+                    symbol("total", SymbolKind::Method, 3, vec![]),
+                    symbol("status", SymbolKind::Field, 5, vec![]),
+                ],
+            ),
+            symbol(
+                "helper",
+                SymbolKind::Function,
+                7,
+                vec![symbol("inner", SymbolKind::Function, 8, vec![])],
+            ),
+            symbol("public_api", SymbolKind::Function, 10, vec![]),
+            symbol("__version__", SymbolKind::Variable, 12, vec![]),
+            symbol("_", SymbolKind::Variable, 13, vec![]),
+            symbol("os", SymbolKind::Module, 14, vec![]),
+        ];
+        let exports = HashSet::from(["public_api".to_string()]);
+        let names: Vec<String> =
+            candidates(&symbols, &exports).into_iter().map(|c| c.name).collect();
+        assert_eq!(names, ["Order", "Order.total", "helper"]);
+    }
+
+    #[test]
+    fn all_exports_reads_lists_tuples_and_extensions() {
+        let text = "\
+__all__ = [
+    \"Order\",  # the one everybody's after
+    'helper',
+]
+__all__ += (\"extra\",)
+__all__.append('late')
+if __all__ == ['nope']:
+    pass
+x = '__all__ = [\"ignored\"]'
+";
+        let exports = all_exports(text);
+        let mut names: Vec<&str> = exports.iter().map(String::as_str).collect();
+        names.sort_unstable();
+        assert_eq!(names, ["Order", "extra", "helper", "late"]);
+    }
+
+    #[test]
+    fn all_exports_handles_annotated_and_missing() {
+        assert!(all_exports("def f():\n    return 1\n").is_empty());
+        assert_eq!(all_exports("__all__: list[str] = ['a']"), HashSet::from(["a".to_string()]));
+    }
+
+    #[test]
+    fn is_unused_discounts_only_the_declaration() {
+        let uri = "file:///proj/app.py";
+        let declaration = location(uri, 3, 4, 9);
+        let at = pos(3, 4);
+        assert_eq!(is_unused(uri, &at, std::slice::from_ref(&declaration)), Some(true));
+        assert_eq!(
+            is_unused(uri, &at, &[declaration.clone(), location("file:///proj/b.py", 3, 4, 9)]),
+            Some(false)
+        );
+        assert_eq!(is_unused(uri, &at, &[declaration, location(uri, 10, 0, 5)]), Some(false));
+        assert_eq!(is_unused(uri, &at, &[]), None);
+        assert_eq!(is_unused(uri, &at, &[location(uri, 10, 0, 5)]), None);
+    }
+}
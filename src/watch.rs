@@ -0,0 +1,115 @@
+//! Change detection for `--watch`.
+//!
+//! Like the daemon's symbol index, the watcher polls rather than relying on
+//! filesystem events: every [`POLL_INTERVAL`] it re-stats the Python files
+//! ty would analyse and compares their size and mtime with the last look.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::daemon::index::{walk_python_files, FileStamp};
+use crate::daemon::protocol::FileChange;
+use crate::lsp::protocol::FileChangeType;
+use crate::workspace::ty_config::TyConfig;
+
+/// How often the workspace is re-statted.
+pub const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Watches the Python files of one workspace.
+pub struct Watcher {
+    root: PathBuf,
+    config: TyConfig,
+    stamps: HashMap<PathBuf, FileStamp>,
+}
+
+impl Watcher {
+    /// Start watching `root`, taking its current state as the baseline.
+    pub fn new(root: &Path) -> Self {
+        let config = TyConfig::load_or_default(root);
+        let stamps = walk_python_files(root, &config);
+        Self { root: root.to_path_buf(), config, stamps }
+    }
+
+    /// Wait until files change, and return the changes.
+    ///
+    /// Editors often write a file in several steps and tools like formatters
+    /// touch many files at once, so after the first change the watcher waits
+    /// one more interval and reports everything that changed meanwhile.
+    pub async fn next_changes(&mut self) -> Vec<FileChange> {
+        loop {
+            tokio::time::sleep(POLL_INTERVAL).await;
+            let current = walk_python_files(&self.root, &self.config);
+            if changes(&self.stamps, &current).is_empty() {
+                continue;
+            }
+            tokio::time::sleep(POLL_INTERVAL).await;
+            let settled = walk_python_files(&self.root, &self.config);
+            let found = changes(&self.stamps, &settled);
+            self.stamps = settled;
+            if !found.is_empty() {
+                return found;
+            }
+        }
+    }
+}
+
+/// Files created, changed or deleted between two looks, sorted by path.
+pub fn changes(
+    before: &HashMap<PathBuf, FileStamp>,
+    after: &HashMap<PathBuf, FileStamp>,
+) -> Vec<FileChange> {
+    let mut found: Vec<FileChange> = after
+        .iter()
+        .filter_map(|(file, stamp)| {
+            let change = match before.get(file) {
+                None => FileChangeType::Created,
+                Some(old) if old != stamp => FileChangeType::Changed,
+                Some(_) => return None,
+            };
+            Some(FileChange { file: file.clone(), change })
+        })
+        .chain(
+            before
+                .keys()
+                .filter(|file| !after.contains_key(*file))
+                .map(|file| FileChange { file: file.clone(), change: FileChangeType::Deleted }),
+        )
+        .collect();
+    found.sort_by(|a, b| a.file.cmp(&b.file));
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_changes_reports_created_changed_and_deleted() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let root = dir.path();
+        for file in ["a.py", "b.py", "c.py"] {
+            std::fs::write(root.join(file), "x = 1\n").expect("write");
+        }
+        let before = walk_python_files(root, &TyConfig::default());
+
+        std::fs::write(root.join("a.py"), "x = 1000\n").expect("write");
+        std::fs::remove_file(root.join("b.py")).expect("remove");
+        std::fs::write(root.join("d.py"), "y = 2\n").expect("write");
+        let after = walk_python_files(root, &TyConfig::default());
+
+        let found: Vec<(String, FileChangeType)> = changes(&before, &after)
+            .into_iter()
+            .map(|c| (c.file.file_name().unwrap().to_string_lossy().into_owned(), c.change))
+            .collect();
+        assert_eq!(
+            found,
+            [
+                ("a.py".to_string(), FileChangeType::Changed),
+                ("b.py".to_string(), FileChangeType::Deleted),
+                ("d.py".to_string(), FileChangeType::Created),
+            ]
+        );
+        assert!(changes(&after, &after).is_empty());
+    }
+}
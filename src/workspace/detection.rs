@@ -1,4 +1,6 @@
+use std::fmt;
 use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
 
 /// Python project marker files/directories, checked in order of priority.
 const MARKERS: &[&str] = &[
@@ -12,6 +14,42 @@ const MARKERS: &[&str] = &[
     "src",
 ];
 
+/// In-project virtualenv directories, checked in order.
+const VENV_DIRS: &[&str] = &[".venv", "venv", "env"];
+
+/// Conda's environment files, checked in order.
+const CONDA_FILES: &[&str] = &["environment.yml", "environment.yaml"];
+
+/// How a workspace's Python environment was found.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EnvironmentKind {
+    /// A virtualenv inside the project, e.g. `.venv`.
+    Venv,
+    /// The environment Poetry manages for the project.
+    Poetry,
+    /// The conda environment named in `environment.yml`.
+    Conda,
+}
+
+/// The virtualenv or conda environment holding a workspace's dependencies.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PythonEnvironment {
+    pub kind: EnvironmentKind,
+    /// The environment's root directory, e.g. `/proj/.venv`.
+    pub prefix: PathBuf,
+}
+
+impl fmt::Display for PythonEnvironment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match self.kind {
+            EnvironmentKind::Venv => "virtualenv",
+            EnvironmentKind::Poetry => "poetry",
+            EnvironmentKind::Conda => "conda",
+        };
+        write!(f, "{} ({kind})", self.prefix.display())
+    }
+}
+
 #[allow(dead_code)]
 pub struct WorkspaceDetector;
 
@@ -50,6 +88,89 @@ impl WorkspaceDetector {
     fn has_python_markers(path: &Path) -> bool {
         MARKERS.iter().any(|marker| path.join(marker).exists())
     }
+
+    /// The Python environment of the project at `workspace_root`: a
+    /// virtualenv in the project, else the one Poetry manages for it, else
+    /// the conda environment its `environment.yml` names.
+    ///
+    /// `None` leaves the choice to the language server. Asking Poetry runs
+    /// `poetry env info --path`, so this may block for a moment.
+    pub fn find_environment(workspace_root: &Path) -> Option<PythonEnvironment> {
+        let found = |kind, prefix| Some(PythonEnvironment { kind, prefix });
+        if let Some(prefix) =
+            VENV_DIRS.iter().map(|dir| workspace_root.join(dir)).find(|dir| is_virtualenv(dir))
+        {
+            return found(EnvironmentKind::Venv, prefix);
+        }
+        if workspace_root.join("poetry.lock").is_file() {
+            if let Some(prefix) = poetry_environment(workspace_root) {
+                return found(EnvironmentKind::Poetry, prefix);
+            }
+        }
+        let name = CONDA_FILES.iter().find_map(|file| {
+            conda_environment_name(&std::fs::read_to_string(workspace_root.join(file)).ok()?)
+        })?;
+        find_conda_environment(&name, &conda_envs_dirs())
+            .and_then(|prefix| found(EnvironmentKind::Conda, prefix))
+    }
+}
+
+/// The environment `poetry env info --path` reports for the project.
+fn poetry_environment(workspace_root: &Path) -> Option<PathBuf> {
+    let output = Command::new("poetry")
+        .args(["env", "info", "--path"])
+        .current_dir(workspace_root)
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .ok()
+        .filter(|output| output.status.success())?;
+    let prefix = PathBuf::from(String::from_utf8_lossy(&output.stdout).trim());
+    prefix.is_dir().then_some(prefix)
+}
+
+/// The `name:` of a conda `environment.yml`.
+fn conda_environment_name(text: &str) -> Option<String> {
+    text.lines()
+        .find_map(|line| line.strip_prefix("name:"))
+        .map(|name| name.trim().trim_matches(|c| c == '"' || c == '\'').to_string())
+        .filter(|name| !name.is_empty())
+}
+
+/// Directories conda keeps named environments in: `CONDA_ENVS_PATH`, the
+/// `envs` of the conda install in use, then the usual install locations.
+fn conda_envs_dirs() -> Vec<PathBuf> {
+    let mut dirs: Vec<PathBuf> =
+        std::env::var_os("CONDA_ENVS_PATH").iter().flat_map(std::env::split_paths).collect();
+    // CONDA_EXE is `<install>/bin/conda`.
+    if let Some(install) = std::env::var_os("CONDA_EXE")
+        .map(PathBuf::from)
+        .and_then(|exe| Some(exe.parent()?.parent()?.to_path_buf()))
+    {
+        dirs.push(install.join("envs"));
+    }
+    if let Some(home) = std::env::var_os("HOME").map(PathBuf::from) {
+        dirs.push(home.join(".conda").join("envs"));
+        for install in ["miniconda3", "anaconda3", "miniforge3", "mambaforge"] {
+            dirs.push(home.join(install).join("envs"));
+        }
+    }
+    dirs
+}
+
+/// The conda environment called `name` in the first of `envs_dirs` holding one.
+fn find_conda_environment(name: &str, envs_dirs: &[PathBuf]) -> Option<PathBuf> {
+    envs_dirs.iter().map(|dir| dir.join(name)).find(|prefix| is_conda_prefix(prefix))
+}
+
+/// Whether `dir` is a virtualenv: every venv has a `pyvenv.cfg` at its top.
+pub fn is_virtualenv(dir: &Path) -> bool {
+    dir.join("pyvenv.cfg").is_file()
+}
+
+/// Whether `dir` is the prefix of a conda environment.
+pub fn is_conda_prefix(dir: &Path) -> bool {
+    dir.join("conda-meta").is_dir()
 }
 
 #[cfg(test)]
@@ -135,6 +256,34 @@ mod tests {
         assert!(desc.contains("pyproject.toml"), "should mention the marker found: {desc}");
     }
 
+    #[test]
+    fn test_find_environment_prefers_in_project_venv() {
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(WorkspaceDetector::find_environment(dir.path()), None);
+
+        // A directory without pyvenv.cfg is not a virtualenv.
+        std::fs::create_dir(dir.path().join(".venv")).unwrap();
+        std::fs::create_dir(dir.path().join("venv")).unwrap();
+        std::fs::write(dir.path().join("venv/pyvenv.cfg"), "home = /usr/bin\n").unwrap();
+        let env = WorkspaceDetector::find_environment(dir.path()).unwrap();
+        assert_eq!(env.kind, EnvironmentKind::Venv);
+        assert_eq!(env.prefix, dir.path().join("venv"));
+        assert!(env.to_string().ends_with("venv (virtualenv)"), "{env}");
+    }
+
+    #[test]
+    fn test_conda_environment_from_environment_yml() {
+        let yml = "# deps\nname: \"shop\"\nchannels:\n  - conda-forge\n";
+        assert_eq!(conda_environment_name(yml).as_deref(), Some("shop"));
+        assert_eq!(conda_environment_name("dependencies:\n  - python\n"), None);
+
+        let dir = tempfile::tempdir().unwrap();
+        let envs = [dir.path().join("a"), dir.path().join("b")];
+        std::fs::create_dir_all(envs[1].join("shop/conda-meta")).unwrap();
+        assert_eq!(find_conda_environment("shop", &envs), Some(envs[1].join("shop")));
+        assert_eq!(find_conda_environment("other", &envs), None);
+    }
+
     #[test]
     fn test_describe_detection_no_marker() {
         let dir = tempfile::tempdir().unwrap();
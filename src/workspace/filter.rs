@@ -0,0 +1,138 @@
+//! `--include` / `--exclude` path filters for result locations.
+//!
+//! The filter travels with `references`, `batch_references` and
+//! `workspace_symbols` requests, so the daemon drops unwanted locations
+//! before serializing its answer.
+
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::lsp::protocol::Location;
+use crate::lsp::uri::uri_to_path;
+use crate::workspace::ty_config::{gitignore_match, path_segments};
+
+/// Paths hidden unless `--include-deps` is given or an `--include` pattern
+/// names them: virtualenvs, installed packages and build output.
+pub const DEFAULT_EXCLUDES: &[&str] = &[".venv", "site-packages", "build/"];
+
+/// Which result paths to keep.
+///
+/// Patterns follow gitignore rules and are matched against the path relative
+/// to the workspace root (or the absolute path for files outside it); a
+/// pattern naming a directory matches everything below it.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct PathFilter {
+    /// Keep only paths matching one of these (everything when empty)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub include: Vec<String>,
+
+    /// Drop paths matching any of these
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub exclude: Vec<String>,
+
+    /// Also drop paths matching [`DEFAULT_EXCLUDES`], except those an
+    /// `include` pattern names
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub default_excludes: bool,
+}
+
+impl PathFilter {
+    /// The filter for `--include` / `--exclude`; the default exclusions
+    /// apply unless dependencies were asked for.
+    pub fn new(include: Vec<String>, exclude: Vec<String>, include_deps: bool) -> Self {
+        Self { include, exclude, default_excludes: !include_deps }
+    }
+
+    /// Whether the filter keeps every path.
+    pub fn is_empty(&self) -> bool {
+        self.include.is_empty() && self.exclude.is_empty() && !self.default_excludes
+    }
+
+    /// Whether results in `path` are kept.
+    pub fn allows(&self, path: &Path, workspace_root: &Path) -> bool {
+        let relative = path.strip_prefix(workspace_root).unwrap_or(path);
+        let segments = path_segments(relative);
+        let segments: Vec<&str> = segments.iter().map(String::as_str).collect();
+        let matches = |pattern: &String| gitignore_match(pattern, &segments);
+
+        let included = self.include.iter().any(matches);
+        if !self.include.is_empty() && !included {
+            return false;
+        }
+        if self.exclude.iter().any(matches) {
+            return false;
+        }
+        // A default exclusion is lifted by an include pattern naming it, like
+        // `.venv/**/site-packages/attrs`, not by one that merely covers it.
+        let named = |default: &str| {
+            let name = default.trim_end_matches('/');
+            self.include.iter().any(|pattern| pattern.split('/').any(|part| part == name))
+        };
+        !self.default_excludes
+            || !DEFAULT_EXCLUDES
+                .iter()
+                .any(|default| !named(default) && gitignore_match(default, &segments))
+    }
+
+    /// Drop the locations whose file the filter rejects.
+    pub fn retain_locations(&self, locations: &mut Vec<Location>, workspace_root: &Path) {
+        if self.is_empty() {
+            return;
+        }
+        locations.retain(|loc| self.allows(Path::new(&uri_to_path(&loc.uri)), workspace_root));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn allows(filter: &PathFilter, path: &str) -> bool {
+        filter.allows(Path::new(path), Path::new("/proj"))
+    }
+
+    #[test]
+    fn test_default_excludes_hide_venv_packages_and_build() {
+        let filter = PathFilter::new(Vec::new(), Vec::new(), false);
+        assert!(allows(&filter, "/proj/app/models.py"));
+        assert!(!allows(&filter, "/proj/.venv/lib/python3.12/site-packages/attr/x.py"));
+        assert!(!allows(&filter, "/usr/lib/python3/site-packages/attr/x.py"));
+        assert!(!allows(&filter, "/proj/build/lib/app/models.py"));
+        assert!(allows(&filter, "/proj/app/build.py"), "only a build/ directory is hidden");
+
+        let with_deps = PathFilter::new(Vec::new(), Vec::new(), true);
+        assert!(with_deps.is_empty());
+        assert!(allows(&with_deps, "/proj/.venv/lib/python3.12/site-packages/attr/x.py"));
+    }
+
+    #[test]
+    fn test_include_and_exclude_globs() {
+        let filter = PathFilter::new(vec!["src/".to_string()], vec!["*_pb2.py".to_string()], false);
+        assert!(allows(&filter, "/proj/src/app/models.py"));
+        assert!(!allows(&filter, "/proj/tests/test_models.py"), "not under an include");
+        assert!(!allows(&filter, "/proj/src/app/models_pb2.py"), "excluded");
+        assert!(!allows(&filter, "/proj/src/build/gen.py"), "default exclusions still apply");
+
+        let vendored =
+            PathFilter::new(vec![".venv/**/site-packages/attr".to_string()], Vec::new(), false);
+        assert!(allows(&vendored, "/proj/.venv/lib/python3.12/site-packages/attr/x.py"));
+    }
+
+    #[test]
+    fn test_retain_locations() {
+        use crate::lsp::protocol::{Position, Range};
+        let loc = |uri: &str| {
+            let pos = Position { line: 0, character: 0 };
+            Location { uri: uri.to_string(), range: Range { start: pos.clone(), end: pos } }
+        };
+        let mut locations = vec![
+            loc("file:///proj/app/models.py"),
+            loc("file:///proj/.venv/lib/python3.12/site-packages/attr/x.py"),
+        ];
+        PathFilter::new(Vec::new(), Vec::new(), false)
+            .retain_locations(&mut locations, Path::new("/proj"));
+        assert_eq!(locations.len(), 1);
+        assert_eq!(locations[0].uri, "file:///proj/app/models.py");
+    }
+}
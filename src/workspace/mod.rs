@@ -1,2 +1,9 @@
 pub mod detection;
+pub mod filter;
 pub mod navigation;
+pub mod origin;
+pub mod page;
+pub mod paths;
+pub mod source_cache;
+pub mod ty_config;
+pub mod walker;
@@ -1,8 +1,32 @@
+//! Locating a symbol's occurrences in one Python file.
+//!
+//! The file is parsed with tree-sitter, so only identifiers count: a name
+//! that appears in a comment, a string, or as a keyword argument's name is
+//! not an occurrence. Occurrences can be narrowed to definition sites, call
+//! sites, or attribute accesses with [`SymbolSite`].
+
 use anyhow::{Context, Result};
+use tree_sitter::{Node, Parser, Tree};
+
+use crate::lsp::position::{convert_column, ColumnUnit, LSP_UNIT};
+
+/// The syntactic role of an occurrence.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SymbolSite {
+    /// A name being bound: `def`/`class` names, assignment and `for`
+    /// targets, parameters, and `import ... as` aliases.
+    Definition,
+    /// The callee of a call, `name(...)` or `obj.name(...)`.
+    Call,
+    /// The attribute part of `obj.name`.
+    Attribute,
+}
 
 #[allow(dead_code)]
 pub struct SymbolFinder {
+    source: String,
     lines: Vec<String>,
+    tree: Tree,
 }
 
 #[allow(dead_code)]
@@ -11,57 +35,180 @@ impl SymbolFinder {
         let content = tokio::fs::read_to_string(file_path)
             .await
             .with_context(|| format!("Failed to read file: {file_path}"))?;
-        let lines: Vec<String> = content.lines().map(String::from).collect();
+        Self::from_source(content).with_context(|| format!("Failed to parse file: {file_path}"))
+    }
 
-        Ok(Self { lines })
+    fn from_source(source: String) -> Result<Self> {
+        let mut parser = Parser::new();
+        parser
+            .set_language(&tree_sitter_python::LANGUAGE.into())
+            .context("Failed to load the Python grammar")?;
+        let tree = parser.parse(&source, None).context("tree-sitter returned no tree")?;
+        let lines = source.lines().map(String::from).collect();
+        Ok(Self { source, lines, tree })
     }
 
+    /// Occurrences of `symbol` as 0-based LSP `(line, column)` pairs.
+    ///
+    /// A dotted `symbol` such as `obj.method` matches attribute accesses
+    /// spelled that way and points at the attribute.
     pub fn find_symbol_positions(&self, symbol: &str) -> Vec<(u32, u32)> {
-        let mut positions = Vec::new();
-
-        for (line_idx, line) in self.lines.iter().enumerate() {
-            let mut char_pos = 0;
-            while let Some(pos) = line[char_pos..].find(symbol) {
-                let actual_pos = char_pos + pos;
+        self.occurrences(symbol).map(|node| self.position(node)).collect()
+    }
 
-                if Self::is_whole_word_match(line, actual_pos, symbol) {
-                    #[allow(clippy::cast_possible_truncation)]
-                    positions.push((line_idx as u32, actual_pos as u32));
-                }
+    /// Occurrences of `symbol` in the role `site` only.
+    pub fn find_symbol_sites(&self, symbol: &str, site: SymbolSite) -> Vec<(u32, u32)> {
+        self.occurrences(symbol)
+            .filter(|&node| match site {
+                SymbolSite::Definition => is_definition(node),
+                SymbolSite::Call => is_call(node),
+                SymbolSite::Attribute => is_attribute(node),
+            })
+            .map(|node| self.position(node))
+            .collect()
+    }
 
-                char_pos = actual_pos + 1;
-            }
-        }
+    /// Where a lookup of `symbol` should start: its first definition site in
+    /// the file, else its first occurrence.
+    pub fn preferred_position(&self, symbol: &str) -> Option<(u32, u32)> {
+        let definition = self.find_symbol_sites(symbol, SymbolSite::Definition).first().copied();
+        definition.or_else(|| self.find_symbol_positions(symbol).first().copied())
+    }
 
-        positions
+    /// The identifiers that start on `line` (0-based), with their LSP
+    /// columns, in source order.
+    ///
+    /// Names in comments and strings are not identifiers, but those inside
+    /// f-string replacement fields are.
+    pub fn line_identifiers(&self, line: u32) -> Vec<(String, u32)> {
+        identifiers(&self.tree)
+            .into_iter()
+            .filter(|node| node.start_position().row == line as usize)
+            .map(|node| (self.text(node).to_string(), self.position(node).1))
+            .collect()
     }
 
-    fn is_whole_word_match(line: &str, pos: usize, symbol: &str) -> bool {
-        let bytes = line.as_bytes();
+    pub fn get_line(&self, line_number: u32) -> Option<&str> {
+        self.lines.get(line_number as usize).map(String::as_str)
+    }
 
-        if pos > 0 {
-            let prev = bytes[pos - 1];
-            if prev.is_ascii_alphanumeric() || prev == b'_' {
+    /// Identifier nodes naming `symbol`, in source order.
+    fn occurrences<'a>(&'a self, symbol: &'a str) -> impl Iterator<Item = Node<'a>> + 'a {
+        let (qualifier, name) = match symbol.rsplit_once('.') {
+            Some((qualifier, name)) => (Some(qualifier), name),
+            None => (None, symbol),
+        };
+        identifiers(&self.tree).into_iter().filter(move |&node| {
+            if self.text(node) != name || is_keyword_argument_name(node) {
                 return false;
             }
-        }
+            let Some(qualifier) = qualifier else { return true };
+            node.parent().filter(|_| is_attribute(node)).is_some_and(|parent| {
+                parent.child_by_field_name("object").is_some_and(|object| {
+                    self.text(object).split_whitespace().collect::<String>() == qualifier
+                })
+            })
+        })
+    }
 
-        let end_pos = pos + symbol.len();
-        if end_pos < bytes.len() {
-            let next = bytes[end_pos];
-            if next.is_ascii_alphanumeric() || next == b'_' {
-                return false;
+    fn text(&self, node: Node<'_>) -> &str {
+        &self.source[node.byte_range()]
+    }
+
+    fn position(&self, node: Node<'_>) -> (u32, u32) {
+        let start = node.start_position();
+        let line = self.lines.get(start.row).map_or("", String::as_str);
+        #[allow(clippy::cast_possible_truncation)]
+        let (row, byte) = (start.row as u32, start.column as u32);
+        (row, convert_column(line, byte, ColumnUnit::Utf8, LSP_UNIT))
+    }
+}
+
+/// Every `identifier` node of `tree`, in source order.
+fn identifiers(tree: &Tree) -> Vec<Node<'_>> {
+    let mut found = Vec::new();
+    let mut cursor = tree.walk();
+    loop {
+        let node = cursor.node();
+        if node.kind() == "identifier" {
+            found.push(node);
+        }
+        if cursor.goto_first_child() {
+            continue;
+        }
+        while !cursor.goto_next_sibling() {
+            if !cursor.goto_parent() {
+                return found;
             }
         }
+    }
+}
+
+/// Whether `node` is the `field` child of its parent.
+fn is_field(node: Node<'_>, field: &str) -> bool {
+    node.parent().and_then(|parent| parent.child_by_field_name(field)) == Some(node)
+}
+
+fn parent_kind(node: Node<'_>) -> &'static str {
+    node.parent().map_or("", |parent| parent.kind())
+}
+
+/// `name` in `f(name=value)`, which names a parameter rather than using the symbol.
+fn is_keyword_argument_name(node: Node<'_>) -> bool {
+    parent_kind(node) == "keyword_argument" && is_field(node, "name")
+}
+
+fn is_attribute(node: Node<'_>) -> bool {
+    parent_kind(node) == "attribute" && is_field(node, "attribute")
+}
+
+fn is_call(node: Node<'_>) -> bool {
+    let callee = if is_attribute(node) { node.parent() } else { Some(node) };
+    callee.is_some_and(|callee| parent_kind(callee) == "call" && is_field(callee, "function"))
+}
 
-        true
+fn is_definition(node: Node<'_>) -> bool {
+    match parent_kind(node) {
+        "function_definition"
+        | "class_definition"
+        | "default_parameter"
+        | "typed_default_parameter" => is_field(node, "name"),
+        "parameters" | "lambda_parameters" | "typed_parameter" => true,
+        "aliased_import" => is_field(node, "alias"),
+        "pattern_list" | "tuple_pattern" => node.parent().is_some_and(is_binding_target),
+        _ => is_binding_target(node),
     }
+}
 
-    pub fn get_line(&self, line_number: u32) -> Option<&str> {
-        self.lines.get(line_number as usize).map(String::as_str)
+/// Whether `node` is the target of an assignment or `for` loop.
+fn is_binding_target(node: Node<'_>) -> bool {
+    match parent_kind(node) {
+        "assignment" | "augmented_assignment" | "for_statement" => is_field(node, "left"),
+        _ => false,
     }
 }
 
+/// Whether `c` can appear in a Python identifier.
+pub fn is_identifier_char(c: char) -> bool {
+    c == '_' || c.is_alphanumeric()
+}
+
+/// Byte offsets of the non-overlapping occurrences of `pattern` in `text`
+/// that don't run into a longer name.
+///
+/// Where `pattern` starts or ends with an identifier character, the text on
+/// that side must not continue the identifier, so `save` is not found inside
+/// `autosave` or `save_all`.
+pub fn find_words<'a>(text: &'a str, pattern: &'a str) -> impl Iterator<Item = usize> + 'a {
+    let starts_word = pattern.starts_with(is_identifier_char);
+    let ends_word = pattern.ends_with(is_identifier_char);
+    text.match_indices(pattern).map(|(byte, _)| byte).filter(move |&byte| {
+        let joins_before = starts_word && text[..byte].ends_with(is_identifier_char);
+        let joins_after = ends_word && text[byte + pattern.len()..].starts_with(is_identifier_char);
+        !joins_before && !joins_after
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -85,6 +232,20 @@ mod tests {
         assert_eq!(positions[2], (3, 9));
     }
 
+    #[test]
+    fn test_line_identifiers_skip_strings_and_comments() {
+        let source = "total = order.subtotal * rate  # rate from config\n\
+                      label = f\"{café.name}: total\"\n";
+        let finder = SymbolFinder::from_source(source.to_string()).unwrap();
+        let names = |line| {
+            finder.line_identifiers(line).into_iter().map(|(name, _)| name).collect::<Vec<_>>()
+        };
+        assert_eq!(names(0), ["total", "order", "subtotal", "rate"]);
+        assert_eq!(names(1), ["label", "café", "name"]);
+        assert_eq!(finder.line_identifiers(1)[2].1, 16);
+        assert!(finder.line_identifiers(5).is_empty());
+    }
+
     #[tokio::test]
     async fn test_symbol_not_found() {
         let mut temp_file = NamedTempFile::new().unwrap();
@@ -137,6 +298,58 @@ mod tests {
         assert!(positions.is_empty());
     }
 
+    #[tokio::test]
+    async fn test_non_ascii_line_reports_lsp_columns() {
+        let mut temp_file = NamedTempFile::new().unwrap();
+        writeln!(temp_file, "msg = \"héllo 🎉\"; greet(msg)").unwrap();
+        writeln!(temp_file, "café_greet = greet").unwrap();
+
+        let finder = SymbolFinder::new(temp_file.path().to_str().unwrap()).await.unwrap();
+        let positions = finder.find_symbol_positions("greet");
+        // 🎉 counts as two UTF-16 units; `café_greet` is not a whole-word match
+        assert_eq!(positions, vec![(0, 18), (1, 13)]);
+    }
+
+    #[tokio::test]
+    async fn test_comments_strings_and_keyword_names_are_skipped() {
+        let mut temp_file = NamedTempFile::new().unwrap();
+        writeln!(temp_file, "# retry the retry loop").unwrap();
+        writeln!(temp_file, "retry = 3").unwrap();
+        writeln!(temp_file, "run(retry=retry, msg=\"no retry\")").unwrap();
+
+        let finder = SymbolFinder::new(temp_file.path().to_str().unwrap()).await.unwrap();
+        assert_eq!(finder.find_symbol_positions("retry"), vec![(1, 0), (2, 10)]);
+    }
+
+    #[tokio::test]
+    async fn test_sites() {
+        let mut temp_file = NamedTempFile::new().unwrap();
+        writeln!(temp_file, "from lib import save").unwrap();
+        writeln!(temp_file, "def save(data):").unwrap();
+        writeln!(temp_file, "    handler.save(data)").unwrap();
+        writeln!(temp_file, "    callback = handler.save").unwrap();
+        writeln!(temp_file, "    return save(data)").unwrap();
+
+        let finder = SymbolFinder::new(temp_file.path().to_str().unwrap()).await.unwrap();
+        assert_eq!(finder.find_symbol_sites("save", SymbolSite::Definition), vec![(1, 4)]);
+        assert_eq!(finder.find_symbol_sites("save", SymbolSite::Call), vec![(2, 12), (4, 11)]);
+        assert_eq!(finder.find_symbol_sites("save", SymbolSite::Attribute), vec![(2, 12), (3, 23)]);
+        assert_eq!(finder.find_symbol_sites("data", SymbolSite::Definition), vec![(1, 9)]);
+        assert_eq!(finder.preferred_position("save"), Some((1, 4)));
+        assert_eq!(finder.preferred_position("handler"), Some((2, 4)));
+    }
+
+    #[tokio::test]
+    async fn test_dotted_symbol_matches_attribute_access() {
+        let mut temp_file = NamedTempFile::new().unwrap();
+        writeln!(temp_file, "self.cache.clear()").unwrap();
+        writeln!(temp_file, "cache.clear()").unwrap();
+
+        let finder = SymbolFinder::new(temp_file.path().to_str().unwrap()).await.unwrap();
+        assert_eq!(finder.find_symbol_positions("cache.clear"), vec![(1, 6)]);
+        assert_eq!(finder.find_symbol_positions("self.cache.clear"), vec![(0, 11)]);
+    }
+
     #[tokio::test]
     async fn test_get_line() {
         let mut temp_file = NamedTempFile::new().unwrap();
@@ -148,4 +361,13 @@ mod tests {
         assert_eq!(finder.get_line(1), Some("line one"));
         assert_eq!(finder.get_line(2), None);
     }
+
+    #[test]
+    fn test_find_words_respects_identifier_boundaries() {
+        let text = "autosave(save_all, save) or save";
+        assert_eq!(find_words(text, "save").collect::<Vec<_>>(), vec![19, 28]);
+        assert_eq!(find_words(text, "save(").collect::<Vec<_>>(), Vec::<usize>::new());
+        assert_eq!(find_words("f(save)", "(save").collect::<Vec<_>>(), vec![1]);
+        assert!(is_identifier_char('é') && !is_identifier_char('.'));
+    }
 }
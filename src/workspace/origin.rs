@@ -0,0 +1,205 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+
+use crate::lsp::protocol::Location;
+use crate::lsp::uri::uri_to_path;
+use crate::workspace::detection::{is_conda_prefix, is_virtualenv};
+
+/// Where a result location lives relative to the project being queried.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Origin {
+    /// Project source under the workspace root
+    Workspace,
+    /// Third-party package installed in a virtualenv or system `site-packages`
+    SitePackages,
+    /// Python standard library (including typeshed stubs bundled with ty)
+    Stdlib,
+    /// Outside the workspace but not recognisably a dependency
+    External,
+}
+
+impl Origin {
+    /// Classify a result URI (usually `file://...`) for the given workspace root.
+    ///
+    /// Everything under the root is project source, so first-party packages
+    /// named `stdlib/` or `typeshed/` stay in the workspace. The exception is
+    /// an in-project environment (`.venv/`, a conda prefix) or an installed
+    /// `site-packages`, which are dependencies wherever they live.
+    pub fn classify(uri: &str, workspace_root: &Path) -> Self {
+        let path = uri_to_path(uri);
+        let path = Path::new(&path);
+        let Ok(rel) = path.strip_prefix(workspace_root) else {
+            return Self::from_markers(path).unwrap_or(Self::External);
+        };
+        if let Some(env) = environment_root(workspace_root, rel) {
+            let in_env = path.strip_prefix(env).unwrap_or(rel);
+            return Self::from_markers(in_env).unwrap_or(Self::SitePackages);
+        }
+        if segments(rel).any(is_site_packages) {
+            return Self::SitePackages;
+        }
+        Self::Workspace
+    }
+
+    /// The dependency origin that `path`'s segments point to, if any.
+    fn from_markers(path: &Path) -> Option<Self> {
+        let segments: Vec<&str> = segments(path).collect();
+        if segments.iter().any(|s| is_site_packages(s)) {
+            return Some(Self::SitePackages);
+        }
+        let in_python_lib = segments
+            .windows(2)
+            .any(|pair| (pair[0] == "lib" || pair[0] == "Lib") && is_python_dir(pair[1]));
+        if in_python_lib || segments.iter().any(|s| *s == "typeshed" || *s == "stdlib") {
+            return Some(Self::Stdlib);
+        }
+        None
+    }
+
+    /// Short label used in output (`workspace`, `site-packages`, `stdlib`, `external`).
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Workspace => "workspace",
+            Self::SitePackages => "site-packages",
+            Self::Stdlib => "stdlib",
+            Self::External => "external",
+        }
+    }
+}
+
+fn segments(path: &Path) -> impl Iterator<Item = &str> {
+    path.iter().filter_map(|s| s.to_str())
+}
+
+fn is_site_packages(segment: &str) -> bool {
+    segment == "site-packages" || segment == "dist-packages"
+}
+
+/// `python3.12` or `python3`, but not a package like `python_utils`.
+fn is_python_dir(segment: &str) -> bool {
+    segment.strip_prefix("python").is_some_and(|v| v.starts_with(|c: char| c.is_ascii_digit()))
+}
+
+/// The virtualenv or conda prefix below `root` that contains `rel`, if any.
+fn environment_root(root: &Path, rel: &Path) -> Option<PathBuf> {
+    let mut dir = root.to_path_buf();
+    let mut parts = rel.iter().peekable();
+    while let Some(part) = parts.next() {
+        // The last part is the file itself.
+        parts.peek()?;
+        dir.push(part);
+        if is_environment(&dir) {
+            return Some(dir);
+        }
+    }
+    None
+}
+
+/// Whether `dir` is a virtualenv or conda prefix.
+///
+/// Results share their directories, so the answer is kept per directory
+/// for the life of the process rather than looked up for every result.
+fn is_environment(dir: &Path) -> bool {
+    static ENVIRONMENTS: OnceLock<Mutex<HashMap<PathBuf, bool>>> = OnceLock::new();
+    let environments = ENVIRONMENTS.get_or_init(Mutex::default);
+    if let Some(&known) = environments.lock().expect("environment cache poisoned").get(dir) {
+        return known;
+    }
+    let found = is_virtualenv(dir) || is_conda_prefix(dir);
+    environments.lock().expect("environment cache poisoned").insert(dir.to_path_buf(), found);
+    found
+}
+
+/// Drop locations that are not part of the workspace (dependencies, stdlib).
+pub fn retain_workspace_locations(locations: &mut Vec<Location>, workspace_root: &Path) {
+    locations.retain(|loc| Origin::classify(&loc.uri, workspace_root) == Origin::Workspace);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lsp::protocol::{Position, Range};
+
+    fn loc(uri: &str) -> Location {
+        let pos = Position { line: 0, character: 0 };
+        Location { uri: uri.to_string(), range: Range { start: pos.clone(), end: pos } }
+    }
+
+    #[test]
+    fn test_classify_workspace_file() {
+        let root = Path::new("/proj");
+        assert_eq!(Origin::classify("file:///proj/app/main.py", root), Origin::Workspace);
+    }
+
+    #[test]
+    fn test_classify_in_project_venv_is_site_packages() {
+        let root = Path::new("/proj");
+        let uri = "file:///proj/.venv/lib/python3.12/site-packages/requests/api.py";
+        assert_eq!(Origin::classify(uri, root), Origin::SitePackages);
+    }
+
+    #[test]
+    fn test_classify_stdlib_and_typeshed() {
+        let root = Path::new("/proj");
+        assert_eq!(
+            Origin::classify("file:///usr/lib/python3.12/json/__init__.py", root),
+            Origin::Stdlib
+        );
+        assert_eq!(
+            Origin::classify("file:///home/u/.cache/ty/typeshed/stdlib/builtins.pyi", root),
+            Origin::Stdlib
+        );
+    }
+
+    #[test]
+    fn test_classify_ignores_markers_above_workspace_root() {
+        let root = Path::new("/opt/lib/python-tools/proj");
+        assert_eq!(
+            Origin::classify("file:///opt/lib/python-tools/proj/app.py", root),
+            Origin::Workspace
+        );
+    }
+
+    #[test]
+    fn test_classify_first_party_stdlib_dir_is_workspace() {
+        let root = Path::new("/proj");
+        for uri in [
+            "file:///proj/src/stdlib/compat.py",
+            "file:///proj/lib/python_utils/x.py",
+            "file:///proj/vendor/typeshed/stubs.pyi",
+        ] {
+            assert_eq!(Origin::classify(uri, root), Origin::Workspace, "{uri}");
+        }
+    }
+
+    #[test]
+    fn test_classify_in_project_environment() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        std::fs::create_dir_all(root.join("env/lib/python3.12")).unwrap();
+        std::fs::write(root.join("env/pyvenv.cfg"), "home = /usr/bin\n").unwrap();
+        let uri = |rel: &str| format!("file://{}", root.join(rel).display());
+        assert_eq!(Origin::classify(&uri("env/lib/python3.12/os.py"), root), Origin::Stdlib);
+        assert_eq!(Origin::classify(&uri("env/bin/tool.py"), root), Origin::SitePackages);
+        assert_eq!(Origin::classify(&uri("stdlib/compat.py"), root), Origin::Workspace);
+    }
+
+    #[test]
+    fn test_classify_unrelated_path_is_external() {
+        let root = Path::new("/proj");
+        assert_eq!(Origin::classify("file:///other/lib.py", root), Origin::External);
+    }
+
+    #[test]
+    fn test_retain_workspace_locations() {
+        let mut locations = vec![
+            loc("file:///proj/a.py"),
+            loc("file:///proj/.venv/lib/python3.12/site-packages/x.py"),
+            loc("file:///usr/lib/python3.12/os.py"),
+        ];
+        retain_workspace_locations(&mut locations, Path::new("/proj"));
+        assert_eq!(locations.len(), 1);
+        assert_eq!(locations[0].uri, "file:///proj/a.py");
+    }
+}
@@ -0,0 +1,81 @@
+//! `--limit` / `--offset` pages of long result lists.
+//!
+//! The page travels with `batch_references` and `workspace_symbols`
+//! requests, so the daemon only serializes the results asked for and says
+//! how many more there are.
+
+use serde::{Deserialize, Serialize};
+
+#[allow(clippy::trivially_copy_pass_by_ref)]
+const fn is_zero(v: &usize) -> bool {
+    *v == 0
+}
+
+/// Which part of a long list of results to show: at most `limit` of them,
+/// after skipping the first `offset`.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ResultPage {
+    /// Results to skip
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub offset: usize,
+
+    /// Most results to send (all of them when not set)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limit: Option<usize>,
+}
+
+impl ResultPage {
+    /// The page `--offset` and `--limit` ask for; a limit of 0 means all.
+    pub fn new(offset: usize, limit: Option<usize>) -> Self {
+        Self { offset, limit: limit.filter(|&n| n > 0) }
+    }
+
+    /// Whether this page is every result.
+    pub const fn is_all(&self) -> bool {
+        self.offset == 0 && self.limit.is_none()
+    }
+
+    /// Where the page after this one starts.
+    pub fn next_offset(&self) -> usize {
+        self.offset + self.limit.unwrap_or(0)
+    }
+
+    /// Cut `items` down to the page, returning how many results follow it.
+    pub fn apply<T>(&self, items: &mut Vec<T>) -> usize {
+        items.drain(..self.offset.min(items.len()));
+        let Some(limit) = self.limit else { return 0 };
+        let remaining = items.len().saturating_sub(limit);
+        items.truncate(limit);
+        remaining
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn apply_keeps_the_page_and_counts_the_rest() {
+        let mut items: Vec<u32> = (0..10).collect();
+        assert_eq!(ResultPage::new(2, Some(3)).apply(&mut items), 5);
+        assert_eq!(items, [2, 3, 4]);
+
+        let mut items: Vec<u32> = (0..10).collect();
+        assert_eq!(ResultPage::new(8, Some(5)).apply(&mut items), 0);
+        assert_eq!(items, [8, 9]);
+
+        let mut items: Vec<u32> = (0..3).collect();
+        assert_eq!(ResultPage::new(5, None).apply(&mut items), 0);
+        assert!(items.is_empty());
+    }
+
+    #[test]
+    fn limit_zero_means_all() {
+        assert!(ResultPage::new(0, Some(0)).is_all());
+        assert!(!ResultPage::new(1, None).is_all());
+        assert_eq!(
+            serde_json::to_value(ResultPage::new(0, Some(0))).unwrap(),
+            serde_json::json!({})
+        );
+    }
+}
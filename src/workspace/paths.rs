@@ -0,0 +1,165 @@
+//! Symlink-aware path normalization.
+//!
+//! ty reports canonical (symlink-resolved) paths, while users often work in a
+//! symlinked checkout. tyf resolves symlinks once, talks to the daemon and ty
+//! only in canonical paths, and maps results back to the user's spelling for
+//! display.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use anyhow::{Context, Result};
+
+use crate::lsp::protocol::Location;
+use crate::lsp::uri::{self, path_to_uri, uri_to_path};
+
+/// The current directory as the user sees it.
+///
+/// `std::env::current_dir` resolves symlinks; the shell's `$PWD` keeps them.
+/// `$PWD` is used when it still points at the same directory.
+pub fn logical_cwd() -> Result<PathBuf> {
+    let physical = std::env::current_dir().context("Failed to get current directory")?;
+    let logical = std::env::var_os("PWD").map(PathBuf::from).filter(|pwd| {
+        pwd.is_absolute()
+            && pwd.canonicalize().ok().as_deref() == physical.canonicalize().ok().as_deref()
+    });
+    Ok(logical.unwrap_or(physical))
+}
+
+/// Maps paths between the workspace root as the user spelled it and its
+/// canonical form.
+#[derive(Debug, Clone)]
+pub struct PathMapper {
+    canonical_root: PathBuf,
+    user_root: PathBuf,
+}
+
+impl PathMapper {
+    /// Build a mapper for `user_root` (absolute, possibly through symlinks).
+    pub fn new(user_root: &Path) -> Result<Self> {
+        let canonical_root =
+            uri::canonicalize(user_root).context("Failed to canonicalize workspace path")?;
+        Ok(Self { canonical_root, user_root: user_root.to_path_buf() })
+    }
+
+    pub fn canonical_root(&self) -> &Path {
+        &self.canonical_root
+    }
+
+    /// The user's spelling of a canonical path; paths outside the workspace
+    /// root are returned unchanged.
+    pub fn to_user(&self, path: &Path) -> PathBuf {
+        match path.strip_prefix(&self.canonical_root) {
+            Ok(rel) if self.user_root != self.canonical_root => self.user_root.join(rel),
+            _ => path.to_path_buf(),
+        }
+    }
+
+    /// The user's spelling of the path behind a `file://` URI.
+    pub fn uri_to_user_path(&self, uri: &str) -> String {
+        self.to_user(Path::new(&uri_to_path(uri))).to_string_lossy().into_owned()
+    }
+}
+
+/// Rewrites `file://` URIs to their canonical form, resolving each path once.
+///
+/// ty can report the same file under different spellings when the project is
+/// reached through a symlink; normalizing keeps dedup and relative paths working.
+#[derive(Default)]
+pub struct UriNormalizer {
+    resolved: Mutex<HashMap<String, String>>,
+}
+
+impl UriNormalizer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Canonical form of `uri`; non-file URIs and missing files are returned as-is.
+    pub fn normalize(&self, uri: &str) -> String {
+        if !uri.starts_with("file://") {
+            return uri.to_string();
+        }
+        if let Some(hit) = self.resolved.lock().expect("uri cache mutex poisoned").get(uri) {
+            return hit.clone();
+        }
+        let normalized = uri::canonicalize(Path::new(&uri_to_path(uri)))
+            .map_or_else(|_| uri.to_string(), |p| path_to_uri(&p));
+        self.resolved
+            .lock()
+            .expect("uri cache mutex poisoned")
+            .insert(uri.to_string(), normalized.clone());
+        normalized
+    }
+
+    /// Normalize the URI of every location in place.
+    pub fn normalize_locations(&self, locations: &mut [Location]) {
+        for loc in locations {
+            loc.uri = self.normalize(&loc.uri);
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::lsp::protocol::{Position, Range};
+
+    /// A real project dir plus a symlink pointing at it.
+    fn symlinked_project() -> (tempfile::TempDir, PathBuf, PathBuf) {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let real = dir.path().join("real");
+        std::fs::create_dir_all(real.join("pkg")).expect("Failed to create dir");
+        std::fs::write(real.join("pkg/app.py"), "x = 1\n").expect("Failed to write file");
+        let link = dir.path().join("link");
+        std::os::unix::fs::symlink(&real, &link).expect("Failed to create symlink");
+        let real = real.canonicalize().expect("Failed to canonicalize");
+        (dir, real, link)
+    }
+
+    #[test]
+    fn test_mapper_maps_canonical_paths_to_symlinked_spelling() {
+        let (_dir, real, link) = symlinked_project();
+        let mapper = PathMapper::new(&link).unwrap();
+
+        assert_eq!(mapper.canonical_root(), real);
+        assert_eq!(mapper.to_user(&real.join("pkg/app.py")), link.join("pkg/app.py"));
+        let uri = format!("file://{}", real.join("pkg/app.py").display());
+        assert_eq!(mapper.uri_to_user_path(&uri), link.join("pkg/app.py").to_string_lossy());
+    }
+
+    #[test]
+    fn test_mapper_leaves_outside_paths_alone() {
+        let (_dir, _real, link) = symlinked_project();
+        let mapper = PathMapper::new(&link).unwrap();
+
+        let outside = Path::new("/usr/lib/python3.12/os.py");
+        assert_eq!(mapper.to_user(outside), outside);
+        assert_eq!(
+            mapper.uri_to_user_path("file:///usr/lib/python3.12/os.py"),
+            "/usr/lib/python3.12/os.py"
+        );
+    }
+
+    #[test]
+    fn test_uri_normalizer_resolves_symlinked_spelling() {
+        let (_dir, real, link) = symlinked_project();
+        let normalizer = UriNormalizer::new();
+        let pos = Position { line: 0, character: 0 };
+        let mut locations = vec![
+            Location {
+                uri: format!("file://{}", link.join("pkg/app.py").display()),
+                range: Range { start: pos.clone(), end: pos.clone() },
+            },
+            Location {
+                uri: "untitled:Untitled-1".to_string(),
+                range: Range { start: pos.clone(), end: pos },
+            },
+        ];
+        normalizer.normalize_locations(&mut locations);
+
+        assert_eq!(locations[0].uri, format!("file://{}", real.join("pkg/app.py").display()));
+        assert_eq!(locations[1].uri, "untitled:Untitled-1");
+    }
+}
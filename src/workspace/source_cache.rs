@@ -0,0 +1,166 @@
+//! Source files read once per command and indexed by line.
+//!
+//! Formatting a list of results looks up the line of every result, and a
+//! file with many results would otherwise be read, or scanned for line
+//! breaks, once per result. A [`SourceCache`] reads each file once and keeps
+//! where its lines start, so any line is a slice away.
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::lsp::uri::uri_to_path;
+
+/// The text of one source file and the byte offset each of its lines starts at.
+pub struct SourceFile {
+    text: String,
+    line_starts: Vec<usize>,
+}
+
+impl SourceFile {
+    /// Index `text` that is already in memory.
+    pub fn new(text: String) -> Self {
+        let line_starts = (!text.is_empty())
+            .then_some(0)
+            .into_iter()
+            .chain(
+                text.bytes()
+                    .enumerate()
+                    .filter(|&(i, byte)| byte == b'\n' && i + 1 < text.len())
+                    .map(|(i, _)| i + 1),
+            )
+            .collect();
+        Self { text, line_starts }
+    }
+
+    /// Read the file at `path`; `None` if it can't be read or isn't UTF-8.
+    pub fn read(path: &Path) -> Option<Self> {
+        std::fs::read_to_string(path).ok().map(Self::new)
+    }
+
+    /// The whole file.
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// How many lines the file has, counted like [`str::lines`] counts them.
+    pub fn line_count(&self) -> usize {
+        self.line_starts.len()
+    }
+
+    /// The 0-based line `n` without its line ending, like the `n`th item of
+    /// [`str::lines`], but without scanning the lines before it.
+    pub fn line(&self, n: usize) -> Option<&str> {
+        let start = *self.line_starts.get(n)?;
+        let end = self.line_starts.get(n + 1).copied().unwrap_or(self.text.len());
+        let line = &self.text[start..end];
+        Some(line.strip_suffix('\n').map_or(line, |line| line.strip_suffix('\r').unwrap_or(line)))
+    }
+
+    /// Every line of the file, as [`str::lines`] gives them.
+    pub fn lines(&self) -> impl Iterator<Item = &str> {
+        self.lines_from(0).map(|(_, line)| line)
+    }
+
+    /// The lines from the 0-based line `n` on, each with its number.
+    pub fn lines_from(&self, n: usize) -> impl Iterator<Item = (usize, &str)> {
+        (n..self.line_count()).filter_map(|n| Some((n, self.line(n)?)))
+    }
+}
+
+/// Source files for formatting results, each read once.
+///
+/// Built asynchronously (via a blocking task) in command handlers, then
+/// passed into synchronous formatters so they never block the async runtime
+/// on file I/O.
+#[derive(Default)]
+pub struct SourceCache {
+    files: HashMap<String, SourceFile>,
+}
+
+impl SourceCache {
+    /// An empty cache: every lookup misses.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Read all files referenced by the given `file://` URIs.
+    ///
+    /// Deduplicates paths and silently skips files that cannot be read.
+    pub async fn from_uris<'a>(uris: impl IntoIterator<Item = &'a str>) -> Self {
+        let mut paths: Vec<String> =
+            uris.into_iter().filter(|uri| uri.starts_with("file://")).map(uri_to_path).collect();
+        paths.sort();
+        paths.dedup();
+        let read = tokio::task::spawn_blocking(move || {
+            paths
+                .into_iter()
+                .filter_map(|path| SourceFile::read(Path::new(&path)).map(|file| (path, file)))
+                .collect()
+        });
+        Self { files: read.await.unwrap_or_default() }
+    }
+
+    /// A cache of files whose text is already known, by absolute path.
+    pub fn from_entries(entries: impl IntoIterator<Item = (String, String)>) -> Self {
+        Self {
+            files: entries.into_iter().map(|(path, text)| (path, SourceFile::new(text))).collect(),
+        }
+    }
+
+    /// The file at an absolute path, if cached.
+    pub fn get(&self, file_path: &str) -> Option<&SourceFile> {
+        self.files.get(file_path)
+    }
+
+    /// The file behind a `file://` URI (or plain path), if cached.
+    pub fn get_uri(&self, uri: &str) -> Option<&SourceFile> {
+        self.get(&uri_to_path(uri))
+    }
+
+    /// Content of the file at an absolute path, if cached.
+    pub fn get_content(&self, file_path: &str) -> Option<&str> {
+        self.get(file_path).map(SourceFile::text)
+    }
+
+    /// Content of the file behind a `file://` URI (or plain path), if cached.
+    pub fn get_uri_content(&self, uri: &str) -> Option<&str> {
+        self.get_uri(uri).map(SourceFile::text)
+    }
+
+    /// The 0-based line `n` of the file behind a `file://` URI, if cached.
+    pub fn get_uri_line(&self, uri: &str, n: u32) -> Option<&str> {
+        self.get_uri(uri)?.line(n as usize)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lines_match_str_lines() {
+        for text in ["", "\n", "a", "a\n", "a\r\nb", "a\n\nb\n", "é\r\n😀 x\n\n", "a\rb\n"] {
+            let file = SourceFile::new(text.to_string());
+            assert_eq!(
+                file.lines().collect::<Vec<_>>(),
+                text.lines().collect::<Vec<_>>(),
+                "{text:?}"
+            );
+            assert_eq!(file.line_count(), text.lines().count(), "{text:?}");
+            assert_eq!(file.line(file.line_count()), None);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_from_uris_skips_unreadable_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("app.py");
+        std::fs::write(&path, "import os\n\ndef main():\n    pass\n").unwrap();
+        let uri = crate::lsp::uri::path_to_uri(&path);
+        let missing = crate::lsp::uri::path_to_uri(&dir.path().join("missing.py"));
+        let cache = SourceCache::from_uris([uri.as_str(), missing.as_str(), uri.as_str()]).await;
+        assert_eq!(cache.get_uri_line(&uri, 2), Some("def main():"));
+        assert_eq!(cache.get_uri_line(&uri, 4), None);
+        assert!(cache.get_uri(&missing).is_none());
+    }
+}
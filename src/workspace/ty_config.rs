@@ -0,0 +1,414 @@
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+
+use crate::config::Config;
+
+/// The subset of ty's project configuration that affects what tyf considers
+/// part of the project.
+///
+/// Read from `ty.toml` (top-level keys) or `pyproject.toml` (`[tool.ty]`),
+/// with `ty.toml` taking precedence, the same way `ty check` resolves it.
+/// The `exclude` patterns in tyf's own configuration are added to
+/// `src.exclude`, so ty leaves those paths out as well.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TyConfig {
+    /// First-party search roots (`environment.root`, or the legacy `src.root`)
+    pub src_roots: Vec<String>,
+    /// `src.include` patterns
+    pub include: Vec<String>,
+    /// `src.exclude` patterns
+    pub exclude: Vec<String>,
+    /// `environment.python-version`, e.g. `"3.12"`
+    pub python_version: Option<String>,
+    /// `environment.python`: the Python environment (or interpreter) whose
+    /// installed packages ty resolves imports from
+    pub python: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct RawOptions {
+    #[serde(default)]
+    src: RawSrc,
+    #[serde(default)]
+    environment: RawEnvironment,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct RawSrc {
+    root: Option<String>,
+    #[serde(default)]
+    include: Vec<String>,
+    #[serde(default)]
+    exclude: Vec<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct RawEnvironment {
+    root: Option<OneOrMany>,
+    python_version: Option<String>,
+    python: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum OneOrMany {
+    One(String),
+    Many(Vec<String>),
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct RawPyproject {
+    #[serde(default)]
+    tool: RawTool,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct RawTool {
+    ty: Option<RawOptions>,
+}
+
+impl TyConfig {
+    /// Load the ty configuration for `workspace_root`.
+    ///
+    /// Returns the default (empty) config when neither `ty.toml` nor a
+    /// `[tool.ty]` table exists. Malformed files are reported as errors.
+    pub fn load(workspace_root: &Path) -> Result<Self> {
+        let mut config = Self::load_ty(workspace_root)?;
+        config.exclude.extend(Config::load_or_default(workspace_root).exclude());
+        Ok(config)
+    }
+
+    fn load_ty(workspace_root: &Path) -> Result<Self> {
+        let ty_toml = workspace_root.join("ty.toml");
+        if ty_toml.is_file() {
+            let text = std::fs::read_to_string(&ty_toml)
+                .with_context(|| format!("Failed to read {}", ty_toml.display()))?;
+            let raw: RawOptions = toml::from_str(&text)
+                .with_context(|| format!("Failed to parse {}", ty_toml.display()))?;
+            return Ok(Self::from_raw(raw));
+        }
+
+        let pyproject = workspace_root.join("pyproject.toml");
+        if pyproject.is_file() {
+            let text = std::fs::read_to_string(&pyproject)
+                .with_context(|| format!("Failed to read {}", pyproject.display()))?;
+            let raw: RawPyproject = toml::from_str(&text)
+                .with_context(|| format!("Failed to parse {}", pyproject.display()))?;
+            return Ok(raw.tool.ty.map(Self::from_raw).unwrap_or_default());
+        }
+
+        Ok(Self::default())
+    }
+
+    /// Like [`TyConfig::load`], but falls back to the default config (and logs
+    /// why) so a broken `pyproject.toml` never stops a lookup.
+    pub fn load_or_default(workspace_root: &Path) -> Self {
+        Self::load(workspace_root).unwrap_or_else(|e| {
+            tracing::warn!("Ignoring ty configuration: {e:#}");
+            Self::default()
+        })
+    }
+
+    fn from_raw(raw: RawOptions) -> Self {
+        let src_roots = match raw.environment.root {
+            Some(OneOrMany::One(root)) => vec![root],
+            Some(OneOrMany::Many(roots)) => roots,
+            None => raw.src.root.into_iter().collect(),
+        };
+        Self {
+            src_roots,
+            include: raw.src.include,
+            exclude: raw.src.exclude,
+            python_version: raw.environment.python_version,
+            python: raw.environment.python,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// The `configuration` object sent in the LSP `initializationOptions`.
+    ///
+    /// Without an explicit `src.include` we still pass `["**"]` so ty indexes
+    /// the whole workspace rather than only what it auto-detects as source.
+    pub fn lsp_configuration(&self) -> serde_json::Value {
+        let include =
+            if self.include.is_empty() { vec!["**".to_string()] } else { self.include.clone() };
+        let mut src = serde_json::json!({ "include": include });
+        if !self.exclude.is_empty() {
+            src["exclude"] = serde_json::json!(self.exclude);
+        }
+
+        let mut configuration = serde_json::json!({ "src": src });
+        let mut environment = serde_json::Map::new();
+        if !self.src_roots.is_empty() {
+            environment.insert("root".to_string(), serde_json::json!(self.src_roots));
+        }
+        if let Some(version) = &self.python_version {
+            environment.insert("python-version".to_string(), serde_json::json!(version));
+        }
+        if let Some(python) = &self.python {
+            environment.insert("python".to_string(), serde_json::json!(python));
+        }
+        if !environment.is_empty() {
+            configuration["environment"] = serde_json::Value::Object(environment);
+        }
+        configuration
+    }
+
+    /// Ripgrep `--glob` arguments mirroring `src.exclude`.
+    pub fn rg_exclude_globs(&self) -> Vec<String> {
+        self.exclude.iter().map(|pattern| format!("!{}", pattern.trim_start_matches('/'))).collect()
+    }
+
+    /// Whether `relative` (a path under the workspace root) matches `src.exclude`.
+    ///
+    /// Patterns follow gitignore rules: a leading `/` or an inner `/` anchors the
+    /// pattern at the root, otherwise it may match at any depth; `*` and `?`
+    /// stay within a path segment and `**` spans any number of segments. A
+    /// match on a directory excludes everything below it.
+    #[cfg_attr(not(unix), allow(dead_code))]
+    pub fn is_excluded(&self, relative: &Path) -> bool {
+        let segments = path_segments(relative);
+        let segments: Vec<&str> = segments.iter().map(String::as_str).collect();
+        // `!pattern` re-includes paths; the index errs on the side of scanning them.
+        self.exclude
+            .iter()
+            .filter(|pattern| !pattern.starts_with('!'))
+            .any(|pattern| gitignore_match(pattern, &segments))
+    }
+
+    /// Whether `relative` falls under `src.include` (everything does when it is unset).
+    ///
+    /// Include patterns are always anchored at the root; a pattern naming a
+    /// directory includes everything below it.
+    #[cfg_attr(not(unix), allow(dead_code))]
+    pub fn is_included(&self, relative: &Path) -> bool {
+        if self.include.is_empty() {
+            return true;
+        }
+        let segments = path_segments(relative);
+        let segments: Vec<&str> = segments.iter().map(String::as_str).collect();
+        self.include.iter().any(|pattern| {
+            let parts: Vec<&str> =
+                pattern.split('/').filter(|p| !p.is_empty() && *p != ".").collect();
+            (1..=segments.len()).any(|len| glob_segments_match(&parts, &segments[..len]))
+        })
+    }
+
+    /// One-line summary for debug logging.
+    pub fn describe(&self) -> String {
+        if self.is_empty() {
+            return "none".to_string();
+        }
+        let list = |items: &[String]| {
+            if items.is_empty() {
+                "-".to_string()
+            } else {
+                items.join(", ")
+            }
+        };
+        format!(
+            "roots: {}; include: {}; exclude: {}; python-version: {}; python: {}",
+            list(&self.src_roots),
+            list(&self.include),
+            list(&self.exclude),
+            self.python_version.as_deref().unwrap_or("-"),
+            self.python.as_deref().unwrap_or("-"),
+        )
+    }
+}
+
+pub fn path_segments(path: &Path) -> Vec<String> {
+    path.components().map(|c| c.as_os_str().to_string_lossy().into_owned()).collect()
+}
+
+/// Whether a gitignore-style `pattern` matches the path `segments` or one of
+/// its parent directories.
+///
+/// A leading `/` or an inner `/` anchors the pattern at the first segment,
+/// otherwise it may match at any depth.
+pub fn gitignore_match(pattern: &str, segments: &[&str]) -> bool {
+    let trimmed = pattern.trim_end_matches('/');
+    let anchored = trimmed.starts_with('/') || trimmed.contains('/');
+    let mut parts: Vec<&str> =
+        trimmed.trim_start_matches('/').split('/').filter(|p| !p.is_empty()).collect();
+    if !anchored {
+        parts.insert(0, "**");
+    }
+    // Match the pattern against every prefix so directories match their contents.
+    (1..=segments.len()).any(|len| glob_segments_match(&parts, &segments[..len]))
+}
+
+/// Match path segments against glob segments, with `**` spanning segments.
+pub fn glob_segments_match(pattern: &[&str], path: &[&str]) -> bool {
+    match pattern.split_first() {
+        None => path.is_empty(),
+        Some((&"**", rest)) => {
+            (0..=path.len()).any(|skip| glob_segments_match(rest, &path[skip..]))
+        }
+        Some((first, rest)) => match path.split_first() {
+            Some((segment, path_rest)) => {
+                glob_segment_match(first.as_bytes(), segment.as_bytes())
+                    && glob_segments_match(rest, path_rest)
+            }
+            None => false,
+        },
+    }
+}
+
+/// Match one path segment against a glob with `*` and `?`.
+fn glob_segment_match(pattern: &[u8], text: &[u8]) -> bool {
+    match pattern.split_first() {
+        None => text.is_empty(),
+        Some((b'*', rest)) => (0..=text.len()).any(|skip| glob_segment_match(rest, &text[skip..])),
+        Some((b'?', rest)) => !text.is_empty() && glob_segment_match(rest, &text[1..]),
+        Some((c, rest)) => text.first() == Some(c) && glob_segment_match(rest, &text[1..]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(dir: &Path, name: &str, content: &str) {
+        std::fs::write(dir.join(name), content).unwrap();
+    }
+
+    #[test]
+    fn test_load_without_config_is_empty() {
+        let dir = tempfile::tempdir().unwrap();
+        write(dir.path(), "pyproject.toml", "[project]\nname = \"demo\"\n");
+        let config = TyConfig::load(dir.path()).unwrap();
+        assert!(config.is_empty());
+        assert_eq!(config.describe(), "none");
+    }
+
+    #[test]
+    fn test_load_tool_ty_from_pyproject() {
+        let dir = tempfile::tempdir().unwrap();
+        write(
+            dir.path(),
+            "pyproject.toml",
+            r#"
+[tool.ty.src]
+include = ["src", "tests"]
+exclude = ["src/generated"]
+
+[tool.ty.environment]
+root = ["./src"]
+python-version = "3.11"
+"#,
+        );
+        let config = TyConfig::load(dir.path()).unwrap();
+        assert_eq!(config.src_roots, vec!["./src"]);
+        assert_eq!(config.include, vec!["src", "tests"]);
+        assert_eq!(config.exclude, vec!["src/generated"]);
+        assert_eq!(config.python_version.as_deref(), Some("3.11"));
+    }
+
+    #[test]
+    fn test_load_adds_tyf_excludes() {
+        let dir = tempfile::tempdir().unwrap();
+        write(dir.path(), "pyproject.toml", "[tool.ty.src]\nexclude = [\"src/generated\"]\n");
+        write(dir.path(), ".ty-find.toml", "exclude = [\"build\"]\n");
+        let config = TyConfig::load(dir.path()).unwrap();
+        assert_eq!(config.exclude.first().map(String::as_str), Some("src/generated"));
+        assert!(config.exclude.iter().any(|pattern| pattern == "build"));
+        assert!(config.is_excluded(Path::new("build/lib/app.py")));
+    }
+
+    #[test]
+    fn test_ty_toml_takes_precedence() {
+        let dir = tempfile::tempdir().unwrap();
+        write(dir.path(), "pyproject.toml", "[tool.ty.environment]\npython-version = \"3.9\"\n");
+        write(dir.path(), "ty.toml", "[environment]\npython-version = \"3.13\"\n");
+        let config = TyConfig::load(dir.path()).unwrap();
+        assert_eq!(config.python_version.as_deref(), Some("3.13"));
+    }
+
+    #[test]
+    fn test_legacy_src_root() {
+        let dir = tempfile::tempdir().unwrap();
+        write(dir.path(), "ty.toml", "[src]\nroot = \"lib\"\n");
+        let config = TyConfig::load(dir.path()).unwrap();
+        assert_eq!(config.src_roots, vec!["lib"]);
+    }
+
+    #[test]
+    fn test_malformed_pyproject_is_error() {
+        let dir = tempfile::tempdir().unwrap();
+        write(dir.path(), "pyproject.toml", "[tool.ty\n");
+        assert!(TyConfig::load(dir.path()).is_err());
+        assert!(TyConfig::load_or_default(dir.path()).is_empty());
+    }
+
+    #[test]
+    fn test_lsp_configuration_defaults_to_full_include() {
+        let configuration = TyConfig::default().lsp_configuration();
+        assert_eq!(configuration, serde_json::json!({ "src": { "include": ["**"] } }));
+    }
+
+    #[test]
+    fn test_lsp_configuration_passes_project_settings() {
+        let config = TyConfig {
+            src_roots: vec!["src".to_string()],
+            include: vec!["src".to_string()],
+            exclude: vec!["src/generated".to_string()],
+            python_version: Some("3.12".to_string()),
+            python: Some("/proj/.venv".to_string()),
+        };
+        let configuration = config.lsp_configuration();
+        assert_eq!(configuration["src"]["include"], serde_json::json!(["src"]));
+        assert_eq!(configuration["src"]["exclude"], serde_json::json!(["src/generated"]));
+        assert_eq!(configuration["environment"]["root"], serde_json::json!(["src"]));
+        assert_eq!(configuration["environment"]["python-version"], "3.12");
+        assert_eq!(configuration["environment"]["python"], "/proj/.venv");
+    }
+
+    #[test]
+    fn test_is_excluded_follows_gitignore_rules() {
+        let config = TyConfig {
+            exclude: vec![
+                "/build".to_string(),
+                "**/migrations".to_string(),
+                "generated/".to_string(),
+                "src/*_pb2.py".to_string(),
+            ],
+            ..TyConfig::default()
+        };
+        assert!(config.is_excluded(Path::new("build/lib/x.py")));
+        assert!(!config.is_excluded(Path::new("pkg/build/x.py")), "leading / anchors at the root");
+        assert!(config.is_excluded(Path::new("app/migrations/0001.py")));
+        assert!(config.is_excluded(Path::new("deep/generated/models.py")));
+        assert!(config.is_excluded(Path::new("src/api_pb2.py")));
+        assert!(!config.is_excluded(Path::new("src/nested/api_pb2.py")));
+        assert!(!config.is_excluded(Path::new("src/app.py")));
+    }
+
+    #[test]
+    fn test_is_included_anchors_at_root() {
+        assert!(TyConfig::default().is_included(Path::new("any/file.py")));
+        let config = TyConfig {
+            include: vec!["src".to_string(), "tests/*.py".to_string()],
+            ..TyConfig::default()
+        };
+        assert!(config.is_included(Path::new("src/pkg/mod.py")));
+        assert!(config.is_included(Path::new("tests/test_a.py")));
+        assert!(!config.is_included(Path::new("lib/src/mod.py")));
+        assert!(!config.is_included(Path::new("tests/sub/test_b.py")));
+    }
+
+    #[test]
+    fn test_rg_exclude_globs() {
+        let config = TyConfig {
+            exclude: vec!["/build".to_string(), "**/migrations".to_string()],
+            ..TyConfig::default()
+        };
+        assert_eq!(config.rg_exclude_globs(), vec!["!build", "!**/migrations"]);
+    }
+}
@@ -0,0 +1,178 @@
+//! The Python files of a workspace, as ty sees them.
+//!
+//! Everything that scans the workspace (the daemon's symbol index, `watch`,
+//! `check`, `bench` and the commands that look through every file) walks it
+//! with a [`WorkspaceWalker`], so they all agree on what is part of the
+//! project: hidden files and directories, virtualenvs and the like are
+//! skipped, as is anything git ignores or `src.include` / `src.exclude`
+//! leave out (the latter including tyf's own `exclude` setting).
+
+use std::cmp::Ordering;
+use std::ops::ControlFlow;
+use std::path::{Path, PathBuf};
+
+use ignore::WalkBuilder;
+
+use super::ty_config::TyConfig;
+
+/// Directory names ty never analyses, in addition to hidden directories.
+const DEFAULT_EXCLUDED_DIRS: &[&str] = &[
+    "__pycache__",
+    "__pypackages__",
+    "_build",
+    "buck-out",
+    "dist",
+    "node_modules",
+    "site-packages",
+    "venv",
+];
+
+/// Walks the `.py` and `.pyi` files under a workspace root that ty would
+/// analyse.
+pub struct WorkspaceWalker<'a> {
+    root: &'a Path,
+    config: &'a TyConfig,
+}
+
+impl<'a> WorkspaceWalker<'a> {
+    pub const fn new(root: &'a Path, config: &'a TyConfig) -> Self {
+        Self { root, config }
+    }
+
+    /// Call `visit` with each Python file until it breaks: a directory's
+    /// files first, in name order, then its subdirectories.
+    ///
+    /// Ignore rules are read the way git reads them: `.gitignore` files,
+    /// `.git/info/exclude` and the global excludes file, plus `.ignore`
+    /// files, as ty does. They apply even outside a git repository.
+    /// Symlinks are not followed, so linked directories (and any cycles
+    /// through them) are skipped.
+    pub fn walk(&self, mut visit: impl FnMut(&Path) -> ControlFlow<()>) {
+        let root = self.root.to_path_buf();
+        let config = self.config.clone();
+        let walk = WalkBuilder::new(self.root)
+            .require_git(false)
+            .sort_by_file_path(files_first)
+            .filter_entry(move |entry| {
+                let is_dir = entry.file_type().is_some_and(|t| t.is_dir());
+                entry.depth() == 0 || !is_dir || walks_into(&root, &config, entry.path())
+            })
+            .build();
+        for entry in walk.flatten() {
+            if entry.file_type().is_some_and(|t| t.is_file())
+                && self.is_python_file(entry.path())
+                && visit(entry.path()).is_break()
+            {
+                return;
+            }
+        }
+    }
+
+    /// Every Python file, in sorted order.
+    pub fn python_files(&self) -> Vec<PathBuf> {
+        let mut files = Vec::new();
+        self.walk(|path| {
+            files.push(path.to_path_buf());
+            ControlFlow::Continue(())
+        });
+        files.sort();
+        files
+    }
+
+    fn is_python_file(&self, file: &Path) -> bool {
+        let relative = file.strip_prefix(self.root).unwrap_or(file);
+        file.extension().is_some_and(|ext| ext == "py" || ext == "pyi")
+            && self.config.is_included(relative)
+            && !self.config.is_excluded(relative)
+    }
+}
+
+/// Order the entries of one directory: files before subdirectories, each
+/// by name.
+fn files_first(a: &Path, b: &Path) -> Ordering {
+    a.is_dir().cmp(&b.is_dir()).then_with(|| a.file_name().cmp(&b.file_name()))
+}
+
+fn walks_into(root: &Path, config: &TyConfig, dir: &Path) -> bool {
+    let name = dir.file_name().unwrap_or_default().to_string_lossy();
+    let relative = dir.strip_prefix(root).unwrap_or(dir);
+    !(DEFAULT_EXCLUDED_DIRS.contains(&name.as_ref()) || config.is_excluded(relative))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn relative_names(root: &Path, files: &[PathBuf]) -> Vec<PathBuf> {
+        files.iter().map(|f| f.strip_prefix(root).expect("under root").to_path_buf()).collect()
+    }
+
+    #[test]
+    fn test_python_files_respects_gitignore() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let root = dir.path();
+        fs::create_dir_all(root.join("pkg/generated")).expect("mkdir");
+        fs::write(root.join(".gitignore"), "scratch_*.py\n").expect("write");
+        fs::write(root.join("pkg/.gitignore"), "generated/\n").expect("write");
+        for file in ["app.py", "scratch_1.py", "pkg/models.py", "pkg/generated/api.py"] {
+            fs::write(root.join(file), "x = 1\n").expect("write");
+        }
+
+        let files = WorkspaceWalker::new(root, &TyConfig::default()).python_files();
+        assert_eq!(
+            relative_names(root, &files),
+            [PathBuf::from("app.py"), PathBuf::from("pkg/models.py")]
+        );
+    }
+
+    #[test]
+    fn test_python_files_respects_git_info_exclude() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let root = dir.path();
+        fs::create_dir_all(root.join(".git/info")).expect("mkdir");
+        fs::write(root.join(".git/info/exclude"), "local_*.py\n").expect("write");
+        for file in ["app.py", "local_notes.py"] {
+            fs::write(root.join(file), "x = 1\n").expect("write");
+        }
+
+        let files = WorkspaceWalker::new(root, &TyConfig::default()).python_files();
+        assert_eq!(relative_names(root, &files), [PathBuf::from("app.py")]);
+    }
+
+    #[test]
+    fn test_walk_is_breadth_first_and_stops_on_break() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let root = dir.path();
+        for sub in ["a", "node_modules/lib", ".venv/lib", "tests"] {
+            fs::create_dir_all(root.join(sub)).expect("mkdir");
+        }
+        let files = [
+            "z.py",
+            "a/deep.py",
+            "b.pyi",
+            "node_modules/lib/x.py",
+            ".venv/lib/y.py",
+            "tests/test_z.py",
+            "notes.txt",
+        ];
+        for file in files {
+            fs::write(root.join(file), "x = 1\n").expect("write");
+        }
+        let config = TyConfig { exclude: vec!["tests".to_string()], ..TyConfig::default() };
+
+        let mut seen = Vec::new();
+        WorkspaceWalker::new(root, &config).walk(|path| {
+            seen.push(path.to_path_buf());
+            if seen.len() == 3 {
+                ControlFlow::Break(())
+            } else {
+                ControlFlow::Continue(())
+            }
+        });
+        assert_eq!(
+            relative_names(root, &seen),
+            [PathBuf::from("b.pyi"), PathBuf::from("z.py"), PathBuf::from("a/deep.py")]
+        );
+    }
+}
@@ -59,6 +59,98 @@ async fn test_json_output() {
     assert!(predicate::str::contains("range").eval(&stdout));
 }
 
+#[tokio::test]
+async fn test_json_errors_carry_a_code_and_exit_status() {
+    // Usage errors are caught before ty is needed.
+    let mut cmd = cargo_bin_cmd!("tyf");
+    cmd.args(["--format", "json", "no-such-command"]);
+    let output = cmd.output().expect("failed to run tyf");
+    assert_eq!(output.status.code(), Some(2));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let report: serde_json::Value = serde_json::from_str(stderr.trim()).expect("JSON on stderr");
+    assert_eq!(report["error"]["code"], "usage");
+    assert!(report["error"]["message"].as_str().is_some_and(|m| m.contains("no-such-command")));
+
+    let mut cmd = cargo_bin_cmd!("tyf");
+    cmd.arg("--workspace").arg(workspace_root()).args(["--watch", "daemon", "status"]);
+    let output = cmd.output().expect("failed to run tyf");
+    assert_eq!(output.status.code(), Some(2));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.starts_with("Error: --watch re-runs queries"), "got: {stderr}");
+}
+
+#[tokio::test]
+async fn test_config_show_merges_files_and_flags() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(
+        dir.path().join("pyproject.toml"),
+        "[project]\nname = \"demo\"\n\n[tool.ty-find]\ntimeout = 20\nbackend = \"pylsp\"\n",
+    )
+    .unwrap();
+    std::fs::write(
+        dir.path().join(".ty-find.toml"),
+        "format = \"json\"\nexclude = [\"build\"]\n[aliases]\ncs = \"config show\"\n",
+    )
+    .unwrap();
+
+    // Run through the alias, so the workspace's `format = "json"` applies.
+    let mut cmd = cargo_bin_cmd!("tyf");
+    cmd.current_dir(dir.path())
+        .env("TYF_CONFIG", dir.path().join("no-user-config.toml"))
+        .env_remove("TYF_BACKEND")
+        .args(["--timeout", "7", "cs"]);
+    let output = cmd.output().expect("failed to run tyf");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "config show failed: {stdout}");
+    let envelope: serde_json::Value = serde_json::from_str(&stdout).expect("JSON output");
+    assert_eq!(envelope["command"], "config show");
+    let report = &envelope["results"][0];
+    let setting = |key: &str| {
+        report["settings"].as_array().unwrap().iter().find(|s| s["key"] == key).unwrap().clone()
+    };
+    assert_eq!(setting("format")["value"], "json");
+    assert!(setting("format")["source"].as_str().unwrap().ends_with(".ty-find.toml"));
+    assert_eq!(setting("timeout")["value"], 7);
+    assert_eq!(setting("timeout")["source"], "command line");
+    assert_eq!(setting("backend")["value"], "pylsp");
+    assert!(setting("backend")["source"].as_str().unwrap().ends_with("[tool.ty-find]"));
+    assert_eq!(setting("idle-timeout")["source"], "default");
+    assert_eq!(setting("exclude")["value"], serde_json::json!(["build"]));
+    assert_eq!(setting("aliases.cs")["value"], "config show");
+    assert_eq!(report["files"][0]["status"], "not found");
+    assert_eq!(report["files"][1]["status"], "read");
+
+    // A bad value is a usage error naming the file.
+    std::fs::write(dir.path().join(".ty-find.toml"), "timeout = \"soon\"\n").unwrap();
+    let mut cmd = cargo_bin_cmd!("tyf");
+    cmd.current_dir(dir.path()).args(["config", "show"]);
+    let output = cmd.output().expect("failed to run tyf");
+    assert_eq!(output.status.code(), Some(2));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains(".ty-find.toml"), "got: {stderr}");
+}
+
+#[tokio::test]
+async fn test_verbose_reports_detected_workspace_root() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(dir.path().join("pyproject.toml"), "[project]\nname = \"demo\"\n").unwrap();
+    let sub = dir.path().join("pkg").join("sub");
+    std::fs::create_dir_all(&sub).unwrap();
+
+    let mut cmd = cargo_bin_cmd!("tyf");
+    cmd.current_dir(&sub)
+        .env("TYF_CONFIG", dir.path().join("no-user-config.toml"))
+        .args(["-v", "config", "show"]);
+    let output = cmd.output().expect("failed to run tyf");
+    assert!(output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let root = dir.path().canonicalize().unwrap();
+    assert!(
+        stderr.contains(&format!("Workspace root: {} (found pyproject.toml", root.display())),
+        "got: {stderr}"
+    );
+}
+
 #[tokio::test]
 async fn test_show_command_with_file() {
     common::require_ty();
@@ -835,9 +927,9 @@ async fn test_find_nonexistent_symbol_returns_quickly() {
     let output = cmd.output().expect("failed to run tyf");
     let elapsed = start.elapsed();
 
-    // The command should succeed (exit 0) with no results
+    // Finding nothing exits with status 1
     let stdout = String::from_utf8_lossy(&output.stdout);
-    assert!(output.status.success(), "command failed: {stdout}");
+    assert_eq!(output.status.code(), Some(1), "expected no results: {stdout}");
 
     // With rg early termination, this should complete well under 1 second.
     // Without it, the retry chain would take ~3 seconds.
@@ -937,7 +1029,7 @@ async fn test_show_dotted_notation_nonexistent() {
 
     let output = cmd.output().expect("failed to run tyf");
     let stdout = String::from_utf8_lossy(&output.stdout);
-    assert!(output.status.success(), "command failed: {stdout}");
+    assert_eq!(output.status.code(), Some(1), "expected no results: {stdout}");
 
     // Should not find anything
     assert!(
@@ -1022,3 +1114,430 @@ async fn test_show_mixed_dotted_and_bare() {
         "mixed notation should find bare name, got:\n{stdout}"
     );
 }
+
+// ==================== def command tests ====================
+
+#[tokio::test]
+async fn test_def_by_name() {
+    common::require_ty();
+
+    let mut cmd = cargo_bin_cmd!("tyf");
+    cmd.arg("--workspace").arg(workspace_root()).arg("def").arg("Calculator.add");
+
+    let output = cmd.output().expect("failed to run tyf");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "command failed: {stdout}");
+    assert!(
+        predicate::str::contains("example.py:10").eval(&stdout),
+        "def should land on the add method, got:\n{stdout}"
+    );
+}
+
+#[tokio::test]
+async fn test_def_by_position_follows_usage() {
+    common::require_ty();
+
+    // Line 19 calls calculate_sum; its definition is on line 5.
+    let position = format!("{}:19:13", fixture_path().display());
+    let mut cmd = cargo_bin_cmd!("tyf");
+    cmd.arg("--workspace").arg(workspace_root()).arg("def").arg(position);
+
+    let output = cmd.output().expect("failed to run tyf");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "command failed: {stdout}");
+    assert!(
+        predicate::str::contains("example.py:5").eval(&stdout),
+        "def should follow the call to calculate_sum, got:\n{stdout}"
+    );
+}
+
+// ==================== hover command tests ====================
+
+#[tokio::test]
+async fn test_hover_by_name() {
+    common::require_ty();
+
+    let mut cmd = cargo_bin_cmd!("tyf");
+    cmd.arg("--workspace").arg(workspace_root()).arg("hover").arg("calculate_sum");
+
+    let output = cmd.output().expect("failed to run tyf");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "command failed: {stdout}");
+    assert!(
+        predicate::str::contains("def calculate_sum").eval(&stdout),
+        "hover should print the signature, got:\n{stdout}"
+    );
+}
+
+#[tokio::test]
+async fn test_bench_prints_percentiles() {
+    common::require_ty();
+
+    let mut cmd = cargo_bin_cmd!("tyf");
+    cmd.arg("--workspace").arg(workspace_root()).args([
+        "--format",
+        "csv",
+        "bench",
+        "calculate_sum",
+        "--iterations",
+        "2",
+        "--runs",
+        "1",
+    ]);
+
+    let output = cmd.output().expect("failed to run tyf");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "command failed: {stdout}");
+    assert!(
+        stdout.starts_with("benchmark,samples,p50_ms,p90_ms,p99_ms,max_ms\n"),
+        "got:\n{stdout}"
+    );
+    for row in ["cold start,1,", "warm hover,2,", "definition,2,", "100 batched refs,1,"] {
+        assert!(stdout.contains(row), "missing '{row}' in:\n{stdout}");
+    }
+}
+
+#[tokio::test]
+async fn test_grep_keeps_only_matches_of_the_target() {
+    common::require_ty();
+
+    // `def add` also appears in test_project/; only Calculator.add should survive.
+    let mut cmd = cargo_bin_cmd!("tyf");
+    cmd.arg("--workspace").arg(workspace_root()).args([
+        "--format",
+        "csv",
+        "grep",
+        "add",
+        "--resolves-to",
+        "Calculator.add",
+    ]);
+
+    let output = cmd.output().expect("failed to run tyf");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "command failed: {stdout}");
+    assert!(stdout.starts_with("file,line,column,text\n"), "got:\n{stdout}");
+    assert!(stdout.contains("example.py,10,9,"), "got:\n{stdout}");
+    assert!(!stdout.contains("decorators.py"), "got:\n{stdout}");
+    assert!(!stdout.contains("patterns.py"), "got:\n{stdout}");
+}
+
+#[tokio::test]
+async fn test_sig_check_reports_calls_broken_by_keyword_only_parameter() {
+    common::require_ty();
+
+    // main() calls calculate_sum(1, 2); making `b` keyword-only breaks it.
+    let mut cmd = cargo_bin_cmd!("tyf");
+    cmd.arg("--workspace").arg(workspace_root()).args([
+        "--format",
+        "csv",
+        "sig-check",
+        "calculate_sum",
+        "--new-sig",
+        "def calculate_sum(a, *, b=1)",
+    ]);
+
+    let output = cmd.output().expect("failed to run tyf");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "command failed: {stdout}");
+    assert!(stdout.starts_with("file,line,column,status,problem\n"), "got:\n{stdout}");
+    assert!(stdout.contains("example.py,19,13,breaks,"), "got:\n{stdout}");
+    assert!(stdout.contains("positional argument"), "got:\n{stdout}");
+}
+
+#[tokio::test]
+async fn test_check_baseline_only_fails_on_new_errors() {
+    common::require_ty();
+
+    let dir = tempfile::tempdir().expect("failed to create temp dir");
+    std::fs::write(dir.path().join("pyproject.toml"), "").expect("write pyproject");
+    let module = dir.path().join("legacy.py");
+    std::fs::write(&module, "print(undefined_one)\n").expect("write module");
+    let baseline = dir.path().join(".tyf-baseline.json");
+
+    let check = |extra: &[&str]| {
+        let mut cmd = cargo_bin_cmd!("tyf");
+        cmd.arg("--workspace").arg(dir.path()).args(["--format", "csv", "check", "--baseline"]);
+        cmd.arg(&baseline).args(extra);
+        cmd.output().expect("failed to run tyf")
+    };
+
+    let output = check(&["--update-baseline"]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert!(baseline.exists());
+
+    // The recorded error moves down a line: still known.
+    std::fs::write(&module, "\nprint(undefined_one)\n").expect("write module");
+    let output = check(&[]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stdout));
+
+    std::fs::write(&module, "\nprint(undefined_one)\nprint(undefined_two)\n").expect("write");
+    let output = check(&[]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(!output.status.success(), "new error should fail the check:\n{stdout}");
+    assert!(stdout.contains("legacy.py,3,7,error,"), "got:\n{stdout}");
+    assert!(!stdout.contains("undefined_one"), "got:\n{stdout}");
+}
+
+#[tokio::test]
+async fn test_check_all_counts_per_file_and_skips_gitignored() {
+    common::require_ty();
+
+    let dir = tempfile::tempdir().expect("failed to create temp dir");
+    let root = dir.path();
+    std::fs::write(root.join("pyproject.toml"), "").expect("write pyproject");
+    std::fs::write(root.join(".gitignore"), "scratch.py\n").expect("write gitignore");
+    std::fs::write(root.join("clean.py"), "x = 1\n").expect("write clean");
+    std::fs::write(root.join("broken.py"), "print(a)\nprint(b)\n").expect("write broken");
+    std::fs::write(root.join("scratch.py"), "print(c)\n").expect("write scratch");
+
+    let mut cmd = cargo_bin_cmd!("tyf");
+    cmd.arg("--workspace").arg(root).args(["--format", "csv", "check-all", "--jobs", "2"]);
+    let output = cmd.output().expect("failed to run tyf");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(!output.status.success(), "errors should fail check-all:\n{stdout}");
+    assert_eq!(stdout, "file,errors,warnings,notes\nbroken.py,2,0,0\n");
+}
+
+#[tokio::test]
+async fn test_unused_reports_symbols_nothing_references() {
+    common::require_ty();
+
+    let dir = tempfile::tempdir().expect("failed to create temp dir");
+    let root = dir.path();
+    std::fs::write(root.join("pyproject.toml"), "").expect("write pyproject");
+    std::fs::write(
+        root.join("lib.py"),
+        "__all__ = ['exported']\n\n\
+         def used():\n    return 1\n\n\
+         def dead():\n    return 2\n\n\
+         def exported():\n    return 3\n\n\
+         class Box:\n    def __init__(self):\n        self.v = used()\n\n    def unread(self):\n        return self.v\n",
+    )
+    .expect("write lib");
+    std::fs::write(root.join("main.py"), "from lib import Box\n\nBox()\n").expect("write main");
+
+    let mut cmd = cargo_bin_cmd!("tyf");
+    cmd.arg("--workspace").arg(root).args(["--format", "csv", "unused", "--path", "lib.py"]);
+    let output = cmd.output().expect("failed to run tyf");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "command failed: {stdout}");
+    assert!(stdout.contains("lib.py,6,5,func,dead\n"), "got:\n{stdout}");
+    assert!(stdout.contains(",method,Box.unread\n"), "got:\n{stdout}");
+    for name in [",used\n", ",exported\n", "__init__", ",Box\n"] {
+        assert!(!stdout.contains(name), "{name} should not be reported:\n{stdout}");
+    }
+}
+
+#[tokio::test]
+async fn test_rdeps_lists_referencing_files() {
+    common::require_ty();
+
+    let dir = tempfile::tempdir().expect("failed to create temp dir");
+    let root = dir.path();
+    std::fs::write(root.join("pyproject.toml"), "").expect("write pyproject");
+    std::fs::write(
+        root.join("lib.py"),
+        "def helper():\n    return 1\n\nclass Box:\n    def size(self):\n        return 2\n",
+    )
+    .expect("write lib");
+    std::fs::write(root.join("a.py"), "from lib import helper\n\nhelper()\n").expect("write a");
+    std::fs::write(root.join("b.py"), "import lib\n\nlib.Box().size()\n").expect("write b");
+    std::fs::write(root.join("c.py"), "x = 1\n").expect("write c");
+
+    let mut cmd = cargo_bin_cmd!("tyf");
+    cmd.arg("--workspace").arg(root).args(["--format", "paths", "rdeps", "lib.py"]);
+    let output = cmd.output().expect("failed to run tyf");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "command failed: {stdout}");
+    let files: Vec<&str> = stdout.lines().filter_map(|line| line.rsplit('/').next()).collect();
+    assert_eq!(files, ["a.py", "b.py"], "got:\n{stdout}");
+}
+
+#[tokio::test]
+async fn test_annotate_lists_function_signatures() {
+    common::require_ty();
+
+    let mut cmd = cargo_bin_cmd!("tyf");
+    cmd.arg("--workspace").arg(workspace_root()).args([
+        "--format",
+        "csv",
+        "annotate",
+        "example.py",
+    ]);
+
+    let output = cmd.output().expect("failed to run tyf");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "command failed: {stdout}");
+    assert!(stdout.starts_with("file,line,column,kind,name,type\n"), "got:\n{stdout}");
+    assert!(stdout.contains("example.py,5,5,function,calculate_sum,\"(a, b)"), "got:\n{stdout}");
+    assert!(stdout.contains("example.py,10,9,function,add,\"(self, a, b)"), "got:\n{stdout}");
+}
+
+#[tokio::test]
+async fn test_move_previews_then_rewrites_imports() {
+    common::require_ty();
+
+    let dir = tempfile::tempdir().expect("failed to create temp dir");
+    let root = dir.path();
+    std::fs::write(root.join("pyproject.toml"), "").expect("write pyproject");
+    std::fs::create_dir_all(root.join("app/core")).expect("create package");
+    std::fs::write(root.join("app/__init__.py"), "").expect("write package");
+    std::fs::write(root.join("app/utils.py"), "def slugify(s):\n    return s\n").expect("write");
+    std::fs::write(
+        root.join("app/main.py"),
+        "import app.utils\nfrom app import utils\nfrom app.utils import slugify\n\nx = app.utils.slugify('a')\n",
+    )
+    .expect("write main");
+
+    let run = |extra: &[&str]| {
+        let mut cmd = cargo_bin_cmd!("tyf");
+        cmd.arg("--workspace").arg(root).args(["move", "app/utils.py", "app/core/helpers.py"]);
+        cmd.args(extra).output().expect("failed to run tyf")
+    };
+
+    let output = run(&[]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert!(stdout.contains("+from app.core import helpers as utils\n"), "got:\n{stdout}");
+    assert!(stdout.contains("+x = app.core.helpers.slugify('a')\n"), "got:\n{stdout}");
+    assert!(root.join("app/utils.py").exists(), "preview must not move the file");
+
+    let output = run(&["--apply"]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert!(root.join("app/core/helpers.py").exists());
+    let main = std::fs::read_to_string(root.join("app/main.py")).expect("read main");
+    assert_eq!(
+        main,
+        "import app.core.helpers\nfrom app.core import helpers as utils\nfrom app.core.helpers import slugify\n\nx = app.core.helpers.slugify('a')\n"
+    );
+}
+
+#[tokio::test]
+async fn test_imports_shows_both_directions() {
+    common::require_ty();
+
+    let dir = tempfile::tempdir().expect("failed to create temp dir");
+    let root = dir.path();
+    std::fs::write(root.join("pyproject.toml"), "").expect("write pyproject");
+    std::fs::create_dir_all(root.join("app")).expect("create package");
+    std::fs::write(root.join("app/__init__.py"), "").expect("write package");
+    std::fs::write(root.join("app/db.py"), "conn = None\n").expect("write db");
+    std::fs::write(root.join("app/models.py"), "import os\nfrom . import db\n").expect("write");
+    std::fs::write(root.join("app/views.py"), "from app.models import *\n").expect("write");
+
+    let mut cmd = cargo_bin_cmd!("tyf");
+    cmd.arg("--workspace").arg(root).args(["--format", "dot", "imports", "app.models"]);
+    let output = cmd.output().expect("failed to run tyf");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert!(stdout.contains("\"app.models\" -> \"app.db\";"), "got:\n{stdout}");
+    assert!(stdout.contains("\"app.models\" -> \"os\" [style=dashed];"), "got:\n{stdout}");
+    assert!(stdout.contains("\"app.views\" -> \"app.models\";"), "got:\n{stdout}");
+}
+
+#[tokio::test]
+async fn test_doctor_reports_every_check() {
+    common::require_ty();
+
+    let dir = tempfile::tempdir().expect("failed to create temp dir");
+    std::fs::write(dir.path().join("pyproject.toml"), "").expect("write pyproject");
+
+    let mut cmd = cargo_bin_cmd!("tyf");
+    cmd.arg("--workspace").arg(dir.path()).args(["--format", "json", "doctor"]);
+    let output = cmd.output().expect("failed to run tyf");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "{stdout}\n{}", String::from_utf8_lossy(&output.stderr));
+    let envelope: serde_json::Value = serde_json::from_str(&stdout).expect("valid JSON");
+    let checks = envelope["results"][0]["checks"].as_array().expect("checks array");
+    let names: Vec<&str> = checks.iter().filter_map(|c| c["name"].as_str()).collect();
+    assert_eq!(names, ["workspace", "language server", "hover probe", "socket", "daemon"]);
+    assert!(checks.iter().all(|c| c["status"] == "ok"), "got:\n{stdout}");
+}
+
+#[tokio::test]
+async fn test_symbols_filters_by_kind_and_directory() {
+    common::require_ty();
+
+    let dir = tempfile::tempdir().expect("failed to create temp dir");
+    let root = dir.path();
+    std::fs::write(root.join("pyproject.toml"), "").expect("write pyproject");
+    std::fs::create_dir_all(root.join("app")).expect("create package");
+    std::fs::write(root.join("app/models.py"), "class User:\n    pass\n\nuser_count = 0\n")
+        .expect("write models");
+    std::fs::write(root.join("views.py"), "class UserView:\n    pass\n").expect("write views");
+
+    let mut cmd = cargo_bin_cmd!("tyf");
+    cmd.arg("--workspace").arg(root).args(["--format", "json", "symbols", "User"]);
+    cmd.args(["--kind", "class", "--in"]).arg(root.join("app"));
+    let output = cmd.output().expect("failed to run tyf");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let envelope: serde_json::Value = serde_json::from_str(&stdout).expect("valid JSON");
+    assert_eq!(envelope["query"]["kind"], serde_json::json!(["class"]));
+    let names: Vec<&str> = envelope["results"]
+        .as_array()
+        .expect("array")
+        .iter()
+        .filter_map(|s| s["name"].as_str())
+        .collect();
+    assert_eq!(names, ["User"], "got:\n{stdout}");
+}
+
+#[tokio::test]
+async fn test_batch_answers_each_line() {
+    common::require_ty();
+
+    let dir = tempfile::tempdir().expect("failed to create temp dir");
+    std::fs::write(dir.path().join("pyproject.toml"), "").expect("write pyproject");
+    std::fs::write(dir.path().join("app.py"), "def greet():\n    pass\n\ngreet()\n")
+        .expect("write app");
+
+    let mut cmd = cargo_bin_cmd!("tyf");
+    cmd.current_dir(dir.path()).arg("--workspace").arg(dir.path()).arg("batch");
+    cmd.write_stdin("def greet\n\n{\"id\": \"b\", \"command\": \"bogus\"}\n");
+    let output = cmd.output().expect("failed to run tyf");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let lines: Vec<serde_json::Value> =
+        stdout.lines().map(|l| serde_json::from_str(l).expect("valid JSON line")).collect();
+    assert_eq!(lines.len(), 2, "got:\n{stdout}");
+    assert_eq!(lines[0]["id"], 1);
+    assert!(stdout.lines().next().is_some_and(|l| l.contains("app.py")), "got:\n{stdout}");
+    assert_eq!(lines[1]["id"], "b");
+    assert!(lines[1]["error"].is_string(), "got:\n{stdout}");
+}
+
+#[tokio::test]
+async fn test_serve_stdio_answers_until_shutdown() {
+    let frame = |body: &str| format!("Content-Length: {}\r\n\r\n{body}", body.len());
+    let ping = r#"{"jsonrpc":"2.0","id":1,"method":"ping","params":{}}"#;
+    let shutdown = r#"{"jsonrpc":"2.0","id":2,"method":"shutdown","params":{}}"#;
+
+    let mut cmd = cargo_bin_cmd!("tyf");
+    cmd.args(["serve", "--stdio"]);
+    // Requests after the shutdown go unanswered.
+    cmd.write_stdin(format!("{}{}{}", frame(ping), frame(shutdown), frame(ping)));
+    let output = cmd.output().expect("failed to run tyf");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(stdout.matches("Content-Length: ").count(), 2, "got:\n{stdout}");
+    assert!(stdout.contains(r#""status":"running""#), "got:\n{stdout}");
+    assert!(!stdout.contains("socket_path"), "got:\n{stdout}");
+    assert!(stdout.contains("Daemon shutting down"), "got:\n{stdout}");
+}
+
+#[tokio::test]
+async fn test_daemon_warm_waits_for_the_workspace() {
+    common::require_ty();
+
+    let dir = tempfile::tempdir().expect("failed to create temp dir");
+    std::fs::write(dir.path().join("pyproject.toml"), "").expect("write pyproject");
+    std::fs::write(dir.path().join("app.py"), "def greet():\n    pass\n").expect("write app");
+
+    let mut cmd = cargo_bin_cmd!("tyf");
+    cmd.args(["daemon", "warm", "--wait"]).arg(dir.path());
+    let output = cmd.output().expect("failed to run tyf");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert!(stdout.contains(" ready"), "got:\n{stdout}");
+}